@@ -0,0 +1,70 @@
+//! Purge planning throughput: collecting a channel's whole history before planning, as
+//! `/purge_old` does, against planning each page as it arrives.
+
+use chrono::{DateTime, Duration, Utc};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use poise::serenity_prelude::MessageId;
+
+#[allow(dead_code)]
+#[path = "../src/purge/plan.rs"]
+mod plan;
+
+use plan::{plan_purge, Planner, PAGE_SIZE};
+
+/// Milliseconds between the Unix epoch and Discord's, which snowflakes count from.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+/// Page `index` of a channel's history, newest first, a minute per message. The first three
+/// weeks are recent enough to bulk-delete; everything after goes one by one.
+fn page(now: DateTime<Utc>, index: usize) -> Vec<MessageId> {
+    (0..PAGE_SIZE)
+        .map(|i| {
+            let at = now - Duration::minutes((index * PAGE_SIZE + i) as i64);
+            MessageId::new(((at.timestamp_millis() - DISCORD_EPOCH_MS) as u64) << 22)
+        })
+        .collect()
+}
+
+fn purge_planning(c: &mut Criterion) {
+    let now = Utc::now();
+    let mut group = c.benchmark_group("plan_purge");
+    for messages in [1_000, 10_000, 100_000] {
+        let pages = messages / PAGE_SIZE;
+        group.throughput(Throughput::Elements(messages as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("collect", messages),
+            &pages,
+            |b, &pages| {
+                b.iter(|| {
+                    let history = (0..pages)
+                        .flat_map(|index| page(now, index))
+                        .collect::<Vec<_>>();
+                    black_box(plan_purge(&history, now))
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("streamed", messages),
+            &pages,
+            |b, &pages| {
+                b.iter(|| {
+                    let mut planner = Planner::new(now);
+                    for index in 0..pages {
+                        for id in page(now, index) {
+                            if let Some(step) = planner.push(id) {
+                                black_box(step);
+                            }
+                        }
+                    }
+                    black_box(planner.finish())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, purge_planning);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id BIGINT PRIMARY KEY,
+    events_channel_id BIGINT,
+    approval_queue BOOLEAN NOT NULL DEFAULT FALSE,
+    approval_channel_id BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    host_id BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    starts_at TIMESTAMPTZ NOT NULL,
+    duration_minutes INTEGER NOT NULL,
+    capacity INTEGER,
+    status TEXT NOT NULL,
+    message_id BIGINT,
+    queue_message_id BIGINT,
+    scheduled_event_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS events_guild_status_idx ON events (guild_id, status);
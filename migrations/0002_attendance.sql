@@ -0,0 +1,24 @@
+ALTER TABLE events
+    ADD COLUMN IF NOT EXISTS confirmed_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS waitlist_count INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS event_rsvps (
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    state TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (event_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS event_attendance (
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (event_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS streak_badges (
+    guild_id BIGINT NOT NULL,
+    streak INTEGER NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, streak)
+);
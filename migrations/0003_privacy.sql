@@ -0,0 +1,15 @@
+-- Forgotten users' rows are kept for counts but re-keyed to unique negative IDs, which can never
+-- collide with a real Discord snowflake.
+CREATE SEQUENCE IF NOT EXISTS anonymous_user_ids;
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT,
+    actor_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    target_id BIGINT,
+    details TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_guild_idx ON audit_log (guild_id, created_at);
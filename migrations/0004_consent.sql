@@ -0,0 +1,4 @@
+ALTER TABLE guild_settings
+    ADD COLUMN IF NOT EXISTS message_content_consent BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS message_content_consent_by BIGINT,
+    ADD COLUMN IF NOT EXISTS message_content_consent_at TIMESTAMPTZ;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS role_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    taken_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS role_snapshot_members (
+    snapshot_id BIGINT NOT NULL REFERENCES role_snapshots (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (snapshot_id, user_id, role_id)
+);
@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS permission_templates (
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    created_by BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS permission_template_overwrites (
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    -- 'role' or 'member'
+    kind TEXT NOT NULL,
+    target_id BIGINT NOT NULL,
+    allow BIGINT NOT NULL,
+    deny BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, name, kind, target_id),
+    FOREIGN KEY (guild_id, name) REFERENCES permission_templates (guild_id, name) ON DELETE CASCADE
+);
+
+-- Channels a template was applied to, checked for drift.
+CREATE TABLE IF NOT EXISTS permission_template_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    FOREIGN KEY (guild_id, name) REFERENCES permission_templates (guild_id, name) ON DELETE CASCADE
+);
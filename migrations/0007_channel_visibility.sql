@@ -0,0 +1,23 @@
+-- A channel that's only visible during a daily window (open_time..close_time, UTC) or around an
+-- event. Exactly one of the two is set.
+CREATE TABLE IF NOT EXISTS channel_schedules (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    open_time TIME,
+    close_time TIME,
+    event_id BIGINT REFERENCES events (id) ON DELETE CASCADE,
+    lead_minutes INT NOT NULL DEFAULT 0,
+    linger_minutes INT NOT NULL DEFAULT 0,
+    is_open BOOLEAN NOT NULL DEFAULT false
+);
+
+-- The channel's overwrites from just before it opened, put back when it closes.
+CREATE TABLE IF NOT EXISTS channel_schedule_overwrites (
+    channel_id BIGINT NOT NULL REFERENCES channel_schedules (channel_id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    target_id BIGINT NOT NULL,
+    allow BIGINT NOT NULL,
+    deny BIGINT NOT NULL,
+    PRIMARY KEY (channel_id, kind, target_id)
+);
@@ -0,0 +1,10 @@
+ALTER TABLE guild_settings
+    ADD COLUMN IF NOT EXISTS audit_channel_id BIGINT,
+    ADD COLUMN IF NOT EXISTS voice_cleanup_minutes INT NOT NULL DEFAULT 15;
+
+-- Temporary voice and stage channels the bot created for an event, removed once it's over.
+CREATE TABLE IF NOT EXISTS event_channels (
+    channel_id BIGINT PRIMARY KEY,
+    event_id BIGINT NOT NULL REFERENCES events (id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
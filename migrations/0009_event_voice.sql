@@ -0,0 +1,5 @@
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS event_voice TEXT NOT NULL DEFAULT 'off';
+
+ALTER TABLE events
+    ADD COLUMN IF NOT EXISTS voice_channel_id BIGINT,
+    ADD COLUMN IF NOT EXISTS event_role_id BIGINT;
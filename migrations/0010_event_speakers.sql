@@ -0,0 +1,6 @@
+-- Members a host has lined up to speak on their event's stage.
+CREATE TABLE IF NOT EXISTS event_speakers (
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (event_id, user_id)
+);
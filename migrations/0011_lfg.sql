@@ -0,0 +1,39 @@
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS lfg_voice BOOLEAN NOT NULL DEFAULT false;
+
+-- Members waiting for a group. Activities are stored lowercased so queues match loosely.
+CREATE TABLE IF NOT EXISTS lfg_queue (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    activity TEXT NOT NULL,
+    party_size INT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- Already sent to the back once for not confirming a group.
+    missed BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS lfg_queue_match_idx ON lfg_queue (guild_id, activity, party_size, queued_at);
+
+CREATE TABLE IF NOT EXISTS lfg_groups (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    activity TEXT NOT NULL,
+    party_size INT NOT NULL,
+    -- 'forming', 'ready' or 'expired'
+    status TEXT NOT NULL DEFAULT 'forming',
+    voice_channel_id BIGINT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS lfg_group_members (
+    group_id BIGINT NOT NULL REFERENCES lfg_groups (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    queued_at TIMESTAMPTZ NOT NULL,
+    missed BOOLEAN NOT NULL,
+    ready BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY (group_id, user_id)
+);
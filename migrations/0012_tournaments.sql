@@ -0,0 +1,38 @@
+CREATE TABLE IF NOT EXISTS tournaments (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    host_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    max_players INT NOT NULL,
+    -- 'signup', 'running', 'finished' or 'cancelled'
+    status TEXT NOT NULL DEFAULT 'signup',
+    winner_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS tournament_players (
+    tournament_id BIGINT NOT NULL REFERENCES tournaments (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tournament_id, user_id)
+);
+
+-- Every match of the bracket exists from the start; later rounds fill in as winners advance.
+-- The winner of (round, slot) plays in (round + 1, slot / 2).
+CREATE TABLE IF NOT EXISTS tournament_matches (
+    id BIGSERIAL PRIMARY KEY,
+    tournament_id BIGINT NOT NULL REFERENCES tournaments (id) ON DELETE CASCADE,
+    round INT NOT NULL,
+    slot INT NOT NULL,
+    player1_id BIGINT,
+    player2_id BIGINT,
+    score1 INT,
+    score2 INT,
+    -- Set while a reported score waits for the other player to confirm it.
+    reported_by BIGINT,
+    winner_id BIGINT,
+    thread_id BIGINT,
+    UNIQUE (tournament_id, round, slot)
+);
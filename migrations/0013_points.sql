@@ -0,0 +1,21 @@
+-- Guilds start in season 1 without a row here; each reset adds the next season.
+CREATE TABLE IF NOT EXISTS points_seasons (
+    guild_id BIGINT NOT NULL,
+    season INT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    started_by BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, season)
+);
+
+CREATE TABLE IF NOT EXISTS points_ledger (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    season INT NOT NULL,
+    user_id BIGINT NOT NULL,
+    amount INT NOT NULL,
+    reason TEXT NOT NULL,
+    awarded_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS points_ledger_season_idx ON points_ledger (guild_id, season, user_id);
@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS raffles (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    host_id BIGINT NOT NULL,
+    prize TEXT NOT NULL,
+    -- 'flat', 'attendance' or 'points'
+    weighting TEXT NOT NULL,
+    winners INT NOT NULL,
+    -- 'open', 'drawn' or 'cancelled'
+    status TEXT NOT NULL DEFAULT 'open',
+    -- With the entries' weights, enough to replay the draw.
+    seed BIGINT,
+    drawn_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS raffle_entries (
+    raffle_id BIGINT NOT NULL REFERENCES raffles (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    -- Fixed when the raffle is drawn.
+    weight BIGINT,
+    -- 1 for the first winner drawn, and so on.
+    won_place INT,
+    entered_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (raffle_id, user_id)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS tags (
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    content TEXT NOT NULL,
+    as_embed BOOLEAN NOT NULL DEFAULT false,
+    created_by BIGINT NOT NULL,
+    uses BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, name)
+);
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS macros (
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    created_by BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, name)
+);
+
+-- Which of the columns a step uses depends on its kind.
+CREATE TABLE IF NOT EXISTS macro_steps (
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    position INT NOT NULL,
+    kind TEXT NOT NULL,
+    channel_id BIGINT,
+    role_id BIGINT,
+    text TEXT,
+    amount INT,
+    PRIMARY KEY (guild_id, name, position),
+    FOREIGN KEY (guild_id, name) REFERENCES macros (guild_id, name) ON DELETE CASCADE
+);
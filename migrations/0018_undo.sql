@@ -0,0 +1,6 @@
+-- How to reverse an action, one step per line, for actions `/undo` can take back.
+ALTER TABLE audit_log
+    ADD COLUMN IF NOT EXISTS undo TEXT,
+    ADD COLUMN IF NOT EXISTS undone_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS audit_log_actor_idx ON audit_log (guild_id, actor_id, created_at);
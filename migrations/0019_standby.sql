@@ -0,0 +1,7 @@
+-- Which running instance is active. Only one row ever exists; whoever holds it handles Discord
+-- events and scheduled work, and any other instance waits on standby for the lease to lapse.
+CREATE TABLE IF NOT EXISTS instance_lease (
+    id INT PRIMARY KEY CHECK (id = 1),
+    holder TEXT NOT NULL,
+    renewed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,38 @@
+-- Every message the bot owns and keeps up to date, whatever feature it belongs to.
+CREATE TABLE IF NOT EXISTS bot_posts (
+    message_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    -- The event, raffle, tournament or group the post is about.
+    subject_id BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bot_posts_guild_idx ON bot_posts (guild_id);
+
+-- Posts made before the registry existed.
+INSERT INTO bot_posts (message_id, guild_id, channel_id, kind, subject_id)
+SELECT message_id, guild_id, channel_id, 'event', id FROM events
+WHERE message_id IS NOT NULL AND status = 'published'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO bot_posts (message_id, guild_id, channel_id, kind, subject_id)
+SELECT e.queue_message_id, e.guild_id, s.approval_channel_id, 'event_approval', e.id
+FROM events e JOIN guild_settings s ON s.guild_id = e.guild_id
+WHERE e.queue_message_id IS NOT NULL AND e.status = 'pending' AND s.approval_channel_id IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+INSERT INTO bot_posts (message_id, guild_id, channel_id, kind, subject_id)
+SELECT message_id, guild_id, channel_id, 'raffle', id FROM raffles
+WHERE message_id IS NOT NULL AND status = 'open'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO bot_posts (message_id, guild_id, channel_id, kind, subject_id)
+SELECT message_id, guild_id, channel_id, 'tournament', id FROM tournaments
+WHERE message_id IS NOT NULL AND status IN ('signup', 'running')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO bot_posts (message_id, guild_id, channel_id, kind, subject_id)
+SELECT message_id, guild_id, channel_id, 'lfg_group', id FROM lfg_groups
+WHERE message_id IS NOT NULL AND status = 'forming'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,6 @@
+-- Guilds the bot was removed from. Their data is kept for a grace period in case the bot is added
+-- back, and deleted once it's over.
+CREATE TABLE IF NOT EXISTS detached_guilds (
+    guild_id BIGINT PRIMARY KEY,
+    detached_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
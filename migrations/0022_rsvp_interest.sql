@@ -0,0 +1,3 @@
+-- RSVPs can now be `interested` (no place taken) or `rejected` by the host, besides confirmed and
+-- waitlisted.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS interested_count INTEGER NOT NULL DEFAULT 0;
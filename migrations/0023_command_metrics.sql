@@ -0,0 +1,12 @@
+-- How each slash command is used, per guild. DMs are counted under guild 0.
+CREATE TABLE IF NOT EXISTS command_metrics (
+    guild_id BIGINT NOT NULL,
+    command TEXT NOT NULL,
+    invocations BIGINT NOT NULL DEFAULT 0,
+    failures BIGINT NOT NULL DEFAULT 0,
+    -- Time spent handling the command, summed for averages.
+    total_ms BIGINT NOT NULL DEFAULT 0,
+    max_ms BIGINT NOT NULL DEFAULT 0,
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, command)
+);
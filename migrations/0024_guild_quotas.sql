@@ -0,0 +1,6 @@
+-- Limits on how much of the shared bot one guild can use. NULL means the default.
+CREATE TABLE IF NOT EXISTS guild_quotas (
+    guild_id BIGINT PRIMARY KEY,
+    max_purge_jobs INT,
+    max_active_events INT
+);
@@ -0,0 +1,23 @@
+-- One row per batch of DMs, like an event's reminders, with how delivery went.
+CREATE TABLE IF NOT EXISTS notification_runs (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    subject_id BIGINT NOT NULL,
+    delivered INT NOT NULL,
+    dms_closed INT NOT NULL,
+    failed INT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS notification_deliveries (
+    run_id BIGINT NOT NULL REFERENCES notification_runs (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    outcome TEXT NOT NULL,
+    attempts INT NOT NULL,
+    error TEXT,
+    PRIMARY KEY (run_id, user_id)
+);
+
+-- When attendees were reminded, so each event is only reminded about once.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS reminded_at TIMESTAMPTZ;
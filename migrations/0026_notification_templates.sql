@@ -0,0 +1,7 @@
+-- Guilds' own wording for notification DMs. Kinds without a row use the built-in text.
+CREATE TABLE IF NOT EXISTS notification_templates (
+    guild_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    template TEXT NOT NULL,
+    PRIMARY KEY (guild_id, kind)
+);
@@ -0,0 +1,22 @@
+-- Settings members choose for themselves, the same in every guild.
+CREATE TABLE IF NOT EXISTS user_preferences (
+    user_id BIGINT PRIMARY KEY,
+    -- Whether notifications are gathered into one DM a day instead of sent as they happen.
+    digest BOOLEAN NOT NULL DEFAULT false,
+    -- The hour of the day, in UTC, the digest is sent at.
+    digest_hour SMALLINT NOT NULL DEFAULT 9,
+    last_digest_at TIMESTAMPTZ
+);
+
+-- Notifications held back for members' next digest.
+CREATE TABLE IF NOT EXISTS digest_queue (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS digest_queue_user ON digest_queue (user_id);
+
+ALTER TABLE notification_runs ADD COLUMN IF NOT EXISTS digested INT NOT NULL DEFAULT 0;
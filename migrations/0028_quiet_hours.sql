@@ -0,0 +1,7 @@
+-- Hours of the day, in UTC, notifications that can wait are held back during. Quiet hours run
+-- from `quiet_start` up to `quiet_end`, wrapping past midnight if `quiet_end` is earlier.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS quiet_start SMALLINT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS quiet_end SMALLINT;
+ALTER TABLE user_preferences ADD COLUMN IF NOT EXISTS quiet_start SMALLINT;
+ALTER TABLE user_preferences ADD COLUMN IF NOT EXISTS quiet_end SMALLINT;
+ALTER TABLE notification_runs ADD COLUMN IF NOT EXISTS held INT NOT NULL DEFAULT 0;
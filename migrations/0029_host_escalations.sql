@@ -0,0 +1,7 @@
+-- Hosts of full events are pinged about members left waiting on them, backing off each time.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS host_reviewed_at TIMESTAMPTZ;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS escalations INT NOT NULL DEFAULT 0;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS escalated_at TIMESTAMPTZ;
+
+-- 0 turns escalation off.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS escalation_minutes INT NOT NULL DEFAULT 240;
@@ -0,0 +1,7 @@
+-- A discussion thread on each event's post, summarised for absentees once it archives.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS thread_id BIGINT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS thread_summarized_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS events_thread_id ON events (thread_id) WHERE thread_id IS NOT NULL;
+
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS event_threads BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS thread_summaries BOOLEAN NOT NULL DEFAULT false;
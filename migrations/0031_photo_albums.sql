@@ -0,0 +1,7 @@
+-- A time-limited thread for photos after each event, compiled into a pinned album once it closes.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS album_thread_id BIGINT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS album_closes_at TIMESTAMPTZ;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS album_compiled_at TIMESTAMPTZ;
+
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS photo_albums BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS album_hours INT NOT NULL DEFAULT 24;
@@ -0,0 +1,3 @@
+-- A generated banner on each event's post and scheduled event.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS event_banners BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS banner BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,5 @@
+-- A code shown at in-person events. Once an event has one, only members who check in with it
+-- are recorded as having attended.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS checkin_code TEXT;
+CREATE INDEX IF NOT EXISTS events_checkin_code ON events (guild_id, checkin_code)
+    WHERE checkin_code IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Where an event happens: a venue and address for events in person, or a voice or stage channel
+-- for ones held on the server.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS venue TEXT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS address TEXT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS location_channel_id BIGINT;
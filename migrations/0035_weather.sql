@@ -0,0 +1,15 @@
+-- Forecasts for outdoor events, fetched the day before from the API in `WEATHER_API_URL`.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS outdoor BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS forecast TEXT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS forecast_checked_at TIMESTAMPTZ;
+
+-- What the API said for a place and day, shared by every event there so lookups stay within the
+-- API's quota. A missing forecast is cached too, so a place the API doesn't know isn't retried
+-- every tick.
+CREATE TABLE IF NOT EXISTS weather_forecasts (
+    location TEXT NOT NULL,
+    day DATE NOT NULL,
+    forecast TEXT,
+    fetched_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (location, day)
+);
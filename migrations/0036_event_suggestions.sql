@@ -0,0 +1,9 @@
+-- Topic tags on events, used to suggest new events to members who went to similar ones.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS topics TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN IF NOT EXISTS suggested_at TIMESTAMPTZ;
+
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS event_suggestions BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS suggestions_channel_id BIGINT;
+
+-- Suggestions are only ever DMed to members who asked for them.
+ALTER TABLE user_preferences ADD COLUMN IF NOT EXISTS suggestions BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,22 @@
+-- Topics become tags, which members can also browse by and follow. `events.tags` keeps a copy
+-- for rendering posts, and `event_tags` is what lookups by tag use. Both are only written
+-- together, by `events::tags::replace`.
+ALTER TABLE events RENAME COLUMN topics TO tags;
+
+CREATE TABLE IF NOT EXISTS event_tags (
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (event_id, tag)
+);
+CREATE INDEX IF NOT EXISTS event_tags_tag_idx ON event_tags (tag);
+INSERT INTO event_tags (event_id, tag)
+    SELECT id, unnest(tags) FROM events
+    ON CONFLICT DO NOTHING;
+
+-- Members who want to hear about every new event with a tag.
+CREATE TABLE IF NOT EXISTS tag_subscriptions (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (guild_id, user_id, tag)
+);
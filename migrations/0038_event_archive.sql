@@ -0,0 +1,14 @@
+-- Past events are moved out of the events channel into a read-only archive, with how they went.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS archive_channel_id BIGINT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS archive_hours INT NOT NULL DEFAULT 24;
+
+ALTER TABLE events ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS archive_message_id BIGINT;
+
+-- How members who went rated an event, from 1 to 5.
+CREATE TABLE IF NOT EXISTS event_feedback (
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    score SMALLINT NOT NULL CHECK (score BETWEEN 1 AND 5),
+    PRIMARY KEY (event_id, user_id)
+);
@@ -0,0 +1,4 @@
+-- A bring-list for an event: things or jobs the host needs someone to take on. `item_claims`
+-- runs alongside `items`, holding who took each one, or 0 while nobody has.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS items TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN IF NOT EXISTS item_claims BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+-- Events that only go ahead if enough people are going by a deadline, `confirm_hours` before
+-- they start. `retry_days` has the bot draft the event again that many days later when it falls
+-- through. `threshold_checked_at` is set once the deadline has been checked, so it's only
+-- checked once per start time.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS min_attendees INT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS confirm_hours INT NOT NULL DEFAULT 24;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS retry_days INT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS threshold_checked_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS events_threshold_idx ON events (starts_at)
+    WHERE min_attendees IS NOT NULL AND threshold_checked_at IS NULL;
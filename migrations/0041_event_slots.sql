@@ -0,0 +1,24 @@
+-- Time slots or shifts within an event, each with its own sign-ups. Offsets are from the event's
+-- start, so moving the event moves its slots with it.
+CREATE TABLE IF NOT EXISTS event_slots (
+    id BIGSERIAL PRIMARY KEY,
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    offset_minutes INT NOT NULL,
+    duration_minutes INT NOT NULL,
+    label TEXT,
+    capacity INT
+);
+CREATE INDEX IF NOT EXISTS event_slots_event_idx ON event_slots (event_id, offset_minutes);
+
+CREATE TABLE IF NOT EXISTS event_slot_signups (
+    slot_id BIGINT NOT NULL REFERENCES event_slots (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (slot_id, user_id)
+);
+
+-- Copies of the schedule for the event's post to render from, kept in step by `slots::sync`.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS slot_ids BIGINT[] NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN IF NOT EXISTS slot_offsets INT[] NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN IF NOT EXISTS slot_minutes INT[] NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN IF NOT EXISTS slot_lines TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Interest that came from the "Interested" button on the event's Discord scheduled event, rather
+-- than from the bot's own buttons. Only that is taken back when it's removed on Discord.
+ALTER TABLE event_rsvps ADD COLUMN IF NOT EXISTS from_discord BOOLEAN NOT NULL DEFAULT false;
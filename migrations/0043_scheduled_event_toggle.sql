@@ -0,0 +1,6 @@
+-- Whether events are mirrored into Discord's scheduled events. Some guilds would rather the
+-- bot's RSVPs were the only place to sign up. The guild setting is stored inverted, so guilds
+-- without a settings row keep getting them.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS scheduled_events_off BOOLEAN NOT NULL DEFAULT false;
+-- Overrides the guild setting for one event when set.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS discord_event BOOLEAN;
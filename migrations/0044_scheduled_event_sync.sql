@@ -0,0 +1,9 @@
+-- Two-way sync between an event and its Discord scheduled event. `synced_fingerprint` is the
+-- title, description and times both sides last agreed on. Comparing each side against it shows
+-- which one changed since. `edited_at` is when the event was last edited on the bot.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS synced_fingerprint TEXT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS edited_at TIMESTAMPTZ;
+-- Set while a conflict waits for the host to pick a side, so they're only asked once.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS sync_conflict BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS sync_policy TEXT NOT NULL DEFAULT 'bot_wins';
@@ -0,0 +1,5 @@
+-- Past events brought in from posts made before the bot was added. The post they were read from
+-- is kept so scanning the channel again doesn't import them twice.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS imported_message_id BIGINT;
+CREATE UNIQUE INDEX IF NOT EXISTS events_imported_message
+    ON events (guild_id, imported_message_id) WHERE imported_message_id IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Other event bots seen posting in a guild's event channels, so admins are only warned once.
+CREATE TABLE IF NOT EXISTS other_event_bots (
+    guild_id BIGINT NOT NULL,
+    bot_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    noticed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (guild_id, bot_id)
+);
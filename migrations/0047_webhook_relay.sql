@@ -0,0 +1,11 @@
+-- Announcements can go out through a webhook, under a name and avatar of the guild's choosing.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS relay_name TEXT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS relay_avatar_url TEXT;
+
+-- The webhook the bot made in each channel it relays to. Made again if someone deletes it.
+CREATE TABLE IF NOT EXISTS relay_webhooks (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    webhook_id BIGINT NOT NULL,
+    token TEXT NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Events can also get a post in a forum channel, or get one there instead of in the events
+-- channel.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS forum_channel_id BIGINT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS forum_only BOOLEAN NOT NULL DEFAULT false;
+
+-- The forum post for the event, and its name, tags and state as last written, so it's only
+-- edited when one of them changes. Discord limits how often threads can be renamed.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS forum_thread_id BIGINT;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS forum_fingerprint TEXT;
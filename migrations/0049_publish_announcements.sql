@@ -0,0 +1,2 @@
+-- Whether posts landing in an announcement channel are published to servers following it.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS publish_announcements BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- Whether posts spell out in words what they'd otherwise only show with emoji and embed styling.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS plain_text BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- Emoji guilds picked for the bot's buttons and posts, as `slot=emoji` entries.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS emoji TEXT[] NOT NULL DEFAULT '{}';
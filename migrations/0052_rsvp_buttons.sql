@@ -0,0 +1,4 @@
+-- Hosts' own labels and colours for an event's RSVP buttons, one place per button and empty for
+-- the usual one. Events that were never customized have none.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS rsvp_labels TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN IF NOT EXISTS rsvp_styles TEXT[] NOT NULL DEFAULT '{}';
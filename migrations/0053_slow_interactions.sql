@@ -0,0 +1,3 @@
+-- Component presses are counted alongside commands, as `button:<prefix>`. Slow ones are those
+-- that had to be deferred because they weren't answered within Discord's three seconds.
+ALTER TABLE command_metrics ADD COLUMN IF NOT EXISTS slow BIGINT NOT NULL DEFAULT 0;
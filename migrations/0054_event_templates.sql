@@ -0,0 +1,14 @@
+-- Events saved from the creation wizard to start the next one from. Names are per guild, and
+-- saving under one that's taken replaces it.
+CREATE TABLE IF NOT EXISTS event_templates (
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    created_by BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    duration_minutes INT NOT NULL,
+    capacity INT,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, name)
+);
@@ -0,0 +1,3 @@
+-- How the bot sounds in a guild's posts: which set of copy it uses, and how many emoji go in it.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS tone TEXT NOT NULL DEFAULT 'formal';
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS emoji_density TEXT NOT NULL DEFAULT 'normal';
@@ -0,0 +1,14 @@
+-- Announcements posted once at a set time. `utc_offset_minutes` is the timezone the time was
+-- given in, so it's shown and edited in the same one. Rows go once they're posted or cancelled.
+CREATE TABLE IF NOT EXISTS scheduled_announcements (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    send_at TIMESTAMPTZ NOT NULL,
+    utc_offset_minutes INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_announcements_send_at ON scheduled_announcements (send_at);
@@ -0,0 +1,24 @@
+-- Rules for archiving threads under a channel. A thread is archived once it's older than
+-- `max_age_hours` or has been quiet for `idle_hours`, whichever comes first; with a `prefix`,
+-- only threads whose names start with it are looked at.
+CREATE TABLE IF NOT EXISTS thread_janitor_rules (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    max_age_hours INT,
+    idle_hours INT,
+    prefix TEXT,
+    lock BOOLEAN NOT NULL DEFAULT false,
+    report_channel_id BIGINT,
+    last_report_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Threads archived since their channel's last weekly report.
+CREATE TABLE IF NOT EXISTS thread_janitor_archived (
+    thread_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL REFERENCES thread_janitor_rules (channel_id) ON DELETE CASCADE,
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,23 @@
+-- Channels where questions are checked against recent ones. A new question whose words overlap an
+-- earlier one's by at least `similarity_percent` gets a reply linking to it.
+CREATE TABLE IF NOT EXISTS help_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    similarity_percent INT NOT NULL DEFAULT 60,
+    window_days INT NOT NULL DEFAULT 14,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Questions asked in a help channel within its window. Only the words compared are kept, not the
+-- message itself. `posted_in` is the thread for a forum post, otherwise the help channel.
+CREATE TABLE IF NOT EXISTS help_questions (
+    message_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL REFERENCES help_channels (channel_id) ON DELETE CASCADE,
+    posted_in BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    words TEXT[] NOT NULL,
+    asked_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS help_questions_channel_asked ON help_questions (channel_id, asked_at);
@@ -0,0 +1,21 @@
+-- Phrases that answer themselves with a tag when written in an FAQ channel. `fires` counts the
+-- answers given, and `last_fired_at` keeps a trigger quiet for `cooldown_minutes` after one.
+CREATE TABLE IF NOT EXISTS faq_triggers (
+    guild_id BIGINT NOT NULL,
+    phrase TEXT NOT NULL,
+    tag_name TEXT NOT NULL,
+    cooldown_minutes INT NOT NULL DEFAULT 10,
+    fires BIGINT NOT NULL DEFAULT 0,
+    last_fired_at TIMESTAMPTZ,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, phrase),
+    FOREIGN KEY (guild_id, tag_name) REFERENCES tags (guild_id, name) ON DELETE CASCADE
+);
+
+-- Channels where FAQ triggers are listened for.
+CREATE TABLE IF NOT EXISTS faq_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
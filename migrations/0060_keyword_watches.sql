@@ -0,0 +1,20 @@
+-- Terms a moderator is alerted about when they're written in the guild. `pattern` is lowercase,
+-- with `*` standing for anything. Alerts go to `channel_id`, or to the moderator's DMs without one.
+CREATE TABLE IF NOT EXISTS keyword_watches (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    pattern TEXT NOT NULL,
+    channel_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (guild_id, user_id, pattern)
+);
+
+-- Hours of the day, in UTC, when a moderator's watches stay quiet.
+CREATE TABLE IF NOT EXISTS keyword_watch_mutes (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    mute_start SMALLINT NOT NULL,
+    mute_end SMALLINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,27 @@
+-- Channels whose links are checked, and what happens to a message with a bad one: `delete` or
+-- `flag`.
+CREATE TABLE IF NOT EXISTS link_channels (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    action TEXT NOT NULL DEFAULT 'delete',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- A guild's own verdicts on domains, which cover their subdomains too. Allowed domains are never
+-- looked up.
+CREATE TABLE IF NOT EXISTS link_rules (
+    guild_id BIGINT NOT NULL,
+    domain TEXT NOT NULL,
+    allowed BOOLEAN NOT NULL,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, domain)
+);
+
+-- What the reputation API said about a domain, shared by every guild. `malicious` is null when it
+-- couldn't say.
+CREATE TABLE IF NOT EXISTS link_reputation (
+    domain TEXT PRIMARY KEY,
+    malicious BOOLEAN,
+    checked_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,17 @@
+-- Whether invites to other servers are taken down, and what the poster is told when they are.
+-- `invite_notice` is a template; without one, the default copy is used.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS invite_policy BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS invite_notice TEXT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS invite_appeal TEXT;
+
+-- Where invites may still be posted: in a `channel`, by members with a `role`, or to a `partner`
+-- guild.
+CREATE TABLE IF NOT EXISTS invite_exemptions (
+    guild_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    target_id BIGINT NOT NULL,
+    name TEXT,
+    added_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, kind, target_id)
+);
@@ -0,0 +1,35 @@
+-- Raid detection: `raid_joins` accounts younger than `raid_account_days` joining within
+-- `raid_window_minutes` make an incident, announced in `raid_channel_id` or the audit channel.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS raid_detection BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS raid_joins INT NOT NULL DEFAULT 10;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS raid_window_minutes INT NOT NULL DEFAULT 5;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS raid_account_days INT NOT NULL DEFAULT 7;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS raid_channel_id BIGINT;
+
+-- New accounts that joined recently, kept for as long as the window.
+CREATE TABLE IF NOT EXISTS raid_joins (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    joined_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+-- Bursts of new accounts. An `open` incident has raised the verification level from
+-- `previous_verification`; a `locked` one also turns new accounts away, until it's `dismissed`.
+CREATE TABLE IF NOT EXISTS raid_incidents (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'open',
+    joins INT NOT NULL,
+    turned_away INT NOT NULL DEFAULT 0,
+    previous_verification SMALLINT,
+    channel_id BIGINT,
+    message_id BIGINT,
+    started_at TIMESTAMPTZ NOT NULL,
+    resolved_by BIGINT,
+    resolved_at TIMESTAMPTZ
+);
+
+-- Only one incident at a time is still going.
+CREATE UNIQUE INDEX IF NOT EXISTS raid_incidents_one_active
+    ON raid_incidents (guild_id) WHERE status <> 'dismissed';
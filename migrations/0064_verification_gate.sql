@@ -0,0 +1,26 @@
+-- Verification gate: members who join get `verification_role_id` once they've passed the
+-- challenge and been in the server for `verification_wait_minutes`. Those who haven't passed
+-- after `verification_timeout_minutes` are kicked; 0 never kicks.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS verification_gate BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS verification_role_id BIGINT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS verification_wait_minutes INT NOT NULL DEFAULT 0;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS verification_timeout_minutes INT NOT NULL DEFAULT 0;
+
+-- Questions asked in the challenge's form, in order. One with an `answer` must be answered with
+-- it, ignoring case; one without takes anything, for moderators to read in the audit channel.
+CREATE TABLE IF NOT EXISTS verification_questions (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    prompt TEXT NOT NULL,
+    answer TEXT,
+    created_by BIGINT NOT NULL
+);
+
+-- Members who joined while the gate was up and don't have the role yet.
+CREATE TABLE IF NOT EXISTS verification_pending (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    joined_at TIMESTAMPTZ NOT NULL,
+    passed_at TIMESTAMPTZ,
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,21 @@
+-- Timeout appeals: members timed out while `appeals` is on are offered a way to appeal, which
+-- lands in `appeal_channel_id` or the audit channel.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS appeals BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS appeal_channel_id BIGINT;
+
+-- One per timeout. An `offered` appeal is waiting on the member, a `pending` one on the
+-- moderators, until it's `approved` (lifting the timeout) or `denied`.
+CREATE TABLE IF NOT EXISTS timeout_appeals (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    until TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL DEFAULT 'offered',
+    reason TEXT,
+    channel_id BIGINT,
+    message_id BIGINT,
+    decided_by BIGINT,
+    decided_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (guild_id, user_id, until)
+);
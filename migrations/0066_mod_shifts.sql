@@ -0,0 +1,11 @@
+-- Moderator coverage: a rota is an event with `coverage` set, whose slots are the shifts mods
+-- claim. Shift changes and unclaimed shifts are announced in `shift_channel_id` (or the audit
+-- channel), and whoever is on shift holds `duty_role_id`.
+ALTER TABLE events ADD COLUMN IF NOT EXISTS coverage BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS shift_channel_id BIGINT;
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS duty_role_id BIGINT;
+
+-- When each shift was flagged for having nobody, started and ended, so each happens once.
+ALTER TABLE event_slots ADD COLUMN IF NOT EXISTS gap_flagged_at TIMESTAMPTZ;
+ALTER TABLE event_slots ADD COLUMN IF NOT EXISTS started_at TIMESTAMPTZ;
+ALTER TABLE event_slots ADD COLUMN IF NOT EXISTS ended_at TIMESTAMPTZ;
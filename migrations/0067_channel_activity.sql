@@ -0,0 +1,10 @@
+-- Messages posted per channel per hour, counted with the guild's consent to message features.
+-- Only how many, never what or by whom. Kept for `stats::RETENTION_DAYS`.
+CREATE TABLE IF NOT EXISTS channel_activity (
+    channel_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    messages INT NOT NULL DEFAULT 0,
+    PRIMARY KEY (channel_id, hour)
+);
+CREATE INDEX IF NOT EXISTS channel_activity_hour_idx ON channel_activity (hour);
@@ -0,0 +1,17 @@
+-- Milestone announcements: server member counts, the server's birthday and members' join
+-- anniversaries are posted in `milestone_channel_id` once a day. Members can opt out of their
+-- own with `/preferences milestones`.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS milestone_channel_id BIGINT;
+-- The last day milestones were checked for, so each day is only checked once.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS milestones_checked_on DATE;
+ALTER TABLE user_preferences ADD COLUMN IF NOT EXISTS milestones BOOLEAN NOT NULL DEFAULT true;
+
+-- Every milestone that has been announced, so none is announced twice. `user_id` is set for a
+-- member's own.
+CREATE TABLE IF NOT EXISTS milestones (
+    guild_id BIGINT NOT NULL,
+    milestone TEXT NOT NULL,
+    user_id BIGINT,
+    announced_on DATE NOT NULL,
+    PRIMARY KEY (guild_id, milestone)
+);
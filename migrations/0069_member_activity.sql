@@ -0,0 +1,9 @@
+-- When each member last posted or reacted, counted alongside `channel_activity` with the guild's
+-- consent, for `/report inactive`. Only the time is kept, not what was said.
+CREATE TABLE IF NOT EXISTS member_activity (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    last_message_at TIMESTAMPTZ,
+    last_reaction_at TIMESTAMPTZ,
+    PRIMARY KEY (guild_id, user_id)
+);
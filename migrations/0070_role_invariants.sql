@@ -0,0 +1,16 @@
+-- Role invariants: rules about who holds which roles, like "everyone with one role also has
+-- another" (`requires`) or "nobody holds both" (`excludes`). Each is checked every few hours and
+-- drift is reported in the audit channel with buttons to fix it.
+CREATE TABLE IF NOT EXISTS role_invariants (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    rule TEXT NOT NULL,
+    role_id BIGINT NOT NULL,
+    other_role_id BIGINT NOT NULL,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    checked_at TIMESTAMPTZ,
+    -- Who broke it at the last check, so a report is only posted when that changes.
+    drifted BIGINT[] NOT NULL DEFAULT '{}',
+    UNIQUE (guild_id, rule, role_id, other_role_id)
+);
@@ -0,0 +1,27 @@
+-- Role rotations: a role handed from one member of a pool to the next on a schedule, like a
+-- weekly event MC. Whoever has had the fewest turns goes next.
+CREATE TABLE IF NOT EXISTS role_rotations (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    -- Where handovers are announced, if anywhere.
+    channel_id BIGINT,
+    every_days INT NOT NULL,
+    next_at TIMESTAMPTZ NOT NULL,
+    holder_id BIGINT,
+    created_by BIGINT NOT NULL,
+    UNIQUE (guild_id, role_id)
+);
+
+-- `passes` counts the turns a member went without, for being away when theirs came up or for
+-- joining after everyone else had some, so they aren't owed a run of turns afterwards.
+CREATE TABLE IF NOT EXISTS role_rotation_members (
+    rotation_id BIGINT NOT NULL REFERENCES role_rotations (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    turns INT NOT NULL DEFAULT 0,
+    passes INT NOT NULL DEFAULT 0,
+    last_turn_at TIMESTAMPTZ,
+    away_until TIMESTAMPTZ,
+    added_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (rotation_id, user_id)
+);
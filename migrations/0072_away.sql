@@ -0,0 +1,5 @@
+-- Away status: members set when they're back with `/away set`. Until then role rotations skip
+-- them, hosts see it next to their RSVPs, and event reminders aren't sent to them.
+ALTER TABLE user_preferences ADD COLUMN IF NOT EXISTS away_until TIMESTAMPTZ;
+-- Reminders left unsent in a run because the member was away.
+ALTER TABLE notification_runs ADD COLUMN IF NOT EXISTS away INT NOT NULL DEFAULT 0;
@@ -0,0 +1,14 @@
+-- Retention policies: messages in a channel older than `max_age_days` are deleted automatically.
+-- Each run's summary goes to `bot_spam_channel_id`, or the audit channel without one.
+ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS bot_spam_channel_id BIGINT;
+
+CREATE TABLE IF NOT EXISTS retention_policies (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    max_age_days INT NOT NULL,
+    paused BOOLEAN NOT NULL DEFAULT false,
+    created_by BIGINT NOT NULL,
+    -- Claimed at the start of each run, so a slow run can't be picked up twice.
+    last_run_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS retention_policies_guild ON retention_policies (guild_id);
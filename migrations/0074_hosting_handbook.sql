@@ -0,0 +1,8 @@
+-- Hosts who have been shown the hosting handbook after their first event, so it's only shown
+-- once. The handbook's pages themselves are tags named `hosting-1`, `hosting-2` and so on.
+CREATE TABLE IF NOT EXISTS hosting_handbook_shown (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    shown_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,309 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{config::Config, util::send_dm, Data, SlimeError};
+
+/// How many commands have to fail within [`SPIKE_WINDOW_MINUTES`] to count as a spike.
+const SPIKE_FAILURES: usize = 10;
+const SPIKE_WINDOW_MINUTES: i64 = 5;
+
+/// How many runs in a row a scheduled job has to fail before anyone is told.
+const JOB_FAILURES: u32 = 3;
+
+/// How many scheduler ticks in a row the pool has to be fully in use for.
+const SATURATED_TICKS: u32 = 3;
+
+/// How long the same alert stays quiet after being sent, so one outage isn't fifty DMs.
+const DEDUPE_MINUTES: i64 = 60;
+
+/// Something the bot's owners should hear about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alert {
+    /// Commands are failing far more than usual.
+    ErrorSpike { failures: usize },
+    /// A scheduled job has failed several runs in a row.
+    JobFailing {
+        job: &'static str,
+        failures: u32,
+        error: String,
+    },
+    /// Every database connection is busy, so commands and jobs are left waiting.
+    PoolSaturated { connections: u32 },
+    /// Something gave up waiting for a database connection.
+    PoolTimedOut,
+}
+
+impl Alert {
+    /// Alerts with the same key are the same problem, and are deduplicated together.
+    fn key(&self) -> String {
+        match self {
+            Alert::ErrorSpike { .. } => "error_spike".to_string(),
+            Alert::JobFailing { job, .. } => format!("job:{job}"),
+            Alert::PoolSaturated { .. } | Alert::PoolTimedOut => "pool".to_string(),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Alert::ErrorSpike { failures } => {
+                format!("{failures} commands failed in the last {SPIKE_WINDOW_MINUTES} minutes.")
+            }
+            Alert::JobFailing {
+                job,
+                failures,
+                error,
+            } => format!("{job} has failed {failures} times in a row, most recently with: {error}"),
+            Alert::PoolSaturated { connections } => format!(
+                "All {connections} database connections are in use, so work is queueing up."
+            ),
+            Alert::PoolTimedOut => "Timed out waiting for a database connection, so every \
+                connection is busy or the database is unreachable."
+                .to_string(),
+        }
+    }
+}
+
+/// Whether `error` came from waiting too long for a database connection.
+fn is_pool_timeout(error: &SlimeError) -> bool {
+    matches!(error, SlimeError::DatabaseError(sqlx::Error::PoolTimedOut))
+}
+
+/// Whether every connection the pool may open is open and busy.
+fn pool_saturated(pool: &PgPool) -> bool {
+    pool.size() >= pool.options().get_max_connections() && pool.num_idle() == 0
+}
+
+#[derive(Default)]
+struct State {
+    /// When recent commands failed, oldest first.
+    failures: VecDeque<DateTime<Utc>>,
+    /// Runs in a row each job has failed.
+    job_failures: HashMap<&'static str, u32>,
+    saturated_ticks: u32,
+    /// When each alert was last sent, and how many times it has come up since.
+    sent: HashMap<String, (DateTime<Utc>, u32)>,
+}
+
+/// Tracks failures as they happen and decides when they add up to an alert.
+#[derive(Default)]
+pub struct Alerts {
+    state: Mutex<State>,
+}
+
+impl Alerts {
+    /// Counts a failed command, returning an alert if failures are spiking.
+    pub fn command_failed(&self, error: &SlimeError, now: DateTime<Utc>) -> Option<Alert> {
+        let mut state = self.state.lock().unwrap();
+        let window_start = now - Duration::minutes(SPIKE_WINDOW_MINUTES);
+        while state.failures.front().is_some_and(|at| *at <= window_start) {
+            state.failures.pop_front();
+        }
+        state.failures.push_back(now);
+
+        if is_pool_timeout(error) {
+            return Some(Alert::PoolTimedOut);
+        }
+        (state.failures.len() >= SPIKE_FAILURES).then(|| Alert::ErrorSpike {
+            failures: state.failures.len(),
+        })
+    }
+
+    /// Counts a scheduled job's run, returning an alert once it has failed too often in a row.
+    pub fn job_finished<T>(
+        &self,
+        job: &'static str,
+        result: &Result<T, SlimeError>,
+    ) -> Option<Alert> {
+        let mut state = self.state.lock().unwrap();
+        let Err(e) = result else {
+            state.job_failures.remove(job);
+            return None;
+        };
+        let failures = state.job_failures.entry(job).or_default();
+        *failures += 1;
+
+        if is_pool_timeout(e) {
+            return Some(Alert::PoolTimedOut);
+        }
+        (*failures >= JOB_FAILURES).then(|| Alert::JobFailing {
+            job,
+            failures: *failures,
+            error: e.to_string(),
+        })
+    }
+
+    /// Notes how busy the pool was on a scheduler tick, returning an alert once it has stayed
+    /// saturated.
+    pub fn pool_checked(&self, pool: &PgPool) -> Option<Alert> {
+        let mut state = self.state.lock().unwrap();
+        if !pool_saturated(pool) {
+            state.saturated_ticks = 0;
+            return None;
+        }
+        state.saturated_ticks += 1;
+
+        (state.saturated_ticks >= SATURATED_TICKS).then(|| Alert::PoolSaturated {
+            connections: pool.size(),
+        })
+    }
+
+    /// The message to send for `alert`, or `None` if the same alert went out recently.
+    fn admit(&self, alert: &Alert, now: DateTime<Utc>) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let key = alert.key();
+        if let Some((sent_at, repeats)) = state.sent.get_mut(&key) {
+            if now - *sent_at < Duration::minutes(DEDUPE_MINUTES) {
+                *repeats += 1;
+                return None;
+            }
+        }
+
+        let mut message = alert.message();
+        match state.sent.insert(key, (now, 0)) {
+            Some((_, repeats)) if repeats > 0 => message.push_str(&format!(
+                " This came up {repeats} more time(s) since the last alert."
+            )),
+            _ => {}
+        }
+        Some(message)
+    }
+}
+
+/// Tells the bot's owners about `alert`, unless they were told about it recently.
+pub async fn raise(ctx: &SerenityContext, data: &Data, alert: Alert) {
+    warn!("Alert: {:?}", alert);
+    if let Some(message) = data.alerts.admit(&alert, data.clock.now()) {
+        notify_owners(ctx, &data.config, &message).await;
+    }
+}
+
+/// Posts to the owner channel if one is configured, or DMs the bot's owners, or its team's
+/// members, about something they should look at.
+pub async fn notify_owners(ctx: &SerenityContext, config: &Config, report: &str) {
+    // Discord caps messages at 2000 characters.
+    let report = match report.char_indices().nth(1990) {
+        Some((end, _)) => format!("{}…", &report[..end]),
+        None => report.to_string(),
+    };
+
+    if let Some(channel) = config.owner_channel {
+        match channel
+            .send_message(ctx, CreateMessage::new().content(&report))
+            .await
+        {
+            Ok(_) => return,
+            Err(e) => error!("Could not post to owner channel {}: {}", channel, e),
+        }
+    }
+
+    let info = match ctx.http.get_current_application_info().await {
+        Ok(info) => info,
+        Err(e) => {
+            error!("Could not look up the bot's owners: {}", e);
+            return;
+        }
+    };
+    let mut owners = info.owner.into_iter().map(|u| u.id).collect::<Vec<_>>();
+    if let Some(team) = info.team {
+        owners.extend(team.members.into_iter().map(|m| m.user.id));
+    }
+    owners.dedup();
+
+    for owner in owners {
+        if let Err(e) = send_dm(ctx, owner, CreateMessage::new().content(&report)).await {
+            error!("Could not DM owner {}: {}", owner, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure() -> SlimeError {
+        SlimeError::DatabaseError(sqlx::Error::RowNotFound)
+    }
+
+    #[test]
+    fn failures_spike_only_within_the_window() {
+        let alerts = Alerts::default();
+        let start = Utc::now();
+
+        // Spread out, failures never add up to a spike.
+        for i in 0..20 {
+            let at = start + Duration::minutes(i);
+            assert_eq!(alerts.command_failed(&failure(), at), None);
+        }
+
+        let burst = start + Duration::hours(1);
+        let raised = (0..SPIKE_FAILURES)
+            .filter_map(|i| alerts.command_failed(&failure(), burst + Duration::seconds(i as i64)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            raised,
+            vec![Alert::ErrorSpike {
+                failures: SPIKE_FAILURES
+            }]
+        );
+    }
+
+    #[test]
+    fn jobs_alert_after_repeated_failures_and_reset_on_success() {
+        let alerts = Alerts::default();
+        let failed: Result<(), _> = Err(failure());
+
+        assert_eq!(alerts.job_finished("LFG upkeep", &failed), None);
+        assert_eq!(alerts.job_finished("LFG upkeep", &failed), None);
+        assert!(matches!(
+            alerts.job_finished("LFG upkeep", &failed),
+            Some(Alert::JobFailing { failures: 3, .. })
+        ));
+
+        assert_eq!(alerts.job_finished("LFG upkeep", &Ok(())), None);
+        assert_eq!(alerts.job_finished("LFG upkeep", &failed), None);
+        // Other jobs keep their own count.
+        assert_eq!(alerts.job_finished("Orphan collection", &failed), None);
+    }
+
+    #[test]
+    fn pool_timeouts_alert_immediately() {
+        let alerts = Alerts::default();
+        let timed_out = SlimeError::DatabaseError(sqlx::Error::PoolTimedOut);
+
+        assert!(matches!(
+            alerts.command_failed(&timed_out, Utc::now()),
+            Some(Alert::PoolTimedOut)
+        ));
+    }
+
+    #[test]
+    fn repeated_alerts_are_deduplicated() {
+        let alerts = Alerts::default();
+        let alert = Alert::ErrorSpike { failures: 10 };
+        let start = Utc::now();
+
+        assert!(alerts.admit(&alert, start).is_some());
+        for minute in 1..DEDUPE_MINUTES {
+            assert_eq!(
+                alerts.admit(&alert, start + Duration::minutes(minute)),
+                None
+            );
+        }
+        // A different problem isn't held back by this one.
+        let other = Alert::PoolSaturated { connections: 10 };
+        assert!(alerts.admit(&other, start + Duration::minutes(1)).is_some());
+
+        let message = alerts
+            .admit(&alert, start + Duration::minutes(DEDUPE_MINUTES))
+            .unwrap();
+        assert!(message.contains(&format!("{} more time(s)", DEDUPE_MINUTES - 1)));
+    }
+}
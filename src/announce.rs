@@ -0,0 +1,437 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use poise::{serenity_prelude::*, CreateReply, Modal};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    events::parse_time_in,
+    forms::{self, Form},
+    i18n, relay,
+    util::paginate,
+    ApplicationContext, Context, Data, SlimeError,
+};
+
+/// How many pending announcements `/announce list` shows a page.
+const PAGE_SIZE: usize = 10;
+
+/// An announcement waiting for its time.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Announcement {
+    id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    content: String,
+    send_at: DateTime<Utc>,
+    utc_offset_minutes: i32,
+}
+
+impl Announcement {
+    fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    fn offset(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset_minutes * 60)
+            .unwrap_or(FixedOffset::east_opt(0).unwrap())
+    }
+
+    /// When it goes out: for each reader in their own time, and in the timezone it was set in.
+    fn when(&self) -> String {
+        let offset = self.offset();
+        format!(
+            "{} ({} {})",
+            i18n::timestamp(self.send_at, FormattedTimestampStyle::LongDateTime),
+            self.send_at.with_timezone(&offset).format("%Y-%m-%d %H:%M"),
+            describe_offset(offset)
+        )
+    }
+}
+
+/// What an announcement says.
+#[derive(Debug, Clone, poise::Modal)]
+#[name = "Announcement"]
+struct AnnouncementModal {
+    #[name = "What to post"]
+    #[paragraph]
+    #[max_length = 2000]
+    content: String,
+}
+
+impl Form for AnnouncementModal {
+    type Output = String;
+
+    fn validate(&self) -> Result<String, SlimeError> {
+        let content = self.content.trim();
+        if content.is_empty() {
+            return Err(SlimeError::NothingToPost);
+        }
+        Ok(content.to_string())
+    }
+}
+
+/// Reads a timezone given as an offset from UTC, like `+02:00`, `-5`, `UTC+5:30` or `GMT`.
+pub fn parse_utc_offset(input: &str) -> Option<FixedOffset> {
+    let input = input.trim().to_uppercase();
+    let rest = input
+        .strip_prefix("UTC")
+        .or_else(|| input.strip_prefix("GMT"))
+        .unwrap_or(&input)
+        .trim();
+    if rest.is_empty() || rest == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, rest) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// An offset the way people write it, like `UTC+05:30`.
+fn describe_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    if seconds == 0 {
+        return "UTC".to_string();
+    }
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("UTC{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// The offset and moment `when` and `timezone` mean, checking the moment is still to come.
+pub fn schedule(
+    when: &str,
+    timezone: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<(FixedOffset, DateTime<Utc>), SlimeError> {
+    let offset = match timezone {
+        Some(timezone) => parse_utc_offset(timezone)
+            .ok_or_else(|| SlimeError::InvalidTimezone(timezone.to_string()))?,
+        None => FixedOffset::east_opt(0).unwrap(),
+    };
+    let send_at = parse_time_in(when, offset)
+        .filter(|at| *at > now)
+        .ok_or_else(|| SlimeError::InvalidTime(when.to_string()))?;
+    Ok((offset, send_at))
+}
+
+/// A pending announcement in this guild.
+async fn fetch_pending(ctx: Context<'_>, id: i64) -> Result<Announcement, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    sqlx::query_as::<_, Announcement>(
+        "SELECT * FROM scheduled_announcements WHERE id = $1 AND guild_id = $2",
+    )
+    .bind(id)
+    .bind(guild_id.get() as i64)
+    .fetch_optional(&ctx.data().pool)
+    .await?
+    .ok_or(SlimeError::AnnouncementNotFound(id))
+}
+
+/// Waits for the content typed into the modal that answered `ctx`.
+async fn content_from_modal(
+    ctx: ApplicationContext<'_>,
+    defaults: Option<AnnouncementModal>,
+) -> Result<Option<(ModalInteraction, String)>, SlimeError> {
+    let modal_id = ctx.interaction.id.to_string();
+    ctx.interaction
+        .create_response(ctx, AnnouncementModal::create(defaults, modal_id.clone()))
+        .await?;
+    ctx.has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+
+    forms::collect::<AnnouncementModal>(ctx.serenity_context(), ctx.author().id, modal_id).await
+}
+
+async fn respond(
+    ctx: ApplicationContext<'_>,
+    submitted: &ModalInteraction,
+    content: String,
+) -> Result<(), SlimeError> {
+    submitted
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Post announcements later.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("later", "list", "edit", "cancel")
+)]
+pub async fn announce(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Write an announcement now and have it posted at a set time.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn later(
+    ctx: ApplicationContext<'_>,
+    #[description = "When to post it, e.g. `2024-03-01 19:30` or a Unix timestamp"] when: String,
+    #[description = "Channel to post it in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "Timezone `when` is in, as an offset like `+02:00` or `UTC-5` (default UTC)"]
+    timezone: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let (offset, send_at) = schedule(&when, timezone.as_deref(), ctx.data().clock.now())?;
+
+    let Some((submitted, content)) = content_from_modal(ctx, None).await? else {
+        return Ok(());
+    };
+    let announcement = sqlx::query_as::<_, Announcement>(
+        "INSERT INTO scheduled_announcements
+            (guild_id, channel_id, author_id, content, send_at, utc_offset_minutes)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&content)
+    .bind(send_at)
+    .bind(offset.local_minus_utc() / 60)
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    respond(
+        ctx,
+        &submitted,
+        format!(
+            "Announcement #{id} will be posted in {} on {}. Change it with `/announce edit {id}`.",
+            channel.mention(),
+            announcement.when(),
+            id = announcement.id
+        ),
+    )
+    .await
+}
+
+/// List the announcements waiting to be posted here.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    let pending = sqlx::query_as::<_, Announcement>(
+        "SELECT * FROM scheduled_announcements WHERE guild_id = $1 ORDER BY send_at",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if pending.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .content("There are no announcements waiting to be posted.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let lines = pending
+        .iter()
+        .map(|a| {
+            let preview = a.content.lines().next().unwrap_or_default();
+            let preview = match preview.char_indices().nth(80) {
+                Some((end, _)) => format!("{}…", &preview[..end]),
+                None => preview.to_string(),
+            };
+            format!(
+                "**#{}** in {} on {}\n> {preview}",
+                a.id,
+                a.channel().mention(),
+                a.when()
+            )
+        })
+        .collect::<Vec<_>>();
+    let pages = lines
+        .chunks(PAGE_SIZE)
+        .map(|page| page.join("\n"))
+        .collect::<Vec<_>>();
+
+    paginate(ctx, "Scheduled announcements", &pages).await
+}
+
+/// Change what a scheduled announcement says, or when and where it's posted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn edit(
+    ctx: ApplicationContext<'_>,
+    #[description = "Announcement number, shown in `/announce list`"] id: i64,
+    #[description = "New time to post it, in the timezone it was set in unless you give one"]
+    when: Option<String>,
+    #[description = "New channel to post it in"]
+    #[channel_types("Text", "News")]
+    channel: Option<GuildChannel>,
+    #[description = "Timezone `when` is in, as an offset like `+02:00` or `UTC-5`"]
+    timezone: Option<String>,
+) -> Result<(), SlimeError> {
+    let mut announcement = fetch_pending(ctx.into(), id).await?;
+    if let Some(when) = &when {
+        let timezone = timezone
+            .clone()
+            .unwrap_or_else(|| describe_offset(announcement.offset()));
+        let (offset, send_at) = schedule(when, Some(&timezone), ctx.data().clock.now())?;
+        announcement.utc_offset_minutes = offset.local_minus_utc() / 60;
+        announcement.send_at = send_at;
+    }
+    if let Some(channel) = &channel {
+        announcement.channel_id = channel.id.get() as i64;
+    }
+
+    let defaults = AnnouncementModal {
+        content: announcement.content.clone(),
+    };
+    let Some((submitted, content)) = content_from_modal(ctx, Some(defaults)).await? else {
+        return Ok(());
+    };
+    // It may have gone out or been cancelled while the modal was open.
+    let updated = sqlx::query(
+        "UPDATE scheduled_announcements
+         SET content = $2, channel_id = $3, send_at = $4, utc_offset_minutes = $5
+         WHERE id = $1",
+    )
+    .bind(announcement.id)
+    .bind(&content)
+    .bind(announcement.channel_id)
+    .bind(announcement.send_at)
+    .bind(announcement.utc_offset_minutes)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let reply = if updated == 0 {
+        format!("Announcement #{id} was already posted or cancelled, so nothing changed.")
+    } else {
+        format!(
+            "Announcement #{id} will be posted in {} on {}.",
+            announcement.channel().mention(),
+            announcement.when()
+        )
+    };
+    respond(ctx, &submitted, reply).await
+}
+
+/// Call off a scheduled announcement.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Announcement number, shown in `/announce list`"] id: i64,
+) -> Result<(), SlimeError> {
+    let announcement = fetch_pending(ctx, id).await?;
+    sqlx::query("DELETE FROM scheduled_announcements WHERE id = $1")
+        .bind(announcement.id)
+        .execute(&ctx.data().pool)
+        .await?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Announcement #{id} won't be posted in {}.",
+                announcement.channel().mention()
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Posts the announcements that are due.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    // Claimed before posting, so each one is only ever posted once.
+    let due = sqlx::query_as::<_, Announcement>(
+        "DELETE FROM scheduled_announcements
+         WHERE send_at <= $1 AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for announcement in due {
+        data.calls.turn().await;
+        let channel = announcement.channel();
+        if let Err(e) = relay::say(
+            ctx,
+            pool,
+            announcement.guild(),
+            channel,
+            announcement.content,
+        )
+        .await
+        {
+            error!(
+                "Could not post announcement #{} in {}: {}",
+                announcement.id, channel, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn timezones_read_as_offsets() {
+        let minutes = |input| parse_utc_offset(input).map(|o| o.local_minus_utc() / 60);
+        assert_eq!(minutes("UTC"), Some(0));
+        assert_eq!(minutes("+02:00"), Some(120));
+        assert_eq!(minutes("utc-5"), Some(-300));
+        assert_eq!(minutes("GMT+5:30"), Some(330));
+        assert_eq!(minutes("-0330"), Some(-210));
+        assert_eq!(minutes("Europe/Paris"), None);
+        assert_eq!(minutes("+15"), None);
+
+        let offset = parse_utc_offset("+05:30").unwrap();
+        assert_eq!(describe_offset(offset), "UTC+05:30");
+        assert_eq!(parse_utc_offset(&describe_offset(offset)), Some(offset));
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let (_, at) = schedule("2024-03-01 19:30", Some("+02:00"), now).unwrap();
+        assert_eq!(at, Utc.with_ymd_and_hms(2024, 3, 1, 17, 30, 0).unwrap());
+        assert!(matches!(
+            schedule("2024-03-01 13:30", Some("+02:00"), now),
+            Err(SlimeError::InvalidTime(_))
+        ));
+        assert!(matches!(
+            schedule("2024-03-01 19:30", Some("CET"), now),
+            Err(SlimeError::InvalidTimezone(_))
+        ));
+    }
+}
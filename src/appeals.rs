@@ -0,0 +1,387 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use tracing::{error, info};
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    discord,
+    settings::GuildSettings,
+    util::{http_status, respond_ephemeral, send_dm},
+    Data, SlimeError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum Status {
+    /// The member was told they can appeal, and hasn't yet.
+    Offered,
+    /// Waiting on a moderator.
+    Pending,
+    Approved,
+    Denied,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Appeal {
+    id: i64,
+    guild_id: i64,
+    user_id: i64,
+    until: DateTime<Utc>,
+    status: Status,
+    reason: Option<String>,
+    decided_by: Option<i64>,
+}
+
+/// The post moderators decide `appeal` from, with buttons while it's still undecided.
+fn queue_post(appeal: &Appeal) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let status = match (appeal.status, appeal.decided_by) {
+        (Status::Approved, Some(by)) => {
+            format!(
+                "Approved by {}, the timeout was lifted.",
+                UserId::new(by as u64).mention()
+            )
+        }
+        (Status::Denied, Some(by)) => format!("Denied by {}.", UserId::new(by as u64).mention()),
+        _ => "Waiting for a moderator.".to_string(),
+    };
+    let embed = CreateEmbed::new()
+        .title("Timeout appeal")
+        .field(
+            "Member",
+            UserId::new(appeal.user_id as u64).mention().to_string(),
+            true,
+        )
+        .field(
+            "Timed out until",
+            format!("<t:{}:f>", appeal.until.timestamp()),
+            true,
+        )
+        .field("Appeal", appeal.reason.as_deref().unwrap_or("—"), false)
+        .field("Status", status, false)
+        .footer(CreateEmbedFooter::new(format!("Appeal #{}", appeal.id)));
+    let rows = if appeal.status == Status::Pending {
+        let button = |action, label, style| {
+            CreateButton::new(CustomId::new(Kind::Appeal, action, appeal.id))
+                .label(label)
+                .style(style)
+        };
+        vec![CreateActionRow::Buttons(vec![
+            button("approve", "Lift timeout", ButtonStyle::Success),
+            button("deny", "Deny", ButtonStyle::Secondary),
+        ])]
+    } else {
+        vec![]
+    };
+    (embed, rows)
+}
+
+fn server_name(ctx: &SerenityContext, guild_id: GuildId) -> String {
+    ctx.cache
+        .guild(guild_id)
+        .map_or_else(|| "the server".to_string(), |g| g.name.clone())
+}
+
+/// Offers an appeal to a member who was just timed out, whether by a moderator or by Discord's
+/// auto-mod. Each timeout is offered once, however many updates mention it.
+pub async fn timed_out(
+    ctx: &SerenityContext,
+    data: &Data,
+    event: &GuildMemberUpdateEvent,
+) -> Result<(), SlimeError> {
+    let now = data.clock.now();
+    let Some(until) = event
+        .communication_disabled_until
+        .map(|at| *at)
+        .filter(|until| *until > now)
+    else {
+        return Ok(());
+    };
+    if event.user.bot
+        || !GuildSettings::load(&data.pool, event.guild_id)
+            .await?
+            .appeals
+    {
+        return Ok(());
+    }
+    let Some(appeal) = sqlx::query_as::<_, Appeal>(
+        "INSERT INTO timeout_appeals (guild_id, user_id, until, created_at) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id, user_id, until) DO NOTHING
+         RETURNING *",
+    )
+    .bind(event.guild_id.get() as i64)
+    .bind(event.user.id.get() as i64)
+    .bind(until)
+    .bind(now)
+    .fetch_optional(&data.pool)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let offer = CreateMessage::new()
+        .embed(
+            CreateEmbed::new()
+                .title(format!(
+                    "You were timed out in {}",
+                    server_name(ctx, event.guild_id)
+                ))
+                .description(format!(
+                    "You can talk again <t:{}:R>. If you think this was a mistake, you can ask \
+                     the moderators to lift it.",
+                    until.timestamp()
+                )),
+        )
+        .button(
+            CreateButton::new(CustomId::new(Kind::Appeal, "open", appeal.id))
+                .label("Appeal")
+                .style(ButtonStyle::Primary),
+        );
+    if let Err(e) = send_dm(ctx, event.user.id, offer).await {
+        info!("Could not offer {} an appeal: {}", event.user.id, e);
+    }
+    Ok(())
+}
+
+/// Handles the buttons of an appeal: the member's **Appeal**, which opens the form, and the
+/// moderators' decision. Only members who can time others out may decide.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let appeal = sqlx::query_as::<_, Appeal>("SELECT * FROM timeout_appeals WHERE id = $1")
+        .bind(custom_id.id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(appeal) = appeal else {
+        return respond_ephemeral(ctx, interaction, "That appeal doesn't exist any more.").await;
+    };
+    let guild_id = GuildId::new(appeal.guild_id as u64);
+    let member = UserId::new(appeal.user_id as u64);
+    let now = data.clock.now();
+
+    let status = match custom_id.action.as_str() {
+        "open" => {
+            let problem = if interaction.user.id != member {
+                Some("That appeal isn't yours.")
+            } else if appeal.status != Status::Offered {
+                Some("You've already appealed this timeout.")
+            } else if appeal.until <= now {
+                Some("Your timeout is already over.")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return respond_ephemeral(ctx, interaction, problem).await;
+            }
+            let reason = CreateInputText::new(
+                InputTextStyle::Paragraph,
+                "Why should it be lifted?",
+                "reason",
+            )
+            .max_length(1000);
+            let modal = CreateModal::new(
+                CustomId::new(Kind::Appeal, "submit", appeal.id),
+                "Appeal your timeout",
+            )
+            .components(vec![CreateActionRow::InputText(reason)]);
+            discord::respond(ctx, interaction, CreateInteractionResponse::Modal(modal)).await?;
+            return Ok(());
+        }
+        "approve" => Status::Approved,
+        "deny" => Status::Denied,
+        _ => return Ok(()),
+    };
+
+    let allowed = interaction
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.moderate_members());
+    if !allowed || interaction.guild_id != Some(guild_id) {
+        return respond_ephemeral(ctx, interaction, "Only moderators can decide appeals.").await;
+    }
+    if appeal.status != Status::Pending {
+        return respond_ephemeral(ctx, interaction, "Someone has already decided that.").await;
+    }
+    // One that ran out while waiting has nothing left to lift.
+    if status == Status::Approved && appeal.until > now {
+        let lift = EditMember::new()
+            .enable_communication()
+            .audit_log_reason("Appeal approved");
+        match guild_id.edit_member(ctx, member, lift).await {
+            Ok(_) => {}
+            Err(e) if http_status(&e) == Some(404) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let decided = sqlx::query_as::<_, Appeal>(
+        "UPDATE timeout_appeals SET status = $2, decided_by = $3, decided_at = $4
+         WHERE id = $1 AND status = 'pending'
+         RETURNING *",
+    )
+    .bind(appeal.id)
+    .bind(status)
+    .bind(interaction.user.id.get() as i64)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+    let Some(appeal) = decided else {
+        return respond_ephemeral(ctx, interaction, "Someone has already decided that.").await;
+    };
+    let (action, outcome) = match status {
+        Status::Approved => (
+            "appeal_approved",
+            "Your appeal was approved, and your timeout lifted.",
+        ),
+        _ => ("appeal_denied", "Your appeal was denied."),
+    };
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: interaction.user.id,
+            action,
+            target: Some(member.get()),
+            details: format!("appeal #{}", appeal.id),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    let (embed, components) = queue_post(&appeal);
+    discord::respond(
+        ctx,
+        interaction,
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(components),
+        ),
+    )
+    .await?;
+    let told = format!("{outcome} ({})", server_name(ctx, guild_id));
+    if let Err(e) = send_dm(ctx, member, CreateMessage::new().content(told)).await {
+        info!("Could not tell {} how their appeal went: {}", member, e);
+    }
+
+    Ok(())
+}
+
+/// Sends a member's appeal to the moderators' queue.
+pub async fn submitted(
+    ctx: &SerenityContext,
+    data: &Data,
+    modal: &ModalInteraction,
+) -> Result<(), SlimeError> {
+    let Some(custom_id) = CustomId::parse(&modal.data.custom_id) else {
+        return Ok(());
+    };
+    let pool = &data.pool;
+    let reason = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) => input.value.clone(),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let appeal =
+        sqlx::query_as::<_, Appeal>("SELECT * FROM timeout_appeals WHERE id = $1 AND user_id = $2")
+            .bind(custom_id.id)
+            .bind(modal.user.id.get() as i64)
+            .fetch_optional(pool)
+            .await?;
+
+    let content = match appeal {
+        None => "That appeal doesn't exist any more.".to_string(),
+        Some(appeal) if appeal.status != Status::Offered => {
+            "You've already appealed this timeout.".to_string()
+        }
+        Some(appeal) => {
+            let guild_id = GuildId::new(appeal.guild_id as u64);
+            let settings = GuildSettings::load(pool, guild_id).await?;
+            match settings.appeal_channel() {
+                None => "This server has nowhere to send appeals right now. Please try again \
+                         later."
+                    .to_string(),
+                Some(channel) => {
+                    let appeal = sqlx::query_as::<_, Appeal>(
+                        "UPDATE timeout_appeals SET status = 'pending', reason = $2
+                         WHERE id = $1 RETURNING *",
+                    )
+                    .bind(appeal.id)
+                    .bind(reason.trim())
+                    .fetch_one(pool)
+                    .await?;
+                    let (embed, components) = queue_post(&appeal);
+                    match channel
+                        .send_message(
+                            ctx,
+                            CreateMessage::new().embed(embed).components(components),
+                        )
+                        .await
+                    {
+                        Ok(message) => {
+                            sqlx::query(
+                                "UPDATE timeout_appeals SET channel_id = $2, message_id = $3
+                                 WHERE id = $1",
+                            )
+                            .bind(appeal.id)
+                            .bind(channel.get() as i64)
+                            .bind(message.id.get() as i64)
+                            .execute(pool)
+                            .await?;
+                        }
+                        Err(e) => error!("Could not post appeal in {}: {}", channel, e),
+                    }
+                    format!(
+                        "Your appeal was sent to the moderators of **{}**. You'll hear back \
+                         here.",
+                        server_name(ctx, guild_id)
+                    )
+                }
+            }
+        }
+    };
+    modal
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_undecided_appeals_can_be_decided() {
+        let mut appeal = Appeal {
+            id: 1,
+            guild_id: 2,
+            user_id: 3,
+            until: Utc::now(),
+            status: Status::Pending,
+            reason: Some("It was a joke".to_string()),
+            decided_by: None,
+        };
+        assert_eq!(queue_post(&appeal).1.len(), 1);
+        for status in [Status::Offered, Status::Approved, Status::Denied] {
+            appeal.status = status;
+            assert!(queue_post(&appeal).1.is_empty());
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    settings::GuildSettings,
+    undo::{self, UndoStep},
+    SlimeError,
+};
+
+/// Something the bot did on someone's behalf that should leave a trail.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// `None` for actions that span guilds, like forgetting a user everywhere.
+    pub guild_id: Option<GuildId>,
+    pub actor: UserId,
+    pub action: &'static str,
+    pub target: Option<u64>,
+    pub details: String,
+    /// How `/undo` reverses the action, empty if it can't be.
+    pub undo: Vec<UndoStep>,
+}
+
+/// Writes `entry` to the audit log. Takes any executor so it can share a transaction with the
+/// action it records.
+pub async fn record<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    entry: AuditEntry,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO audit_log (guild_id, actor_id, action, target_id, details, undo)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(entry.guild_id.map(|id| id.get() as i64))
+    .bind(entry.actor.get() as i64)
+    .bind(entry.action)
+    .bind(entry.target.map(|id| id as i64))
+    .bind(entry.details)
+    .bind(undo::encode(&entry.undo))
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Records `entry` and posts `summary` to the guild's audit channel, if it has one. Posting
+/// failures are only logged, since the database record is the one that counts.
+pub async fn record_and_post(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    entry: AuditEntry,
+    summary: &str,
+) -> Result<(), SlimeError> {
+    let guild_id = entry.guild_id;
+    let actor = entry.actor;
+    record(pool, entry).await?;
+
+    let Some(guild_id) = guild_id else {
+        return Ok(());
+    };
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let Some(channel) = settings.audit_channel() else {
+        return Ok(());
+    };
+    // Screen readers read a plain message straight through, where an embed's fields come out
+    // jumbled, and the message's own time stands in for the embed's.
+    let post = if settings.plain_text {
+        CreateMessage::new()
+            .content(format!("{summary}\nBy {}.", actor.mention()))
+            .allowed_mentions(CreateAllowedMentions::new())
+    } else {
+        CreateMessage::new().embed(
+            CreateEmbed::new()
+                .description(summary)
+                .field("By", actor.mention().to_string(), true)
+                .timestamp(Timestamp::now()),
+        )
+    };
+    if let Err(e) = channel.send_message(ctx, post).await {
+        error!("Could not post to audit channel {}: {}", channel, e);
+    }
+
+    Ok(())
+}
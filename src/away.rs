@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{announce, i18n, Context, SlimeError};
+
+/// The longest anyone can be away for in one go, so a typo doesn't leave someone away for years.
+const MAX_AWAY_DAYS: i64 = 365;
+
+/// Which of `users` are away at `now`, and when they're back.
+pub async fn away_now(
+    pool: &PgPool,
+    users: &[UserId],
+    now: DateTime<Utc>,
+) -> Result<HashMap<UserId, DateTime<Utc>>, SlimeError> {
+    let ids = users.iter().map(|u| u.get() as i64).collect::<Vec<_>>();
+    let away = sqlx::query_as::<_, (i64, DateTime<Utc>)>(
+        "SELECT user_id, away_until FROM user_preferences
+         WHERE away_until > $2 AND user_id = ANY($1)",
+    )
+    .bind(&ids)
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(away
+        .into_iter()
+        .map(|(id, until)| (UserId::new(id as u64), until))
+        .collect())
+}
+
+/// What's shown after an away member's name.
+pub fn marker(until: DateTime<Utc>) -> String {
+    format!(
+        " (away until {})",
+        i18n::timestamp(until, FormattedTimestampStyle::ShortDate)
+    )
+}
+
+/// Why `until` won't do as when someone is back, if it won't.
+fn problem(until: DateTime<Utc>, now: DateTime<Utc>) -> Option<String> {
+    (until - now > Duration::days(MAX_AWAY_DAYS))
+        .then(|| format!("You can be away for up to {MAX_AWAY_DAYS} days at a time."))
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Let hosts and rotations know when you're away.
+#[poise::command(slash_command, subcommands("set", "clear"))]
+pub async fn away(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Mark yourself away until a date. Rotations skip you and you aren't sent event reminders.
+#[poise::command(slash_command)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "When you're back, e.g. `2024-03-01 09:00` or a Unix timestamp"] until: String,
+    #[description = "Timezone `until` is in, as an offset like `+02:00` or `UTC-5` (default UTC)"]
+    timezone: Option<String>,
+) -> Result<(), SlimeError> {
+    let now = ctx.data().clock.now();
+    let (_, until) = announce::schedule(&until, timezone.as_deref(), now)?;
+    if let Some(problem) = problem(until, now) {
+        return reply(ctx, problem).await;
+    }
+    sqlx::query(
+        "INSERT INTO user_preferences (user_id, away_until) VALUES ($1, $2)
+         ON CONFLICT (user_id) DO UPDATE SET away_until = EXCLUDED.away_until",
+    )
+    .bind(ctx.author().id.get() as i64)
+    .bind(until)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "You're away until {}. Role rotations skip you, hosts can see you're away, and you \
+             won't be sent event reminders until then.",
+            i18n::timestamp(until, FormattedTimestampStyle::LongDateTime)
+        ),
+    )
+    .await
+}
+
+/// Mark yourself back early.
+#[poise::command(slash_command)]
+async fn clear(ctx: Context<'_>) -> Result<(), SlimeError> {
+    sqlx::query("UPDATE user_preferences SET away_until = NULL WHERE user_id = $1")
+        .bind(ctx.author().id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?;
+    reply(ctx, "Welcome back! You're no longer marked away.").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_can_be_away_for_up_to_a_year() {
+        let now = Utc::now();
+        assert_eq!(problem(now + Duration::days(14), now), None);
+        assert_eq!(problem(now + Duration::days(MAX_AWAY_DAYS), now), None);
+        assert!(problem(now + Duration::days(MAX_AWAY_DAYS + 1), now).is_some());
+    }
+}
@@ -0,0 +1,217 @@
+//! Images for events, like banners and check-in cards, drawn and encoded here so the bot needs
+//! no image libraries.
+
+mod font;
+mod png;
+mod qr;
+
+use qr::QrCode;
+
+/// Discord's recommended size for scheduled event covers.
+const WIDTH: usize = 800;
+const HEIGHT: usize = 320;
+const MARGIN: usize = 40;
+
+/// What the banner is attached as, so embeds can point at it.
+pub const FILENAME: &str = "banner.png";
+
+/// What check-in cards are attached as.
+pub const CHECKIN_FILENAME: &str = "checkin.png";
+
+/// Background gradients, top to bottom, picked between by the title.
+const PALETTES: [([u8; 3], [u8; 3]); 4] = [
+    ([0x2b, 0x5f, 0x3a], [0x0f, 0x24, 0x16]),
+    ([0x3b, 0x3f, 0x8f], [0x14, 0x16, 0x3a]),
+    ([0x8f, 0x3b, 0x5a], [0x38, 0x12, 0x22]),
+    ([0x1f, 0x6f, 0x7a], [0x0a, 0x26, 0x2c]),
+];
+
+const TEXT: [u8; 3] = [0xff, 0xff, 0xff];
+const MUTED: [u8; 3] = [0xc8, 0xd0, 0xcc];
+
+struct Canvas {
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Canvas {
+    fn gradient(top: [u8; 3], bottom: [u8; 3]) -> Self {
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT);
+        for y in 0..HEIGHT {
+            let mix = |a: u8, b: u8| (a as usize * (HEIGHT - y) + b as usize * y) / HEIGHT;
+            let row = [0, 1, 2].map(|i| mix(top[i], bottom[i]) as u8);
+            pixels.extend(std::iter::repeat_n(row, WIDTH));
+        }
+        Self { pixels }
+    }
+
+    fn rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        for row in y..(y + height).min(HEIGHT) {
+            for column in x..(x + width).min(WIDTH) {
+                self.pixels[row * WIDTH + column] = color;
+            }
+        }
+    }
+
+    /// Writes `text` with its top left corner at (`x`, `y`), each font pixel `scale` wide.
+    fn text(&mut self, x: usize, y: usize, scale: usize, text: &str, color: [u8; 3]) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * (font::WIDTH + 1) * scale;
+            for row in 0..font::HEIGHT {
+                for column in 0..font::WIDTH {
+                    if font::is_set(c, column, row) {
+                        let (px, py) = (left + column * scale, y + row * scale);
+                        self.rect(px, py, scale, scale, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How many characters fit across the banner at `scale`.
+fn fits(scale: usize) -> usize {
+    (WIDTH - 2 * MARGIN + scale) / ((font::WIDTH + 1) * scale)
+}
+
+/// Splits `text` into at most `lines` lines of at most `width` characters, breaking between
+/// words. Returns `None` if it doesn't fit.
+fn wrap(text: &str, width: usize, lines: usize) -> Option<Vec<String>> {
+    let mut wrapped = Vec::<String>::new();
+    for word in text.split_whitespace() {
+        if word.chars().count() > width {
+            return None;
+        }
+        match wrapped.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => wrapped.push(word.to_string()),
+        }
+    }
+    (wrapped.len() <= lines).then_some(wrapped)
+}
+
+/// The title laid out as large as it fits on two lines, cut short at the smallest size if it
+/// never does.
+fn layout_title(title: &str) -> (usize, Vec<String>) {
+    for scale in (4..=9).rev() {
+        if let Some(lines) = wrap(title, fits(scale), 2) {
+            return (scale, lines);
+        }
+    }
+    let width = fits(4);
+    let mut cut = title.chars().take(width - 3).collect::<String>();
+    cut.push_str("...");
+    (4, vec![cut])
+}
+
+/// Draws an event's banner as a PNG.
+pub fn render(title: &str, when: &str, host: &str) -> Vec<u8> {
+    let palette = PALETTES[title.bytes().map(usize::from).sum::<usize>() % PALETTES.len()];
+    let mut canvas = Canvas::gradient(palette.0, palette.1);
+    canvas.rect(0, 0, 12, HEIGHT, palette.0.map(|c| c.saturating_add(0x30)));
+
+    let (scale, lines) = layout_title(title);
+    let line_height = (font::HEIGHT + 3) * scale;
+    for (i, line) in lines.iter().enumerate() {
+        canvas.text(MARGIN, MARGIN + i * line_height, scale, line, TEXT);
+    }
+
+    let details = 4;
+    let host_y = HEIGHT - MARGIN - font::HEIGHT * 3;
+    let when_y = host_y - (font::HEIGHT + 4) * details;
+    canvas.text(MARGIN, when_y, details, when, TEXT);
+    if !host.is_empty() {
+        let host = format!("Hosted by {host}");
+        let host = host.chars().take(fits(3)).collect::<String>();
+        canvas.text(MARGIN, host_y, 3, &host, MUTED);
+    }
+
+    png::encode(WIDTH, HEIGHT, &canvas.pixels)
+}
+
+/// Draws a card to show at an in-person event: a QR code for `link` beside the check-in `code`.
+/// Returns `None` if the link is too long for a QR code.
+pub fn checkin_card(title: &str, link: &str, code: &str) -> Option<Vec<u8>> {
+    let qr = QrCode::encode(link)?;
+    let mut canvas = Canvas::gradient([0xf4, 0xf6, 0xf4], [0xdc, 0xe4, 0xde]);
+
+    // The code with a quiet zone of four modules around it, as large as the card allows.
+    let modules = qr.size + 8;
+    let scale = (HEIGHT - 2 * 12) / modules;
+    let (left, top) = (12, (HEIGHT - modules * scale) / 2);
+    canvas.rect(
+        left,
+        top,
+        modules * scale,
+        modules * scale,
+        [0xff, 0xff, 0xff],
+    );
+    for y in 0..qr.size {
+        for x in 0..qr.size {
+            if qr.is_dark(x, y) {
+                let (px, py) = (left + (x + 4) * scale, top + (y + 4) * scale);
+                canvas.rect(px, py, scale, scale, [0, 0, 0]);
+            }
+        }
+    }
+
+    let text_x = left + modules * scale + 28;
+    let columns = (WIDTH - MARGIN - text_x) / ((font::WIDTH + 1) * 3);
+    let dark = [0x1a, 0x22, 0x1d];
+    let title = wrap(title, columns, 3).unwrap_or_else(|| {
+        vec![
+            title
+                .chars()
+                .take(columns.saturating_sub(3))
+                .collect::<String>()
+                + "...",
+        ]
+    });
+    for (i, line) in title.iter().enumerate() {
+        canvas.text(text_x, MARGIN + i * (font::HEIGHT + 3) * 3, 3, line, dark);
+    }
+    let code_y = HEIGHT / 2 + 10;
+    canvas.text(text_x, code_y, 3, "Check-in code", [0x55, 0x60, 0x58]);
+    canvas.text(text_x, code_y + 36, 8, code, dark);
+
+    Some(png::encode(WIDTH, HEIGHT, &canvas.pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_are_as_large_as_fit() {
+        assert_eq!(
+            layout_title("Game night"),
+            (9, vec!["Game night".to_string()])
+        );
+
+        let (scale, lines) = layout_title("Weekly community movie marathon and watch party");
+        assert!(scale < 9);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.chars().count() <= fits(scale)));
+
+        let (scale, lines) = layout_title(&"x".repeat(200));
+        assert_eq!((scale, lines.len()), (4, 1));
+        assert!(lines[0].ends_with("..."));
+    }
+
+    #[test]
+    fn banners_are_full_size_pngs() {
+        let banner = render("Raid night", "SAT 02 MAR 2024, 19:30 UTC", "ana");
+        // The signature, then the header's width and height.
+        assert_eq!(&banner[1..4], b"PNG");
+        assert_eq!(&banner[16..24], &[0, 0, 3, 32, 0, 0, 1, 64]);
+    }
+
+    #[test]
+    fn checkin_cards_need_a_link_that_fits() {
+        let card = checkin_card("Meetup", "https://discord.com/channels/1/2/3", "K7P2QX").unwrap();
+        assert_eq!(&card[1..4], b"PNG");
+        assert!(checkin_card("Meetup", &"x".repeat(200), "K7P2QX").is_none());
+    }
+}
@@ -0,0 +1,361 @@
+//! QR codes for short links: byte mode at error correction level L, versions 1 to 6, which is
+//! up to 134 bytes.
+
+/// Data codewords, error correction codewords per block, and blocks, for versions 1 to 6 at L.
+const VERSIONS: [(usize, usize, usize); 6] = [
+    (19, 7, 1),
+    (34, 10, 1),
+    (55, 15, 1),
+    (80, 20, 1),
+    (108, 26, 1),
+    (136, 18, 2),
+];
+
+/// Multiplication in GF(2^8) over the polynomial QR codes use.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z = 0u32;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11d);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
+
+/// The Reed-Solomon generator polynomial of `degree`, leading coefficient left out.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_mul(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_mul(root, 2);
+    }
+    divisor
+}
+
+/// The error correction codewords for `data`.
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_mul(*d, factor);
+        }
+    }
+    remainder
+}
+
+/// The 15 format bits for level L and `mask`, with their BCH check bits.
+fn format_bits(mask: u8) -> u32 {
+    // Level L is 01.
+    let data = (1 << 3 | mask as u32) & 0x1f;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+fn masked(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// A square of modules, dark where `true`.
+#[derive(Clone)]
+pub struct QrCode {
+    pub size: usize,
+    modules: Vec<bool>,
+    /// Modules belonging to finders, timing and the like, which data and masks leave alone.
+    reserved: Vec<bool>,
+}
+
+impl QrCode {
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.reserved[y * self.size + x] = true;
+    }
+
+    fn finder(&mut self, cx: isize, cy: isize) {
+        for dy in -4..=4isize {
+            for dx in -4..=4isize {
+                let (x, y) = (cx + dx, cy + dy);
+                if (0..self.size as isize).contains(&x) && (0..self.size as isize).contains(&y) {
+                    let distance = dx.abs().max(dy.abs());
+                    self.set(x as usize, y as usize, distance != 2 && distance != 4);
+                }
+            }
+        }
+    }
+
+    fn alignment(&mut self, cx: usize, cy: usize) {
+        for dy in -2..=2isize {
+            for dx in -2..=2isize {
+                let distance = dx.abs().max(dy.abs());
+                let (x, y) = ((cx as isize + dx) as usize, (cy as isize + dy) as usize);
+                self.set(x, y, distance != 1);
+            }
+        }
+    }
+
+    fn format(&mut self, mask: u8) {
+        let bits = format_bits(mask);
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+        for i in 0..6 {
+            self.set(8, i, bit(i));
+        }
+        self.set(8, 7, bit(6));
+        self.set(8, 8, bit(7));
+        self.set(7, 8, bit(8));
+        for i in 9..15 {
+            self.set(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set(8, size - 15 + i, bit(i));
+        }
+        // Always dark.
+        self.set(8, size - 8, true);
+    }
+
+    fn function_patterns(version: usize) -> Self {
+        let size = version * 4 + 17;
+        let mut qr = Self {
+            size,
+            modules: vec![false; size * size],
+            reserved: vec![false; size * size],
+        };
+        for i in 0..size {
+            qr.set(6, i, i % 2 == 0);
+            qr.set(i, 6, i % 2 == 0);
+        }
+        qr.finder(3, 3);
+        qr.finder(size as isize - 4, 3);
+        qr.finder(3, size as isize - 4);
+        if version > 1 {
+            // Versions up to 6 have a single alignment pattern, clear of the finders.
+            let at = size - 7;
+            qr.alignment(at, at);
+        }
+        // Reserved now, written once the mask is chosen.
+        qr.format(0);
+        qr
+    }
+
+    fn place(&mut self, codewords: &[u8]) {
+        let size = self.size as isize;
+        let mut i = 0;
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    } as usize;
+                    if !self.reserved[y * self.size + x] && i < codewords.len() * 8 {
+                        let dark = (codewords[i >> 3] >> (7 - (i & 7))) & 1 == 1;
+                        self.modules[y * self.size + x] = dark;
+                        i += 1;
+                    }
+                }
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u8) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if !self.reserved[y * self.size + x] && masked(mask, x, y) {
+                    self.modules[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// How hard the symbol is to scan, by the standard's penalty rules. Lower is better.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        let line = |i: usize, horizontal: bool| {
+            (0..size)
+                .map(|j| {
+                    if horizontal {
+                        self.is_dark(j, i)
+                    } else {
+                        self.is_dark(i, j)
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let finder_like = [true, false, true, true, true, false, true];
+        for i in 0..size {
+            for horizontal in [true, false] {
+                let modules = line(i, horizontal);
+                let mut run = 1;
+                for j in 1..=size {
+                    if j < size && modules[j] == modules[j - 1] {
+                        run += 1;
+                        continue;
+                    }
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+                for start in 0..=size - 7 {
+                    if modules[start..start + 7] != finder_like {
+                        continue;
+                    }
+                    let light =
+                        |range: std::ops::Range<usize>| range.into_iter().all(|k| !modules[k]);
+                    let before = start >= 4 && light(start - 4..start);
+                    let after = start + 11 <= size && light(start + 7..start + 11);
+                    if before || after {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.is_dark(x, y);
+                if dark == self.is_dark(x + 1, y)
+                    && dark == self.is_dark(x, y + 1)
+                    && dark == self.is_dark(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+        let total = size * size;
+        let dark = self.modules.iter().filter(|m| **m).count();
+        let k = (dark * 20)
+            .abs_diff(total * 10)
+            .div_ceil(total)
+            .saturating_sub(1);
+        penalty + k * 10
+    }
+
+    /// Encodes `text`, or returns `None` if it's too long.
+    pub fn encode(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        // Byte mode, then an 8 bit length for versions up to 9.
+        let (version, &(data_len, ec_len, blocks)) = VERSIONS
+            .iter()
+            .enumerate()
+            .find(|(_, (data_len, _, _))| bytes.len() + 2 <= *data_len)?;
+        let version = version + 1;
+
+        let mut bits = Vec::with_capacity(data_len * 8);
+        let mut push = |value: u32, count: usize| {
+            for i in (0..count).rev() {
+                bits.push((value >> i) & 1 == 1);
+            }
+        };
+        push(0b0100, 4);
+        push(bytes.len() as u32, 8);
+        for byte in bytes {
+            push(*byte as u32, 8);
+        }
+        let terminator = (data_len * 8 - bits.len()).min(4);
+        bits.extend(std::iter::repeat_n(false, terminator));
+        while bits.len() % 8 != 0 {
+            bits.push(false);
+        }
+        let mut data = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |b, bit| b << 1 | *bit as u8))
+            .collect::<Vec<_>>();
+        for pad in [0xec, 0x11].into_iter().cycle() {
+            if data.len() == data_len {
+                break;
+            }
+            data.push(pad);
+        }
+
+        // Blocks are all the same length at level L up to version 6, and are interleaved.
+        let divisor = rs_divisor(ec_len);
+        let block_len = data_len / blocks;
+        let data_blocks = data.chunks(block_len).collect::<Vec<_>>();
+        let ec_blocks = data_blocks
+            .iter()
+            .map(|block| rs_remainder(block, &divisor))
+            .collect::<Vec<_>>();
+        let mut codewords = Vec::new();
+        for i in 0..block_len {
+            codewords.extend(data_blocks.iter().map(|block| block[i]));
+        }
+        for i in 0..ec_len {
+            codewords.extend(ec_blocks.iter().map(|block| block[i]));
+        }
+
+        let mut base = Self::function_patterns(version);
+        base.place(&codewords);
+        (0..8)
+            .map(|mask| {
+                let mut qr = base.clone();
+                qr.apply_mask(mask);
+                qr.format(mask);
+                qr
+            })
+            .min_by_key(QrCode::penalty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_correction_matches_the_reference() {
+        // The worked "HELLO WORLD" example, at version 1 level M.
+        let data = [
+            0x20, 0x5b, 0x0b, 0x78, 0xd1, 0x72, 0xdc, 0x4d, 0x43, 0x40, 0xec, 0x11, 0xec, 0x11,
+            0xec, 0x11,
+        ];
+        assert_eq!(
+            rs_remainder(&data, &rs_divisor(10)),
+            [0xc4, 0x23, 0x27, 0x77, 0xeb, 0xd7, 0xe7, 0xe2, 0x5d, 0x17]
+        );
+        assert_eq!(format_bits(0), 0b111011111000100);
+        assert_eq!(format_bits(4), 0b110011000101111);
+    }
+
+    #[test]
+    fn links_pick_the_smallest_version_that_fits() {
+        assert_eq!(QrCode::encode("https://example.com").unwrap().size, 25);
+        let link =
+            "https://discord.com/channels/123456789012345678/123456789012345678/123456789012345678";
+        assert_eq!(QrCode::encode(link).unwrap().size, 37);
+        assert!(QrCode::encode(&"x".repeat(135)).is_none());
+    }
+}
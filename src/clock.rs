@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+
+/// Where scheduled work gets the time from. The bot runs on [`SystemClock`]; tests use
+/// [`SimulatedClock`] to fast-forward through schedules instead of waiting on them.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[cfg(test)]
+pub struct SimulatedClock(std::sync::Mutex<DateTime<Utc>>);
+
+#[cfg(test)]
+impl SimulatedClock {
+    pub fn starting_at(at: DateTime<Utc>) -> Self {
+        Self(std::sync::Mutex::new(at))
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
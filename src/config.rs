@@ -0,0 +1,269 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use poise::serenity_prelude::*;
+use serenity::{client::Context as SerenityContext, Error as SerenityError};
+use shuttle_secrets::SecretStore;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use tracing_subscriber::{prelude::*, EnvFilter};
+
+use crate::{links, templates, weather};
+
+/// How long a departed guild's data is kept, unless `DETACHED_GRACE_DAYS` says otherwise.
+const DEFAULT_GRACE_DAYS: i64 = 30;
+
+/// Which deployment this is. Anything but production only touches its test guild, so a staging
+/// bot can run against real Discord without anyone else seeing its commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl FromStr for Environment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dev" | "development" => Ok(Environment::Dev),
+            "staging" => Ok(Environment::Staging),
+            "prod" | "production" => Ok(Environment::Prod),
+            other => Err(anyhow!(
+                "'ENVIRONMENT' is `{other}`, expected dev, staging or prod"
+            )),
+        }
+    }
+}
+
+impl Environment {
+    /// What gets logged when `LOG_FILTER` isn't set.
+    fn default_log_filter(self) -> &'static str {
+        match self {
+            Environment::Dev => "info,pond_slime=trace",
+            Environment::Staging => "info,pond_slime=debug",
+            Environment::Prod => "info",
+        }
+    }
+
+    /// The database schema used when `DB_SCHEMA` isn't set. Production keeps the tables it
+    /// always had.
+    fn default_schema(self) -> &'static str {
+        match self {
+            Environment::Dev => "dev",
+            Environment::Staging => "staging",
+            Environment::Prod => "public",
+        }
+    }
+}
+
+/// Deployment settings, read from `Secrets.toml`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub environment: Environment,
+    /// Where commands are registered outside production.
+    pub test_guild: Option<GuildId>,
+    /// The database schema every table lives in, so environments can share a database.
+    pub schema: String,
+    pub log_filter: String,
+    /// How long data is kept for a guild the bot was removed from.
+    pub detached_grace: chrono::Duration,
+    /// Where alerts and reports for the bot's owners go. They're DMed when this isn't set.
+    pub owner_channel: Option<ChannelId>,
+    /// Where forecasts for outdoor events come from, as a URL with `{location}` and `{date}` in
+    /// it. Outdoor events go without when this isn't set.
+    pub weather_api: Option<String>,
+    /// Where links in watched channels are checked, as a URL with `{url}` or `{domain}` in it.
+    /// Only the guilds' own deny-lists are used when this isn't set.
+    pub link_reputation_api: Option<String>,
+}
+
+impl Config {
+    pub fn from_secrets(secrets: &SecretStore) -> Result<Self, anyhow::Error> {
+        let environment = match secrets.get("ENVIRONMENT") {
+            Some(environment) => environment.parse()?,
+            None => Environment::Prod,
+        };
+
+        let test_guild = match secrets.get("TEST_GUILD_ID") {
+            Some(id) => Some(
+                id.trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|id| *id != 0)
+                    .map(GuildId::new)
+                    .ok_or_else(|| anyhow!("'TEST_GUILD_ID' is not a server ID"))?,
+            ),
+            None => None,
+        };
+        if environment != Environment::Prod && test_guild.is_none() {
+            bail!("'TEST_GUILD_ID' is required outside production");
+        }
+
+        let schema = secrets
+            .get("DB_SCHEMA")
+            .unwrap_or_else(|| environment.default_schema().to_string());
+        // It ends up in SQL unquoted, so only plain identifiers will do.
+        let valid = schema.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+            && schema
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            bail!("'DB_SCHEMA' must be lowercase letters, digits and underscores");
+        }
+
+        let owner_channel = match secrets.get("OWNER_CHANNEL_ID") {
+            Some(id) => Some(
+                id.trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|id| *id != 0)
+                    .map(ChannelId::new)
+                    .ok_or_else(|| anyhow!("'OWNER_CHANNEL_ID' is not a channel ID"))?,
+            ),
+            None => None,
+        };
+
+        let weather_api = secrets.get("WEATHER_API_URL");
+        if let Some(url) = &weather_api {
+            if !url.starts_with("https://") {
+                bail!("'WEATHER_API_URL' must be an https:// URL");
+            }
+            templates::validate(url, weather::URL_VARIABLES)
+                .map_err(|e| anyhow!("'WEATHER_API_URL' is not a valid template: {e}"))?;
+        }
+
+        let link_reputation_api = secrets.get("LINK_REPUTATION_API_URL");
+        if let Some(url) = &link_reputation_api {
+            if !url.starts_with("https://") {
+                bail!("'LINK_REPUTATION_API_URL' must be an https:// URL");
+            }
+            templates::validate(url, links::URL_VARIABLES)
+                .map_err(|e| anyhow!("'LINK_REPUTATION_API_URL' is not a valid template: {e}"))?;
+        }
+
+        let detached_grace = secrets
+            .get("DETACHED_GRACE_DAYS")
+            .and_then(|days| days.trim().parse::<i64>().ok())
+            .filter(|days| *days >= 0)
+            .unwrap_or(DEFAULT_GRACE_DAYS);
+
+        Ok(Self {
+            environment,
+            test_guild,
+            schema,
+            log_filter: secrets
+                .get("LOG_FILTER")
+                .unwrap_or_else(|| environment.default_log_filter().to_string()),
+            detached_grace: chrono::Duration::days(detached_grace),
+            owner_channel,
+            weather_api,
+            link_reputation_api,
+        })
+    }
+
+    /// Installs the log subscriber. `RUST_LOG` still wins, for local runs.
+    pub fn init_tracing(&self) -> Result<(), anyhow::Error> {
+        let filter =
+            EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(&self.log_filter))?;
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().without_time())
+            .with(filter)
+            .try_init()?;
+        Ok(())
+    }
+
+    /// Points `pool` at this environment's schema, creating it if needed. Every connection has
+    /// the schema first on its search path, so queries and migrations need no changes.
+    pub async fn connect(&self, pool: PgPool) -> Result<PgPool, sqlx::Error> {
+        if self.schema == "public" {
+            return Ok(pool);
+        }
+
+        sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS {}", self.schema))
+            .execute(&pool)
+            .await?;
+        let options = (*pool.connect_options())
+            .clone()
+            .options([("search_path", &self.schema)]);
+        let scoped = PgPoolOptions::new().connect_with(options).await?;
+        pool.close().await;
+
+        Ok(scoped)
+    }
+
+    /// Registers the bot's commands globally in production, and only in the test guild anywhere
+    /// else.
+    pub async fn register_commands(
+        &self,
+        ctx: &SerenityContext,
+        commands: Vec<CreateCommand>,
+    ) -> Result<(), SerenityError> {
+        match self.test_guild {
+            Some(guild_id) if self.environment != Environment::Prod => {
+                guild_id.set_commands(ctx, commands).await?;
+            }
+            _ => {
+                Command::set_global_commands(ctx, commands).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn secrets(pairs: &[(&str, &str)]) -> SecretStore {
+        SecretStore::new(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string().into()))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn production_is_the_default() {
+        let config = Config::from_secrets(&secrets(&[])).unwrap();
+        assert_eq!(config.environment, Environment::Prod);
+        assert_eq!(config.schema, "public");
+        assert_eq!(config.log_filter, "info");
+        assert_eq!(
+            config.detached_grace,
+            chrono::Duration::days(DEFAULT_GRACE_DAYS)
+        );
+    }
+
+    #[test]
+    fn other_environments_need_a_test_guild() {
+        assert!(Config::from_secrets(&secrets(&[("ENVIRONMENT", "staging")])).is_err());
+
+        let config = Config::from_secrets(&secrets(&[
+            ("ENVIRONMENT", " Development "),
+            ("TEST_GUILD_ID", "123"),
+        ]))
+        .unwrap();
+        assert_eq!(config.environment, Environment::Dev);
+        assert_eq!(config.test_guild, Some(GuildId::new(123)));
+        assert_eq!(config.schema, "dev");
+    }
+
+    #[test]
+    fn bad_secrets_are_refused() {
+        for bad in [
+            [("ENVIRONMENT", "qa")],
+            [("TEST_GUILD_ID", "0")],
+            [("DB_SCHEMA", "public; DROP TABLE events")],
+            [("DB_SCHEMA", "1st")],
+            [("OWNER_CHANNEL_ID", "general")],
+            [("WEATHER_API_URL", "http://weather.example/{location}")],
+        ] {
+            assert!(Config::from_secrets(&secrets(&bad)).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+/// What a component belongs to, which decides who handles presses of it. It leads the
+/// component's custom ID, spelled the way posts already out there have it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    EventRsvp,
+    EventQueue,
+    EventSlot,
+    EventItem,
+    EventSync,
+    Lfg,
+    Tournament,
+    Raffle,
+    /// The lockdown and dismiss buttons on a raid alert.
+    Raid,
+    /// The buttons of the verification gate: the one on its post and those of the challenge.
+    Verify,
+    /// The button on an offer to appeal a timeout, and those deciding the appeal.
+    Appeal,
+    /// The fix buttons on a role drift report.
+    Drift,
+    /// The yes and no buttons under a confirmation prompt.
+    Confirm,
+    /// The previous and next buttons on a paged reply.
+    Page,
+    /// The button that opens a form again after what was typed in it didn't check out.
+    Form,
+    /// The buttons that step through `/event wizard`.
+    Wizard,
+    /// The publish, edit and discard buttons under a post's preview.
+    Preview,
+    /// The channel picker and buttons of a message being moved or copied.
+    Move,
+}
+
+impl Kind {
+    const ALL: [Self; 18] = [
+        Self::EventRsvp,
+        Self::EventQueue,
+        Self::EventSlot,
+        Self::EventItem,
+        Self::EventSync,
+        Self::Lfg,
+        Self::Tournament,
+        Self::Raffle,
+        Self::Raid,
+        Self::Verify,
+        Self::Appeal,
+        Self::Drift,
+        Self::Confirm,
+        Self::Page,
+        Self::Form,
+        Self::Wizard,
+        Self::Preview,
+        Self::Move,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::EventRsvp => "event-rsvp",
+            Self::EventQueue => "event-queue",
+            Self::EventSlot => "event-slot",
+            Self::EventItem => "event-item",
+            Self::EventSync => "event-sync",
+            Self::Lfg => "lfg",
+            Self::Tournament => "tournament",
+            Self::Raffle => "raffle",
+            Self::Raid => "raid",
+            Self::Verify => "verify",
+            Self::Appeal => "appeal",
+            Self::Drift => "drift",
+            Self::Confirm => "confirm",
+            Self::Page => "page",
+            Self::Form => "form",
+            Self::Wizard => "wizard",
+            Self::Preview => "preview",
+            Self::Move => "move",
+        }
+    }
+
+    /// The kind `custom_id` says it is, whether or not the rest of it makes sense.
+    pub fn of(custom_id: &str) -> Option<Self> {
+        let key = custom_id.split(':').next()?;
+        Self::ALL.into_iter().find(|kind| kind.key() == key)
+    }
+
+    /// Whether presses are collected by the command that sent the component, rather than handled
+    /// wherever they come in.
+    pub fn is_collected(self) -> bool {
+        matches!(
+            self,
+            Self::Confirm | Self::Page | Self::Form | Self::Wizard | Self::Preview | Self::Move
+        )
+    }
+}
+
+/// A component's custom ID: `kind:action:id`, with `:nonce` after when there is one. The nonce
+/// tells apart sendings of the same component, like the prompts from two runs of one command,
+/// so a press on an old one doesn't count for the new.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomId {
+    pub kind: Kind,
+    pub action: String,
+    /// What the component acts on, like an event or a tournament match.
+    pub id: i64,
+    pub nonce: Option<u64>,
+}
+
+impl CustomId {
+    pub fn new(kind: Kind, action: &str, id: i64) -> Self {
+        Self {
+            kind,
+            action: action.to_string(),
+            id,
+            nonce: None,
+        }
+    }
+
+    /// A component on the reply to one run of a command, which that run collects presses of.
+    pub fn for_invocation(kind: Kind, action: &str, invocation: u64) -> Self {
+        Self::new(kind, action, 0).nonce(invocation)
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Reads a custom ID the bot made. Select menus used to leave out the action, as
+    /// `kind:id`, and still parse with an empty one.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let kind = Kind::of(custom_id)?;
+        let parts = custom_id.split(':').skip(1).collect::<Vec<_>>();
+        let (action, id, nonce) = match parts[..] {
+            [id] => ("", id, None),
+            [action, id] => (action, id, None),
+            [action, id, nonce] => (action, id, Some(nonce.parse().ok()?)),
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            action: action.to_string(),
+            id: id.parse().ok()?,
+            nonce,
+        })
+    }
+}
+
+impl fmt::Display for CustomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.kind.key(), self.action, self.id)?;
+        if let Some(nonce) = self.nonce {
+            write!(f, ":{nonce}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<CustomId> for String {
+    fn from(custom_id: CustomId) -> Self {
+        custom_id.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_ids_read_back_as_written() {
+        let rsvp = CustomId::new(Kind::EventRsvp, "join", 42);
+        assert_eq!(rsvp.to_string(), "event-rsvp:join:42");
+        assert_eq!(CustomId::parse("event-rsvp:join:42"), Some(rsvp));
+
+        let yes = CustomId::for_invocation(Kind::Confirm, "yes", 1234);
+        assert_eq!(yes.to_string(), "confirm:yes:0:1234");
+        assert_eq!(CustomId::parse(&yes.to_string()), Some(yes));
+
+        let legacy = CustomId::parse("event-slot:7").unwrap();
+        assert_eq!(
+            (legacy.kind, legacy.action.as_str(), legacy.id),
+            (Kind::EventSlot, "", 7)
+        );
+
+        assert_eq!(CustomId::parse("event-rsvp:join"), None);
+        assert_eq!(CustomId::parse("event-rsvp:join:x"), None);
+        assert_eq!(CustomId::parse("42-yes"), None);
+        assert_eq!(Kind::of("event-rsvp:join:x"), Some(Kind::EventRsvp));
+        assert_eq!(Kind::of("yes_disabled"), None);
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{
+    audit::{self, AuditEntry},
+    util::{confirm, http_status},
+    Context, Data, SlimeError,
+};
+
+/// Tables keyed by guild, deleted from in this order when a guild is purged. Everything else
+/// hangs off one of these and goes with it.
+const GUILD_TABLES: [&str; 50] = [
+    "bot_posts",
+    "channel_schedules",
+    "lfg_queue",
+    "lfg_groups",
+    "events",
+    "streak_badges",
+    "role_snapshots",
+    "role_invariants",
+    "role_rotations",
+    "permission_templates",
+    "tournaments",
+    "points_ledger",
+    "points_seasons",
+    "raffles",
+    "faq_triggers",
+    "faq_channels",
+    "tags",
+    "tag_subscriptions",
+    "macros",
+    "event_templates",
+    "hosting_handbook_shown",
+    "scheduled_announcements",
+    // Before the rules, which the archived threads point at.
+    "thread_janitor_archived",
+    "thread_janitor_rules",
+    "help_questions",
+    "help_channels",
+    "keyword_watches",
+    "keyword_watch_mutes",
+    "link_channels",
+    "link_rules",
+    "retention_policies",
+    "invite_exemptions",
+    "raid_joins",
+    "raid_incidents",
+    "verification_questions",
+    "verification_pending",
+    "timeout_appeals",
+    "channel_activity",
+    "member_activity",
+    "milestones",
+    "command_metrics",
+    "guild_quotas",
+    "notification_runs",
+    "notification_templates",
+    "digest_queue",
+    "other_event_bots",
+    "relay_webhooks",
+    "guild_settings",
+    "detached_guilds",
+    // Written by the bot itself rather than by members, but still about the guild.
+    "audit_log",
+];
+
+/// Marks a guild's data as detached after the bot was removed from it. Its scheduled work stops
+/// and the data is deleted once the grace period is over.
+pub async fn detach(pool: &PgPool, guild_id: GuildId) -> Result<(), SlimeError> {
+    sqlx::query("INSERT INTO detached_guilds (guild_id) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(guild_id.get() as i64)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Picks a guild's data back up after the bot rejoined it. Returns whether it was detached.
+pub async fn reattach(pool: &PgPool, guild_id: GuildId) -> Result<bool, SlimeError> {
+    Ok(
+        sqlx::query("DELETE FROM detached_guilds WHERE guild_id = $1")
+            .bind(guild_id.get() as i64)
+            .execute(pool)
+            .await?
+            .rows_affected()
+            > 0,
+    )
+}
+
+/// Deletes everything stored about a guild. Returns how many rows went, not counting the ones
+/// removed along with them.
+pub async fn purge(pool: &PgPool, guild_id: GuildId) -> Result<u64, SlimeError> {
+    let guild = guild_id.get() as i64;
+    let mut tx = pool.begin().await?;
+
+    // The only table that refers to events without going with them.
+    let mut deleted = sqlx::query(
+        "DELETE FROM event_channels
+         WHERE event_id IN (SELECT id FROM events WHERE guild_id = $1)",
+    )
+    .bind(guild)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    for table in GUILD_TABLES {
+        deleted += sqlx::query(&format!("DELETE FROM {table} WHERE guild_id = $1"))
+            .bind(guild)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    tx.commit().await?;
+
+    Ok(deleted)
+}
+
+/// Purges `guild_id` and records it, keeping the record outside the guild so it survives.
+async fn purge_and_record(
+    pool: &PgPool,
+    actor: UserId,
+    guild_id: GuildId,
+    reason: &str,
+) -> Result<u64, SlimeError> {
+    let deleted = purge(pool, guild_id).await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: None,
+            actor,
+            action: "purge_guild",
+            target: Some(guild_id.get()),
+            details: format!("{reason}, {deleted} row(s)"),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    Ok(deleted)
+}
+
+/// Purges every guild whose grace period is over. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let expired = sqlx::query_scalar::<_, i64>(
+        "SELECT guild_id FROM detached_guilds WHERE detached_at <= $1",
+    )
+    .bind(now - data.config.detached_grace)
+    .fetch_all(pool)
+    .await?;
+
+    let bot = ctx.cache.current_user().id;
+    for guild in expired {
+        let guild_id = GuildId::new(guild as u64);
+        // A rejoin while no instance was active goes unnoticed, so check before deleting.
+        match guild_id.to_partial_guild(ctx).await {
+            Ok(_) => {
+                reattach(pool, guild_id).await?;
+                info!("Guild {} is back, keeping its data", guild_id);
+                continue;
+            }
+            Err(e) if matches!(http_status(&e), Some(403 | 404)) => {}
+            Err(e) => {
+                error!("Could not check on detached guild {}: {}", guild_id, e);
+                continue;
+            }
+        }
+
+        match purge_and_record(pool, bot, guild_id, "grace period over").await {
+            Ok(deleted) => info!("Purged {} row(s) of detached guild {}", deleted, guild_id),
+            Err(e) => error!("Could not purge detached guild {}: {}", guild_id, e),
+        }
+    }
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Delete everything stored about a server the bot was removed from, without waiting.
+#[poise::command(slash_command, owners_only, rename = "purge-guild")]
+pub async fn purge_guild_command(
+    ctx: Context<'_>,
+    #[description = "ID of the server"] guild: String,
+) -> Result<(), SlimeError> {
+    let guild_id = guild
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(GuildId::new)
+        .ok_or_else(|| SlimeError::InvalidNumber(guild.clone()))?;
+    let pool = &ctx.data().pool;
+
+    let detached_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+        "SELECT detached_at FROM detached_guilds WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_optional(pool)
+    .await?;
+    let Some(detached_at) = detached_at else {
+        return reply(
+            ctx,
+            "The bot hasn't been removed from that server, so its data is still in use.",
+        )
+        .await;
+    };
+
+    let due = detached_at + ctx.data().config.detached_grace;
+    if !confirm(
+        ctx,
+        &format!(
+            "This deletes all data for server {guild_id} now, instead of {}. It can't be \
+             recovered if the bot is added back. Continue?",
+            FormattedTimestamp::new(due.into(), Some(FormattedTimestampStyle::RelativeTime))
+        ),
+    )
+    .await?
+    {
+        return reply(ctx, "Nothing was deleted.").await;
+    }
+
+    let deleted = purge_and_record(pool, ctx.author().id, guild_id, "purged by owner").await?;
+    reply(
+        ctx,
+        format!("Deleted {deleted} row(s) for server {guild_id}."),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_guild_table_is_purged_once_and_in_order() {
+        let mut tables = GUILD_TABLES.to_vec();
+        tables.sort_unstable();
+        tables.dedup();
+        assert_eq!(tables.len(), GUILD_TABLES.len());
+
+        // Rows that point at another guild table have to go first.
+        let position = |table| GUILD_TABLES.iter().position(|t| *t == table).unwrap();
+        assert!(position("thread_janitor_archived") < position("thread_janitor_rules"));
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Timelike, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    events::Event,
+    notify::{self, DmFailure, NotificationKind, Outcome},
+    quiet::{self, QuietHours},
+    util::send_dm,
+    Data, SlimeError,
+};
+
+/// How far ahead a digest looks for events, and how long it covers their reminders for.
+const COVERS_HOURS: i64 = 24;
+
+/// Discord caps messages at 2000 characters.
+const MAX_LENGTH: usize = 2000;
+
+/// Which of `users` have asked for a daily digest.
+pub async fn wanted_by(pool: &PgPool, users: &[UserId]) -> Result<HashSet<UserId>, SlimeError> {
+    let ids = users.iter().map(|u| u.get() as i64).collect::<Vec<_>>();
+    let wanted = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM user_preferences WHERE digest AND user_id = ANY($1)",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(wanted
+        .into_iter()
+        .map(|id| UserId::new(id as u64))
+        .collect())
+}
+
+/// Which of `users` already saw `event` in a digest sent after they signed up, so don't need
+/// reminding about it separately.
+pub async fn covered(
+    pool: &PgPool,
+    event: &Event,
+    users: &[UserId],
+) -> Result<HashSet<UserId>, SlimeError> {
+    let ids = users.iter().map(|u| u.get() as i64).collect::<Vec<_>>();
+    let covered = sqlx::query_scalar::<_, i64>(
+        "SELECT p.user_id FROM user_preferences p
+         JOIN event_rsvps r ON r.user_id = p.user_id AND r.event_id = $1
+         WHERE p.digest AND p.user_id = ANY($2)
+            AND p.last_digest_at >= r.created_at
+            AND p.last_digest_at >= $3 - make_interval(hours => $4)",
+    )
+    .bind(event.id)
+    .bind(&ids)
+    .bind(event.starts_at)
+    .bind(COVERS_HOURS as i32)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(covered
+        .into_iter()
+        .map(|id| UserId::new(id as u64))
+        .collect())
+}
+
+/// Holds a notification back for `user`'s next digest.
+pub async fn hold(
+    pool: &PgPool,
+    user: UserId,
+    guild_id: GuildId,
+    kind: NotificationKind,
+    content: &str,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO digest_queue (user_id, guild_id, kind, content) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(kind)
+    .bind(content)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Holds a notification back for whichever of `users` shouldn't get it right now: those who
+/// want a digest, and those in quiet hours. Urgent kinds are never held. Returns who was held,
+/// and why.
+pub async fn hold_back(
+    pool: &PgPool,
+    users: &[UserId],
+    guild_id: GuildId,
+    kind: NotificationKind,
+    content: &str,
+    now: DateTime<Utc>,
+) -> Result<HashMap<UserId, Outcome>, SlimeError> {
+    let mut held = HashMap::new();
+    if !kind.can_wait() || users.is_empty() {
+        return Ok(held);
+    }
+
+    for user in wanted_by(pool, users).await? {
+        held.insert(user, Outcome::Digested);
+    }
+    for user in quiet::quiet_now(pool, guild_id, users, now).await? {
+        held.entry(user).or_insert(Outcome::Held);
+    }
+    for user in held.keys() {
+        hold(pool, *user, guild_id, kind, content).await?;
+    }
+
+    Ok(held)
+}
+
+/// Whether a digest sent at `hour` is due at `now`, given when the last one went out. A digest
+/// held back by quiet hours is still due once they end, but never more than one a day.
+fn digest_due(hour: i16, last: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    let mut latest = now
+        .with_hour(hour as u32)
+        .and_then(|at| at.with_minute(0))
+        .and_then(|at| at.with_second(0))
+        .and_then(|at| at.with_nanosecond(0))
+        .unwrap_or(now);
+    if latest > now {
+        latest -= Duration::days(1);
+    }
+    last.is_none_or(|last| last < latest && now - last >= Duration::hours(23))
+}
+
+/// Puts a digest together from reminders for the upcoming events and the held-back updates.
+fn compose(intro: &str, reminders: &[String], updates: &[String]) -> String {
+    let mut digest = intro.to_string();
+    for (heading, lines) in [("Coming up", reminders), ("Updates", updates)] {
+        if lines.is_empty() {
+            continue;
+        }
+        digest.push_str(&format!("\n\n**{heading}**"));
+        for line in lines {
+            digest.push_str(&format!("\n- {line}"));
+        }
+    }
+
+    match digest.char_indices().nth(MAX_LENGTH - 1) {
+        Some((end, _)) => format!("{}…", &digest[..end]),
+        None => digest,
+    }
+}
+
+/// The member's confirmed events starting within the next day, ready to list in a digest.
+async fn upcoming(
+    pool: &PgPool,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<Vec<String>, SlimeError> {
+    let events = sqlx::query_as::<_, Event>(
+        "SELECT e.* FROM events e
+         JOIN event_rsvps r ON r.event_id = e.id AND r.user_id = $1 AND r.state = 'confirmed'
+         WHERE e.status = 'published' AND e.starts_at > $2
+            AND e.starts_at <= $2 + make_interval(hours => $3)
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         ORDER BY e.starts_at",
+    )
+    .bind(user.get() as i64)
+    .bind(now)
+    .bind(COVERS_HOURS as i32)
+    .fetch_all(pool)
+    .await?;
+
+    let mut reminders = Vec::new();
+    for event in &events {
+        reminders.push(notify::event_message(pool, NotificationKind::Reminder, event, None).await?);
+    }
+    Ok(reminders)
+}
+
+/// Sends one member `reminders` and the `held` updates in a single DM, then clears the updates
+/// from the queue. Does nothing if there's nothing to send.
+async fn send(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    user: UserId,
+    intro: &str,
+    reminders: &[String],
+    held: &[(i64, String)],
+) -> Result<(), SlimeError> {
+    if reminders.is_empty() && held.is_empty() {
+        return Ok(());
+    }
+
+    let updates = held.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>();
+    let sent = send_dm(
+        ctx,
+        user,
+        CreateMessage::new().content(compose(intro, reminders, &updates)),
+    )
+    .await;
+
+    // Held updates are kept for next time only if Discord might take them then.
+    if let Err(e) = sent {
+        error!("Could not send digest to {}: {}", user, e);
+        if let DmFailure::Transient(_) = DmFailure::from(e) {
+            return Ok(());
+        }
+    }
+    let ids = held.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    sqlx::query("DELETE FROM digest_queue WHERE id = ANY($1)")
+        .bind(&ids)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Sends one member's digest, with their events for the coming day and everything held for them.
+async fn send_digest(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let reminders = upcoming(pool, user, now).await?;
+    let held = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, content FROM digest_queue WHERE user_id = $1 ORDER BY created_at, id",
+    )
+    .bind(user.get() as i64)
+    .fetch_all(pool)
+    .await?;
+
+    send(ctx, pool, user, "Here's your day.", &reminders, &held).await
+}
+
+/// Sends the digests that are due, and passes on anything held for members who don't get one
+/// once neither they nor the guild it came from are in quiet hours. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let hour = now.hour();
+
+    let digests = sqlx::query_as::<_, (i64, i16, Option<DateTime<Utc>>, Option<i16>, Option<i16>)>(
+        "SELECT user_id, digest_hour, last_digest_at, quiet_start, quiet_end
+         FROM user_preferences WHERE digest",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (user, digest_hour, last, start, end) in digests {
+        if !digest_due(digest_hour, last, now)
+            || quiet::is_quiet(&[QuietHours::from_columns(start, end)], hour)
+        {
+            continue;
+        }
+        // Claimed before sending, so another instance's tick can't send it too.
+        let claimed = sqlx::query(
+            "UPDATE user_preferences SET last_digest_at = $2
+             WHERE user_id = $1 AND last_digest_at IS NOT DISTINCT FROM $3",
+        )
+        .bind(user)
+        .bind(now)
+        .bind(last)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            continue;
+        }
+
+        data.calls.turn().await;
+        if let Err(e) = send_digest(ctx, pool, UserId::new(user as u64), now).await {
+            error!("Could not put together digest for {}: {}", user, e);
+        }
+    }
+
+    let queued = sqlx::query_as::<
+        _,
+        (
+            i64,
+            i64,
+            String,
+            Option<i16>,
+            Option<i16>,
+            Option<i16>,
+            Option<i16>,
+        ),
+    >(
+        "SELECT q.id, q.user_id, q.content,
+            g.quiet_start, g.quiet_end, p.quiet_start, p.quiet_end
+         FROM digest_queue q
+         LEFT JOIN user_preferences p ON p.user_id = q.user_id
+         LEFT JOIN guild_settings g ON g.guild_id = q.guild_id
+         WHERE NOT COALESCE(p.digest, false)
+         ORDER BY q.created_at, q.id",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut missed = HashMap::<i64, Vec<(i64, String)>>::new();
+    for (id, user, content, guild_start, guild_end, start, end) in queued {
+        let hours = [
+            QuietHours::from_columns(guild_start, guild_end),
+            QuietHours::from_columns(start, end),
+        ];
+        if !quiet::is_quiet(&hours, hour) {
+            missed.entry(user).or_default().push((id, content));
+        }
+    }
+    for (user, held) in missed {
+        data.calls.turn().await;
+        let intro = "Here's what you missed.";
+        if let Err(e) = send(ctx, pool, UserId::new(user as u64), intro, &[], &held).await {
+            error!("Could not pass on held updates to {}: {}", user, e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn digests_list_reminders_then_updates() {
+        let digest = compose(
+            "Here's your day.",
+            &["**Game night** starts soon.".to_string()],
+            &["**Raid** has been cancelled.".to_string()],
+        );
+        assert_eq!(
+            digest,
+            "Here's your day.\n\n**Coming up**\n- **Game night** starts soon.\
+             \n\n**Updates**\n- **Raid** has been cancelled."
+        );
+    }
+
+    #[test]
+    fn long_digests_are_cut_to_fit() {
+        let updates = vec!["x".repeat(500); 10];
+        assert_eq!(
+            compose("Here's your day.", &[], &updates).chars().count(),
+            MAX_LENGTH
+        );
+    }
+
+    #[test]
+    fn digests_held_by_quiet_hours_go_out_once_they_end() {
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap();
+        let sent_yesterday = Some(at(1, 9) + Duration::seconds(30));
+
+        assert!(!digest_due(9, sent_yesterday, at(2, 8)));
+        assert!(digest_due(9, sent_yesterday, at(2, 9)));
+        // Quiet until noon, so it goes out then instead.
+        assert!(digest_due(9, sent_yesterday, at(2, 12)));
+        assert!(!digest_due(9, Some(at(2, 12)), at(2, 13)));
+        // Moving the hour later doesn't send a second one the same day.
+        assert!(!digest_due(15, Some(at(2, 9)), at(2, 15)));
+        assert!(digest_due(15, Some(at(2, 9)), at(3, 15)));
+        assert!(digest_due(9, None, at(2, 17)));
+    }
+}
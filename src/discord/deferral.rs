@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use poise::serenity_prelude::*;
+use serenity::{client::Context as SerenityContext, json, Error as SerenityError};
+use tokio::{sync::Mutex as AsyncMutex, time::Instant};
+use tracing::{error, warn};
+
+use super::Discord;
+
+/// How long a component handler has to answer before its interaction is deferred for it. Discord
+/// shows "This interaction failed" after three seconds, and the defer itself has to get there.
+const DEFER_AFTER: Duration = Duration::from_millis(2500);
+
+/// How long a component handler gets to answer before it's abandoned. One that has answered is
+/// left to finish, since it may be waiting on the user, like a form being filled in.
+const GIVE_UP_AFTER: Duration = Duration::from_secs(60);
+
+const WORKING: &str = "Working on it…";
+const GAVE_UP: &str = "That took too long, so I stopped. Please try again in a bit.";
+
+/// Where an interaction being handled has got to.
+enum State {
+    Waiting,
+    /// The handler answered it, itself or through [`respond`] after it was deferred.
+    Answered,
+    /// It was deferred, with a "working on it" note to the user that the answer replaces, if
+    /// that could be sent.
+    Deferred {
+        notice: Option<MessageId>,
+    },
+}
+
+type States = Mutex<HashMap<InteractionId, Arc<AsyncMutex<State>>>>;
+
+/// Interactions being handled by [`guard`]. It's global rather than in [`crate::Data`] so
+/// [`respond`] can be called anywhere a handler has a Serenity context to answer with.
+fn states() -> &'static States {
+    static STATES: OnceLock<States> = OnceLock::new();
+    STATES.get_or_init(Default::default)
+}
+
+fn state(id: InteractionId) -> Option<Arc<AsyncMutex<State>>> {
+    states().lock().unwrap().get(&id).cloned()
+}
+
+/// The parts of a pressed component's interaction that answering it takes.
+#[derive(Clone, Copy)]
+struct Pressed<'a> {
+    id: InteractionId,
+    token: &'a str,
+    custom_id: &'a str,
+}
+
+impl<'a> From<&'a ComponentInteraction> for Pressed<'a> {
+    fn from(interaction: &'a ComponentInteraction) -> Self {
+        Self {
+            id: interaction.id,
+            token: &interaction.token,
+            custom_id: &interaction.data.custom_id,
+        }
+    }
+}
+
+/// How a guarded handler went.
+pub struct Handled<T> {
+    /// What the handler returned, or `None` if it was abandoned.
+    pub result: Option<T>,
+    /// Whether it ran long enough to be deferred.
+    pub deferred: bool,
+    pub elapsed: Duration,
+}
+
+/// Runs `handler` for `interaction`, deferring the interaction if the handler hasn't answered it
+/// in time, so the user gets "working on it" instead of a failure. Handlers must answer through
+/// [`respond`] for this to work. One that hasn't answered after a minute is abandoned and the user
+/// told.
+pub async fn guard<T>(
+    ctx: &SerenityContext,
+    interaction: &ComponentInteraction,
+    handler: impl Future<Output = T>,
+) -> Handled<T> {
+    guard_pressed(ctx, interaction.into(), handler).await
+}
+
+async fn guard_pressed<T>(
+    discord: &impl Discord,
+    pressed: Pressed<'_>,
+    handler: impl Future<Output = T>,
+) -> Handled<T> {
+    let started = Instant::now();
+    let state = Arc::new(AsyncMutex::new(State::Waiting));
+    states().lock().unwrap().insert(pressed.id, state.clone());
+
+    tokio::pin!(handler);
+    let mut deferred = false;
+    let mut result = tokio::select! {
+        result = &mut handler => Some(result),
+        _ = tokio::time::sleep(DEFER_AFTER) => {
+            deferred = defer(discord, pressed, &state).await;
+            None
+        }
+    };
+    if result.is_none() {
+        let left = GIVE_UP_AFTER.saturating_sub(started.elapsed());
+        result = tokio::time::timeout(left, &mut handler).await.ok();
+    }
+    if result.is_none() && matches!(*state.lock().await, State::Answered) {
+        result = Some(handler.await);
+    }
+
+    states().lock().unwrap().remove(&pressed.id);
+    let notice = match &*state.lock().await {
+        State::Deferred { notice } => *notice,
+        _ => None,
+    };
+    match (&result, notice) {
+        (None, _) => {
+            warn!(
+                "Gave up on interaction {} ({}) after {:?}",
+                pressed.id, pressed.custom_id, GIVE_UP_AFTER
+            );
+            let told = if deferred {
+                tell(discord, pressed, notice, GAVE_UP).await.map(drop)
+            } else {
+                discord
+                    .create_response(
+                        pressed.id,
+                        pressed.token,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(GAVE_UP)
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await
+            };
+            if let Err(e) = told {
+                error!(
+                    "Could not tell the user interaction {} failed: {}",
+                    pressed.id, e
+                );
+            }
+        }
+        // The handler finished without answering, so the note would say "working on it" forever.
+        (Some(_), Some(notice)) => {
+            let _ = discord.delete_followup(pressed.token, notice).await;
+        }
+        (Some(_), None) => {}
+    }
+
+    Handled {
+        result,
+        deferred,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Sends the user an ephemeral `content` after the interaction was deferred, in place of the
+/// follow-up `replacing` if there is one. Returns the follow-up's ID.
+async fn tell(
+    discord: &impl Discord,
+    pressed: Pressed<'_>,
+    replacing: Option<MessageId>,
+    content: &str,
+) -> Result<MessageId, SerenityError> {
+    let message = json::to_value(
+        CreateInteractionResponseFollowup::new()
+            .content(content)
+            .ephemeral(true),
+    )?;
+    discord.followup(pressed.token, replacing, &message).await
+}
+
+/// Defers the interaction unless its handler answered it first. Returns whether it was deferred.
+async fn defer(discord: &impl Discord, pressed: Pressed<'_>, state: &AsyncMutex<State>) -> bool {
+    let mut state = state.lock().await;
+    if !matches!(*state, State::Waiting) {
+        return false;
+    }
+    // A deferred update leaves the answer free to be an edit of the pressed message or a new one.
+    if let Err(e) = discord
+        .create_response(
+            pressed.id,
+            pressed.token,
+            CreateInteractionResponse::Acknowledge,
+        )
+        .await
+    {
+        error!("Could not defer interaction {}: {}", pressed.id, e);
+        return false;
+    }
+    let notice = tell(discord, pressed, None, WORKING)
+        .await
+        .inspect_err(|e| error!("Could not say interaction {} is slow: {}", pressed.id, e))
+        .ok();
+    *state = State::Deferred { notice };
+    true
+}
+
+/// Answers `interaction` with `response`. If [`guard`] already deferred it, the answer goes out
+/// as what the response would have done: an edit of the pressed message, or a message in place
+/// of the "working on it" note. A modal can't follow a deferral, so the user is asked to try
+/// again instead and the handler gets an error.
+pub async fn respond(
+    ctx: &SerenityContext,
+    interaction: &ComponentInteraction,
+    response: CreateInteractionResponse,
+) -> Result<(), SerenityError> {
+    respond_pressed(ctx, interaction.into(), response).await
+}
+
+async fn respond_pressed(
+    discord: &impl Discord,
+    pressed: Pressed<'_>,
+    response: CreateInteractionResponse,
+) -> Result<(), SerenityError> {
+    let Some(state) = state(pressed.id) else {
+        return discord
+            .create_response(pressed.id, pressed.token, response)
+            .await;
+    };
+    let mut state = state.lock().await;
+    let State::Deferred { notice } = *state else {
+        *state = State::Answered;
+        return discord
+            .create_response(pressed.id, pressed.token, response)
+            .await;
+    };
+
+    let token = pressed.token;
+    // Whatever the answer is, the note has served its purpose.
+    *state = State::Answered;
+    let retire = |notice: Option<MessageId>| async move {
+        if let Some(notice) = notice {
+            let _ = discord.delete_followup(token, notice).await;
+        }
+    };
+    match response {
+        CreateInteractionResponse::Message(message) => {
+            discord
+                .followup(token, notice, &json::to_value(message)?)
+                .await?;
+        }
+        CreateInteractionResponse::UpdateMessage(message) => {
+            discord
+                .edit_response(token, &json::to_value(message)?)
+                .await?;
+            retire(notice).await;
+        }
+        CreateInteractionResponse::Modal(_) => {
+            tell(
+                discord,
+                pressed,
+                notice,
+                "That took too long to open. Please press the button again.",
+            )
+            .await?;
+            *state = State::Deferred { notice: None };
+            // So the handler stops instead of waiting for a modal that isn't coming.
+            return Err(SerenityError::Other(
+                "a modal can't follow a deferred interaction",
+            ));
+        }
+        _ => retire(notice).await,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord::mock::{Call, MockDiscord};
+
+    /// Each test gets its own interaction, since the states are shared by every test.
+    fn pressed(id: u64) -> Pressed<'static> {
+        Pressed {
+            id: InteractionId::new(id),
+            token: "token",
+            custom_id: "test:press",
+        }
+    }
+
+    fn update(content: &str) -> CreateInteractionResponse {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new().content(content),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fast_handlers_are_not_deferred() {
+        let discord = MockDiscord::new();
+        let handled = guard_pressed(&discord, pressed(1), async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            7
+        })
+        .await;
+
+        assert_eq!(handled.result, Some(7));
+        assert!(!handled.deferred);
+        assert_eq!(handled.elapsed, Duration::from_secs(1));
+        assert!(discord.calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_handlers_are_deferred_and_answer_with_an_edit() {
+        let discord = MockDiscord::new();
+        let pressed = pressed(2);
+        let handled = guard_pressed(&discord, pressed, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            respond_pressed(&discord, pressed, update("Done")).await
+        })
+        .await;
+
+        assert!(matches!(handled.result, Some(Ok(()))));
+        assert!(handled.deferred);
+        let [notice] = discord.followups()[..] else {
+            panic!("expected one \"working on it\" note");
+        };
+        assert_eq!(
+            discord.calls(),
+            [
+                Call::Respond(pressed.id, None),
+                Call::Followup(None, Some(WORKING.to_string())),
+                Call::EditResponse(Some("Done".to_string())),
+                Call::DeleteFollowup(notice),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn handlers_that_answered_are_not_deferred() {
+        let discord = MockDiscord::new();
+        let pressed = pressed(3);
+        let handled = guard_pressed(&discord, pressed, async {
+            respond_pressed(&discord, pressed, update("Done")).await?;
+            // Still busy after answering, past the point a silent handler would be deferred.
+            tokio::time::sleep(DEFER_AFTER * 2).await;
+            Ok::<_, SerenityError>(())
+        })
+        .await;
+
+        assert!(matches!(handled.result, Some(Ok(()))));
+        assert!(!handled.deferred);
+        assert_eq!(
+            discord.calls(),
+            [Call::Respond(pressed.id, Some("Done".to_string()))]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! An in-memory stand-in for Discord, for tests and local runs with the `mock-discord` feature.
+#![cfg_attr(not(test), allow(dead_code))]
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::{json::Value, Error as SerenityError};
+
+use super::Discord;
+
+/// Milliseconds between the Unix epoch and Discord's, which snowflakes count from.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+/// A call the code under test made, in the order it was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Messages(ChannelId, Option<MessageId>, u8),
+    DeleteMessage(ChannelId, MessageId),
+    DeleteMessages(ChannelId, Vec<MessageId>),
+    AwaitButton(UserId, Vec<String>),
+    /// An interaction's answer, with its content if it has any.
+    Respond(InteractionId, Option<String>),
+    Followup(Option<MessageId>, Option<String>),
+    EditResponse(Option<String>),
+    DeleteFollowup(MessageId),
+}
+
+/// What a message being sent or edited says.
+fn content(message: &Value) -> Option<String> {
+    let message = message.get("data").unwrap_or(message);
+    message.get("content")?.as_str().map(str::to_string)
+}
+
+/// Channels full of messages plus a queue of button presses, enforcing the limits Discord does
+/// where the bot relies on them.
+#[derive(Default)]
+pub struct MockDiscord {
+    channels: Mutex<HashMap<ChannelId, BTreeMap<MessageId, Message>>>,
+    presses: Mutex<VecDeque<Option<String>>>,
+    calls: Mutex<Vec<Call>>,
+    followups: Mutex<Vec<MessageId>>,
+    sequence: Mutex<u64>,
+}
+
+impl MockDiscord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A message ID whose timestamp is `at`, like Discord would have given it.
+    fn snowflake(&self, at: DateTime<Utc>) -> MessageId {
+        let mut sequence = self.sequence.lock().unwrap();
+        *sequence = (*sequence + 1) % 4096;
+        let millis = (at.timestamp_millis() - DISCORD_EPOCH_MS) as u64;
+        MessageId::new((millis << 22) | *sequence)
+    }
+
+    /// Adds a message to `channel` as if it were posted at `at`.
+    pub fn post(&self, channel: ChannelId, at: DateTime<Utc>) -> MessageId {
+        let mut message = Message::default();
+        message.id = self.snowflake(at);
+        message.channel_id = channel;
+        let id = message.id;
+        self.channels
+            .lock()
+            .unwrap()
+            .entry(channel)
+            .or_default()
+            .insert(id, message);
+        id
+    }
+
+    /// Queues the next answer to a button prompt: the custom ID pressed, or `None` for a timeout.
+    pub fn press(&self, custom_id: Option<&str>) {
+        self.presses
+            .lock()
+            .unwrap()
+            .push_back(custom_id.map(str::to_string));
+    }
+
+    /// Every message still in `channel`, oldest first.
+    pub fn remaining(&self, channel: ChannelId) -> Vec<MessageId> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(&channel)
+            .map(|messages| messages.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every follow-up sent to an interaction, oldest first.
+    pub fn followups(&self) -> Vec<MessageId> {
+        self.followups.lock().unwrap().clone()
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: Call) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+impl Discord for MockDiscord {
+    async fn messages(
+        &self,
+        channel: ChannelId,
+        before: Option<MessageId>,
+        limit: u8,
+    ) -> Result<Vec<Message>, SerenityError> {
+        self.record(Call::Messages(channel, before, limit));
+        if !(1..=100).contains(&limit) {
+            return Err(SerenityError::Other("limit must be between 1 and 100"));
+        }
+
+        let channels = self.channels.lock().unwrap();
+        let Some(messages) = channels.get(&channel) else {
+            return Ok(Vec::new());
+        };
+        Ok(messages
+            .range(..before.unwrap_or(MessageId::new(u64::MAX)))
+            .rev()
+            .take(limit as usize)
+            .map(|(_, message)| message.clone())
+            .collect())
+    }
+
+    async fn delete_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+    ) -> Result<(), SerenityError> {
+        self.record(Call::DeleteMessage(channel, message));
+        self.channels
+            .lock()
+            .unwrap()
+            .get_mut(&channel)
+            .and_then(|messages| messages.remove(&message))
+            .map(|_| ())
+            .ok_or(SerenityError::Other("unknown message"))
+    }
+
+    async fn delete_messages(
+        &self,
+        channel: ChannelId,
+        messages: &[MessageId],
+    ) -> Result<(), SerenityError> {
+        self.record(Call::DeleteMessages(channel, messages.to_vec()));
+        if !(2..=100).contains(&messages.len()) {
+            return Err(SerenityError::Other(
+                "bulk deletes take between 2 and 100 messages",
+            ));
+        }
+        let oldest_allowed = (Utc::now() - chrono::Duration::days(14)).timestamp();
+        if messages
+            .iter()
+            .any(|id| id.created_at().unix_timestamp() < oldest_allowed)
+        {
+            return Err(SerenityError::Other(
+                "bulk deletes can't include messages older than two weeks",
+            ));
+        }
+
+        let mut channels = self.channels.lock().unwrap();
+        let stored = channels.entry(channel).or_default();
+        for id in messages {
+            stored.remove(id);
+        }
+        Ok(())
+    }
+
+    async fn await_button(
+        &self,
+        user: UserId,
+        custom_ids: &[String],
+        _timeout: Duration,
+        _response: CreateInteractionResponse,
+    ) -> Result<Option<String>, SerenityError> {
+        self.record(Call::AwaitButton(user, custom_ids.to_vec()));
+        let press = self.presses.lock().unwrap().pop_front().flatten();
+        // A press of anything else is ignored by the collector, which then times out.
+        Ok(press.filter(|id| custom_ids.contains(id)))
+    }
+    async fn create_response(
+        &self,
+        id: InteractionId,
+        _token: &str,
+        response: CreateInteractionResponse,
+    ) -> Result<(), SerenityError> {
+        let response = serenity::json::to_value(response)?;
+        self.record(Call::Respond(id, content(&response)));
+        Ok(())
+    }
+
+    async fn followup(
+        &self,
+        _token: &str,
+        replacing: Option<MessageId>,
+        message: &Value,
+    ) -> Result<MessageId, SerenityError> {
+        self.record(Call::Followup(replacing, content(message)));
+        if let Some(replacing) = replacing {
+            return Ok(replacing);
+        }
+        let id = self.snowflake(Utc::now());
+        self.followups.lock().unwrap().push(id);
+        Ok(id)
+    }
+
+    async fn edit_response(&self, _token: &str, message: &Value) -> Result<(), SerenityError> {
+        self.record(Call::EditResponse(content(message)));
+        Ok(())
+    }
+
+    async fn delete_followup(&self, _token: &str, message: MessageId) -> Result<(), SerenityError> {
+        self.record(Call::DeleteFollowup(message));
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::*;
+use serenity::{
+    builder::Builder, client::Context as SerenityContext, json::Value, Error as SerenityError,
+};
+
+mod deferral;
+#[cfg(any(test, feature = "mock-discord"))]
+pub mod mock;
+mod queue;
+
+pub use deferral::{guard, respond, Handled};
+pub use queue::{Batched, CallQueue};
+
+/// The Discord calls made by flows that are worth testing on their own, like purging,
+/// confirmation prompts and answering interactions in time. The bot runs them against [`SerenityContext`]; tests run them against
+/// [`mock::MockDiscord`], which keeps everything in memory.
+pub trait Discord {
+    /// Up to `limit` (at most 100) messages in `channel` older than `before`, newest first.
+    async fn messages(
+        &self,
+        channel: ChannelId,
+        before: Option<MessageId>,
+        limit: u8,
+    ) -> Result<Vec<Message>, SerenityError>;
+
+    async fn delete_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+    ) -> Result<(), SerenityError>;
+
+    /// Deletes 2 to 100 messages at once. Discord refuses any older than two weeks.
+    async fn delete_messages(
+        &self,
+        channel: ChannelId,
+        messages: &[MessageId],
+    ) -> Result<(), SerenityError>;
+
+    /// Waits up to `timeout` for `user` to press a button with one of `custom_ids`, answers the
+    /// press with `response`, and returns the ID that was pressed.
+    async fn await_button(
+        &self,
+        user: UserId,
+        custom_ids: &[String],
+        timeout: Duration,
+        response: CreateInteractionResponse,
+    ) -> Result<Option<String>, SerenityError>;
+
+    /// Answers interaction `id`. Discord only waits three seconds for this.
+    async fn create_response(
+        &self,
+        id: InteractionId,
+        token: &str,
+        response: CreateInteractionResponse,
+    ) -> Result<(), SerenityError>;
+
+    /// Sends `message` after an interaction has been answered, or puts it in place of the
+    /// follow-up `replacing`. Returns the follow-up's ID.
+    async fn followup(
+        &self,
+        token: &str,
+        replacing: Option<MessageId>,
+        message: &Value,
+    ) -> Result<MessageId, SerenityError>;
+
+    /// Replaces the message an interaction was answered with, or the pressed message if the
+    /// answer was deferred.
+    async fn edit_response(&self, token: &str, message: &Value) -> Result<(), SerenityError>;
+
+    async fn delete_followup(&self, token: &str, message: MessageId) -> Result<(), SerenityError>;
+}
+
+impl Discord for SerenityContext {
+    async fn messages(
+        &self,
+        channel: ChannelId,
+        before: Option<MessageId>,
+        limit: u8,
+    ) -> Result<Vec<Message>, SerenityError> {
+        let mut builder = GetMessages::new().limit(limit);
+        if let Some(before) = before {
+            builder = builder.before(before);
+        }
+        channel.messages(self, builder).await
+    }
+
+    async fn delete_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+    ) -> Result<(), SerenityError> {
+        channel.delete_message(self, message).await
+    }
+
+    async fn delete_messages(
+        &self,
+        channel: ChannelId,
+        messages: &[MessageId],
+    ) -> Result<(), SerenityError> {
+        channel.delete_messages(self, messages).await
+    }
+
+    async fn await_button(
+        &self,
+        user: UserId,
+        custom_ids: &[String],
+        timeout: Duration,
+        response: CreateInteractionResponse,
+    ) -> Result<Option<String>, SerenityError> {
+        let Some(press) = ComponentInteractionCollector::new(self)
+            .timeout(timeout)
+            .author_id(user)
+            .custom_ids(custom_ids.to_vec())
+            .await
+        else {
+            return Ok(None);
+        };
+
+        press.create_response(self, response).await?;
+        Ok(Some(press.data.custom_id))
+    }
+    async fn create_response(
+        &self,
+        id: InteractionId,
+        token: &str,
+        response: CreateInteractionResponse,
+    ) -> Result<(), SerenityError> {
+        response.execute(self, (id, token)).await
+    }
+
+    async fn followup(
+        &self,
+        token: &str,
+        replacing: Option<MessageId>,
+        message: &Value,
+    ) -> Result<MessageId, SerenityError> {
+        let sent = match replacing {
+            Some(replacing) => {
+                self.http
+                    .edit_followup_message(token, replacing, message, Vec::new())
+                    .await?
+            }
+            None => {
+                self.http
+                    .create_followup_message(token, message, Vec::new())
+                    .await?
+            }
+        };
+        Ok(sent.id)
+    }
+
+    async fn edit_response(&self, token: &str, message: &Value) -> Result<(), SerenityError> {
+        self.http
+            .edit_original_interaction_response(token, message, Vec::new())
+            .await
+            .map(drop)
+    }
+
+    async fn delete_followup(&self, token: &str, message: MessageId) -> Result<(), SerenityError> {
+        self.http.delete_followup_message(token, message).await
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use poise::serenity_prelude::*;
+use serenity::{json::Value, Error as SerenityError};
+use tokio::{sync::Notify, time::Instant};
+
+use super::Discord;
+
+/// How long an interaction holds batches back at most. Discord wants an answer within three
+/// seconds, and once it has one the rest of a command can share the buckets.
+const ACK_WINDOW: Duration = Duration::from_secs(3);
+
+/// Gives interactive calls priority over batches on the same rate-limit buckets.
+///
+/// Every command and component interaction opens a window while it's being answered. Batch work,
+/// like purging or refreshing every post, takes a turn before each of its calls, and the turn
+/// only comes once no window is open. Serenity queues requests per bucket in the order they're
+/// made, so holding batch requests back keeps the buckets free for the answers.
+#[derive(Default)]
+pub struct CallQueue {
+    /// Open windows by interaction ID, with when they close on their own.
+    windows: Mutex<HashMap<u64, Instant>>,
+    closed: Notify,
+}
+
+impl CallQueue {
+    /// Holds batches back while interaction `id` is answered.
+    pub fn open(&self, id: u64) {
+        self.windows
+            .lock()
+            .unwrap()
+            .insert(id, Instant::now() + ACK_WINDOW);
+    }
+
+    /// Lets batches carry on once interaction `id` has been answered.
+    pub fn close(&self, id: u64) {
+        if self.windows.lock().unwrap().remove(&id).is_some() {
+            self.closed.notify_waiters();
+        }
+    }
+
+    /// When the last open window closes on its own, if any are open.
+    fn busy_until(&self) -> Option<Instant> {
+        let mut windows = self.windows.lock().unwrap();
+        let now = Instant::now();
+        windows.retain(|_, closes_at| *closes_at > now);
+        windows.values().max().copied()
+    }
+
+    /// Waits until no interaction is being answered. Batch work calls this before each request.
+    pub async fn turn(&self) {
+        while let Some(closes_at) = self.busy_until() {
+            let closed = self.closed.notified();
+            // Something may have closed between looking and waiting.
+            if self.busy_until().is_none() {
+                return;
+            }
+            tokio::select! {
+                _ = closed => {}
+                _ = tokio::time::sleep_until(closes_at) => {}
+            }
+        }
+    }
+}
+
+/// Runs a batch's calls on `inner`, each one waiting for its turn on the queue. Button prompts
+/// and interaction answers are interactive, so they go straight through.
+pub struct Batched<'a, D> {
+    pub inner: &'a D,
+    pub queue: &'a CallQueue,
+}
+
+impl<D: Discord> Discord for Batched<'_, D> {
+    async fn messages(
+        &self,
+        channel: ChannelId,
+        before: Option<MessageId>,
+        limit: u8,
+    ) -> Result<Vec<Message>, SerenityError> {
+        self.queue.turn().await;
+        self.inner.messages(channel, before, limit).await
+    }
+
+    async fn delete_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+    ) -> Result<(), SerenityError> {
+        self.queue.turn().await;
+        self.inner.delete_message(channel, message).await
+    }
+
+    async fn delete_messages(
+        &self,
+        channel: ChannelId,
+        messages: &[MessageId],
+    ) -> Result<(), SerenityError> {
+        self.queue.turn().await;
+        self.inner.delete_messages(channel, messages).await
+    }
+
+    async fn await_button(
+        &self,
+        user: UserId,
+        custom_ids: &[String],
+        timeout: Duration,
+        response: CreateInteractionResponse,
+    ) -> Result<Option<String>, SerenityError> {
+        self.inner
+            .await_button(user, custom_ids, timeout, response)
+            .await
+    }
+
+    async fn create_response(
+        &self,
+        id: InteractionId,
+        token: &str,
+        response: CreateInteractionResponse,
+    ) -> Result<(), SerenityError> {
+        self.inner.create_response(id, token, response).await
+    }
+
+    async fn followup(
+        &self,
+        token: &str,
+        replacing: Option<MessageId>,
+        message: &Value,
+    ) -> Result<MessageId, SerenityError> {
+        self.inner.followup(token, replacing, message).await
+    }
+
+    async fn edit_response(&self, token: &str, message: &Value) -> Result<(), SerenityError> {
+        self.inner.edit_response(token, message).await
+    }
+
+    async fn delete_followup(&self, token: &str, message: MessageId) -> Result<(), SerenityError> {
+        self.inner.delete_followup(token, message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn batches_wait_for_interactions_to_be_answered() {
+        let queue = Arc::new(CallQueue::default());
+        queue.open(1);
+
+        let waiting = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move {
+                queue.turn().await;
+                Instant::now()
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!waiting.is_finished());
+
+        let answered_at = Instant::now();
+        queue.close(1);
+        assert_eq!(waiting.await.unwrap(), answered_at);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unanswered_interactions_only_hold_batches_briefly() {
+        let queue = CallQueue::default();
+        let opened_at = Instant::now();
+        queue.open(1);
+
+        queue.turn().await;
+        assert_eq!(Instant::now(), opened_at + ACK_WINDOW);
+        // With nothing open, the turn comes straight away.
+        queue.turn().await;
+        assert_eq!(Instant::now(), opened_at + ACK_WINDOW);
+    }
+}
@@ -0,0 +1,640 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    discord, roles,
+    settings::GuildSettings,
+    undo::UndoStep,
+    util::respond_ephemeral,
+    Context, Data, SlimeError,
+};
+
+/// How often each invariant is checked for drift.
+const CHECK_HOURS: i32 = 6;
+
+const MAX_INVARIANTS: i64 = 25;
+
+/// How many members one press of a fix button changes, so it finishes before the interaction
+/// is given up on. Pressing again carries on.
+const FIXES_PER_PRESS: usize = 25;
+
+/// Members named in a report, past which they're only counted.
+const LISTED: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum Rule {
+    /// Everyone with the role must also have the other.
+    Requires,
+    /// Nobody may have both roles.
+    Excludes,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Invariant {
+    id: i64,
+    guild_id: i64,
+    rule: Rule,
+    role_id: i64,
+    other_role_id: i64,
+    /// Who drifted at the last check, so unchanged drift isn't reported again.
+    drifted: Vec<i64>,
+}
+
+impl Invariant {
+    fn role(&self) -> RoleId {
+        RoleId::new(self.role_id as u64)
+    }
+
+    fn other(&self) -> RoleId {
+        RoleId::new(self.other_role_id as u64)
+    }
+
+    /// Whether a member holding `held` breaks the invariant.
+    fn broken_by(&self, held: &HashSet<RoleId>) -> bool {
+        match self.rule {
+            Rule::Requires => held.contains(&self.role()) && !held.contains(&self.other()),
+            Rule::Excludes => held.contains(&self.role()) && held.contains(&self.other()),
+        }
+    }
+
+    /// The members who break the invariant, in ID order.
+    fn drift(&self, members: &HashMap<UserId, HashSet<RoleId>>) -> Vec<UserId> {
+        let mut drifted = members
+            .iter()
+            .filter(|(_, held)| self.broken_by(held))
+            .map(|(user, _)| *user)
+            .collect::<Vec<_>>();
+        drifted.sort();
+        drifted
+    }
+
+    fn describe(&self) -> String {
+        match self.rule {
+            Rule::Requires => format!(
+                "Everyone with {} must also have {}",
+                self.role().mention(),
+                self.other().mention()
+            ),
+            Rule::Excludes => format!(
+                "Nobody should hold both {} and {}",
+                self.role().mention(),
+                self.other().mention()
+            ),
+        }
+    }
+
+    /// The fixes on offer, as (action, whether the role is given, role).
+    fn fixes(&self) -> [(&'static str, bool, RoleId); 2] {
+        match self.rule {
+            Rule::Requires => [
+                ("grant", true, self.other()),
+                ("revoke", false, self.role()),
+            ],
+            Rule::Excludes => [
+                ("revoke", false, self.role()),
+                ("revoke_other", false, self.other()),
+            ],
+        }
+    }
+}
+
+/// A drift report: who breaks `invariant`, with a button for each way of fixing it while anyone
+/// does. `names` are the guild's role names, since buttons can't mention roles.
+fn report(
+    invariant: &Invariant,
+    drifted: &[UserId],
+    names: &HashMap<RoleId, String>,
+    note: Option<String>,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let mut listed = drifted
+        .iter()
+        .take(LISTED)
+        .map(|u| u.mention().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if drifted.len() > LISTED {
+        listed.push_str(&format!(" and {} more", drifted.len() - LISTED));
+    }
+    let mut embed = CreateEmbed::new()
+        .title("Role drift")
+        .description(format!("{}.", invariant.describe()))
+        .footer(CreateEmbedFooter::new(format!(
+            "Invariant #{}",
+            invariant.id
+        )));
+    embed = if drifted.is_empty() {
+        embed.field("Members", "Nobody breaks it any more.", false)
+    } else {
+        embed.field(format!("{} member(s)", drifted.len()), listed, false)
+    };
+    if let Some(note) = note {
+        embed = embed.field("Fixed", note, false);
+    }
+
+    let rows = if drifted.is_empty() {
+        vec![]
+    } else {
+        let buttons = invariant
+            .fixes()
+            .into_iter()
+            .map(|(action, grant, role)| {
+                let name = names.get(&role).map_or("the role", String::as_str);
+                let label = if grant {
+                    format!("Give them @{name}")
+                } else {
+                    format!("Take @{name} away")
+                };
+                CreateButton::new(CustomId::new(Kind::Drift, action, invariant.id))
+                    .label(label.chars().take(80).collect::<String>())
+                    .style(if grant {
+                        ButtonStyle::Primary
+                    } else {
+                        ButtonStyle::Secondary
+                    })
+            })
+            .collect();
+        vec![CreateActionRow::Buttons(buttons)]
+    };
+    (embed, rows)
+}
+
+async fn role_names(
+    ctx: &SerenityContext,
+    guild_id: GuildId,
+) -> Result<HashMap<RoleId, String>, SlimeError> {
+    Ok(guild_id
+        .roles(ctx)
+        .await?
+        .into_iter()
+        .map(|(id, role)| (id, role.name))
+        .collect())
+}
+
+async fn held_roles(
+    ctx: &SerenityContext,
+    guild_id: GuildId,
+) -> Result<HashMap<UserId, HashSet<RoleId>>, SlimeError> {
+    Ok(roles::fetch_all_members(ctx, guild_id)
+        .await?
+        .into_iter()
+        .filter(|m| !m.user.bot)
+        .map(|m| (m.user.id, m.roles.into_iter().collect()))
+        .collect())
+}
+
+async fn remember(
+    data: &Data,
+    invariant: &Invariant,
+    drifted: &[UserId],
+) -> Result<(), SlimeError> {
+    sqlx::query("UPDATE role_invariants SET drifted = $2 WHERE id = $1")
+        .bind(invariant.id)
+        .bind(drifted.iter().map(|u| u.get() as i64).collect::<Vec<_>>())
+        .execute(&data.pool)
+        .await?;
+    Ok(())
+}
+
+/// Checks the invariants that are due, reporting any whose drift has changed since the last check
+/// to the audit channel.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let due = sqlx::query_as::<_, Invariant>(
+        "UPDATE role_invariants SET checked_at = $1
+         WHERE (checked_at IS NULL OR checked_at <= $1 - make_interval(hours => $2))
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .bind(CHECK_HOURS)
+    .fetch_all(pool)
+    .await?;
+    let mut by_guild = HashMap::<i64, Vec<Invariant>>::new();
+    for invariant in due {
+        by_guild
+            .entry(invariant.guild_id)
+            .or_default()
+            .push(invariant);
+    }
+
+    for (guild, invariants) in by_guild {
+        let guild_id = GuildId::new(guild as u64);
+        data.calls.turn().await;
+        let (members, names) =
+            match tokio::try_join!(held_roles(ctx, guild_id), role_names(ctx, guild_id)) {
+                Ok(found) => found,
+                Err(e) => {
+                    error!("Could not check role drift in guild {}: {}", guild_id, e);
+                    continue;
+                }
+            };
+        let channel = GuildSettings::load(pool, guild_id).await?.audit_channel();
+        for invariant in invariants {
+            let drifted = invariant.drift(&members);
+            let before = invariant
+                .drifted
+                .iter()
+                .map(|u| UserId::new(*u as u64))
+                .collect::<Vec<_>>();
+            if drifted == before {
+                continue;
+            }
+            remember(data, &invariant, &drifted).await?;
+            let Some(channel) = channel.filter(|_| !drifted.is_empty()) else {
+                continue;
+            };
+            let (embed, components) = report(&invariant, &drifted, &names, None);
+            let post = CreateMessage::new()
+                .embed(embed)
+                .components(components)
+                .allowed_mentions(CreateAllowedMentions::new());
+            if let Err(e) = channel.send_message(ctx, post).await {
+                error!("Could not report role drift in {}: {}", channel, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles the fix buttons on a drift report, changing the roles of whoever still breaks the
+/// invariant. Only members who can manage roles may press them.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let allowed = interaction
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_roles());
+    if !allowed {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "Only members who manage roles can fix drift.",
+        )
+        .await;
+    }
+    let invariant = sqlx::query_as::<_, Invariant>("SELECT * FROM role_invariants WHERE id = $1")
+        .bind(custom_id.id)
+        .fetch_optional(&data.pool)
+        .await?
+        .filter(|i| Some(GuildId::new(i.guild_id as u64)) == interaction.guild_id);
+    let Some(invariant) = invariant else {
+        return respond_ephemeral(ctx, interaction, "That invariant doesn't exist any more.").await;
+    };
+    let Some((_, grant, role)) = invariant
+        .fixes()
+        .into_iter()
+        .find(|(action, _, _)| *action == custom_id.action)
+    else {
+        return Ok(());
+    };
+    let guild_id = GuildId::new(invariant.guild_id as u64);
+
+    // Whoever drifted since the report was posted is fixed too, and whoever was fixed by hand
+    // is left alone.
+    let drifted = invariant.drift(&held_roles(ctx, guild_id).await?);
+    let mut undo = Vec::new();
+    let mut failed = 0;
+    for user in drifted.iter().take(FIXES_PER_PRESS) {
+        let reason = Some("Fixing role drift");
+        let changed = if grant {
+            ctx.http
+                .add_member_role(guild_id, *user, role, reason)
+                .await
+        } else {
+            ctx.http
+                .remove_member_role(guild_id, *user, role, reason)
+                .await
+        };
+        match changed {
+            Ok(()) if grant => undo.push(UndoStep::RemoveRole(*user, role)),
+            Ok(()) => undo.push(UndoStep::AddRole(*user, role)),
+            Err(e) => {
+                error!("Could not fix role drift for {}: {}", user, e);
+                failed += 1;
+            }
+        }
+    }
+    let fixed = undo.len();
+    let remaining = drifted
+        .iter()
+        .skip(FIXES_PER_PRESS)
+        .copied()
+        .collect::<Vec<_>>();
+    remember(data, &invariant, &remaining).await?;
+    audit::record(
+        &data.pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: interaction.user.id,
+            action: "roles_drift_fixed",
+            target: Some(invariant.id as u64),
+            details: format!(
+                "{} {role} for {fixed} member(s), failed: {failed}",
+                if grant { "gave" } else { "took" }
+            ),
+            undo,
+        },
+    )
+    .await?;
+
+    let mut note = if grant {
+        format!(
+            "{} gave {} to {fixed} member(s).",
+            interaction.user.mention(),
+            role.mention()
+        )
+    } else {
+        format!(
+            "{} took {} away from {fixed} member(s).",
+            interaction.user.mention(),
+            role.mention()
+        )
+    };
+    if failed > 0 {
+        note.push_str(&format!(
+            " {failed} couldn't be changed, check that my role is above {}.",
+            role.mention()
+        ));
+    }
+    if !remaining.is_empty() {
+        note.push_str(" Press again for the rest.");
+    }
+    let names = role_names(ctx, guild_id).await?;
+    let (embed, components) = report(&invariant, &remaining, &names, Some(note));
+    discord::respond(
+        ctx,
+        interaction,
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(components),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+async fn add(ctx: Context<'_>, rule: Rule, role: RoleId, other: RoleId) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    if role == other {
+        return reply(ctx, "Those are the same role.").await;
+    }
+    // Either way round means the same thing for roles that exclude each other.
+    let (role, other) = match rule {
+        Rule::Excludes if other < role => (other, role),
+        _ => (role, other),
+    };
+    let count =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM role_invariants WHERE guild_id = $1")
+            .bind(guild_id.get() as i64)
+            .fetch_one(pool)
+            .await?;
+    if count >= MAX_INVARIANTS {
+        return reply(
+            ctx,
+            format!("This server already has {MAX_INVARIANTS} invariants, remove one first."),
+        )
+        .await;
+    }
+    let invariant = sqlx::query_as::<_, Invariant>(
+        "INSERT INTO role_invariants (guild_id, rule, role_id, other_role_id, created_by, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (guild_id, rule, role_id, other_role_id) DO NOTHING
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(rule)
+    .bind(role.get() as i64)
+    .bind(other.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(ctx.data().clock.now())
+    .fetch_optional(pool)
+    .await?;
+    let Some(invariant) = invariant else {
+        return reply(ctx, "That invariant is already being checked.").await;
+    };
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "roles_invariant_added",
+            target: Some(invariant.id as u64),
+            details: format!("{:?} {role} {other}", invariant.rule),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    let mut content = format!(
+        "Invariant #{}: {}. It's checked every {CHECK_HOURS} hours, with drift reported in the \
+         audit channel.",
+        invariant.id,
+        invariant.describe()
+    );
+    if GuildSettings::load(pool, guild_id)
+        .await?
+        .audit_channel()
+        .is_none()
+    {
+        content.push_str(
+            " There's no audit channel yet, so set one with `/settings audit_channel` or check \
+             with `/roles drift`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// Make sure everyone with one role also has another.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+pub async fn require(
+    ctx: Context<'_>,
+    #[description = "Role whose members are checked"] role: Role,
+    #[description = "Role they must also have"] needs: Role,
+) -> Result<(), SlimeError> {
+    add(ctx, Rule::Requires, role.id, needs.id).await
+}
+
+/// Make sure nobody holds two roles at once.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+pub async fn exclude(
+    ctx: Context<'_>,
+    #[description = "One role"] role: Role,
+    #[description = "The role nobody with it should also have"] other: Role,
+) -> Result<(), SlimeError> {
+    add(ctx, Rule::Excludes, role.id, other.id).await
+}
+
+/// See the role invariants being checked.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+pub async fn invariants(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let found = sqlx::query_as::<_, Invariant>(
+        "SELECT * FROM role_invariants WHERE guild_id = $1 ORDER BY id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if found.is_empty() {
+        return reply(
+            ctx,
+            "No invariants are checked. Add one with `/roles require` or `/roles exclude`.",
+        )
+        .await;
+    }
+    let lines = found
+        .iter()
+        .map(|i| {
+            format!(
+                "**#{}** {} ({} drifted at the last check)",
+                i.id,
+                i.describe(),
+                i.drifted.len()
+            )
+        })
+        .collect::<Vec<_>>();
+    reply(ctx, lines.join("\n")).await
+}
+
+/// Stop checking a role invariant.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+pub async fn drop_invariant(
+    ctx: Context<'_>,
+    #[description = "Invariant number, from `/roles invariants`"] invariant: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let dropped = sqlx::query_as::<_, Invariant>(
+        "DELETE FROM role_invariants WHERE id = $1 AND guild_id = $2 RETURNING *",
+    )
+    .bind(invariant)
+    .bind(guild_id.get() as i64)
+    .fetch_optional(pool)
+    .await?;
+    let Some(dropped) = dropped else {
+        return reply(ctx, format!("There's no invariant #{invariant}.")).await;
+    };
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "roles_invariant_dropped",
+            target: Some(dropped.id as u64),
+            details: format!("{:?} {} {}", dropped.rule, dropped.role(), dropped.other()),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+    reply(
+        ctx,
+        format!("Invariant #{} isn't checked any more.", dropped.id),
+    )
+    .await
+}
+
+/// Check every role invariant now, with buttons to fix any drift.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+pub async fn drift(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    ctx.defer_ephemeral().await?;
+    let found = sqlx::query_as::<_, Invariant>(
+        "SELECT * FROM role_invariants WHERE guild_id = $1 ORDER BY id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if found.is_empty() {
+        return reply(
+            ctx,
+            "No invariants are checked. Add one with `/roles require` or `/roles exclude`.",
+        )
+        .await;
+    }
+    let members = held_roles(ctx.serenity_context(), guild_id).await?;
+    let names = role_names(ctx.serenity_context(), guild_id).await?;
+
+    let mut clean = 0;
+    for invariant in &found {
+        let drifted = invariant.drift(&members);
+        remember(ctx.data(), invariant, &drifted).await?;
+        if drifted.is_empty() {
+            clean += 1;
+            continue;
+        }
+        let (embed, components) = report(invariant, &drifted, &names, None);
+        ctx.send(
+            CreateReply::default()
+                .embed(embed)
+                .components(components)
+                .ephemeral(true),
+        )
+        .await?;
+    }
+    if clean == found.len() {
+        reply(ctx, "Nobody breaks any of this server's role invariants.").await?;
+    } else if clean > 0 {
+        reply(
+            ctx,
+            format!("{clean} other invariant(s) hold for everyone."),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drift_is_whoever_breaks_the_rule() {
+        let (raider, member, red) = (RoleId::new(1), RoleId::new(2), RoleId::new(3));
+        let members = HashMap::from([
+            (UserId::new(10), HashSet::from([raider, member])),
+            (UserId::new(11), HashSet::from([raider])),
+            (UserId::new(12), HashSet::from([raider, red])),
+            (UserId::new(13), HashSet::from([member, red])),
+        ]);
+        let mut invariant = Invariant {
+            id: 1,
+            guild_id: 5,
+            rule: Rule::Requires,
+            role_id: 1,
+            other_role_id: 2,
+            drifted: vec![],
+        };
+        assert_eq!(
+            invariant.drift(&members),
+            [UserId::new(11), UserId::new(12)]
+        );
+
+        invariant.rule = Rule::Excludes;
+        invariant.other_role_id = 3;
+        assert_eq!(invariant.drift(&members), [UserId::new(12)]);
+        assert!(invariant.fixes().iter().all(|(_, grant, _)| !grant));
+    }
+}
@@ -0,0 +1,125 @@
+use poise::serenity_prelude::*;
+
+use crate::settings::GuildSettings;
+
+/// Places in the bot's posts that show an emoji, which guilds can swap for their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Slot {
+    #[name = "Event \"I'm going\" button"]
+    Going,
+    #[name = "Event \"Interested\" button"]
+    Interested,
+    #[name = "Event \"Can't make it\" button"]
+    CantGo,
+    #[name = "LFG member who's ready"]
+    Ready,
+    #[name = "LFG member who isn't ready yet"]
+    NotReady,
+    #[name = "Previous page button"]
+    Previous,
+    #[name = "Next page button"]
+    Next,
+}
+
+impl Slot {
+    /// What the slot is stored as.
+    fn key(self) -> &'static str {
+        match self {
+            Self::Going => "going",
+            Self::Interested => "interested",
+            Self::CantGo => "cant_go",
+            Self::Ready => "ready",
+            Self::NotReady => "not_ready",
+            Self::Previous => "previous",
+            Self::Next => "next",
+        }
+    }
+
+    /// The emoji shown when the guild hasn't picked one. The event buttons have none, since their
+    /// labels say it all.
+    fn default(self) -> Option<&'static str> {
+        match self {
+            Self::Going | Self::Interested | Self::CantGo => None,
+            Self::Ready => Some("✅"),
+            Self::NotReady => Some("⏳"),
+            Self::Previous => Some("◀"),
+            Self::Next => Some("▶"),
+        }
+    }
+}
+
+/// The emoji the guild shows in `slot`: its own pick, or the bot's default.
+pub fn lookup(settings: &GuildSettings, slot: Slot) -> Option<ReactionType> {
+    let prefix = format!("{}=", slot.key());
+    settings
+        .emoji
+        .iter()
+        .find_map(|entry| entry.strip_prefix(&prefix))
+        .or(slot.default())
+        .and_then(|emoji| ReactionType::try_from(emoji).ok())
+}
+
+/// Like [`lookup`], as text to put in a message. Empty when the slot has no emoji.
+pub fn text(settings: &GuildSettings, slot: Slot) -> String {
+    lookup(settings, slot).map_or_else(String::new, |emoji| emoji.to_string())
+}
+
+/// The guild's emoji picks with `slot` set to `emoji`, or back to the default for `None`.
+pub fn with(settings: &GuildSettings, slot: Slot, emoji: Option<&ReactionType>) -> Vec<String> {
+    let prefix = format!("{}=", slot.key());
+    let mut picks = settings
+        .emoji
+        .iter()
+        .filter(|entry| !entry.starts_with(&prefix))
+        .cloned()
+        .collect::<Vec<_>>();
+    if let Some(emoji) = emoji {
+        picks.push(format!("{prefix}{emoji}"));
+    }
+    picks
+}
+
+/// Reads an emoji someone typed: a custom one like `<:frog:123>`, or a Unicode one. Discord
+/// refuses buttons with an emoji it doesn't know, which would break every post showing it, so
+/// anything that doesn't look like a single emoji is turned away.
+pub fn parse(input: &str) -> Option<ReactionType> {
+    let input = input.trim();
+    if input.starts_with('<') {
+        return ReactionType::try_from(input)
+            .ok()
+            .filter(|emoji| matches!(emoji, ReactionType::Custom { .. }));
+    }
+    let looks_like_emoji = !input.is_empty()
+        && input.chars().count() <= 8
+        && input
+            .chars()
+            .all(|c| !c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace());
+    looks_like_emoji.then(|| ReactionType::Unicode(input.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_picks_override_defaults() {
+        let mut settings = GuildSettings::default();
+        assert_eq!(text(&settings, Slot::Ready), "✅");
+        assert_eq!(lookup(&settings, Slot::Going), None);
+
+        let frog = parse("<:frog:123456789012345678>").unwrap();
+        settings.emoji = with(&settings, Slot::Going, Some(&frog));
+        settings.emoji = with(&settings, Slot::Ready, parse("🐸").as_ref());
+        assert_eq!(lookup(&settings, Slot::Going), Some(frog));
+        assert_eq!(text(&settings, Slot::Ready), "🐸");
+
+        settings.emoji = with(&settings, Slot::Ready, None);
+        assert_eq!(text(&settings, Slot::Ready), "✅");
+        assert_eq!(settings.emoji.len(), 1);
+
+        assert_eq!(parse("frog"), None);
+        assert_eq!(parse(":frog:"), None);
+        assert_eq!(parse("<frog>"), None);
+        assert_eq!(parse("🐸 🐸"), None);
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::DateTime;
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{interest, submit_or_publish, Event, EventStatus, NewEvent};
+use crate::{quotas, settings::GuildSettings, Context, SlimeError};
+
+/// Scheduled events in the guild the bot doesn't have an event for yet, soonest first.
+pub(super) async fn unmanaged(
+    ctx: Context<'_>,
+    pool: &PgPool,
+    guild_id: GuildId,
+) -> Result<Vec<ScheduledEvent>, SlimeError> {
+    let managed = sqlx::query_scalar::<_, i64>(
+        "SELECT scheduled_event_id FROM events
+         WHERE guild_id = $1 AND scheduled_event_id IS NOT NULL
+            AND status IN ('draft', 'pending', 'published')",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+
+    let mut scheduled = guild_id
+        .scheduled_events(ctx, false)
+        .await?
+        .into_iter()
+        .filter(|s| s.status == ScheduledEventStatus::Scheduled)
+        .filter(|s| !managed.contains(&(s.id.get() as i64)))
+        .collect::<Vec<_>>();
+    scheduled.sort_by_key(|s| s.start_time);
+    Ok(scheduled)
+}
+
+async fn autocomplete_scheduled(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    let partial = partial.to_lowercase();
+    unmanaged(ctx, &ctx.data().pool, guild_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|s| s.name.to_lowercase().contains(&partial))
+        .map(|s| {
+            let when = s.start_time.format("%d %b %H:%M UTC");
+            let name = format!("{when}: {}", s.name)
+                .chars()
+                .take(100)
+                .collect::<String>();
+            AutocompleteChoice::new(name, s.id.to_string())
+        })
+        .collect()
+}
+
+/// Gives `scheduled` an event hosted by `host`, posting it or sending it for approval like any
+/// other. Returns the event and what to tell whoever asked.
+pub(super) async fn take_over(
+    ctx: Context<'_>,
+    settings: &GuildSettings,
+    scheduled: &ScheduledEvent,
+    host: UserId,
+) -> Result<(Event, String), SlimeError> {
+    let pool = &ctx.data().pool;
+
+    let starts_at = DateTime::from_timestamp(scheduled.start_time.unix_timestamp(), 0)
+        .ok_or_else(|| SlimeError::InvalidTime(scheduled.start_time.to_string()))?;
+    let duration_minutes = scheduled
+        .end_time
+        .map(|end| (end.unix_timestamp() - scheduled.start_time.unix_timestamp()) / 60)
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(60) as i32;
+    let new = NewEvent {
+        guild_id: scheduled.guild_id,
+        channel_id: settings.events_channel().unwrap_or(ctx.channel_id()),
+        host_id: host,
+        title: scheduled.name.chars().take(100).collect(),
+        description: scheduled.description.clone().unwrap_or_default(),
+        starts_at,
+        duration_minutes,
+        capacity: None,
+        tags: Vec::new(),
+    };
+    let mut event = Event::insert(pool, new, EventStatus::Draft).await?;
+
+    // Where it happens comes along too, so the post shows it and syncing doesn't move it.
+    event.scheduled_event_id = Some(scheduled.id.get() as i64);
+    event.save(pool).await?;
+    sqlx::query("UPDATE events SET venue = $2, location_channel_id = $3 WHERE id = $1")
+        .bind(event.id)
+        .bind(scheduled.metadata.as_ref().and_then(|m| m.location.clone()))
+        .bind(scheduled.channel_id.map(|c| c.get() as i64))
+        .execute(pool)
+        .await?;
+    let mut event = Event::fetch(pool, event.id)
+        .await?
+        .ok_or(SlimeError::EventNotFound(event.id))?;
+
+    let content = submit_or_publish(ctx, settings, &mut event).await?;
+    // Whoever was already interested on Discord is interested on the post from the start.
+    if event.status == EventStatus::Published {
+        if let Err(e) = interest::reconcile(ctx.serenity_context(), pool, &event).await {
+            error!(
+                "Could not bring over interest for adopted event {}: {}",
+                event.id, e
+            );
+        }
+    }
+
+    Ok((event, content))
+}
+
+/// Take over an event from Discord's event list, giving it an RSVP post.
+#[poise::command(slash_command, guild_only)]
+pub async fn adopt(
+    ctx: Context<'_>,
+    #[description = "Event from Discord's event list"]
+    #[autocomplete = "autocomplete_scheduled"]
+    event: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let scheduled = match event.trim().parse::<u64>() {
+        Ok(id) if id > 0 => unmanaged(ctx, pool, guild_id)
+            .await?
+            .into_iter()
+            .find(|s| s.id.get() == id),
+        _ => None,
+    };
+    let Some(scheduled) = scheduled else {
+        ctx.send(
+            CreateReply::default()
+                .content("Pick an upcoming event from the list that the bot isn't already running.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    // Only whoever made it, or someone who could edit it anyway, takes it over.
+    let can_manage_events = ctx
+        .author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_events());
+    if scheduled.creator_id != Some(ctx.author().id) && !can_manage_events {
+        return Err(SlimeError::EventNotFound(scheduled.id.get() as i64));
+    }
+    quotas::ensure_events(pool, guild_id, 1).await?;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+
+    let (_, content) = take_over(ctx, &settings, &scheduled, ctx.author().id).await?;
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
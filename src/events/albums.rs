@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{threads, Event};
+use crate::{settings::GuildSettings, Data, SlimeError};
+
+/// Discord shows at most ten embeds on a message, so that many photos fit in the album itself.
+const SHOWN: usize = 10;
+
+/// Links to other uploads listed under the photos before only counting the rest.
+const LISTED: usize = 15;
+
+/// The uploads from an album thread, in the order they were posted.
+#[derive(Debug, Default)]
+struct Album {
+    /// Image links, with who posted them.
+    photos: Vec<(String, String)>,
+    /// Videos and anything else, which can't be shown in an embed.
+    other: Vec<String>,
+    members: HashSet<UserId>,
+}
+
+impl Album {
+    fn add(&mut self, poster: &User, url: &str, content_type: Option<&str>) {
+        if poster.bot {
+            return;
+        }
+        self.members.insert(poster.id);
+        match content_type {
+            Some(kind) if kind.starts_with("image/") => {
+                self.photos.push((url.to_string(), poster.name.clone()))
+            }
+            _ => self.other.push(url.to_string()),
+        }
+    }
+
+    fn uploads(&self) -> usize {
+        self.photos.len() + self.other.len()
+    }
+
+    fn message(&self, title: &str, thread: ChannelId) -> CreateMessage {
+        let mut content = format!(
+            "**Photos & highlights from {title}**\n{} upload(s) from {} member(s), all of them in {}.",
+            self.uploads(),
+            self.members.len(),
+            thread.mention()
+        );
+        let extra = self
+            .photos
+            .iter()
+            .skip(SHOWN)
+            .map(|(url, _)| url)
+            .chain(&self.other)
+            .collect::<Vec<_>>();
+        for url in extra.iter().take(LISTED) {
+            content.push_str(&format!("\n- <{url}>"));
+        }
+        if extra.len() > LISTED {
+            content.push_str(&format!("\n- and {} more", extra.len() - LISTED));
+        }
+
+        let embeds = self
+            .photos
+            .iter()
+            .take(SHOWN)
+            .map(|(url, poster)| {
+                CreateEmbed::new()
+                    .image(url)
+                    .footer(CreateEmbedFooter::new(poster))
+            })
+            .collect();
+        CreateMessage::new()
+            .content(content)
+            .embeds(embeds)
+            .allowed_mentions(CreateAllowedMentions::new())
+    }
+}
+
+/// Opens the photo thread for an event that just ended.
+async fn open(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &Event,
+    closes_at: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let starter = event
+        .channel()
+        .send_message(
+            ctx,
+            CreateMessage::new().content(format!(
+                "Thanks for coming to **{}**! Share your photos and highlights in the thread \
+                 below until <t:{}:f>, and they'll be gathered into an album.",
+                event.title,
+                closes_at.timestamp()
+            )),
+        )
+        .await?;
+    let name = format!("Photos & highlights: {}", event.title);
+    let thread = event
+        .channel()
+        .create_thread_from_message(
+            ctx,
+            starter.id,
+            CreateThread::new(name.chars().take(100).collect::<String>())
+                .auto_archive_duration(AutoArchiveDuration::OneWeek),
+        )
+        .await?;
+    sqlx::query("UPDATE events SET album_thread_id = $2 WHERE id = $1")
+        .bind(event.id)
+        .bind(thread.id.get() as i64)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Closes an event's photo thread and pins an album of what was shared in it.
+async fn compile(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &Event,
+    thread: ChannelId,
+) -> Result<(), SlimeError> {
+    thread
+        .edit_thread(ctx, EditThread::new().archived(true).locked(true))
+        .await?;
+    // Uploads are part of what members posted, so the album needs consent to read them.
+    if !GuildSettings::load(pool, event.guild())
+        .await?
+        .message_content_consent
+    {
+        return Ok(());
+    }
+
+    let mut messages = Vec::new();
+    threads::read(ctx, thread, |m| messages.push(m.clone())).await?;
+    let mut album = Album::default();
+    for message in messages.iter().rev() {
+        for attachment in &message.attachments {
+            album.add(
+                &message.author,
+                &attachment.url,
+                attachment.content_type.as_deref(),
+            );
+        }
+    }
+    if album.uploads() == 0 {
+        return Ok(());
+    }
+
+    let message = event
+        .channel()
+        .send_message(ctx, album.message(&event.title, thread))
+        .await?;
+    message.pin(ctx).await?;
+
+    Ok(())
+}
+
+/// Opens photo threads for events that just ended, and compiles albums from the ones that have
+/// closed. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    // Only events that ended within the hour, so turning albums on doesn't open threads for
+    // every event the guild has ever had.
+    let ended = sqlx::query_as::<_, Event>(
+        "UPDATE events e SET album_closes_at = $1 + make_interval(hours => g.album_hours)
+         FROM guild_settings g
+         WHERE g.guild_id = e.guild_id AND g.photo_albums AND e.album_closes_at IS NULL
+            AND e.status IN ('published', 'completed')
+            AND e.starts_at + make_interval(mins => e.duration_minutes) <= $1
+            AND e.starts_at + make_interval(mins => e.duration_minutes + 60) > $1
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING e.*",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    for event in ended {
+        let closes_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+            "SELECT album_closes_at FROM events WHERE id = $1",
+        )
+        .bind(event.id)
+        .fetch_one(pool)
+        .await?;
+        data.calls.turn().await;
+        if let Err(e) = open(ctx, pool, &event, closes_at).await {
+            error!("Could not open photo thread for event {}: {}", event.id, e);
+        }
+    }
+
+    let closed = sqlx::query_as::<_, (i64, i64)>(
+        "UPDATE events SET album_compiled_at = $1
+         WHERE album_thread_id IS NOT NULL AND album_compiled_at IS NULL AND album_closes_at <= $1
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING id, album_thread_id",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    for (event_id, thread_id) in closed {
+        let Some(event) = Event::fetch(pool, event_id).await? else {
+            continue;
+        };
+        data.calls.turn().await;
+        let thread = ChannelId::new(thread_id as u64);
+        if let Err(e) = compile(ctx, pool, &event, thread).await {
+            error!("Could not compile album for event {}: {}", event_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn albums_show_photos_and_list_everything_else() {
+        let mut poster = User::default();
+        poster.id = UserId::new(1);
+        poster.name = "ana".to_string();
+        let mut bot = User::default();
+        bot.bot = true;
+
+        let mut album = Album::default();
+        for i in 0..12 {
+            album.add(
+                &poster,
+                &format!("https://cdn.example/{i}.png"),
+                Some("image/png"),
+            );
+        }
+        album.add(&poster, "https://cdn.example/clip.mp4", Some("video/mp4"));
+        album.add(&bot, "https://cdn.example/bot.png", Some("image/png"));
+
+        assert_eq!(album.uploads(), 13);
+        assert_eq!(album.members.len(), 1);
+        assert_eq!(album.photos.len(), 12);
+        assert_eq!(
+            album.other,
+            vec!["https://cdn.example/clip.mp4".to_string()]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use poise::{serenity_prelude::*, Modal};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{cache, Event, EventModal, EventStatus};
+use crate::{
+    custom_id::{CustomId, Kind},
+    discord, forms,
+    i18n::Voice,
+    posts::{self, PostContent, PostKind},
+    settings::GuildSettings,
+    util::{respond_ephemeral, send_dm},
+    Data, SlimeError,
+};
+
+fn make_review_buttons(event_id: i64) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(CustomId::new(Kind::EventQueue, "approve", event_id))
+            .label("Approve")
+            .style(ButtonStyle::Success),
+        CreateButton::new(CustomId::new(Kind::EventQueue, "reject", event_id))
+            .label("Reject")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(CustomId::new(Kind::EventQueue, "edit", event_id))
+            .label("Edit")
+            .style(ButtonStyle::Secondary),
+    ])
+}
+
+/// Puts a pending event in front of the guild's moderators.
+pub async fn submit(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    settings: &GuildSettings,
+    event: &mut Event,
+) -> Result<(), SlimeError> {
+    let channel = settings
+        .approval_channel()
+        .ok_or(SlimeError::MissingSetting("approval channel"))?;
+
+    let message = channel
+        .send_message(ctx, queue_post(ctx, event, settings).create())
+        .await?;
+
+    event.queue_message_id = Some(message.id.get() as i64);
+    event.save(pool).await?;
+    posts::register(
+        pool,
+        PostKind::EventApproval,
+        event.id,
+        event.guild(),
+        &message,
+    )
+    .await
+}
+
+fn queue_post(ctx: &SerenityContext, event: &Event, settings: &GuildSettings) -> PostContent {
+    PostContent {
+        content: Some(format!(
+            "{} would like to post this event:",
+            event.host().mention()
+        )),
+        embed: event.embed(
+            &Voice::of(ctx, event.guild(), settings),
+            settings.plain_text,
+        ),
+        components: vec![make_review_buttons(event.id)],
+    }
+}
+
+/// A queued event's review post as it should look now, while it's still waiting for review.
+pub async fn render_post(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    id: i64,
+) -> Result<Option<PostContent>, SlimeError> {
+    let Some(event) = Event::fetch(pool, id)
+        .await?
+        .filter(|e| e.status == EventStatus::Pending)
+    else {
+        return Ok(None);
+    };
+    let settings = GuildSettings::load(pool, event.guild()).await?;
+
+    Ok(Some(queue_post(ctx, &event, &settings)))
+}
+
+/// Handles the approve/reject/edit buttons on queued events.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let (action, event_id) = (custom_id.action.as_str(), custom_id.id);
+
+    let is_moderator = interaction
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_events());
+    if !is_moderator {
+        return respond_ephemeral(ctx, interaction, "You need Manage Events to review events.")
+            .await;
+    }
+
+    let pool = &data.pool;
+    let event = if matches!(action, "approve" | "reject") {
+        // Decided here rather than after reading it, so two moderators pressing at once can't
+        // both review it.
+        let status = if action == "approve" {
+            EventStatus::Published
+        } else {
+            EventStatus::Rejected
+        };
+        let claimed = sqlx::query_as::<_, Event>(
+            "UPDATE events SET status = $2 WHERE id = $1 AND status = 'pending' RETURNING *",
+        )
+        .bind(event_id)
+        .bind(status)
+        .fetch_optional(pool)
+        .await?;
+        cache::written(event_id);
+        claimed
+    } else {
+        Event::fetch(pool, event_id)
+            .await?
+            .filter(|e| e.status == EventStatus::Pending)
+    };
+    let Some(mut event) = event else {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "That event has already been reviewed, or no longer exists.",
+        )
+        .await;
+    };
+
+    let reviewer = interaction.user.mention();
+    match action {
+        "approve" => {
+            discord::respond(ctx, interaction, CreateInteractionResponse::Acknowledge).await?;
+            if let Err(e) = event.publish(ctx, pool).await {
+                // Back in the queue, so it can be approved again.
+                sqlx::query(
+                    "UPDATE events SET status = 'pending' WHERE id = $1 AND message_id IS NULL",
+                )
+                .bind(event.id)
+                .execute(pool)
+                .await?;
+                cache::written(event.id);
+                return Err(e);
+            }
+            posts::retire(pool, interaction.message.id).await?;
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(format!("Approved by {reviewer}."))
+                        .embed(event.post_embed(ctx, pool).await?)
+                        .components(vec![]),
+                )
+                .await?;
+            notify_host(
+                ctx,
+                &event,
+                format!("Your event **{}** was approved and posted.", event.title),
+            )
+            .await;
+        }
+        "reject" => {
+            posts::retire(pool, interaction.message.id).await?;
+            discord::respond(
+                ctx,
+                interaction,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("Rejected by {reviewer}."))
+                        .embed(event.post_embed(ctx, pool).await?)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+            notify_host(
+                ctx,
+                &event,
+                format!(
+                    "Your event **{}** was not approved by the moderators.",
+                    event.title
+                ),
+            )
+            .await;
+        }
+        "edit" => {
+            let defaults = EventModal::from_event(&event);
+            let modal_id = interaction.id.to_string();
+            discord::respond(
+                ctx,
+                interaction,
+                EventModal::create(Some(defaults), modal_id.clone()),
+            )
+            .await?;
+
+            let Some((submitted, edited)) =
+                forms::collect::<EventModal>(ctx, interaction.user.id, modal_id).await?
+            else {
+                return Ok(());
+            };
+            edited.apply(&mut event);
+            if !event.save_details(pool, EventStatus::Pending).await? {
+                return submitted
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!(
+                                    "Event #{} was reviewed while you were editing it, so your \
+                                     changes weren't saved.",
+                                    event.id
+                                ))
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await
+                    .map_err(Into::into);
+            }
+
+            // Edited directly rather than as the answer to the form, which may have been
+            // reopened from somewhere else after a mistake.
+            interaction
+                .channel_id
+                .edit_message(
+                    ctx,
+                    interaction.message.id,
+                    EditMessage::new().embed(event.post_embed(ctx, pool).await?),
+                )
+                .await?;
+            submitted
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!("Updated event #{}.", event.id))
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn notify_host(ctx: &SerenityContext, event: &Event, content: String) {
+    if let Err(e) = send_dm(ctx, event.host(), CreateMessage::new().content(content)).await {
+        error!("Could not DM host of event {}: {}", event.id, e);
+    }
+}
@@ -0,0 +1,276 @@
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{forum, Event, EventStatus};
+use crate::{i18n::Voice, posts, settings::GuildSettings, Context, Data, SlimeError};
+
+/// How an event went, for its archived post.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Outcome {
+    /// Members recorded as having attended, once the host has finished the event.
+    attended: Option<i64>,
+    ratings: i64,
+    average: Option<f64>,
+}
+
+impl Outcome {
+    async fn of(pool: &PgPool, event: &Event) -> Result<Self, SlimeError> {
+        let attended = match event.status {
+            EventStatus::Completed => Some(
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT COUNT(*) FROM event_attendance WHERE event_id = $1",
+                )
+                .bind(event.id)
+                .fetch_one(pool)
+                .await?,
+            ),
+            _ => None,
+        };
+        let (ratings, average) = sqlx::query_as::<_, (i64, Option<f64>)>(
+            "SELECT COUNT(*), AVG(score)::FLOAT8 FROM event_feedback WHERE event_id = $1",
+        )
+        .bind(event.id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(Self {
+            attended,
+            ratings,
+            average,
+        })
+    }
+
+    /// The event's post with how it went added, in `voice`.
+    fn embed(&self, event: &Event, voice: &Voice, plain_text: bool) -> CreateEmbed {
+        let mut embed = event.embed(voice, false);
+        if plain_text {
+            // Whatever its status, an event in the archive is over.
+            embed = embed.title(format!("[{}] {}", voice.t("event-label-over"), event.title));
+        }
+        if let Some(attended) = self.attended {
+            let going = event.confirmed_count.max(1) as f64;
+            embed = embed.field(
+                voice.heading("event-attended"),
+                voice.t_with(
+                    "event-attended-count",
+                    &[
+                        ("count", &attended),
+                        (
+                            "percent",
+                            &((attended as f64 / going * 100.0).round() as i64),
+                        ),
+                    ],
+                ),
+                true,
+            );
+        }
+        if let Some(average) = self.average {
+            embed = embed.field(
+                voice.heading("event-feedback"),
+                voice.t_with(
+                    "event-feedback-score",
+                    &[
+                        ("score", &format!("{average:.1}")),
+                        ("count", &self.ratings),
+                    ],
+                ),
+                true,
+            );
+        }
+        embed
+    }
+}
+
+/// Brings an archived event's post up to date, after it was finished or rated.
+pub async fn refresh(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &Event,
+) -> Result<(), SlimeError> {
+    let Some(message) = event.archive_message_id else {
+        return Ok(());
+    };
+    let Some(channel) = archive_channel(pool, event.guild()).await? else {
+        return Ok(());
+    };
+
+    let settings = GuildSettings::load(pool, event.guild()).await?;
+    let embed = Outcome::of(pool, event).await?.embed(
+        event,
+        &Voice::of(ctx, event.guild(), &settings),
+        settings.plain_text,
+    );
+    channel
+        .edit_message(
+            ctx,
+            MessageId::new(message as u64),
+            EditMessage::new().embed(embed),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn archive_channel(
+    pool: &PgPool,
+    guild_id: GuildId,
+) -> Result<Option<ChannelId>, SlimeError> {
+    let channel = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT archive_channel_id FROM guild_settings WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_optional(pool)
+    .await?
+    .flatten();
+
+    Ok(channel.map(|c| ChannelId::new(c as u64)))
+}
+
+/// Moves the post of an event that's over into the archive channel, taking it down from the
+/// events channel.
+async fn archive(
+    ctx: &SerenityContext,
+    data: &Data,
+    event: &mut Event,
+    channel: ChannelId,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let settings = GuildSettings::load(pool, event.guild()).await?;
+    let embed = Outcome::of(pool, event).await?.embed(
+        event,
+        &Voice::of(ctx, event.guild(), &settings),
+        settings.plain_text,
+    );
+    data.calls.turn().await;
+    let archived = channel
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+    // Recorded before the post comes down, so a failure after this can't archive it twice.
+    event.archive_message_id = Some(archived.id.get() as i64);
+    sqlx::query("UPDATE events SET archive_message_id = $2 WHERE id = $1")
+        .bind(event.id)
+        .bind(event.archive_message_id)
+        .execute(pool)
+        .await?;
+
+    if let Some(message) = event.message_id.take() {
+        // A forum post is closed by `forum::sync` rather than taken down.
+        let in_forum = event.forum_thread_id == Some(message);
+        let message = MessageId::new(message as u64);
+        if !in_forum {
+            data.calls.turn().await;
+            if let Err(e) = event.channel().delete_message(ctx, message).await {
+                error!(
+                    "Could not take down post for archived event {}: {}",
+                    event.id, e
+                );
+            }
+        }
+        posts::retire(pool, message).await?;
+        event.save(pool).await?;
+    }
+    forum::sync(ctx, pool, event).await;
+
+    Ok(())
+}
+
+/// Archives the posts of events that ended long enough ago, in guilds with an archive channel.
+/// Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    // Claimed before posting, so a slow run can't archive an event twice.
+    let due = sqlx::query_as::<_, Event>(
+        "UPDATE events e SET archived_at = $1
+         FROM guild_settings g
+         WHERE g.guild_id = e.guild_id AND g.archive_channel_id IS NOT NULL
+            AND e.status IN ('published', 'completed') AND e.archived_at IS NULL
+            AND e.message_id IS NOT NULL
+            AND e.starts_at + make_interval(mins => e.duration_minutes, hours => g.archive_hours) <= $1
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING e.*",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for mut event in due {
+        let Some(channel) = archive_channel(pool, event.guild()).await? else {
+            continue;
+        };
+        if let Err(e) = archive(ctx, data, &mut event, channel).await {
+            error!("Could not archive event {}: {}", event.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Rate an event you went to, from 1 to 5.
+#[poise::command(slash_command, guild_only)]
+pub async fn rate(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "How it was, from 1 to 5"]
+    #[min = 1]
+    #[max = 5]
+    score: u8,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let user = ctx.author().id.get() as i64;
+
+    let event = Event::fetch(pool, id)
+        .await?
+        .filter(|e| e.guild() == guild_id && e.starts_at <= ctx.data().clock.now())
+        .filter(|e| matches!(e.status, EventStatus::Published | EventStatus::Completed))
+        .ok_or(SlimeError::EventNotFound(id))?;
+    // Once the host has finished the event, only those who attended may rate it.
+    let went = sqlx::query_scalar::<_, bool>(
+        "SELECT CASE WHEN $3 THEN
+                EXISTS (SELECT 1 FROM event_attendance WHERE event_id = $1 AND user_id = $2)
+            ELSE
+                EXISTS (SELECT 1 FROM event_rsvps
+                    WHERE event_id = $1 AND user_id = $2 AND state = 'confirmed')
+            END",
+    )
+    .bind(event.id)
+    .bind(user)
+    .bind(event.status == EventStatus::Completed)
+    .fetch_one(pool)
+    .await?;
+    if !went {
+        let content = format!("Only members who went to **{}** can rate it.", event.title);
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO event_feedback (event_id, user_id, score) VALUES ($1, $2, $3)
+         ON CONFLICT (event_id, user_id) DO UPDATE SET score = EXCLUDED.score",
+    )
+    .bind(event.id)
+    .bind(user)
+    .bind(score as i16)
+    .execute(pool)
+    .await?;
+    if let Err(e) = refresh(ctx.serenity_context(), pool, &event).await {
+        error!(
+            "Could not update archived post for event {}: {}",
+            event.id, e
+        );
+    }
+
+    let content = format!("Thanks! You rated **{}** {score} out of 5.", event.title);
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
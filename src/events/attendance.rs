@@ -0,0 +1,342 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use chrono::Duration;
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{archive, fetch_managed, forum, rsvp, Event, EventStatus};
+use crate::{banner, Context, SlimeError};
+
+/// Letters and digits that can't be mistaken for one another when read off a screen.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 6;
+
+/// How long before an event starts and after it ends members can check in.
+const CHECKIN_GRACE_MINUTES: i64 = 30;
+
+/// How many of a member's events in a row they actually turned up to.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Streak {
+    /// Consecutive attended events, counting back from the most recent one.
+    pub current: u32,
+    pub best: u32,
+    pub attended: u32,
+}
+
+/// Works out `user`'s streak from every completed event they had a confirmed place at.
+pub async fn streak(pool: &PgPool, guild_id: GuildId, user: UserId) -> Result<Streak, SlimeError> {
+    let history = sqlx::query_scalar::<_, bool>(
+        "SELECT a.user_id IS NOT NULL
+         FROM events e
+         JOIN event_rsvps r ON r.event_id = e.id AND r.user_id = $2 AND r.state = 'confirmed'
+         LEFT JOIN event_attendance a ON a.event_id = e.id AND a.user_id = $2
+         WHERE e.guild_id = $1 AND e.status = 'completed'
+         ORDER BY e.starts_at DESC",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(user.get() as i64)
+    .fetch_all(pool)
+    .await?;
+
+    let mut streak = Streak {
+        current: history.iter().take_while(|attended| **attended).count() as u32,
+        ..Default::default()
+    };
+    let mut run = 0;
+    for attended in history {
+        if attended {
+            run += 1;
+            streak.attended += 1;
+            streak.best = streak.best.max(run);
+        } else {
+            run = 0;
+        }
+    }
+
+    Ok(streak)
+}
+
+/// Gives `user` every badge role their current streak has earned. Roles they already hold are
+/// skipped by Discord, so this is safe to repeat.
+async fn award_badges(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    user: UserId,
+) -> Result<(), SlimeError> {
+    let streak = streak(pool, guild_id, user).await?;
+    let roles = sqlx::query_scalar::<_, i64>(
+        "SELECT role_id FROM streak_badges WHERE guild_id = $1 AND streak <= $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(streak.current as i32)
+    .fetch_all(pool)
+    .await?;
+
+    for role in roles {
+        let _ = ctx
+            .http
+            .add_member_role(
+                guild_id,
+                user,
+                RoleId::new(role as u64),
+                Some("Attendance streak badge"),
+            )
+            .await
+            .inspect_err(|e| error!("Could not award streak badge {} to {}: {}", role, user, e));
+    }
+
+    Ok(())
+}
+
+/// Close out an event, recording everyone with a confirmed place as having attended.
+#[poise::command(slash_command, guild_only)]
+pub async fn finish(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    ctx.defer_ephemeral().await?;
+
+    let pool = &ctx.data().pool;
+    // At events with a check-in code, only those who checked in were really there.
+    let checked_in = checkin_code(pool, event.id).await?.is_some();
+    let attendees = if checked_in {
+        checked_in_members(pool, event.id).await?
+    } else {
+        sqlx::query(
+            "INSERT INTO event_attendance (event_id, user_id)
+             SELECT event_id, user_id FROM event_rsvps WHERE event_id = $1 AND state = 'confirmed'
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(event.id)
+        .execute(pool)
+        .await?;
+        rsvp::confirmed(pool, event.id).await?
+    };
+
+    event.status = EventStatus::Completed;
+    event.save(pool).await?;
+    forum::sync(ctx.serenity_context(), pool, &event).await;
+    if let Err(e) = archive::refresh(ctx.serenity_context(), pool, &event).await {
+        error!(
+            "Could not update archived post for event {}: {}",
+            event.id, e
+        );
+    }
+
+    for attendee in &attendees {
+        award_badges(ctx.serenity_context(), pool, event.guild(), *attendee).await?;
+    }
+
+    let how = if checked_in { " who checked in" } else { "" };
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Recorded {} attendee(s){how} for **{}**. Use `/event absent` for anyone who didn't show.",
+                attendees.len(),
+                event.title
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Mark someone as not having turned up to a finished event.
+#[poise::command(slash_command, guild_only)]
+pub async fn absent(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Member who didn't show"] member: User,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Completed {
+        return Err(SlimeError::EventNotFound(id));
+    }
+
+    sqlx::query("DELETE FROM event_attendance WHERE event_id = $1 AND user_id = $2")
+        .bind(event.id)
+        .bind(member.id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?;
+    if let Err(e) = archive::refresh(ctx.serenity_context(), &ctx.data().pool, &event).await {
+        error!(
+            "Could not update archived post for event {}: {}",
+            event.id, e
+        );
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Marked {} as absent from **{}**.",
+                member.mention(),
+                event.title
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn checkin_code(pool: &PgPool, event_id: i64) -> Result<Option<String>, SlimeError> {
+    Ok(
+        sqlx::query_scalar::<_, Option<String>>("SELECT checkin_code FROM events WHERE id = $1")
+            .bind(event_id)
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+async fn checked_in_members(pool: &PgPool, event_id: i64) -> Result<Vec<UserId>, SlimeError> {
+    let ids =
+        sqlx::query_scalar::<_, i64>("SELECT user_id FROM event_attendance WHERE event_id = $1")
+            .bind(event_id)
+            .fetch_all(pool)
+            .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// A fresh code for members to check in with.
+fn new_code(event_id: i64) -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_i64(event_id);
+    let mut seed = hasher.finish();
+    (0..CODE_LENGTH)
+        .map(|_| {
+            let c = CODE_ALPHABET[(seed % CODE_ALPHABET.len() as u64) as usize];
+            seed /= CODE_ALPHABET.len() as u64;
+            c as char
+        })
+        .collect()
+}
+
+/// Get a QR code and check-in code to show at an in-person event.
+///
+/// Once an event has a code, only members who check in with it count as having attended.
+#[poise::command(slash_command, guild_only, rename = "checkin_qr")]
+pub async fn checkin_qr(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let pool = &ctx.data().pool;
+
+    let code = sqlx::query_scalar::<_, String>(
+        "UPDATE events SET checkin_code = COALESCE(checkin_code, $2) WHERE id = $1
+         RETURNING checkin_code",
+    )
+    .bind(event.id)
+    .bind(new_code(event.id))
+    .fetch_one(pool)
+    .await?;
+    // Scanning opens the event's post, where the code is all that's missing.
+    let link = match event.message_id {
+        Some(message) => MessageId::new(message as u64).link(event.channel(), Some(event.guild())),
+        None => event.channel().mention().to_string(),
+    };
+    let Some(card) = banner::checkin_card(&event.title, &link, &code) else {
+        return Err(SlimeError::EventNotFound(id));
+    };
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Show this at **{}**. Members scan it to find the event, then check in with \
+                 `/checkin {code}` from {CHECKIN_GRACE_MINUTES} minutes before it starts until \
+                 {CHECKIN_GRACE_MINUTES} minutes after it ends. Don't post it where people who \
+                 aren't there can see it.",
+                event.title
+            ))
+            .attachment(CreateAttachment::bytes(card, banner::CHECKIN_FILENAME))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Check in at an in-person event with the code shown there.
+#[poise::command(slash_command, guild_only)]
+pub async fn checkin(
+    ctx: Context<'_>,
+    #[description = "Code shown at the event"]
+    #[max_length = 12]
+    code: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let now = ctx.data().clock.now();
+    let grace = Duration::minutes(CHECKIN_GRACE_MINUTES);
+
+    let events = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events
+         WHERE guild_id = $1 AND checkin_code = $2 AND status = 'published'",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(code.trim().to_uppercase())
+    .fetch_all(pool)
+    .await?;
+    let Some(event) = events
+        .into_iter()
+        .find(|e| e.starts_at - grace <= now && now <= e.ends_at() + grace)
+    else {
+        let content = "That code isn't for an event happening now.";
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+    if !rsvp::confirmed(pool, event.id)
+        .await?
+        .contains(&ctx.author().id)
+    {
+        let content = format!("You don't have a place at **{}**.", event.title);
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO event_attendance (event_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(event.id)
+    .bind(ctx.author().id.get() as i64)
+    .execute(pool)
+    .await?;
+
+    let content = format!("You're checked in at **{}**. Enjoy!", event.title);
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_in_codes_are_easy_to_read_out() {
+        for event_id in 0..50 {
+            let code = new_code(event_id);
+            assert_eq!(code.len(), CODE_LENGTH);
+            // No 0/O, 1/I/L to mix up when reading it off a screen.
+            assert!(code.bytes().all(|c| CODE_ALPHABET.contains(&c)), "{code}");
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use super::{cache, fetch_managed, Event, EventStatus};
+use crate::{Context, SlimeError};
+
+/// One of the RSVP buttons under an event's post.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum RsvpButton {
+    #[name = "I'm going"]
+    Going,
+    Interested,
+    #[name = "Can't make it"]
+    CantGo,
+}
+
+impl RsvpButton {
+    /// Where the button's label and style sit in the event's `rsvp_labels` and `rsvp_styles`.
+    fn index(self) -> usize {
+        match self {
+            Self::Going => 0,
+            Self::Interested => 1,
+            Self::CantGo => 2,
+        }
+    }
+
+    fn default_label(self) -> &'static str {
+        match self {
+            Self::Going => "I'm going",
+            Self::Interested => "Interested",
+            Self::CantGo => "Can't make it",
+        }
+    }
+
+    fn default_style(self) -> ButtonStyle {
+        match self {
+            Self::Going => ButtonStyle::Success,
+            Self::Interested => ButtonStyle::Primary,
+            Self::CantGo => ButtonStyle::Secondary,
+        }
+    }
+}
+
+/// The colours Discord lets a button be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Colour {
+    Green,
+    Blurple,
+    Grey,
+    Red,
+}
+
+impl Colour {
+    fn key(self) -> &'static str {
+        match self {
+            Self::Green => "green",
+            Self::Blurple => "blurple",
+            Self::Grey => "grey",
+            Self::Red => "red",
+        }
+    }
+
+    fn parse(key: &str) -> Option<Self> {
+        [Self::Green, Self::Blurple, Self::Grey, Self::Red]
+            .into_iter()
+            .find(|c| c.key() == key)
+    }
+
+    fn style(self) -> ButtonStyle {
+        match self {
+            Self::Green => ButtonStyle::Success,
+            Self::Blurple => ButtonStyle::Primary,
+            Self::Grey => ButtonStyle::Secondary,
+            Self::Red => ButtonStyle::Danger,
+        }
+    }
+}
+
+/// What the host put at `button`'s place in `stored`, if anything.
+fn chosen(stored: &[String], button: RsvpButton) -> Option<&str> {
+    stored
+        .get(button.index())
+        .map(String::as_str)
+        .filter(|s| !s.is_empty())
+}
+
+impl Event {
+    /// The label the host gave `button`, or its usual one.
+    pub fn rsvp_label(&self, button: RsvpButton) -> &str {
+        chosen(&self.rsvp_labels, button).unwrap_or(button.default_label())
+    }
+
+    /// The colour the host picked for `button`, or its usual one.
+    pub fn rsvp_style(&self, button: RsvpButton) -> ButtonStyle {
+        chosen(&self.rsvp_styles, button)
+            .and_then(Colour::parse)
+            .map_or(button.default_style(), Colour::style)
+    }
+}
+
+/// `stored` with `button`'s place set to `value`, empty meaning the usual one. Events that were
+/// never customized have nothing stored, so the list is filled out first.
+fn set(stored: &[String], button: RsvpButton, value: Option<&str>) -> Vec<String> {
+    let mut stored = stored.to_vec();
+    stored.resize(3, String::new());
+    stored[button.index()] = value.unwrap_or_default().to_string();
+    if stored.iter().all(String::is_empty) {
+        stored.clear();
+    }
+    stored
+}
+
+async fn save(pool: &PgPool, event: &Event) -> Result<(), SlimeError> {
+    sqlx::query("UPDATE events SET rsvp_labels = $2, rsvp_styles = $3 WHERE id = $1")
+        .bind(event.id)
+        .bind(&event.rsvp_labels)
+        .bind(&event.rsvp_styles)
+        .execute(pool)
+        .await?;
+    cache::written(event.id);
+
+    Ok(())
+}
+
+/// Rename or recolour one of your event's RSVP buttons. Leave both out to put it back.
+#[poise::command(slash_command, guild_only, rename = "buttons")]
+pub async fn buttons_command(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Which button to change"] button: RsvpButton,
+    #[description = "What the button says, like \"I'm in!\""]
+    #[max_length = 80]
+    label: Option<String>,
+    #[description = "The button's colour"] colour: Option<Colour>,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if !matches!(
+        event.status,
+        EventStatus::Draft | EventStatus::Pending | EventStatus::Published
+    ) {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let label = label.as_deref().map(str::trim).filter(|l| !l.is_empty());
+    event.rsvp_labels = set(&event.rsvp_labels, button, label);
+    event.rsvp_styles = set(&event.rsvp_styles, button, colour.map(Colour::key));
+
+    let pool = &ctx.data().pool;
+    save(pool, &event).await?;
+    if event.status == EventStatus::Published {
+        event.refresh_post(ctx.serenity_context(), pool).await?;
+    }
+
+    let content = if label.is_none() && colour.is_none() {
+        format!(
+            "The **{}** button on **{}** is back to normal.",
+            button.default_label(),
+            event.title
+        )
+    } else {
+        format!(
+            "The **{}** button on **{}** now says **{}**.",
+            button.default_label(),
+            event.title,
+            event.rsvp_label(button)
+        )
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons_fall_back_to_their_usual_look() {
+        let labels = set(&[], RsvpButton::Interested, Some("Maybe"));
+        assert_eq!(labels, vec!["", "Maybe", ""]);
+        assert_eq!(chosen(&labels, RsvpButton::Interested), Some("Maybe"));
+        assert_eq!(chosen(&labels, RsvpButton::Going), None);
+
+        assert!(set(&labels, RsvpButton::Interested, None).is_empty());
+        assert_eq!(Colour::parse(Colour::Red.key()), Some(Colour::Red));
+        assert_eq!(Colour::parse("purple"), None);
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use sqlx::PgPool;
+
+use super::Event;
+use crate::SlimeError;
+
+/// How long a cached event is trusted. Whatever decides an RSVP, like the event's capacity and
+/// whether it's still taking them, is read again under the event's lock when the RSVP is written,
+/// so this only bounds how far behind the rest can be.
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// When recently written events were last written. It's kept for the whole process rather than
+/// in each cache so [`Event::save`] and [`Event::refresh_post`] can mark their writes wherever
+/// they're called from, without a handle on the cache.
+fn writes() -> &'static Mutex<HashMap<i64, Instant>> {
+    static WRITES: OnceLock<Mutex<HashMap<i64, Instant>>> = OnceLock::new();
+    WRITES.get_or_init(Default::default)
+}
+
+/// Marks the event as just written, so copies cached before now are loaded again.
+pub fn written(id: i64) {
+    let mut writes = writes().lock().unwrap();
+    // Anything cached before an older write has expired anyway.
+    writes.retain(|_, at| at.elapsed() < CACHE_TTL);
+    writes.insert(id, Instant::now());
+}
+
+/// Whether the event was written after `loaded_at`.
+fn written_since(id: i64, loaded_at: Instant) -> bool {
+    writes()
+        .lock()
+        .unwrap()
+        .get(&id)
+        .is_some_and(|at| *at >= loaded_at)
+}
+
+/// Recently loaded events, so a burst of button presses on one post doesn't load its event for
+/// every press.
+#[derive(Default)]
+pub struct EventCache {
+    entries: Mutex<HashMap<i64, (Instant, Event)>>,
+}
+
+impl EventCache {
+    pub async fn get(&self, pool: &PgPool, id: i64) -> Result<Option<Event>, SlimeError> {
+        if let Some((loaded_at, event)) = self.entries.lock().unwrap().get(&id) {
+            if loaded_at.elapsed() < CACHE_TTL && !written_since(id, *loaded_at) {
+                return Ok(Some(event.clone()));
+            }
+        }
+
+        // Timed from before the read, so a write landing during it isn't mistaken for older.
+        let loading = Instant::now();
+        let event = Event::fetch(pool, id).await?;
+        let mut entries = self.entries.lock().unwrap();
+        // Events nobody is pressing buttons on any more would otherwise stay forever.
+        entries.retain(|_, (loaded_at, _)| loaded_at.elapsed() < CACHE_TTL);
+        match &event {
+            Some(event) => entries.insert(id, (loading, event.clone())),
+            None => entries.remove(&id),
+        };
+        Ok(event)
+    }
+
+    /// Drops a cached event after it's written, so the next press sees the change. The write is
+    /// marked too, in case a load from before it finishes after this.
+    pub fn invalidate(&self, id: i64) {
+        written(id);
+        self.entries.lock().unwrap().remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_outdate_what_was_cached_before_them() {
+        let loaded_at = Instant::now();
+        assert!(!written_since(-1, loaded_at));
+        written(-1);
+        assert!(written_since(-1, loaded_at));
+        // Loaded again after the write.
+        assert!(!written_since(
+            -1,
+            Instant::now() + Duration::from_millis(1)
+        ));
+        assert!(!written_since(-2, loaded_at));
+    }
+
+    #[test]
+    fn invalidating_outdates_loads_already_under_way() {
+        let loading = Instant::now();
+        EventCache::default().invalidate(-3);
+        assert!(written_since(-3, loading));
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{
+    custom_id::{CustomId, Kind},
+    util::page_buttons,
+    Context, SlimeError,
+};
+
+/// Events listed under the grid before only counting the rest.
+const LISTED: usize = 20;
+
+/// A calendar month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Month {
+    year: i32,
+    month: u32,
+}
+
+impl Month {
+    fn of(at: DateTime<Utc>) -> Self {
+        Self {
+            year: at.year(),
+            month: at.month(),
+        }
+    }
+
+    /// Reads `2024-03` style months.
+    fn parse(value: &str) -> Option<Self> {
+        let (year, month) = value.trim().split_once('-')?;
+        let month = Self {
+            year: year.parse().ok()?,
+            month: month.parse().ok()?,
+        };
+        month.first_day().map(|_| month)
+    }
+
+    fn first_day(self) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(self.year, self.month, 1)
+    }
+
+    fn next(self) -> Self {
+        match self.month {
+            12 => Self {
+                year: self.year + 1,
+                month: 1,
+            },
+            month => Self {
+                month: month + 1,
+                ..self
+            },
+        }
+    }
+
+    fn previous(self) -> Self {
+        match self.month {
+            1 => Self {
+                year: self.year - 1,
+                month: 12,
+            },
+            month => Self {
+                month: month - 1,
+                ..self
+            },
+        }
+    }
+
+    fn days(self) -> u32 {
+        let (Some(first), Some(next)) = (self.first_day(), self.next().first_day()) else {
+            return 0;
+        };
+        (next - first).num_days() as u32
+    }
+
+    /// When the month starts and ends, in UTC.
+    fn bounds(self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = self.first_day()?.and_hms_opt(0, 0, 0)?;
+        let end = self.next().first_day()?.and_hms_opt(0, 0, 0)?;
+        Some((Utc.from_utc_datetime(&start), Utc.from_utc_datetime(&end)))
+    }
+
+    fn name(self) -> String {
+        self.first_day()
+            .map(|day| day.format("%B %Y").to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// The month as a grid of days starting on Monday, with an asterisk by every day in
+/// `event_days`.
+fn grid(month: Month, event_days: &BTreeMap<u32, usize>) -> String {
+    let Some(first) = month.first_day() else {
+        return String::new();
+    };
+    let mut grid = " Mo  Tu  We  Th  Fr  Sa  Su\n".to_string();
+    let offset = first.weekday().num_days_from_monday();
+    grid.push_str(&"    ".repeat(offset as usize));
+    for day in 1..=month.days() {
+        let marker = if event_days.contains_key(&day) {
+            '*'
+        } else {
+            ' '
+        };
+        grid.push_str(&format!("{day:>3}{marker}"));
+        if (offset + day) % 7 == 0 {
+            grid.truncate(grid.trim_end_matches(' ').len());
+            grid.push('\n');
+        }
+    }
+    format!("```\n{}\n```", grid.trim_end())
+}
+
+/// The guild's published and finished events in `month`, as the month's page.
+async fn page(pool: &PgPool, guild_id: GuildId, month: Month) -> Result<CreateEmbed, SlimeError> {
+    let Some((start, end)) = month.bounds() else {
+        return Err(SlimeError::InvalidTime(month.name()));
+    };
+    let events = sqlx::query_as::<_, (i64, String, DateTime<Utc>)>(
+        "SELECT id, title, starts_at FROM events
+         WHERE guild_id = $1 AND status IN ('published', 'completed')
+            AND starts_at >= $2 AND starts_at < $3
+         ORDER BY starts_at, id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+
+    let mut event_days = BTreeMap::new();
+    for (_, _, starts_at) in &events {
+        *event_days.entry(starts_at.day()).or_insert(0) += 1;
+    }
+    let mut description = grid(month, &event_days);
+    if events.is_empty() {
+        description.push_str("\nNothing on this month.");
+    }
+    for (id, title, starts_at) in events.iter().take(LISTED) {
+        description.push_str(&format!(
+            "\n`{}` **{title}** <t:{}:t> (#{id})",
+            starts_at.format("%b %d"),
+            starts_at.timestamp()
+        ));
+    }
+    if events.len() > LISTED {
+        description.push_str(&format!("\n…and {} more", events.len() - LISTED));
+    }
+
+    Ok(CreateEmbed::new()
+        .title(month.name())
+        .description(description)
+        .footer(CreateEmbedFooter::new(
+            "Days marked * have events. Times are in UTC.",
+        )))
+}
+
+/// See the server's events laid out by month.
+#[poise::command(slash_command, guild_only, rename = "calendar")]
+pub async fn calendar_command(
+    ctx: Context<'_>,
+    #[description = "Month to start on, e.g. `2024-03` (default this month)"] month: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let mut current = match month {
+        Some(month) => Month::parse(&month).ok_or(SlimeError::InvalidTime(month))?,
+        None => Month::of(ctx.data().clock.now()),
+    };
+
+    let id = ctx.id();
+    let prev_id = CustomId::for_invocation(Kind::Page, "prev", id).to_string();
+    let next_id = CustomId::for_invocation(Kind::Page, "next", id).to_string();
+    let buttons = page_buttons(ctx, &prev_id, &next_id).await?;
+    ctx.send(
+        CreateReply::default()
+            .embed(page(pool, guild_id, current).await?)
+            .components(vec![buttons])
+            .ephemeral(true),
+    )
+    .await?;
+
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .custom_ids(vec![prev_id.clone(), next_id.clone()])
+        .timeout(Duration::from_secs(300))
+        .await
+    {
+        current = if press.data.custom_id == next_id {
+            current.next()
+        } else {
+            current.previous()
+        };
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(page(pool, guild_id, current).await?),
+                ),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn months_are_laid_out_from_monday() {
+        // February 2024 starts on a Thursday and, being a leap year, has 29 days.
+        let february = Month::parse("2024-02").unwrap();
+        assert_eq!(february.days(), 29);
+        let grid = grid(february, &BTreeMap::from([(1, 1), (14, 2)]));
+        let lines = grid.lines().collect::<Vec<_>>();
+        assert_eq!(lines[2], "              1*  2   3   4");
+        assert_eq!(lines[4], " 12  13  14* 15  16  17  18");
+        assert_eq!(lines[6], " 26  27  28  29");
+    }
+
+    #[test]
+    fn months_roll_over_the_year() {
+        let december = Month::parse("2023-12").unwrap();
+        assert_eq!(
+            december.next(),
+            Month {
+                year: 2024,
+                month: 1
+            }
+        );
+        assert_eq!(december.next().previous(), december);
+        assert_eq!(Month::parse("2024-13"), None);
+        assert_eq!(Month::parse("March"), None);
+    }
+}
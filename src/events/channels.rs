@@ -0,0 +1,305 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::{client::Context as SerenityContext, http::HttpError, Error as SerenityError};
+use tracing::error;
+
+use super::{rsvp, speakers, Event};
+use crate::{
+    audit::{self, AuditEntry},
+    settings::GuildSettings,
+    Data, SlimeError,
+};
+
+/// What a guild's events get when they go live.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum EventVoice {
+    #[default]
+    #[name = "Nothing"]
+    Off,
+    #[name = "A voice channel"]
+    Voice,
+    #[name = "A stage"]
+    Stage,
+}
+
+/// What attendees (and the host) can do in an event's channel. Everyone else can't see it.
+pub const ATTENDEE_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::CONNECT)
+    .union(Permissions::SPEAK);
+
+/// Gives or takes the event role from `user`, if the event has one. Failures are only logged,
+/// since the RSVP itself has already gone through.
+pub async fn sync_role(ctx: &SerenityContext, event: &Event, user: UserId, attending: bool) {
+    let Some(role) = event.event_role_id.map(|id| RoleId::new(id as u64)) else {
+        return;
+    };
+    let result = if attending {
+        ctx.http
+            .add_member_role(event.guild(), user, role, Some("Confirmed for event"))
+            .await
+    } else {
+        ctx.http
+            .remove_member_role(event.guild(), user, role, Some("No longer going to event"))
+            .await
+    };
+    if let Err(e) = result {
+        error!(
+            "Could not update event role for {} on event {}: {}",
+            user, event.id, e
+        );
+    }
+}
+
+/// Creates the event's role and a channel only its holders can join, then links it from the
+/// event post.
+async fn go_live(
+    ctx: &SerenityContext,
+    data: &Data,
+    event: &mut Event,
+    kind: EventVoice,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let guild_id = event.guild();
+    let bot = ctx.cache.current_user().id;
+
+    let role = guild_id
+        .create_role(
+            ctx,
+            EditRole::new()
+                .name(format!("Event #{}", event.id))
+                .audit_log_reason("Event role for attendees"),
+        )
+        .await?;
+    for attendee in rsvp::confirmed(pool, event.id).await? {
+        if let Err(e) = ctx
+            .http
+            .add_member_role(guild_id, attendee, role.id, Some("Confirmed for event"))
+            .await
+        {
+            error!("Could not give event role to {}: {}", attendee, e);
+        }
+    }
+
+    let mut overwrites = vec![
+        PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+            kind: PermissionOverwriteType::Role(RoleId::new(guild_id.get())),
+        },
+        PermissionOverwrite {
+            allow: ATTENDEE_PERMISSIONS,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Role(role.id),
+        },
+        // Stages also need the bot to be a stage moderator, to pick who speaks.
+        PermissionOverwrite {
+            allow: ATTENDEE_PERMISSIONS
+                | Permissions::MANAGE_CHANNELS
+                | Permissions::MUTE_MEMBERS
+                | Permissions::MOVE_MEMBERS,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(bot),
+        },
+    ];
+    if event.host_id > 0 {
+        overwrites.push(PermissionOverwrite {
+            allow: ATTENDEE_PERMISSIONS,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(event.host()),
+        });
+    }
+
+    // Keep it next to the post, under the same category.
+    let category = event
+        .channel()
+        .to_channel(ctx)
+        .await
+        .ok()
+        .and_then(Channel::guild)
+        .and_then(|c| c.parent_id);
+    let mut builder = CreateChannel::new(&event.title)
+        .kind(match kind {
+            EventVoice::Stage => ChannelType::Stage,
+            _ => ChannelType::Voice,
+        })
+        .permissions(overwrites)
+        .audit_log_reason("Event channel");
+    if let Some(category) = category {
+        builder = builder.category(category);
+    }
+    let channel = guild_id.create_channel(ctx, builder).await?;
+
+    sqlx::query("INSERT INTO event_channels (channel_id, event_id) VALUES ($1, $2)")
+        .bind(channel.id.get() as i64)
+        .bind(event.id)
+        .execute(pool)
+        .await?;
+    sqlx::query("UPDATE events SET voice_channel_id = $2, event_role_id = $3 WHERE id = $1")
+        .bind(event.id)
+        .bind(channel.id.get() as i64)
+        .bind(role.id.get() as i64)
+        .execute(pool)
+        .await?;
+    event.voice_channel_id = Some(channel.id.get() as i64);
+    event.event_role_id = Some(role.id.get() as i64);
+
+    if kind == EventVoice::Stage {
+        if let Err(e) = channel
+            .create_stage_instance(ctx, CreateStageInstance::new(&event.title))
+            .await
+        {
+            error!("Could not start stage for event {}: {}", event.id, e);
+        }
+        speakers::start_stage(ctx, pool, event, channel.id).await?;
+    }
+
+    event.refresh_post(ctx, pool).await
+}
+
+/// Gives every event that has just started its channel, if its guild wants one.
+async fn start_due(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let due = sqlx::query_as::<_, Event>(
+        "SELECT e.* FROM events e JOIN guild_settings g ON g.guild_id = e.guild_id
+         WHERE e.status = 'published' AND g.event_voice <> 'off' AND NOT e.coverage
+            AND e.voice_channel_id IS NULL
+            AND e.starts_at <= $1 AND e.starts_at + make_interval(mins => e.duration_minutes) > $1
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for mut event in due {
+        let kind = GuildSettings::load(pool, event.guild()).await?.event_voice;
+        if let Err(e) = go_live(ctx, data, &mut event, kind).await {
+            error!("Could not create channel for event {}: {}", event.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes the event role and unlinks the channel from the post once its last channel is gone.
+async fn finish_cleanup(
+    ctx: &SerenityContext,
+    data: &Data,
+    event_id: i64,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let Some(mut event) = Event::fetch(pool, event_id).await? else {
+        return Ok(());
+    };
+    let remaining =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM event_channels WHERE event_id = $1")
+            .bind(event_id)
+            .fetch_one(pool)
+            .await?;
+    if remaining > 0 {
+        return Ok(());
+    }
+
+    if let Some(role) = event.event_role_id {
+        if let Err(e) = event
+            .guild()
+            .delete_role(ctx, RoleId::new(role as u64))
+            .await
+        {
+            error!("Could not delete role for event {}: {}", event_id, e);
+        }
+    }
+    sqlx::query("UPDATE events SET voice_channel_id = NULL, event_role_id = NULL WHERE id = $1")
+        .bind(event_id)
+        .execute(pool)
+        .await?;
+    event.voice_channel_id = None;
+    event.event_role_id = None;
+
+    event.refresh_post(ctx, pool).await
+}
+
+/// Whether anyone is still connected to the voice or stage channel, going by the cache.
+pub fn is_occupied(ctx: &SerenityContext, guild_id: GuildId, channel: ChannelId) -> bool {
+    ctx.cache.guild(guild_id).is_some_and(|g| {
+        g.voice_states
+            .values()
+            .any(|state| state.channel_id == Some(channel))
+    })
+}
+
+/// Creates channels for events that just started, and removes them once an event ended more
+/// than the guild's grace period ago. Channels people are still talking in are left for a later
+/// tick. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    start_due(ctx, data, now).await?;
+
+    let pool = &data.pool;
+    let bot = ctx.cache.current_user().id;
+    let due = sqlx::query_as::<_, (i64, i64, i64, String)>(
+        "SELECT c.channel_id, e.id, e.guild_id, e.title
+         FROM event_channels c
+         JOIN events e ON e.id = c.event_id
+         LEFT JOIN guild_settings g ON g.guild_id = e.guild_id
+         WHERE (e.status IN ('cancelled', 'rejected')
+            OR e.starts_at + make_interval(mins => e.duration_minutes + COALESCE(g.voice_cleanup_minutes, 15)) <= $1)
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for (channel_id, event_id, guild_id, title) in due {
+        let channel = ChannelId::new(channel_id as u64);
+        let guild_id = GuildId::new(guild_id as u64);
+        if is_occupied(ctx, guild_id, channel) {
+            continue;
+        }
+
+        match channel.delete(ctx).await {
+            Ok(_) => {}
+            // Someone already deleted it by hand.
+            Err(SerenityError::Http(HttpError::UnsuccessfulRequest(response)))
+                if response.status_code.as_u16() == 404 => {}
+            Err(e) => {
+                error!(
+                    "Could not remove channel {} for event {}: {}",
+                    channel, event_id, e
+                );
+                continue;
+            }
+        }
+        sqlx::query("DELETE FROM event_channels WHERE channel_id = $1")
+            .bind(channel_id)
+            .execute(pool)
+            .await?;
+
+        audit::record_and_post(
+            ctx,
+            pool,
+            AuditEntry {
+                guild_id: Some(guild_id),
+                actor: bot,
+                action: "event_channel_cleanup",
+                target: Some(channel.get()),
+                details: format!("event {event_id}"),
+                undo: Vec::new(),
+            },
+            &format!(
+                "Removed the voice channel for **{title}** (event #{event_id}) now that it's over."
+            ),
+        )
+        .await?;
+        finish_cleanup(ctx, data, event_id).await?;
+    }
+
+    Ok(())
+}
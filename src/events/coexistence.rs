@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use tracing::{error, info};
+
+use super::history::{first_timestamp, plain};
+use crate::{settings::GuildSettings, Data, SlimeError};
+
+/// Other event bots whose posts are recognised, so the same events aren't run twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtherBot {
+    Sesh,
+    Apollo,
+}
+
+impl OtherBot {
+    /// The bot `user` is, if it's one of the known ones.
+    pub fn of(user: UserId) -> Option<Self> {
+        match user.get() {
+            616754792965865495 => Some(Self::Sesh),
+            475744554910351370 => Some(Self::Apollo),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sesh => "sesh",
+            Self::Apollo => "Apollo",
+        }
+    }
+}
+
+/// The title, description and start of the event an embed from another bot is about. Both put
+/// the time in the description or a field as a Discord timestamp, and only then fall back on the
+/// embed's own timestamp.
+fn read_embed(embed: &Embed) -> Option<(String, String, DateTime<Utc>)> {
+    let title = plain(embed.title.as_deref()?);
+    if !title.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    let starts_at = embed
+        .description
+        .as_deref()
+        .and_then(first_timestamp)
+        .or_else(|| embed.fields.iter().find_map(|f| first_timestamp(&f.value)))
+        .or_else(|| {
+            embed
+                .timestamp
+                .and_then(|t| DateTime::from_timestamp(t.unix_timestamp(), 0))
+        })?;
+    let description = embed
+        .description
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .take(1000)
+        .collect();
+
+    Some((title.chars().take(100).collect(), description, starts_at))
+}
+
+/// The event another bot's `message` announces, read from its first embed that has one.
+pub(super) fn read_post(message: &Message) -> Option<(String, String, DateTime<Utc>)> {
+    message.embeds.iter().find_map(read_embed)
+}
+
+/// Warns admins the first time another event bot posts in one of the guild's event channels,
+/// since members then see events twice and sign up in two places.
+pub async fn noticed(
+    ctx: &SerenityContext,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    let (Some(bot), Some(guild_id)) = (OtherBot::of(message.author.id), message.guild_id) else {
+        return Ok(());
+    };
+    let pool = &data.pool;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let watched = [settings.events_channel(), settings.suggestions_channel()];
+    if !watched.contains(&Some(message.channel_id)) {
+        return Ok(());
+    }
+
+    let first = sqlx::query(
+        "INSERT INTO other_event_bots (guild_id, bot_id, channel_id, noticed_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(message.author.id.get() as i64)
+    .bind(message.channel_id.get() as i64)
+    .bind(data.clock.now())
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0;
+    if !first {
+        return Ok(());
+    }
+    info!(
+        "Noticed {} posting events in guild {}",
+        bot.name(),
+        guild_id
+    );
+
+    // Admins read the audit channel; without one, the system channel is the next best place.
+    let channel = match settings.audit_channel() {
+        Some(channel) => Some(channel),
+        None => guild_id
+            .to_partial_guild(ctx)
+            .await
+            .ok()
+            .and_then(|g| g.system_channel_id),
+    };
+    let Some(channel) = channel else {
+        return Ok(());
+    };
+    let content = format!(
+        "**{}** is also posting events in {}, so members may see events twice and sign up in two \
+         places. `/event import-history` can move its events over, after which it can stop \
+         posting there.",
+        bot.name(),
+        message.channel_id.mention()
+    );
+    if let Err(e) = channel.say(ctx, content).await {
+        error!(
+            "Could not warn guild {} about {}: {}",
+            guild_id,
+            bot.name(),
+            e
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embed(json: &str) -> Embed {
+        serenity::json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn other_bots_embeds_are_read() {
+        let apollo = embed(
+            r#"{"type": "rich", "title": "**Raid night**", "description": "Bring potions",
+                "fields": [{"name": "Time", "value": "<t:1717259400:F> - <t:1717266600:t>",
+                    "inline": false}]}"#,
+        );
+        assert_eq!(
+            read_embed(&apollo),
+            Some((
+                "Raid night".to_string(),
+                "Bring potions".to_string(),
+                DateTime::from_timestamp(1_717_259_400, 0).unwrap()
+            ))
+        );
+
+        let sesh = embed(
+            r#"{"type": "rich", "title": "Movie night",
+                "timestamp": "2024-06-01T16:30:00+00:00"}"#,
+        );
+        assert_eq!(
+            read_embed(&sesh).map(|(_, _, starts_at)| starts_at),
+            DateTime::from_timestamp(1_717_259_400, 0)
+        );
+
+        // Embeds without a title or a time aren't announcements.
+        assert_eq!(
+            read_embed(&embed(r#"{"type": "rich", "title": "Hi"}"#)),
+            None
+        );
+        assert_eq!(
+            read_embed(&embed(
+                r#"{"type": "rich", "description": "<t:1717259400:F>"}"#
+            )),
+            None
+        );
+    }
+}
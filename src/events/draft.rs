@@ -0,0 +1,188 @@
+use poise::{serenity_prelude::*, CreateReply, Modal};
+
+use super::{parse_start_time, tags, Event, EventModal, EventPreview, EventStatus, NewEvent};
+use crate::{
+    forms, preview, quotas, settings::GuildSettings, ApplicationContext, Context, SlimeError,
+};
+
+/// Loads one of the author's drafts in this guild.
+async fn fetch_draft(ctx: Context<'_>, id: i64) -> Result<Event, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    Event::fetch(&ctx.data().pool, id)
+        .await?
+        .filter(|e| {
+            e.guild() == guild_id && e.host() == ctx.author().id && e.status == EventStatus::Draft
+        })
+        .ok_or(SlimeError::EventNotFound(id))
+}
+
+/// Save an event as a draft without posting it.
+#[poise::command(slash_command, guild_only)]
+pub async fn draft(
+    ctx: Context<'_>,
+    #[description = "Name of the event"]
+    #[max_length = 100]
+    title: String,
+    #[description = "Start time, e.g. `2024-03-01 19:30` (UTC) or a Unix timestamp"] when: String,
+    #[description = "Length of the event in minutes (default 60)"]
+    #[min = 1]
+    duration: Option<u32>,
+    #[description = "Maximum number of attendees"]
+    #[min = 1]
+    capacity: Option<u32>,
+    #[description = "What the event is about"]
+    #[max_length = 1000]
+    description: Option<String>,
+    #[description = "Tags, separated by commas, like `game, irl`"]
+    #[max_length = 200]
+    tags: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let starts_at = parse_start_time(&when).ok_or(SlimeError::InvalidTime(when))?;
+    let pool = &ctx.data().pool;
+    quotas::ensure_events(pool, guild_id, 1).await?;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+
+    let new = NewEvent {
+        guild_id,
+        channel_id: settings.events_channel().unwrap_or(ctx.channel_id()),
+        host_id: ctx.author().id,
+        title,
+        description: description.unwrap_or_default(),
+        starts_at,
+        duration_minutes: duration.unwrap_or(60) as i32,
+        capacity: capacity.map(|c| c as i32),
+        tags: tags.as_deref().map(tags::parse).unwrap_or_default(),
+    };
+    let event = Event::insert(pool, new, EventStatus::Draft).await?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Saved draft #{id}. Change it with `/event edit {id}` and post it with `/event publish {id}`.",
+                id = event.id
+            ))
+            .embed(event.post_embed(ctx.serenity_context(), &ctx.data().pool).await?)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Edit one of your drafts.
+#[poise::command(slash_command, guild_only)]
+pub async fn edit(
+    ctx: ApplicationContext<'_>,
+    #[description = "Draft number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_draft(ctx.into(), id).await?;
+
+    let modal_id = ctx.interaction.id.to_string();
+    ctx.interaction
+        .create_response(
+            ctx,
+            EventModal::create(Some(EventModal::from_event(&event)), modal_id.clone()),
+        )
+        .await?;
+    ctx.has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+
+    let Some((submitted, edited)) =
+        forms::collect::<EventModal>(ctx.serenity_context(), ctx.author().id, modal_id).await?
+    else {
+        return Ok(());
+    };
+
+    edited.apply(&mut event);
+    if !event
+        .save_details(&ctx.data().pool, EventStatus::Draft)
+        .await?
+    {
+        let response = CreateInteractionResponseMessage::new().content(format!(
+            "Draft #{} was posted or deleted while you were editing it, so your changes weren't \
+             saved.",
+            event.id
+        ));
+        submitted
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(response.ephemeral(true)),
+            )
+            .await?;
+        return Ok(());
+    }
+    let response = CreateInteractionResponseMessage::new()
+        .content(format!("Updated draft #{}.", event.id))
+        .embed(
+            event
+                .post_embed(ctx.serenity_context(), &ctx.data().pool)
+                .await?,
+        );
+    submitted
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(response.ephemeral(true)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Post one of your drafts.
+#[poise::command(slash_command, guild_only)]
+pub async fn publish(
+    ctx: Context<'_>,
+    #[description = "Draft number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let event = fetch_draft(ctx, id).await?;
+    let settings = GuildSettings::load(&ctx.data().pool, event.guild()).await?;
+
+    preview::run(
+        ctx,
+        EventPreview {
+            event,
+            settings,
+            fresh: false,
+        },
+    )
+    .await
+}
+
+/// List your unpublished drafts.
+#[poise::command(slash_command, guild_only)]
+pub async fn drafts(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    let drafts = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events WHERE guild_id = $1 AND host_id = $2 AND status = $3
+         ORDER BY starts_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(EventStatus::Draft)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    let content = if drafts.is_empty() {
+        "You don't have any drafts. Start one with `/event draft`.".to_string()
+    } else {
+        drafts
+            .iter()
+            .map(|e| {
+                format!(
+                    "#{} **{}** <t:{}:f>",
+                    e.id,
+                    e.title,
+                    e.starts_at.timestamp()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
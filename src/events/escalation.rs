@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{fetch_managed, rsvp::RsvpState, Event, EventStatus};
+use crate::{away, quiet, util::send_dm, Context, Data, SlimeError};
+
+/// How long hosts are left alone between pings at most, however many they've ignored.
+const MAX_BACKOFF_HOURS: i64 = 24;
+
+/// How many waiting members a summary names before only counting the rest.
+const LISTED: usize = 20;
+
+/// Whether the host of an event with members waiting since `oldest` should be pinged again at
+/// `now`. The first ping comes `after` the oldest has waited that long, and each one after waits
+/// twice as long as the last.
+fn escalation_due(
+    oldest: DateTime<Utc>,
+    escalations: i32,
+    escalated_at: Option<DateTime<Utc>>,
+    after: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    match escalated_at {
+        Some(last) if escalations > 0 => {
+            let backoff = after * 2i32.saturating_pow(escalations.min(16) as u32);
+            now - last >= backoff.min(Duration::hours(MAX_BACKOFF_HOURS))
+        }
+        _ => now - oldest >= after,
+    }
+}
+
+/// Members on the waitlist or interested in a full event who've signed up since the host last
+/// looked, longest waiting first.
+async fn pending(pool: &PgPool, event: &Event) -> Result<Vec<(UserId, RsvpState)>, SlimeError> {
+    let pending = sqlx::query_as::<_, (i64, RsvpState)>(
+        "SELECT r.user_id, r.state FROM event_rsvps r JOIN events e ON e.id = r.event_id
+         WHERE r.event_id = $1 AND r.state IN ('waitlist', 'interested') AND r.user_id > 0
+            AND r.created_at > COALESCE(e.host_reviewed_at, '-infinity')
+         ORDER BY r.created_at, r.user_id",
+    )
+    .bind(event.id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(pending
+        .into_iter()
+        .map(|(id, state)| (UserId::new(id as u64), state))
+        .collect())
+}
+
+/// What the host is told about the members waiting on them.
+fn summary(
+    event: &Event,
+    pending: &[(UserId, RsvpState)],
+    away: &HashMap<UserId, DateTime<Utc>>,
+) -> String {
+    let mut summary = format!(
+        "**{}** is full, and {} member(s) are waiting on you:",
+        event.title,
+        pending.len()
+    );
+    for (user, state) in pending.iter().take(LISTED) {
+        let waiting = match state {
+            RsvpState::Waitlist => "on the waitlist",
+            _ => "interested",
+        };
+        let marker = away.get(user).map(|&until| away::marker(until));
+        summary.push_str(&format!(
+            "\n- {} {waiting}{}",
+            user.mention(),
+            marker.unwrap_or_default()
+        ));
+    }
+    if pending.len() > LISTED {
+        summary.push_str(&format!("\n- and {} more", pending.len() - LISTED));
+    }
+    summary.push_str(&format!(
+        "\n\nTurn down anyone you can't take with `/event reject {id}`, or mark these as seen \
+         with `/event pending {id}`.",
+        id = event.id
+    ));
+    summary
+}
+
+/// Marks the host as having dealt with everyone waiting so far, so pings start over for anyone
+/// who signs up after.
+pub async fn acted(pool: &PgPool, event_id: i64) -> Result<(), SlimeError> {
+    sqlx::query(
+        "UPDATE events SET host_reviewed_at = now(), escalations = 0, escalated_at = NULL
+         WHERE id = $1",
+    )
+    .bind(event_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Pings hosts of full events about members who've been left waiting on them, backing off each
+/// time they don't act. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let waiting = sqlx::query_as::<_, (i64, i32, Option<DateTime<Utc>>, DateTime<Utc>, i32)>(
+        "SELECT e.id, e.escalations, e.escalated_at, MIN(r.created_at),
+            COALESCE(g.escalation_minutes, 240)
+         FROM events e
+         JOIN event_rsvps r ON r.event_id = e.id
+         LEFT JOIN guild_settings g ON g.guild_id = e.guild_id
+         WHERE e.status = 'published' AND e.starts_at > $1
+            AND e.capacity IS NOT NULL AND e.confirmed_count >= e.capacity
+            AND r.state IN ('waitlist', 'interested') AND r.user_id > 0
+            AND r.created_at > COALESCE(e.host_reviewed_at, '-infinity')
+            AND COALESCE(g.escalation_minutes, 240) > 0
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         GROUP BY e.id, g.escalation_minutes",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for (event_id, escalations, escalated_at, oldest, minutes) in waiting {
+        let after = Duration::minutes(minutes as i64);
+        if !escalation_due(oldest, escalations, escalated_at, after, now) {
+            continue;
+        }
+        let Some(event) = Event::fetch(pool, event_id).await? else {
+            continue;
+        };
+        // The ping waits for the host's quiet hours to end rather than being skipped.
+        let host = event.host();
+        if !quiet::quiet_now(pool, event.guild(), &[host], now)
+            .await?
+            .is_empty()
+        {
+            continue;
+        }
+
+        // Claimed before sending, so a slow run can't be picked up again by the next tick.
+        let claimed = sqlx::query(
+            "UPDATE events SET escalations = escalations + 1, escalated_at = $2
+             WHERE id = $1 AND escalations = $3",
+        )
+        .bind(event_id)
+        .bind(now)
+        .bind(escalations)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            continue;
+        }
+
+        let pending = pending(pool, &event).await?;
+        let users = pending.iter().map(|p| p.0).collect::<Vec<_>>();
+        let away = away::away_now(pool, &users, now).await?;
+        data.calls.turn().await;
+        let message = CreateMessage::new().content(summary(&event, &pending, &away));
+        if let Err(e) = send_dm(ctx, host, message).await {
+            error!("Could not ping host of event {}: {}", event_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// See who's waiting on you for a full event, and mark them as seen.
+#[poise::command(slash_command, guild_only, rename = "pending")]
+pub async fn pending_command(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let pool = &ctx.data().pool;
+
+    let pending = pending(pool, &event).await?;
+    acted(pool, event.id).await?;
+    let content = if pending.is_empty() {
+        format!("Nobody new is waiting on you for **{}**.", event.title)
+    } else {
+        let users = pending.iter().map(|p| p.0).collect::<Vec<_>>();
+        let away = away::away_now(pool, &users, ctx.data().clock.now()).await?;
+        summary(&event, &pending, &away)
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts_are_pinged_with_growing_gaps() {
+        let start = Utc::now();
+        let after = Duration::hours(2);
+        let at = |hours| start + Duration::hours(hours);
+
+        assert!(!escalation_due(start, 0, None, after, at(1)));
+        assert!(escalation_due(start, 0, None, after, at(2)));
+        // Pinged at 2h, then 4h later, then 8h after that.
+        assert!(!escalation_due(start, 1, Some(at(2)), after, at(5)));
+        assert!(escalation_due(start, 1, Some(at(2)), after, at(6)));
+        assert!(!escalation_due(start, 2, Some(at(6)), after, at(13)));
+        assert!(escalation_due(start, 2, Some(at(6)), after, at(14)));
+        // However many were ignored, a day is the longest gap.
+        assert!(escalation_due(start, 10, Some(at(14)), after, at(38)));
+    }
+}
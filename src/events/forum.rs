@@ -0,0 +1,145 @@
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{Event, EventStatus};
+use crate::SlimeError;
+
+/// Whether the event has been and gone: closed out by its host, or moved to the archive.
+fn over(event: &Event) -> bool {
+    event.status == EventStatus::Completed || event.archive_message_id.is_some()
+}
+
+/// Whether the event is done with, so its forum post is closed to new replies.
+fn closed(event: &Event) -> bool {
+    over(event) || matches!(event.status, EventStatus::Cancelled | EventStatus::Rejected)
+}
+
+/// The forum post's name: the event's title, marked once it's over or called off.
+fn name(event: &Event) -> String {
+    let name = if over(event) {
+        format!("[Over] {}", event.title)
+    } else if closed(event) {
+        format!("[Cancelled] {}", event.title)
+    } else {
+        event.title.clone()
+    };
+    name.chars().take(100).collect()
+}
+
+/// The forum's tags named like one of the event's. Forums can only use tags they already have,
+/// so event tags without a matching one are left off.
+async fn matching_tags(ctx: &SerenityContext, forum: ChannelId, event: &Event) -> Vec<ForumTagId> {
+    let Ok(Some(forum)) = forum.to_channel(ctx).await.map(Channel::guild) else {
+        return Vec::new();
+    };
+    forum
+        .available_tags
+        .iter()
+        .filter(|tag| event.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag.name)))
+        .map(|tag| tag.id)
+        .collect()
+}
+
+/// What the post was last written as, to compare against before editing it again.
+fn fingerprint(name: &str, closed: bool, tags: &[ForumTagId]) -> String {
+    let tags = tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    format!("{name}\n{closed}\n{}", tags.join(","))
+}
+
+/// Starts a post for the event in `forum` with `post` as its first message, tagged like the
+/// event. Returns that first message.
+pub async fn open(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &mut Event,
+    forum: ChannelId,
+    post: CreateMessage,
+) -> Result<Message, SlimeError> {
+    let name = name(event);
+    let tags = matching_tags(ctx, forum, event).await;
+    let thread = forum
+        .create_forum_post(
+            ctx,
+            CreateForumPost::new(&name, post).set_applied_tags(tags.clone()),
+        )
+        .await?;
+    sqlx::query("UPDATE events SET forum_thread_id = $2, forum_fingerprint = $3 WHERE id = $1")
+        .bind(event.id)
+        .bind(thread.id.get() as i64)
+        .bind(fingerprint(&name, false, &tags))
+        .execute(pool)
+        .await?;
+    event.forum_thread_id = Some(thread.id.get() as i64);
+
+    // A forum post's first message shares the post's ID.
+    Ok(thread
+        .id
+        .message(ctx, MessageId::new(thread.id.get()))
+        .await?)
+}
+
+/// Brings the event's forum post in line with the event: its name, its tags, and closing it
+/// once the event is over or called off. Does nothing if nothing changed since it was last
+/// written. Failures are only logged, since the event itself is fine either way.
+pub async fn sync(ctx: &SerenityContext, pool: &PgPool, event: &Event) {
+    let Some(thread) = event.forum_thread_id.map(|id| ChannelId::new(id as u64)) else {
+        return;
+    };
+    let synced = async {
+        let forum = thread
+            .to_channel(ctx)
+            .await?
+            .guild()
+            .and_then(|t| t.parent_id);
+        let tags = match forum {
+            Some(forum) => matching_tags(ctx, forum, event).await,
+            None => Vec::new(),
+        };
+        let (name, closed) = (name(event), closed(event));
+        let fingerprint = fingerprint(&name, closed, &tags);
+        let written = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT forum_fingerprint FROM events WHERE id = $1",
+        )
+        .bind(event.id)
+        .fetch_one(pool)
+        .await?;
+        if written.as_ref() == Some(&fingerprint) {
+            return Ok(());
+        }
+
+        if closed {
+            // Nobody should keep signing up on a post for an event that's over. The post is
+            // rendered again too, for guilds whose posts say so in words.
+            thread
+                .edit_message(
+                    ctx,
+                    MessageId::new(thread.get()),
+                    EditMessage::new()
+                        .embed(event.post_embed(ctx, pool).await?)
+                        .components(vec![]),
+                )
+                .await?;
+        }
+        thread
+            .edit_thread(
+                ctx,
+                EditThread::new()
+                    .name(&name)
+                    .applied_tags(tags)
+                    .locked(closed)
+                    .archived(closed),
+            )
+            .await?;
+        sqlx::query("UPDATE events SET forum_fingerprint = $2 WHERE id = $1")
+            .bind(event.id)
+            .bind(&fingerprint)
+            .execute(pool)
+            .await?;
+        Ok::<_, SlimeError>(())
+    };
+    if let Err(e) = synced.await {
+        error!("Could not update forum post of event {}: {}", event.id, e);
+    }
+}
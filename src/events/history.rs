@@ -0,0 +1,506 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use super::{
+    adopt,
+    coexistence::{self, OtherBot},
+    submit_or_publish, Event, EventStatus, NewEvent,
+};
+use crate::{
+    custom_id::{CustomId, Kind},
+    discord::Discord,
+    i18n, make_uuid_buttons, quotas,
+    settings::GuildSettings,
+    Context, Data, SlimeError,
+};
+
+/// Most messages read back through a channel's history in one scan.
+const SCAN_LIMIT: usize = 1000;
+
+/// Members brought over from the reactions on each old post, at most.
+const REACTORS_LIMIT: u8 = 100;
+
+/// An event read out of a post the bot didn't make, either by a member before the bot was around
+/// or by another event bot.
+#[derive(Debug, Clone, PartialEq)]
+struct Announcement {
+    message: MessageId,
+    host: UserId,
+    title: String,
+    description: String,
+    starts_at: DateTime<Utc>,
+    /// The bot that posted it, if it wasn't a member.
+    from: Option<OtherBot>,
+}
+
+/// The first Discord timestamp in `content`, like `<t:1717259400:F>`.
+pub(super) fn first_timestamp(content: &str) -> Option<DateTime<Utc>> {
+    content.match_indices("<t:").find_map(|(at, _)| {
+        let rest = &content[at + 3..];
+        let end = rest.find([':', '>'])?;
+        let seconds = rest[..end].parse::<i64>().ok()?;
+        DateTime::from_timestamp(seconds, 0)
+    })
+}
+
+/// `line` without timestamps, mentions of everyone and the markdown around headings.
+pub(super) fn plain(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some(at) = rest.find("<t:") {
+        text.push_str(&rest[..at]);
+        rest = rest[at..].find('>').map_or("", |end| &rest[at + end + 1..]);
+    }
+    text.push_str(rest);
+
+    text.replace("@everyone", "")
+        .replace("@here", "")
+        .replace(['*', '_', '~', '`', '|'], "")
+        .trim_start_matches(['#', '>', '-', ' '])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The title and start of the event `content` announced, if it looks like an announcement for
+/// one that has already happened by `now`. The first line with words on it is taken as the title.
+fn read_post(content: &str, now: DateTime<Utc>) -> Option<(String, DateTime<Utc>)> {
+    let starts_at = first_timestamp(content).filter(|t| *t < now)?;
+    let title = content
+        .lines()
+        .map(plain)
+        .find(|line| line.chars().any(char::is_alphanumeric))?;
+
+    Some((title.chars().take(100).collect(), starts_at))
+}
+
+/// Announcements in `channel` that aren't imported yet, oldest first. Members' posts are only
+/// read with `consent`, and only for events that have happened. Other bots' posts are hosted by
+/// `importer`, since they don't say who the host was in a way that can be read back.
+async fn scan(
+    discord: &impl Discord,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel: ChannelId,
+    now: DateTime<Utc>,
+    importer: UserId,
+    consent: bool,
+) -> Result<Vec<Announcement>, SlimeError> {
+    let imported = sqlx::query_scalar::<_, i64>(
+        "SELECT imported_message_id FROM events
+         WHERE guild_id = $1 AND imported_message_id IS NOT NULL",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect::<HashSet<_>>();
+
+    let mut found = Vec::new();
+    let mut before = None;
+    let mut read = 0;
+    while read < SCAN_LIMIT {
+        let page = discord.messages(channel, before, 100).await?;
+        read += page.len();
+        for message in &page {
+            if imported.contains(&(message.id.get() as i64)) {
+                continue;
+            }
+            let announcement = match OtherBot::of(message.author.id) {
+                Some(bot) => {
+                    coexistence::read_post(message).map(|(title, description, starts_at)| {
+                        Announcement {
+                            message: message.id,
+                            host: importer,
+                            title,
+                            description,
+                            starts_at,
+                            from: Some(bot),
+                        }
+                    })
+                }
+                None if consent && !message.author.bot => {
+                    read_post(&message.content, now).map(|(title, starts_at)| Announcement {
+                        message: message.id,
+                        host: message.author.id,
+                        title,
+                        description: String::new(),
+                        starts_at,
+                        from: None,
+                    })
+                }
+                None => None,
+            };
+            found.extend(announcement);
+        }
+        match page.last() {
+            Some(oldest) if page.len() == 100 => before = Some(oldest.id),
+            _ => break,
+        }
+    }
+    // Read newest first.
+    found.reverse();
+
+    Ok(found)
+}
+
+/// Members who reacted to a post, taken as having gone. Only the most popular reaction counts,
+/// since the rest tend to be comments rather than sign-ups.
+async fn reactors(ctx: &SerenityContext, channel: ChannelId, message: MessageId) -> Vec<UserId> {
+    let Ok(message) = channel.message(ctx, message).await else {
+        return Vec::new();
+    };
+    let Some(reaction) = message.reactions.iter().max_by_key(|r| r.count) else {
+        return Vec::new();
+    };
+    match message
+        .reaction_users(
+            ctx,
+            reaction.reaction_type.clone(),
+            Some(REACTORS_LIMIT),
+            None,
+        )
+        .await
+    {
+        Ok(users) => users.into_iter().filter(|u| !u.bot).map(|u| u.id).collect(),
+        Err(e) => {
+            error!("Could not list reactions on message {}: {}", message.id, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Stores `post` as a completed event, with whoever reacted to it as having gone.
+async fn import_post(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel: ChannelId,
+    post: &Announcement,
+) -> Result<(), SlimeError> {
+    let going = reactors(ctx, channel, post.message).await;
+    let new = NewEvent {
+        guild_id,
+        channel_id: channel,
+        host_id: post.host,
+        title: post.title.clone(),
+        description: post.description.clone(),
+        starts_at: post.starts_at,
+        duration_minutes: 60,
+        capacity: None,
+        tags: Vec::new(),
+    };
+    let event = Event::insert(pool, new, EventStatus::Completed).await?;
+
+    let going = going.iter().map(|u| u.get() as i64).collect::<Vec<_>>();
+    sqlx::query(
+        "INSERT INTO event_rsvps (event_id, user_id, state, created_at)
+         SELECT $1, unnest($2::bigint[]), 'confirmed', $3
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(event.id)
+    .bind(&going)
+    .bind(post.starts_at)
+    .execute(pool)
+    .await?;
+    sqlx::query("UPDATE events SET imported_message_id = $2, confirmed_count = $3 WHERE id = $1")
+        .bind(event.id)
+        .bind(post.message.get() as i64)
+        .bind(going.len() as i32)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Gives an upcoming event another bot announced a post of its own, posting it or sending it for
+/// approval like any other.
+async fn import_upcoming(
+    ctx: Context<'_>,
+    settings: &GuildSettings,
+    post: &Announcement,
+) -> Result<(), SlimeError> {
+    let pool = &ctx.data().pool;
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let new = NewEvent {
+        guild_id,
+        channel_id: settings.events_channel().unwrap_or(ctx.channel_id()),
+        host_id: post.host,
+        title: post.title.clone(),
+        description: post.description.clone(),
+        starts_at: post.starts_at,
+        duration_minutes: 60,
+        capacity: None,
+        tags: Vec::new(),
+    };
+    let mut event = Event::insert(pool, new, EventStatus::Draft).await?;
+    sqlx::query("UPDATE events SET imported_message_id = $2 WHERE id = $1")
+        .bind(event.id)
+        .bind(post.message.get() as i64)
+        .execute(pool)
+        .await?;
+    submit_or_publish(ctx, settings, &mut event).await?;
+
+    Ok(())
+}
+
+/// Bring in events from before the bot: Discord's event list, old announcements and other bots'
+/// posts.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "import-history",
+    required_permissions = "MANAGE_EVENTS"
+)]
+pub async fn import_history(
+    ctx: Context<'_>,
+    #[description = "Channel events were announced in, if not the events channel"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    ctx.defer_ephemeral().await?;
+
+    let pool = &ctx.data().pool;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let channel = channel
+        .map(|c| c.id)
+        .or(settings.events_channel())
+        .unwrap_or(ctx.channel_id());
+    let now = ctx.data().clock.now();
+
+    let scheduled = adopt::unmanaged(ctx, pool, guild_id).await?;
+    // Old announcements are what members wrote, so they're only read with consent.
+    let consent = settings.message_content_consent;
+    let (posts, upcoming): (Vec<_>, Vec<_>) = scan(
+        ctx.serenity_context(),
+        pool,
+        guild_id,
+        channel,
+        now,
+        ctx.author().id,
+        consent,
+    )
+    .await?
+    .into_iter()
+    .partition(|post| post.starts_at <= now);
+    // Bots that made a scheduled event as well are covered by taking that over.
+    let upcoming = upcoming
+        .into_iter()
+        .filter(|post| {
+            !scheduled.iter().any(|s| {
+                s.name == post.title && s.start_time.unix_timestamp() == post.starts_at.timestamp()
+            })
+        })
+        .collect::<Vec<_>>();
+    if scheduled.is_empty() && posts.is_empty() && upcoming.is_empty() {
+        let mut content = format!(
+            "Found nothing to import: Discord's event list has no events the bot isn't running, \
+             and no posts in {} announce an event.",
+            channel.mention()
+        );
+        if !consent {
+            content.push_str(
+                " Reading members' past announcements needs the bot to read messages, which an \
+                 admin can allow with `/settings message_content`.",
+            );
+        }
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    quotas::ensure_events(pool, guild_id, scheduled.len() + upcoming.len()).await?;
+
+    let mut preview = String::new();
+    if !scheduled.is_empty() {
+        preview.push_str(&format!(
+            "**{}** upcoming event(s) from Discord's event list will get RSVP posts:\n",
+            scheduled.len()
+        ));
+        for s in scheduled.iter().take(10) {
+            preview.push_str(&format!(
+                "- **{}** <t:{}:f>\n",
+                s.name,
+                s.start_time.unix_timestamp()
+            ));
+        }
+        if scheduled.len() > 10 {
+            preview.push_str(&format!("…and {} more.\n", scheduled.len() - 10));
+        }
+    }
+    if !upcoming.is_empty() {
+        preview.push_str(&format!(
+            "**{}** upcoming event(s) posted by other bots will get RSVP posts too:\n",
+            upcoming.len()
+        ));
+        for post in upcoming.iter().take(10) {
+            let bot = post.from.map_or("", OtherBot::name);
+            preview.push_str(&format!(
+                "- **{}** {} ({bot})\n",
+                post.title,
+                i18n::timestamp(post.starts_at, FormattedTimestampStyle::ShortDateTime)
+            ));
+        }
+        if upcoming.len() > 10 {
+            preview.push_str(&format!("…and {} more.\n", upcoming.len() - 10));
+        }
+    }
+    if !posts.is_empty() {
+        preview.push_str(&format!(
+            "**{}** past event(s) announced in {} will be kept as history:\n",
+            posts.len(),
+            channel.mention()
+        ));
+        for post in posts.iter().rev().take(10) {
+            preview.push_str(&format!(
+                "- **{}** {}\n",
+                post.title,
+                i18n::timestamp(post.starts_at, FormattedTimestampStyle::ShortDate)
+            ));
+        }
+        if posts.len() > 10 {
+            preview.push_str(&format!("…and {} older.\n", posts.len() - 10));
+        }
+    }
+    preview.push_str("Continue?");
+
+    let id = ctx.id();
+    let yes_uuid = CustomId::for_invocation(Kind::Confirm, "yes", id).to_string();
+    let no_uuid = CustomId::for_invocation(Kind::Confirm, "no", id).to_string();
+    ctx.send(
+        CreateReply::default()
+            .content(&preview)
+            .components(vec![make_uuid_buttons(&yes_uuid, &no_uuid, false)])
+            .ephemeral(true),
+    )
+    .await?;
+
+    let Some(interaction) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .timeout(std::time::Duration::from_secs(120))
+        .author_id(ctx.author().id)
+        .custom_ids(vec![yes_uuid.clone(), no_uuid.clone()])
+        .await
+    else {
+        return Ok(());
+    };
+
+    let confirmed = interaction.data.custom_id == yes_uuid;
+    let content = if confirmed {
+        "Importing…"
+    } else {
+        "Import cancelled."
+    };
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![make_uuid_buttons("yes_disabled", "no_disabled", true)]),
+            ),
+        )
+        .await?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    let mut adopted = 0;
+    for s in &scheduled {
+        let host = s.creator_id.unwrap_or(ctx.author().id);
+        match adopt::take_over(ctx, &settings, s, host).await {
+            Ok(_) => adopted += 1,
+            Err(e) => error!("Could not adopt scheduled event {}: {}", s.id, e),
+        }
+    }
+    let mut moved = 0;
+    for post in &upcoming {
+        match import_upcoming(ctx, &settings, post).await {
+            Ok(()) => moved += 1,
+            Err(e) => error!("Could not import message {}: {}", post.message, e),
+        }
+    }
+    let mut imported = 0;
+    for post in &posts {
+        match import_post(ctx.serenity_context(), pool, guild_id, channel, post).await {
+            Ok(()) => imported += 1,
+            Err(e) => error!("Could not import message {}: {}", post.message, e),
+        }
+    }
+
+    info!(
+        "Imported history in guild {}: {} adopted, {} from other bots, {} past",
+        guild_id, adopted, moved, imported
+    );
+    let mut summary = format!(
+        "Took over {adopted} upcoming event(s), reposted {moved} from other bots and imported \
+         {imported} past one(s)."
+    );
+    if moved > 0 {
+        summary.push_str(
+            " The other bots' posts are still up, so remove them before members sign up twice.",
+        );
+    }
+    interaction
+        .create_followup(
+            ctx,
+            CreateInteractionResponseFollowup::new()
+                .content(summary)
+                .ephemeral(true),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Offers a guild the bot has just been added to a way to bring its past events along, so there's
+/// history to work from. Guilds the bot already has events for aren't asked again.
+pub async fn offer(ctx: &SerenityContext, data: &Data, guild: &Guild) -> Result<(), SlimeError> {
+    let known =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM events WHERE guild_id = $1)")
+            .bind(guild.id.get() as i64)
+            .fetch_one(&data.pool)
+            .await?;
+    let Some(channel) = guild.system_channel_id.filter(|_| !known) else {
+        return Ok(());
+    };
+
+    let content = "Thanks for adding me! If this server already runs events, `/event \
+                   import-history` takes over the ones in Discord's event list and keeps past \
+                   announcements as history, for stats and suggestions to work from.";
+    if let Err(e) = channel.say(ctx, content).await {
+        error!(
+            "Could not offer importing history in guild {}: {}",
+            guild.id, e
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn past_announcements_are_read() {
+        let now = DateTime::from_timestamp(1_717_300_000, 0).unwrap();
+        let post = "@everyone\n## **Board game night** <t:1717259400:F>\nBring snacks!";
+        assert_eq!(
+            read_post(post, now),
+            Some((
+                "Board game night".to_string(),
+                DateTime::from_timestamp(1_717_259_400, 0).unwrap()
+            ))
+        );
+
+        // Upcoming events, and posts without a time, aren't history.
+        assert_eq!(read_post("Movie night <t:1717400000:R>", now), None);
+        assert_eq!(read_post("Anyone up for a game later?", now), None);
+        assert_eq!(read_post("<t:1717259400:F>", now), None);
+    }
+}
@@ -0,0 +1,370 @@
+use poise::{serenity_prelude::*, CreateReply};
+
+use super::{parse_start_time, submit_or_publish, Event, EventStatus, NewEvent};
+use crate::{
+    custom_id::{CustomId, Kind},
+    i18n, make_uuid_buttons, quotas,
+    settings::GuildSettings,
+    Context, SlimeError,
+};
+
+/// Upper bound on rows per import, so one file can't flood the events channel.
+const MAX_IMPORT_ROWS: usize = 50;
+
+/// Files bigger than this are turned away before they're downloaded. Fifty full rows fit with
+/// plenty to spare.
+const MAX_IMPORT_BYTES: u32 = 256 * 1024;
+
+/// Columns recognised in an import file. Only `title` and `time` are required.
+const COLUMNS: [&str; 5] = ["title", "time", "duration", "capacity", "description"];
+
+/// A row that failed validation, with the line number as the host sees it in their editor.
+#[derive(Debug)]
+struct RowError {
+    line: u64,
+    reason: String,
+}
+
+/// Parses an import file into events that are ready to insert, or every problem found in it.
+fn parse_import(bytes: &[u8], template: &NewEvent) -> Result<Vec<NewEvent>, Vec<RowError>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(bytes);
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            return Err(vec![RowError {
+                line: 1,
+                reason: e.to_string(),
+            }])
+        }
+    };
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let [title, time, duration, capacity, description] = COLUMNS.map(column);
+    let (Some(title), Some(time)) = (title, time) else {
+        return Err(vec![RowError {
+            line: 1,
+            reason: format!(
+                "the header row must include `title` and `time` (known columns: {})",
+                COLUMNS.join(", ")
+            ),
+        }]);
+    };
+
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(RowError {
+                    line: e.position().map_or(0, |p| p.line()),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .filter(|value| !value.is_empty())
+        };
+
+        let row = (|| {
+            let title = field(Some(title)).ok_or("missing title")?;
+            if title.chars().count() > 100 {
+                return Err("title is longer than 100 characters".to_string());
+            }
+            let when = field(Some(time)).ok_or("missing time")?;
+            let starts_at =
+                parse_start_time(when).ok_or_else(|| format!("can't read `{when}` as a time"))?;
+            let parse_count = |value: &str| {
+                value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("`{value}` is not a positive whole number"))
+            };
+            let duration_minutes = field(duration).map(parse_count).transpose()?.unwrap_or(60);
+            let capacity = field(capacity).map(parse_count).transpose()?;
+            let description = field(description).unwrap_or_default();
+            if description.chars().count() > 1000 {
+                return Err("description is longer than 1000 characters".to_string());
+            }
+
+            Ok(NewEvent {
+                title: title.to_string(),
+                description: description.to_string(),
+                starts_at,
+                duration_minutes,
+                capacity,
+                ..template.clone()
+            })
+        })();
+
+        match row {
+            Ok(event) => events.push(event),
+            Err(reason) => errors.push(RowError { line, reason }),
+        }
+    }
+
+    if events.len() + errors.len() > MAX_IMPORT_ROWS {
+        errors.push(RowError {
+            line: 0,
+            reason: format!("files can contain at most {MAX_IMPORT_ROWS} events"),
+        });
+    }
+    if events.is_empty() && errors.is_empty() {
+        errors.push(RowError {
+            line: 0,
+            reason: "the file has no events in it".to_string(),
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(events)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Create many events at once from a CSV file.
+#[poise::command(slash_command, guild_only)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "CSV with columns title, time, duration, capacity, description"]
+    file: Attachment,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    if file.size > MAX_IMPORT_BYTES {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "That file is too big to import, it can be at most {} KiB.",
+                    MAX_IMPORT_BYTES / 1024
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+
+    let pool = &ctx.data().pool;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let template = NewEvent {
+        guild_id,
+        channel_id: settings.events_channel().unwrap_or(ctx.channel_id()),
+        host_id: ctx.author().id,
+        title: String::new(),
+        description: String::new(),
+        starts_at: Default::default(),
+        duration_minutes: 60,
+        capacity: None,
+        tags: Vec::new(),
+    };
+
+    let bytes = file.download().await?;
+    let events = match parse_import(&bytes, &template) {
+        Ok(events) => events,
+        Err(errors) => {
+            let mut report = format!(
+                "Nothing was imported, **{}** problem(s) need fixing first:\n",
+                errors.len()
+            );
+            for error in errors.iter().take(20) {
+                if error.line == 0 {
+                    report.push_str(&format!("- {}\n", error.reason));
+                } else {
+                    report.push_str(&format!("- line {}: {}\n", error.line, error.reason));
+                }
+            }
+            if errors.len() > 20 {
+                report.push_str(&format!("…and {} more.", errors.len() - 20));
+            }
+            ctx.send(CreateReply::default().content(report).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    quotas::ensure_events(pool, guild_id, events.len()).await?;
+
+    let mut preview = format!("Ready to create **{}** event(s):\n", events.len());
+    for event in events.iter().take(15) {
+        preview.push_str(&format!(
+            "- **{}** {}\n",
+            event.title,
+            i18n::timestamp(event.starts_at, FormattedTimestampStyle::ShortDateTime)
+        ));
+    }
+    if events.len() > 15 {
+        preview.push_str(&format!("…and {} more.\n", events.len() - 15));
+    }
+    preview.push_str("Continue?");
+
+    let id = ctx.id();
+    let yes_uuid = CustomId::for_invocation(Kind::Confirm, "yes", id).to_string();
+    let no_uuid = CustomId::for_invocation(Kind::Confirm, "no", id).to_string();
+    ctx.send(
+        CreateReply::default()
+            .content(&preview)
+            .components(vec![make_uuid_buttons(&yes_uuid, &no_uuid, false)])
+            .ephemeral(true),
+    )
+    .await?;
+
+    let Some(interaction) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .timeout(std::time::Duration::from_secs(120))
+        .author_id(ctx.author().id)
+        .custom_ids(vec![yes_uuid.clone(), no_uuid.clone()])
+        .await
+    else {
+        return Ok(());
+    };
+
+    let confirmed = interaction.data.custom_id == yes_uuid;
+    let content = if confirmed {
+        "Importing…"
+    } else {
+        "Import cancelled."
+    };
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![make_uuid_buttons("yes_disabled", "no_disabled", true)]),
+            ),
+        )
+        .await?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    let total = events.len();
+    // All or nothing, so a failure partway doesn't leave half the file imported.
+    let mut tx = pool.begin().await?;
+    let mut imported = Vec::with_capacity(total);
+    for new in events {
+        imported.push(Event::insert_untagged(&mut tx, new, EventStatus::Draft).await?);
+    }
+    tx.commit().await?;
+
+    let mut queued = 0;
+    for mut event in imported {
+        submit_or_publish(ctx, &settings, &mut event).await?;
+        if event.status == EventStatus::Pending {
+            queued += 1;
+        }
+    }
+
+    let summary = if queued > 0 {
+        format!("Imported {total} event(s); {queued} are waiting for moderator approval.")
+    } else {
+        format!("Imported and posted {total} event(s).")
+    };
+    interaction
+        .create_followup(
+            ctx,
+            CreateInteractionResponseFollowup::new()
+                .content(summary)
+                .ephemeral(true),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> NewEvent {
+        NewEvent {
+            guild_id: GuildId::new(1),
+            channel_id: ChannelId::new(2),
+            host_id: UserId::new(3),
+            title: String::new(),
+            description: String::new(),
+            starts_at: Default::default(),
+            duration_minutes: 60,
+            capacity: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn problems(csv: &str) -> Vec<(u64, String)> {
+        parse_import(csv.as_bytes(), &template())
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.line, error.reason))
+            .collect()
+    }
+
+    #[test]
+    fn rows_become_events() {
+        let csv = "Title,Time,Capacity,Description\n\
+                   Game night, 2024-03-01 19:30 ,8,Bring snacks\n\
+                   Movie,1709321400,,\n";
+        let events = parse_import(csv.as_bytes(), &template()).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].title, "Game night");
+        assert_eq!(events[0].starts_at.timestamp(), 1709321400);
+        assert_eq!(events[0].capacity, Some(8));
+        assert_eq!(events[0].description, "Bring snacks");
+        assert_eq!(events[1].duration_minutes, 60);
+        assert_eq!(events[1].capacity, None);
+        assert_eq!(events[1].host_id, UserId::new(3));
+    }
+
+    #[test]
+    fn files_without_the_required_columns_are_refused() {
+        let errors = problems("title,duration\nGame night,60\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 1);
+        assert!(errors[0].1.contains("`title` and `time`"));
+
+        assert_eq!(
+            problems("title,time\n")[0].1,
+            "the file has no events in it"
+        );
+    }
+
+    #[test]
+    fn every_bad_row_is_reported_by_line() {
+        let csv = "title,time,duration,capacity\n\
+                   Fine,2024-03-01 19:30,,\n\
+                   Bad date,next tuesday,,\n\
+                   ,2024-03-01 19:30,,\n\
+                   Empty,2024-03-01 19:30,0,\n\
+                   Short row\n";
+        let errors = problems(csv);
+
+        assert_eq!(
+            errors,
+            [
+                (3, "can't read `next tuesday` as a time".to_string()),
+                (4, "missing title".to_string()),
+                (5, "`0` is not a positive whole number".to_string()),
+                (6, "missing time".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_files_are_refused() {
+        let mut csv = "title,time\n".to_string();
+        for n in 0..=MAX_IMPORT_ROWS {
+            csv.push_str(&format!("Event {n},2024-03-01 19:30\n"));
+        }
+        let errors = problems(&csv);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.contains("at most"));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+
+use poise::serenity_prelude::*;
+use serenity::{client::Context as SerenityContext, http::UserPagination};
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{refresh, rsvp, Event};
+use crate::{Data, SlimeError};
+
+/// How often every upcoming event's interest is checked against Discord's, to catch changes made
+/// while no instance was listening.
+pub const INTERVAL_MINUTES: i64 = 30;
+
+/// Discord hands out a scheduled event's interested users this many at a time.
+const PAGE_SIZE: u64 = 100;
+
+/// The published event mirrored into `scheduled`, if any.
+async fn mirrored(pool: &PgPool, scheduled: ScheduledEventId) -> Result<Option<Event>, SlimeError> {
+    Ok(sqlx::query_as::<_, Event>(
+        "SELECT * FROM events WHERE scheduled_event_id = $1 AND status = 'published'",
+    )
+    .bind(scheduled.get() as i64)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Someone pressed "Interested" on a scheduled event. Called from the event handler.
+pub async fn user_added(
+    ctx: &SerenityContext,
+    data: &Data,
+    added: &GuildScheduledEventUserAddEvent,
+) -> Result<(), SlimeError> {
+    let Some(event) = mirrored(&data.pool, added.scheduled_event_id).await? else {
+        return Ok(());
+    };
+    if added.user_id == ctx.cache.current_user().id {
+        return Ok(());
+    }
+    if rsvp::noticed(&data.pool, &event, added.user_id).await? {
+        refresh::soon(ctx, &data.pool, event.id);
+    }
+    Ok(())
+}
+
+/// Someone took back their interest in a scheduled event. Called from the event handler.
+pub async fn user_removed(
+    ctx: &SerenityContext,
+    data: &Data,
+    removed: &GuildScheduledEventUserRemoveEvent,
+) -> Result<(), SlimeError> {
+    let Some(event) = mirrored(&data.pool, removed.scheduled_event_id).await? else {
+        return Ok(());
+    };
+    if rsvp::unnoticed(&data.pool, &event, removed.user_id).await? {
+        refresh::soon(ctx, &data.pool, event.id);
+    }
+    Ok(())
+}
+
+async fn refresh(ctx: &SerenityContext, pool: &PgPool, event_id: i64) -> Result<(), SlimeError> {
+    if let Some(event) = Event::fetch(pool, event_id).await? {
+        event.refresh_post(ctx, pool).await?;
+    }
+    Ok(())
+}
+
+/// Everyone interested in the scheduled event on Discord, other than bots.
+async fn interested_on_discord(
+    ctx: &SerenityContext,
+    event: &Event,
+    scheduled: ScheduledEventId,
+) -> Result<HashSet<UserId>, SlimeError> {
+    let mut users = HashSet::new();
+    let mut after = None;
+    loop {
+        let page = event
+            .guild()
+            .scheduled_event_users_optioned(
+                ctx,
+                scheduled,
+                Some(PAGE_SIZE),
+                after.map(UserPagination::After),
+                Some(false),
+            )
+            .await?;
+        after = page.iter().map(|u| u.user.id).max();
+        let full = page.len() as u64 == PAGE_SIZE;
+        users.extend(page.into_iter().filter(|u| !u.user.bot).map(|u| u.user.id));
+        if !full {
+            return Ok(users);
+        }
+    }
+}
+
+/// Brings one event's interest in line with its scheduled event: everyone interested there is
+/// at least interested here, and interest that came from there and has since gone is dropped.
+pub async fn reconcile(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &Event,
+) -> Result<(), SlimeError> {
+    let Some(scheduled) = event.scheduled_event_id else {
+        return Ok(());
+    };
+    let on_discord =
+        interested_on_discord(ctx, event, ScheduledEventId::new(scheduled as u64)).await?;
+    let from_discord = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM event_rsvps
+         WHERE event_id = $1 AND state = 'interested' AND from_discord",
+    )
+    .bind(event.id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut changed = false;
+    for user in &on_discord {
+        changed |= rsvp::noticed(pool, event, *user).await?;
+    }
+    for user in from_discord {
+        let user = UserId::new(user as u64);
+        if !on_discord.contains(&user) {
+            changed |= rsvp::unnoticed(pool, event, user).await?;
+        }
+    }
+    if changed {
+        refresh(ctx, pool, event.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Reconciles every upcoming event mirrored into Discord. Called by the scheduler every
+/// [`INTERVAL_MINUTES`].
+pub async fn sync_all(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let events = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events
+         WHERE status = 'published' AND scheduled_event_id IS NOT NULL AND starts_at > $1
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now)
+    .fetch_all(&data.pool)
+    .await?;
+
+    for event in events {
+        data.calls.turn().await;
+        if let Err(e) = reconcile(ctx, &data.pool, &event).await {
+            error!(
+                "Could not sync Discord interest for event {}: {}",
+                event.id, e
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgConnection;
+
+use super::{cache, fetch_managed, refresh, rsvp, Event, EventStatus};
+use crate::{
+    custom_id::{CustomId, Kind},
+    i18n::Voice,
+    util::respond_ephemeral,
+    Context, Data, SlimeError,
+};
+
+/// Most items an event's list can have, so the list fits in one embed field.
+const MAX_ITEMS: usize = 12;
+const MAX_ITEM_LENGTH: usize = 40;
+
+impl Event {
+    /// The event's items, with who claimed each, if anyone.
+    pub fn bring_list(&self) -> impl Iterator<Item = (&str, Option<i64>)> {
+        self.items.iter().enumerate().map(|(i, item)| {
+            let claim = self.item_claims.get(i).copied().filter(|c| *c != 0);
+            (item.as_str(), claim)
+        })
+    }
+
+    /// Items nobody has claimed yet.
+    pub fn unclaimed_items(&self) -> Vec<&str> {
+        self.bring_list()
+            .filter(|(_, claim)| claim.is_none())
+            .map(|(item, _)| item)
+            .collect()
+    }
+}
+
+/// The bring-list as shown on the event's post, if it has one.
+pub fn render(event: &Event, voice: &Voice) -> Option<String> {
+    if event.items.is_empty() {
+        return None;
+    }
+    let lines = event
+        .bring_list()
+        .map(|(item, claim)| {
+            let by = match claim {
+                None => format!("*{}*", voice.t("event-item-unclaimed")),
+                // Forgotten members (see `crate::privacy`) keep their claim, but not their name.
+                Some(user) if user < 0 => voice.t("event-host-deleted"),
+                Some(user) => UserId::new(user as u64).mention().to_string(),
+            };
+            format!("• {item}: {by}")
+        })
+        .collect::<Vec<_>>();
+
+    Some(lines.join("\n"))
+}
+
+/// A menu for claiming items on the event's post, if it has any.
+pub fn make_item_menu(event: &Event) -> Option<CreateActionRow> {
+    if event.items.is_empty() {
+        return None;
+    }
+    let options = event
+        .bring_list()
+        .enumerate()
+        .map(|(i, (item, claim))| {
+            let status = if claim.is_some() { "Taken" } else { "Needed" };
+            CreateSelectMenuOption::new(item, i.to_string()).description(status)
+        })
+        .collect();
+
+    Some(CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(
+            CustomId::new(Kind::EventItem, "pick", event.id),
+            CreateSelectMenuKind::String { options },
+        )
+        .placeholder("Bring something or help out"),
+    ))
+}
+
+/// Gives up anything `user` claimed, once they no longer have a place at the event. Called by
+/// the RSVP queries, inside their transaction.
+pub async fn release(
+    conn: &mut PgConnection,
+    event_id: i64,
+    user: UserId,
+) -> Result<(), SlimeError> {
+    sqlx::query("UPDATE events SET item_claims = array_replace(item_claims, $2, 0) WHERE id = $1")
+        .bind(event_id)
+        .bind(user.get() as i64)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Handles a pick from an event's item menu: claims the item, or gives it up if the member
+/// already had it.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let event_id = custom_id.id;
+    let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind else {
+        return Ok(());
+    };
+    let Some(Ok(index)) = values.first().map(|v| v.parse::<usize>()) else {
+        return Ok(());
+    };
+
+    let pool = &data.pool;
+    let event = data
+        .events
+        .get(pool, event_id)
+        .await?
+        .filter(|e| e.status == EventStatus::Published);
+    let Some(event) = event else {
+        return respond_ephemeral(ctx, interaction, "This event is over.").await;
+    };
+    let Some(item) = event.items.get(index) else {
+        return respond_ephemeral(ctx, interaction, "That item isn't on the list any more.").await;
+    };
+    let user = interaction.user.id;
+    if !rsvp::confirmed(pool, event.id).await?.contains(&user) {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "Press **I'm going** first, then pick what you'll bring.",
+        )
+        .await;
+    }
+
+    // Checked against the item's name too, in case the list changed since the menu was drawn.
+    let holder = sqlx::query_scalar::<_, i64>(
+        "UPDATE events SET item_claims[$2] = CASE
+                WHEN item_claims[$2] = 0 THEN $4
+                WHEN item_claims[$2] = $4 THEN 0
+                ELSE item_claims[$2]
+            END
+         WHERE id = $1 AND items[$2] = $3
+         RETURNING item_claims[$2]",
+    )
+    .bind(event.id)
+    .bind(index as i32 + 1)
+    .bind(item)
+    .bind(user.get() as i64)
+    .fetch_optional(pool)
+    .await?;
+    data.events.invalidate(event.id);
+
+    let content = match holder {
+        None => "That item isn't on the list any more.".to_string(),
+        Some(0) => format!("You're no longer bringing **{item}**."),
+        Some(holder) if holder == user.get() as i64 => {
+            format!("Thanks! You're down for **{item}**.")
+        }
+        Some(_) => format!("Someone else has already taken **{item}**."),
+    };
+    respond_ephemeral(ctx, interaction, &content).await?;
+    refresh::soon(ctx, pool, event.id);
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Ask attendees to bring things or help out.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "items",
+    subcommands("add", "remove")
+)]
+pub async fn items_command(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Add something to an event's bring-list, like snacks or a referee.
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "What's needed"]
+    #[max_length = 40]
+    item: String,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    let item = item
+        .trim()
+        .chars()
+        .take(MAX_ITEM_LENGTH)
+        .collect::<String>();
+    if item.is_empty() {
+        return reply(ctx, "Say what's needed.").await;
+    }
+
+    let added = sqlx::query(
+        "UPDATE events SET items = array_append(items, $2), item_claims = array_append(item_claims, 0)
+         WHERE id = $1 AND cardinality(items) < $3 AND NOT $2 = ANY(items)",
+    )
+    .bind(event.id)
+    .bind(&item)
+    .bind(MAX_ITEMS as i32)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+    cache::written(event.id);
+    if added == 0 {
+        return reply(
+            ctx,
+            format!(
+                "**{}** already has **{item}** or is at its {MAX_ITEMS} items.",
+                event.title
+            ),
+        )
+        .await;
+    }
+
+    refresh(ctx, event.id).await?;
+    reply(
+        ctx,
+        format!("Added **{item}** to the list for **{}**.", event.title),
+    )
+    .await
+}
+
+/// Take something off an event's bring-list.
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "What's no longer needed"]
+    #[max_length = 40]
+    item: String,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    let item = item.trim();
+    let Some(index) = event
+        .items
+        .iter()
+        .position(|i| i.eq_ignore_ascii_case(item))
+    else {
+        return reply(ctx, format!("**{}** doesn't have **{item}**.", event.title)).await;
+    };
+
+    sqlx::query(
+        "UPDATE events SET
+            items = items[:$2 - 1] || items[$2 + 1:],
+            item_claims = item_claims[:$2 - 1] || item_claims[$2 + 1:]
+         WHERE id = $1 AND items[$2] = $3",
+    )
+    .bind(event.id)
+    .bind(index as i32 + 1)
+    .bind(&event.items[index])
+    .execute(&ctx.data().pool)
+    .await?;
+    cache::written(event.id);
+
+    refresh(ctx, event.id).await?;
+    reply(
+        ctx,
+        format!(
+            "Took **{}** off the list for **{}**.",
+            event.items[index], event.title
+        ),
+    )
+    .await
+}
+
+/// Redraws the post after the list changed, if the event is still up.
+async fn refresh(ctx: Context<'_>, event_id: i64) -> Result<(), SlimeError> {
+    if let Some(event) = Event::fetch(&ctx.data().pool, event_id)
+        .await?
+        .filter(|e| e.status == EventStatus::Published)
+    {
+        event
+            .refresh_post(ctx.serenity_context(), &ctx.data().pool)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{cache, fetch_managed, Event, EventStatus};
+use crate::{util::query_encode, Context, SlimeError};
+
+/// Discord caps a scheduled event's location at 100 characters.
+const SCHEDULED_LOCATION_LENGTH: usize = 100;
+
+/// Where an event happens, as the host described it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    pub venue: Option<String>,
+    pub address: Option<String>,
+    /// A voice or stage channel, for events held on the server.
+    pub channel: Option<ChannelId>,
+}
+
+/// Where Discord's scheduled event for an event says it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Place {
+    /// Somewhere off the server, described in at most 100 characters.
+    External(String),
+    Channel(ScheduledEventType, ChannelId),
+}
+
+impl Location {
+    pub fn of(event: &Event) -> Self {
+        Self {
+            venue: event.venue.clone(),
+            address: event.address.clone(),
+            channel: event.location_channel_id.map(|c| ChannelId::new(c as u64)),
+        }
+    }
+
+    /// The location as shown on the event's post, with the address linking to a map.
+    pub fn render(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        if let Some(venue) = &self.venue {
+            lines.push(format!("**{venue}**"));
+        }
+        if let Some(address) = &self.address {
+            lines.push(format!("[{address}]({})", map_link(address)));
+        }
+        if let Some(channel) = self.channel {
+            lines.push(channel.mention().to_string());
+        }
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// How the location reads in places that only take plain text, cut down to `max` characters.
+    fn summary(&self, max: usize) -> Option<String> {
+        let summary = [&self.venue, &self.address]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if summary.is_empty() {
+            return None;
+        }
+
+        Some(match summary.char_indices().nth(max - 1) {
+            Some((end, _)) => format!("{}…", &summary[..end]),
+            None => summary,
+        })
+    }
+
+    /// Where the scheduled event should say the event is. A channel wins over an address, since
+    /// Discord can only show one. With no location at all, the post's `link` stands in, so
+    /// members can still find the event from the scheduled event.
+    pub async fn place(&self, ctx: &SerenityContext, link: String) -> Place {
+        if let Some(channel) = self.channel {
+            let kind = match channel.to_channel(ctx).await {
+                Ok(Channel::Guild(c)) if c.kind == ChannelType::Stage => {
+                    ScheduledEventType::StageInstance
+                }
+                _ => ScheduledEventType::Voice,
+            };
+            return Place::Channel(kind, channel);
+        }
+
+        Place::External(self.summary(SCHEDULED_LOCATION_LENGTH).unwrap_or(link))
+    }
+}
+
+/// A link that opens `address` in a map, or the maps app on phones.
+pub fn map_link(address: &str) -> String {
+    format!(
+        "https://www.google.com/maps/search/?api=1&query={}",
+        query_encode(address.trim())
+    )
+}
+
+/// Points the event's scheduled event at its current location.
+pub async fn sync_scheduled(ctx: &SerenityContext, event: &Event) -> Result<(), SlimeError> {
+    let Some(scheduled) = event.scheduled_event_id else {
+        return Ok(());
+    };
+    let link = match event.message_id {
+        Some(message) => MessageId::new(message as u64).link(event.channel(), Some(event.guild())),
+        None => event.channel().mention().to_string(),
+    };
+
+    let edit = match Location::of(event).place(ctx, link).await {
+        Place::External(location) => EditScheduledEvent::new()
+            .kind(ScheduledEventType::External)
+            .location(location),
+        Place::Channel(kind, channel) => EditScheduledEvent::new().kind(kind).channel_id(channel),
+    };
+    event
+        .guild()
+        .edit_scheduled_event(
+            ctx,
+            ScheduledEventId::new(scheduled as u64),
+            edit.end_time(event.ends_at()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn save(pool: &PgPool, event: &Event) -> Result<(), SlimeError> {
+    sqlx::query(
+        "UPDATE events SET
+            venue = $2, address = $3, location_channel_id = $4, outdoor = $5,
+            -- A forecast for somewhere else is no use.
+            forecast = NULL, forecast_checked_at = NULL
+         WHERE id = $1",
+    )
+    .bind(event.id)
+    .bind(&event.venue)
+    .bind(&event.address)
+    .bind(event.location_channel_id)
+    .bind(event.outdoor)
+    .execute(pool)
+    .await?;
+    cache::written(event.id);
+
+    Ok(())
+}
+
+/// Say where an event happens. Leave everything out to clear its location.
+#[poise::command(slash_command, guild_only, rename = "location")]
+pub async fn location_command(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Name of the place, like a café or park"]
+    #[max_length = 100]
+    venue: Option<String>,
+    #[description = "Street address, linked to a map on the post"]
+    #[max_length = 200]
+    address: Option<String>,
+    #[description = "Voice or stage channel, for events held on the server"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<GuildChannel>,
+    #[description = "Whether it's outdoors, so the post shows the forecast the day before"]
+    outdoor: Option<bool>,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if !matches!(event.status, EventStatus::Draft | EventStatus::Published) {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let tidy = |text: Option<String>| text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    event.venue = tidy(venue);
+    event.address = tidy(address);
+    event.location_channel_id = channel.map(|c| c.id.get() as i64);
+    event.outdoor = outdoor.unwrap_or(false);
+    event.forecast = None;
+    save(&ctx.data().pool, &event).await?;
+
+    if event.status == EventStatus::Published {
+        event
+            .refresh_post(ctx.serenity_context(), &ctx.data().pool)
+            .await?;
+        if let Err(e) = sync_scheduled(ctx.serenity_context(), &event).await {
+            error!(
+                "Could not update scheduled event location for event {}: {}",
+                event.id, e
+            );
+        }
+    }
+
+    let content = match Location::of(&event).render() {
+        Some(location) => format!("**{}** is at:\n{location}", event.title),
+        None => format!("**{}** no longer has a location.", event.title),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_link_to_maps() {
+        assert_eq!(
+            map_link(" 1 Rue de l'Été, Paris "),
+            "https://www.google.com/maps/search/?api=1&query=1+Rue+de+l%27%C3%89t%C3%A9%2C+Paris"
+        );
+    }
+
+    #[test]
+    fn locations_render_and_summarise() {
+        let location = Location {
+            venue: Some("The Frog & Lily".into()),
+            address: Some("12 Pond Lane".into()),
+            channel: None,
+        };
+        assert_eq!(
+            location.render().unwrap(),
+            "**The Frog & Lily**\n[12 Pond Lane](https://www.google.com/maps/search/?api=1&query=12+Pond+Lane)"
+        );
+        assert_eq!(
+            location.summary(100).unwrap(),
+            "The Frog & Lily, 12 Pond Lane"
+        );
+        assert_eq!(location.summary(10).unwrap(), "The Frog …");
+
+        assert_eq!(Location::default().render(), None);
+    }
+}
@@ -0,0 +1,996 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Offset, TimeZone, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::{PgConnection, PgPool};
+use tracing::error;
+
+use self::location::Location;
+use crate::{
+    audit::{self, AuditEntry},
+    banner,
+    forms::Form,
+    help,
+    i18n::{self, Voice},
+    notify::{self, NotificationKind, NotificationRun},
+    posts::{self, PostContent, PostKind},
+    preview::{self, Preview},
+    quotas, relay,
+    settings::GuildSettings,
+    undo::UndoStep,
+    util::rehearse,
+    Context, SlimeError,
+};
+
+mod adopt;
+pub mod albums;
+pub mod approval;
+pub mod archive;
+pub mod attendance;
+mod buttons;
+pub mod cache;
+mod calendar;
+pub mod channels;
+pub mod coexistence;
+mod draft;
+pub mod escalation;
+pub mod forum;
+pub mod history;
+mod import;
+pub mod interest;
+pub mod items;
+pub mod location;
+mod refresh;
+pub mod reminders;
+pub mod rsvp;
+mod scheduled;
+pub mod slots;
+pub mod speakers;
+pub mod suggestions;
+pub mod sync;
+pub mod tags;
+pub mod threads;
+pub mod threshold;
+mod timing;
+mod wizard;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum EventStatus {
+    /// Saved by the host but not posted anywhere yet.
+    Draft,
+    /// Waiting in the guild's approval queue.
+    Pending,
+    Published,
+    /// The host has closed the event out and attendance has been recorded.
+    Completed,
+    Rejected,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Event {
+    pub id: i64,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub host_id: i64,
+    pub title: String,
+    pub description: String,
+    pub starts_at: DateTime<Utc>,
+    pub duration_minutes: i32,
+    pub capacity: Option<i32>,
+    pub status: EventStatus,
+    pub message_id: Option<i64>,
+    pub queue_message_id: Option<i64>,
+    pub scheduled_event_id: Option<i64>,
+    /// Maintained by the RSVP queries rather than [`Event::save`], so a stale copy of the event
+    /// can't overwrite a fresher count.
+    pub confirmed_count: i32,
+    pub waitlist_count: i32,
+    pub interested_count: i32,
+    /// Set by [`channels::go_live`] and likewise left alone by [`Event::save`].
+    pub voice_channel_id: Option<i64>,
+    /// Role held by everyone with a confirmed place while the event is live.
+    pub event_role_id: Option<i64>,
+    /// Set by [`threads::open`], and also left alone by [`Event::save`].
+    pub thread_id: Option<i64>,
+    /// Whether the post was published with a generated banner, which its embed shows.
+    pub banner: bool,
+    /// Where the event happens, set with `/event location`. See [`location::Location`].
+    pub venue: Option<String>,
+    pub address: Option<String>,
+    pub location_channel_id: Option<i64>,
+    /// Outdoor events with an address get a forecast the day before, from [`crate::weather`].
+    pub outdoor: bool,
+    pub forecast: Option<String>,
+    /// What the event is about, tidied by [`tags::parse`]. Only written by [`tags::replace`],
+    /// which keeps `event_tags` in step.
+    pub tags: Vec<String>,
+    /// The event's post in the archive channel, once [`archive::tick`] has moved it there.
+    pub archive_message_id: Option<i64>,
+    /// The bring-list, and alongside it who claimed each item (0 for nobody). Changed only by
+    /// the queries in [`items`], and left alone by [`Event::save`].
+    pub items: Vec<String>,
+    pub item_claims: Vec<i64>,
+    /// Whether the event is mirrored into Discord's scheduled events, or `None` to follow the
+    /// guild's setting. Set with `/event discord_event`.
+    pub discord_event: Option<bool>,
+    /// Set with `/event threshold`: the event is called off [`Event::confirm_by`] unless at
+    /// least this many are going. See [`threshold::tick`].
+    pub min_attendees: Option<i32>,
+    pub confirm_hours: i32,
+    /// Days later to draft the event again if it falls through.
+    pub retry_days: Option<i32>,
+    /// The event's slots, copied from `event_slots` by [`slots::sync`] for its post to render.
+    pub slot_ids: Vec<i64>,
+    pub slot_offsets: Vec<i32>,
+    pub slot_minutes: Vec<i32>,
+    pub slot_lines: Vec<String>,
+    /// The event's post in the guild's forum channel, set by [`forum::open`]. When the guild
+    /// posts events only there, this is also where `channel_id` and `message_id` point.
+    pub forum_thread_id: Option<i64>,
+    /// The host's labels and colours for the RSVP buttons, set with `/event buttons`. Read
+    /// through [`Event::rsvp_label`] and [`Event::rsvp_style`].
+    pub rsvp_labels: Vec<String>,
+    pub rsvp_styles: Vec<String>,
+    /// Whether this is a moderator rota, whose slots are shifts claimed without an RSVP. See
+    /// [`crate::shifts`].
+    pub coverage: bool,
+}
+
+/// The host-provided fields of an event, before it has an ID.
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub host_id: UserId,
+    pub title: String,
+    pub description: String,
+    pub starts_at: DateTime<Utc>,
+    pub duration_minutes: i32,
+    pub capacity: Option<i32>,
+    pub tags: Vec<String>,
+}
+
+impl Event {
+    pub async fn fetch(pool: &PgPool, id: i64) -> Result<Option<Self>, SlimeError> {
+        Ok(
+            sqlx::query_as::<_, Event>("SELECT * FROM events WHERE id = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?,
+        )
+    }
+
+    pub async fn insert(
+        pool: &PgPool,
+        mut new: NewEvent,
+        status: EventStatus,
+    ) -> Result<Self, SlimeError> {
+        let tags = std::mem::take(&mut new.tags);
+        let mut event = Self::insert_untagged(&mut *pool.acquire().await?, new, status).await?;
+        if !tags.is_empty() {
+            tags::replace(pool, &mut event, tags).await?;
+        }
+
+        Ok(event)
+    }
+
+    /// Inserts the event without its tags, so several can go in one transaction.
+    async fn insert_untagged(
+        conn: &mut PgConnection,
+        new: NewEvent,
+        status: EventStatus,
+    ) -> Result<Self, SlimeError> {
+        Ok(sqlx::query_as::<_, Event>(
+            "INSERT INTO events
+                (guild_id, channel_id, host_id, title, description, starts_at, duration_minutes, capacity, status)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             RETURNING *",
+        )
+        .bind(new.guild_id.get() as i64)
+        .bind(new.channel_id.get() as i64)
+        .bind(new.host_id.get() as i64)
+        .bind(new.title)
+        .bind(new.description)
+        .bind(new.starts_at)
+        .bind(new.duration_minutes)
+        .bind(new.capacity)
+        .bind(status)
+        .fetch_one(conn)
+        .await?)
+    }
+
+    /// Writes the editable fields and message references back to the database.
+    pub async fn save(&self, pool: &PgPool) -> Result<(), SlimeError> {
+        sqlx::query(
+            "UPDATE events SET
+                title = $2, description = $3, starts_at = $4, duration_minutes = $5, capacity = $6,
+                status = $7, message_id = $8, queue_message_id = $9, scheduled_event_id = $10,
+                -- Moving the event means reminding attendees again at the new time, and checking
+                -- its minimum again before then.
+                reminded_at = CASE WHEN starts_at = $4 THEN reminded_at END,
+                threshold_checked_at = CASE WHEN starts_at = $4 THEN threshold_checked_at END
+             WHERE id = $1",
+        )
+        .bind(self.id)
+        .bind(&self.title)
+        .bind(&self.description)
+        .bind(self.starts_at)
+        .bind(self.duration_minutes)
+        .bind(self.capacity)
+        .bind(self.status)
+        .bind(self.message_id)
+        .bind(self.queue_message_id)
+        .bind(self.scheduled_event_id)
+        .execute(pool)
+        .await?;
+        cache::written(self.id);
+
+        Ok(())
+    }
+
+    /// Writes only the fields the event form edits, and only while the event is still `status`.
+    /// For forms left open long enough that someone else may have moved the event on meanwhile,
+    /// whose [`Event::save`] would put it back. Returns whether it was written.
+    pub async fn save_details(
+        &self,
+        pool: &PgPool,
+        status: EventStatus,
+    ) -> Result<bool, SlimeError> {
+        let saved = sqlx::query(
+            "UPDATE events SET
+                title = $2, description = $3, starts_at = $4, duration_minutes = $5, capacity = $6,
+                reminded_at = CASE WHEN starts_at = $4 THEN reminded_at END,
+                threshold_checked_at = CASE WHEN starts_at = $4 THEN threshold_checked_at END
+             WHERE id = $1 AND status = $7",
+        )
+        .bind(self.id)
+        .bind(&self.title)
+        .bind(&self.description)
+        .bind(self.starts_at)
+        .bind(self.duration_minutes)
+        .bind(self.capacity)
+        .bind(status)
+        .execute(pool)
+        .await?
+        .rows_affected()
+            > 0;
+        cache::written(self.id);
+
+        Ok(saved)
+    }
+
+    pub fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    pub fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    /// Forgotten hosts (see [`crate::privacy`]) map to an ID no Discord user has.
+    pub fn host(&self) -> UserId {
+        UserId::new(self.host_id as u64)
+    }
+
+    pub fn ends_at(&self) -> DateTime<Utc> {
+        self.starts_at + Duration::minutes(self.duration_minutes.into())
+    }
+
+    /// Where the event stands, for posts that spell it out rather than leaving it to be read off
+    /// the post's surroundings, like which channel it's in or its missing buttons.
+    fn status_label(&self) -> Option<&'static str> {
+        match self.status {
+            EventStatus::Draft => Some("event-label-draft"),
+            EventStatus::Pending => Some("event-label-pending"),
+            EventStatus::Rejected => Some("event-label-rejected"),
+            EventStatus::Cancelled => Some("event-label-cancelled"),
+            EventStatus::Completed => Some("event-label-over"),
+            EventStatus::Published
+                if self
+                    .capacity
+                    .is_some_and(|capacity| self.confirmed_count >= capacity) =>
+            {
+                Some("event-label-full")
+            }
+            EventStatus::Published => None,
+        }
+    }
+
+    /// Renders the event post in `voice`, with times as dynamic timestamps. In `plain_text` mode
+    /// the title also says where the event stands, like `[CANCELLED]`.
+    pub fn embed(&self, voice: &Voice, plain_text: bool) -> CreateEmbed {
+        let going = match self.capacity {
+            Some(capacity) => format!("{} / {capacity}", self.confirmed_count),
+            None => self.confirmed_count.to_string(),
+        };
+        let mut extra = Vec::new();
+        if self.waitlist_count > 0 {
+            extra.push(voice.t_with("event-waitlist", &[("count", &self.waitlist_count)]));
+        }
+        if self.interested_count > 0 {
+            extra.push(voice.t_with("event-interested", &[("count", &self.interested_count)]));
+        }
+        let going = if extra.is_empty() {
+            going
+        } else {
+            format!("{going} ({})", extra.join(", "))
+        };
+        let starts = format!(
+            "{} ({})",
+            i18n::timestamp(self.starts_at, FormattedTimestampStyle::LongDateTime),
+            i18n::timestamp(self.starts_at, FormattedTimestampStyle::RelativeTime),
+        );
+
+        let title = match self.status_label().filter(|_| plain_text) {
+            Some(label) => format!("[{}] {}", voice.t(label), self.title),
+            None => self.title.clone(),
+        };
+
+        let mut embed = CreateEmbed::new()
+            .title(title)
+            .field(voice.heading("event-starts"), starts, false)
+            .field(
+                voice.heading("event-duration"),
+                voice.t_with(
+                    "event-duration-minutes",
+                    &[("minutes", &self.duration_minutes)],
+                ),
+                true,
+            )
+            .field(voice.heading("event-going"), going, true)
+            .field(
+                voice.heading("event-host"),
+                if self.host_id < 0 {
+                    voice.t("event-host-deleted")
+                } else {
+                    self.host().mention().to_string()
+                },
+                true,
+            )
+            .footer(CreateEmbedFooter::new(
+                voice.t_with("event-footer", &[("id", &self.id)]),
+            ));
+        if let (Some(minimum), Some(deadline)) = (self.min_attendees, self.confirm_by()) {
+            if self.short_of_minimum() {
+                embed = embed.field(
+                    voice.heading("event-threshold"),
+                    voice.t_with(
+                        "event-threshold-by",
+                        &[
+                            ("count", &minimum),
+                            (
+                                "deadline",
+                                &i18n::timestamp(deadline, FormattedTimestampStyle::RelativeTime),
+                            ),
+                        ],
+                    ),
+                    false,
+                );
+            }
+        }
+        if let Some(location) = Location::of(self).render() {
+            embed = embed.field(voice.heading("event-location"), location, false);
+        }
+        if !self.tags.is_empty() {
+            let tags = self
+                .tags
+                .iter()
+                .map(|t| format!("`{t}`"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            embed = embed.field(voice.heading("event-tags"), tags, false);
+        }
+        if let Some(schedule) = self.schedule() {
+            embed = embed.field(voice.heading("event-slots"), schedule, false);
+        }
+        if let Some(items) = items::render(self, voice) {
+            embed = embed.field(voice.heading("event-items"), items, false);
+        }
+        if let Some(forecast) = self.forecast.as_ref().filter(|_| self.outdoor) {
+            embed = embed.field(voice.heading("event-forecast"), forecast, false);
+        }
+        if let Some(channel) = self.voice_channel_id {
+            embed = embed.field(
+                voice.heading("event-voice"),
+                ChannelId::new(channel as u64).mention().to_string(),
+                true,
+            );
+        }
+        if !self.description.is_empty() {
+            embed = embed.description(&self.description);
+        }
+        if self.banner {
+            embed = embed.image(format!("attachment://{}", banner::FILENAME));
+        }
+
+        embed
+    }
+
+    /// The event's post as its guild wants it rendered.
+    pub async fn post_embed(
+        &self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+    ) -> Result<CreateEmbed, SlimeError> {
+        let settings = GuildSettings::load(pool, self.guild()).await?;
+        Ok(self.embed(
+            &Voice::of(ctx, self.guild(), &settings),
+            settings.plain_text,
+        ))
+    }
+
+    /// The buttons and menus under the event's post, with the guild's emoji.
+    pub fn components(&self, settings: &GuildSettings) -> Vec<CreateActionRow> {
+        if self.coverage {
+            return slots::make_slot_menu(self).into_iter().collect();
+        }
+        let mut components = vec![rsvp::make_rsvp_buttons(self, settings)];
+        components.extend(slots::make_slot_menu(self));
+        components.extend(items::make_item_menu(self));
+        components
+    }
+
+    /// Posts the event in its channel and mirrors it into Discord's scheduled events, unless it
+    /// already has one or the event or guild has turned that off (see [`scheduled::wanted`]).
+    pub async fn publish(
+        &mut self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+    ) -> Result<(), SlimeError> {
+        let banner = self.banner_image(ctx, pool).await?;
+        self.banner = banner.is_some();
+        let settings = GuildSettings::load(pool, self.guild()).await?;
+        let voice = Voice::of(ctx, self.guild(), &settings);
+        let mut post = CreateMessage::new()
+            .embed(self.embed(&voice, settings.plain_text))
+            .components(self.components(&settings));
+        if let Some(banner) = &banner {
+            post = post.add_file(banner.clone());
+        }
+        let forum = settings.forum_channel();
+        let in_forum = forum.is_some() && settings.forum_only;
+        let message = match forum {
+            Some(forum) if in_forum => {
+                let message = forum::open(ctx, pool, self, forum, post.clone()).await?;
+                self.channel_id = message.channel_id.get() as i64;
+                message
+            }
+            _ => {
+                let message = self.channel().send_message(ctx, post.clone()).await?;
+                relay::publish(ctx, &settings, &message).await;
+                message
+            }
+        };
+        if let Some(forum) = forum.filter(|_| !in_forum) {
+            if let Err(e) = forum::open(ctx, pool, self, forum, post).await {
+                error!("Could not crosspost event {} to the forum: {}", self.id, e);
+            }
+        }
+
+        // Adopted events already have one.
+        if self.scheduled_event_id.is_none() && scheduled::wanted(self, &settings) {
+            self.scheduled_event_id = scheduled::create(ctx, self, message.link(), banner.as_ref())
+                .await
+                .map(|s| s.id.get() as i64);
+        }
+
+        self.status = EventStatus::Published;
+        self.message_id = Some(message.id.get() as i64);
+        self.save(pool).await?;
+        if self.scheduled_event_id.is_some() {
+            sync::mark_synced(pool, self).await?;
+        }
+        sqlx::query("UPDATE events SET banner = $2, channel_id = $3 WHERE id = $1")
+            .bind(self.id)
+            .bind(self.banner)
+            .bind(self.channel_id)
+            .execute(pool)
+            .await?;
+        // A forum post is a thread already.
+        if !in_forum {
+            threads::open(ctx, pool, self, &message).await;
+        }
+        posts::register(pool, PostKind::Event, self.id, self.guild(), &message).await
+    }
+
+    /// A banner for the event's post, if the guild wants them.
+    async fn banner_image(
+        &self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+    ) -> Result<Option<CreateAttachment>, SlimeError> {
+        if !GuildSettings::load(pool, self.guild()).await?.event_banners {
+            return Ok(None);
+        }
+        let host = match self.host_id {
+            id if id < 0 => String::new(),
+            _ => self
+                .host()
+                .to_user(ctx)
+                .await
+                .map(|u| u.global_name.unwrap_or(u.name))
+                .unwrap_or_default(),
+        };
+        let when = self.starts_at.format("%a %d %b %Y, %H:%M UTC").to_string();
+        let png = banner::render(&self.title, &when, &host);
+
+        Ok(Some(CreateAttachment::bytes(png, banner::FILENAME)))
+    }
+
+    /// Takes a published event back down: deletes its post and scheduled event and marks it
+    /// cancelled. Either may already be gone, which is fine.
+    pub async fn withdraw(
+        &mut self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+    ) -> Result<(), SlimeError> {
+        if let Some(message_id) = self.message_id.take() {
+            // A forum post can't lose its first message, so it's closed by `forum::sync` instead.
+            if self.forum_thread_id != Some(message_id) {
+                let _ = self
+                    .channel()
+                    .delete_message(ctx, MessageId::new(message_id as u64))
+                    .await
+                    .inspect_err(|e| error!("Could not delete post for event {}: {}", self.id, e));
+            }
+            posts::retire(pool, MessageId::new(message_id as u64)).await?;
+        }
+        if let Some(scheduled) = self.scheduled_event_id.take() {
+            let _ = self
+                .guild()
+                .delete_scheduled_event(ctx, ScheduledEventId::new(scheduled as u64))
+                .await
+                .inspect_err(|e| {
+                    error!(
+                        "Could not delete scheduled event for event {}: {}",
+                        self.id, e
+                    )
+                });
+        }
+
+        self.status = EventStatus::Cancelled;
+        self.save(pool).await?;
+        forum::sync(ctx, pool, self).await;
+        Ok(())
+    }
+
+    /// Re-renders the public post after something shown on it changed.
+    pub async fn refresh_post(
+        &self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+    ) -> Result<(), SlimeError> {
+        // Whatever changed was written first, often by a query of its own rather than `save`.
+        cache::written(self.id);
+        let Some(message_id) = self.message_id else {
+            return Ok(());
+        };
+
+        let settings = GuildSettings::load(pool, self.guild()).await?;
+        let voice = Voice::of(ctx, self.guild(), &settings);
+        let edit = EditMessage::new()
+            .embed(self.embed(&voice, settings.plain_text))
+            .components(self.components(&settings));
+        self.channel()
+            .edit_message(ctx, MessageId::new(message_id as u64), edit.clone())
+            .await?;
+        // The copy in the forum, when the post isn't there already. Its first message shares the
+        // forum post's ID.
+        if let Some(thread) = self.forum_thread_id.filter(|t| *t != message_id) {
+            let thread = ChannelId::new(thread as u64);
+            if let Err(e) = thread
+                .edit_message(ctx, MessageId::new(thread.get()), edit)
+                .await
+            {
+                error!("Could not update forum post of event {}: {}", self.id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `member` may run host actions on this event: its host, or anyone who can manage
+    /// events in the guild.
+    pub fn is_managed_by(&self, user: UserId, permissions: Option<Permissions>) -> bool {
+        self.host() == user || permissions.is_some_and(|p| p.manage_events())
+    }
+}
+
+/// Parses a start time given either as a Unix timestamp, RFC 3339, or `YYYY-MM-DD HH:MM` in UTC.
+pub fn parse_start_time(input: &str) -> Option<DateTime<Utc>> {
+    parse_time_in(input, Utc.fix())
+}
+
+/// Like [`parse_start_time`], reading `YYYY-MM-DD HH:MM` as a time at `offset` from UTC. The
+/// other forms say which moment they mean on their own.
+pub fn parse_time_in(input: &str, offset: FixedOffset) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+
+    if let Ok(secs) = input.parse::<i64>() {
+        return Utc.timestamp_opt(secs, 0).single();
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Some(time.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+        .ok()
+        .and_then(|time| offset.from_local_datetime(&time).single())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// The editable fields of an event as a Discord modal, shared by hosts editing their drafts and
+/// moderators editing queued events.
+#[derive(Debug, Clone, poise::Modal)]
+#[name = "Edit event"]
+pub struct EventModal {
+    #[name = "Title"]
+    #[max_length = 100]
+    title: String,
+    #[name = "Start time"]
+    #[placeholder = "2024-03-01 19:30"]
+    when: String,
+    #[name = "Duration (minutes)"]
+    #[max_length = 5]
+    duration: String,
+    #[name = "Capacity (blank for unlimited)"]
+    #[max_length = 5]
+    capacity: Option<String>,
+    #[name = "Description"]
+    #[paragraph]
+    #[max_length = 1000]
+    description: Option<String>,
+}
+
+impl EventModal {
+    pub fn from_event(event: &Event) -> Self {
+        Self {
+            title: event.title.clone(),
+            when: event.starts_at.format("%Y-%m-%d %H:%M").to_string(),
+            duration: event.duration_minutes.to_string(),
+            capacity: event.capacity.map(|c| c.to_string()),
+            description: Some(event.description.clone()).filter(|d| !d.is_empty()),
+        }
+    }
+}
+
+impl Form for EventModal {
+    type Output = EventFields;
+
+    fn validate(&self) -> Result<EventFields, SlimeError> {
+        Ok(EventFields {
+            title: self.title.clone(),
+            starts_at: parse_start_time(&self.when)
+                .ok_or_else(|| SlimeError::InvalidTime(self.when.clone()))?,
+            duration_minutes: parse_positive(&self.duration)?,
+            capacity: self.capacity.as_deref().map(parse_positive).transpose()?,
+            description: self.description.clone().unwrap_or_default(),
+        })
+    }
+}
+
+/// What an [`EventModal`] makes once its fields check out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventFields {
+    pub title: String,
+    pub starts_at: DateTime<Utc>,
+    pub duration_minutes: i32,
+    pub capacity: Option<i32>,
+    pub description: String,
+}
+
+impl EventFields {
+    /// Copies the fields onto `event`.
+    pub fn apply(self, event: &mut Event) {
+        event.title = self.title;
+        event.starts_at = self.starts_at;
+        event.duration_minutes = self.duration_minutes;
+        event.capacity = self.capacity;
+        event.description = self.description;
+    }
+}
+
+fn parse_positive(input: &str) -> Result<i32, SlimeError> {
+    input
+        .trim()
+        .parse::<i32>()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| SlimeError::InvalidNumber(input.to_string()))
+}
+
+/// An event's public post as it should look now, while the event is published.
+pub async fn render_post(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    id: i64,
+) -> Result<Option<PostContent>, SlimeError> {
+    let Some(event) = Event::fetch(pool, id)
+        .await?
+        .filter(|e| e.status == EventStatus::Published)
+    else {
+        return Ok(None);
+    };
+
+    let settings = GuildSettings::load(pool, event.guild()).await?;
+
+    Ok(Some(PostContent {
+        content: None,
+        embed: event.embed(
+            &Voice::of(ctx, event.guild(), &settings),
+            settings.plain_text,
+        ),
+        components: event.components(&settings),
+    }))
+}
+
+/// Loads an event in this guild that the author is allowed to run host actions on.
+async fn fetch_managed(ctx: Context<'_>, id: i64) -> Result<Event, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let permissions = ctx.author_member().await.and_then(|m| m.permissions);
+
+    Event::fetch(&ctx.data().pool, id)
+        .await?
+        .filter(|e| e.guild() == guild_id && e.is_managed_by(ctx.author().id, permissions))
+        .ok_or(SlimeError::EventNotFound(id))
+}
+
+/// Sends an event out, either straight into its channel or into the approval queue when the guild
+/// requires it and the host can't manage events themselves. Returns a message for the host.
+async fn submit_or_publish(
+    ctx: Context<'_>,
+    settings: &GuildSettings,
+    event: &mut Event,
+) -> Result<String, SlimeError> {
+    let pool = &ctx.data().pool;
+    let can_manage_events = ctx
+        .author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_events());
+
+    if settings.approval_queue && !can_manage_events {
+        event.status = EventStatus::Pending;
+        approval::submit(ctx.serenity_context(), pool, settings, event).await?;
+        Ok("Your event has been submitted to the moderators for approval.".to_string())
+    } else {
+        event.publish(ctx.serenity_context(), pool).await?;
+        Ok(format!(
+            "Your event has been posted in {}.",
+            event.channel().mention()
+        ))
+    }
+}
+
+/// An event about to be sent out, previewed for its host first.
+struct EventPreview {
+    event: Event,
+    settings: GuildSettings,
+    /// Whether the draft was made just for this preview, and so goes if it's discarded.
+    fresh: bool,
+}
+
+impl Preview for EventPreview {
+    type Form = EventModal;
+
+    async fn render(&self, ctx: Context<'_>) -> Result<CreateEmbed, SlimeError> {
+        self.event
+            .post_embed(ctx.serenity_context(), &ctx.data().pool)
+            .await
+    }
+
+    fn form(&self) -> EventModal {
+        EventModal::from_event(&self.event)
+    }
+
+    async fn edit(&mut self, ctx: Context<'_>, fields: EventFields) -> Result<(), SlimeError> {
+        fields.apply(&mut self.event);
+        self.event.save(&ctx.data().pool).await
+    }
+
+    async fn publish(&mut self, ctx: Context<'_>) -> Result<String, SlimeError> {
+        submit_or_publish(ctx, &self.settings, &mut self.event).await
+    }
+
+    async fn discard(&mut self, ctx: Context<'_>) -> Result<String, SlimeError> {
+        if !self.fresh {
+            return Ok(format!(
+                "Nothing was posted. Draft #{id} is still saved for `/event publish {id}`.",
+                id = self.event.id
+            ));
+        }
+        sqlx::query("DELETE FROM events WHERE id = $1 AND status = 'draft'")
+            .bind(self.event.id)
+            .execute(&ctx.data().pool)
+            .await?;
+        Ok("Discarded. Nothing was posted.".to_string())
+    }
+}
+
+/// Create and manage events.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands(
+        "create",
+        "wizard::wizard",
+        "cancel",
+        "draft::draft",
+        "draft::edit",
+        "draft::publish",
+        "draft::drafts",
+        "import::import",
+        "rsvp::reject",
+        "rsvp::readmit",
+        "rsvp::rsvps",
+        "escalation::pending_command",
+        "threads::links_command",
+        "calendar::calendar_command",
+        "tags::list_command",
+        "tags::tags_command",
+        "attendance::finish",
+        "attendance::absent",
+        "attendance::checkin_qr",
+        "archive::rate",
+        "location::location_command",
+        "speakers::speakers_command",
+        "items::items_command",
+        "threshold::threshold_command",
+        "buttons::buttons_command",
+        "slots::slots_command",
+        "scheduled::discord_event",
+        "sync::update",
+        "sync::resolve_command",
+        "sync::sync_status",
+        "adopt::adopt",
+        "history::import_history",
+        "timing::suggest_time"
+    )
+)]
+pub async fn event(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Create a new event.
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Name of the event"]
+    #[max_length = 100]
+    title: String,
+    #[description = "Start time, e.g. `2024-03-01 19:30` (UTC) or a Unix timestamp"] when: String,
+    #[description = "Length of the event in minutes (default 60)"]
+    #[min = 1]
+    duration: Option<u32>,
+    #[description = "Maximum number of attendees"]
+    #[min = 1]
+    capacity: Option<u32>,
+    #[description = "What the event is about"]
+    #[max_length = 1000]
+    description: Option<String>,
+    #[description = "Tags, separated by commas, like `game, irl`"]
+    #[max_length = 200]
+    tags: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let starts_at = parse_start_time(&when).ok_or(SlimeError::InvalidTime(when))?;
+    let pool = &ctx.data().pool;
+    quotas::ensure_events(pool, guild_id, 1).await?;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+
+    let new = NewEvent {
+        guild_id,
+        channel_id: settings.events_channel().unwrap_or(ctx.channel_id()),
+        host_id: ctx.author().id,
+        title,
+        description: description.unwrap_or_default(),
+        starts_at,
+        duration_minutes: duration.unwrap_or(60) as i32,
+        capacity: capacity.map(|c| c as i32),
+        tags: tags.as_deref().map(tags::parse).unwrap_or_default(),
+    };
+
+    let event = Event::insert(pool, new, EventStatus::Draft).await?;
+    let id = event.id;
+    preview::run(
+        ctx,
+        EventPreview {
+            event,
+            settings,
+            fresh: true,
+        },
+    )
+    .await?;
+    help::after_create(ctx, id).await
+}
+
+/// Call off an upcoming event, taking down its post. This can be undone for a short while.
+#[poise::command(slash_command, guild_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    if rehearse(
+        ctx,
+        &format!("cancelled **{}** and taken down its post.", event.title),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    let pool = &ctx.data().pool;
+    let signed_up = rsvp::signed_up(pool, event.id).await?;
+    event.withdraw(ctx.serenity_context(), pool).await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(event.guild()),
+            actor: ctx.author().id,
+            action: "event_cancel",
+            target: Some(event.id as u64),
+            details: event.title.clone(),
+            undo: vec![UndoStep::RepublishEvent(event.id)],
+        },
+    )
+    .await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "**{}** was cancelled. Use `/undo` if that was a mistake.",
+                event.title
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    // Answered, so the DMs can go out as a batch behind everyone else's interactions.
+    ctx.data().calls.close(ctx.id());
+    let content = notify::event_message(pool, NotificationKind::Cancellation, &event, None).await?;
+    notify::fan_out(
+        ctx.serenity_context(),
+        pool,
+        &ctx.data().calls,
+        NotificationRun {
+            guild_id: event.guild(),
+            kind: NotificationKind::Cancellation,
+            subject_id: event.id,
+        },
+        signed_up,
+        &content,
+        ctx.data().clock.now(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_forms_check_their_fields() {
+        let mut form = EventModal {
+            title: "Pond cleanup".to_string(),
+            when: "2024-03-01 19:30".to_string(),
+            duration: "90".to_string(),
+            capacity: None,
+            description: None,
+        };
+        let fields = form.validate().unwrap();
+        assert_eq!(fields.duration_minutes, 90);
+        assert_eq!(fields.capacity, None);
+
+        form.when = "next friday".to_string();
+        assert!(matches!(form.validate(), Err(SlimeError::InvalidTime(_))));
+        form.when = "2024-03-01 19:30".to_string();
+        form.capacity = Some("0".to_string());
+        assert!(matches!(form.validate(), Err(SlimeError::InvalidNumber(_))));
+    }
+}
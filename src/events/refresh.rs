@@ -0,0 +1,73 @@
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::Event;
+
+/// How long sign-ups gather before the post shows them. Popular events get RSVPs faster than
+/// Discord lets one message be edited, so each change goes to the database straight away and the
+/// post catches up with all of them at once.
+const SETTLE: Duration = Duration::from_secs(5);
+
+/// Events with a post update on the way.
+fn pending() -> &'static Mutex<HashSet<i64>> {
+    static PENDING: OnceLock<Mutex<HashSet<i64>>> = OnceLock::new();
+    PENDING.get_or_init(Default::default)
+}
+
+/// Takes the event's next update, unless one is already coming that will cover this change.
+fn claim(pending: &Mutex<HashSet<i64>>, event_id: i64) -> bool {
+    pending.lock().unwrap().insert(event_id)
+}
+
+fn release(pending: &Mutex<HashSet<i64>>, event_id: i64) {
+    pending.lock().unwrap().remove(&event_id);
+}
+
+/// Brings the event's post up to date shortly, along with any other changes made in the
+/// meantime. The post is drawn from what the database has by then, so its counts end up exact
+/// however many changes it skipped showing. Failures are only logged, since the RSVPs themselves
+/// are saved either way.
+pub fn soon(ctx: &SerenityContext, pool: &PgPool, event_id: i64) {
+    if !claim(pending(), event_id) {
+        return;
+    }
+    let (ctx, pool) = (ctx.clone(), pool.clone());
+    tokio::spawn(async move {
+        tokio::time::sleep(SETTLE).await;
+        // Released before reading, so a change that lands while the post is being drawn gets
+        // an update of its own instead of being lost.
+        release(pending(), event_id);
+        let refreshed = async {
+            match Event::fetch(&pool, event_id).await? {
+                Some(event) => event.refresh_post(&ctx, &pool).await,
+                None => Ok(()),
+            }
+        };
+        if let Err(e) = refreshed.await {
+            error!("Could not update the post of event {}: {}", event_id, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_share_the_update_already_coming() {
+        let pending = Mutex::default();
+        assert!(claim(&pending, 1));
+        assert!(!claim(&pending, 1));
+        assert!(claim(&pending, 2));
+
+        release(&pending, 1);
+        assert!(claim(&pending, 1));
+    }
+}
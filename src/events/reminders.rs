@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{rsvp, Event};
+use crate::{
+    digest,
+    notify::{self, NotificationKind, NotificationRun},
+    Data, SlimeError,
+};
+
+/// How long before an event starts its attendees are reminded.
+const LEAD_MINUTES: i32 = 60;
+
+/// Who to remind of `event`, and what to tell them.
+async fn prepare(pool: &PgPool, event: &Event) -> Result<(Vec<UserId>, String), SlimeError> {
+    let mut attendees = rsvp::confirmed(pool, event.id).await?;
+    // Their digest already listed the event.
+    let covered = digest::covered(pool, event, &attendees).await?;
+    attendees.retain(|user| !covered.contains(user));
+    let mut content = notify::event_message(pool, NotificationKind::Reminder, event, None).await?;
+    if let Some(forecast) = event.forecast.as_ref().filter(|_| event.outdoor) {
+        content.push_str(&format!("\nForecast: {forecast}"));
+    }
+    let unclaimed = event.unclaimed_items();
+    if !unclaimed.is_empty() {
+        content.push_str(&format!(
+            "\nStill needed, pick one on the event's post: **{}**",
+            unclaimed.join("**, **")
+        ));
+    }
+
+    Ok((attendees, content))
+}
+
+/// DMs everyone with a confirmed place at events starting within the hour. Called by the
+/// scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    // Claimed before sending, so a slow run can't be picked up again by the next tick.
+    let due = sqlx::query_as::<_, Event>(
+        "UPDATE events SET reminded_at = $1
+         WHERE status = 'published' AND reminded_at IS NULL
+            AND starts_at > $1 AND starts_at <= $1 + make_interval(mins => $2)
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .bind(LEAD_MINUTES)
+    .fetch_all(pool)
+    .await?;
+
+    for event in due {
+        // One event's trouble shouldn't cost the others their reminders.
+        let (attendees, content) = match prepare(pool, &event).await {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                error!("Could not prepare reminders for event {}: {}", event.id, e);
+                // Nothing was sent, so the next tick can try again.
+                let released = sqlx::query("UPDATE events SET reminded_at = NULL WHERE id = $1")
+                    .bind(event.id)
+                    .execute(pool)
+                    .await;
+                if let Err(e) = released {
+                    error!("Could not release reminders for event {}: {}", event.id, e);
+                }
+                continue;
+            }
+        };
+        // Some may have gone out by the time this fails, so it isn't tried again.
+        let sent = notify::fan_out(
+            ctx,
+            pool,
+            &data.calls,
+            NotificationRun {
+                guild_id: event.guild(),
+                kind: NotificationKind::Reminder,
+                subject_id: event.id,
+            },
+            attendees,
+            &content,
+            now,
+        )
+        .await;
+        if let Err(e) = sent {
+            error!("Could not send reminders for event {}: {}", event.id, e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,817 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{
+    buttons::RsvpButton, channels, escalation, fetch_managed, items, refresh, slots, Event,
+    EventStatus,
+};
+use crate::{
+    away,
+    custom_id::{CustomId, Kind},
+    digest,
+    emoji::{self, Slot},
+    notify::{self, NotificationKind},
+    settings::GuildSettings,
+    util::{respond_ephemeral, send_dm},
+    Context, Data, SlimeError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum RsvpState {
+    /// Has one of the event's places.
+    Confirmed,
+    /// Waiting for a place to free up, in order of `created_at`.
+    Waitlist,
+    /// Keeping an eye on the event without taking a place.
+    Interested,
+    /// Turned down by the host, and can't sign up again until readmitted.
+    Rejected,
+}
+
+/// What one RSVP change did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub user: UserId,
+    /// The member's state before and after. The same on both sides when nothing changed.
+    pub from: Option<RsvpState>,
+    pub to: Option<RsvpState>,
+    /// Taken off the waitlist into the place the change freed.
+    pub promoted: Option<UserId>,
+    /// Where the member stands on the waitlist afterwards, counting from 1, if they're on it.
+    pub waitlist_place: Option<usize>,
+}
+
+/// An event's RSVPs in the order they reached their current state, and the rules for changing
+/// them. Free of the database and Discord so the rules can be checked on their own; [`join`] and
+/// the other changes load one, apply a transition, and write it back.
+#[derive(Debug, Clone, Default)]
+pub struct RsvpStateMachine {
+    capacity: Option<usize>,
+    rsvps: Vec<(UserId, RsvpState)>,
+}
+
+impl RsvpStateMachine {
+    pub fn new(capacity: Option<usize>, rsvps: Vec<(UserId, RsvpState)>) -> Self {
+        Self { capacity, rsvps }
+    }
+
+    pub fn state(&self, user: UserId) -> Option<RsvpState> {
+        self.rsvps
+            .iter()
+            .find(|(id, _)| *id == user)
+            .map(|(_, state)| *state)
+    }
+
+    /// Members in `state`, longest there first.
+    pub fn in_state(&self, state: RsvpState) -> impl Iterator<Item = UserId> + '_ {
+        self.rsvps
+            .iter()
+            .filter(move |(_, s)| *s == state)
+            .map(|(id, _)| *id)
+    }
+
+    /// Where `user` stands on the waitlist, counting from 1.
+    pub fn waitlist_place(&self, user: UserId) -> Option<usize> {
+        self.in_state(RsvpState::Waitlist)
+            .position(|id| id == user)
+            .map(|i| i + 1)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|capacity| self.in_state(RsvpState::Confirmed).count() >= capacity)
+    }
+
+    /// Moves `user` to `to`, at the back of the line for it. A place given up goes to whoever
+    /// has waited longest.
+    fn transition(&mut self, user: UserId, to: Option<RsvpState>) -> Transition {
+        let from = self.state(user);
+        let mut transition = Transition {
+            user,
+            from,
+            to: from,
+            promoted: None,
+            waitlist_place: self.waitlist_place(user),
+        };
+        if from == to {
+            return transition;
+        }
+
+        self.rsvps.retain(|(id, _)| *id != user);
+        if let Some(to) = to {
+            self.rsvps.push((user, to));
+        }
+        transition.to = to;
+
+        if from == Some(RsvpState::Confirmed) && !self.is_full() {
+            transition.promoted = self.in_state(RsvpState::Waitlist).next();
+            if let Some(promoted) = transition.promoted {
+                let entry = self.rsvps.iter_mut().find(|(id, _)| *id == promoted);
+                entry.expect("promoted from the list").1 = RsvpState::Confirmed;
+            }
+        }
+        transition.waitlist_place = self.waitlist_place(user);
+
+        transition
+    }
+
+    /// Takes a place if one is free, and joins the waitlist otherwise.
+    pub fn join(&mut self, user: UserId) -> Transition {
+        match self.state(user) {
+            None | Some(RsvpState::Interested) => {
+                let to = if self.is_full() {
+                    RsvpState::Waitlist
+                } else {
+                    RsvpState::Confirmed
+                };
+                self.transition(user, Some(to))
+            }
+            state => self.transition(user, state),
+        }
+    }
+
+    /// Marks interest, giving up any place or spot on the waitlist.
+    pub fn interested(&mut self, user: UserId) -> Transition {
+        match self.state(user) {
+            Some(RsvpState::Rejected) => self.transition(user, Some(RsvpState::Rejected)),
+            _ => self.transition(user, Some(RsvpState::Interested)),
+        }
+    }
+
+    /// Marks interest shown somewhere other than the bot, like the event's Discord scheduled
+    /// event. An RSVP made here always wins over that.
+    pub fn noticed(&mut self, user: UserId) -> Transition {
+        match self.state(user) {
+            None => self.transition(user, Some(RsvpState::Interested)),
+            state => self.transition(user, state),
+        }
+    }
+
+    /// Drops out entirely. Leaving doesn't lift a rejection.
+    pub fn leave(&mut self, user: UserId) -> Transition {
+        match self.state(user) {
+            Some(RsvpState::Rejected) => self.transition(user, Some(RsvpState::Rejected)),
+            _ => self.transition(user, None),
+        }
+    }
+
+    /// The host turns `user` down, whatever they had signed up as.
+    pub fn reject(&mut self, user: UserId) -> Transition {
+        self.transition(user, Some(RsvpState::Rejected))
+    }
+
+    /// The host lets a rejected `user` sign up again.
+    pub fn readmit(&mut self, user: UserId) -> Transition {
+        match self.state(user) {
+            Some(RsvpState::Rejected) => self.transition(user, None),
+            state => self.transition(user, state),
+        }
+    }
+}
+
+/// The event's RSVP buttons, as its host labelled them and with the guild's emoji.
+pub fn make_rsvp_buttons(event: &Event, settings: &GuildSettings) -> CreateActionRow {
+    let button = |action: &str, which: RsvpButton, slot: Slot| {
+        let button = CreateButton::new(CustomId::new(Kind::EventRsvp, action, event.id))
+            .label(event.rsvp_label(which))
+            .style(event.rsvp_style(which));
+        match emoji::lookup(settings, slot) {
+            Some(emoji) => button.emoji(emoji),
+            None => button,
+        }
+    };
+    CreateActionRow::Buttons(vec![
+        button("join", RsvpButton::Going, Slot::Going),
+        button("interested", RsvpButton::Interested, Slot::Interested),
+        button("leave", RsvpButton::CantGo, Slot::CantGo),
+    ])
+}
+
+/// Recounts an event's RSVPs into its cached counters. Must run inside the transaction that
+/// changed them.
+async fn recount(conn: &mut sqlx::PgConnection, event_id: i64) -> Result<(), SlimeError> {
+    sqlx::query(
+        "UPDATE events SET
+            confirmed_count = (SELECT COUNT(*) FROM event_rsvps WHERE event_id = $1 AND state = 'confirmed'),
+            waitlist_count = (SELECT COUNT(*) FROM event_rsvps WHERE event_id = $1 AND state = 'waitlist'),
+            interested_count = (SELECT COUNT(*) FROM event_rsvps WHERE event_id = $1 AND state = 'interested')
+         WHERE id = $1",
+    )
+    .bind(event_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Loads the event's RSVPs, applies `change` and writes back what it did, all under a lock on the
+/// event so concurrent changes to it queue up.
+async fn apply(
+    pool: &PgPool,
+    event: &Event,
+    change: impl FnOnce(&mut RsvpStateMachine) -> Transition,
+) -> Result<Transition, SlimeError> {
+    apply_from(pool, event, false, change).await
+}
+
+/// Like [`apply`], recording whether the change came from the event's Discord scheduled event
+/// rather than from the member using the bot.
+async fn apply_from(
+    pool: &PgPool,
+    event: &Event,
+    from_discord: bool,
+    change: impl FnOnce(&mut RsvpStateMachine) -> Transition,
+) -> Result<Transition, SlimeError> {
+    let mut tx = pool.begin().await?;
+
+    // Two members pressing for the last place at once both see it free unless their changes take
+    // turns, so the lock comes before anything is read. The capacity and status are read under it
+    // too, since `event` may be from before the host changed them.
+    let (capacity, status) = sqlx::query_as::<_, (Option<i32>, EventStatus)>(
+        "SELECT capacity, status FROM events WHERE id = $1 FOR UPDATE",
+    )
+    .bind(event.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(event.id));
+    }
+    let rsvps = sqlx::query_as::<_, (i64, RsvpState)>(
+        "SELECT user_id, state FROM event_rsvps WHERE event_id = $1 ORDER BY created_at, user_id",
+    )
+    .bind(event.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut machine = RsvpStateMachine::new(
+        capacity.map(|capacity| capacity.max(0) as usize),
+        rsvps
+            .into_iter()
+            .map(|(id, state)| (UserId::new(id as u64), state))
+            .collect(),
+    );
+
+    let transition = change(&mut machine);
+    let user = transition.user.get() as i64;
+    match (transition.from, transition.to) {
+        // Answering on the bot what Discord already had makes the RSVP the member's own.
+        (Some(_), Some(_)) if transition.from == transition.to && !from_discord => {
+            sqlx::query(
+                "UPDATE event_rsvps SET from_discord = false WHERE event_id = $1 AND user_id = $2",
+            )
+            .bind(event.id)
+            .bind(user)
+            .execute(&mut *tx)
+            .await?;
+        }
+        (from, to) if from == to => {}
+        (None, Some(to)) => {
+            sqlx::query(
+                "INSERT INTO event_rsvps (event_id, user_id, state, from_discord)
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(event.id)
+            .bind(user)
+            .bind(to)
+            .bind(from_discord)
+            .execute(&mut *tx)
+            .await?;
+        }
+        (Some(_), None) => {
+            sqlx::query("DELETE FROM event_rsvps WHERE event_id = $1 AND user_id = $2")
+                .bind(event.id)
+                .bind(user)
+                .execute(&mut *tx)
+                .await?;
+        }
+        // Back of the line for the new state, like the machine has it.
+        (_, to) => {
+            sqlx::query(
+                "UPDATE event_rsvps SET state = $3, created_at = now(), from_discord = $4
+                 WHERE event_id = $1 AND user_id = $2",
+            )
+            .bind(event.id)
+            .bind(user)
+            .bind(to)
+            .bind(from_discord)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    if let Some(promoted) = transition.promoted {
+        sqlx::query(
+            "UPDATE event_rsvps SET state = 'confirmed' WHERE event_id = $1 AND user_id = $2",
+        )
+        .bind(event.id)
+        .bind(promoted.get() as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let confirmed = Some(RsvpState::Confirmed);
+    if transition.from == confirmed && transition.to != confirmed {
+        items::release(&mut tx, event.id, transition.user).await?;
+        slots::release(&mut tx, event.id, transition.user).await?;
+    }
+    recount(&mut tx, event.id).await?;
+    tx.commit().await?;
+
+    Ok(transition)
+}
+
+/// Signs `user` up, taking a place if one is free and joining the waitlist otherwise.
+pub async fn join(pool: &PgPool, event: &Event, user: UserId) -> Result<Transition, SlimeError> {
+    apply(pool, event, |machine| machine.join(user)).await
+}
+
+/// Marks `user` interested because they are on the event's Discord scheduled event, unless they
+/// already answered here.
+pub async fn noticed(pool: &PgPool, event: &Event, user: UserId) -> Result<bool, SlimeError> {
+    let transition = apply_from(pool, event, true, |machine| machine.noticed(user)).await?;
+    Ok(transition.from != transition.to)
+}
+
+/// Takes back interest that only came from the event's Discord scheduled event, once `user`
+/// isn't interested there any more.
+pub async fn unnoticed(pool: &PgPool, event: &Event, user: UserId) -> Result<bool, SlimeError> {
+    let mut tx = pool.begin().await?;
+    let removed = sqlx::query(
+        "DELETE FROM event_rsvps
+         WHERE event_id = $1 AND user_id = $2 AND state = 'interested' AND from_discord",
+    )
+    .bind(event.id)
+    .bind(user.get() as i64)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    recount(&mut tx, event.id).await?;
+    tx.commit().await?;
+
+    Ok(removed > 0)
+}
+
+/// Drops `user`'s RSVP. If that freed a place, the longest-waiting member is promoted into it
+/// and returned.
+pub async fn leave(
+    pool: &PgPool,
+    event: &Event,
+    user: UserId,
+) -> Result<Option<UserId>, SlimeError> {
+    Ok(apply(pool, event, |machine| machine.leave(user))
+        .await?
+        .promoted)
+}
+
+/// Brings Discord in line with a transition: event roles follow confirmed places, and whoever
+/// was promoted hears about it.
+async fn sync(ctx: &SerenityContext, pool: &PgPool, event: &Event, transition: &Transition) {
+    let confirmed = Some(RsvpState::Confirmed);
+    if transition.from == confirmed && transition.to != confirmed {
+        channels::sync_role(ctx, event, transition.user, false).await;
+    }
+    if transition.to == confirmed && transition.from != confirmed {
+        channels::sync_role(ctx, event, transition.user, true).await;
+    }
+    if let Some(promoted) = transition.promoted {
+        channels::sync_role(ctx, event, promoted, true).await;
+        notify_promoted(ctx, pool, event, promoted).await;
+    }
+}
+
+/// Members holding a confirmed place at the event.
+pub async fn confirmed(pool: &PgPool, event_id: i64) -> Result<Vec<UserId>, SlimeError> {
+    let ids = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM event_rsvps WHERE event_id = $1 AND state = 'confirmed'",
+    )
+    .bind(event_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Members with a confirmed place or on the waitlist.
+pub async fn signed_up(pool: &PgPool, event_id: i64) -> Result<Vec<UserId>, SlimeError> {
+    let ids = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM event_rsvps
+         WHERE event_id = $1 AND state IN ('confirmed', 'waitlist') AND user_id > 0",
+    )
+    .bind(event_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Handles the RSVP buttons on event posts.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let (action, event_id) = (custom_id.action.as_str(), custom_id.id);
+
+    const CLOSED: &str = "This event is no longer taking RSVPs.";
+    let pool = &data.pool;
+    let event = data
+        .events
+        .get(pool, event_id)
+        .await?
+        .filter(|e| e.status == EventStatus::Published);
+    let Some(event) = event else {
+        return respond_ephemeral(ctx, interaction, CLOSED).await;
+    };
+
+    let user = interaction.user.id;
+    let transition = match action {
+        "join" => join(pool, &event, user).await,
+        "interested" => apply(pool, &event, |machine| machine.interested(user)).await,
+        "leave" => apply(pool, &event, |machine| machine.leave(user)).await,
+        _ => return Ok(()),
+    };
+    data.events.invalidate(event_id);
+    let transition = match transition {
+        // Closed since it was cached.
+        Err(SlimeError::EventNotFound(_)) => {
+            return respond_ephemeral(ctx, interaction, CLOSED).await
+        }
+        transition => transition?,
+    };
+    sync(ctx, pool, &event, &transition).await;
+    let content = match transition.to {
+        Some(RsvpState::Rejected) => {
+            "The host has turned down your RSVP, so you can't sign up for this event.".to_string()
+        }
+        Some(RsvpState::Confirmed) => "You're going! See you there.".to_string(),
+        // Also what someone who lost the race for the last place hears, whatever the post said.
+        Some(RsvpState::Waitlist) => format!(
+            "The event is full, so you're #{} on the waitlist. You'll get a DM if a place opens \
+             up.",
+            transition.waitlist_place.unwrap_or(1)
+        ),
+        Some(RsvpState::Interested) => {
+            "Marked you as interested. Press **I'm going** when you want a place.".to_string()
+        }
+        None => "You're no longer signed up.".to_string(),
+    };
+    respond_ephemeral(ctx, interaction, &content).await?;
+    refresh::soon(ctx, pool, event_id);
+
+    Ok(())
+}
+
+/// Lets a member know they got a place after someone else dropped out.
+pub async fn notify_promoted(ctx: &SerenityContext, pool: &PgPool, event: &Event, user: UserId) {
+    let message = async {
+        let position = confirmed(pool, event.id)
+            .await?
+            .iter()
+            .position(|id| *id == user)
+            .map(|i| i + 1);
+        notify::event_message(pool, NotificationKind::Promotion, event, position).await
+    };
+    let content = match message.await {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Could not word promotion for event {}: {}", event.id, e);
+            return;
+        }
+    };
+    let held = digest::hold_back(
+        pool,
+        &[user],
+        event.guild(),
+        NotificationKind::Promotion,
+        &content,
+        Utc::now(),
+    )
+    .await;
+    match held {
+        Ok(held) if held.contains_key(&user) => return,
+        Ok(_) => {}
+        Err(e) => error!("Could not hold promotion for event {}: {}", event.id, e),
+    }
+    if let Err(e) = send_dm(ctx, user, CreateMessage::new().content(content)).await {
+        error!("Could not DM promoted member of event {}: {}", event.id, e);
+    }
+}
+
+/// Applies a host's decision and brings Discord and the post up to date.
+async fn decide(
+    ctx: Context<'_>,
+    event: &Event,
+    change: impl FnOnce(&mut RsvpStateMachine) -> Transition,
+) -> Result<Transition, SlimeError> {
+    let pool = &ctx.data().pool;
+    let transition = apply(pool, event, change).await?;
+    escalation::acted(pool, event.id).await?;
+    sync(ctx.serenity_context(), pool, event, &transition).await;
+    if let Some(event) = Event::fetch(pool, event.id).await? {
+        event.refresh_post(ctx.serenity_context(), pool).await?;
+    }
+
+    Ok(transition)
+}
+
+/// Turn someone down for your event, freeing any place they had.
+#[poise::command(slash_command, guild_only)]
+pub async fn reject(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Member to turn down"] member: User,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+
+    let transition = decide(ctx, &event, |machine| machine.reject(member.id)).await?;
+    let content = if transition.from == Some(RsvpState::Rejected) {
+        format!(
+            "{} was already turned down for **{}**.",
+            member.mention(),
+            event.title
+        )
+    } else {
+        format!(
+            "Turned {} down for **{}**. They can't sign up again unless you `/event readmit` them.",
+            member.mention(),
+            event.title
+        )
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Who's signed up, in order, with anyone who's away marked.
+fn roster(rsvps: &[(UserId, RsvpState)], away: &HashMap<UserId, DateTime<Utc>>) -> String {
+    let mut roster = String::new();
+    for (state, heading) in [
+        (RsvpState::Confirmed, "Going"),
+        (RsvpState::Waitlist, "Waitlist"),
+        (RsvpState::Interested, "Interested"),
+    ] {
+        let lines = rsvps
+            .iter()
+            .filter(|r| r.1 == state)
+            .map(|(user, _)| {
+                let marker = away.get(user).map(|&until| away::marker(until));
+                format!("- {}{}", user.mention(), marker.unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+        if !lines.is_empty() {
+            roster.push_str(&format!("\n\n**{heading}**\n{}", lines.join("\n")));
+        }
+    }
+    roster
+}
+
+/// See who's signed up for your event, and who of them is away.
+#[poise::command(slash_command, guild_only)]
+pub async fn rsvps(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    let pool = &ctx.data().pool;
+    let rsvps = sqlx::query_as::<_, (i64, RsvpState)>(
+        "SELECT user_id, state FROM event_rsvps
+         WHERE event_id = $1 AND state <> 'rejected' AND user_id > 0
+         ORDER BY created_at",
+    )
+    .bind(event.id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(id, state)| (UserId::new(id as u64), state))
+    .collect::<Vec<_>>();
+
+    let users = rsvps.iter().map(|r| r.0).collect::<Vec<_>>();
+    let away = away::away_now(pool, &users, ctx.data().clock.now()).await?;
+    let content = if rsvps.is_empty() {
+        format!("Nobody has signed up for **{}** yet.", event.title)
+    } else {
+        format!("RSVPs for **{}**:{}", event.title, roster(&rsvps, &away))
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Let someone you turned down sign up for your event again.
+#[poise::command(slash_command, guild_only)]
+pub async fn readmit(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Member to let back in"] member: User,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+
+    let transition = decide(ctx, &event, |machine| machine.readmit(member.id)).await?;
+    let content = if transition.from == Some(RsvpState::Rejected) {
+        format!(
+            "{} can sign up for **{}** again.",
+            member.mention(),
+            event.title
+        )
+    } else {
+        format!(
+            "{} wasn't turned down for **{}**.",
+            member.mention(),
+            event.title
+        )
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Op {
+        Join(u64),
+        Interested(u64),
+        Noticed(u64),
+        Leave(u64),
+        Reject(u64),
+        Readmit(u64),
+    }
+
+    impl Op {
+        fn run(self, machine: &mut RsvpStateMachine) -> Transition {
+            match self {
+                Op::Join(user) => machine.join(UserId::new(user)),
+                Op::Interested(user) => machine.interested(UserId::new(user)),
+                Op::Noticed(user) => machine.noticed(UserId::new(user)),
+                Op::Leave(user) => machine.leave(UserId::new(user)),
+                Op::Reject(user) => machine.reject(UserId::new(user)),
+                Op::Readmit(user) => machine.readmit(UserId::new(user)),
+            }
+        }
+
+        fn user(self) -> UserId {
+            let (Op::Join(user)
+            | Op::Interested(user)
+            | Op::Noticed(user)
+            | Op::Leave(user)
+            | Op::Reject(user)
+            | Op::Readmit(user)) = self;
+            UserId::new(user)
+        }
+    }
+
+    /// Few enough members that the same ones keep coming back, with joins weighted up so events
+    /// actually fill.
+    fn op() -> impl Strategy<Value = Op> {
+        let user = 1..=12u64;
+        prop_oneof![
+            4 => user.clone().prop_map(Op::Join),
+            2 => user.clone().prop_map(Op::Interested),
+            1 => user.clone().prop_map(Op::Noticed),
+            2 => user.clone().prop_map(Op::Leave),
+            1 => user.clone().prop_map(Op::Reject),
+            1 => user.prop_map(Op::Readmit),
+        ]
+    }
+
+    fn waitlist(machine: &RsvpStateMachine) -> Vec<UserId> {
+        machine.in_state(RsvpState::Waitlist).collect()
+    }
+
+    #[test]
+    fn the_last_place_goes_to_whoever_is_first() {
+        let (host, first, second) = (UserId::new(1), UserId::new(2), UserId::new(3));
+        let mut machine = RsvpStateMachine::new(Some(2), vec![(host, RsvpState::Confirmed)]);
+
+        assert_eq!(machine.join(first).to, Some(RsvpState::Confirmed));
+        let lost = machine.join(second);
+        assert_eq!(lost.to, Some(RsvpState::Waitlist));
+        assert_eq!(lost.waitlist_place, Some(1));
+
+        let promoted = machine.leave(first);
+        assert_eq!(promoted.promoted, Some(second));
+        assert_eq!(machine.waitlist_place(second), None);
+    }
+
+    #[test]
+    fn roster_groups_rsvps_and_marks_who_is_away() {
+        let until = Utc::now() + chrono::Duration::days(3);
+        let rsvps = [
+            (UserId::new(1), RsvpState::Waitlist),
+            (UserId::new(2), RsvpState::Confirmed),
+            (UserId::new(3), RsvpState::Confirmed),
+        ];
+        let away = HashMap::from([(UserId::new(3), until)]);
+
+        let roster = roster(&rsvps, &away);
+        assert_eq!(
+            roster,
+            format!(
+                "\n\n**Going**\n- <@2>\n- <@3>{}\n\n**Waitlist**\n- <@1>",
+                away::marker(until)
+            )
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn capacity_is_never_exceeded(
+            capacity in proptest::option::of(0..6usize),
+            ops in vec(op(), 0..200),
+        ) {
+            let mut machine = RsvpStateMachine::new(capacity, Vec::new());
+            for op in ops {
+                op.run(&mut machine);
+                let confirmed = machine.in_state(RsvpState::Confirmed).count();
+                if let Some(capacity) = capacity {
+                    prop_assert!(confirmed <= capacity);
+                }
+                // Nobody waits while there's a place free.
+                prop_assert!(machine.is_full() || waitlist(&machine).is_empty());
+            }
+        }
+
+        #[test]
+        fn waitlist_order_is_preserved(
+            capacity in proptest::option::of(0..6usize),
+            ops in vec(op(), 0..200),
+        ) {
+            let mut machine = RsvpStateMachine::new(capacity, Vec::new());
+            for op in ops {
+                let before = waitlist(&machine);
+                let transition = op.run(&mut machine);
+                let after = waitlist(&machine);
+
+                // Whoever is still waiting is in the same order, and anyone new is at the back.
+                let kept = before
+                    .iter()
+                    .copied()
+                    .filter(|user| after.contains(user))
+                    .collect::<Vec<_>>();
+                prop_assert_eq!(&after[..kept.len()], &kept[..]);
+                prop_assert!(after.len() <= kept.len() + 1);
+                if after.len() > kept.len() {
+                    prop_assert_eq!(after.last(), Some(&op.user()));
+                }
+                if let Some(promoted) = transition.promoted {
+                    prop_assert_eq!(before.first(), Some(&promoted));
+                }
+            }
+        }
+
+        #[test]
+        fn transitions_describe_the_change(
+            capacity in proptest::option::of(0..6usize),
+            ops in vec(op(), 0..200),
+        ) {
+            let mut machine = RsvpStateMachine::new(capacity, Vec::new());
+            for op in ops {
+                let user = op.user();
+                let from = machine.state(user);
+                let transition = op.run(&mut machine);
+                prop_assert_eq!(transition.user, user);
+                prop_assert_eq!(transition.from, from);
+                prop_assert_eq!(transition.to, machine.state(user));
+                prop_assert_eq!(transition.waitlist_place, machine.waitlist_place(user));
+                if let Some(place) = transition.waitlist_place {
+                    let waiting = waitlist(&machine);
+                    prop_assert_eq!(waiting.get(place - 1), Some(&user));
+                }
+                if let Some(promoted) = transition.promoted {
+                    prop_assert_eq!(transition.from, Some(RsvpState::Confirmed));
+                    prop_assert_eq!(machine.state(promoted), Some(RsvpState::Confirmed));
+                }
+                // Only the host lifts a rejection.
+                if from == Some(RsvpState::Rejected) && !matches!(op, Op::Readmit(_)) {
+                    prop_assert_eq!(transition.to, Some(RsvpState::Rejected));
+                }
+                // Interest on Discord never overrides an answer given here.
+                if matches!(op, Op::Noticed(_)) && from.is_some() {
+                    prop_assert_eq!(transition.to, from);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use super::{
+    fetch_managed,
+    location::{Location, Place},
+    sync, Event, EventStatus,
+};
+use crate::{settings::GuildSettings, Context, SlimeError};
+
+/// Whether the event should have a Discord scheduled event: its own choice if it made one, and
+/// the guild's otherwise.
+pub fn wanted(event: &Event, settings: &GuildSettings) -> bool {
+    event
+        .discord_event
+        .unwrap_or(!settings.scheduled_events_off)
+}
+
+/// Mirrors the event into Discord's scheduled events, pointing at `link` when it has no location.
+///
+/// Failing to create it (usually missing Manage Events) is logged, and doesn't stop the event
+/// from being posted.
+pub async fn create(
+    ctx: &SerenityContext,
+    event: &Event,
+    link: String,
+    banner: Option<&CreateAttachment>,
+) -> Option<ScheduledEvent> {
+    let mut scheduled = match Location::of(event).place(ctx, link).await {
+        Place::External(location) => {
+            CreateScheduledEvent::new(ScheduledEventType::External, &event.title, event.starts_at)
+                .location(location)
+        }
+        Place::Channel(kind, channel) => {
+            CreateScheduledEvent::new(kind, &event.title, event.starts_at).channel_id(channel)
+        }
+    }
+    .end_time(event.ends_at());
+    if !event.description.is_empty() {
+        scheduled = scheduled.description(&event.description);
+    }
+    if let Some(banner) = banner {
+        scheduled = scheduled.image(banner);
+    }
+
+    event
+        .guild()
+        .create_scheduled_event(ctx, scheduled)
+        .await
+        .inspect_err(|e| {
+            error!(
+                "Could not create scheduled event for event {}: {}",
+                event.id, e
+            )
+        })
+        .ok()
+}
+
+/// Choose whether an event shows up in Discord's event list, or only on its post.
+#[poise::command(slash_command, guild_only)]
+pub async fn discord_event(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Whether it gets a Discord event. Leave out to follow the server setting"]
+    enabled: Option<bool>,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if !matches!(event.status, EventStatus::Draft | EventStatus::Published) {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let pool = &ctx.data().pool;
+    event.discord_event = enabled;
+    sqlx::query("UPDATE events SET discord_event = $2 WHERE id = $1")
+        .bind(event.id)
+        .bind(event.discord_event)
+        .execute(pool)
+        .await?;
+    let wanted = wanted(&event, &GuildSettings::load(pool, event.guild()).await?);
+
+    // Published events get theirs added or taken down straight away.
+    if event.status == EventStatus::Published {
+        match (wanted, event.scheduled_event_id) {
+            (false, Some(scheduled)) => {
+                event
+                    .guild()
+                    .delete_scheduled_event(ctx, ScheduledEventId::new(scheduled as u64))
+                    .await?;
+                event.scheduled_event_id = None;
+                event.save(pool).await?;
+            }
+            (true, None) => {
+                let link = match event.message_id {
+                    Some(message) => {
+                        MessageId::new(message as u64).link(event.channel(), Some(event.guild()))
+                    }
+                    None => event.channel().mention().to_string(),
+                };
+                let banner = event.banner_image(ctx.serenity_context(), pool).await?;
+                let Some(scheduled) =
+                    create(ctx.serenity_context(), &event, link, banner.as_ref()).await
+                else {
+                    let content = "I couldn't create a Discord event for it. Check that I have \
+                                   the Manage Events permission, then try again.";
+                    ctx.send(CreateReply::default().content(content).ephemeral(true))
+                        .await?;
+                    return Ok(());
+                };
+                event.scheduled_event_id = Some(scheduled.id.get() as i64);
+                event.save(pool).await?;
+                sync::mark_synced(pool, &event).await?;
+            }
+            _ => {}
+        }
+    }
+
+    let content = if wanted {
+        format!("**{}** shows up in Discord's event list.", event.title)
+    } else {
+        format!(
+            "**{}** doesn't show up in Discord's event list, so the buttons on its post are the \
+             only way to sign up.",
+            event.title
+        )
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
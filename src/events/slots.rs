@@ -0,0 +1,475 @@
+use chrono::Duration;
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgConnection;
+
+use super::{fetch_managed, parse_start_time, refresh, rsvp, Event, EventStatus};
+use crate::{
+    custom_id::{CustomId, Kind},
+    util::respond_ephemeral,
+    Context, Data, SlimeError,
+};
+
+/// Most slots an event can be divided into, so the schedule fits in one embed field.
+const MAX_SLOTS: usize = 12;
+const MAX_LABEL_LENGTH: usize = 40;
+/// Discord caps an embed field's value at this many characters.
+const FIELD_LENGTH: usize = 1024;
+
+impl Event {
+    pub fn has_slots(&self) -> bool {
+        !self.slot_ids.is_empty()
+    }
+
+    /// The schedule as shown on the event's post, one slot per line.
+    pub fn schedule(&self) -> Option<String> {
+        if !self.has_slots() {
+            return None;
+        }
+        let mut schedule = String::new();
+        for (i, line) in self.slot_lines.iter().enumerate() {
+            let offset = self.slot_offsets.get(i).copied().unwrap_or_default();
+            let minutes = self.slot_minutes.get(i).copied().unwrap_or_default();
+            let starts = self.starts_at + Duration::minutes(offset.into());
+            let ends = starts + Duration::minutes(minutes.into());
+            let line = format!(
+                "`{}.` <t:{}:t>–<t:{}:t> {line}\n",
+                i + 1,
+                starts.timestamp(),
+                ends.timestamp()
+            );
+            if schedule.len() + line.len() > FIELD_LENGTH - 1 {
+                schedule.push('…');
+                break;
+            }
+            schedule.push_str(&line);
+        }
+        Some(schedule.trim_end().to_string())
+    }
+}
+
+/// A slot's line in the schedule, after its times.
+fn line(label: Option<&str>, capacity: Option<i32>, members: &[i64]) -> String {
+    let mut line = String::new();
+    if let Some(label) = label {
+        line.push_str(&format!("**{label}** "));
+    }
+    match capacity {
+        Some(capacity) => line.push_str(&format!("({}/{capacity})", members.len())),
+        None => line.push_str(&format!("({})", members.len())),
+    }
+    if members.is_empty() {
+        line.push_str(": *open*");
+    } else {
+        let names = members
+            .iter()
+            .map(|id| UserId::new(*id as u64).mention().to_string())
+            .collect::<Vec<_>>();
+        line.push_str(&format!(": {}", names.join(", ")));
+    }
+    line
+}
+
+/// Rewrites the copy of the event's schedule its post renders from. Called after anything
+/// changes its slots or who's in them, in the same transaction.
+pub async fn sync(conn: &mut PgConnection, event_id: i64) -> Result<(), SlimeError> {
+    let slots = sqlx::query_as::<_, (i64, i32, i32, Option<String>, Option<i32>, Vec<i64>)>(
+        "SELECT s.id, s.offset_minutes, s.duration_minutes, s.label, s.capacity,
+            COALESCE(array_agg(u.user_id ORDER BY u.created_at)
+                FILTER (WHERE u.user_id IS NOT NULL), '{}')
+         FROM event_slots s LEFT JOIN event_slot_signups u ON u.slot_id = s.id
+         WHERE s.event_id = $1
+         GROUP BY s.id
+         ORDER BY s.offset_minutes, s.id",
+    )
+    .bind(event_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let ids = slots.iter().map(|s| s.0).collect::<Vec<_>>();
+    let offsets = slots.iter().map(|s| s.1).collect::<Vec<_>>();
+    let minutes = slots.iter().map(|s| s.2).collect::<Vec<_>>();
+    let lines = slots
+        .iter()
+        .map(|(_, _, _, label, capacity, members)| line(label.as_deref(), *capacity, members))
+        .collect::<Vec<_>>();
+    sqlx::query(
+        "UPDATE events SET slot_ids = $2, slot_offsets = $3, slot_minutes = $4, slot_lines = $5
+         WHERE id = $1",
+    )
+    .bind(event_id)
+    .bind(ids)
+    .bind(offsets)
+    .bind(minutes)
+    .bind(lines)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Takes `user` out of every slot of the event, once they no longer have a place at it.
+pub async fn release(
+    conn: &mut PgConnection,
+    event_id: i64,
+    user: UserId,
+) -> Result<(), SlimeError> {
+    let removed = sqlx::query(
+        "DELETE FROM event_slot_signups
+         WHERE user_id = $2 AND slot_id IN (SELECT id FROM event_slots WHERE event_id = $1)",
+    )
+    .bind(event_id)
+    .bind(user.get() as i64)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    if removed > 0 {
+        sync(conn, event_id).await?;
+    }
+    Ok(())
+}
+
+/// A menu for signing up to slots on the event's post, if it has any.
+pub fn make_slot_menu(event: &Event) -> Option<CreateActionRow> {
+    if !event.has_slots() {
+        return None;
+    }
+    let options = event
+        .slot_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| CreateSelectMenuOption::new(format!("Slot {}", i + 1), id.to_string()))
+        .collect();
+
+    Some(CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(
+            CustomId::new(Kind::EventSlot, "pick", event.id),
+            CreateSelectMenuKind::String { options },
+        )
+        .placeholder("Sign up for a slot"),
+    ))
+}
+
+/// Adds a slot starting `offset` minutes into the event. The caller syncs once it's done.
+pub async fn insert(
+    conn: &mut PgConnection,
+    event_id: i64,
+    (offset, length): (i32, i32),
+    label: Option<&str>,
+    capacity: Option<i32>,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO event_slots (event_id, offset_minutes, duration_minutes, label, capacity)
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(event_id)
+    .bind(offset)
+    .bind(length)
+    .bind(label)
+    .bind(capacity)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Splits `duration` minutes into back-to-back slots of `length`, as (offset, length) pairs. The
+/// last slot is cut short if it would run past the end.
+pub fn divide(duration: i32, length: i32) -> Vec<(i32, i32)> {
+    (0..duration)
+        .step_by(length.max(1) as usize)
+        .take(MAX_SLOTS)
+        .map(|offset| (offset, length.min(duration - offset)))
+        .collect()
+}
+
+/// Handles a pick from an event's slot menu: signs the member up for the slot, or takes them
+/// out of it if they were already in.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let event_id = custom_id.id;
+    let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind else {
+        return Ok(());
+    };
+    let Some(Ok(slot_id)) = values.first().map(|v| v.parse::<i64>()) else {
+        return Ok(());
+    };
+
+    let pool = &data.pool;
+    let event = data
+        .events
+        .get(pool, event_id)
+        .await?
+        .filter(|e| e.status == EventStatus::Published);
+    let Some(event) = event else {
+        return respond_ephemeral(ctx, interaction, "This event is over.").await;
+    };
+    let user = interaction.user.id;
+    if event.coverage {
+        // Shifts on a rota are for the moderators, who don't RSVP to them.
+        let moderator = interaction
+            .member
+            .as_ref()
+            .and_then(|m| m.permissions)
+            .is_some_and(|p| p.moderate_members());
+        if !moderator {
+            return respond_ephemeral(ctx, interaction, "Only moderators can take shifts.").await;
+        }
+    } else if !rsvp::confirmed(pool, event.id).await?.contains(&user) {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "Press **I'm going** first, then pick a slot.",
+        )
+        .await;
+    }
+
+    let mut tx = pool.begin().await?;
+    // Locked so two members can't both take a slot's last place.
+    let capacity = sqlx::query_scalar::<_, Option<i32>>(
+        "SELECT capacity FROM event_slots WHERE id = $1 AND event_id = $2 FOR UPDATE",
+    )
+    .bind(slot_id)
+    .bind(event.id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(capacity) = capacity else {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "That slot isn't on the schedule any more.",
+        )
+        .await;
+    };
+    let number = event
+        .slot_ids
+        .iter()
+        .position(|id| *id == slot_id)
+        .map_or(0, |i| i + 1);
+
+    let left = sqlx::query("DELETE FROM event_slot_signups WHERE slot_id = $1 AND user_id = $2")
+        .bind(slot_id)
+        .bind(user.get() as i64)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let content = if left > 0 {
+        format!("You're no longer down for slot {number}.")
+    } else {
+        let taken = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM event_slot_signups WHERE slot_id = $1",
+        )
+        .bind(slot_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if capacity.is_some_and(|c| taken >= c.into()) {
+            return respond_ephemeral(ctx, interaction, &format!("Slot {number} is full.")).await;
+        }
+        sqlx::query("INSERT INTO event_slot_signups (slot_id, user_id) VALUES ($1, $2)")
+            .bind(slot_id)
+            .bind(user.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+        format!("You're down for slot {number}.")
+    };
+    sync(&mut tx, event.id).await?;
+    tx.commit().await?;
+    data.events.invalidate(event.id);
+    respond_ephemeral(ctx, interaction, &content).await?;
+    refresh::soon(ctx, pool, event.id);
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Redraws the post after the schedule changed, if the event is up.
+async fn refresh(ctx: Context<'_>, event_id: i64) -> Result<(), SlimeError> {
+    if let Some(event) = Event::fetch(&ctx.data().pool, event_id)
+        .await?
+        .filter(|e| e.status == EventStatus::Published)
+    {
+        event
+            .refresh_post(ctx.serenity_context(), &ctx.data().pool)
+            .await?;
+    }
+    Ok(())
+}
+
+fn tidy_label(label: Option<String>) -> Option<String> {
+    label
+        .map(|l| l.trim().chars().take(MAX_LABEL_LENGTH).collect::<String>())
+        .filter(|l| !l.is_empty())
+}
+
+/// Divide an event into time slots or shifts that members sign up for one at a time.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "slots",
+    subcommands("split", "add", "remove")
+)]
+pub async fn slots_command(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Divide an event into back-to-back slots, replacing any it had.
+#[poise::command(slash_command, guild_only)]
+async fn split(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Length of each slot in minutes"]
+    #[min = 5]
+    minutes: u32,
+    #[description = "How many people each slot needs"]
+    #[min = 1]
+    capacity: Option<u32>,
+    #[description = "What each slot is, like `Front desk`"]
+    #[max_length = 40]
+    label: Option<String>,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if !matches!(event.status, EventStatus::Draft | EventStatus::Published) {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let slots = divide(event.duration_minutes, minutes as i32);
+    let label = tidy_label(label);
+
+    let mut tx = ctx.data().pool.begin().await?;
+    sqlx::query("DELETE FROM event_slots WHERE event_id = $1")
+        .bind(event.id)
+        .execute(&mut *tx)
+        .await?;
+    for slot in &slots {
+        insert(
+            &mut tx,
+            event.id,
+            *slot,
+            label.as_deref(),
+            capacity.map(|c| c as i32),
+        )
+        .await?;
+    }
+    sync(&mut tx, event.id).await?;
+    tx.commit().await?;
+
+    refresh(ctx, event.id).await?;
+    reply(
+        ctx,
+        format!(
+            "**{}** is now {} slot(s) of up to {minutes} minutes.",
+            event.title,
+            slots.len()
+        ),
+    )
+    .await
+}
+
+/// Add one slot or shift to an event's schedule.
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Start time, e.g. `2024-03-01 19:30` (UTC) or a Unix timestamp"] start: String,
+    #[description = "Length of the slot in minutes"]
+    #[min = 5]
+    minutes: u32,
+    #[description = "How many people the slot needs"]
+    #[min = 1]
+    capacity: Option<u32>,
+    #[description = "What the slot is, like `Stream host`"]
+    #[max_length = 40]
+    label: Option<String>,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    if !matches!(event.status, EventStatus::Draft | EventStatus::Published) {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let starts_at = parse_start_time(&start).ok_or(SlimeError::InvalidTime(start))?;
+    let offset = (starts_at - event.starts_at).num_minutes();
+    if starts_at < event.starts_at || starts_at >= event.ends_at() {
+        return reply(ctx, "Slots have to start while the event is on.").await;
+    }
+    if event.slot_ids.len() >= MAX_SLOTS {
+        return reply(
+            ctx,
+            format!("**{}** already has {MAX_SLOTS} slots.", event.title),
+        )
+        .await;
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+    insert(
+        &mut tx,
+        event.id,
+        (offset as i32, minutes as i32),
+        tidy_label(label).as_deref(),
+        capacity.map(|c| c as i32),
+    )
+    .await?;
+    sync(&mut tx, event.id).await?;
+    tx.commit().await?;
+
+    refresh(ctx, event.id).await?;
+    reply(
+        ctx,
+        format!(
+            "Added a slot at <t:{}:t> to **{}**.",
+            starts_at.timestamp(),
+            event.title
+        ),
+    )
+    .await
+}
+
+/// Take a slot off an event's schedule, along with its sign-ups.
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Slot number, as listed on the post"]
+    #[min = 1]
+    slot: u32,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    let Some(slot_id) = event.slot_ids.get(slot as usize - 1) else {
+        return reply(ctx, format!("**{}** has no slot {slot}.", event.title)).await;
+    };
+
+    let mut tx = ctx.data().pool.begin().await?;
+    sqlx::query("DELETE FROM event_slots WHERE id = $1 AND event_id = $2")
+        .bind(slot_id)
+        .bind(event.id)
+        .execute(&mut *tx)
+        .await?;
+    sync(&mut tx, event.id).await?;
+    tx.commit().await?;
+
+    refresh(ctx, event.id).await?;
+    reply(ctx, format!("Took slot {slot} off **{}**.", event.title)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_split_into_slots() {
+        assert_eq!(divide(90, 30), vec![(0, 30), (30, 30), (60, 30)]);
+        assert_eq!(divide(100, 45), vec![(0, 45), (45, 45), (90, 10)]);
+        assert_eq!(divide(600, 5).len(), MAX_SLOTS);
+    }
+
+    #[test]
+    fn slot_lines_show_who_is_in() {
+        assert_eq!(line(Some("Desk"), Some(2), &[]), "**Desk** (0/2): *open*");
+        assert_eq!(line(None, None, &[1, 2]), "(2): <@1>, <@2>");
+    }
+}
@@ -0,0 +1,228 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{channels::ATTENDEE_PERMISSIONS, fetch_managed, Event};
+use crate::{Context, Data, SlimeError};
+
+/// Members lined up to speak at the event. The host always may, so isn't listed.
+pub async fn speakers(pool: &PgPool, event_id: i64) -> Result<Vec<UserId>, SlimeError> {
+    let ids =
+        sqlx::query_scalar::<_, i64>("SELECT user_id FROM event_speakers WHERE event_id = $1")
+            .bind(event_id)
+            .fetch_all(pool)
+            .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Lets `user` into the stage even without the event role.
+async fn allow_into(
+    ctx: &SerenityContext,
+    stage: ChannelId,
+    user: UserId,
+) -> Result<(), SlimeError> {
+    stage
+        .create_permission(
+            ctx,
+            PermissionOverwrite {
+                allow: ATTENDEE_PERMISSIONS | Permissions::REQUEST_TO_SPEAK,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(user),
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Called once an event's stage exists: lets its speakers in, and moves any who are already
+/// sitting in another voice channel onto the stage, where [`handle_voice_state`] promotes them.
+pub async fn start_stage(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &Event,
+    stage: ChannelId,
+) -> Result<(), SlimeError> {
+    let speakers = speakers(pool, event.id).await?;
+    for speaker in &speakers {
+        allow_into(ctx, stage, *speaker).await?;
+    }
+
+    let connected = ctx
+        .cache
+        .guild(event.guild())
+        .map(|g| {
+            g.voice_states
+                .values()
+                .filter(|state| state.channel_id.is_some_and(|c| c != stage))
+                .map(|state| state.user_id)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut movers = speakers;
+    if event.host_id > 0 {
+        movers.push(event.host());
+    }
+    for user in movers.into_iter().filter(|u| connected.contains(u)) {
+        if let Err(e) = event.guild().move_member(ctx, user, stage).await {
+            error!(
+                "Could not move speaker {} onto stage for event {}: {}",
+                user, event.id, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes speakers joining an event's stage speakers straight away, and moves anyone else who
+/// joins as a speaker down to the audience.
+pub async fn handle_voice_state(
+    ctx: &SerenityContext,
+    data: &Data,
+    old: Option<&VoiceState>,
+    new: &VoiceState,
+) -> Result<(), SlimeError> {
+    let (Some(guild_id), Some(channel_id)) = (new.guild_id, new.channel_id) else {
+        return Ok(());
+    };
+    // Only joins matter, so the bot doesn't fight a moderator who changes things afterwards.
+    if old.and_then(|o| o.channel_id) == Some(channel_id) {
+        return Ok(());
+    }
+    let Some(stage) = ctx
+        .cache
+        .guild(guild_id)
+        .and_then(|g| g.channels.get(&channel_id).cloned())
+        .filter(|c| c.kind == ChannelType::Stage)
+    else {
+        return Ok(());
+    };
+
+    let pool = &data.pool;
+    let event = sqlx::query_as::<_, Event>("SELECT * FROM events WHERE voice_channel_id = $1")
+        .bind(channel_id.get() as i64)
+        .fetch_optional(pool)
+        .await?;
+    let Some(event) = event else {
+        return Ok(());
+    };
+
+    let is_speaker =
+        new.user_id == event.host() || speakers(pool, event.id).await?.contains(&new.user_id);
+    if new.suppress == is_speaker {
+        stage
+            .edit_voice_state(
+                ctx,
+                new.user_id,
+                EditVoiceState::new().suppress(!is_speaker),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Choose who speaks on your event's stage.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "speakers",
+    subcommands("add", "remove", "list")
+)]
+pub async fn speakers_command(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Line someone up to speak. They'll be made a speaker as soon as they join the stage.
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Member who'll speak"] member: User,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+
+    sqlx::query(
+        "INSERT INTO event_speakers (event_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(event.id)
+    .bind(member.id.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?;
+    // A stage that's already live needs to let them in now.
+    if let Some(stage) = event.voice_channel_id {
+        allow_into(
+            ctx.serenity_context(),
+            ChannelId::new(stage as u64),
+            member.id,
+        )
+        .await?;
+    }
+
+    reply(
+        ctx,
+        format!("{} will speak at **{}**.", member.mention(), event.title),
+    )
+    .await
+}
+
+/// Take someone off the speaker list.
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Member who won't speak"] member: User,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+
+    sqlx::query("DELETE FROM event_speakers WHERE event_id = $1 AND user_id = $2")
+        .bind(event.id)
+        .bind(member.id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "{} is no longer a speaker at **{}**.",
+            member.mention(),
+            event.title
+        ),
+    )
+    .await
+}
+
+/// See who's lined up to speak.
+#[poise::command(slash_command, guild_only)]
+async fn list(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let event = fetch_managed(ctx, id).await?;
+    let speakers = speakers(&ctx.data().pool, event.id).await?;
+
+    let content = if speakers.is_empty() {
+        format!("Only the host speaks at **{}**.", event.title)
+    } else {
+        format!(
+            "Speaking at **{}**: {}",
+            event.title,
+            speakers
+                .iter()
+                .map(|s| s.mention().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{rsvp, tags, Event};
+use crate::{
+    i18n,
+    notify::{self, NotificationKind, NotificationRun},
+    relay,
+    settings::GuildSettings,
+    Data, SlimeError,
+};
+
+/// How far back a member's RSVPs count towards what they like.
+const HISTORY_DAYS: i64 = 180;
+
+/// How many past events on a shared topic a member needs before they're suggested anything, so
+/// one RSVP doesn't make for a pattern.
+const MIN_MATCHES: usize = 2;
+
+/// The share of a member's past tagged events that have to share a topic with a new one.
+const MIN_AFFINITY: f64 = 1.0 / 3.0;
+
+/// Events are only suggested while they're new, so turning suggestions on doesn't suggest
+/// everything already on the calendar.
+const NEW_FOR_HOURS: i32 = 48;
+
+/// How well an event on `topics` fits a member who went to events on each of `history`: the
+/// share of those that had one of the same topics. Zero until at least [`MIN_MATCHES`] did.
+fn affinity(history: &[Vec<String>], topics: &[String]) -> f64 {
+    if history.is_empty() {
+        return 0.0;
+    }
+    let matches = history
+        .iter()
+        .filter(|past| past.iter().any(|t| topics.contains(t)))
+        .count();
+    if matches < MIN_MATCHES {
+        return 0.0;
+    }
+
+    matches as f64 / history.len() as f64
+}
+
+/// Members whose past RSVPs in the guild make `event` look like their kind of thing, best match
+/// first. Anyone who has already signed up, and the host, are left out.
+async fn interested_members(
+    pool: &PgPool,
+    event: &Event,
+    now: DateTime<Utc>,
+) -> Result<Vec<UserId>, SlimeError> {
+    let rows = sqlx::query_as::<_, (i64, Vec<String>)>(
+        "SELECT r.user_id, e.tags FROM event_rsvps r JOIN events e ON e.id = r.event_id
+         WHERE e.guild_id = $1 AND e.id <> $2 AND r.state = 'confirmed' AND r.user_id > 0
+            AND e.status IN ('published', 'completed') AND cardinality(e.tags) > 0
+            AND e.starts_at < $3 AND e.starts_at >= $3 - make_interval(days => $4)",
+    )
+    .bind(event.guild_id)
+    .bind(event.id)
+    .bind(now)
+    .bind(HISTORY_DAYS as i32)
+    .fetch_all(pool)
+    .await?;
+
+    let mut histories = HashMap::<i64, Vec<Vec<String>>>::new();
+    for (user, topics) in rows {
+        histories.entry(user).or_default().push(topics);
+    }
+    let signed_up = rsvp::signed_up(pool, event.id).await?;
+
+    let mut scored = histories
+        .into_iter()
+        .map(|(user, history)| (UserId::new(user as u64), affinity(&history, &event.tags)))
+        .filter(|(user, score)| {
+            *score >= MIN_AFFINITY && *user != event.host() && !signed_up.contains(user)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    Ok(scored.into_iter().map(|(user, _)| user).collect())
+}
+
+/// Of `members`, those who asked to be DMed suggestions.
+async fn opted_in(pool: &PgPool, members: &[UserId]) -> Result<Vec<UserId>, SlimeError> {
+    let ids = members.iter().map(|m| m.get() as i64).collect::<Vec<_>>();
+    let opted_in = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM user_preferences WHERE suggestions AND user_id = ANY($1)",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members
+        .iter()
+        .filter(|m| opted_in.contains(&(m.get() as i64)))
+        .copied()
+        .collect())
+}
+
+/// Posts `event` in the guild's suggestions channel, for members who follow it there.
+async fn post_suggestion(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    channel: ChannelId,
+    event: &Event,
+    link: &str,
+    interested: usize,
+) -> Result<(), SlimeError> {
+    let tags = event
+        .tags
+        .iter()
+        .map(|t| format!("`{t}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut content = format!(
+        "New event for anyone into {tags}: **{}**, {}.",
+        event.title,
+        i18n::timestamp(event.starts_at, FormattedTimestampStyle::LongDateTime)
+    );
+    if interested > 0 {
+        content.push_str(&format!(
+            " {interested} member(s) who went to events like it might be keen."
+        ));
+    }
+    content.push_str(&format!("\n{link}"));
+    relay::say(ctx, pool, event.guild(), channel, content).await?;
+
+    Ok(())
+}
+
+/// Tells members about new events with tags: by DM to those following one of its tags, and, in
+/// guilds with suggestions on, to those whose past RSVPs match who opted in, and in the guild's
+/// suggestions channel if it has one. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    // Claimed before sending, so each event is only ever suggested once.
+    let due = sqlx::query_as::<_, Event>(
+        "UPDATE events SET suggested_at = $1
+         WHERE status = 'published' AND suggested_at IS NULL AND cardinality(tags) > 0
+            AND starts_at > $1 AND created_at > $1 - make_interval(hours => $2)
+            AND (guild_id IN (SELECT guild_id FROM guild_settings WHERE event_suggestions)
+                OR EXISTS (SELECT 1 FROM tag_subscriptions s JOIN event_tags t ON t.tag = s.tag
+                    WHERE s.guild_id = events.guild_id AND t.event_id = events.id))
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .bind(NEW_FOR_HOURS)
+    .fetch_all(pool)
+    .await?;
+
+    for event in due {
+        let Some(message) = event.message_id else {
+            continue;
+        };
+        let link = MessageId::new(message as u64).link(event.channel(), Some(event.guild()));
+        let mut recipients = tags::subscribers(pool, &event).await?;
+
+        let settings = GuildSettings::load(pool, event.guild()).await?;
+        if settings.event_suggestions {
+            let interested = interested_members(pool, &event, now).await?;
+            if let Some(channel) = settings.suggestions_channel() {
+                data.calls.turn().await;
+                if let Err(e) =
+                    post_suggestion(ctx, pool, channel, &event, &link, interested.len()).await
+                {
+                    error!("Could not post suggestion for event {}: {}", event.id, e);
+                }
+            }
+            for member in opted_in(pool, &interested).await? {
+                if !recipients.contains(&member) {
+                    recipients.push(member);
+                }
+            }
+        }
+        if recipients.is_empty() {
+            continue;
+        }
+        let mut content =
+            notify::event_message(pool, NotificationKind::Suggestion, &event, None).await?;
+        content.push_str(&format!("\n{link}"));
+        notify::fan_out(
+            ctx,
+            pool,
+            &data.calls,
+            NotificationRun {
+                guild_id: event.guild(),
+                kind: NotificationKind::Suggestion,
+                subject_id: event.id,
+            },
+            recipients,
+            &content,
+            now,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn affinity_needs_a_pattern() {
+        let new = topics(&["hiking", "outdoors"]);
+        let hiker = vec![
+            topics(&["hiking"]),
+            topics(&["outdoors", "photography"]),
+            topics(&["board games"]),
+        ];
+        assert!((affinity(&hiker, &new) - 2.0 / 3.0).abs() < 1e-9);
+
+        // One matching event isn't enough to go on, however few others there are.
+        let once = vec![topics(&["hiking"])];
+        assert_eq!(affinity(&once, &new), 0.0);
+
+        let gamer = vec![
+            topics(&["board games"]),
+            topics(&["board games"]),
+            topics(&["strategy"]),
+            topics(&["hiking"]),
+            topics(&["hiking"]),
+            topics(&["board games"]),
+            topics(&["board games"]),
+        ];
+        assert!(affinity(&gamer, &new) < MIN_AFFINITY);
+        assert_eq!(affinity(&[], &new), 0.0);
+    }
+}
@@ -0,0 +1,729 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply, Modal};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{
+    fetch_managed, forum,
+    location::{self, Location, Place},
+    Event, EventModal, EventStatus,
+};
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    forms,
+    settings::GuildSettings,
+    util::{http_status, respond_ephemeral, send_dm},
+    ApplicationContext, Context, Data, SlimeError,
+};
+
+/// What happens when an event was edited both on the bot and on Discord since they last agreed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum SyncPolicy {
+    #[default]
+    #[name = "Keep the bot's version"]
+    BotWins,
+    #[name = "Keep Discord's version"]
+    DiscordWins,
+    #[name = "Keep whichever was edited last"]
+    LastWriter,
+    #[name = "Ask the host"]
+    Review,
+}
+
+/// The parts of an event its scheduled event also has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fields {
+    title: String,
+    description: String,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+}
+
+impl Fields {
+    fn of_event(event: &Event) -> Self {
+        Self {
+            title: event.title.clone(),
+            description: event.description.clone(),
+            starts_at: event.starts_at,
+            ends_at: event.ends_at(),
+        }
+    }
+
+    fn of_scheduled(scheduled: &ScheduledEvent, event: &Event) -> Option<Self> {
+        let starts_at = DateTime::from_timestamp(scheduled.start_time.unix_timestamp(), 0)?;
+        let ends_at = match scheduled.end_time {
+            Some(end) => DateTime::from_timestamp(end.unix_timestamp(), 0)?,
+            None => starts_at + Duration::minutes(event.duration_minutes.into()),
+        };
+        Some(Self {
+            title: scheduled.name.clone(),
+            description: scheduled.description.clone().unwrap_or_default(),
+            starts_at,
+            ends_at,
+        })
+    }
+
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}\u{1f}{}\u{1f}{}\u{1f}{}",
+            self.title,
+            self.description,
+            self.starts_at.timestamp(),
+            self.ends_at.timestamp()
+        )
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "**{}**, <t:{}:f> to <t:{}:t>",
+            self.title,
+            self.starts_at.timestamp(),
+            self.ends_at.timestamp()
+        )
+    }
+}
+
+/// Which side's version an event and its scheduled event should both end up with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    InSync,
+    /// Copy the bot's version onto Discord.
+    Push,
+    /// Copy Discord's version onto the bot.
+    Pull,
+    /// Leave both alone until the host picks one.
+    Review,
+}
+
+/// Works out which way to sync, and whether both sides had changed. Without anything synced yet,
+/// only Discord can have been edited, since the bot's copy is what the scheduled event was
+/// created from. For [`SyncPolicy::LastWriter`], Discord's edit is taken to have happened when it
+/// was `seen_at`, as Discord doesn't say when it was made.
+fn resolve(
+    synced: Option<&str>,
+    bot: &Fields,
+    discord: &Fields,
+    policy: SyncPolicy,
+    edited_at: Option<DateTime<Utc>>,
+    seen_at: DateTime<Utc>,
+) -> (Resolution, bool) {
+    if bot == discord {
+        return (Resolution::InSync, false);
+    }
+    let bot_changed = synced.is_some_and(|s| s != bot.fingerprint());
+    let discord_changed = synced.is_none_or(|s| s != discord.fingerprint());
+    match (bot_changed, discord_changed) {
+        (true, false) => (Resolution::Push, false),
+        (false, _) => (Resolution::Pull, false),
+        (true, true) => {
+            let resolution = match policy {
+                SyncPolicy::BotWins => Resolution::Push,
+                SyncPolicy::DiscordWins => Resolution::Pull,
+                SyncPolicy::LastWriter if edited_at.is_some_and(|at| at > seen_at) => {
+                    Resolution::Push
+                }
+                SyncPolicy::LastWriter => Resolution::Pull,
+                SyncPolicy::Review => Resolution::Review,
+            };
+            (resolution, true)
+        }
+    }
+}
+
+/// Records that the event and its scheduled event agree on its current fields.
+pub async fn mark_synced(pool: &PgPool, event: &Event) -> Result<(), SlimeError> {
+    sqlx::query("UPDATE events SET synced_fingerprint = $2, sync_conflict = false WHERE id = $1")
+        .bind(event.id)
+        .bind(Fields::of_event(event).fingerprint())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+async fn push(
+    ctx: &SerenityContext,
+    event: &Event,
+    scheduled: ScheduledEventId,
+) -> Result<(), SlimeError> {
+    event
+        .guild()
+        .edit_scheduled_event(
+            ctx,
+            scheduled,
+            EditScheduledEvent::new()
+                .name(&event.title)
+                .description(&event.description)
+                .start_time(event.starts_at)
+                .end_time(event.ends_at()),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn pull(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    event: &mut Event,
+    discord: Fields,
+) -> Result<(), SlimeError> {
+    event.title = discord.title;
+    event.description = discord.description;
+    event.starts_at = discord.starts_at;
+    event.duration_minutes = (discord.ends_at - discord.starts_at).num_minutes().max(1) as i32;
+    event.save(pool).await?;
+    event.refresh_post(ctx, pool).await?;
+    forum::sync(ctx, pool, event).await;
+    Ok(())
+}
+
+/// Brings the event and its scheduled event back in line after either changed, following the
+/// guild's [`SyncPolicy`] when both did.
+async fn reconcile(
+    ctx: &SerenityContext,
+    data: &Data,
+    mut event: Event,
+    scheduled: &ScheduledEvent,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let Some(discord) = Fields::of_scheduled(scheduled, &event) else {
+        return Ok(());
+    };
+    let (synced, edited_at, flagged) =
+        sqlx::query_as::<_, (Option<String>, Option<DateTime<Utc>>, bool)>(
+            "SELECT synced_fingerprint, edited_at, sync_conflict FROM events WHERE id = $1",
+        )
+        .bind(event.id)
+        .fetch_one(pool)
+        .await?;
+    let policy = GuildSettings::load(pool, event.guild()).await?.sync_policy;
+    let bot = Fields::of_event(&event);
+
+    let (resolution, conflict) = resolve(synced.as_deref(), &bot, &discord, policy, edited_at, now);
+    match resolution {
+        Resolution::InSync if synced.as_deref() == Some(&bot.fingerprint()) => return Ok(()),
+        Resolution::InSync => {}
+        Resolution::Push => push(ctx, &event, scheduled.id).await?,
+        Resolution::Pull => pull(ctx, pool, &mut event, discord.clone()).await?,
+        Resolution::Review if flagged => return Ok(()),
+        Resolution::Review => {
+            sqlx::query("UPDATE events SET sync_conflict = true WHERE id = $1")
+                .bind(event.id)
+                .execute(pool)
+                .await?;
+            ask_host(ctx, &event, &bot, &discord).await;
+        }
+    }
+    if resolution != Resolution::Review {
+        mark_synced(pool, &event).await?;
+    }
+
+    if conflict {
+        let outcome = match resolution {
+            Resolution::Push => "kept the bot's version",
+            Resolution::Pull => "kept Discord's version",
+            _ => "left it for the host",
+        };
+        let bot_user = ctx.cache.current_user().id;
+        audit::record(
+            pool,
+            AuditEntry {
+                guild_id: Some(event.guild()),
+                actor: bot_user,
+                action: "event_sync_conflict",
+                target: Some(event.id as u64),
+                details: format!("{:?}: {outcome}", policy),
+                undo: Vec::new(),
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn ask_host(ctx: &SerenityContext, event: &Event, bot: &Fields, discord: &Fields) {
+    if event.host_id < 0 {
+        return;
+    }
+    let message = format!(
+        "Event #{} was edited both here and in Discord's event list, and they don't match.\n\
+         Here: {}\nOn Discord: {}\n\
+         Pick which to keep with `/event resolve` in the server.",
+        event.id,
+        bot.summary(),
+        discord.summary()
+    );
+    if let Err(e) = send_dm(ctx, event.host(), CreateMessage::new().content(message)).await {
+        error!(
+            "Could not ask the host of event {} about a sync conflict: {}",
+            event.id, e
+        );
+    }
+}
+
+/// The published event mirrored into `scheduled`, if any.
+async fn mirrored(pool: &PgPool, scheduled: ScheduledEventId) -> Result<Option<Event>, SlimeError> {
+    Ok(sqlx::query_as::<_, Event>(
+        "SELECT * FROM events WHERE scheduled_event_id = $1 AND status = 'published'",
+    )
+    .bind(scheduled.get() as i64)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// A scheduled event was edited, maybe by the bot. Called from the event handler.
+pub async fn scheduled_updated(
+    ctx: &SerenityContext,
+    data: &Data,
+    scheduled: &ScheduledEvent,
+) -> Result<(), SlimeError> {
+    let Some(event) = mirrored(&data.pool, scheduled.id).await? else {
+        return Ok(());
+    };
+    reconcile(ctx, data, event, scheduled, data.clock.now()).await
+}
+
+/// The event's scheduled event as Discord has it now.
+async fn fetch_scheduled(
+    ctx: &SerenityContext,
+    event: &Event,
+) -> Result<Option<ScheduledEvent>, SlimeError> {
+    let Some(id) = event.scheduled_event_id else {
+        return Ok(None);
+    };
+    Ok(Some(
+        event
+            .guild()
+            .scheduled_event(ctx, ScheduledEventId::new(id as u64), false)
+            .await?,
+    ))
+}
+
+/// Compares every upcoming mirrored event with its scheduled event, to catch edits made while no
+/// instance was listening. Called by the scheduler alongside [`super::interest::sync_all`].
+pub async fn sync_all(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let events = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events
+         WHERE status = 'published' AND scheduled_event_id IS NOT NULL AND starts_at > $1
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now)
+    .fetch_all(&data.pool)
+    .await?;
+
+    for event in events {
+        let id = event.id;
+        data.calls.turn().await;
+        let synced = match fetch_scheduled(ctx, &event).await {
+            Ok(Some(scheduled)) => reconcile(ctx, data, event, &scheduled, now).await,
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = synced {
+            error!("Could not sync event {} with Discord: {}", id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Edit a posted event. Its Discord event is updated to match.
+#[poise::command(slash_command, guild_only)]
+pub async fn update(
+    ctx: ApplicationContext<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx.into(), id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+
+    let modal_id = ctx.interaction.id.to_string();
+    ctx.interaction
+        .create_response(
+            ctx,
+            EventModal::create(Some(EventModal::from_event(&event)), modal_id.clone()),
+        )
+        .await?;
+    ctx.has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+
+    let Some((submitted, edited)) =
+        forms::collect::<EventModal>(ctx.serenity_context(), ctx.author().id, modal_id).await?
+    else {
+        return Ok(());
+    };
+
+    // Places already handed out or waited for would need reshuffling.
+    let response = if edited.capacity != event.capacity {
+        CreateInteractionResponseMessage::new()
+            .content("A posted event's capacity can't be changed.")
+    } else {
+        edited.apply(&mut event);
+        let pool = &ctx.data().pool;
+        event.save(pool).await?;
+        sqlx::query("UPDATE events SET edited_at = $2 WHERE id = $1")
+            .bind(event.id)
+            .bind(ctx.data().clock.now())
+            .execute(pool)
+            .await?;
+        event.refresh_post(ctx.serenity_context(), pool).await?;
+        forum::sync(ctx.serenity_context(), pool, &event).await;
+        if let Some(scheduled) = fetch_scheduled(ctx.serenity_context(), &event).await? {
+            let now = ctx.data().clock.now();
+            reconcile(
+                ctx.serenity_context(),
+                ctx.data(),
+                event.clone(),
+                &scheduled,
+                now,
+            )
+            .await?;
+        }
+        CreateInteractionResponseMessage::new()
+            .content(format!("Updated event #{}.", event.id))
+            .embed(event.post_embed(ctx.serenity_context(), pool).await?)
+    };
+    submitted
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(response.ephemeral(true)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Which version of an event to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Version {
+    #[name = "The bot's"]
+    Bot,
+    #[name = "Discord's"]
+    Discord,
+}
+
+/// Settle an event edited both here and in Discord's event list.
+#[poise::command(slash_command, guild_only, rename = "resolve")]
+pub async fn resolve_command(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Which version to keep"] keep: Version,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if event.status != EventStatus::Published {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    let Some(scheduled) = fetch_scheduled(ctx.serenity_context(), &event).await? else {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("**{}** has no Discord event.", event.title))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let pool = &ctx.data().pool;
+    match keep {
+        Version::Bot => push(ctx.serenity_context(), &event, scheduled.id).await?,
+        Version::Discord => {
+            if let Some(discord) = Fields::of_scheduled(&scheduled, &event) {
+                pull(ctx.serenity_context(), pool, &mut event, discord).await?;
+            }
+        }
+    }
+    mark_synced(pool, &event).await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(event.guild()),
+            actor: ctx.author().id,
+            action: "event_sync_resolved",
+            target: Some(event.id as u64),
+            details: format!("kept {keep:?}"),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "**{}** and its Discord event match again.",
+                event.title
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Most events `/event sync-status` lists, so each drifted one can have a button.
+const STATUS_LIMIT: i64 = 20;
+
+/// Where an event's post and scheduled event disagree with its row, which is what the bot goes
+/// by. Each entry names the copy and what's off, like `post: time`.
+async fn drift(ctx: &SerenityContext, event: &Event) -> Result<Vec<String>, SlimeError> {
+    let mut drift = Vec::new();
+    let location = Location::of(event);
+    let starts = format!("<t:{}:", event.starts_at.timestamp());
+
+    match event.message_id {
+        None => drift.push("post: missing".to_string()),
+        Some(message) => match event
+            .channel()
+            .message(ctx, MessageId::new(message as u64))
+            .await
+        {
+            Err(e) if http_status(&e) == Some(404) => drift.push("post: missing".to_string()),
+            Err(e) => return Err(e.into()),
+            Ok(message) => {
+                let embed = message.embeds.first();
+                let values = embed
+                    .map(|e| {
+                        e.fields
+                            .iter()
+                            .map(|f| f.value.as_str())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                if embed.and_then(|e| e.title.as_deref()) != Some(event.title.as_str()) {
+                    drift.push("post: title".to_string());
+                }
+                if !values.iter().any(|v| v.contains(&starts)) {
+                    drift.push("post: time".to_string());
+                }
+                if let Some(rendered) = location.render() {
+                    if !values.contains(&rendered.as_str()) {
+                        drift.push("post: location".to_string());
+                    }
+                }
+            }
+        },
+    }
+
+    match fetch_scheduled(ctx, event).await {
+        Ok(None) => {}
+        Err(SlimeError::SerenityError(e)) if http_status(&e) == Some(404) => {
+            drift.push("Discord event: missing".to_string());
+        }
+        Err(e) => return Err(e),
+        Ok(Some(scheduled)) => {
+            let bot = Fields::of_event(event);
+            if let Some(discord) = Fields::of_scheduled(&scheduled, event) {
+                if discord.title != bot.title {
+                    drift.push("Discord event: title".to_string());
+                }
+                if discord.starts_at != bot.starts_at || discord.ends_at != bot.ends_at {
+                    drift.push("Discord event: time".to_string());
+                }
+            }
+            let link = match event.message_id {
+                Some(message) => {
+                    MessageId::new(message as u64).link(event.channel(), Some(event.guild()))
+                }
+                None => event.channel().mention().to_string(),
+            };
+            let located = match location.place(ctx, link).await {
+                Place::External(location) => {
+                    scheduled.metadata.and_then(|m| m.location) == Some(location)
+                }
+                Place::Channel(_, channel) => scheduled.channel_id == Some(channel),
+            };
+            if !located {
+                drift.push("Discord event: location".to_string());
+            }
+        }
+    }
+
+    Ok(drift)
+}
+
+/// Rewrites the event's post and scheduled event from its row.
+async fn force_sync(ctx: &SerenityContext, pool: &PgPool, event: &Event) -> Result<(), SlimeError> {
+    event.refresh_post(ctx, pool).await?;
+    forum::sync(ctx, pool, event).await;
+    if let Some(scheduled) = event.scheduled_event_id {
+        push(ctx, event, ScheduledEventId::new(scheduled as u64)).await?;
+        location::sync_scheduled(ctx, event).await?;
+        mark_synced(pool, event).await?;
+    }
+    Ok(())
+}
+
+/// Check whether upcoming events' posts and Discord events match what the bot has.
+#[poise::command(slash_command, guild_only, rename = "sync-status")]
+pub async fn sync_status(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let permissions = ctx.author_member().await.and_then(|m| m.permissions);
+    let events = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events
+         WHERE guild_id = $1 AND status = 'published' AND starts_at > $2
+         ORDER BY starts_at
+         LIMIT $3",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.data().clock.now())
+    .bind(STATUS_LIMIT)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    let events = events
+        .into_iter()
+        .filter(|e| e.is_managed_by(ctx.author().id, permissions))
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .content("You don't have any upcoming events to check.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+
+    let mut lines = Vec::new();
+    let mut buttons = Vec::new();
+    for event in &events {
+        let status = match drift(ctx.serenity_context(), event).await {
+            Ok(drift) if drift.is_empty() => "in sync".to_string(),
+            Ok(drift) => {
+                buttons.push(
+                    CreateButton::new(CustomId::new(Kind::EventSync, "force", event.id))
+                        .label(format!("Force sync #{}", event.id))
+                        .style(ButtonStyle::Secondary),
+                );
+                format!("out of sync on {}", drift.join(", "))
+            }
+            Err(e) => format!("couldn't check ({e})"),
+        };
+        lines.push(format!("#{} **{}**: {status}", event.id, event.title));
+    }
+
+    let rows = buttons
+        .chunks(5)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect::<Vec<_>>();
+    ctx.send(
+        CreateReply::default()
+            .content(lines.join("\n"))
+            .components(rows)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Handles the force sync buttons under `/event sync-status`.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let event_id = custom_id.id;
+
+    let permissions = interaction.member.as_ref().and_then(|m| m.permissions);
+    let event = Event::fetch(&data.pool, event_id).await?.filter(|e| {
+        e.status == EventStatus::Published && e.is_managed_by(interaction.user.id, permissions)
+    });
+    let Some(event) = event else {
+        return respond_ephemeral(ctx, interaction, "That event isn't yours to sync any more.")
+            .await;
+    };
+
+    let content = match force_sync(ctx, &data.pool, &event).await {
+        Ok(()) => format!("Synced **{}** from what the bot has.", event.title),
+        Err(e) => format!("Couldn't sync **{}**: {e}", event.title),
+    };
+    respond_ephemeral(ctx, interaction, &content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(title: &str, hour: u32) -> Fields {
+        let starts_at =
+            DateTime::from_timestamp(1_717_200_000 + i64::from(hour) * 3600, 0).unwrap();
+        Fields {
+            title: title.to_string(),
+            description: String::new(),
+            starts_at,
+            ends_at: starts_at + Duration::hours(1),
+        }
+    }
+
+    #[test]
+    fn only_the_side_that_changed_is_copied() {
+        let synced = fields("Game night", 19).fingerprint();
+        let moved = fields("Game night", 20);
+        let now = Utc::now();
+        let policy = SyncPolicy::Review;
+
+        assert_eq!(
+            resolve(
+                Some(&synced),
+                &moved,
+                &fields("Game night", 19),
+                policy,
+                None,
+                now
+            ),
+            (Resolution::Push, false)
+        );
+        assert_eq!(
+            resolve(
+                Some(&synced),
+                &fields("Game night", 19),
+                &moved,
+                policy,
+                None,
+                now
+            ),
+            (Resolution::Pull, false)
+        );
+        assert_eq!(
+            resolve(Some(&synced), &moved, &moved, policy, None, now),
+            (Resolution::InSync, false)
+        );
+        // Nothing synced yet means only Discord can have changed.
+        assert_eq!(
+            resolve(None, &fields("Game night", 19), &moved, policy, None, now),
+            (Resolution::Pull, false)
+        );
+    }
+
+    #[test]
+    fn conflicts_follow_the_policy() {
+        let synced = fields("Game night", 19).fingerprint();
+        let bot = fields("Board game night", 19);
+        let discord = fields("Game night", 21);
+        let now = Utc::now();
+        let resolve =
+            |policy, edited_at| resolve(Some(&synced), &bot, &discord, policy, edited_at, now).0;
+
+        assert_eq!(resolve(SyncPolicy::BotWins, None), Resolution::Push);
+        assert_eq!(resolve(SyncPolicy::DiscordWins, None), Resolution::Pull);
+        assert_eq!(resolve(SyncPolicy::Review, None), Resolution::Review);
+        assert_eq!(
+            resolve(SyncPolicy::LastWriter, Some(now + Duration::minutes(1))),
+            Resolution::Push
+        );
+        assert_eq!(
+            resolve(SyncPolicy::LastWriter, Some(now - Duration::minutes(1))),
+            Resolution::Pull
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use super::{cache, fetch_managed, forum, Event, EventStatus};
+use crate::{util::paginate, Context, SlimeError};
+
+/// Most tags an event can have.
+pub const MAX_TAGS: usize = 5;
+const MAX_TAG_LENGTH: usize = 32;
+
+/// Events listed on each page of `/event list`.
+const PAGE_SIZE: usize = 10;
+
+/// Reads tags given as a comma-separated list, like `board games, #irl`. They're lowercased so
+/// the same tag always matches, and anything past [`MAX_TAGS`] is dropped.
+pub fn parse(input: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for tag in input.split(',') {
+        let tag = normalize(tag);
+        if !tag.is_empty() && !tags.contains(&tag) && tags.len() < MAX_TAGS {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub fn normalize(tag: &str) -> String {
+    let tag = tag
+        .trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    tag.chars().take(MAX_TAG_LENGTH).collect()
+}
+
+/// Replaces the event's tags, both the copy its post renders from and the rows lookups by tag
+/// use.
+pub async fn replace(
+    pool: &PgPool,
+    event: &mut Event,
+    tags: Vec<String>,
+) -> Result<(), SlimeError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE events SET tags = $2 WHERE id = $1")
+        .bind(event.id)
+        .bind(&tags)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM event_tags WHERE event_id = $1")
+        .bind(event.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO event_tags (event_id, tag) SELECT $1, unnest($2::text[])")
+        .bind(event.id)
+        .bind(&tags)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    cache::written(event.id);
+
+    event.tags = tags;
+    Ok(())
+}
+
+/// Members of the event's guild following one of its tags, other than its host.
+pub async fn subscribers(pool: &PgPool, event: &Event) -> Result<Vec<UserId>, SlimeError> {
+    let ids = sqlx::query_scalar::<_, i64>(
+        "SELECT DISTINCT s.user_id FROM tag_subscriptions s
+         JOIN event_tags t ON t.tag = s.tag AND t.event_id = $2
+         WHERE s.guild_id = $1 AND s.user_id <> $3",
+    )
+    .bind(event.guild_id)
+    .bind(event.id)
+    .bind(event.host_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+fn list_line(event: &Event) -> String {
+    let mut line = format!(
+        "#{} **{}** <t:{}:f>",
+        event.id,
+        event.title,
+        event.starts_at.timestamp()
+    );
+    if !event.tags.is_empty() {
+        line.push_str(&format!(" `{}`", event.tags.join("` `")));
+    }
+    line
+}
+
+/// See upcoming events, optionally only those with a tag.
+#[poise::command(slash_command, guild_only, rename = "list")]
+pub async fn list_command(
+    ctx: Context<'_>,
+    #[description = "Only show events with this tag"]
+    #[max_length = 32]
+    tag: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let tag = tag.as_deref().map(normalize).filter(|t| !t.is_empty());
+
+    let events = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events e
+         WHERE guild_id = $1 AND status = $2 AND starts_at + make_interval(mins => duration_minutes) > $3
+            AND ($4::text IS NULL OR EXISTS (
+                SELECT 1 FROM event_tags t WHERE t.event_id = e.id AND t.tag = $4))
+         ORDER BY starts_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(EventStatus::Published)
+    .bind(ctx.data().clock.now())
+    .bind(&tag)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if events.is_empty() {
+        let content = match &tag {
+            Some(tag) => format!("There are no upcoming events tagged `{tag}`."),
+            None => "There are no upcoming events.".to_string(),
+        };
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let pages = events
+        .chunks(PAGE_SIZE)
+        .map(|page| page.iter().map(list_line).collect::<Vec<_>>().join("\n"))
+        .collect::<Vec<_>>();
+    let title = match &tag {
+        Some(tag) => format!("Upcoming events tagged `{tag}`"),
+        None => "Upcoming events".to_string(),
+    };
+    paginate(ctx, &title, &pages).await
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Tag events, and follow the tags you like.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "tags",
+    subcommands("set", "follow", "unfollow", "stats")
+)]
+pub async fn tags_command(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Change an event's tags. Leave them out to clear them.
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Tags, separated by commas, like `game, irl`"]
+    #[max_length = 200]
+    tags: Option<String>,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    let pool = &ctx.data().pool;
+    let tags = tags.as_deref().map(parse).unwrap_or_default();
+    replace(pool, &mut event, tags).await?;
+    if event.status == EventStatus::Published {
+        event.refresh_post(ctx.serenity_context(), pool).await?;
+        forum::sync(ctx.serenity_context(), pool, &event).await;
+    }
+
+    let content = if event.tags.is_empty() {
+        format!("**{}** no longer has any tags.", event.title)
+    } else {
+        format!(
+            "**{}** is tagged `{}`.",
+            event.title,
+            event.tags.join("`, `")
+        )
+    };
+    reply(ctx, content).await
+}
+
+/// Get a DM whenever a new event is posted with a tag.
+#[poise::command(slash_command, guild_only)]
+async fn follow(
+    ctx: Context<'_>,
+    #[description = "Tag to follow"]
+    #[max_length = 32]
+    tag: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let tag = normalize(&tag);
+    if tag.is_empty() {
+        return reply(ctx, "That isn't a tag.").await;
+    }
+
+    sqlx::query(
+        "INSERT INTO tag_subscriptions (guild_id, user_id, tag) VALUES ($1, $2, $3)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&tag)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!("You'll get a DM when a new event is tagged `{tag}`."),
+    )
+    .await
+}
+
+/// Stop following a tag.
+#[poise::command(slash_command, guild_only)]
+async fn unfollow(
+    ctx: Context<'_>,
+    #[description = "Tag to stop following"]
+    #[max_length = 32]
+    tag: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let tag = normalize(&tag);
+
+    let removed = sqlx::query(
+        "DELETE FROM tag_subscriptions WHERE guild_id = $1 AND user_id = $2 AND tag = $3",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&tag)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if removed == 0 {
+        format!("You weren't following `{tag}`.")
+    } else {
+        format!("You've stopped following `{tag}`.")
+    };
+    reply(ctx, content).await
+}
+
+/// See which tags this server's events use most.
+#[poise::command(slash_command, guild_only)]
+async fn stats(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    // Events, and places taken at them, per tag, alongside how many members follow it.
+    let rows = sqlx::query_as::<_, (String, i64, i64, i64)>(
+        "SELECT t.tag, COUNT(*), COALESCE(SUM(e.confirmed_count), 0),
+            (SELECT COUNT(*) FROM tag_subscriptions s WHERE s.guild_id = $1 AND s.tag = t.tag)
+         FROM event_tags t JOIN events e ON e.id = t.event_id
+         WHERE e.guild_id = $1 AND e.status IN ('published', 'completed')
+         GROUP BY t.tag
+         ORDER BY COUNT(*) DESC, t.tag",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if rows.is_empty() {
+        return reply(ctx, "No events here have been tagged yet.").await;
+    }
+    let pages = rows
+        .chunks(PAGE_SIZE)
+        .map(|page| {
+            page.iter()
+                .map(|(tag, events, going, followers)| {
+                    format!("`{tag}`: {events} event(s), {going} going, {followers} follower(s)")
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>();
+    paginate(ctx, "Event tags", &pages).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_tidied() {
+        assert_eq!(
+            parse(" Board  Games, #irl,board games,, co-op"),
+            vec!["board games", "irl", "co-op"]
+        );
+        assert_eq!(parse("a, b, c, d, e, f").len(), MAX_TAGS);
+        assert_eq!(normalize(&"x".repeat(40)).len(), MAX_TAG_LENGTH);
+    }
+}
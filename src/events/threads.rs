@@ -0,0 +1,392 @@
+use std::collections::HashSet;
+
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use super::Event;
+use crate::{discord::Discord, relay, settings::GuildSettings, Context, Data, SlimeError};
+
+/// Discord caps messages at 2000 characters.
+const MAX_LENGTH: usize = 2000;
+
+/// How much of a pinned message a summary quotes.
+const EXCERPT_CHARS: usize = 120;
+
+/// What went on in an event's thread. Anything taken from what members wrote is only collected
+/// with the guild's consent to read message content; the counts are always there.
+#[derive(Debug, Default)]
+struct ThreadStats {
+    messages: usize,
+    participants: HashSet<UserId>,
+    pinned: usize,
+    attachments: usize,
+    /// Excerpts of pinned messages, with links to them.
+    decisions: Vec<(String, String)>,
+    /// File names and links.
+    files: Vec<(String, String)>,
+}
+
+impl ThreadStats {
+    fn tally(&mut self, message: &Message, consent: bool) {
+        if message.author.bot {
+            return;
+        }
+        self.messages += 1;
+        self.participants.insert(message.author.id);
+        self.attachments += message.attachments.len();
+        if message.pinned {
+            self.pinned += 1;
+        }
+        if !consent {
+            return;
+        }
+
+        if message.pinned {
+            let excerpt = match message.content.char_indices().nth(EXCERPT_CHARS) {
+                Some((end, _)) => format!("{}…", &message.content[..end]),
+                None => message.content.clone(),
+            };
+            self.decisions.push((excerpt, message.link()));
+        }
+        for attachment in &message.attachments {
+            self.files
+                .push((attachment.filename.clone(), attachment.url.clone()));
+        }
+    }
+}
+
+/// Shows `visit` every message in `thread`, newest first, a page at a time.
+pub(super) async fn read(
+    discord: &impl Discord,
+    thread: ChannelId,
+    mut visit: impl FnMut(&Message),
+) -> Result<(), SlimeError> {
+    let mut before = None;
+    loop {
+        let page = discord.messages(thread, before, 100).await?;
+        page.iter().for_each(&mut visit);
+        match page.last() {
+            Some(oldest) if page.len() == 100 => before = Some(oldest.id),
+            _ => return Ok(()),
+        }
+    }
+}
+
+async fn gather(
+    discord: &impl Discord,
+    thread: ChannelId,
+    consent: bool,
+) -> Result<ThreadStats, SlimeError> {
+    let mut stats = ThreadStats::default();
+    read(discord, thread, |message| stats.tally(message, consent)).await?;
+    // Read newest first, so everything was collected backwards.
+    stats.decisions.reverse();
+    stats.files.reverse();
+
+    Ok(stats)
+}
+
+/// The catch-up posted for members who missed the event.
+fn compose(title: &str, thread: ChannelId, stats: &ThreadStats) -> String {
+    let mut summary = format!(
+        "**Catching up on {title}**\n{} message(s) from {} member(s) in {}.",
+        stats.messages,
+        stats.participants.len(),
+        thread.mention()
+    );
+    // Without consent there's nothing to list, but the thread is still worth pointing at.
+    if stats.decisions.is_empty() && stats.files.is_empty() && stats.pinned + stats.attachments > 0
+    {
+        summary.push_str(&format!(
+            " It has {} pinned message(s) and {} attachment(s).",
+            stats.pinned, stats.attachments
+        ));
+    }
+    for (heading, lines) in [("Pinned", &stats.decisions), ("Attachments", &stats.files)] {
+        if lines.is_empty() {
+            continue;
+        }
+        summary.push_str(&format!("\n\n**{heading}**"));
+        for (text, link) in lines {
+            summary.push_str(&format!("\n- [{text}]({link})"));
+        }
+    }
+
+    match summary.char_indices().nth(MAX_LENGTH - 1) {
+        Some((end, _)) => format!("{}…", &summary[..end]),
+        None => summary,
+    }
+}
+
+/// Opens a discussion thread on the event's post if the guild wants them. A thread that
+/// couldn't be opened is only logged, since the event is up either way.
+pub async fn open(ctx: &SerenityContext, pool: &PgPool, event: &mut Event, message: &Message) {
+    let opened = async {
+        if !GuildSettings::load(pool, event.guild())
+            .await?
+            .event_threads
+        {
+            return Ok(());
+        }
+        let thread = event
+            .channel()
+            .create_thread_from_message(
+                ctx,
+                message.id,
+                CreateThread::new(event.title.chars().take(100).collect::<String>())
+                    .auto_archive_duration(AutoArchiveDuration::OneDay),
+            )
+            .await?;
+        sqlx::query("UPDATE events SET thread_id = $2 WHERE id = $1")
+            .bind(event.id)
+            .bind(thread.id.get() as i64)
+            .execute(pool)
+            .await?;
+        event.thread_id = Some(thread.id.get() as i64);
+        Ok::<_, SlimeError>(())
+    };
+    if let Err(e) = opened.await {
+        error!("Could not open thread for event {}: {}", event.id, e);
+    }
+}
+
+/// Posts a summary of an event's thread to the event's channel once the thread archives, if the
+/// guild wants them. Only the first archive is summarised.
+pub async fn archived(
+    ctx: &SerenityContext,
+    data: &Data,
+    thread: &GuildChannel,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let settings = GuildSettings::load(pool, thread.guild_id).await?;
+    if !settings.thread_summaries {
+        return Ok(());
+    }
+    let Some(event) = sqlx::query_as::<_, Event>(
+        "UPDATE events SET thread_summarized_at = now()
+         WHERE thread_id = $1 AND thread_summarized_at IS NULL
+         RETURNING *",
+    )
+    .bind(thread.id.get() as i64)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let stats = gather(ctx, thread.id, settings.message_content_consent).await?;
+    if stats.messages == 0 {
+        return Ok(());
+    }
+    info!("Summarising thread of event {}: {:?}", event.id, stats);
+    let summary = compose(&event.title, thread.id, &stats);
+    relay::say(ctx, pool, event.guild(), event.channel(), summary).await?;
+
+    Ok(())
+}
+
+/// The web links written in `content`, without the brackets or punctuation around them.
+fn urls(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split_whitespace()
+        .filter_map(|word| {
+            let start = word.find("https://").or_else(|| word.find("http://"))?;
+            let url = word[start..].trim_end_matches(['>', ')', ']', '.', ',', '!', '?', '*', '_']);
+            url.contains("://").then_some(url)
+        })
+        .filter(|url| url.len() > "https://".len())
+}
+
+/// Everything linked or attached in a thread, grouped by who posted it first.
+#[derive(Debug, Default)]
+struct Links {
+    /// Posters in the order they first shared something, with what they shared in order.
+    by_poster: Vec<(UserId, String, Vec<String>)>,
+    seen: HashSet<String>,
+}
+
+impl Links {
+    /// Adds a message's links and attachments, which must come oldest first.
+    fn add(&mut self, message: &Message) {
+        if message.author.bot {
+            return;
+        }
+        let shared = urls(&message.content)
+            .map(|url| (url.to_string(), url.to_string()))
+            .chain(
+                message
+                    .attachments
+                    .iter()
+                    .map(|a| (a.url.clone(), format!("[{}]({})", a.filename, a.url))),
+            )
+            .filter(|(url, _)| self.seen.insert(url.clone()))
+            .map(|(_, line)| line)
+            .collect::<Vec<_>>();
+        if shared.is_empty() {
+            return;
+        }
+
+        let poster = &message.author;
+        match self.by_poster.iter_mut().find(|(id, ..)| *id == poster.id) {
+            Some((.., lines)) => lines.extend(shared),
+            None => self
+                .by_poster
+                .push((poster.id, poster.name.clone(), shared)),
+        }
+    }
+
+    fn embed(&self, title: &str) -> CreateEmbed {
+        // Discord allows 25 fields of up to 1024 characters each.
+        let mut embed = CreateEmbed::new().title(format!("Shared in {title}"));
+        for (_, poster, lines) in self.by_poster.iter().take(25) {
+            let mut value = String::new();
+            for (i, line) in lines.iter().enumerate() {
+                let more = format!("…and {} more", lines.len() - i);
+                if value.len() + line.len() + more.len() + 2 > 1024 {
+                    value.push_str(&more);
+                    break;
+                }
+                value.push_str(&format!("{line}\n"));
+            }
+            embed = embed.field(poster, value, false);
+        }
+        embed
+    }
+}
+
+/// Collect every link and attachment posted in an event's thread.
+#[poise::command(slash_command, guild_only, rename = "links")]
+pub async fn links_command(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let event = Event::fetch(pool, id)
+        .await?
+        .filter(|e| e.guild() == guild_id)
+        .ok_or(SlimeError::EventNotFound(id))?;
+
+    let reply = |content: String| CreateReply::default().content(content).ephemeral(true);
+    let Some(thread) = event.thread_id.map(|id| ChannelId::new(id as u64)) else {
+        ctx.send(reply(format!("**{}** doesn't have a thread.", event.title)))
+            .await?;
+        return Ok(());
+    };
+    // Links and attachments are part of what members wrote.
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        ctx.send(reply(
+            "Collecting links needs the bot to read messages, which an admin can allow with \
+             `/settings message_content`."
+                .to_string(),
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.defer().await?;
+    let mut messages = Vec::new();
+    read(ctx.serenity_context(), thread, |m| messages.push(m.clone())).await?;
+    let mut links = Links::default();
+    messages.iter().rev().for_each(|m| links.add(m));
+
+    let response = if links.by_poster.is_empty() {
+        CreateReply::default().content(format!(
+            "Nothing has been linked or attached in {} yet.",
+            thread.mention()
+        ))
+    } else {
+        CreateReply::default().embed(links.embed(&event.title))
+    };
+    ctx.send(response).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::discord::mock::MockDiscord;
+
+    #[tokio::test]
+    async fn every_page_of_the_thread_is_counted() {
+        let discord = MockDiscord::new();
+        let thread = ChannelId::new(1);
+        let start = Utc::now() - Duration::hours(3);
+        for i in 0..250 {
+            discord.post(thread, start + Duration::seconds(i));
+        }
+
+        let stats = gather(&discord, thread, true).await.unwrap();
+        assert_eq!(stats.messages, 250);
+    }
+
+    #[test]
+    fn content_is_only_quoted_with_consent() {
+        let mut pinned = Message::default();
+        pinned.pinned = true;
+        pinned.content = "We're meeting at the north gate.".to_string();
+        pinned.author.id = UserId::new(7);
+
+        let mut without = ThreadStats::default();
+        without.tally(&pinned, false);
+        let summary = compose("Raid", ChannelId::new(1), &without);
+        assert!(summary.contains("1 message(s) from 1 member(s)"));
+        assert!(summary.contains("1 pinned message(s)"));
+        assert!(!summary.contains("north gate"));
+
+        let mut with = ThreadStats::default();
+        with.tally(&pinned, true);
+        let summary = compose("Raid", ChannelId::new(1), &with);
+        assert!(summary.contains("**Pinned**\n- [We're meeting at the north gate.]("));
+    }
+
+    #[test]
+    fn links_are_found_in_what_members_wrote() {
+        let found = urls("guide: <https://example.com/build>, and (http://vods.example/1).")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec!["https://example.com/build", "http://vods.example/1"]
+        );
+        assert_eq!(urls("https:// is not a link").count(), 0);
+    }
+
+    #[test]
+    fn links_are_grouped_by_whoever_shared_them_first() {
+        let message = |id: u64, content: &str| {
+            let mut message = Message::default();
+            message.author.id = UserId::new(id);
+            message.author.name = format!("member{id}");
+            message.content = content.to_string();
+            message
+        };
+        let mut links = Links::default();
+        links.add(&message(1, "https://a.example"));
+        links.add(&message(
+            2,
+            "same one https://a.example and https://b.example",
+        ));
+        links.add(&message(1, "https://c.example"));
+
+        let grouped = links
+            .by_poster
+            .iter()
+            .map(|(_, name, lines)| (name.as_str(), lines.join(" ")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            grouped,
+            vec![
+                ("member1", "https://a.example https://c.example".to_string()),
+                ("member2", "https://b.example".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{cache, fetch_managed, rsvp, Event, EventStatus, NewEvent};
+use crate::{
+    audit::{self, AuditEntry},
+    notify::{self, NotificationKind, NotificationRun},
+    undo::UndoStep,
+    util::send_dm,
+    Context, Data, SlimeError,
+};
+
+impl Event {
+    /// When enough people need to be going for the event to go ahead, if it has a minimum.
+    pub fn confirm_by(&self) -> Option<DateTime<Utc>> {
+        self.min_attendees
+            .map(|_| self.starts_at - Duration::hours(self.confirm_hours.into()))
+    }
+
+    /// Whether the event still needs more people going to go ahead.
+    pub fn short_of_minimum(&self) -> bool {
+        self.min_attendees
+            .is_some_and(|minimum| self.confirmed_count < minimum)
+    }
+}
+
+async fn save(pool: &PgPool, event: &Event) -> Result<(), SlimeError> {
+    sqlx::query(
+        "UPDATE events SET
+            min_attendees = $2, confirm_hours = $3, retry_days = $4, threshold_checked_at = NULL
+         WHERE id = $1",
+    )
+    .bind(event.id)
+    .bind(event.min_attendees)
+    .bind(event.confirm_hours)
+    .bind(event.retry_days)
+    .execute(pool)
+    .await?;
+    cache::written(event.id);
+
+    Ok(())
+}
+
+/// Drafts the event again `days` later, with the same location and minimum, for its host to
+/// publish if they want another try.
+async fn propose_retry(pool: &PgPool, event: &Event, days: i32) -> Result<Event, SlimeError> {
+    let retry = Event::insert(
+        pool,
+        NewEvent {
+            guild_id: event.guild(),
+            channel_id: event.channel(),
+            host_id: event.host(),
+            title: event.title.clone(),
+            description: event.description.clone(),
+            starts_at: event.starts_at + Duration::days(days.into()),
+            duration_minutes: event.duration_minutes,
+            capacity: event.capacity,
+            tags: event.tags.clone(),
+        },
+        EventStatus::Draft,
+    )
+    .await?;
+    sqlx::query(
+        "UPDATE events e SET
+            venue = o.venue, address = o.address, location_channel_id = o.location_channel_id,
+            outdoor = o.outdoor, min_attendees = o.min_attendees, confirm_hours = o.confirm_hours,
+            retry_days = o.retry_days
+         FROM events o
+         WHERE e.id = $1 AND o.id = $2",
+    )
+    .bind(retry.id)
+    .bind(event.id)
+    .execute(pool)
+    .await?;
+
+    Ok(retry)
+}
+
+/// Calls off an event that didn't get enough people going in time, tells everyone signed up,
+/// and offers its host another date if the event asks for one.
+async fn fall_through(
+    ctx: &SerenityContext,
+    data: &Data,
+    mut event: Event,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let minimum = event.min_attendees.unwrap_or_default();
+    let signed_up = rsvp::signed_up(pool, event.id).await?;
+    data.calls.turn().await;
+    event.withdraw(ctx, pool).await?;
+    let bot = ctx.cache.current_user().id;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(event.guild()),
+            actor: bot,
+            action: "event_fall_through",
+            target: Some(event.id as u64),
+            details: format!(
+                "{}: {} of {minimum} going",
+                event.title, event.confirmed_count
+            ),
+            undo: vec![UndoStep::RepublishEvent(event.id)],
+        },
+    )
+    .await?;
+
+    let retry = match event.retry_days {
+        Some(days) => Some(propose_retry(pool, &event, days).await?),
+        None => None,
+    };
+
+    let mut content =
+        notify::event_message(pool, NotificationKind::Cancellation, &event, None).await?;
+    content.push_str(&format!(
+        "\nIt needed {minimum} people going, and {} were.",
+        event.confirmed_count
+    ));
+    notify::fan_out(
+        ctx,
+        pool,
+        &data.calls,
+        NotificationRun {
+            guild_id: event.guild(),
+            kind: NotificationKind::Cancellation,
+            subject_id: event.id,
+        },
+        signed_up,
+        &content,
+        now,
+    )
+    .await?;
+
+    if event.host_id < 0 {
+        return Ok(());
+    }
+    let mut message = format!(
+        "**{}** only had {} of the {minimum} people it needed going, so it's been cancelled. \
+         Use `/undo` in the server to put it back up anyway.",
+        event.title, event.confirmed_count
+    );
+    if let Some(retry) = &retry {
+        message.push_str(&format!(
+            "\nI've drafted it again for <t:{}:f> (#{}). Publish it with `/event publish` to try \
+             again.",
+            retry.starts_at.timestamp(),
+            retry.id
+        ));
+    }
+    data.calls.turn().await;
+    if let Err(e) = send_dm(ctx, event.host(), CreateMessage::new().content(message)).await {
+        error!(
+            "Could not tell the host of event {} it fell through: {}",
+            event.id, e
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks events with a minimum once their deadline passes, cancelling those short of it.
+/// Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    // Claimed before cancelling, so a slow run can't be picked up again by the next tick.
+    let due = sqlx::query_as::<_, Event>(
+        "UPDATE events SET threshold_checked_at = $1
+         WHERE status = 'published' AND min_attendees IS NOT NULL
+            AND threshold_checked_at IS NULL
+            AND starts_at - make_interval(hours => confirm_hours) <= $1
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for event in due {
+        if !event.short_of_minimum() {
+            continue;
+        }
+        let id = event.id;
+        if let Err(e) = fall_through(ctx, data, event, now).await {
+            error!("Could not call off event {} that fell through: {}", id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Only go ahead if enough people are going in time. Leave out the minimum to remove it.
+#[poise::command(slash_command, guild_only, rename = "threshold")]
+pub async fn threshold_command(
+    ctx: Context<'_>,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "How many people need to be going"]
+    #[min = 2]
+    minimum: Option<u32>,
+    #[description = "How many hours before the start they need to be going by (default 24)"]
+    #[min = 1]
+    #[max = 336]
+    hours_before: Option<u32>,
+    #[description = "If it falls through, draft it again this many days later"]
+    #[min = 1]
+    #[max = 90]
+    retry_days: Option<u32>,
+) -> Result<(), SlimeError> {
+    let mut event = fetch_managed(ctx, id).await?;
+    if !matches!(event.status, EventStatus::Draft | EventStatus::Published) {
+        return Err(SlimeError::EventNotFound(id));
+    }
+    event.min_attendees = minimum.map(|m| m as i32);
+    event.confirm_hours = hours_before.unwrap_or(24) as i32;
+    event.retry_days = retry_days.filter(|_| minimum.is_some()).map(|d| d as i32);
+
+    let content = match event.confirm_by() {
+        Some(deadline) if deadline <= ctx.data().clock.now() => {
+            ctx.send(
+                CreateReply::default()
+                    .content(format!(
+                        "That deadline, <t:{}:f>, has already passed.",
+                        deadline.timestamp()
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+        Some(deadline) => {
+            let mut content = format!(
+                "**{}** will be cancelled unless {} people are going by <t:{}:f>.",
+                event.title,
+                event.min_attendees.unwrap_or_default(),
+                deadline.timestamp()
+            );
+            if let Some(days) = event.retry_days {
+                content.push_str(&format!(
+                    " If it is, I'll draft it again {days} day(s) later."
+                ));
+            }
+            content
+        }
+        None => format!("**{}** goes ahead however many are going.", event.title),
+    };
+
+    save(&ctx.data().pool, &event).await?;
+    if event.status == EventStatus::Published {
+        event
+            .refresh_post(ctx.serenity_context(), &ctx.data().pool)
+            .await?;
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
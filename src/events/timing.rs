@@ -0,0 +1,222 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Timelike, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+
+use super::tags;
+use crate::{
+    announce::parse_utc_offset,
+    stats::{self, Heat, WEEKDAYS},
+    Context, SlimeError,
+};
+
+/// How many past events a time needs before its own average counts for more than the tag's.
+const PRIOR_EVENTS: f64 = 2.0;
+
+/// How far back server activity is counted.
+const ACTIVITY_WEEKS: i64 = 4;
+
+const SUGGESTIONS: usize = 3;
+
+/// A weekday and hour worth hosting at, and why.
+#[derive(Debug, Clone, PartialEq)]
+struct Suggestion {
+    day: usize,
+    hour: usize,
+    /// Past events with the tag that started then, and how many turned up to them.
+    events: u32,
+    attended: i64,
+    /// Messages posted at that hour across the activity window.
+    messages: i64,
+    /// Expected turnout, weighted by how active the server is then.
+    score: f64,
+}
+
+/// The best times to host, from past events as (weekday, hour, attended) and the server's
+/// activity. A time's turnout is its own average pulled towards the tag's overall one, so one
+/// lucky event doesn't decide it, then scaled between half and all of that by how busy the server
+/// is at that hour compared to its busiest. Times within an hour of a better one on the same day
+/// are left out, so the suggestions are actually different.
+fn rank(history: &[(usize, usize, i64)], activity: &Heat) -> Vec<Suggestion> {
+    let overall = if history.is_empty() {
+        0.0
+    } else {
+        history.iter().map(|h| h.2).sum::<i64>() as f64 / history.len() as f64
+    };
+    let busiest = activity.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut times = Vec::new();
+    for (day, hours) in activity.iter().enumerate() {
+        for (hour, &messages) in hours.iter().enumerate() {
+            let past = history
+                .iter()
+                .filter(|h| h.0 == day && h.1 == hour)
+                .collect::<Vec<_>>();
+            let attended = past.iter().map(|h| h.2).sum::<i64>();
+            let turnout =
+                (attended as f64 + PRIOR_EVENTS * overall) / (past.len() as f64 + PRIOR_EVENTS);
+            let share = if busiest > 0 {
+                messages as f64 / busiest as f64
+            } else {
+                0.0
+            };
+            let score = if overall > 0.0 {
+                turnout * (0.5 + 0.5 * share)
+            } else {
+                share
+            };
+            // A time nothing is known about isn't worth suggesting.
+            if score > 0.0 && (!past.is_empty() || messages > 0) {
+                times.push(Suggestion {
+                    day,
+                    hour,
+                    events: past.len() as u32,
+                    attended,
+                    messages,
+                    score,
+                });
+            }
+        }
+    }
+    times.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut picked = Vec::<Suggestion>::new();
+    for time in times {
+        let near = picked
+            .iter()
+            .any(|p| p.day == time.day && p.hour.abs_diff(time.hour) <= 1);
+        if !near {
+            picked.push(time);
+        }
+        if picked.len() == SUGGESTIONS {
+            break;
+        }
+    }
+    picked
+}
+
+/// The data behind a suggestion, in words.
+fn explain(suggestion: &Suggestion) -> String {
+    let history = match suggestion.events {
+        0 => "No events with this tag have started then yet".to_string(),
+        events => format!(
+            "{events} past event(s) started then, averaging {:.1} attendee(s)",
+            suggestion.attended as f64 / f64::from(events)
+        ),
+    };
+    format!(
+        "{history}. About {} message(s) a week are posted at that hour.",
+        suggestion.messages / ACTIVITY_WEEKS
+    )
+}
+
+/// Find the times an event with a tag is likely to draw the most people.
+#[poise::command(slash_command, guild_only, rename = "suggest-time")]
+pub async fn suggest_time(
+    ctx: Context<'_>,
+    #[description = "Kind of event, as tagged"]
+    #[max_length = 32]
+    tag: String,
+    #[description = "Timezone to suggest times in, as an offset like `+02:00` or `UTC-5` (default UTC)"]
+    timezone: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let tag = tags::normalize(&tag);
+    let offset = match &timezone {
+        Some(timezone) => parse_utc_offset(timezone)
+            .ok_or_else(|| SlimeError::InvalidTimezone(timezone.clone()))?,
+        None => FixedOffset::east_opt(0).unwrap(),
+    };
+
+    let past = sqlx::query_as::<_, (DateTime<Utc>, i64)>(
+        "SELECT e.starts_at, COUNT(a.user_id)
+         FROM events e
+         JOIN event_tags t ON t.event_id = e.id AND t.tag = $2
+         LEFT JOIN event_attendance a ON a.event_id = e.id
+         WHERE e.guild_id = $1 AND e.status = 'completed'
+         GROUP BY e.id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&tag)
+    .fetch_all(pool)
+    .await?;
+    let history = past
+        .iter()
+        .map(|(starts_at, attended)| {
+            let local = starts_at.with_timezone(&offset);
+            (
+                local.weekday().num_days_from_monday() as usize,
+                local.hour() as usize,
+                *attended,
+            )
+        })
+        .collect::<Vec<_>>();
+    let counts = sqlx::query_as::<_, (DateTime<Utc>, i32)>(
+        "SELECT hour, messages FROM channel_activity WHERE guild_id = $1 AND hour >= $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.data().clock.now() - Duration::weeks(ACTIVITY_WEEKS))
+    .fetch_all(pool)
+    .await?;
+    let activity = stats::bucket(&counts, offset);
+
+    let suggestions = rank(&history, &activity);
+    if suggestions.is_empty() {
+        let content = format!(
+            "There isn't enough to go on yet: no completed events tagged `{tag}` and no counted \
+             server activity. Activity is counted once message content features are allowed \
+             with `/settings message_content`."
+        );
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("Best times for `{tag}` events"))
+        .description(format!(
+            "From {} completed event(s) with the tag and the last {ACTIVITY_WEEKS} weeks of \
+             server activity, in UTC{offset}.",
+            past.len()
+        ));
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        embed = embed.field(
+            format!(
+                "{}. {} {:02}:00",
+                i + 1,
+                WEEKDAYS[suggestion.day],
+                suggestion.hour
+            ),
+            explain(suggestion),
+            false,
+        );
+    }
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestions_favour_turnout_and_activity_and_spread_out() {
+        let mut activity = [[0; 24]; 7];
+        activity[4][20] = 100;
+        activity[4][19] = 90;
+        activity[5][14] = 40;
+        activity[2][12] = 10;
+        // Friday 19:00 drew crowds twice; Saturday 14:00 once, with fewer.
+        let history = [(4, 19, 12), (4, 19, 10), (5, 14, 4)];
+
+        let picks = rank(&history, &activity);
+        assert_eq!(picks.len(), SUGGESTIONS);
+        assert_eq!((picks[0].day, picks[0].hour), (4, 19));
+        assert_eq!(picks[0].events, 2);
+        // Friday 20:00 is busier, but right next to the top pick.
+        assert!(!picks.iter().any(|p| (p.day, p.hour) == (4, 20)));
+
+        assert!(rank(&[], &[[0; 24]; 7]).is_empty());
+    }
+}
@@ -0,0 +1,494 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply, Modal};
+use sqlx::PgPool;
+
+use super::{
+    parse_positive, parse_start_time, submit_or_publish, tags, Event, EventStatus, NewEvent,
+};
+use crate::{
+    custom_id::{CustomId, Kind},
+    forms::{self, Form},
+    quotas,
+    settings::GuildSettings,
+    ApplicationContext, SlimeError,
+};
+
+/// How long the wizard waits for the host to press on to the next step.
+const STEP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// What an event is and what it's about: the wizard's first step.
+#[derive(Debug, Clone, Default, poise::Modal)]
+#[name = "New event: the basics"]
+struct Basics {
+    #[name = "Title"]
+    #[max_length = 100]
+    title: String,
+    #[name = "Description"]
+    #[paragraph]
+    #[max_length = 1000]
+    description: Option<String>,
+    #[name = "Tags, separated by commas"]
+    #[placeholder = "game, irl"]
+    #[max_length = 200]
+    tags: Option<String>,
+}
+
+struct BasicsFields {
+    title: String,
+    description: String,
+    tags: Vec<String>,
+}
+
+impl Form for Basics {
+    type Output = BasicsFields;
+
+    fn validate(&self) -> Result<BasicsFields, SlimeError> {
+        Ok(BasicsFields {
+            title: self.title.trim().to_string(),
+            description: self.description.clone().unwrap_or_default(),
+            tags: self.tags.as_deref().map(tags::parse).unwrap_or_default(),
+        })
+    }
+}
+
+/// When the event is on.
+#[derive(Debug, Clone, Default, poise::Modal)]
+#[name = "New event: when"]
+struct Time {
+    #[name = "Start time (UTC)"]
+    #[placeholder = "2024-03-01 19:30"]
+    when: String,
+    #[name = "Duration (minutes, blank for 60)"]
+    #[max_length = 5]
+    duration: Option<String>,
+}
+
+impl Form for Time {
+    type Output = (DateTime<Utc>, i32);
+
+    fn validate(&self) -> Result<Self::Output, SlimeError> {
+        let starts_at = parse_start_time(&self.when)
+            .ok_or_else(|| SlimeError::InvalidTime(self.when.clone()))?;
+        let duration = self.duration.as_deref().map(parse_positive).transpose()?;
+        Ok((starts_at, duration.unwrap_or(60)))
+    }
+}
+
+/// How many can come.
+#[derive(Debug, Clone, Default, poise::Modal)]
+#[name = "New event: places"]
+struct Places {
+    #[name = "Capacity (blank for unlimited)"]
+    #[max_length = 5]
+    capacity: Option<String>,
+}
+
+impl Form for Places {
+    type Output = Option<i32>;
+
+    fn validate(&self) -> Result<Option<i32>, SlimeError> {
+        self.capacity.as_deref().map(parse_positive).transpose()
+    }
+}
+
+/// What to call a template saved from the wizard.
+#[derive(Debug, Clone, poise::Modal)]
+#[name = "Save as a template"]
+struct TemplateName {
+    #[name = "Template name"]
+    #[max_length = 50]
+    name: String,
+}
+
+impl Form for TemplateName {
+    type Output = String;
+
+    fn validate(&self) -> Result<String, SlimeError> {
+        let name = self.name.trim().to_lowercase();
+        if name.is_empty() {
+            return Err(SlimeError::InvalidTemplate(
+                "its name can't be blank".into(),
+            ));
+        }
+        Ok(name)
+    }
+}
+
+/// An event saved to start others from: everything but when it's on.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Template {
+    title: String,
+    description: String,
+    duration_minutes: i32,
+    capacity: Option<i32>,
+    tags: Vec<String>,
+}
+
+async fn load_template(
+    pool: &PgPool,
+    guild_id: GuildId,
+    name: &str,
+) -> Result<Option<Template>, SlimeError> {
+    Ok(sqlx::query_as::<_, Template>(
+        "SELECT title, description, duration_minutes, capacity, tags
+         FROM event_templates WHERE guild_id = $1 AND name = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(name.trim().to_lowercase())
+    .fetch_optional(pool)
+    .await?)
+}
+
+async fn save_template(
+    pool: &PgPool,
+    event: &Event,
+    name: &str,
+    created_by: UserId,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO event_templates
+            (guild_id, name, created_by, title, description, duration_minutes, capacity, tags)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (guild_id, name) DO UPDATE SET
+            created_by = EXCLUDED.created_by, title = EXCLUDED.title,
+            description = EXCLUDED.description, duration_minutes = EXCLUDED.duration_minutes,
+            capacity = EXCLUDED.capacity, tags = EXCLUDED.tags, created_at = now()",
+    )
+    .bind(event.guild_id)
+    .bind(name)
+    .bind(created_by.get() as i64)
+    .bind(&event.title)
+    .bind(&event.description)
+    .bind(event.duration_minutes)
+    .bind(event.capacity)
+    .bind(&event.tags)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn basics_of(event: &Event) -> Basics {
+    Basics {
+        title: event.title.clone(),
+        description: Some(event.description.clone()).filter(|d| !d.is_empty()),
+        tags: Some(event.tags.join(", ")).filter(|t| !t.is_empty()),
+    }
+}
+
+fn time_of(event: &Event) -> Time {
+    Time {
+        when: event.starts_at.format("%Y-%m-%d %H:%M").to_string(),
+        duration: Some(event.duration_minutes.to_string()),
+    }
+}
+
+fn places_of(capacity: Option<i32>) -> Places {
+    Places {
+        capacity: capacity.map(|c| c.to_string()),
+    }
+}
+
+/// A button on one of the wizard's messages, tied to this run of it.
+fn button(
+    ctx: ApplicationContext<'_>,
+    action: &str,
+    label: &str,
+    style: ButtonStyle,
+) -> CreateButton {
+    CreateButton::new(CustomId::for_invocation(Kind::Wizard, action, ctx.id()))
+        .label(label)
+        .style(style)
+}
+
+/// Waits for the host to press one of the buttons under the wizard's latest message.
+async fn await_press(
+    ctx: ApplicationContext<'_>,
+    actions: &[&str],
+) -> Option<(ComponentInteraction, String)> {
+    let ids = actions
+        .iter()
+        .map(|action| CustomId::for_invocation(Kind::Wizard, action, ctx.id()).to_string())
+        .collect::<Vec<_>>();
+    let press = ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .custom_ids(ids)
+        .timeout(STEP_TIMEOUT)
+        .await?;
+    let action = CustomId::parse(&press.data.custom_id)?.action;
+    Some((press, action))
+}
+
+/// Answers `submitted` with the next step: a note and a button to open its form.
+async fn next_step(
+    ctx: ApplicationContext<'_>,
+    submitted: &ModalInteraction,
+    content: &str,
+    action: &str,
+    label: &str,
+) -> Result<(), SlimeError> {
+    submitted
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![CreateActionRow::Buttons(vec![button(
+                        ctx,
+                        action,
+                        label,
+                        ButtonStyle::Primary,
+                    )])])
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// The draft as it will look once posted, with everything the host can do before that.
+async fn preview(
+    ctx: ApplicationContext<'_>,
+    event: &Event,
+    note: &str,
+) -> Result<CreateInteractionResponseMessage, SlimeError> {
+    let embed = event
+        .post_embed(ctx.serenity_context(), &ctx.data().pool)
+        .await?;
+    let buttons = vec![
+        button(ctx, "basics", "Edit basics", ButtonStyle::Secondary),
+        button(ctx, "time", "Edit time", ButtonStyle::Secondary),
+        button(ctx, "places", "Edit places", ButtonStyle::Secondary),
+        button(ctx, "template", "Save as template", ButtonStyle::Secondary),
+        button(ctx, "publish", "Publish", ButtonStyle::Success),
+    ];
+    Ok(CreateInteractionResponseMessage::new()
+        .content(format!(
+            "{note}Here's how **{}** will look. It's saved as draft #{id}, so you can also \
+             finish it later with `/event publish {id}`.",
+            event.title,
+            id = event.id
+        ))
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(buttons)])
+        .ephemeral(true))
+}
+
+/// Opens `F` prefilled with `defaults` in answer to `press`, and waits for it to check out.
+async fn ask<F: Form>(
+    ctx: ApplicationContext<'_>,
+    press: &ComponentInteraction,
+    defaults: F,
+) -> Result<Option<(ModalInteraction, F::Output)>, SlimeError> {
+    let modal_id = press.id.to_string();
+    press
+        .create_response(ctx, F::create(Some(defaults), modal_id.clone()))
+        .await?;
+    forms::collect::<F>(ctx.serenity_context(), ctx.author().id, modal_id).await
+}
+
+/// Create an event step by step, with a preview before it's posted.
+#[poise::command(slash_command, guild_only)]
+pub async fn wizard(
+    ctx: ApplicationContext<'_>,
+    #[description = "Start from a template saved here before"]
+    #[max_length = 50]
+    template: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    quotas::ensure_events(pool, guild_id, 1).await?;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let template = match template {
+        Some(name) => {
+            let Some(template) = load_template(pool, guild_id, &name).await? else {
+                ctx.send(
+                    CreateReply::default()
+                        .content(format!("There's no template called **{name}** here."))
+                        .ephemeral(true),
+                )
+                .await?;
+                return Ok(());
+            };
+            Some(template)
+        }
+        None => None,
+    };
+
+    // The basics, straight away in answer to the command.
+    let basics = template.as_ref().map(|t| Basics {
+        title: t.title.clone(),
+        description: Some(t.description.clone()).filter(|d| !d.is_empty()),
+        tags: Some(t.tags.join(", ")).filter(|t| !t.is_empty()),
+    });
+    let modal_id = ctx.interaction.id.to_string();
+    ctx.interaction
+        .create_response(ctx, Basics::create(basics, modal_id.clone()))
+        .await?;
+    ctx.has_sent_initial_response
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+    let Some((submitted, basics)) =
+        forms::collect::<Basics>(ctx.serenity_context(), ctx.author().id, modal_id).await?
+    else {
+        return Ok(());
+    };
+    next_step(
+        ctx,
+        &submitted,
+        "Next, when is it on?",
+        "time",
+        "Set the time",
+    )
+    .await?;
+
+    let Some((press, _)) = await_press(ctx, &["time"]).await else {
+        return Ok(());
+    };
+    let time = Time {
+        duration: template.as_ref().map(|t| t.duration_minutes.to_string()),
+        ..Default::default()
+    };
+    let Some((submitted, (starts_at, duration_minutes))) = ask(ctx, &press, time).await? else {
+        return Ok(());
+    };
+    next_step(
+        ctx,
+        &submitted,
+        "Last, how many can come?",
+        "places",
+        "Set places",
+    )
+    .await?;
+
+    let Some((press, _)) = await_press(ctx, &["places"]).await else {
+        return Ok(());
+    };
+    let places = places_of(template.as_ref().and_then(|t| t.capacity));
+    let Some((mut submitted, capacity)) = ask(ctx, &press, places).await? else {
+        return Ok(());
+    };
+
+    let new = NewEvent {
+        guild_id,
+        channel_id: settings.events_channel().unwrap_or(ctx.channel_id()),
+        host_id: ctx.author().id,
+        title: basics.title,
+        description: basics.description,
+        starts_at,
+        duration_minutes,
+        capacity,
+        tags: basics.tags,
+    };
+    let mut event = Event::insert(pool, new, EventStatus::Draft).await?;
+
+    // Changes go straight onto the draft, and each one shows it again.
+    let mut note = String::new();
+    loop {
+        submitted
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(preview(ctx, &event, &note).await?),
+            )
+            .await?;
+        note.clear();
+
+        let actions = ["basics", "time", "places", "template", "publish"];
+        let Some((press, action)) = await_press(ctx, &actions).await else {
+            return Ok(());
+        };
+        submitted = match action.as_str() {
+            "basics" => {
+                let Some((submitted, basics)) = ask(ctx, &press, basics_of(&event)).await? else {
+                    return Ok(());
+                };
+                event.title = basics.title;
+                event.description = basics.description;
+                event.save(pool).await?;
+                tags::replace(pool, &mut event, basics.tags).await?;
+                submitted
+            }
+            "time" => {
+                let Some((submitted, (starts_at, duration))) =
+                    ask(ctx, &press, time_of(&event)).await?
+                else {
+                    return Ok(());
+                };
+                event.starts_at = starts_at;
+                event.duration_minutes = duration;
+                event.save(pool).await?;
+                submitted
+            }
+            "places" => {
+                let Some((submitted, capacity)) =
+                    ask(ctx, &press, places_of(event.capacity)).await?
+                else {
+                    return Ok(());
+                };
+                event.capacity = capacity;
+                event.save(pool).await?;
+                submitted
+            }
+            "template" => {
+                let name = TemplateName {
+                    name: String::new(),
+                };
+                let Some((submitted, name)) = ask(ctx, &press, name).await? else {
+                    return Ok(());
+                };
+                save_template(pool, &event, &name, ctx.author().id).await?;
+                note = format!(
+                    "Saved as template **{name}**. Start from it with `/event wizard template:{name}`.\n"
+                );
+                submitted
+            }
+            _ => {
+                let content = submit_or_publish(ctx.into(), &settings, &mut event).await?;
+                press
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .content(content)
+                                .components(vec![]),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wizard_steps_check_their_fields() {
+        let time = Time {
+            when: "2024-03-01 19:30".to_string(),
+            duration: None,
+        };
+        assert_eq!(time.validate().unwrap().1, 60);
+        let time = Time {
+            duration: Some("ninety".to_string()),
+            ..time
+        };
+        assert!(matches!(time.validate(), Err(SlimeError::InvalidNumber(_))));
+
+        assert_eq!(places_of(None).validate().unwrap(), None);
+        assert_eq!(places_of(Some(8)).validate().unwrap(), Some(8));
+
+        let basics = Basics {
+            title: "  Pond cleanup ".to_string(),
+            tags: Some("IRL, irl, outdoors".to_string()),
+            ..Default::default()
+        };
+        let fields = basics.validate().unwrap();
+        assert_eq!(fields.title, "Pond cleanup");
+        assert_eq!(fields.tags.len(), 2);
+    }
+}
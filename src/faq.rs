@@ -0,0 +1,317 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::info;
+
+use crate::{
+    settings::GuildSettings,
+    tags::{self, autocomplete_tag},
+    Context, Data, SlimeError,
+};
+
+/// How long a trigger stays quiet after answering, unless it's given its own.
+const DEFAULT_COOLDOWN_MINUTES: u32 = 10;
+
+/// The words of `text`, lowercased, for matching phrases regardless of case and punctuation.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Whether `text` contains every word of `phrase`, in order and next to each other.
+fn matches(phrase: &str, text: &str) -> bool {
+    let phrase = words(phrase);
+    !phrase.is_empty() && words(text).windows(phrase.len()).any(|w| w == phrase)
+}
+
+/// Answers `message` with the tag of the first trigger it contains, if it's in an FAQ channel
+/// and that trigger isn't cooling down. The longest phrase wins, being the most specific. Only
+/// runs with the guild's consent to reading message content.
+pub async fn noticed(
+    ctx: &SerenityContext,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    let pool = &data.pool;
+    let triggers = sqlx::query_as::<_, (String, String)>(
+        "SELECT phrase, tag_name FROM faq_triggers
+         WHERE guild_id = $1 AND EXISTS (SELECT 1 FROM faq_channels WHERE channel_id = $2)
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         ORDER BY length(phrase) DESC",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(message.channel_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let Some((phrase, tag)) = triggers
+        .into_iter()
+        .find(|(phrase, _)| matches(phrase, &message.content))
+    else {
+        return Ok(());
+    };
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        return Ok(());
+    }
+
+    // Claimed before answering, so two messages at once don't both get the answer.
+    let now = data.clock.now();
+    let claimed = sqlx::query(
+        "UPDATE faq_triggers SET fires = fires + 1, last_fired_at = $3
+         WHERE guild_id = $1 AND phrase = $2
+            AND (last_fired_at IS NULL
+                OR last_fired_at + make_interval(mins => cooldown_minutes) <= $3)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&phrase)
+    .bind(now)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(());
+    }
+    if tags::answer(ctx, pool, guild_id, &tag, message)
+        .await?
+        .is_none()
+    {
+        info!("FAQ trigger \"{}\" points at missing tag {}", phrase, tag);
+    }
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+async fn autocomplete_trigger(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    sqlx::query_scalar::<_, String>(
+        "SELECT phrase FROM faq_triggers WHERE guild_id = $1 AND phrase LIKE $2 || '%'
+         ORDER BY fires DESC LIMIT 25",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(phrase(partial))
+    .fetch_all(&ctx.data().pool)
+    .await
+    .unwrap_or_default()
+}
+
+/// Phrases are stored as their words, so spacing and punctuation don't make two of them.
+fn phrase(text: &str) -> String {
+    words(text).join(" ")
+}
+
+/// Answer common questions with a tag whenever someone asks them.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("add", "remove", "channel", "stats")
+)]
+pub async fn faq(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Answer with a tag whenever a phrase is written in an FAQ channel.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Words to listen for, like \"how do I sign up\""]
+    #[max_length = 100]
+    trigger: String,
+    #[description = "Tag to answer with"]
+    #[autocomplete = "autocomplete_tag"]
+    tag: String,
+    #[description = "Minutes to stay quiet after answering (default 10)"]
+    #[max = 1440]
+    cooldown: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let trigger = phrase(&trigger);
+    let tag = tags::normalize(&tag);
+    if trigger.is_empty() {
+        return reply(ctx, "A trigger needs at least one word.").await;
+    }
+
+    let pool = &ctx.data().pool;
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM tags WHERE guild_id = $1 AND name = $2)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&tag)
+    .fetch_one(pool)
+    .await?;
+    if !exists {
+        return reply(
+            ctx,
+            format!("There's no tag called **{tag}**. Make it with `/tag create` first."),
+        )
+        .await;
+    }
+
+    let cooldown = cooldown.unwrap_or(DEFAULT_COOLDOWN_MINUTES);
+    sqlx::query(
+        "INSERT INTO faq_triggers (guild_id, phrase, tag_name, cooldown_minutes, created_by)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (guild_id, phrase) DO UPDATE SET
+            tag_name = EXCLUDED.tag_name, cooldown_minutes = EXCLUDED.cooldown_minutes,
+            created_by = EXCLUDED.created_by",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&trigger)
+    .bind(&tag)
+    .bind(cooldown as i32)
+    .bind(ctx.author().id.get() as i64)
+    .execute(pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Messages with \"{trigger}\" in FAQ channels will be answered with **{tag}**, at \
+             most once every {cooldown} minute(s)."
+        ),
+    )
+    .await
+}
+
+/// Stop answering a phrase.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Trigger to remove"]
+    #[autocomplete = "autocomplete_trigger"]
+    trigger: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let trigger = phrase(&trigger);
+    let removed = sqlx::query("DELETE FROM faq_triggers WHERE guild_id = $1 AND phrase = $2")
+        .bind(guild_id.get() as i64)
+        .bind(&trigger)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("\"{trigger}\" will no longer be answered.")
+    } else {
+        format!("There's no trigger \"{trigger}\".")
+    };
+    reply(ctx, content).await
+}
+
+/// Choose whether triggers are listened for in a channel.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn channel(
+    ctx: Context<'_>,
+    #[description = "Channel to listen in"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "Whether to answer triggers there"] enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    if enabled {
+        sqlx::query(
+            "INSERT INTO faq_channels (channel_id, guild_id) VALUES ($1, $2)
+             ON CONFLICT (channel_id) DO NOTHING",
+        )
+        .bind(channel.id.get() as i64)
+        .bind(guild_id.get() as i64)
+        .execute(pool)
+        .await?;
+    } else {
+        sqlx::query("DELETE FROM faq_channels WHERE channel_id = $1 AND guild_id = $2")
+            .bind(channel.id.get() as i64)
+            .bind(guild_id.get() as i64)
+            .execute(pool)
+            .await?;
+    }
+
+    let mut content = if enabled {
+        format!("Triggers will be answered in {}.", channel.mention())
+    } else {
+        format!("Triggers won't be answered in {}.", channel.mention())
+    };
+    if enabled
+        && !GuildSettings::load(pool, guild_id)
+            .await?
+            .message_content_consent
+    {
+        content.push_str(
+            "\nMessages are only read once message content features are allowed with \
+             `/settings message_content`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// See which FAQs get answered most, and where.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn stats(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let triggers = sqlx::query_as::<_, (String, String, i32, i64)>(
+        "SELECT phrase, tag_name, cooldown_minutes, fires FROM faq_triggers
+         WHERE guild_id = $1 ORDER BY fires DESC, phrase",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let channels = sqlx::query_scalar::<_, i64>(
+        "SELECT channel_id FROM faq_channels WHERE guild_id = $1 ORDER BY channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+
+    if triggers.is_empty() {
+        return reply(ctx, "This server has no FAQ triggers yet.").await;
+    }
+    let mut lines = triggers
+        .iter()
+        .map(|(phrase, tag, cooldown, fires)| {
+            format!("• \"{phrase}\" → **{tag}**: answered {fires} time(s), every {cooldown} min at most")
+        })
+        .collect::<Vec<_>>();
+    lines.push(if channels.is_empty() {
+        "Not listening in any channels yet; add one with `/faq channel`.".to_string()
+    } else {
+        format!(
+            "Listening in {}.",
+            channels
+                .iter()
+                .map(|c| ChannelId::new(*c as u64).mention().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    });
+    reply(ctx, lines.join("\n")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers_match_whole_words_in_order() {
+        assert!(matches("sign up", "How do I SIGN-UP for raids?"));
+        assert!(matches("sign up", "where's the sign up"));
+        assert!(!matches("sign up", "sign me up"));
+        assert!(!matches("raid", "are the raiders on?"));
+        assert!(!matches("?!", "anything?!"));
+        assert_eq!(phrase("  How do I,  sign up? "), "how do i sign up");
+    }
+}
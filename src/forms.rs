@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::*;
+use serenity::{client::Context as SerenityContext, Error as SerenityError};
+
+use crate::{
+    custom_id::{CustomId, Kind},
+    discord::Discord,
+    SlimeError,
+};
+
+/// How long a member has to fill in a form, and to come back and fix one that didn't check out.
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A modal whose fields make something typed once they're checked, like an event's start time
+/// read from what the member wrote.
+pub trait Form: poise::Modal + Clone + Send + 'static {
+    type Output;
+
+    /// What the fields make, or what's wrong with them, worded for the member.
+    fn validate(&self) -> Result<Self::Output, SlimeError>;
+}
+
+/// Waits for `user` to submit form `F` from the modal sent with `modal_id`. A submission that
+/// doesn't check out is answered with what's wrong and a button to open the form again as they
+/// left it, as often as it takes. Returns the submission that did, for the caller to answer, and
+/// what it made; `None` if the member walked away.
+pub async fn collect<F: Form>(
+    ctx: &SerenityContext,
+    user: UserId,
+    modal_id: String,
+) -> Result<Option<(ModalInteraction, F::Output)>, SlimeError> {
+    // Tied to this modal, so fix buttons under older forms don't reopen this one.
+    let retry_id =
+        CustomId::for_invocation(Kind::Form, "retry", modal_id.parse().unwrap_or_default())
+            .to_string();
+    loop {
+        let filter_id = modal_id.clone();
+        let Some(submitted) = ModalInteractionCollector::new(&ctx.shard)
+            .author_id(user)
+            .filter(move |m| m.data.custom_id == filter_id)
+            .timeout(TIMEOUT)
+            .await
+        else {
+            return Ok(None);
+        };
+        let fields = F::parse(submitted.data.clone()).map_err(SerenityError::Other)?;
+        let problem = match fields.validate() {
+            Ok(output) => return Ok(Some((submitted, output))),
+            Err(problem) => problem,
+        };
+
+        submitted
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!(
+                            "That didn't go in: {problem}. Press **Fix it** to change it."
+                        ))
+                        .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(
+                            &retry_id,
+                        )
+                        .label("Fix it")
+                        .style(ButtonStyle::Primary)])])
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        let reopened = ctx
+            .await_button(
+                user,
+                std::slice::from_ref(&retry_id),
+                TIMEOUT,
+                F::create(Some(fields), modal_id.clone()),
+            )
+            .await?;
+        if reopened.is_none() {
+            return Ok(None);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{
+    audit::{self, AuditEntry},
+    departure,
+    events::{rsvp, Event},
+    roles::fetch_all_members,
+    util::http_status,
+    Data, SlimeError,
+};
+
+/// How often orphans are collected. They do no harm for a while, and listing members is slow.
+pub const INTERVAL_MINUTES: i64 = 60;
+
+/// What one collection cleaned up.
+#[derive(Debug, Default)]
+pub struct Collected {
+    /// Published or pending events whose channel was deleted.
+    pub events_cancelled: u64,
+    /// RSVPs to upcoming events from members who have left.
+    pub rsvps_removed: u64,
+    /// Channel schedules for deleted channels.
+    pub schedules_removed: u64,
+    pub lfg_entries_removed: u64,
+    pub posts_retired: u64,
+    /// Guilds the bot was removed from while no instance was active to notice.
+    pub guilds_detached: u64,
+}
+
+impl Collected {
+    fn is_empty(&self) -> bool {
+        self.events_cancelled
+            + self.rsvps_removed
+            + self.schedules_removed
+            + self.lfg_entries_removed
+            + self.posts_retired
+            + self.guilds_detached
+            == 0
+    }
+}
+
+/// Guilds that have live rows worth checking. Detached guilds are left for their grace period.
+async fn guilds_with_data(pool: &PgPool) -> Result<Vec<GuildId>, SlimeError> {
+    let ids = sqlx::query_scalar::<_, i64>(
+        "SELECT guild_id FROM (
+            SELECT guild_id FROM events WHERE status IN ('pending', 'published')
+            UNION SELECT guild_id FROM channel_schedules
+            UNION SELECT guild_id FROM lfg_queue
+            UNION SELECT guild_id FROM bot_posts
+         ) live
+         WHERE guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids.into_iter().map(|id| GuildId::new(id as u64)).collect())
+}
+
+/// Channels the guild's live rows point at that Discord says no longer exist. Threads aren't in
+/// the guild's channel list, so anything missing from it is looked up on its own before it's
+/// treated as deleted.
+async fn deleted_channels(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channels: &[i64],
+) -> Result<Vec<i64>, SlimeError> {
+    let unlisted = sqlx::query_scalar::<_, i64>(
+        "SELECT channel_id FROM (
+            SELECT guild_id, channel_id FROM events WHERE status IN ('pending', 'published')
+            UNION SELECT guild_id, channel_id FROM channel_schedules
+            UNION SELECT guild_id, channel_id FROM lfg_queue
+            UNION SELECT guild_id, channel_id FROM bot_posts
+         ) referenced
+         WHERE guild_id = $1 AND NOT channel_id = ANY($2)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channels)
+    .fetch_all(pool)
+    .await?;
+
+    let mut deleted = Vec::new();
+    for id in unlisted {
+        match ChannelId::new(id as u64).to_channel(ctx).await {
+            Err(e) if http_status(&e) == Some(404) => deleted.push(id),
+            _ => {}
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Deletes rows of `table` in the guild pointing at one of `deleted`. Returns how many went.
+async fn delete_in(
+    pool: &PgPool,
+    table: &str,
+    guild_id: GuildId,
+    deleted: &[i64],
+) -> Result<u64, SlimeError> {
+    Ok(sqlx::query(&format!(
+        "DELETE FROM {table} WHERE guild_id = $1 AND channel_id = ANY($2)"
+    ))
+    .bind(guild_id.get() as i64)
+    .bind(deleted)
+    .execute(pool)
+    .await?
+    .rows_affected())
+}
+
+/// Cleans up after the guild's deleted channels and departed members.
+async fn collect_guild(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    deleted: &[i64],
+    now: DateTime<Utc>,
+    collected: &mut Collected,
+) -> Result<(), SlimeError> {
+    if deleted.is_empty() {
+        return collect_departed(ctx, pool, guild_id, now, collected).await;
+    }
+
+    let stranded = sqlx::query_as::<_, Event>(
+        "SELECT * FROM events
+         WHERE guild_id = $1 AND status IN ('pending', 'published') AND channel_id = ANY($2)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(deleted)
+    .fetch_all(pool)
+    .await?;
+    for mut event in stranded {
+        event.withdraw(ctx, pool).await?;
+        collected.events_cancelled += 1;
+    }
+
+    collected.schedules_removed += delete_in(pool, "channel_schedules", guild_id, deleted).await?;
+    collected.lfg_entries_removed += delete_in(pool, "lfg_queue", guild_id, deleted).await?;
+    collected.posts_retired += delete_in(pool, "bot_posts", guild_id, deleted).await?;
+
+    collect_departed(ctx, pool, guild_id, now, collected).await
+}
+
+/// Drops RSVPs to upcoming events from members who have left the guild, promoting from the
+/// waitlist into any places that frees.
+async fn collect_departed(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    now: DateTime<Utc>,
+    collected: &mut Collected,
+) -> Result<(), SlimeError> {
+    let rsvps = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT r.event_id, r.user_id FROM event_rsvps r JOIN events e ON e.id = r.event_id
+         WHERE e.guild_id = $1 AND e.status = 'published' AND e.starts_at > $2
+            AND r.user_id > 0",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    if rsvps.is_empty() {
+        return Ok(());
+    }
+
+    let members = fetch_all_members(ctx, guild_id)
+        .await?
+        .into_iter()
+        .map(|m| m.user.id.get() as i64)
+        .collect::<HashSet<_>>();
+    let mut touched = HashSet::new();
+    for (event_id, user_id) in rsvps {
+        if members.contains(&user_id) {
+            continue;
+        }
+        let Some(event) = Event::fetch(pool, event_id).await? else {
+            continue;
+        };
+        if let Some(promoted) = rsvp::leave(pool, &event, UserId::new(user_id as u64)).await? {
+            rsvp::notify_promoted(ctx, pool, &event, promoted).await;
+        }
+        collected.rsvps_removed += 1;
+        touched.insert(event_id);
+    }
+
+    for event_id in touched {
+        if let Some(event) = Event::fetch(pool, event_id).await? {
+            event.refresh_post(ctx, pool).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds and cleans up rows that point at channels or members that no longer exist, detaches
+/// guilds the bot is no longer in, and records a summary in the audit log when anything was found.
+pub async fn collect(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<Collected, SlimeError> {
+    let pool = &data.pool;
+    let mut collected = Collected::default();
+
+    for guild_id in guilds_with_data(pool).await? {
+        // Asking Discord directly, rather than trusting the cache, so a guild that hasn't come
+        // back from an outage yet isn't mistaken for one the bot was removed from.
+        let deleted = match guild_id.channels(ctx).await {
+            Ok(channels) => {
+                let channels = channels
+                    .keys()
+                    .map(|id| id.get() as i64)
+                    .collect::<Vec<_>>();
+                deleted_channels(ctx, pool, guild_id, &channels).await?
+            }
+            Err(e) if matches!(http_status(&e), Some(403 | 404)) => {
+                departure::detach(pool, guild_id).await?;
+                collected.guilds_detached += 1;
+                continue;
+            }
+            Err(e) => {
+                error!(
+                    "Could not list channels of guild {}, skipping it: {}",
+                    guild_id, e
+                );
+                continue;
+            }
+        };
+
+        if let Err(e) = collect_guild(ctx, pool, guild_id, &deleted, now, &mut collected).await {
+            error!("Could not collect orphans in guild {}: {}", guild_id, e);
+        }
+    }
+
+    if !collected.is_empty() {
+        info!("Collected orphaned data: {:?}", collected);
+        let bot = ctx.cache.current_user().id;
+        audit::record(
+            pool,
+            AuditEntry {
+                guild_id: None,
+                actor: bot,
+                action: "gc",
+                target: None,
+                details: format!("{collected:?}"),
+                undo: Vec::new(),
+            },
+        )
+        .await?;
+    }
+
+    Ok(collected)
+}
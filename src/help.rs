@@ -0,0 +1,124 @@
+use crate::{
+    events::{Event, EventStatus},
+    tags,
+    util::paginate,
+    Context, SlimeError,
+};
+
+/// Tags named this followed by a page number make up a guild's own hosting handbook.
+const PAGE_PREFIX: &str = "hosting-";
+
+/// The handbook for guilds that haven't written their own.
+const DEFAULT_PAGES: [&str; 3] = [
+    "**Putting an event up**\n\
+     `/event create` shows you a preview before anything is posted, so you can fix it up or \
+     throw it away. Give it tags, like `game` or `irl`, so members subscribed to them hear \
+     about it. `/event suggest-time` finds the times events like yours draw the most people.",
+    "**Looking after sign-ups**\n\
+     Once your event is full, new sign-ups join the waitlist and get a place when one frees up. \
+     `/event rsvps` shows who's coming and who's away. You're pinged if people are left waiting \
+     on you; turn down anyone you can't take with `/event reject`, and see who's waiting with \
+     `/event pending`.",
+    "**On the day, and after**\n\
+     Members who signed up are reminded before it starts. Afterwards, close it out with \
+     `/event finish` so attendance is counted, and mark no-shows with `/event absent`. If it \
+     can't go ahead, `/event cancel` tells everyone who signed up.",
+];
+
+/// A guild's handbook pages in order, from tags named `hosting-1`, `hosting-2` and so on. Names
+/// that don't end in a page number are left out.
+fn order(tags: Vec<(String, String)>) -> Vec<String> {
+    let mut pages = tags
+        .into_iter()
+        .filter_map(|(name, content)| {
+            let number = name.strip_prefix(PAGE_PREFIX)?.parse::<u32>().ok()?;
+            Some((number, content))
+        })
+        .collect::<Vec<_>>();
+    pages.sort_by_key(|page| page.0);
+    pages.into_iter().map(|page| page.1).collect()
+}
+
+/// Shows the hosting handbook, the guild's own if it has one.
+async fn show(ctx: Context<'_>, title: &str) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let tags = tags::with_prefix(&ctx.data().pool, guild_id, PAGE_PREFIX).await?;
+    let server = tags::server_name(ctx.serenity_context(), guild_id);
+    let mut pages = order(tags)
+        .iter()
+        .map(|page| tags::substitute(page, ctx.author(), ctx.channel_id(), &server))
+        .collect::<Vec<_>>();
+    if pages.is_empty() {
+        pages = DEFAULT_PAGES
+            .iter()
+            .map(|page| {
+                format!(
+                    "{page}\n\n*Admins can write this server's own guide as tags named \
+                     `{PAGE_PREFIX}1`, `{PAGE_PREFIX}2` and so on.*"
+                )
+            })
+            .collect();
+    }
+    paginate(ctx, title, &pages).await
+}
+
+/// Shows first-time hosts the handbook once their first event has gone up. Hosts who had events
+/// before the handbook existed aren't counted as new.
+pub async fn after_create(ctx: Context<'_>, event_id: i64) -> Result<(), SlimeError> {
+    let pool = &ctx.data().pool;
+    let Some(event) = Event::fetch(pool, event_id).await? else {
+        return Ok(());
+    };
+    if event.status == EventStatus::Draft {
+        return Ok(());
+    }
+    let first = sqlx::query(
+        "INSERT INTO hosting_handbook_shown (guild_id, user_id)
+         SELECT $1, $2
+         WHERE NOT EXISTS (
+            SELECT 1 FROM events WHERE guild_id = $1 AND host_id = $2 AND id <> $3
+         )
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(event.guild().get() as i64)
+    .bind(event.host().get() as i64)
+    .bind(event.id)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0;
+    if first {
+        show(ctx, "New to hosting? Here's how it works").await?;
+    }
+    Ok(())
+}
+
+/// Guides to using pond-slime.
+#[poise::command(slash_command, guild_only, subcommands("hosting"))]
+pub async fn help(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Read this server's guide to hosting events.
+#[poise::command(slash_command, guild_only)]
+async fn hosting(ctx: Context<'_>) -> Result<(), SlimeError> {
+    show(ctx, "Hosting events").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handbook_pages_go_by_number() {
+        let tag = |name: &str, content: &str| (name.to_string(), content.to_string());
+        let tags = vec![
+            tag("hosting-10", "ten"),
+            tag("hosting-2", "two"),
+            tag("hosting-rules", "not a page"),
+            tag("hosting-1", "one"),
+        ];
+        assert_eq!(order(tags), ["one", "two", "ten"]);
+        assert!(order(Vec::new()).is_empty());
+    }
+}
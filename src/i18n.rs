@@ -0,0 +1,298 @@
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+
+use crate::{settings::GuildSettings, templates};
+
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Which set of copy a guild's posts are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Tone {
+    /// Short and to the point, the copy every locale has.
+    #[default]
+    #[name = "Formal"]
+    Formal,
+    /// The slime's own voice, where a locale has it. Anything it doesn't say falls back to the
+    /// formal copy in the same language.
+    #[name = "Playful"]
+    Playful,
+}
+
+/// How many emoji go in a guild's posts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum EmojiDensity {
+    /// None at all, even where the copy has them.
+    #[name = "None"]
+    None,
+    /// Only the ones written into the copy.
+    #[default]
+    #[name = "Some"]
+    Normal,
+    /// Headings get one too.
+    #[name = "Lots"]
+    Lots,
+}
+
+/// Resource files, keyed by Discord locale code and tone. Locales only need the keys they
+/// translate, and playful sets only the ones they say differently.
+const RESOURCES: &[(&str, Tone, &str)] = &[
+    ("en-US", Tone::Formal, include_str!("../locales/en-US.txt")),
+    (
+        "en-US",
+        Tone::Playful,
+        include_str!("../locales/en-US.playful.txt"),
+    ),
+    ("de", Tone::Formal, include_str!("../locales/de.txt")),
+    ("es-ES", Tone::Formal, include_str!("../locales/es-ES.txt")),
+    ("fr", Tone::Formal, include_str!("../locales/fr.txt")),
+];
+
+type Catalog = HashMap<(&'static str, Tone), HashMap<&'static str, &'static str>>;
+
+fn catalog() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| {
+        RESOURCES
+            .iter()
+            .map(|(locale, tone, source)| {
+                let strings = source
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, value)| (key.trim(), value.trim()))
+                    .collect();
+                ((*locale, *tone), strings)
+            })
+            .collect()
+    })
+}
+
+/// Looks `key` up for `locale` in `tone`, trying the bare language (`es` for `es-419`) and then
+/// English. A guild keeps its language over its tone, so formal copy in its language comes before
+/// playful copy in English.
+fn lookup(locale: &str, tone: Tone, key: &str) -> Option<&'static str> {
+    let catalog = catalog();
+    let language = locale.split('-').next().unwrap_or(locale);
+
+    [
+        (locale, tone),
+        (language, tone),
+        (locale, Tone::Formal),
+        (language, Tone::Formal),
+        (DEFAULT_LOCALE, tone),
+        (DEFAULT_LOCALE, Tone::Formal),
+    ]
+    .iter()
+    .filter_map(|l| catalog.get(l))
+    .find_map(|strings| strings.get(key).copied())
+}
+
+/// How a guild's posts are worded: in its language, and in the tone and with the emoji it picked
+/// with `/settings style`.
+#[derive(Debug, Clone)]
+pub struct Voice {
+    pub locale: String,
+    pub tone: Tone,
+    pub emoji: EmojiDensity,
+}
+
+impl Voice {
+    /// The voice `settings` ask for in the guild's language.
+    pub fn of(ctx: &SerenityContext, guild_id: GuildId, settings: &GuildSettings) -> Self {
+        Self {
+            locale: guild_locale(ctx, guild_id),
+            tone: settings.tone,
+            emoji: settings.emoji_density,
+        }
+    }
+
+    /// The copy for `key`, or the key itself if no resource file has it.
+    pub fn t(&self, key: &str) -> String {
+        let text = lookup(&self.locale, self.tone, key).unwrap_or(key);
+        match self.emoji {
+            EmojiDensity::None => without_emoji(text),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Like [`Voice::t`], filling each `{name}` placeholder with its value.
+    pub fn t_with(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        templates::render(&self.t(key), args)
+    }
+
+    /// A heading, like the name of a post's field, led by its emoji when the guild wants lots.
+    /// Headings' emoji are the same in every language, so only English has them, as `emoji-` and
+    /// the heading's key.
+    pub fn heading(&self, key: &str) -> String {
+        let heading = self.t(key);
+        let emoji = catalog()
+            .get(&(DEFAULT_LOCALE, Tone::Formal))
+            .and_then(|strings| strings.get(format!("emoji-{key}").as_str()));
+        match emoji {
+            Some(emoji) if self.emoji == EmojiDensity::Lots => format!("{emoji} {heading}"),
+            _ => heading,
+        }
+    }
+}
+
+/// Whether `c` is part of an emoji, including the joiners and variation selectors between them.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B50..=0x2B55 | 0xFE0F | 0x200D
+    )
+}
+
+/// `text` with its emoji taken out, and the gaps they leave closed up.
+fn without_emoji(text: &str) -> String {
+    if !text.chars().any(is_emoji) {
+        return text.to_string();
+    }
+    text.split(' ')
+        .map(|word| word.chars().filter(|c| !is_emoji(*c)).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The locale a guild's community posts should be written in, from the guild's Discord settings.
+pub fn guild_locale(ctx: &SerenityContext, guild_id: GuildId) -> String {
+    ctx.cache.guild(guild_id).map_or_else(
+        || DEFAULT_LOCALE.to_string(),
+        |g| g.preferred_locale.clone(),
+    )
+}
+
+/// A Discord dynamic timestamp, which every client renders in its own language and timezone.
+pub fn timestamp(time: DateTime<Utc>, style: FormattedTimestampStyle) -> String {
+    FormattedTimestamp::new(time.into(), Some(style)).to_string()
+}
+
+/// Fills in the translated names and descriptions Discord shows each command, its parameters and
+/// its subcommands in, from the same resource files. Keys follow the command's full name, like
+/// `command-event-tags-set-name`, or `command-event-create-title-description` for a parameter.
+/// English comes from the commands themselves, so locales without a key aren't given one.
+pub fn localize_commands<U, E>(commands: &mut [poise::Command<U, E>]) {
+    for command in commands {
+        let key = format!("command-{}", command.qualified_name.replace(' ', "-"));
+        localize(
+            &key,
+            &mut command.name_localizations,
+            &mut command.description_localizations,
+        );
+        for parameter in &mut command.parameters {
+            localize(
+                &format!("{key}-{}", parameter.name),
+                &mut parameter.name_localizations,
+                &mut parameter.description_localizations,
+            );
+        }
+        localize_commands(&mut command.subcommands);
+    }
+}
+
+fn localize(
+    key: &str,
+    names: &mut HashMap<String, String>,
+    descriptions: &mut HashMap<String, String>,
+) {
+    let (name, description) = (format!("{key}-name"), format!("{key}-description"));
+    for ((locale, tone), strings) in catalog() {
+        if *locale == DEFAULT_LOCALE || *tone != Tone::Formal {
+            continue;
+        }
+        if let Some(value) = strings.get(name.as_str()) {
+            names.insert(locale.to_string(), value.to_string());
+        }
+        if let Some(value) = strings.get(description.as_str()) {
+            descriptions.insert(locale.to_string(), value.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Discord refuses to register commands whose localized names or descriptions break its rules,
+    /// and then none of the bot's commands update.
+    fn check<U, E>(commands: &[poise::Command<U, E>]) {
+        for command in commands {
+            for name in command.name_localizations.values() {
+                assert!(
+                    name.chars().count() <= 32
+                        && name
+                            .chars()
+                            .all(|c| c == '-' || c == '_' || c.is_alphanumeric())
+                        && name.to_lowercase() == *name,
+                    "`{name}` can't name /{}",
+                    command.qualified_name
+                );
+            }
+            for description in command.description_localizations.values() {
+                assert!(
+                    (1..=100).contains(&description.chars().count()),
+                    "`{description}` is too long for /{}",
+                    command.qualified_name
+                );
+            }
+            for locale in command.name_localizations.keys() {
+                let mut names = command
+                    .subcommands
+                    .iter()
+                    .map(|c| c.name_localizations.get(locale).unwrap_or(&c.name))
+                    .collect::<Vec<_>>();
+                names.sort();
+                let count = names.len();
+                names.dedup();
+                assert_eq!(
+                    names.len(),
+                    count,
+                    "/{} has two subcommands with one name in {locale}",
+                    command.qualified_name
+                );
+            }
+            check(&command.subcommands);
+        }
+    }
+
+    #[test]
+    fn voices_pick_their_copy() {
+        let voice = |locale: &str| Voice {
+            locale: locale.to_string(),
+            tone: Tone::Formal,
+            emoji: EmojiDensity::Normal,
+        };
+        let mut voice_fr = voice("fr");
+        assert_eq!(voice_fr.t("event-going"), "Participants");
+        voice_fr.tone = Tone::Playful;
+        // French has no playful copy, and stays French rather than going playful in English.
+        assert_eq!(voice_fr.t("event-going"), "Participants");
+
+        let mut voice = voice("en-GB");
+        assert_eq!(voice.heading("event-going"), "Going");
+        voice.tone = Tone::Playful;
+        assert_ne!(voice.t("event-going"), "Going");
+        voice.emoji = EmojiDensity::Lots;
+        assert!(voice
+            .heading("event-starts")
+            .starts_with(|c: char| is_emoji(c)));
+
+        assert_eq!(without_emoji("🐸 Hop in! 🎉"), "Hop in!");
+        assert_eq!(without_emoji("Three 👨‍👩‍👧 go"), "Three go");
+    }
+
+    #[test]
+    fn localized_command_names_are_valid() {
+        let mut commands = vec![crate::events::event()];
+        localize_commands(&mut commands);
+        assert_eq!(commands[0].name_localizations["de"], "event");
+        check(&commands);
+    }
+}
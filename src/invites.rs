@@ -0,0 +1,461 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    i18n::Voice,
+    settings::GuildSettings,
+    templates,
+    util::send_dm,
+    Context, Data, SlimeError,
+};
+
+/// Variables a guild's `invite_notice` may use.
+pub const NOTICE_VARIABLES: &[&str] = &["user", "channel", "server"];
+
+/// Where invite links point, before their code.
+const INVITE_PREFIXES: &[&str] = &[
+    "discord.gg/",
+    "discord.com/invite/",
+    "discordapp.com/invite/",
+];
+
+/// What an exemption lets through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum Exemption {
+    /// Anything posted in the channel.
+    Channel,
+    /// Anything posted by members with the role.
+    Role,
+    /// Invites to the guild, from anyone anywhere.
+    Partner,
+}
+
+/// The invite codes in `text`, in order.
+fn invite_codes(text: &str) -> Vec<String> {
+    // ASCII only, so offsets into it are offsets into `text`.
+    let lower = text.to_ascii_lowercase();
+    let mut codes = Vec::new();
+    for prefix in INVITE_PREFIXES {
+        let mut rest = lower.as_str();
+        let mut offset = 0;
+        while let Some(at) = rest.find(prefix) {
+            let start = offset + at + prefix.len();
+            // Codes are case-sensitive, so they're read from the original text.
+            let code = text[start..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect::<String>();
+            if !code.is_empty() && !codes.contains(&code) {
+                codes.push(code);
+            }
+            offset = start;
+            rest = &lower[start..];
+        }
+    }
+    codes
+}
+
+/// Removes `message` if it invites people to another server and isn't exempt, and tells its
+/// author why. Invites that don't resolve, like expired ones, are left alone. Returns whether
+/// the message was deleted. Only runs with the guild's consent to reading message content.
+pub async fn noticed(
+    ctx: &SerenityContext,
+    data: &Data,
+    message: &Message,
+) -> Result<bool, SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(false);
+    };
+    let codes = invite_codes(&message.content);
+    if codes.is_empty() {
+        return Ok(false);
+    }
+    let pool = &data.pool;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    if !settings.invite_policy || !settings.message_content_consent {
+        return Ok(false);
+    }
+
+    let exemptions = sqlx::query_as::<_, (Exemption, i64)>(
+        "SELECT kind, target_id FROM invite_exemptions
+         WHERE guild_id = $1 AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let exempt = |kind, id: u64| exemptions.contains(&(kind, id as i64));
+    let roles = message
+        .member
+        .as_ref()
+        .map(|m| m.roles.clone())
+        .unwrap_or_default();
+    if exempt(Exemption::Channel, message.channel_id.get())
+        || roles.iter().any(|r| exempt(Exemption::Role, r.get()))
+    {
+        return Ok(false);
+    }
+
+    let mut foreign = None;
+    for code in &codes {
+        let invite = match Invite::get(ctx, code, false, false, None).await {
+            Ok(invite) => invite,
+            Err(e) => {
+                error!("Could not look up invite {}: {}", code, e);
+                continue;
+            }
+        };
+        let Some(guild) = invite.guild else {
+            continue;
+        };
+        if guild.id != guild_id && !exempt(Exemption::Partner, guild.id.get()) {
+            foreign = Some((code, guild));
+            break;
+        }
+    }
+    let Some((code, guild)) = foreign else {
+        return Ok(false);
+    };
+
+    message.delete(ctx).await?;
+    let bot = ctx.cache.current_user().id;
+    audit::record_and_post(
+        ctx,
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: bot,
+            action: "invite_removed",
+            target: Some(message.id.get()),
+            details: format!(
+                "{code} to {} from {} in {}",
+                guild.id, message.author.id, message.channel_id
+            ),
+            undo: Vec::new(),
+        },
+        &format!(
+            "Removed an invite to **{}** from {} in {}.",
+            guild.name,
+            message.author.mention(),
+            message.channel_id.mention()
+        ),
+    )
+    .await?;
+
+    let server = ctx
+        .cache
+        .guild(guild_id)
+        .map_or_else(|| "this server".to_string(), |g| g.name.clone());
+    let voice = Voice::of(ctx, guild_id, &settings);
+    let mut notice = {
+        let values: [(&str, &dyn std::fmt::Display); 3] = [
+            ("user", &message.author.mention()),
+            ("channel", &message.channel_id.mention()),
+            ("server", &server),
+        ];
+        match &settings.invite_notice {
+            Some(template) => templates::render(template, &values),
+            None => voice.t_with("invite-removed", &values),
+        }
+    };
+    if let Some(appeal) = &settings.invite_appeal {
+        notice.push('\n');
+        notice.push_str(&voice.t_with("invite-appeal", &[("appeal", appeal)]));
+    }
+    // Told privately where possible, and in the channel only if their DMs are closed.
+    if send_dm(
+        ctx,
+        message.author.id,
+        CreateMessage::new().content(&notice),
+    )
+    .await
+    .is_err()
+    {
+        let told = message
+            .channel_id
+            .send_message(
+                ctx,
+                CreateMessage::new()
+                    .content(notice)
+                    .allowed_mentions(CreateAllowedMentions::new().users([message.author.id])),
+            )
+            .await;
+        if let Err(e) = told {
+            error!(
+                "Could not explain a removed invite to {}: {}",
+                message.author.id, e
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// The guild `server` names, as an invite to it or its ID, with its name if an invite said.
+async fn server(
+    ctx: Context<'_>,
+    server: &str,
+) -> Result<Option<(GuildId, Option<String>)>, SlimeError> {
+    let server = server.trim();
+    if let Some(id) = server.parse::<u64>().ok().filter(|id| *id != 0) {
+        return Ok(Some((GuildId::new(id), None)));
+    }
+    let code = invite_codes(server)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| server.to_string());
+    Ok(Invite::get(ctx, &code, false, false, None)
+        .await
+        .ok()
+        .and_then(|invite| invite.guild)
+        .map(|guild| (guild.id, Some(guild.name))))
+}
+
+/// Where invites to other servers may be posted, once `/settings invites` removes them.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("allow", "disallow", "partner", "unpartner", "list")
+)]
+pub async fn invites(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Let invites be posted in a channel, or by members with a role.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn allow(
+    ctx: Context<'_>,
+    #[description = "Channel invites can be posted in"]
+    #[channel_types("Text", "News", "Forum")]
+    channel: Option<GuildChannel>,
+    #[description = "Role whose members can post invites anywhere"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let targets = exemptions(channel.as_ref(), role.as_ref());
+    if targets.is_empty() {
+        return reply(ctx, "Pick a channel, a role or both.").await;
+    }
+
+    for (kind, id, _) in &targets {
+        sqlx::query(
+            "INSERT INTO invite_exemptions (guild_id, kind, target_id, added_by)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (guild_id, kind, target_id) DO NOTHING",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(kind)
+        .bind(*id as i64)
+        .bind(ctx.author().id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?;
+    }
+    let allowed = targets
+        .iter()
+        .map(|(_, _, description)| description.as_str())
+        .collect::<Vec<_>>()
+        .join(" and ");
+    reply(ctx, format!("Invites are allowed {allowed}.")).await
+}
+
+/// Stop letting invites be posted in a channel, or by members with a role.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn disallow(
+    ctx: Context<'_>,
+    #[description = "Channel to stop allowing invites in"]
+    #[channel_types("Text", "News", "Forum")]
+    channel: Option<GuildChannel>,
+    #[description = "Role to stop allowing invites from"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let targets = exemptions(channel.as_ref(), role.as_ref());
+    if targets.is_empty() {
+        return reply(ctx, "Pick a channel, a role or both.").await;
+    }
+
+    for (kind, id, _) in &targets {
+        sqlx::query(
+            "DELETE FROM invite_exemptions WHERE guild_id = $1 AND kind = $2 AND target_id = $3",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(kind)
+        .bind(*id as i64)
+        .execute(&ctx.data().pool)
+        .await?;
+    }
+    let disallowed = targets
+        .iter()
+        .map(|(_, _, description)| description.as_str())
+        .collect::<Vec<_>>()
+        .join(" or ");
+    reply(ctx, format!("Invites are no longer allowed {disallowed}.")).await
+}
+
+/// The exemptions a channel and role option make, each with how to describe it.
+fn exemptions(
+    channel: Option<&GuildChannel>,
+    role: Option<&Role>,
+) -> Vec<(Exemption, u64, String)> {
+    let mut targets = Vec::new();
+    if let Some(channel) = channel {
+        targets.push((
+            Exemption::Channel,
+            channel.id.get(),
+            format!("in {}", channel.mention()),
+        ));
+    }
+    if let Some(role) = role {
+        targets.push((
+            Exemption::Role,
+            role.id.get(),
+            format!("from {}", role.mention()),
+        ));
+    }
+    targets
+}
+
+/// Let anyone post invites to a partner server, anywhere.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn partner(
+    ctx: Context<'_>,
+    #[description = "An invite to the server, or its ID"]
+    #[max_length = 100]
+    server_invite: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let Some((partner, name)) = server(ctx, &server_invite).await? else {
+        return reply(
+            ctx,
+            "That isn't an invite that works, or a server ID. Try a fresh invite link.",
+        )
+        .await;
+    };
+    if partner == guild_id {
+        return reply(ctx, "Invites to this server are always allowed.").await;
+    }
+
+    sqlx::query(
+        "INSERT INTO invite_exemptions (guild_id, kind, target_id, name, added_by)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (guild_id, kind, target_id)
+            DO UPDATE SET name = COALESCE(EXCLUDED.name, invite_exemptions.name)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(Exemption::Partner)
+    .bind(partner.get() as i64)
+    .bind(&name)
+    .bind(ctx.author().id.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let name = name.map_or_else(|| format!("server {partner}"), |n| format!("**{n}**"));
+    reply(ctx, format!("Invites to {name} are allowed anywhere.")).await
+}
+
+/// Stop treating a server as a partner.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn unpartner(
+    ctx: Context<'_>,
+    #[description = "An invite to the server, or its ID"]
+    #[max_length = 100]
+    server_invite: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let Some((partner, _)) = server(ctx, &server_invite).await? else {
+        return reply(ctx, "That isn't an invite that works, or a server ID.").await;
+    };
+    let removed = sqlx::query(
+        "DELETE FROM invite_exemptions WHERE guild_id = $1 AND kind = $2 AND target_id = $3",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(Exemption::Partner)
+    .bind(partner.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Server {partner} is no longer a partner.")
+    } else {
+        format!("Server {partner} wasn't a partner.")
+    };
+    reply(ctx, content).await
+}
+
+/// See where invites are allowed.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let enabled = GuildSettings::load(pool, guild_id).await?.invite_policy;
+    let exemptions = sqlx::query_as::<_, (Exemption, i64, Option<String>)>(
+        "SELECT kind, target_id, name FROM invite_exemptions
+         WHERE guild_id = $1 ORDER BY kind, target_id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+
+    let of = |kind| {
+        let listed = exemptions
+            .iter()
+            .filter(|(k, ..)| *k == kind)
+            .map(|(_, id, name)| match kind {
+                Exemption::Channel => ChannelId::new(*id as u64).mention().to_string(),
+                Exemption::Role => RoleId::new(*id as u64).mention().to_string(),
+                Exemption::Partner => name
+                    .as_ref()
+                    .map_or_else(|| id.to_string(), |n| format!("{n} ({id})")),
+            })
+            .collect::<Vec<_>>();
+        if listed.is_empty() {
+            "none".to_string()
+        } else {
+            listed.join(", ")
+        }
+    };
+    let status = if enabled {
+        "Invites to other servers are removed."
+    } else {
+        "Invites aren't removed; turn that on with `/settings invites`."
+    };
+    reply(
+        ctx,
+        format!(
+            "{status}\n**Allowed in:** {}\n**Allowed from:** {}\n**Partners:** {}",
+            of(Exemption::Channel),
+            of(Exemption::Role),
+            of(Exemption::Partner)
+        ),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invite_codes_are_found_in_any_form() {
+        assert_eq!(
+            invite_codes(
+                "join https://discord.gg/AbC123 or discord.com/invite/pond-Slime, \
+                 and again https://DISCORD.GG/AbC123!"
+            ),
+            vec!["AbC123", "pond-Slime"]
+        );
+        assert_eq!(
+            invite_codes("https://discordapp.com/invite/xyz"),
+            vec!["xyz"]
+        );
+        assert!(invite_codes("discord.gg/ and discord.com/channels/1/2").is_empty());
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{Context, Data, SlimeError};
+
+/// How often threads are checked against their channel's rule. Listing a guild's threads is one
+/// call to Discord, so this doesn't run every tick.
+pub const INTERVAL_MINUTES: i64 = 30;
+
+/// How often each channel with a report channel gets a report.
+const REPORT_DAYS: i64 = 7;
+
+/// Discord caps embed descriptions at 4096 characters.
+const MAX_REPORT_LENGTH: usize = 4000;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Rule {
+    channel_id: i64,
+    guild_id: i64,
+    max_age_hours: Option<i32>,
+    idle_hours: Option<i32>,
+    prefix: Option<String>,
+    lock: bool,
+    report_channel_id: Option<i64>,
+    last_report_at: Option<DateTime<Utc>>,
+}
+
+/// The parts of a thread a rule looks at.
+struct ThreadInfo<'a> {
+    name: &'a str,
+    created_at: DateTime<Utc>,
+    last_active_at: DateTime<Utc>,
+}
+
+impl<'a> ThreadInfo<'a> {
+    fn of(thread: &'a GuildChannel) -> Self {
+        let created_at = *thread
+            .thread_metadata
+            .and_then(|m| m.create_timestamp)
+            .unwrap_or_else(|| thread.id.created_at());
+        let last_active_at = thread
+            .last_message_id
+            .map_or(created_at, |m| *m.created_at());
+        Self {
+            name: &thread.name,
+            created_at,
+            last_active_at,
+        }
+    }
+}
+
+impl Rule {
+    fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    /// Why `thread` should be archived at `now`, if it should.
+    fn reason(&self, thread: &ThreadInfo, now: DateTime<Utc>) -> Option<String> {
+        let matches_prefix = self.prefix.as_deref().is_none_or(|prefix| {
+            thread
+                .name
+                .to_lowercase()
+                .starts_with(&prefix.to_lowercase())
+        });
+        if !matches_prefix {
+            return None;
+        }
+        if let Some(hours) = self.max_age_hours {
+            if now - thread.created_at >= Duration::hours(hours.into()) {
+                return Some(format!("older than {}", describe_hours(hours)));
+            }
+        }
+        if let Some(hours) = self.idle_hours {
+            if now - thread.last_active_at >= Duration::hours(hours.into()) {
+                return Some(format!("quiet for {}", describe_hours(hours)));
+            }
+        }
+        None
+    }
+
+    /// Where the weekly report goes: the channel it was asked for in, or the ruled channel.
+    fn report_channel(&self) -> ChannelId {
+        self.report_channel_id
+            .map_or(self.channel(), |c| ChannelId::new(c as u64))
+    }
+
+    fn describe(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(hours) = self.max_age_hours {
+            conditions.push(format!("older than {}", describe_hours(hours)));
+        }
+        if let Some(hours) = self.idle_hours {
+            conditions.push(format!("quiet for {}", describe_hours(hours)));
+        }
+        let mut description = format!(
+            "{}: threads {}",
+            self.channel().mention(),
+            conditions.join(" or ")
+        );
+        if let Some(prefix) = &self.prefix {
+            description.push_str(&format!(" starting with `{prefix}`"));
+        }
+        description.push_str(if self.lock {
+            " are archived and locked"
+        } else {
+            " are archived"
+        });
+        description.push_str(&format!(
+            ", reported weekly in {}",
+            self.report_channel().mention()
+        ));
+        description
+    }
+}
+
+fn describe_hours(hours: i32) -> String {
+    match hours {
+        1 => "an hour".to_string(),
+        h if h % 24 == 0 && h > 24 => format!("{} days", h / 24),
+        24 => "a day".to_string(),
+        h => format!("{h} hours"),
+    }
+}
+
+async fn load_rules(pool: &PgPool, guild_id: Option<GuildId>) -> Result<Vec<Rule>, SlimeError> {
+    Ok(sqlx::query_as::<_, Rule>(
+        "SELECT * FROM thread_janitor_rules
+         WHERE ($1::BIGINT IS NULL OR guild_id = $1)
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         ORDER BY channel_id",
+    )
+    .bind(guild_id.map(|id| id.get() as i64))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Archives the threads under each ruled channel that are due, and sends the weekly reports that
+/// are. Called by the scheduler every [`INTERVAL_MINUTES`].
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let mut by_guild = HashMap::<i64, Vec<Rule>>::new();
+    for rule in load_rules(pool, None).await? {
+        by_guild.entry(rule.guild_id).or_default().push(rule);
+    }
+
+    for (guild, rules) in by_guild {
+        let guild_id = GuildId::new(guild as u64);
+        data.calls.turn().await;
+        let threads = match guild_id.get_active_threads(ctx).await {
+            Ok(active) => active.threads,
+            Err(e) => {
+                error!("Could not list threads in guild {}: {}", guild_id, e);
+                continue;
+            }
+        };
+        for thread in &threads {
+            let Some(rule) = rules.iter().find(|r| Some(r.channel()) == thread.parent_id) else {
+                continue;
+            };
+            let Some(reason) = rule.reason(&ThreadInfo::of(thread), now) else {
+                continue;
+            };
+            if let Err(e) = archive(ctx, data, rule, thread, &reason).await {
+                error!("Could not archive thread {}: {}", thread.id, e);
+            }
+        }
+
+        for rule in &rules {
+            let due = rule
+                .last_report_at
+                .is_none_or(|at| now - at >= Duration::days(REPORT_DAYS));
+            if due {
+                if let Err(e) = report(ctx, data, rule, now).await {
+                    error!(
+                        "Could not report archived threads of {}: {}",
+                        rule.channel(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn archive(
+    ctx: &SerenityContext,
+    data: &Data,
+    rule: &Rule,
+    thread: &GuildChannel,
+    reason: &str,
+) -> Result<(), SlimeError> {
+    data.calls.turn().await;
+    thread
+        .id
+        .edit_thread(
+            ctx,
+            EditThread::new()
+                .archived(true)
+                .locked(rule.lock)
+                .audit_log_reason("Thread janitor"),
+        )
+        .await?;
+    sqlx::query(
+        "INSERT INTO thread_janitor_archived (thread_id, channel_id, guild_id, name, reason)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (thread_id) DO UPDATE SET reason = EXCLUDED.reason, archived_at = now()",
+    )
+    .bind(thread.id.get() as i64)
+    .bind(rule.channel_id)
+    .bind(rule.guild_id)
+    .bind(&thread.name)
+    .bind(reason)
+    .execute(&data.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Posts what was archived under the rule's channel since the last report, if anything was.
+async fn report(
+    ctx: &SerenityContext,
+    data: &Data,
+    rule: &Rule,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let archived = sqlx::query_as::<_, (i64, String, String)>(
+        "DELETE FROM thread_janitor_archived WHERE channel_id = $1
+         RETURNING thread_id, name, reason",
+    )
+    .bind(rule.channel_id)
+    .fetch_all(pool)
+    .await?;
+    sqlx::query("UPDATE thread_janitor_rules SET last_report_at = $2 WHERE channel_id = $1")
+        .bind(rule.channel_id)
+        .bind(now)
+        .execute(pool)
+        .await?;
+    if archived.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = String::new();
+    for (shown, (thread, name, reason)) in archived.iter().enumerate() {
+        let line = format!(
+            "{} **{name}**: {reason}\n",
+            ChannelId::new(*thread as u64).mention()
+        );
+        if lines.len() + line.len() > MAX_REPORT_LENGTH {
+            lines.push_str(&format!("…and {} more", archived.len() - shown));
+            break;
+        }
+        lines.push_str(&line);
+    }
+    data.calls.turn().await;
+    rule.report_channel()
+        .send_message(
+            ctx,
+            CreateMessage::new().embed(
+                CreateEmbed::new()
+                    .title(format!(
+                        "{} thread(s) archived in #{} this week",
+                        archived.len(),
+                        rule.channel()
+                            .name(ctx)
+                            .await
+                            .unwrap_or_else(|_| rule.channel().to_string())
+                    ))
+                    .description(lines),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Archive threads automatically once they're old or quiet.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_THREADS",
+    subcommands("set", "clear", "list")
+)]
+pub async fn janitor(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Set which threads under a channel are archived, replacing its rule if it has one.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_THREADS")]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel whose threads are looked after"]
+    #[channel_types("Text", "News", "Forum")]
+    channel: GuildChannel,
+    #[description = "Archive threads this many hours after they were made"]
+    #[min = 1]
+    older_than: Option<u32>,
+    #[description = "Archive threads nobody has written in for this many hours"]
+    #[min = 1]
+    idle_for: Option<u32>,
+    #[description = "Only look at threads whose names start with this"]
+    #[max_length = 100]
+    prefix: Option<String>,
+    #[description = "Lock threads too, so only moderators can open them again"] lock: Option<bool>,
+    #[description = "Channel to post a weekly list of archived threads in, if not this one"]
+    #[channel_types("Text")]
+    report_in: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    if older_than.is_none() && idle_for.is_none() {
+        return reply(
+            ctx,
+            "Give `older_than`, `idle_for` or both, so the janitor knows when threads are done.",
+        )
+        .await;
+    }
+
+    let rule = sqlx::query_as::<_, Rule>(
+        "INSERT INTO thread_janitor_rules
+            (channel_id, guild_id, max_age_hours, idle_hours, prefix, lock, report_channel_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         ON CONFLICT (channel_id) DO UPDATE SET
+            max_age_hours = EXCLUDED.max_age_hours, idle_hours = EXCLUDED.idle_hours,
+            prefix = EXCLUDED.prefix, lock = EXCLUDED.lock,
+            report_channel_id = EXCLUDED.report_channel_id
+         RETURNING *",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(older_than.map(|h| h as i32))
+    .bind(idle_for.map(|h| h as i32))
+    .bind(
+        prefix
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+    )
+    .bind(lock.unwrap_or(false))
+    .bind(report_in.map(|c| c.id.get() as i64))
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    reply(ctx, format!("From now on, under {}.", rule.describe())).await
+}
+
+/// Stop archiving threads under a channel.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_THREADS")]
+async fn clear(
+    ctx: Context<'_>,
+    #[description = "Channel to leave alone"]
+    #[channel_types("Text", "News", "Forum")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let removed =
+        sqlx::query("DELETE FROM thread_janitor_rules WHERE channel_id = $1 AND guild_id = $2")
+            .bind(channel.id.get() as i64)
+            .bind(guild_id.get() as i64)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Threads under {} will be left alone.", channel.mention())
+    } else {
+        format!("The janitor wasn't looking after {}.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Show which channels' threads are looked after.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_THREADS")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let rules = load_rules(&ctx.data().pool, Some(guild_id)).await?;
+
+    if rules.is_empty() {
+        return reply(ctx, "The janitor isn't looking after any channels.").await;
+    }
+    let lines = rules
+        .iter()
+        .map(|rule| format!("• {}", rule.describe()))
+        .collect::<Vec<_>>();
+    reply(ctx, lines.join("\n")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> Rule {
+        Rule {
+            channel_id: 1,
+            guild_id: 1,
+            max_age_hours: Some(72),
+            idle_hours: Some(24),
+            prefix: None,
+            lock: false,
+            report_channel_id: None,
+            last_report_at: None,
+        }
+    }
+
+    #[test]
+    fn threads_are_archived_by_age_inactivity_and_prefix() {
+        let now = Utc::now();
+        let thread = |name, age, idle| ThreadInfo {
+            name,
+            created_at: now - Duration::hours(age),
+            last_active_at: now - Duration::hours(idle),
+        };
+
+        assert_eq!(rule().reason(&thread("Raid night", 10, 2), now), None);
+        assert_eq!(
+            rule().reason(&thread("Raid night", 80, 2), now).as_deref(),
+            Some("older than 3 days")
+        );
+        assert_eq!(
+            rule().reason(&thread("Raid night", 30, 25), now).as_deref(),
+            Some("quiet for a day")
+        );
+
+        let lfg = Rule {
+            prefix: Some("LFG".to_string()),
+            ..rule()
+        };
+        assert_eq!(lfg.reason(&thread("Raid night", 80, 80), now), None);
+        assert!(lfg.reason(&thread("lfg: raid", 80, 80), now).is_some());
+
+        let idle_only = Rule {
+            max_age_hours: None,
+            ..rule()
+        };
+        assert_eq!(idle_only.reason(&thread("Old but busy", 500, 1), now), None);
+    }
+}
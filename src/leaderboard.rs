@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use poise::{serenity_prelude::*, ChoiceParameter};
+use sqlx::PgPool;
+
+use crate::{util::paginate, Context, SlimeError};
+
+/// How long a computed leaderboard is served before it's recomputed.
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// A member's ID and their value for a metric.
+type Score = (i64, i64);
+
+/// Scores along with when they were computed.
+type CachedScores = (Instant, Vec<Score>);
+
+const ENTRIES_PER_PAGE: usize = 10;
+const MAX_ENTRIES: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, poise::ChoiceParameter)]
+pub enum Metric {
+    #[name = "Events hosted"]
+    Hosted,
+    #[name = "Events attended"]
+    Attended,
+    #[name = "Points this season"]
+    Points,
+}
+
+impl Metric {
+    /// Member IDs and their score, highest first.
+    async fn compute(self, pool: &PgPool, guild_id: GuildId) -> Result<Vec<Score>, SlimeError> {
+        let query = match self {
+            Metric::Hosted => {
+                "SELECT host_id, COUNT(*) FROM events
+                 WHERE guild_id = $1 AND host_id > 0 AND status IN ('published', 'completed')
+                 GROUP BY host_id ORDER BY COUNT(*) DESC LIMIT $2"
+            }
+            Metric::Attended => {
+                "SELECT a.user_id, COUNT(*) FROM event_attendance a
+                 JOIN events e ON e.id = a.event_id
+                 WHERE e.guild_id = $1 AND a.user_id > 0
+                 GROUP BY a.user_id ORDER BY COUNT(*) DESC LIMIT $2"
+            }
+            Metric::Points => {
+                "SELECT user_id, SUM(amount)::BIGINT FROM points_ledger
+                 WHERE guild_id = $1 AND user_id > 0 AND season = (
+                    SELECT COALESCE(MAX(season), 1) FROM points_seasons WHERE guild_id = $1
+                 )
+                 GROUP BY user_id ORDER BY SUM(amount) DESC LIMIT $2"
+            }
+        };
+
+        Ok(sqlx::query_as::<_, Score>(query)
+            .bind(guild_id.get() as i64)
+            .bind(MAX_ENTRIES)
+            .fetch_all(pool)
+            .await?)
+    }
+}
+
+/// Recently computed leaderboards, so repeated invocations don't each hit the database.
+#[derive(Default)]
+pub struct LeaderboardCache {
+    entries: Mutex<HashMap<(GuildId, Metric), CachedScores>>,
+}
+
+impl LeaderboardCache {
+    async fn get(
+        &self,
+        pool: &PgPool,
+        guild_id: GuildId,
+        metric: Metric,
+    ) -> Result<Vec<Score>, SlimeError> {
+        let key = (guild_id, metric);
+        if let Some((computed_at, scores)) = self.entries.lock().unwrap().get(&key) {
+            if computed_at.elapsed() < CACHE_TTL {
+                return Ok(scores.clone());
+            }
+        }
+
+        let scores = metric.compute(pool, guild_id).await?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), scores.clone()));
+        Ok(scores)
+    }
+
+    /// Drops a cached leaderboard after its scores change, so the next view is up to date.
+    pub fn invalidate(&self, guild_id: GuildId, metric: Metric) {
+        self.entries.lock().unwrap().remove(&(guild_id, metric));
+    }
+}
+
+/// See who's most active in this server.
+#[poise::command(slash_command, guild_only)]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "What to rank members by"] metric: Metric,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let data = ctx.data();
+    let scores = data.leaderboards.get(&data.pool, guild_id, metric).await?;
+
+    let pages = if scores.is_empty() {
+        vec!["Nobody is on this leaderboard yet.".to_string()]
+    } else {
+        scores
+            .chunks(ENTRIES_PER_PAGE)
+            .enumerate()
+            .map(|(page, chunk)| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (user, score))| {
+                        format!(
+                            "**{}.** {} — {score}",
+                            page * ENTRIES_PER_PAGE + i + 1,
+                            UserId::new(*user as u64).mention()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect()
+    };
+
+    paginate(ctx, &format!("Leaderboard: {}", metric.name()), &pages).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn awarding_points_clears_only_that_leaderboard() {
+        let cache = LeaderboardCache::default();
+        let (guild, other) = (GuildId::new(1), GuildId::new(2));
+        for key in [
+            (guild, Metric::Points),
+            (guild, Metric::Hosted),
+            (other, Metric::Points),
+        ] {
+            cache
+                .entries
+                .lock()
+                .unwrap()
+                .insert(key, (Instant::now(), vec![(7, 10)]));
+        }
+
+        cache.invalidate(guild, Metric::Points);
+        let entries = cache.entries.lock().unwrap();
+        assert!(!entries.contains_key(&(guild, Metric::Points)));
+        assert!(entries.contains_key(&(guild, Metric::Hosted)));
+        assert!(entries.contains_key(&(other, Metric::Points)));
+    }
+}
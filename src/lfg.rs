@@ -0,0 +1,650 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    custom_id::{CustomId, Kind},
+    discord,
+    emoji::{self, Slot},
+    events::channels::{is_occupied, ATTENDEE_PERMISSIONS},
+    posts::{self, PostContent, PostKind},
+    settings::GuildSettings,
+    util::respond_ephemeral,
+    Context, Data, SlimeError,
+};
+
+/// How long a new group has for everyone to confirm before it falls apart.
+const CONFIRM_MINUTES: i64 = 5;
+
+/// How long a group's voice channel is kept once it's empty.
+const VOICE_GRACE_MINUTES: i64 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum GroupStatus {
+    /// Waiting for every member to press ready.
+    Forming,
+    Ready,
+    /// Someone didn't confirm in time, and everyone went back in the queue.
+    Expired,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Group {
+    id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    activity: String,
+    party_size: i32,
+    status: GroupStatus,
+    expires_at: DateTime<Utc>,
+}
+
+impl Group {
+    fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    /// Members, and whether each has confirmed.
+    async fn members(&self, pool: &PgPool) -> Result<Vec<(UserId, bool)>, SlimeError> {
+        let members = sqlx::query_as::<_, (i64, bool)>(
+            "SELECT user_id, ready FROM lfg_group_members WHERE group_id = $1 ORDER BY queued_at",
+        )
+        .bind(self.id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(members
+            .into_iter()
+            .map(|(id, ready)| (UserId::new(id as u64), ready))
+            .collect())
+    }
+
+    /// The group's post, with the guild's emoji. In plain text mode the roster says who's ready
+    /// in words as well.
+    fn embed(
+        &self,
+        members: &[(UserId, bool)],
+        voice: Option<ChannelId>,
+        settings: &GuildSettings,
+    ) -> CreateEmbed {
+        let roster = members
+            .iter()
+            .map(|(user, ready)| {
+                let (mark, word) = if *ready {
+                    (emoji::text(settings, Slot::Ready), "ready")
+                } else {
+                    (emoji::text(settings, Slot::NotReady), "not ready yet")
+                };
+                if settings.plain_text {
+                    format!("{mark} {} ({word})", user.mention())
+                } else {
+                    format!("{mark} {}", user.mention())
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let status = match (self.status, voice) {
+            (GroupStatus::Forming, _) => format!(
+                "Press **Ready** {}, or everyone goes back in the queue.",
+                FormattedTimestamp::new(
+                    self.expires_at.into(),
+                    Some(FormattedTimestampStyle::RelativeTime)
+                )
+            ),
+            (GroupStatus::Ready, Some(voice)) => {
+                format!("Everyone's in! Head to {}.", voice.mention())
+            }
+            (GroupStatus::Ready, None) => "Everyone's in, have fun!".to_string(),
+            (GroupStatus::Expired, _) => {
+                "Not everyone confirmed in time, so the group was put back in the queue."
+                    .to_string()
+            }
+        };
+
+        CreateEmbed::new()
+            .title(format!("Group found: {}", self.activity))
+            .description(format!("{roster}\n\n{status}"))
+    }
+
+    /// The group's post while it's forming, pinging everyone in it.
+    async fn forming_post(&self, pool: &PgPool) -> Result<PostContent, SlimeError> {
+        let members = self.members(pool).await?;
+        let settings = GuildSettings::load(pool, self.guild()).await?;
+        let mentions = members
+            .iter()
+            .map(|(user, _)| user.mention().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Ok(PostContent {
+            content: Some(mentions),
+            embed: self.embed(&members, None, &settings),
+            components: self.components(),
+        })
+    }
+
+    fn components(&self) -> Vec<CreateActionRow> {
+        if self.status != GroupStatus::Forming {
+            return vec![];
+        }
+        vec![CreateActionRow::Buttons(vec![CreateButton::new(
+            CustomId::new(Kind::Lfg, "ready", self.id),
+        )
+        .label("Ready")
+        .style(ButtonStyle::Success)])]
+    }
+}
+
+/// A group's post as it should look now, while it's still waiting for everyone to confirm.
+pub async fn render_post(pool: &PgPool, id: i64) -> Result<Option<PostContent>, SlimeError> {
+    let group = sqlx::query_as::<_, Group>("SELECT * FROM lfg_groups WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .filter(|g| g.status == GroupStatus::Forming);
+
+    match group {
+        Some(group) => Ok(Some(group.forming_post(pool).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Forms a group for the activity if enough members are queued for it, taking the longest
+/// waiting first, and posts it in `channel`.
+async fn try_match(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    activity: &str,
+    party_size: i32,
+    channel: ChannelId,
+) -> Result<Option<Group>, SlimeError> {
+    let mut tx = pool.begin().await?;
+    let queued = sqlx::query_as::<_, (i64, DateTime<Utc>, bool)>(
+        "SELECT user_id, queued_at, missed FROM lfg_queue
+         WHERE guild_id = $1 AND activity = $2 AND party_size = $3
+         ORDER BY queued_at LIMIT $3
+         FOR UPDATE",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(activity)
+    .bind(party_size)
+    .fetch_all(&mut *tx)
+    .await?;
+    if queued.len() < party_size as usize {
+        return Ok(None);
+    }
+
+    let mut group = sqlx::query_as::<_, Group>(
+        "INSERT INTO lfg_groups (guild_id, channel_id, activity, party_size, expires_at)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.get() as i64)
+    .bind(activity)
+    .bind(party_size)
+    .bind(Utc::now() + Duration::minutes(CONFIRM_MINUTES))
+    .fetch_one(&mut *tx)
+    .await?;
+    for (user_id, queued_at, missed) in &queued {
+        sqlx::query("DELETE FROM lfg_queue WHERE guild_id = $1 AND user_id = $2")
+            .bind(guild_id.get() as i64)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO lfg_group_members (group_id, user_id, queued_at, missed)
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(group.id)
+        .bind(user_id)
+        .bind(queued_at)
+        .bind(missed)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    let message = channel
+        .send_message(ctx, group.forming_post(pool).await?.create())
+        .await?;
+    sqlx::query("UPDATE lfg_groups SET message_id = $2 WHERE id = $1")
+        .bind(group.id)
+        .bind(message.id.get() as i64)
+        .execute(pool)
+        .await?;
+    group.message_id = Some(message.id.get() as i64);
+    posts::register(pool, PostKind::LfgGroup, group.id, guild_id, &message).await?;
+
+    Ok(Some(group))
+}
+
+/// A voice channel only the group can see, next to where the group was posted.
+async fn create_voice(
+    ctx: &SerenityContext,
+    group: &Group,
+    members: &[(UserId, bool)],
+) -> Result<ChannelId, SlimeError> {
+    let guild_id = group.guild();
+    let mut overwrites = vec![PermissionOverwrite {
+        allow: Permissions::empty(),
+        deny: Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+        kind: PermissionOverwriteType::Role(RoleId::new(guild_id.get())),
+    }];
+    let bot = ctx.cache.current_user().id;
+    for user in members.iter().map(|(user, _)| *user).chain([bot]) {
+        overwrites.push(PermissionOverwrite {
+            allow: ATTENDEE_PERMISSIONS,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(user),
+        });
+    }
+
+    let category = group
+        .channel()
+        .to_channel(ctx)
+        .await
+        .ok()
+        .and_then(Channel::guild)
+        .and_then(|c| c.parent_id);
+    let mut builder = CreateChannel::new(&group.activity)
+        .kind(ChannelType::Voice)
+        .permissions(overwrites)
+        .audit_log_reason("LFG group channel");
+    if let Some(category) = category {
+        builder = builder.category(category);
+    }
+
+    Ok(guild_id.create_channel(ctx, builder).await?.id)
+}
+
+/// Handles the ready button on group posts.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    if custom_id.action != "ready" {
+        return Ok(());
+    }
+    let group_id = custom_id.id;
+
+    let pool = &data.pool;
+    let group = sqlx::query_as::<_, Group>("SELECT * FROM lfg_groups WHERE id = $1")
+        .bind(group_id)
+        .fetch_optional(pool)
+        .await?
+        .filter(|g| g.status == GroupStatus::Forming);
+    let Some(mut group) = group else {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "This group has already formed or expired.",
+        )
+        .await;
+    };
+
+    let marked = sqlx::query(
+        "UPDATE lfg_group_members SET ready = true WHERE group_id = $1 AND user_id = $2",
+    )
+    .bind(group.id)
+    .bind(interaction.user.id.get() as i64)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if marked == 0 {
+        return respond_ephemeral(
+            ctx,
+            interaction,
+            "You're not in this group, use `/lfg join` to queue.",
+        )
+        .await;
+    }
+
+    let members = group.members(pool).await?;
+    let settings = GuildSettings::load(pool, group.guild()).await?;
+    let mut voice = None;
+    if members.iter().all(|(_, ready)| *ready) {
+        // Only one of the last presses gets to form the group, and not after it's expired.
+        let formed = sqlx::query(
+            "UPDATE lfg_groups SET status = 'ready' WHERE id = $1 AND status = 'forming'",
+        )
+        .bind(group.id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if formed == 0 {
+            return respond_ephemeral(
+                ctx,
+                interaction,
+                "This group has already formed or expired.",
+            )
+            .await;
+        }
+        group.status = GroupStatus::Ready;
+        if settings.lfg_voice {
+            voice = create_voice(ctx, &group, &members)
+                .await
+                .inspect_err(|e| {
+                    error!(
+                        "Could not create voice channel for LFG group {}: {}",
+                        group.id, e
+                    )
+                })
+                .ok();
+        }
+        sqlx::query("UPDATE lfg_groups SET voice_channel_id = $2 WHERE id = $1")
+            .bind(group.id)
+            .bind(voice.map(|v| v.get() as i64))
+            .execute(pool)
+            .await?;
+    }
+
+    discord::respond(
+        ctx,
+        interaction,
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(group.embed(&members, voice, &settings))
+                .components(group.components()),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// A group member as they were queued, and whether they confirmed.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Queued {
+    user_id: i64,
+    queued_at: DateTime<Utc>,
+    ready: bool,
+    missed: bool,
+}
+
+/// Who goes back in the queue when a group expires at `now`, and when each counts as queued
+/// from. Members who confirmed keep their place; the rest go to the back, or are dropped if
+/// they'd already missed a group before.
+fn requeue(members: &[Queued], now: DateTime<Utc>) -> Vec<Queued> {
+    members
+        .iter()
+        .filter(|m| m.ready || !m.missed)
+        .map(|m| Queued {
+            queued_at: if m.ready { m.queued_at } else { now },
+            missed: m.missed || !m.ready,
+            ready: false,
+            ..m.clone()
+        })
+        .collect()
+}
+
+/// Breaks up a group that didn't confirm in time, putting its members back in the queue as
+/// [`requeue`] says.
+async fn expire(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    mut group: Group,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let members = group.members(pool).await?;
+
+    let mut tx = pool.begin().await?;
+    let expired = sqlx::query(
+        "UPDATE lfg_groups SET status = 'expired' WHERE id = $1 AND status = 'forming'",
+    )
+    .bind(group.id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if expired == 0 {
+        // Everyone confirmed just in time.
+        return Ok(());
+    }
+    let queued = sqlx::query_as::<_, Queued>(
+        "SELECT user_id, queued_at, ready, missed FROM lfg_group_members WHERE group_id = $1",
+    )
+    .bind(group.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    let back = requeue(&queued, now);
+    sqlx::query(
+        "INSERT INTO lfg_queue (guild_id, user_id, activity, party_size, channel_id, queued_at, missed)
+         SELECT $1, user_id, $2, $3, $4, queued_at, missed
+         FROM UNNEST($5::BIGINT[], $6::TIMESTAMPTZ[], $7::BOOLEAN[]) AS r(user_id, queued_at, missed)
+         ON CONFLICT (guild_id, user_id) DO NOTHING",
+    )
+    .bind(group.guild_id)
+    .bind(&group.activity)
+    .bind(group.party_size)
+    .bind(group.channel_id)
+    .bind(back.iter().map(|m| m.user_id).collect::<Vec<_>>())
+    .bind(back.iter().map(|m| m.queued_at).collect::<Vec<_>>())
+    .bind(back.iter().map(|m| m.missed).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    group.status = GroupStatus::Expired;
+    let settings = GuildSettings::load(pool, group.guild()).await?;
+    if let Some(message_id) = group.message_id {
+        if let Err(e) = group
+            .channel()
+            .edit_message(
+                ctx,
+                MessageId::new(message_id as u64),
+                EditMessage::new()
+                    .embed(group.embed(&members, None, &settings))
+                    .components(vec![]),
+            )
+            .await
+        {
+            error!("Could not update expired LFG group {}: {}", group.id, e);
+        }
+    }
+
+    try_match(
+        ctx,
+        pool,
+        group.guild(),
+        &group.activity,
+        group.party_size,
+        group.channel(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Expires groups that weren't confirmed in time, and removes group voice channels once
+/// they've emptied out. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+
+    let expired = sqlx::query_as::<_, Group>(
+        "SELECT * FROM lfg_groups WHERE status = 'forming' AND expires_at <= $1
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    for group in expired {
+        let id = group.id;
+        if let Err(e) = expire(ctx, pool, group, now).await {
+            error!("Could not expire LFG group {}: {}", id, e);
+        }
+    }
+
+    let voice = sqlx::query_as::<_, (i64, i64, i64)>(
+        "SELECT id, guild_id, voice_channel_id FROM lfg_groups
+         WHERE status = 'ready' AND voice_channel_id IS NOT NULL AND created_at <= $1
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now - Duration::minutes(VOICE_GRACE_MINUTES))
+    .fetch_all(pool)
+    .await?;
+    for (id, guild_id, channel_id) in voice {
+        let channel = ChannelId::new(channel_id as u64);
+        if is_occupied(ctx, GuildId::new(guild_id as u64), channel) {
+            continue;
+        }
+        if let Err(e) = channel.delete(ctx).await {
+            error!("Could not remove voice channel for LFG group {}: {}", id, e);
+        }
+        sqlx::query("UPDATE lfg_groups SET voice_channel_id = NULL WHERE id = $1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Find other members to play with.
+#[poise::command(slash_command, guild_only, subcommands("join", "leave"))]
+pub async fn lfg(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Queue up for an activity. You'll be grouped once enough people want the same thing.
+#[poise::command(slash_command, guild_only)]
+async fn join(
+    ctx: Context<'_>,
+    #[description = "What you want to do"]
+    #[max_length = 50]
+    activity: String,
+    #[description = "How many people, including you"]
+    #[min = 2]
+    #[max = 25]
+    party_size: u32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let activity = activity.trim().to_lowercase();
+    let party_size = party_size as i32;
+
+    sqlx::query(
+        "INSERT INTO lfg_queue (guild_id, user_id, activity, party_size, channel_id)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (guild_id, user_id) DO UPDATE SET
+            activity = EXCLUDED.activity, party_size = EXCLUDED.party_size,
+            channel_id = EXCLUDED.channel_id, queued_at = now(), missed = false",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&activity)
+    .bind(party_size)
+    .bind(ctx.channel_id().get() as i64)
+    .execute(pool)
+    .await?;
+
+    let content = match try_match(
+        ctx.serenity_context(),
+        pool,
+        guild_id,
+        &activity,
+        party_size,
+        ctx.channel_id(),
+    )
+    .await?
+    {
+        Some(_) => "Group found! Press **Ready** on the post to confirm.".to_string(),
+        None => {
+            let waiting = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM lfg_queue WHERE guild_id = $1 AND activity = $2 AND party_size = $3",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(&activity)
+            .bind(party_size)
+            .fetch_one(pool)
+            .await?;
+            format!("You're queued for **{activity}** ({waiting} of {party_size} so far).")
+        }
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Stop looking for a group.
+#[poise::command(slash_command, guild_only)]
+async fn leave(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    let left = sqlx::query("DELETE FROM lfg_queue WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id.get() as i64)
+        .bind(ctx.author().id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if left > 0 {
+        "You've left the queue."
+    } else {
+        "You weren't queued for anything."
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn only_those_who_confirmed_keep_their_place() {
+        let at = |minute| Utc.with_ymd_and_hms(2024, 3, 1, 19, minute, 0).unwrap();
+        let member = |user_id, ready, missed| Queued {
+            user_id,
+            queued_at: at(user_id as u32),
+            ready,
+            missed,
+        };
+        let now = at(30);
+        let back = requeue(
+            &[
+                member(1, true, false),
+                member(2, false, false),
+                member(3, false, true),
+                member(4, true, true),
+            ],
+            now,
+        );
+
+        let back = back
+            .iter()
+            .map(|m| (m.user_id, m.queued_at, m.missed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            back,
+            [
+                // Confirmed, so still ahead of anyone who queued after them.
+                (1, at(1), false),
+                // Didn't confirm, so to the back, and out if it happens again. Member 3 already
+                // had, so they're dropped.
+                (2, now, true),
+                // Confirmed this time, keeping both their place and the earlier miss.
+                (4, at(4), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,506 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::{client::Context as SerenityContext, json::Value};
+use tracing::{error, warn};
+
+use crate::{
+    audit::{self, AuditEntry},
+    settings::GuildSettings,
+    templates,
+    util::query_encode,
+    Context, Data, SlimeError,
+};
+
+/// Variables `LINK_REPUTATION_API_URL` may use.
+pub const URL_VARIABLES: &[&str] = &["url", "domain"];
+
+/// How long the reputation API's verdict on a domain is reused.
+const CACHE_HOURS: i64 = 24;
+
+/// What happens to a message with a bad link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum LinkAction {
+    /// Taken down, with a note in the audit channel.
+    #[default]
+    #[name = "Delete it"]
+    Delete,
+    /// Left up, with a warning reaction and a note in the audit channel.
+    #[name = "Flag it"]
+    Flag,
+}
+
+impl LinkAction {
+    /// What's done to a message, as a past participle.
+    fn describe(self) -> &'static str {
+        match self {
+            LinkAction::Delete => "deleted",
+            LinkAction::Flag => "flagged",
+        }
+    }
+}
+
+/// The links in `text`, each with its domain: lowercase, without `www.`, a port or a login.
+fn urls(text: &str) -> Vec<(&str, String)> {
+    text.split(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        .filter_map(|word| {
+            let start = word.find("https://").or_else(|| word.find("http://"))?;
+            let url = word[start..].trim_end_matches(|c: char| ".,;:!?)]*_~|'\"".contains(c));
+            let host = url.split_once("://")?.1;
+            let host = host.split(['/', '?', '#']).next()?;
+            let host = host.rsplit('@').next()?;
+            let host = host.split(':').next()?.trim_end_matches('.').to_lowercase();
+            let domain = host.strip_prefix("www.").unwrap_or(&host).to_string();
+            domain.contains('.').then_some((url, domain))
+        })
+        .collect()
+}
+
+/// A domain as a rule is stored: what [`urls`] would make of it.
+fn normalize(domain: &str) -> Option<String> {
+    let domain = domain.trim();
+    let url = if domain.contains("://") {
+        domain.to_string()
+    } else {
+        format!("https://{domain}")
+    };
+    urls(&url).into_iter().next().map(|(_, domain)| domain)
+}
+
+/// Whether a rule for `rule` covers `domain`, being it or one of its subdomains.
+fn covers(rule: &str, domain: &str) -> bool {
+    domain == rule
+        || domain
+            .strip_suffix(rule)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Reads the verdict out of a reputation API response: a JSON object with a boolean `malicious`.
+fn parse_verdict(body: &str) -> Option<bool> {
+    serenity::json::from_str::<Value>(body).ok()?["malicious"].as_bool()
+}
+
+/// Asks the reputation API about `url`. Failures are logged and come back as `None`.
+async fn fetch(client: &reqwest::Client, template: &str, url: &str, domain: &str) -> Option<bool> {
+    let request = templates::render(
+        template,
+        &[
+            ("url", &query_encode(url)),
+            ("domain", &query_encode(domain)),
+        ],
+    );
+    let response = client
+        .get(request)
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    let body = match response {
+        Ok(response) => response.text().await,
+        Err(e) => Err(e),
+    };
+    match body {
+        Ok(body) => {
+            let verdict = parse_verdict(&body);
+            if verdict.is_none() {
+                warn!("Link reputation API had no verdict on {}", domain);
+            }
+            verdict
+        }
+        Err(e) => {
+            error!(
+                "Could not check {} with the link reputation API: {}",
+                domain, e
+            );
+            None
+        }
+    }
+}
+
+/// Whether the reputation API thinks `domain` is malicious, from the cache while it's fresh.
+/// Unknown without an API, or when it couldn't say.
+async fn reputation(
+    data: &Data,
+    url: &str,
+    domain: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<bool>, SlimeError> {
+    let Some(template) = &data.config.link_reputation_api else {
+        return Ok(None);
+    };
+    let pool = &data.pool;
+    let cached = sqlx::query_scalar::<_, Option<bool>>(
+        "SELECT malicious FROM link_reputation WHERE domain = $1 AND checked_at > $2",
+    )
+    .bind(domain)
+    .bind(now - Duration::hours(CACHE_HOURS))
+    .fetch_optional(pool)
+    .await?;
+    if let Some(verdict) = cached {
+        return Ok(verdict);
+    }
+
+    let verdict = fetch(&data.http, template, url, domain).await;
+    sqlx::query(
+        "INSERT INTO link_reputation (domain, malicious, checked_at) VALUES ($1, $2, $3)
+         ON CONFLICT (domain)
+            DO UPDATE SET malicious = EXCLUDED.malicious, checked_at = EXCLUDED.checked_at",
+    )
+    .bind(domain)
+    .bind(verdict)
+    .bind(now)
+    .execute(pool)
+    .await?;
+
+    Ok(verdict)
+}
+
+/// Checks the links in `message` if it's in a watched channel, and deletes or flags it for the
+/// first bad one: one on the guild's deny-list, or one the reputation API calls malicious.
+/// Allowed domains are never looked up. Returns whether the message was deleted, so nothing
+/// else answers it. Only runs with the guild's consent to reading message content.
+pub async fn noticed(
+    ctx: &SerenityContext,
+    data: &Data,
+    message: &Message,
+) -> Result<bool, SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(false);
+    };
+    let pool = &data.pool;
+    let Some(action) = sqlx::query_scalar::<_, LinkAction>(
+        "SELECT action FROM link_channels
+         WHERE channel_id = $1 AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(message.channel_id.get() as i64)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(false);
+    };
+    let found = urls(&message.content);
+    if found.is_empty()
+        || !GuildSettings::load(pool, guild_id)
+            .await?
+            .message_content_consent
+    {
+        return Ok(false);
+    }
+
+    let rules = sqlx::query_as::<_, (String, bool)>(
+        "SELECT domain, allowed FROM link_rules WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let now = data.clock.now();
+    let mut bad = None;
+    for (url, domain) in &found {
+        // The most specific rule wins, so a deny-listed subdomain of an allowed one is caught.
+        let rule = rules
+            .iter()
+            .filter(|(rule, _)| covers(rule, domain))
+            .max_by_key(|(rule, _)| rule.len());
+        let reason = match rule {
+            Some((_, true)) => continue,
+            Some((rule, false)) => format!("`{rule}` is on this server's deny-list"),
+            None => match reputation(data, url, domain, now).await? {
+                Some(true) => format!("`{domain}` is known to be malicious"),
+                _ => continue,
+            },
+        };
+        bad = Some((domain, reason));
+        break;
+    }
+    let Some((domain, reason)) = bad else {
+        return Ok(false);
+    };
+
+    let deleted = action == LinkAction::Delete;
+    if deleted {
+        message.delete(ctx).await?;
+    } else {
+        message.react(ctx, '⚠').await?;
+    }
+    let summary = format!(
+        "{} a link from {} in {}: {reason}.{}",
+        if deleted { "Deleted" } else { "Flagged" },
+        message.author.mention(),
+        message.channel_id.mention(),
+        if deleted {
+            String::new()
+        } else {
+            format!(" [Jump to it]({})", message.link())
+        }
+    );
+    let bot = ctx.cache.current_user().id;
+    audit::record_and_post(
+        ctx,
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: bot,
+            action: if deleted {
+                "link_deleted"
+            } else {
+                "link_flagged"
+            },
+            target: Some(message.id.get()),
+            details: format!(
+                "{domain} from {} in {}",
+                message.author.id, message.channel_id
+            ),
+            undo: Vec::new(),
+        },
+        &summary,
+    )
+    .await?;
+
+    Ok(deleted)
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Check links posted in channels against a deny-list and a reputation service.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("watch", "unwatch", "deny", "allow", "forget", "list")
+)]
+pub async fn links(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Check links in a channel, or change what happens to bad ones there.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn watch(
+    ctx: Context<'_>,
+    #[description = "Channel whose links are checked"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "What to do with a message with a bad link (default delete it)"] action: Option<
+        LinkAction,
+    >,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let action = action.unwrap_or_default();
+    sqlx::query(
+        "INSERT INTO link_channels (channel_id, guild_id, action) VALUES ($1, $2, $3)
+         ON CONFLICT (channel_id) DO UPDATE SET action = EXCLUDED.action",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(action)
+    .execute(pool)
+    .await?;
+
+    let mut content = format!(
+        "Links in {} are checked, and messages with bad ones are {}.",
+        channel.mention(),
+        action.describe()
+    );
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        content.push_str(
+            "\nMessages are only read once message content features are allowed with \
+             `/settings message_content`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// Stop checking links in a channel.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn unwatch(
+    ctx: Context<'_>,
+    #[description = "Channel to stop checking"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let removed = sqlx::query("DELETE FROM link_channels WHERE channel_id = $1 AND guild_id = $2")
+        .bind(channel.id.get() as i64)
+        .bind(guild_id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Links in {} are no longer checked.", channel.mention())
+    } else {
+        format!("Links in {} weren't being checked.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+async fn set_rule(ctx: Context<'_>, domain: &str, allowed: bool) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let Some(domain) = normalize(domain) else {
+        return reply(ctx, format!("`{domain}` doesn't look like a domain.")).await;
+    };
+    sqlx::query(
+        "INSERT INTO link_rules (guild_id, domain, allowed, created_by) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id, domain)
+            DO UPDATE SET allowed = EXCLUDED.allowed, created_by = EXCLUDED.created_by",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&domain)
+    .bind(allowed)
+    .bind(ctx.author().id.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let content = if allowed {
+        format!("Links to `{domain}` and its subdomains are always allowed.")
+    } else {
+        format!("Links to `{domain}` and its subdomains are treated as bad.")
+    };
+    reply(ctx, content).await
+}
+
+/// Treat links to a domain as bad.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn deny(
+    ctx: Context<'_>,
+    #[description = "Domain, like free-nitro.example"]
+    #[max_length = 253]
+    domain: String,
+) -> Result<(), SlimeError> {
+    set_rule(ctx, &domain, false).await
+}
+
+/// Always allow links to a domain, like the community's own sites.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn allow(
+    ctx: Context<'_>,
+    #[description = "Domain, like pond.example"]
+    #[max_length = 253]
+    domain: String,
+) -> Result<(), SlimeError> {
+    set_rule(ctx, &domain, true).await
+}
+
+/// Take a domain off the deny-list or the allowlist.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn forget(
+    ctx: Context<'_>,
+    #[description = "Domain to take off"]
+    #[max_length = 253]
+    domain: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let domain = normalize(&domain).unwrap_or(domain);
+    let removed = sqlx::query("DELETE FROM link_rules WHERE guild_id = $1 AND domain = $2")
+        .bind(guild_id.get() as i64)
+        .bind(&domain)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("`{domain}` is off both lists.")
+    } else {
+        format!("`{domain}` isn't on either list.")
+    };
+    reply(ctx, content).await
+}
+
+/// See which channels are checked, and the server's deny-list and allowlist.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let channels = sqlx::query_as::<_, (i64, LinkAction)>(
+        "SELECT channel_id, action FROM link_channels WHERE guild_id = $1 ORDER BY channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let rules = sqlx::query_as::<_, (String, bool)>(
+        "SELECT domain, allowed FROM link_rules WHERE guild_id = $1 ORDER BY domain",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+
+    let channels = if channels.is_empty() {
+        "No channels are checked.".to_string()
+    } else {
+        channels
+            .iter()
+            .map(|(channel, action)| {
+                format!(
+                    "{} ({})",
+                    ChannelId::new(*channel as u64).mention(),
+                    action.describe()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let domains = |allowed: bool| {
+        let domains = rules
+            .iter()
+            .filter(|(_, a)| *a == allowed)
+            .map(|(d, _)| format!("`{d}`"))
+            .collect::<Vec<_>>();
+        if domains.is_empty() {
+            "none".to_string()
+        } else {
+            domains.join(", ")
+        }
+    };
+    reply(
+        ctx,
+        format!(
+            "**Checked:** {channels}\n**Denied:** {}\n**Allowed:** {}",
+            domains(false),
+            domains(true)
+        ),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_are_read_down_to_their_domain() {
+        let domains = |text| {
+            urls(text)
+                .into_iter()
+                .map(|(_, domain)| domain)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            domains(
+                "grab it at <https://WWW.Free-Nitro.example/claim?x=1>, or \
+                 (http://user@gift.example:8080/path) today!"
+            ),
+            vec!["free-nitro.example", "gift.example"]
+        );
+        assert_eq!(
+            domains("no links, just https:// and localhost"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            normalize(" Pond.Example/wiki "),
+            Some("pond.example".to_string())
+        );
+
+        assert!(covers("pond.example", "pond.example"));
+        assert!(covers("pond.example", "wiki.pond.example"));
+        assert!(!covers("pond.example", "notpond.example"));
+        assert_eq!(parse_verdict(r#"{"malicious": true}"#), Some(true));
+        assert_eq!(parse_verdict("{}"), None);
+    }
+}
@@ -0,0 +1,512 @@
+use chrono::{Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    events::{Event, EventStatus, NewEvent},
+    quotas,
+    settings::GuildSettings,
+    tags,
+    undo::UndoStep,
+    util::rehearse,
+    Context, SlimeError,
+};
+
+/// Something a macro can do. Each one can be undone if a later step fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum StepKind {
+    /// Send `text` in the channel.
+    #[name = "Post a message"]
+    Post,
+    /// Send the tag named `text` in the channel.
+    #[name = "Send a tag"]
+    Tag,
+    /// Mention the role in the channel, with `text` if given.
+    #[name = "Ping a role"]
+    Ping,
+    /// Publish an event titled `text`, starting `amount` minutes after the macro runs.
+    #[name = "Create an event"]
+    Event,
+    /// Let the role (everyone by default) see and talk in the channel.
+    #[name = "Open a channel"]
+    OpenChannel,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Step {
+    position: i32,
+    kind: StepKind,
+    channel_id: Option<i64>,
+    role_id: Option<i64>,
+    text: Option<String>,
+    amount: Option<i32>,
+}
+
+impl Step {
+    fn channel(&self) -> Option<ChannelId> {
+        self.channel_id.map(|id| ChannelId::new(id as u64))
+    }
+
+    fn role(&self) -> Option<RoleId> {
+        self.role_id.map(|id| RoleId::new(id as u64))
+    }
+
+    /// Why the step can't run, if it's missing something its kind needs.
+    fn problem(&self) -> Option<&'static str> {
+        match self.kind {
+            StepKind::Post | StepKind::Tag if self.text.is_none() => Some("needs some text"),
+            StepKind::Ping if self.role_id.is_none() => Some("needs a role"),
+            StepKind::Event if self.text.is_none() => Some("needs a title as its text"),
+            StepKind::OpenChannel if self.channel_id.is_none() => Some("needs a channel"),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let channel = self.channel().map_or_else(
+            || "the current channel".to_string(),
+            |c| c.mention().to_string(),
+        );
+        let text = self.text.as_deref().unwrap_or_default();
+        match self.kind {
+            StepKind::Post => format!("post \"{text}\" in {channel}"),
+            StepKind::Tag => format!("send tag `{text}` in {channel}"),
+            StepKind::Ping => format!(
+                "ping {} in {channel}",
+                self.role()
+                    .map(|r| r.mention().to_string())
+                    .unwrap_or_default()
+            ),
+            StepKind::Event => format!(
+                "create event **{text}** starting {} minute(s) later",
+                self.amount.unwrap_or(0)
+            ),
+            StepKind::OpenChannel => format!(
+                "open {channel} to {}",
+                self.role()
+                    .map_or_else(|| "everyone".to_string(), |r| r.mention().to_string())
+            ),
+        }
+    }
+}
+
+/// Where a macro is being run from.
+struct Invocation<'a> {
+    guild_id: GuildId,
+    channel: ChannelId,
+    user: &'a User,
+}
+
+async fn run_step(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    at: &Invocation<'_>,
+    step: &Step,
+) -> Result<UndoStep, SlimeError> {
+    let channel = step.channel().unwrap_or(at.channel);
+    let text = step.text.clone().unwrap_or_default();
+
+    match step.kind {
+        StepKind::Post => {
+            let message = channel
+                .send_message(ctx, CreateMessage::new().content(text))
+                .await?;
+            Ok(UndoStep::DeleteMessage(channel, message.id))
+        }
+        StepKind::Tag => {
+            let message = tags::post(ctx, pool, at.guild_id, &text, at.user, channel)
+                .await?
+                .ok_or(SlimeError::MissingSetting("tag with that name"))?;
+            Ok(UndoStep::DeleteMessage(channel, message.id))
+        }
+        StepKind::Ping => {
+            let role = step.role().ok_or(SlimeError::MissingSetting("role"))?;
+            let message = channel
+                .send_message(
+                    ctx,
+                    CreateMessage::new()
+                        .content(format!("{} {text}", role.mention()))
+                        .allowed_mentions(CreateAllowedMentions::new().roles(vec![role])),
+                )
+                .await?;
+            Ok(UndoStep::DeleteMessage(channel, message.id))
+        }
+        StepKind::Event => {
+            quotas::ensure_events(pool, at.guild_id, 1).await?;
+            let settings = GuildSettings::load(pool, at.guild_id).await?;
+            let new = NewEvent {
+                guild_id: at.guild_id,
+                channel_id: step
+                    .channel()
+                    .or_else(|| settings.events_channel())
+                    .unwrap_or(at.channel),
+                host_id: at.user.id,
+                title: text,
+                description: String::new(),
+                starts_at: Utc::now() + Duration::minutes(step.amount.unwrap_or(0).into()),
+                duration_minutes: 60,
+                capacity: None,
+                tags: Vec::new(),
+            };
+            let mut event = Event::insert(pool, new, EventStatus::Draft).await?;
+            let id = event.id;
+            if let Err(e) = event.publish(ctx, pool).await {
+                event.withdraw(ctx, pool).await?;
+                return Err(e);
+            }
+            Ok(UndoStep::WithdrawEvent(id))
+        }
+        StepKind::OpenChannel => {
+            let role = step.role().unwrap_or(RoleId::new(at.guild_id.get()));
+            let current = channel
+                .to_channel(ctx)
+                .await?
+                .guild()
+                .ok_or(SlimeError::MissingSetting("server channel"))?;
+            let previous = current
+                .permission_overwrites
+                .iter()
+                .find(|o| o.kind == PermissionOverwriteType::Role(role))
+                .cloned();
+
+            let opened = Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES;
+            let (allow, deny) = previous
+                .as_ref()
+                .map_or((opened, Permissions::empty()), |p| {
+                    (p.allow | opened, p.deny - opened)
+                });
+            channel
+                .create_permission(
+                    ctx,
+                    PermissionOverwrite {
+                        allow,
+                        deny,
+                        kind: PermissionOverwriteType::Role(role),
+                    },
+                )
+                .await?;
+            Ok(UndoStep::Overwrite(
+                channel,
+                PermissionOverwriteType::Role(role),
+                previous.map(|p| (p.allow, p.deny)),
+            ))
+        }
+    }
+}
+
+async fn load_steps(pool: &PgPool, guild_id: GuildId, name: &str) -> Result<Vec<Step>, SlimeError> {
+    Ok(sqlx::query_as::<_, Step>(
+        "SELECT position, kind, channel_id, role_id, text, amount FROM macro_steps
+         WHERE guild_id = $1 AND name = $2 ORDER BY position",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(name)
+    .fetch_all(pool)
+    .await?)
+}
+
+async fn exists(pool: &PgPool, guild_id: GuildId, name: &str) -> Result<bool, SlimeError> {
+    Ok(sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM macros WHERE guild_id = $1 AND name = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(name)
+    .fetch_one(pool)
+    .await?
+        > 0)
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Run several bot actions at once.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "macro",
+    subcommands("create", "add_step", "show", "delete", "run")
+)]
+pub async fn macro_command(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Start a new, empty macro.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Macro name"]
+    #[max_length = 32]
+    name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = name.trim().to_lowercase();
+
+    let created = sqlx::query(
+        "INSERT INTO macros (guild_id, name, created_by) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&name)
+    .bind(ctx.author().id.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    if created == 0 {
+        reply(ctx, format!("There's already a macro called **{name}**.")).await
+    } else {
+        reply(
+            ctx,
+            format!("Created **{name}**, give it steps with `/macro add_step`."),
+        )
+        .await
+    }
+}
+
+/// Add a step to the end of a macro.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn add_step(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+    #[description = "What the step does"] kind: StepKind,
+    #[description = "Channel it acts on, defaults to wherever the macro runs"] channel: Option<
+        GuildChannel,
+    >,
+    #[description = "Role to ping or open the channel to"] role: Option<Role>,
+    #[description = "Message, tag name or event title"]
+    #[max_length = 1000]
+    text: Option<String>,
+    #[description = "For events, minutes from running the macro until it starts"]
+    #[min = 0]
+    minutes: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let name = name.trim().to_lowercase();
+    if !exists(pool, guild_id, &name).await? {
+        return reply(ctx, format!("There's no macro called **{name}**.")).await;
+    }
+
+    let step = Step {
+        position: 0,
+        kind,
+        channel_id: channel.map(|c| c.id.get() as i64),
+        role_id: role.map(|r| r.id.get() as i64),
+        text,
+        amount: minutes.map(|m| m as i32),
+    };
+    if let Some(problem) = step.problem() {
+        return reply(ctx, format!("That step {problem}.")).await;
+    }
+
+    sqlx::query(
+        "INSERT INTO macro_steps (guild_id, name, position, kind, channel_id, role_id, text, amount)
+         SELECT $1, $2, COALESCE(MAX(position) + 1, 0), $3, $4, $5, $6, $7
+         FROM macro_steps WHERE guild_id = $1 AND name = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&name)
+    .bind(step.kind)
+    .bind(step.channel_id)
+    .bind(step.role_id)
+    .bind(&step.text)
+    .bind(step.amount)
+    .execute(pool)
+    .await?;
+
+    reply(ctx, format!("Added to **{name}**: {}.", step.describe())).await
+}
+
+/// See what a macro does.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = name.trim().to_lowercase();
+    let steps = load_steps(&ctx.data().pool, guild_id, &name).await?;
+
+    let content = if steps.is_empty() {
+        format!("**{name}** doesn't exist or has no steps yet.")
+    } else {
+        let list = steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| format!("{}. {}", i + 1, step.describe()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("**{name}** will:\n{list}")
+    };
+    reply(ctx, content).await
+}
+
+/// Delete a macro.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = name.trim().to_lowercase();
+
+    let mut tx = ctx.data().pool.begin().await?;
+    let deleted = sqlx::query("DELETE FROM macros WHERE guild_id = $1 AND name = $2")
+        .bind(guild_id.get() as i64)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        reply(ctx, format!("There's no macro called **{name}**.")).await
+    } else if rehearse(ctx, &format!("deleted the macro **{name}**.")).await? {
+        Ok(())
+    } else {
+        tx.commit().await?;
+        reply(ctx, format!("Deleted **{name}**.")).await
+    }
+}
+
+/// Run every step of a macro in order. If one fails, the steps before it are undone.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn run(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = name.trim().to_lowercase();
+    let pool = &ctx.data().pool;
+    let steps = load_steps(pool, guild_id, &name).await?;
+    if steps.is_empty() {
+        return reply(
+            ctx,
+            format!("**{name}** doesn't exist or has no steps yet."),
+        )
+        .await;
+    }
+    let plan = steps
+        .iter()
+        .map(|step| format!("\n- {}", step.describe()))
+        .collect::<String>();
+    if rehearse(ctx, &format!("run **{name}**, which would:{plan}")).await? {
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+
+    let at = Invocation {
+        guild_id,
+        channel: ctx.channel_id(),
+        user: ctx.author(),
+    };
+    let serenity_ctx = ctx.serenity_context();
+    let mut done = Vec::new();
+    let mut failure = None;
+    for step in &steps {
+        match run_step(serenity_ctx, pool, &at, step).await {
+            Ok(result) => done.push(result),
+            Err(e) => {
+                failure = Some((step, e));
+                break;
+            }
+        }
+    }
+
+    // A successful run can be taken back later with `/undo`; a failed one is rolled back now.
+    let (content, undo) = match failure {
+        None => (
+            format!("Ran all {} step(s) of **{name}**.", steps.len()),
+            done,
+        ),
+        Some((step, e)) => {
+            let completed = done.len();
+            let mut undo_failed = 0;
+            for result in done.iter().rev() {
+                if let Err(e) = result.apply(serenity_ctx, pool, guild_id).await {
+                    error!("Could not undo macro step `{}`: {}", result, e);
+                    undo_failed += 1;
+                }
+            }
+            let mut content = format!(
+                "Step {} ({}) failed: {e}\nUndid the {completed} step(s) before it",
+                step.position + 1,
+                step.describe()
+            );
+            if undo_failed > 0 {
+                content.push_str(&format!(", but {undo_failed} couldn't be undone"));
+            }
+            content.push('.');
+            (content, Vec::new())
+        }
+    };
+
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "macro_run",
+            target: None,
+            details: format!("{name}: {content}"),
+            undo,
+        },
+    )
+    .await?;
+    reply(ctx, content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(kind: StepKind) -> Step {
+        Step {
+            position: 1,
+            kind,
+            channel_id: None,
+            role_id: None,
+            text: None,
+            amount: None,
+        }
+    }
+
+    #[test]
+    fn steps_need_what_their_kind_uses() {
+        assert_eq!(step(StepKind::Post).problem(), Some("needs some text"));
+        assert_eq!(step(StepKind::Ping).problem(), Some("needs a role"));
+        assert_eq!(
+            step(StepKind::OpenChannel).problem(),
+            Some("needs a channel")
+        );
+        let post = Step {
+            text: Some("Doors open!".to_string()),
+            ..step(StepKind::Post)
+        };
+        assert_eq!(post.problem(), None);
+        assert_eq!(
+            post.describe(),
+            "post \"Doors open!\" in the current channel"
+        );
+    }
+
+    #[test]
+    fn open_channel_steps_default_to_everyone() {
+        let open = Step {
+            channel_id: Some(9),
+            ..step(StepKind::OpenChannel)
+        };
+        assert_eq!(open.describe(), "open <#9> to everyone");
+        let open = Step {
+            role_id: Some(4),
+            ..open
+        };
+        assert_eq!(open.describe(), "open <#9> to <@&4>");
+    }
+}
@@ -1,22 +1,133 @@
+// `SlimeError` is dominated by `serenity::Error`; boxing it would cost every `?` on a Discord call.
+#![allow(clippy::result_large_err)]
+
+use std::sync::Arc;
+
 use anyhow::anyhow;
 use serenity::Error as SerenityError;
 use shuttle_secrets::SecretStore;
 use thiserror::Error;
-use tracing::error;
+use tracing::{error, info};
+
+use custom_id::{CustomId, Kind};
+use poise::serenity_prelude::*;
 
-use poise::{serenity_prelude::*, CreateReply};
+mod alerts;
+mod announce;
+mod appeals;
+mod audit;
+mod away;
+mod banner;
+mod clock;
+mod config;
+mod custom_id;
+mod departure;
+mod digest;
+mod discord;
+mod drift;
+mod emoji;
+mod events;
+mod faq;
+mod forms;
+mod gc;
+mod help;
+mod i18n;
+mod invites;
+mod janitor;
+mod leaderboard;
+mod lfg;
+mod links;
+mod macros;
+mod metrics;
+mod milestones;
+mod notify;
+mod permtemplate;
+mod points;
+mod posts;
+mod preferences;
+mod preview;
+mod privacy;
+mod purge;
+mod questions;
+mod quiet;
+mod quotas;
+mod raffle;
+mod raid;
+mod relay;
+mod report;
+mod repost;
+mod roles;
+mod rotation;
+mod scheduler;
+mod settings;
+mod shifts;
+mod standby;
+mod stats;
+mod tags;
+mod templates;
+mod tournament;
+mod undo;
+mod util;
+mod verification;
+mod visibility;
+mod watch;
+mod weather;
 
 #[derive(Clone)]
 struct Data {
-    _pool: sqlx::PgPool,
+    pool: sqlx::PgPool,
+    leaderboards: Arc<leaderboard::LeaderboardCache>,
+    /// Events behind the posts members are pressing buttons on.
+    events: Arc<events::cache::EventCache>,
+    lease: Arc<standby::Lease>,
+    alerts: Arc<alerts::Alerts>,
+    purges: Arc<quotas::Running>,
+    /// Keeps batch work from holding up answers to interactions.
+    calls: Arc<discord::CallQueue>,
+    config: Arc<config::Config>,
+    /// The time scheduled work runs against.
+    clock: Arc<dyn clock::Clock>,
+    /// For the APIs that aren't Discord's, like weather forecasts.
+    http: reqwest::Client,
 }
 
 #[derive(Error, Debug)]
 enum SlimeError {
     #[error("an occur occurred within Serenity: {0}")]
     SerenityError(#[from] SerenityError),
+    #[error("an error occurred within the database: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("this command can only be used in a server")]
+    NotInGuild,
+    #[error("could not understand `{0}` as a time, try `2024-03-01 19:30` or a Unix timestamp")]
+    InvalidTime(String),
+    #[error("`{0}` is not a positive whole number")]
+    InvalidNumber(String),
+    #[error("event #{0} doesn't exist or isn't yours to change")]
+    EventNotFound(i64),
+    #[error("this server has no {0} configured, ask an admin to set one with `/settings`")]
+    MissingSetting(&'static str),
+    #[error("that can't be undone, {0}")]
+    CannotUndo(&'static str),
+    #[error(
+        "this server already has the most {0} allowed ({1}), ask the bot's owner about raising it"
+    )]
+    QuotaExceeded(&'static str, i64),
+    #[error("that template won't work, {0}")]
+    InvalidTemplate(String),
+    #[error(
+        "could not understand `{0}` as a timezone, try an offset from UTC like `+02:00` or `UTC-5`"
+    )]
+    InvalidTimezone(String),
+    #[error("there's nothing to post")]
+    NothingToPost,
+    #[error("announcement #{0} doesn't exist or has already been posted")]
+    AnnouncementNotFound(i64),
+    #[error("that role isn't rotated, set it up with `/rotation create`")]
+    RotationNotFound,
 }
 type Context<'a> = poise::Context<'a, Data, SlimeError>;
+type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, SlimeError>;
 
 fn make_uuid_buttons(yes_uuid: &str, no_uuid: &str, disabled: bool) -> CreateActionRow {
     CreateActionRow::Buttons(vec![
@@ -31,54 +142,178 @@ fn make_uuid_buttons(yes_uuid: &str, no_uuid: &str, disabled: bool) -> CreateAct
     ])
 }
 
-#[poise::command(slash_command)]
-async fn purge_old(ctx: Context<'_>) -> Result<(), SlimeError> {
-    let _channel = ctx.guild_channel().await.unwrap();
+async fn handle_component(
+    ctx: &serenity::client::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+) -> Result<(), SlimeError> {
+    let Some(custom_id) = CustomId::parse(&component.data.custom_id) else {
+        // One of the bot's own that doesn't read any more, like a button from an older version.
+        if Kind::of(&component.data.custom_id).is_some() {
+            return util::respond_ephemeral(
+                ctx,
+                component,
+                "This button is out of date. Please use a newer post or run the command again.",
+            )
+            .await;
+        }
+        return Ok(());
+    };
+    let id = &custom_id;
+    match custom_id.kind {
+        Kind::EventQueue => events::approval::handle_component(ctx, data, component, id).await,
+        Kind::EventRsvp => events::rsvp::handle_component(ctx, data, component, id).await,
+        Kind::EventSlot => events::slots::handle_component(ctx, data, component, id).await,
+        Kind::EventItem => events::items::handle_component(ctx, data, component, id).await,
+        Kind::EventSync => events::sync::handle_component(ctx, data, component, id).await,
+        Kind::Lfg => lfg::handle_component(ctx, data, component, id).await,
+        Kind::Tournament => tournament::handle_component(ctx, data, component, id).await,
+        Kind::Raffle => raffle::handle_component(ctx, data, component, id).await,
+        Kind::Raid => raid::handle_component(ctx, data, component, id).await,
+        Kind::Verify => verification::handle_component(ctx, data, component, id).await,
+        Kind::Appeal => appeals::handle_component(ctx, data, component, id).await,
+        Kind::Drift => drift::handle_component(ctx, data, component, id).await,
+        // The command that sent it is waiting for the press.
+        Kind::Confirm | Kind::Page | Kind::Form | Kind::Wizard | Kind::Preview | Kind::Move => {
+            Ok(())
+        }
+    }
+}
 
-    let id = ctx.id();
-    let yes_uuid: String = format!("{id}-yes");
-    let no_uuid: String = format!("{id}-no");
+async fn event_handler(
+    ctx: &serenity::client::Context,
+    event: &FullEvent,
+    _framework: poise::FrameworkContext<'_, Data, SlimeError>,
+    data: &Data,
+) -> Result<(), SlimeError> {
+    // A standby instance sees every event too, but must leave them to the active one.
+    if !data.lease.is_active() {
+        return Ok(());
+    }
 
-    let buttons = make_uuid_buttons(&yes_uuid, &no_uuid, false);
+    match event {
+        FullEvent::InteractionCreate {
+            interaction: Interaction::Component(component),
+        } => {
+            data.calls.open(component.id.get());
+            let handled =
+                discord::guard(ctx, component, handle_component(ctx, data, component)).await;
+            data.calls.close(component.id.get());
+            metrics::finish_component(&data.pool, component, &handled).await;
+            // An abandoned handler has already told the user.
+            if let Some(result) = handled.result {
+                result?;
+            }
+        }
+        FullEvent::InteractionCreate {
+            interaction: Interaction::Modal(modal),
+        } => match Kind::of(&modal.data.custom_id) {
+            Some(Kind::Verify) => verification::submitted(ctx, data, modal).await?,
+            Some(Kind::Appeal) => appeals::submitted(ctx, data, modal).await?,
+            // Other forms are collected by the command that opened them.
+            _ => {}
+        },
+        FullEvent::Message { new_message } if new_message.author.bot => {
+            events::coexistence::noticed(ctx, data, new_message).await?;
+        }
+        FullEvent::Message { new_message } => {
+            stats::counted(data, new_message).await?;
+            // A deleted message has nothing left to answer.
+            if links::noticed(ctx, data, new_message).await?
+                || invites::noticed(ctx, data, new_message).await?
+            {
+                return Ok(());
+            }
+            questions::noticed(ctx, data, new_message).await?;
+            faq::noticed(ctx, data, new_message).await?;
+            watch::noticed(ctx, data, new_message).await?;
+        }
+        FullEvent::ReactionAdd { add_reaction } => {
+            stats::reacted(data, add_reaction).await?;
+        }
+        FullEvent::ThreadUpdate { new, .. } if new.thread_metadata.is_some_and(|m| m.archived) => {
+            events::threads::archived(ctx, data, new).await?;
+        }
+        FullEvent::GuildScheduledEventUpdate { event } => {
+            events::sync::scheduled_updated(ctx, data, event).await?;
+        }
+        FullEvent::GuildScheduledEventUserAdd { subscribed } => {
+            events::interest::user_added(ctx, data, subscribed).await?;
+        }
+        FullEvent::GuildScheduledEventUserRemove { unsubscribed } => {
+            events::interest::user_removed(ctx, data, unsubscribed).await?;
+        }
+        FullEvent::GuildMemberAddition { new_member } => {
+            raid::joined(ctx, data, new_member).await?;
+            verification::joined(ctx, data, new_member).await?;
+        }
+        FullEvent::GuildMemberUpdate { event, .. } => {
+            appeals::timed_out(ctx, data, event).await?;
+        }
+        FullEvent::VoiceStateUpdate { old, new } => {
+            events::speakers::handle_voice_state(ctx, data, old.as_ref(), new).await?;
+        }
+        // An unavailable guild is an outage on Discord's side, not the bot being removed.
+        FullEvent::GuildDelete { incomplete, .. } if !incomplete.unavailable => {
+            info!("Removed from guild {}, detaching its data", incomplete.id);
+            departure::detach(&data.pool, incomplete.id).await?;
+        }
+        FullEvent::GuildCreate { guild, is_new } => {
+            // Sent for every guild on connecting, so only a detached one is worth a log line.
+            let rejoined = departure::reattach(&data.pool, guild.id).await?;
+            if rejoined {
+                info!("Rejoined guild {}, keeping its data", guild.id);
+            } else if *is_new == Some(true) {
+                events::history::offer(ctx, data, guild).await?;
+            }
+        }
+        _ => {}
+    }
 
-    let reply = CreateReply::default()
-        .content("The first message to be deleted is <foo>, the last is <bar> continue?")
-        .components(vec![buttons])
-        .ephemeral(true);
+    Ok(())
+}
 
-    ctx.send(reply).await?;
+async fn pre_command(ctx: Context<'_>) {
+    ctx.data().calls.open(ctx.id());
+    metrics::start(ctx).await;
+}
 
-    if let Some(interactions) = ComponentInteractionCollector::new(ctx.serenity_context())
-        .timeout(std::time::Duration::from_secs(120))
-        .custom_ids(vec![yes_uuid.clone(), no_uuid.clone()])
-        .await
-    {
-        let message = CreateInteractionResponseMessage::new()
-            .components(vec![make_uuid_buttons("yes_disabled", "no_disabled", true)])
-            .content(&interactions.message.content);
-
-        let disable_buttons = CreateInteractionResponse::UpdateMessage(message);
-        interactions
-            .create_response(ctx, disable_buttons)
-            .await
-            .inspect_err(|e| error!("{}", e))?;
-
-        let content = match &interactions.data.custom_id {
-            id if id == &yes_uuid => "yes",
-            id if id == &no_uuid => "no",
-            _ => unreachable!(),
-        };
-
-        let followup = CreateInteractionResponseFollowup::new()
-            .content(content)
-            .ephemeral(true);
-        interactions
-            .create_followup(ctx, followup)
-            .await
-            .inspect_err(|e| error!("{}", e))?;
+async fn post_command(ctx: Context<'_>) {
+    ctx.data().calls.close(ctx.id());
+    metrics::finish(ctx, false).await;
+}
+
+async fn on_error(error: poise::FrameworkError<'_, Data, SlimeError>) {
+    if let Some(ctx) = error.ctx() {
+        ctx.data().calls.close(ctx.id());
+        metrics::finish(ctx, true).await;
+    }
+    // Only the bot's own failures count towards alerts, not members using a command wrong.
+    let failure = match &error {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            Some((error, ctx.serenity_context(), ctx.data()))
+        }
+        poise::FrameworkError::EventHandler {
+            error,
+            ctx,
+            framework,
+            ..
+        } => Some((error, *ctx, framework.user_data().await)),
+        _ => None,
+    };
+    if let Some((failure, ctx, data)) = failure {
+        if let Some(alert) = data.alerts.command_failed(failure, data.clock.now()) {
+            alerts::raise(ctx, data, alert).await;
+        }
     }
 
-    Ok(())
+    match error {
+        // Commands that reach a standby instance are being answered by the active one.
+        poise::FrameworkError::CommandCheckFailed { error: None, .. } => {}
+        error => poise::builtins::on_error(error)
+            .await
+            .unwrap_or_else(|e| error!("Could not handle error: {}", e)),
+    }
 }
 
 #[shuttle_runtime::main]
@@ -92,22 +327,98 @@ async fn serenity(
     } else {
         return Err(anyhow!("'DISCORD_TOKEN' was not found").into());
     };
+    let config = config::Config::from_secrets(&secret_store)?;
+    config.init_tracing()?;
+    info!("Starting in {:?}", config.environment);
+    let pool = config.connect(pool).await.map_err(anyhow::Error::from)?;
+
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .map_err(anyhow::Error::from)?;
 
     // Set gateway intents, which decides what events the bot will be notified about
-    let intents = GatewayIntents::GUILD_MESSAGES
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MEMBERS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
         | GatewayIntents::MESSAGE_CONTENT
         | GatewayIntents::GUILD_SCHEDULED_EVENTS
+        | GatewayIntents::GUILD_VOICE_STATES
         | GatewayIntents::DIRECT_MESSAGES;
 
+    let mut commands = vec![
+        purge::purge_old(),
+        purge::retention::retention(),
+        departure::purge_guild_command(),
+        events::event(),
+        announce::announce(),
+        events::attendance::checkin(),
+        faq::faq(),
+        settings::settings(),
+        stats::stats(),
+        tags::tag(),
+        help::help(),
+        tournament::tournament(),
+        invites::invites(),
+        janitor::janitor(),
+        leaderboard::leaderboard(),
+        lfg::lfg(),
+        links::links(),
+        macros::macro_command(),
+        permtemplate::permtemplate(),
+        points::points(),
+        preferences::preferences(),
+        away::away(),
+        privacy::forgetme(),
+        privacy::forget_user_command(),
+        questions::questions(),
+        quotas::quota(),
+        raffle::raffle(),
+        repost::move_message(),
+        report::report(),
+        roles::roles(),
+        rotation::rotation(),
+        shifts::shifts(),
+        undo::undo(),
+        verification::verification(),
+        visibility::visibility(),
+        watch::watch(),
+    ];
+    i18n::localize_commands(&mut commands);
+
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![purge_old()],
+            commands,
+            event_handler: |ctx, event, framework, data| {
+                Box::pin(event_handler(ctx, event, framework, data))
+            },
+            command_check: Some(|ctx| Box::pin(async move { Ok(ctx.data().lease.is_active()) })),
+            pre_command: |ctx| Box::pin(pre_command(ctx)),
+            post_command: |ctx| Box::pin(post_command(ctx)),
+            on_error: |error| Box::pin(on_error(error)),
             ..Default::default()
         })
-        .setup(|ctx, _ready, framework| {
+        .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
-                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data { _pool: pool })
+                let data = Data {
+                    pool,
+                    leaderboards: Default::default(),
+                    events: Default::default(),
+                    lease: Default::default(),
+                    alerts: Default::default(),
+                    purges: Default::default(),
+                    calls: Default::default(),
+                    config: Arc::new(config),
+                    clock: Arc::new(clock::SystemClock),
+                    http: Default::default(),
+                };
+                // Commands are registered once this instance becomes the active one.
+                let commands =
+                    poise::builtins::create_application_commands(&framework.options().commands);
+                standby::start(ctx.clone(), data.clone(), commands);
+                scheduler::start(ctx.clone(), data.clone());
+                Ok(data)
             })
         })
         .build();
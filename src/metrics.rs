@@ -0,0 +1,138 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{custom_id::Kind, discord::Handled, Context, SlimeError};
+
+/// How one command has been used.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CommandUsage {
+    pub command: String,
+    pub invocations: i64,
+    pub failures: i64,
+    pub total_ms: i64,
+    pub max_ms: i64,
+    /// Component presses that had to be deferred. Always 0 for commands.
+    pub slow: i64,
+    pub last_used_at: DateTime<Utc>,
+}
+
+impl CommandUsage {
+    pub fn average_ms(&self) -> i64 {
+        self.total_ms / self.invocations.max(1)
+    }
+}
+
+/// Component presses are counted under this prefix, followed by their custom ID's.
+pub const BUTTON_PREFIX: &str = "button:";
+
+/// Adds one invocation of `command` to the guild's metrics.
+pub async fn record(
+    pool: &PgPool,
+    guild_id: Option<GuildId>,
+    command: &str,
+    elapsed_ms: i64,
+    failed: bool,
+    slow: bool,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO command_metrics (guild_id, command, invocations, failures, total_ms, max_ms, slow)
+         VALUES ($1, $2, 1, $3, $4, $4, $5)
+         ON CONFLICT (guild_id, command) DO UPDATE SET
+            invocations = command_metrics.invocations + 1,
+            failures = command_metrics.failures + EXCLUDED.failures,
+            total_ms = command_metrics.total_ms + EXCLUDED.total_ms,
+            max_ms = GREATEST(command_metrics.max_ms, EXCLUDED.max_ms),
+            slow = command_metrics.slow + EXCLUDED.slow,
+            last_used_at = now()",
+    )
+    .bind(guild_id.map_or(0, |id| id.get() as i64))
+    .bind(command)
+    .bind(failed as i64)
+    .bind(elapsed_ms)
+    .bind(slow as i64)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Usage of every command in `guild_id`, or summed over every guild, most used first.
+pub async fn usage(
+    pool: &PgPool,
+    guild_id: Option<GuildId>,
+) -> Result<Vec<CommandUsage>, SlimeError> {
+    Ok(sqlx::query_as::<_, CommandUsage>(
+        "SELECT command,
+            SUM(invocations)::BIGINT AS invocations,
+            SUM(failures)::BIGINT AS failures,
+            SUM(total_ms)::BIGINT AS total_ms,
+            MAX(max_ms) AS max_ms,
+            SUM(slow)::BIGINT AS slow,
+            MAX(last_used_at) AS last_used_at
+         FROM command_metrics
+         WHERE $1::BIGINT IS NULL OR guild_id = $1
+         GROUP BY command
+         ORDER BY SUM(invocations) DESC, command",
+    )
+    .bind(guild_id.map(|id| id.get() as i64))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Notes when a command started running. Called by the framework before every command.
+pub async fn start(ctx: Context<'_>) {
+    ctx.set_invocation_data(Instant::now()).await;
+}
+
+/// Records how a command went. Called by the framework after every command that got as far as
+/// [`start`]; metrics are best-effort, so failing to record one is only logged.
+pub async fn finish(ctx: Context<'_>, failed: bool) {
+    let Some(started) = ctx.invocation_data::<Instant>().await.map(|at| *at) else {
+        return;
+    };
+    let elapsed_ms = started.elapsed().as_millis() as i64;
+    let command = ctx.command().qualified_name.clone();
+
+    if let Err(e) = record(
+        &ctx.data().pool,
+        ctx.guild_id(),
+        &command,
+        elapsed_ms,
+        failed,
+        false,
+    )
+    .await
+    {
+        error!("Could not record metrics for /{}: {}", command, e);
+    }
+}
+
+/// Records how answering a press of `interaction` went, under the kind of component it was.
+/// Presses collected by a running command are that command's business and aren't counted.
+pub async fn finish_component(
+    pool: &PgPool,
+    interaction: &ComponentInteraction,
+    handled: &Handled<Result<(), SlimeError>>,
+) {
+    let Some(kind) = Kind::of(&interaction.data.custom_id).filter(|k| !k.is_collected()) else {
+        return;
+    };
+    let failed = !matches!(handled.result, Some(Ok(())));
+    let name = format!("{BUTTON_PREFIX}{}", kind.key());
+    if let Err(e) = record(
+        pool,
+        interaction.guild_id,
+        &name,
+        handled.elapsed.as_millis() as i64,
+        failed,
+        handled.deferred,
+    )
+    .await
+    {
+        error!("Could not record metrics for {}: {}", name, e);
+    }
+}
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{roles, Data, SlimeError};
+
+/// The smallest member count worth celebrating. Past it, counts of 1, 2.5 and 5 times a power of
+/// ten are.
+const FIRST_MEMBER_MILESTONE: u64 = 100;
+
+/// Discord's limit on a message's length.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// The biggest member count milestone `count` has reached, if any.
+fn member_milestone(count: u64) -> Option<u64> {
+    let mut reached = None;
+    let mut power = FIRST_MEMBER_MILESTONE;
+    loop {
+        for milestone in [power, power * 5 / 2, power * 5] {
+            if milestone > count {
+                return reached;
+            }
+            reached = Some(milestone);
+        }
+        power *= 10;
+    }
+}
+
+/// How many years ago `since` was, if `today` is its anniversary. Anniversaries of the 29th of
+/// February are on the 28th in other years.
+fn anniversary(since: NaiveDate, today: NaiveDate) -> Option<i32> {
+    let years = today.year() - since.year();
+    if years < 1 {
+        return None;
+    }
+    let on_the_day = (since.month(), since.day()) == (today.month(), today.day());
+    let leap_day = (since.month(), since.day()) == (2, 29)
+        && (today.month(), today.day()) == (2, 28)
+        && NaiveDate::from_ymd_opt(today.year(), 2, 29).is_none();
+    (on_the_day || leap_day).then_some(years)
+}
+
+fn years(years: i32) -> String {
+    match years {
+        1 => "a year".to_string(),
+        years => format!("{years} years"),
+    }
+}
+
+/// Lines put together into as few messages as fit.
+fn messages(lines: &[String]) -> Vec<String> {
+    let mut messages = Vec::<String>::new();
+    for line in lines {
+        match messages.last_mut() {
+            Some(message) if message.len() + 1 + line.len() <= MESSAGE_LIMIT => {
+                message.push('\n');
+                message.push_str(line);
+            }
+            _ => messages.push(line.clone()),
+        }
+    }
+    messages
+}
+
+/// Records `milestone` as announced today, returning whether it hadn't been yet.
+async fn claim(
+    data: &Data,
+    guild_id: GuildId,
+    milestone: &str,
+    user: Option<UserId>,
+    today: NaiveDate,
+) -> Result<bool, SlimeError> {
+    let claimed = sqlx::query(
+        "INSERT INTO milestones (guild_id, milestone, user_id, announced_on) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id, milestone) DO NOTHING",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(milestone)
+    .bind(user.map(|u| u.get() as i64))
+    .bind(today)
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+    Ok(claimed > 0)
+}
+
+/// What's worth celebrating in a guild today, each milestone only ever once.
+async fn celebrate(
+    ctx: &SerenityContext,
+    data: &Data,
+    guild_id: GuildId,
+    channel: ChannelId,
+    today: NaiveDate,
+) -> Result<(), SlimeError> {
+    let members = roles::fetch_all_members(ctx, guild_id).await?;
+    let mut lines = Vec::new();
+
+    // Milestones passed before celebrating was turned on are noted without a post, so a big server
+    // isn't congratulated on a count it reached years ago.
+    let first = claim(data, guild_id, "members:0", None, today).await?;
+    if let Some(milestone) = member_milestone(members.len() as u64) {
+        let key = format!("members:{milestone}");
+        if claim(data, guild_id, &key, None, today).await? && !first {
+            lines.push(format!(
+                "🎉 This server just reached **{milestone}** members!"
+            ));
+        }
+    }
+
+    let founded = guild_id.created_at().date_naive();
+    if let Some(age) = anniversary(founded, today) {
+        let key = format!("server:{age}");
+        if claim(data, guild_id, &key, None, today).await? {
+            lines.push(format!("🎂 This server is {} old today!", years(age)));
+        }
+    }
+
+    let joined = members
+        .iter()
+        .filter(|m| !m.user.bot)
+        .filter_map(|m| {
+            let since = m.joined_at?.date_naive();
+            Some((m.user.id, anniversary(since, today)?))
+        })
+        .collect::<Vec<_>>();
+    let ids = joined.iter().map(|j| j.0.get() as i64).collect::<Vec<_>>();
+    let opted_out = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM user_preferences WHERE NOT milestones AND user_id = ANY($1)",
+    )
+    .bind(&ids)
+    .fetch_all(&data.pool)
+    .await?;
+    let mut mentioned = Vec::new();
+    for (user, age) in joined {
+        if opted_out.contains(&(user.get() as i64)) {
+            continue;
+        }
+        let key = format!("joined:{user}:{age}");
+        if claim(data, guild_id, &key, Some(user), today).await? {
+            lines.push(format!(
+                "🐸 {} joined {} ago today.",
+                user.mention(),
+                years(age)
+            ));
+            mentioned.push(user);
+        }
+    }
+
+    for content in messages(&lines) {
+        let post = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new().users(mentioned.clone()));
+        if let Err(e) = channel.send_message(ctx, post).await {
+            error!("Could not celebrate milestones in {}: {}", channel, e);
+        }
+    }
+    Ok(())
+}
+
+/// Once a day for each guild that celebrates them, announces the milestones reached since the
+/// last check.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let today = now.date_naive();
+    // Claimed before checking, so a slow run can't check a guild twice.
+    let due = sqlx::query_as::<_, (i64, i64)>(
+        "UPDATE guild_settings SET milestones_checked_on = $1
+         WHERE milestone_channel_id IS NOT NULL
+            AND (milestones_checked_on IS NULL OR milestones_checked_on < $1)
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING guild_id, milestone_channel_id",
+    )
+    .bind(today)
+    .fetch_all(&data.pool)
+    .await?;
+
+    for (guild_id, channel) in due {
+        let guild_id = GuildId::new(guild_id as u64);
+        let channel = ChannelId::new(channel as u64);
+        // One guild's trouble shouldn't cost the others their day.
+        if let Err(e) = celebrate(ctx, data, guild_id, channel, today).await {
+            error!("Could not check milestones in {}: {}", guild_id, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn milestones_and_anniversaries() {
+        assert_eq!(member_milestone(99), None);
+        assert_eq!(member_milestone(100), Some(100));
+        assert_eq!(member_milestone(999), Some(500));
+        assert_eq!(member_milestone(1000), Some(1000));
+        assert_eq!(member_milestone(26_000), Some(25_000));
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(anniversary(date(2024, 3, 1), date(2026, 3, 1)), Some(2));
+        assert_eq!(anniversary(date(2026, 3, 1), date(2026, 3, 1)), None);
+        assert_eq!(anniversary(date(2024, 3, 1), date(2026, 3, 2)), None);
+        assert_eq!(anniversary(date(2024, 2, 29), date(2025, 2, 28)), Some(1));
+        assert_eq!(anniversary(date(2024, 2, 29), date(2028, 2, 28)), None);
+        assert_eq!(anniversary(date(2024, 2, 29), date(2028, 2, 29)), Some(4));
+    }
+}
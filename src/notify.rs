@@ -0,0 +1,470 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::*,
+};
+use serenity::{client::Context as SerenityContext, Error as SerenityError};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{
+    away, digest, discord::CallQueue, events::Event, i18n, templates, util::send_dm, SlimeError,
+};
+
+/// How many DMs are in flight at once. DMs to different members share Discord's global limit,
+/// so more would only queue up inside serenity.
+const CONCURRENCY: usize = 5;
+
+/// Tries per member before giving up on a transient failure.
+const ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled for each one after.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The JSON error code Discord answers with when a member doesn't accept DMs from the bot.
+const CANNOT_MESSAGE_USER: isize = 50007;
+
+/// What a notification is about. Each kind's wording can be changed per guild with a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// An event is about to start.
+    #[name = "Event reminder"]
+    Reminder,
+    /// An event a member signed up for was called off.
+    #[name = "Event cancelled"]
+    Cancellation,
+    /// A member got a place after someone else dropped out.
+    #[name = "Off the waitlist"]
+    Promotion,
+    /// A new event looks like something a member would enjoy, going by what they've been to.
+    #[name = "Event suggestion"]
+    Suggestion,
+}
+
+impl NotificationKind {
+    /// Whether this can be held for a member's daily digest, or until quiet hours end. Reminders
+    /// are time-sensitive, and digests already list the day's events.
+    pub fn can_wait(self) -> bool {
+        self != NotificationKind::Reminder
+    }
+
+    /// The variables templates for this kind may use.
+    pub fn variables(self) -> &'static [&'static str] {
+        match self {
+            NotificationKind::Reminder
+            | NotificationKind::Cancellation
+            | NotificationKind::Suggestion => &["event", "time"],
+            NotificationKind::Promotion => &["event", "time", "position"],
+        }
+    }
+
+    fn default_template(self) -> &'static str {
+        match self {
+            NotificationKind::Reminder => "**{event}** starts {time}.",
+            NotificationKind::Cancellation => {
+                "**{event}**, which you signed up for, has been cancelled."
+            }
+            NotificationKind::Promotion => {
+                "A place opened up at **{event}**, you're off the waitlist and going!"
+            }
+            NotificationKind::Suggestion => {
+                "**{event}** is on {time}, and looks like your kind of thing. Sign up on its post \
+                 if you'd like to go."
+            }
+        }
+    }
+
+    /// The guild's template for this kind, or the built-in one.
+    async fn template(self, pool: &PgPool, guild_id: GuildId) -> Result<String, SlimeError> {
+        let template = sqlx::query_scalar::<_, String>(
+            "SELECT template FROM notification_templates WHERE guild_id = $1 AND kind = $2",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(self)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(template.unwrap_or_else(|| self.default_template().to_string()))
+    }
+}
+
+/// The text of a `kind` notification about `event`, from its guild's template. `position` is
+/// the member's place among the attendees, for kinds that have one.
+pub async fn event_message(
+    pool: &PgPool,
+    kind: NotificationKind,
+    event: &Event,
+    position: Option<usize>,
+) -> Result<String, SlimeError> {
+    let template = kind.template(pool, event.guild()).await?;
+    Ok(render_event(
+        &template,
+        &event.title,
+        event.starts_at,
+        position,
+    ))
+}
+
+fn render_event(
+    template: &str,
+    title: &str,
+    starts_at: DateTime<Utc>,
+    position: Option<usize>,
+) -> String {
+    let time = i18n::timestamp(starts_at, FormattedTimestampStyle::LongDateTime);
+    let position = position.map_or_else(String::new, |p| p.to_string());
+    templates::render(
+        template,
+        &[("event", &title), ("time", &time), ("position", &position)],
+    )
+}
+
+/// Saves a guild's template for `kind`, or goes back to the built-in one for `None`. Returns an
+/// example of what the notification will now look like, or why the template was refused.
+pub async fn set_template(
+    pool: &PgPool,
+    guild_id: GuildId,
+    kind: NotificationKind,
+    template: Option<&str>,
+) -> Result<String, SlimeError> {
+    let example = |template| render_event(template, "Game night", Utc::now(), Some(1));
+    let Some(template) = template else {
+        sqlx::query("DELETE FROM notification_templates WHERE guild_id = $1 AND kind = $2")
+            .bind(guild_id.get() as i64)
+            .bind(kind)
+            .execute(pool)
+            .await?;
+        return Ok(example(kind.default_template()));
+    };
+
+    templates::validate(template, kind.variables()).map_err(SlimeError::InvalidTemplate)?;
+    sqlx::query(
+        "INSERT INTO notification_templates (guild_id, kind, template) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id, kind) DO UPDATE SET template = EXCLUDED.template",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(kind)
+    .bind(template)
+    .execute(pool)
+    .await?;
+
+    Ok(example(template))
+}
+
+/// How one member's DM went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Outcome {
+    Delivered,
+    /// Held back for the member's daily digest.
+    Digested,
+    /// Held back until quiet hours, the member's or the guild's, are over.
+    Held,
+    /// Not sent, because the member is away.
+    Away,
+    /// The member doesn't accept DMs from the bot, so there's no point retrying.
+    DmsClosed,
+    Failed,
+}
+
+/// Why a DM didn't go out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DmFailure {
+    DmsClosed,
+    /// Worth trying again shortly: rate limits, Discord having trouble, or the network.
+    Transient(String),
+    Permanent(String),
+}
+
+impl From<SerenityError> for DmFailure {
+    fn from(e: SerenityError) -> Self {
+        match &e {
+            SerenityError::Http(HttpError::UnsuccessfulRequest(response)) => {
+                let status = response.status_code.as_u16();
+                if response.error.code == CANNOT_MESSAGE_USER {
+                    DmFailure::DmsClosed
+                } else if status == 429 || status >= 500 {
+                    DmFailure::Transient(e.to_string())
+                } else {
+                    DmFailure::Permanent(e.to_string())
+                }
+            }
+            SerenityError::Http(HttpError::Request(_)) => DmFailure::Transient(e.to_string()),
+            _ => DmFailure::Permanent(e.to_string()),
+        }
+    }
+}
+
+/// How one member's DM went, for the delivery report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub user: UserId,
+    pub outcome: Outcome,
+    pub attempts: u32,
+    pub error: Option<String>,
+}
+
+/// Who a run is from and what it's about.
+#[derive(Debug, Clone, Copy)]
+pub struct NotificationRun {
+    pub guild_id: GuildId,
+    pub kind: NotificationKind,
+    /// The event, or whatever else, the run is about.
+    pub subject_id: i64,
+}
+
+/// Totals for one run, saved along with every member's [`Delivery`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+    pub run_id: i64,
+    pub delivered: u32,
+    pub digested: u32,
+    pub held: u32,
+    pub away: u32,
+    pub dms_closed: u32,
+    pub failed: u32,
+}
+
+/// Sends to one member, retrying transient failures with backoff.
+async fn deliver<F, Fut>(user: UserId, send: &F) -> Delivery
+where
+    F: Fn(UserId) -> Fut,
+    Fut: Future<Output = Result<(), DmFailure>>,
+{
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let (outcome, error) = match send(user).await {
+            Ok(()) => (Outcome::Delivered, None),
+            Err(DmFailure::DmsClosed) => (Outcome::DmsClosed, None),
+            Err(DmFailure::Transient(_)) if attempts < ATTEMPTS => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                continue;
+            }
+            Err(DmFailure::Transient(e) | DmFailure::Permanent(e)) => (Outcome::Failed, Some(e)),
+        };
+        return Delivery {
+            user,
+            outcome,
+            attempts,
+            error,
+        };
+    }
+}
+
+/// Sends to every member in `users`, a few at a time. Each member gets exactly one delivery,
+/// whatever happens to the others.
+async fn deliver_all<F, Fut>(users: Vec<UserId>, send: F) -> Vec<Delivery>
+where
+    F: Fn(UserId) -> Fut,
+    Fut: Future<Output = Result<(), DmFailure>>,
+{
+    let send = &send;
+    stream::iter(users)
+        .map(|user| deliver(user, send))
+        .buffer_unordered(CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Saves a finished run and every delivery in it.
+async fn save_run(
+    pool: &PgPool,
+    run: NotificationRun,
+    deliveries: &[Delivery],
+) -> Result<DeliveryReport, SlimeError> {
+    let count = |outcome| deliveries.iter().filter(|d| d.outcome == outcome).count() as u32;
+    let mut report = DeliveryReport {
+        run_id: 0,
+        delivered: count(Outcome::Delivered),
+        digested: count(Outcome::Digested),
+        held: count(Outcome::Held),
+        away: count(Outcome::Away),
+        dms_closed: count(Outcome::DmsClosed),
+        failed: count(Outcome::Failed),
+    };
+
+    let mut tx = pool.begin().await?;
+    report.run_id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO notification_runs
+            (guild_id, kind, subject_id, delivered, digested, held, away, dms_closed, failed)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         RETURNING id",
+    )
+    .bind(run.guild_id.get() as i64)
+    .bind(run.kind)
+    .bind(run.subject_id)
+    .bind(report.delivered as i32)
+    .bind(report.digested as i32)
+    .bind(report.held as i32)
+    .bind(report.away as i32)
+    .bind(report.dms_closed as i32)
+    .bind(report.failed as i32)
+    .fetch_one(&mut *tx)
+    .await?;
+    for delivery in deliveries {
+        sqlx::query(
+            "INSERT INTO notification_deliveries (run_id, user_id, outcome, attempts, error)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(report.run_id)
+        .bind(delivery.user.get() as i64)
+        .bind(delivery.outcome)
+        .bind(delivery.attempts as i32)
+        .bind(&delivery.error)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+/// DMs `content` to every member in `users`, then saves how each delivery went. Every DM waits
+/// its turn on `calls`, so a big run doesn't hold up anyone's commands. Members who take a daily
+/// digest get anything that can wait in that instead, and members who are away aren't reminded.
+pub async fn fan_out(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    calls: &CallQueue,
+    run: NotificationRun,
+    mut users: Vec<UserId>,
+    content: &str,
+    now: DateTime<Utc>,
+) -> Result<DeliveryReport, SlimeError> {
+    let mut skipped = HashMap::new();
+    // A reminder would be out of date by the time they're back.
+    if run.kind == NotificationKind::Reminder {
+        for user in away::away_now(pool, &users, now).await?.into_keys() {
+            skipped.insert(user, Outcome::Away);
+        }
+        users.retain(|user| !skipped.contains_key(user));
+    }
+    let held = digest::hold_back(pool, &users, run.guild_id, run.kind, content, now).await?;
+    users.retain(|user| !held.contains_key(user));
+    skipped.extend(held);
+    let mut deliveries = skipped
+        .into_iter()
+        .map(|(user, outcome)| Delivery {
+            user,
+            outcome,
+            attempts: 0,
+            error: None,
+        })
+        .collect::<Vec<_>>();
+
+    deliveries.extend(
+        deliver_all(users, |user| async move {
+            calls.turn().await;
+            send_dm(ctx, user, CreateMessage::new().content(content))
+                .await
+                .map(|_| ())
+                .map_err(DmFailure::from)
+        })
+        .await,
+    );
+
+    for delivery in &deliveries {
+        if let Some(e) = &delivery.error {
+            error!(
+                "Could not send {:?} for {} to {}: {}",
+                run.kind, run.subject_id, delivery.user, e
+            );
+        }
+    }
+    let report = save_run(pool, run, &deliveries).await?;
+    info!("Sent {:?} for {}: {:?}", run.kind, run.subject_id, report);
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_failures_are_retried_and_closed_dms_skipped() {
+        let (ok, flaky, closed, broken, gone) = (
+            UserId::new(1),
+            UserId::new(2),
+            UserId::new(3),
+            UserId::new(4),
+            UserId::new(5),
+        );
+        let transient = || DmFailure::Transient("503".to_string());
+        // Each member's sends fail as scripted, then deliver.
+        let script = Arc::new(Mutex::new(HashMap::from([
+            (flaky, vec![transient(), transient()]),
+            (closed, vec![DmFailure::DmsClosed]),
+            (broken, vec![transient(), transient(), transient()]),
+            (gone, vec![DmFailure::Permanent("404".to_string())]),
+        ])));
+        let sends = Arc::new(Mutex::new(0));
+
+        let mut deliveries = deliver_all(vec![ok, flaky, closed, broken, gone], |user| {
+            let (script, sends) = (Arc::clone(&script), Arc::clone(&sends));
+            async move {
+                *sends.lock().unwrap() += 1;
+                let mut script = script.lock().unwrap();
+                match script.get_mut(&user) {
+                    Some(failures) if !failures.is_empty() => Err(failures.remove(0)),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await;
+        deliveries.sort_by_key(|d| d.user);
+
+        let outcomes = deliveries
+            .iter()
+            .map(|d| (d.outcome, d.attempts))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                (Outcome::Delivered, 1),
+                (Outcome::Delivered, 3),
+                (Outcome::DmsClosed, 1),
+                (Outcome::Failed, ATTEMPTS),
+                (Outcome::Failed, 1),
+            ]
+        );
+        assert_eq!(*sends.lock().unwrap(), 1 + 3 + 1 + ATTEMPTS + 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_a_few_dms_are_in_flight_at_once() {
+        let in_flight = Arc::new(Mutex::new((0usize, 0usize)));
+        let users = (1..=50).map(UserId::new).collect::<Vec<_>>();
+
+        let deliveries = deliver_all(users, |_| {
+            let in_flight = Arc::clone(&in_flight);
+            async move {
+                {
+                    let mut counts = in_flight.lock().unwrap();
+                    counts.0 += 1;
+                    counts.1 = counts.1.max(counts.0);
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                in_flight.lock().unwrap().0 -= 1;
+                Ok(())
+            }
+        })
+        .await;
+
+        assert_eq!(deliveries.len(), 50);
+        assert_eq!(in_flight.lock().unwrap().1, CONCURRENCY);
+    }
+}
@@ -0,0 +1,351 @@
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{
+    audit::{self, AuditEntry},
+    util::rehearse,
+    Context, SlimeError,
+};
+
+/// A permission overwrite in the form it's stored in, shared by anything that saves channel
+/// permissions to put back later.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, sqlx::FromRow)]
+pub struct StoredOverwrite {
+    /// `role` or `member`.
+    pub kind: String,
+    pub target_id: i64,
+    pub allow: i64,
+    pub deny: i64,
+}
+
+impl StoredOverwrite {
+    pub fn from_overwrite(overwrite: &PermissionOverwrite) -> Option<Self> {
+        let (kind, target_id) = match overwrite.kind {
+            PermissionOverwriteType::Role(id) => ("role", id.get()),
+            PermissionOverwriteType::Member(id) => ("member", id.get()),
+            _ => return None,
+        };
+        Some(Self {
+            kind: kind.to_string(),
+            target_id: target_id as i64,
+            allow: overwrite.allow.bits() as i64,
+            deny: overwrite.deny.bits() as i64,
+        })
+    }
+
+    pub fn to_overwrite(&self) -> PermissionOverwrite {
+        let kind = if self.kind == "member" {
+            PermissionOverwriteType::Member(UserId::new(self.target_id as u64))
+        } else {
+            PermissionOverwriteType::Role(RoleId::new(self.target_id as u64))
+        };
+        PermissionOverwrite {
+            allow: Permissions::from_bits_truncate(self.allow as u64),
+            deny: Permissions::from_bits_truncate(self.deny as u64),
+            kind,
+        }
+    }
+}
+
+/// A channel's overwrites in a stable order, so two sets can be compared directly.
+pub fn normalize(overwrites: &[PermissionOverwrite]) -> Vec<StoredOverwrite> {
+    let mut stored = overwrites
+        .iter()
+        .filter_map(StoredOverwrite::from_overwrite)
+        .collect::<Vec<_>>();
+    stored.sort();
+    stored
+}
+
+/// How a channel's overwrites differ from its template: how many of the template's are missing
+/// or changed, and how many of the channel's aren't in the template. `None` if they match.
+fn drift_from(template: &[StoredOverwrite], actual: &[StoredOverwrite]) -> Option<(usize, usize)> {
+    if actual == template {
+        return None;
+    }
+    let missing = template.iter().filter(|o| !actual.contains(o)).count();
+    let extra = actual.iter().filter(|o| !template.contains(o)).count();
+    Some((missing, extra))
+}
+
+async fn load_template(
+    pool: &PgPool,
+    guild_id: GuildId,
+    name: &str,
+) -> Result<Option<Vec<StoredOverwrite>>, SlimeError> {
+    let exists = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM permission_templates WHERE guild_id = $1 AND name = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(name)
+    .fetch_one(pool)
+    .await?;
+    if exists == 0 {
+        return Ok(None);
+    }
+
+    let mut overwrites = sqlx::query_as::<_, StoredOverwrite>(
+        "SELECT kind, target_id, allow, deny FROM permission_template_overwrites
+         WHERE guild_id = $1 AND name = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(name)
+    .fetch_all(pool)
+    .await?;
+    overwrites.sort();
+    Ok(Some(overwrites))
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Reuse a channel's permission setup on other channels.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    subcommands("save", "apply", "drift", "delete")
+)]
+pub async fn permtemplate(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Save a channel's permission overwrites as a named template, replacing any with that name.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn save(
+    ctx: Context<'_>,
+    #[description = "Template name"]
+    #[max_length = 50]
+    name: String,
+    #[description = "Channel to copy permissions from"] channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let guild = guild_id.get() as i64;
+    let overwrites = normalize(&channel.permission_overwrites);
+
+    let mut tx = ctx.data().pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO permission_templates (guild_id, name, created_by) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id, name) DO UPDATE SET created_by = EXCLUDED.created_by",
+    )
+    .bind(guild)
+    .bind(&name)
+    .bind(ctx.author().id.get() as i64)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM permission_template_overwrites WHERE guild_id = $1 AND name = $2")
+        .bind(guild)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+    for overwrite in &overwrites {
+        sqlx::query(
+            "INSERT INTO permission_template_overwrites (guild_id, name, kind, target_id, allow, deny)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(guild)
+        .bind(&name)
+        .bind(&overwrite.kind)
+        .bind(overwrite.target_id)
+        .bind(overwrite.allow)
+        .bind(overwrite.deny)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    reply(
+        ctx,
+        format!(
+            "Saved **{name}** with {} overwrite(s) from {}.",
+            overwrites.len(),
+            channel.mention()
+        ),
+    )
+    .await
+}
+
+/// Replace a channel's permission overwrites with a template's.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn apply(
+    ctx: Context<'_>,
+    #[description = "Template name"] name: String,
+    #[description = "Channel to apply it to"] mut channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let Some(template) = load_template(pool, guild_id, &name).await? else {
+        return reply(ctx, format!("There's no template called **{name}**.")).await;
+    };
+    let plan = format!(
+        "replaced the {} permission overwrite(s) on {} with the {} in **{name}**.",
+        channel.permission_overwrites.len(),
+        channel.mention(),
+        template.len()
+    );
+    if rehearse(ctx, &plan).await? {
+        return Ok(());
+    }
+
+    channel
+        .edit(
+            ctx,
+            EditChannel::new()
+                .permissions(template.iter().map(StoredOverwrite::to_overwrite))
+                .audit_log_reason(&format!("Permission template {name}")),
+        )
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO permission_template_channels (channel_id, guild_id, name) VALUES ($1, $2, $3)
+         ON CONFLICT (channel_id) DO UPDATE SET name = EXCLUDED.name",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(&name)
+    .execute(pool)
+    .await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "permtemplate_apply",
+            target: Some(channel.id.get()),
+            details: name.clone(),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    reply(ctx, format!("Applied **{name}** to {}.", channel.mention())).await
+}
+
+/// List channels whose permissions no longer match the template applied to them.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn drift(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    ctx.defer_ephemeral().await?;
+    let pool = &ctx.data().pool;
+
+    let tracked = sqlx::query_as::<_, (i64, String)>(
+        "SELECT channel_id, name FROM permission_template_channels WHERE guild_id = $1
+         ORDER BY name",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let channels = guild_id.channels(ctx).await?;
+
+    let mut report = Vec::new();
+    for (channel_id, name) in tracked {
+        let channel_id = ChannelId::new(channel_id as u64);
+        let Some(channel) = channels.get(&channel_id) else {
+            // Deleted channels have nothing left to drift.
+            sqlx::query("DELETE FROM permission_template_channels WHERE channel_id = $1")
+                .bind(channel_id.get() as i64)
+                .execute(pool)
+                .await?;
+            continue;
+        };
+        let Some(template) = load_template(pool, guild_id, &name).await? else {
+            continue;
+        };
+
+        let actual = normalize(&channel.permission_overwrites);
+        if let Some((missing, extra)) = drift_from(&template, &actual) {
+            report.push(format!(
+                "- {} differs from **{name}**: {missing} overwrite(s) missing or changed, {extra} not in the template",
+                channel.mention()
+            ));
+        }
+    }
+
+    let content = if report.is_empty() {
+        "Every templated channel matches its template.".to_string()
+    } else {
+        format!(
+            "**{}** channel(s) have drifted:\n{}",
+            report.len(),
+            report.join("\n")
+        )
+    };
+    reply(ctx, content).await
+}
+
+/// Delete a template. Channels it was applied to keep their permissions.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Template name"] name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let mut tx = ctx.data().pool.begin().await?;
+    let deleted = sqlx::query("DELETE FROM permission_templates WHERE guild_id = $1 AND name = $2")
+        .bind(guild_id.get() as i64)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        reply(ctx, format!("There's no template called **{name}**.")).await
+    } else if rehearse(ctx, &format!("deleted the template **{name}**.")).await? {
+        Ok(())
+    } else {
+        tx.commit().await?;
+        reply(ctx, format!("Deleted **{name}**.")).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(id: u64, allow: Permissions, deny: Permissions) -> PermissionOverwrite {
+        PermissionOverwrite {
+            allow,
+            deny,
+            kind: PermissionOverwriteType::Role(RoleId::new(id)),
+        }
+    }
+
+    #[test]
+    fn drift_counts_changed_and_extra_overwrites() {
+        let everyone = role(1, Permissions::empty(), Permissions::VIEW_CHANNEL);
+        let staff = role(2, Permissions::VIEW_CHANNEL, Permissions::empty());
+        let member = PermissionOverwrite {
+            allow: Permissions::SEND_MESSAGES,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(UserId::new(3)),
+        };
+        let template = normalize(&[everyone.clone(), staff.clone()]);
+
+        // Order on the channel doesn't matter.
+        let same = normalize(&[staff.clone(), everyone.clone()]);
+        assert_eq!(drift_from(&template, &same), None);
+
+        let loosened = role(
+            2,
+            Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+            Permissions::empty(),
+        );
+        let drifted = normalize(&[everyone, loosened, member]);
+        assert_eq!(drift_from(&template, &drifted), Some((1, 2)));
+    }
+
+    #[test]
+    fn stored_overwrites_read_back_as_written() {
+        let overwrite = PermissionOverwrite {
+            allow: Permissions::CONNECT,
+            deny: Permissions::SPEAK,
+            kind: PermissionOverwriteType::Member(UserId::new(42)),
+        };
+        let stored = StoredOverwrite::from_overwrite(&overwrite).unwrap();
+        assert_eq!(stored.kind, "member");
+        assert_eq!(stored.to_overwrite(), overwrite);
+    }
+}
@@ -0,0 +1,207 @@
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{
+    audit::{self, AuditEntry},
+    leaderboard::Metric,
+    util::{confirm, rehearse},
+    Context, SlimeError,
+};
+
+/// The guild's current season number.
+pub async fn current_season<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    guild_id: GuildId,
+) -> Result<i32, SlimeError> {
+    Ok(sqlx::query_scalar::<_, i32>(
+        "SELECT COALESCE(MAX(season), 1) FROM points_seasons WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_one(executor)
+    .await?)
+}
+
+/// A member's total this season and over every season.
+async fn totals(pool: &PgPool, guild_id: GuildId, user: UserId) -> Result<(i64, i64), SlimeError> {
+    let season = current_season(pool, guild_id).await?;
+    Ok(sqlx::query_as::<_, (i64, i64)>(
+        "SELECT
+            COALESCE(SUM(amount) FILTER (WHERE season = $3), 0),
+            COALESCE(SUM(amount), 0)
+         FROM points_ledger WHERE guild_id = $1 AND user_id = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(user.get() as i64)
+    .bind(season)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Announces an award, or points taken away.
+fn award_line(member: UserId, amount: i32, reason: &str, total: i64) -> String {
+    let verb = if amount > 0 { "gets" } else { "loses" };
+    format!(
+        "{} {verb} **{}** point(s) for {reason}, and has {total} this season.",
+        member.mention(),
+        amount.abs()
+    )
+}
+
+/// Keep score for game nights and other recurring events.
+#[poise::command(slash_command, guild_only, subcommands("award", "show", "reset"))]
+pub async fn points(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Give a member points, or take some away with a negative amount.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_EVENTS")]
+async fn award(
+    ctx: Context<'_>,
+    #[description = "Member to give points to"] member: User,
+    #[description = "Points to give, negative to take away"]
+    #[min = -1000]
+    #[max = 1000]
+    amount: i32,
+    #[description = "What they're for, like \"trivia night\""]
+    #[max_length = 100]
+    reason: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    if amount == 0 {
+        return Err(SlimeError::InvalidNumber(amount.to_string()));
+    }
+    let data = ctx.data();
+
+    let season = current_season(&data.pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO points_ledger (guild_id, season, user_id, amount, reason, awarded_by)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(season)
+    .bind(member.id.get() as i64)
+    .bind(amount)
+    .bind(&reason)
+    .bind(ctx.author().id.get() as i64)
+    .execute(&data.pool)
+    .await?;
+    data.leaderboards.invalidate(guild_id, Metric::Points);
+
+    let (total, _) = totals(&data.pool, guild_id, member.id).await?;
+    ctx.say(award_line(member.id, amount, &reason, total))
+        .await?;
+
+    Ok(())
+}
+
+/// See a member's points, or your own.
+#[poise::command(slash_command, guild_only)]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "Member to look up, defaults to you"] member: Option<User>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let member = member.as_ref().unwrap_or(ctx.author());
+    let pool = &ctx.data().pool;
+
+    let season = current_season(pool, guild_id).await?;
+    let (this_season, all_time) = totals(pool, guild_id, member.id).await?;
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "{} has **{this_season}** point(s) in season {season}, and {all_time} all time.",
+                member.mention()
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Start a new season, setting everyone's points back to zero. Past seasons are kept.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn reset(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let data = ctx.data();
+    let season = current_season(&data.pool, guild_id).await?;
+
+    if rehearse(
+        ctx,
+        &format!(
+            "ended season {season} and started season {} with everyone on zero.",
+            season + 1
+        ),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    if !confirm(
+        ctx,
+        &format!(
+            "End season {season} and start season {} with everyone on zero?",
+            season + 1
+        ),
+    )
+    .await?
+    {
+        ctx.send(
+            CreateReply::default()
+                .content("The season carries on.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut tx = data.pool.begin().await?;
+    // Reread inside the transaction so two resets at once can't skip a season.
+    let season = current_season(&mut *tx, guild_id).await?;
+    sqlx::query("INSERT INTO points_seasons (guild_id, season, started_by) VALUES ($1, $2, $3)")
+        .bind(guild_id.get() as i64)
+        .bind(season + 1)
+        .bind(ctx.author().id.get() as i64)
+        .execute(&mut *tx)
+        .await?;
+    audit::record(
+        &mut *tx,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "points_reset",
+            target: None,
+            details: format!("season {}", season + 1),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    data.leaderboards.invalidate(guild_id, Metric::Points);
+
+    ctx.say(format!(
+        "Season {} has begun, everyone's back on zero!",
+        season + 1
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn awards_say_which_way_points_went() {
+        let member = UserId::new(7);
+        assert_eq!(
+            award_line(member, 5, "trivia night", 12),
+            "<@7> gets **5** point(s) for trivia night, and has 12 this season."
+        );
+        assert_eq!(
+            award_line(member, -3, "a false start", -3),
+            "<@7> loses **3** point(s) for a false start, and has -3 this season."
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{discord::CallQueue, events, lfg, raffle, tournament, util::http_status, SlimeError};
+
+/// What a bot-owned message is for. Each kind knows how to render its post from the database, so
+/// any post can be brought back up to date, or sent again if it was deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum PostKind {
+    /// The public post for a published event, with its RSVP buttons.
+    Event,
+    /// A pending event waiting for moderators in the approval channel.
+    EventApproval,
+    Raffle,
+    Tournament,
+    LfgGroup,
+}
+
+impl PostKind {
+    /// The table and column the subject keeps its own copy of the message ID in, updated when a
+    /// post has to be sent again.
+    fn message_column(self) -> (&'static str, &'static str) {
+        match self {
+            PostKind::Event => ("events", "message_id"),
+            PostKind::EventApproval => ("events", "queue_message_id"),
+            PostKind::Raffle => ("raffles", "message_id"),
+            PostKind::Tournament => ("tournaments", "message_id"),
+            PostKind::LfgGroup => ("lfg_groups", "message_id"),
+        }
+    }
+
+    /// The post as it should look now, or `None` once its subject is over and the post no longer
+    /// needs looking after.
+    async fn render(
+        self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+        subject_id: i64,
+    ) -> Result<Option<PostContent>, SlimeError> {
+        match self {
+            PostKind::Event => events::render_post(ctx, pool, subject_id).await,
+            PostKind::EventApproval => events::approval::render_post(ctx, pool, subject_id).await,
+            PostKind::Raffle => raffle::render_post(pool, subject_id).await,
+            PostKind::Tournament => tournament::render_post(pool, subject_id).await,
+            PostKind::LfgGroup => lfg::render_post(pool, subject_id).await,
+        }
+    }
+}
+
+/// Everything on a post the bot keeps up to date.
+#[derive(Debug, Clone)]
+pub struct PostContent {
+    pub content: Option<String>,
+    pub embed: CreateEmbed,
+    pub components: Vec<CreateActionRow>,
+}
+
+impl PostContent {
+    pub fn create(self) -> CreateMessage {
+        CreateMessage::new()
+            .content(self.content.unwrap_or_default())
+            .embed(self.embed)
+            .components(self.components)
+    }
+
+    pub fn edit(self) -> EditMessage {
+        EditMessage::new()
+            .content(self.content.unwrap_or_default())
+            .embed(self.embed)
+            .components(self.components)
+    }
+}
+
+/// A message the bot owns and looks after.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Post {
+    pub message_id: i64,
+    pub channel_id: i64,
+    pub kind: PostKind,
+    /// The event, raffle, tournament or group the post is about.
+    pub subject_id: i64,
+}
+
+/// What [`Post::rehydrate`] had to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rehydrated {
+    /// The post was still there and now shows the current state.
+    Refreshed,
+    /// The post had been deleted, so it was sent again.
+    Reposted,
+    /// The post's subject is over, so it was dropped from the registry.
+    Retired,
+}
+
+impl Post {
+    pub fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    pub fn message(&self) -> MessageId {
+        MessageId::new(self.message_id as u64)
+    }
+
+    /// Brings the post back in line with the database: edits it to the current state, sends it
+    /// again if it was deleted, or retires it if its subject is over.
+    pub async fn rehydrate(
+        &self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+    ) -> Result<Rehydrated, SlimeError> {
+        let Some(content) = self.kind.render(ctx, pool, self.subject_id).await? else {
+            // Take the buttons off, so nobody keeps clicking on something that's over.
+            let _ = self
+                .channel()
+                .edit_message(ctx, self.message(), EditMessage::new().components(vec![]))
+                .await;
+            retire(pool, self.message()).await?;
+            return Ok(Rehydrated::Retired);
+        };
+
+        let edit = self
+            .channel()
+            .edit_message(ctx, self.message(), content.clone().edit())
+            .await;
+        match edit {
+            Ok(_) => return Ok(Rehydrated::Refreshed),
+            Err(e) if http_status(&e) != Some(404) => return Err(e.into()),
+            Err(_) => {}
+        }
+
+        let message = self.channel().send_message(ctx, content.create()).await?;
+
+        let (table, column) = self.kind.message_column();
+        let mut tx = pool.begin().await?;
+        sqlx::query(&format!("UPDATE {table} SET {column} = $2 WHERE id = $1"))
+            .bind(self.subject_id)
+            .bind(message.id.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE bot_posts SET message_id = $2 WHERE message_id = $1")
+            .bind(self.message_id)
+            .bind(message.id.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(Rehydrated::Reposted)
+    }
+}
+
+/// Adds a message the bot just posted to the registry.
+pub async fn register<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    kind: PostKind,
+    subject_id: i64,
+    guild_id: GuildId,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO bot_posts (message_id, guild_id, channel_id, kind, subject_id)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (message_id) DO NOTHING",
+    )
+    .bind(message.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(message.channel_id.get() as i64)
+    .bind(kind)
+    .bind(subject_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Drops a message from the registry once the bot has deleted it or stopped looking after it.
+pub async fn retire<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    message: MessageId,
+) -> Result<(), SlimeError> {
+    sqlx::query("DELETE FROM bot_posts WHERE message_id = $1")
+        .bind(message.get() as i64)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Every registered post, optionally only in one guild.
+pub async fn all(pool: &PgPool, guild_id: Option<GuildId>) -> Result<Vec<Post>, SlimeError> {
+    Ok(sqlx::query_as::<_, Post>(
+        "SELECT * FROM bot_posts WHERE ($1::BIGINT IS NULL OR guild_id = $1) ORDER BY message_id",
+    )
+    .bind(guild_id.map(|id| id.get() as i64))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// What checking the whole registry found.
+#[derive(Debug, Default)]
+pub struct Reconciliation {
+    pub checked: usize,
+    /// Posts that had been deleted and were sent again.
+    pub reposted: Vec<Post>,
+    pub retired: usize,
+    /// Posts that couldn't be checked or sent again, usually for lack of access to their channel.
+    pub failed: Vec<(Post, String)>,
+}
+
+impl Reconciliation {
+    pub fn has_discrepancies(&self) -> bool {
+        !self.reposted.is_empty() || !self.failed.is_empty()
+    }
+
+    /// A summary for the bot's owners.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "Checked {} post(s) on startup: {} re-posted, {} retired, {} failed.",
+            self.checked,
+            self.reposted.len(),
+            self.retired,
+            self.failed.len()
+        );
+        for post in &self.reposted {
+            report.push_str(&format!(
+                "\n- Re-posted {:?} #{} in {}",
+                post.kind,
+                post.subject_id,
+                post.channel().mention()
+            ));
+        }
+        for (post, error) in &self.failed {
+            report.push_str(&format!(
+                "\n- Couldn't restore {:?} #{} in {}: {error}",
+                post.kind,
+                post.subject_id,
+                post.channel().mention()
+            ));
+        }
+        report
+    }
+}
+
+/// Rehydrates every registered post, so each one is confirmed to exist and still be editable.
+/// Each post waits its turn on `calls`, so members aren't kept waiting behind the batch.
+pub async fn reconcile(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    calls: &CallQueue,
+) -> Result<Reconciliation, SlimeError> {
+    let mut found = Reconciliation::default();
+    for post in all(pool, None).await? {
+        found.checked += 1;
+        calls.turn().await;
+        match post.rehydrate(ctx, pool).await {
+            Ok(Rehydrated::Refreshed) => {}
+            Ok(Rehydrated::Reposted) => found.reposted.push(post),
+            Ok(Rehydrated::Retired) => found.retired += 1,
+            Err(e) => {
+                error!(
+                    "Could not rehydrate {:?} post {}: {}",
+                    post.kind, post.message_id, e
+                );
+                found.failed.push((post, e.to_string()));
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(kind: PostKind, subject_id: i64) -> Post {
+        Post {
+            message_id: 100,
+            channel_id: 5,
+            kind,
+            subject_id,
+        }
+    }
+
+    #[test]
+    fn reconciliations_report_only_what_needs_a_look() {
+        let quiet = Reconciliation {
+            checked: 4,
+            retired: 1,
+            ..Default::default()
+        };
+        assert!(!quiet.has_discrepancies());
+        assert_eq!(
+            quiet.report(),
+            "Checked 4 post(s) on startup: 0 re-posted, 1 retired, 0 failed."
+        );
+
+        let found = Reconciliation {
+            checked: 3,
+            reposted: vec![post(PostKind::Raffle, 7)],
+            retired: 0,
+            failed: vec![(post(PostKind::LfgGroup, 8), "Missing Access".to_string())],
+        };
+        assert!(found.has_discrepancies());
+        assert_eq!(
+            found.report(),
+            "Checked 3 post(s) on startup: 1 re-posted, 0 retired, 1 failed.\n\
+             - Re-posted Raffle #7 in <#5>\n\
+             - Couldn't restore LfgGroup #8 in <#5>: Missing Access"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{quiet::QuietHours, Context, SlimeError};
+
+/// What a member has chosen for themselves. Members without a row get the defaults.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserPreferences {
+    /// Whether notifications are gathered into one DM a day.
+    pub digest: bool,
+    /// The hour of the day, in UTC, the digest is sent at.
+    pub digest_hour: i16,
+    pub quiet_start: Option<i16>,
+    pub quiet_end: Option<i16>,
+    /// Whether the member wants DMs about new events like ones they've been to.
+    pub suggestions: bool,
+    /// Whether the member's join anniversaries are celebrated in servers that celebrate them.
+    pub milestones: bool,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            digest: false,
+            digest_hour: 9,
+            quiet_start: None,
+            quiet_end: None,
+            suggestions: false,
+            milestones: true,
+        }
+    }
+}
+
+impl UserPreferences {
+    pub async fn load(pool: &PgPool, user: UserId) -> Result<Self, SlimeError> {
+        let preferences = sqlx::query_as::<_, UserPreferences>(
+            "SELECT digest, digest_hour, quiet_start, quiet_end, suggestions, milestones
+             FROM user_preferences
+             WHERE user_id = $1",
+        )
+        .bind(user.get() as i64)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(preferences.unwrap_or_default())
+    }
+
+    /// When the member has asked not to be sent anything that can wait, if ever.
+    pub fn quiet_hours(&self) -> Option<QuietHours> {
+        QuietHours::from_columns(self.quiet_start, self.quiet_end)
+    }
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Choose how the bot gets in touch with you.
+#[poise::command(
+    slash_command,
+    subcommands("show", "digest", "quiet_hours", "suggestions", "milestones")
+)]
+pub async fn preferences(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// See your current preferences.
+#[poise::command(slash_command)]
+async fn show(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let preferences = UserPreferences::load(&ctx.data().pool, ctx.author().id).await?;
+    let digest = if preferences.digest {
+        format!("once a day at {:02}:00 UTC", preferences.digest_hour)
+    } else {
+        "as they happen".to_string()
+    };
+    let quiet = match preferences.quiet_hours() {
+        Some(quiet) => format!(" Anything that can wait is held back from {quiet}."),
+        None => String::new(),
+    };
+    let suggestions = if preferences.suggestions {
+        " You're told about new events like ones you've been to."
+    } else {
+        ""
+    };
+    let milestones = if preferences.milestones {
+        ""
+    } else {
+        " Your join anniversaries aren't celebrated."
+    };
+    reply(
+        ctx,
+        format!("You get event reminders and updates {digest}.{quiet}{suggestions}{milestones}"),
+    )
+    .await
+}
+
+/// Get one DM a day with your upcoming events and updates, instead of one DM each.
+#[poise::command(slash_command)]
+async fn digest(
+    ctx: Context<'_>,
+    #[description = "Whether to gather notifications into a daily digest"] enabled: bool,
+    #[description = "Hour of the day to send it at, in UTC (default 9)"]
+    #[max = 23]
+    hour: Option<u8>,
+) -> Result<(), SlimeError> {
+    let current = UserPreferences::load(&ctx.data().pool, ctx.author().id).await?;
+    let hour = hour.map_or(current.digest_hour, i16::from);
+
+    sqlx::query(
+        "INSERT INTO user_preferences (user_id, digest, digest_hour) VALUES ($1, $2, $3)
+         ON CONFLICT (user_id) DO UPDATE SET
+            digest = EXCLUDED.digest, digest_hour = EXCLUDED.digest_hour",
+    )
+    .bind(ctx.author().id.get() as i64)
+    .bind(enabled)
+    .bind(hour)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let content = if enabled {
+        format!(
+            "You'll get one DM a day at {hour:02}:00 UTC with your upcoming events and any updates."
+        )
+    } else {
+        // Anything already held back is sent on the scheduler's next tick, so nothing is lost.
+        "You'll get reminders and updates as they happen again.".to_string()
+    };
+    reply(ctx, content).await
+}
+
+/// Have DMs that can wait held back during hours you don't want to be disturbed.
+#[poise::command(slash_command)]
+async fn quiet_hours(
+    ctx: Context<'_>,
+    #[description = "Hour quiet hours start at, in UTC; leave both empty to turn them off"]
+    #[max = 23]
+    start: Option<u8>,
+    #[description = "Hour they end at, in UTC"]
+    #[max = 23]
+    end: Option<u8>,
+) -> Result<(), SlimeError> {
+    let quiet = QuietHours::from_columns(start.map(i16::from), end.map(i16::from));
+    if quiet.is_none() && (start.is_some() || end.is_some()) {
+        return reply(ctx, "Quiet hours need both a start and a different end.").await;
+    }
+
+    sqlx::query(
+        "INSERT INTO user_preferences (user_id, quiet_start, quiet_end) VALUES ($1, $2, $3)
+         ON CONFLICT (user_id) DO UPDATE SET
+            quiet_start = EXCLUDED.quiet_start, quiet_end = EXCLUDED.quiet_end",
+    )
+    .bind(ctx.author().id.get() as i64)
+    .bind(quiet.map(|q| q.start as i16))
+    .bind(quiet.map(|q| q.end as i16))
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let content = match quiet {
+        Some(quiet) => format!(
+            "Updates that can wait are held back from {quiet} and sent once your quiet hours \
+            end. Reminders still come on time."
+        ),
+        None => "Your quiet hours are off.".to_string(),
+    };
+    reply(ctx, content).await
+}
+
+/// Get a DM when a new event looks like the ones you've been to.
+#[poise::command(slash_command)]
+async fn suggestions(
+    ctx: Context<'_>,
+    #[description = "Whether to be told about new events you might like"] enabled: bool,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO user_preferences (user_id, suggestions) VALUES ($1, $2)
+         ON CONFLICT (user_id) DO UPDATE SET suggestions = EXCLUDED.suggestions",
+    )
+    .bind(ctx.author().id.get() as i64)
+    .bind(enabled)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let content = if enabled {
+        "You'll get a DM when a new event shares tags with ones you've been to, in servers \
+        that suggest events."
+    } else {
+        "You won't be sent event suggestions."
+    };
+    reply(ctx, content).await
+}
+
+/// Choose whether servers celebrate the anniversary of the day you joined them.
+#[poise::command(slash_command)]
+async fn milestones(
+    ctx: Context<'_>,
+    #[description = "Whether your join anniversaries are celebrated"] enabled: bool,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO user_preferences (user_id, milestones) VALUES ($1, $2)
+         ON CONFLICT (user_id) DO UPDATE SET milestones = EXCLUDED.milestones",
+    )
+    .bind(ctx.author().id.get() as i64)
+    .bind(enabled)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let content = if enabled {
+        "Servers that celebrate milestones will celebrate the anniversary of the day you joined."
+    } else {
+        "Your join anniversaries won't be celebrated."
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use poise::{serenity_prelude::*, CreateReply, Modal};
+
+use crate::{
+    custom_id::{CustomId, Kind},
+    forms::{self, Form},
+    Context, SlimeError,
+};
+
+/// How long a preview waits for its invoker to decide.
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Something the bot is about to post for a member, shown to them first by [`run`].
+pub trait Preview {
+    /// The form the edit button opens.
+    type Form: Form;
+
+    /// The post as it will look.
+    async fn render(&self, ctx: Context<'_>) -> Result<CreateEmbed, SlimeError>;
+
+    /// The form filled in with the post as it stands.
+    fn form(&self) -> Self::Form;
+
+    /// Takes in what the member changed.
+    async fn edit(
+        &mut self,
+        ctx: Context<'_>,
+        fields: <Self::Form as Form>::Output,
+    ) -> Result<(), SlimeError>;
+
+    /// Posts it. Returns what to tell the member.
+    async fn publish(&mut self, ctx: Context<'_>) -> Result<String, SlimeError>;
+
+    /// Throws away whatever was set up for it, once the member discards it or walks away.
+    /// Returns what to tell them.
+    async fn discard(&mut self, ctx: Context<'_>) -> Result<String, SlimeError>;
+}
+
+fn buttons(ctx: Context<'_>) -> Vec<CreateActionRow> {
+    let button = |action, label, style| {
+        CreateButton::new(CustomId::for_invocation(Kind::Preview, action, ctx.id()))
+            .label(label)
+            .style(style)
+    };
+    vec![CreateActionRow::Buttons(vec![
+        button("publish", "Publish", ButtonStyle::Success),
+        button("edit", "Edit", ButtonStyle::Secondary),
+        button("discard", "Discard", ButtonStyle::Danger),
+    ])]
+}
+
+const NOTE: &str = "Only you can see this. Nothing is posted until you press **Publish**.";
+
+/// Shows the invoker `draft` as it will be posted, and posts it only once they press publish.
+/// They can edit it as often as they like first, and nothing goes out if they discard it or
+/// leave it.
+pub async fn run<D: Preview>(ctx: Context<'_>, mut draft: D) -> Result<(), SlimeError> {
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .content(NOTE)
+                .embed(draft.render(ctx).await?)
+                .components(buttons(ctx))
+                .ephemeral(true),
+        )
+        .await?;
+
+    let ids = ["publish", "edit", "discard"]
+        .map(|action| CustomId::for_invocation(Kind::Preview, action, ctx.id()).to_string());
+    loop {
+        let Some(press) = ComponentInteractionCollector::new(ctx)
+            .author_id(ctx.author().id)
+            .custom_ids(ids.to_vec())
+            .timeout(TIMEOUT)
+            .await
+        else {
+            let content = draft.discard(ctx).await?;
+            handle
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .content(format!("This preview timed out. {content}"))
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        };
+        let action = CustomId::parse(&press.data.custom_id).map(|c| c.action);
+
+        match action.as_deref() {
+            Some("edit") => {
+                let modal_id = press.id.to_string();
+                press
+                    .create_response(ctx, D::Form::create(Some(draft.form()), modal_id.clone()))
+                    .await?;
+                let Some((submitted, fields)) =
+                    forms::collect::<D::Form>(ctx.serenity_context(), ctx.author().id, modal_id)
+                        .await?
+                else {
+                    continue;
+                };
+                draft.edit(ctx, fields).await?;
+                submitted
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .content(NOTE)
+                                .embed(draft.render(ctx).await?)
+                                .components(buttons(ctx)),
+                        ),
+                    )
+                    .await?;
+            }
+            Some(action) => {
+                let content = if action == "publish" {
+                    draft.publish(ctx).await?
+                } else {
+                    draft.discard(ctx).await?
+                };
+                press
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .content(content)
+                                .embeds(vec![])
+                                .components(vec![]),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{
+    audit::{self, AuditEntry},
+    events::slots,
+    util::confirm,
+    Context, SlimeError,
+};
+
+/// What was done with a forgotten user's rows.
+#[derive(Debug, Default)]
+pub struct ForgetReport {
+    pub drafts_deleted: u64,
+    pub events_delinked: u64,
+    pub rsvps_anonymized: u64,
+    pub attendance_delinked: u64,
+}
+
+/// Removes `user` from every table, in every guild, and records that it happened.
+///
+/// Rows that other people's counts depend on (RSVPs, attendance, hosted events) are kept but
+/// re-keyed to a fresh negative ID, so they can no longer be tied back to the user. Anything only
+/// the user could see or that only describes them, like drafts and role snapshots, is deleted
+/// outright. New tables holding user IDs need adding here.
+pub async fn forget_user(
+    pool: &PgPool,
+    actor: UserId,
+    user: UserId,
+) -> Result<ForgetReport, SlimeError> {
+    let user_id = user.get() as i64;
+    let mut tx = pool.begin().await?;
+
+    let anonymous_id = sqlx::query_scalar::<_, i64>("SELECT -nextval('anonymous_user_ids')")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let drafts_deleted = sqlx::query("DELETE FROM events WHERE host_id = $1 AND status = 'draft'")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    sqlx::query("DELETE FROM points_ledger WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM lfg_queue WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM lfg_group_members WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM event_speakers WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    // Each event's schedule has the names of who's in its slots copied onto it.
+    let scheduled = sqlx::query_scalar::<_, i64>(
+        "DELETE FROM event_slot_signups u USING event_slots s
+         WHERE u.slot_id = s.id AND u.user_id = $1
+         RETURNING s.event_id",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    for event_id in scheduled.into_iter().collect::<HashSet<_>>() {
+        slots::sync(&mut tx, event_id).await?;
+    }
+    sqlx::query("DELETE FROM tag_subscriptions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM user_preferences WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    for table in [
+        "keyword_watches",
+        "keyword_watch_mutes",
+        "hosting_handbook_shown",
+    ] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE user_id = $1"))
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("DELETE FROM role_rotation_members WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE role_rotations SET holder_id = NULL WHERE holder_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE role_invariants SET drifted = array_remove(drifted, $1)")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM member_activity WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM milestones WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM timeout_appeals WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM verification_pending WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM raid_joins WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM help_questions WHERE author_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM digest_queue WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM notification_deliveries WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM role_snapshot_members WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "DELETE FROM permission_template_overwrites WHERE kind = 'member' AND target_id = $1",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    let mut report = ForgetReport {
+        drafts_deleted,
+        ..Default::default()
+    };
+    for (table, column, count) in [
+        ("events", "host_id", &mut report.events_delinked),
+        ("event_rsvps", "user_id", &mut report.rsvps_anonymized),
+        (
+            "event_attendance",
+            "user_id",
+            &mut report.attendance_delinked,
+        ),
+    ] {
+        *count = sqlx::query(&format!(
+            "UPDATE {table} SET {column} = $2 WHERE {column} = $1"
+        ))
+        .bind(user_id)
+        .bind(anonymous_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+
+    // Brackets, raffle draws, event ratings and other members' point histories have to keep
+    // reading correctly, so these are only re-keyed.
+    for (table, column) in [
+        ("event_feedback", "user_id"),
+        ("tournaments", "host_id"),
+        ("tournaments", "winner_id"),
+        ("tournament_players", "user_id"),
+        ("tournament_matches", "player1_id"),
+        ("tournament_matches", "player2_id"),
+        ("tournament_matches", "winner_id"),
+        ("tournament_matches", "reported_by"),
+        ("points_ledger", "awarded_by"),
+        ("points_seasons", "started_by"),
+        ("raffles", "host_id"),
+        ("raffle_entries", "user_id"),
+        ("tags", "created_by"),
+        ("faq_triggers", "created_by"),
+        ("link_rules", "created_by"),
+        ("retention_policies", "created_by"),
+        ("invite_exemptions", "added_by"),
+        ("raid_incidents", "resolved_by"),
+        ("verification_questions", "created_by"),
+        ("timeout_appeals", "decided_by"),
+        ("macros", "created_by"),
+        ("event_templates", "created_by"),
+        ("scheduled_announcements", "author_id"),
+    ] {
+        sqlx::query(&format!(
+            "UPDATE {table} SET {column} = $2 WHERE {column} = $1"
+        ))
+        .bind(user_id)
+        .bind(anonymous_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    // Claims on bring-lists live in an array on the event, so they're re-keyed in place.
+    sqlx::query("UPDATE events SET item_claims = array_replace(item_claims, $1, $2)")
+        .bind(user_id)
+        .bind(anonymous_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut *tx,
+        AuditEntry {
+            guild_id: None,
+            actor,
+            action: "forget_user",
+            target: Some(user.get()),
+            details: format!("{report:?}"),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+async fn confirm_and_forget(
+    ctx: Context<'_>,
+    user: UserId,
+    prompt: &str,
+) -> Result<(), SlimeError> {
+    if !confirm(ctx, prompt).await? {
+        ctx.send(
+            CreateReply::default()
+                .content("Nothing was deleted.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let report = forget_user(&ctx.data().pool, ctx.author().id, user).await?;
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Done. Deleted {} draft(s) and anonymized {} hosted event(s), {} RSVP(s) and {} attendance record(s).",
+                report.drafts_deleted,
+                report.events_delinked,
+                report.rsvps_anonymized,
+                report.attendance_delinked
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Delete everything the bot knows about you, in every server.
+#[poise::command(slash_command)]
+pub async fn forgetme(ctx: Context<'_>) -> Result<(), SlimeError> {
+    confirm_and_forget(
+        ctx,
+        ctx.author().id,
+        "This deletes your drafts and removes your name from every event you hosted, RSVP'd to or \
+         attended, in every server. Your streaks and badges can't be recovered. Continue?",
+    )
+    .await
+}
+
+/// Delete everything the bot knows about a user, on their behalf.
+#[poise::command(slash_command, owners_only, rename = "forget-user")]
+pub async fn forget_user_command(
+    ctx: Context<'_>,
+    #[description = "User to forget"] user: User,
+) -> Result<(), SlimeError> {
+    confirm_and_forget(
+        ctx,
+        user.id,
+        &format!(
+            "This deletes every draft of {} and anonymizes all their events, RSVPs and attendance \
+             in every server. Continue?",
+            user.mention()
+        ),
+    )
+    .await
+}
@@ -0,0 +1,254 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+
+use self::plan::{plan_purge, posted_at, PurgePlan, PAGE_SIZE};
+use crate::{
+    audit::{self, AuditEntry},
+    discord::{Batched, Discord},
+    quotas::Quota,
+    util::{confirm, rehearse},
+    Context, SlimeError,
+};
+
+pub mod plan;
+pub mod retention;
+
+const DEFAULT_LIMIT: u32 = 100;
+
+/// How many messages a purge deleted, and how.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Purged {
+    /// Deleted in batches of up to a hundred.
+    pub bulk: usize,
+    /// Too old to bulk-delete, or left over alone, so deleted one request at a time.
+    pub single: usize,
+}
+
+impl Purged {
+    pub fn total(&self) -> usize {
+        self.bulk + self.single
+    }
+}
+
+/// Up to `limit` messages in `channel` posted before `cutoff`, newest first.
+pub async fn collect(
+    discord: &impl Discord,
+    channel: ChannelId,
+    cutoff: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<Message>, SlimeError> {
+    let cutoff = cutoff.timestamp();
+    let mut found = Vec::new();
+    let mut before = None;
+    while found.len() < limit {
+        let page = discord.messages(channel, before, PAGE_SIZE as u8).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(last.id);
+        let exhausted = page.len() < PAGE_SIZE;
+        found.extend(page.into_iter().filter(|m| posted_at(m.id) < cutoff));
+        if exhausted {
+            break;
+        }
+    }
+    found.truncate(limit);
+
+    Ok(found)
+}
+
+/// Carries out `plan` in `channel`.
+pub async fn delete(
+    discord: &impl Discord,
+    channel: ChannelId,
+    plan: &PurgePlan,
+) -> Result<Purged, SlimeError> {
+    let mut purged = Purged::default();
+    for chunk in &plan.bulk {
+        discord.delete_messages(channel, chunk).await?;
+        purged.bulk += chunk.len();
+    }
+    for id in &plan.single {
+        discord.delete_message(channel, *id).await?;
+        purged.single += 1;
+    }
+
+    Ok(purged)
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Delete old messages in this channel.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    required_bot_permissions = "MANAGE_MESSAGES | READ_MESSAGE_HISTORY"
+)]
+pub async fn purge_old(
+    ctx: Context<'_>,
+    #[description = "Only delete messages older than this many days (default 0)"]
+    #[max = 3650]
+    older_than_days: Option<u32>,
+    #[description = "Most messages to delete (default 100)"]
+    #[min = 1]
+    #[max = 1000]
+    limit: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let channel = ctx.channel_id();
+    let limit_jobs = Quota::PurgeJobs.limit(&ctx.data().pool, guild_id).await?;
+    let _job = ctx
+        .data()
+        .purges
+        .start(guild_id, Quota::PurgeJobs, limit_jobs)?;
+    ctx.defer_ephemeral().await?;
+    // Answered, so the rest can run as a batch behind everyone else's interactions.
+    ctx.data().calls.close(ctx.id());
+
+    let cutoff = Utc::now() - Duration::days(older_than_days.unwrap_or(0) as i64);
+    let discord = &Batched {
+        inner: ctx.serenity_context(),
+        queue: &ctx.data().calls,
+    };
+    let messages = collect(
+        discord,
+        channel,
+        cutoff,
+        limit.unwrap_or(DEFAULT_LIMIT) as usize,
+    )
+    .await?;
+    let (Some(newest), Some(oldest)) = (messages.first(), messages.last()) else {
+        return reply(ctx, "There's nothing here old enough to delete.").await;
+    };
+
+    let ids = messages.iter().map(|m| m.id).collect::<Vec<_>>();
+    let planned = plan_purge(&ids, Utc::now());
+    let (count, eta) = (planned.total(), planned.eta().as_secs());
+    if rehearse(ctx, &format!("deleted {count} message(s) here.")).await? {
+        return Ok(());
+    }
+    if !confirm(
+        ctx,
+        &format!(
+            "The first message to be deleted is {}, the last is {}. That's {count} message(s), \
+             which takes about {} minute(s), continue?",
+            oldest.id.link(channel, Some(guild_id)),
+            newest.id.link(channel, Some(guild_id)),
+            eta.div_ceil(60)
+        ),
+    )
+    .await?
+    {
+        return reply(ctx, "Nothing was deleted.").await;
+    }
+
+    // Planned again, in case some messages got too old to bulk-delete while this was confirmed.
+    let purged = delete(discord, channel, &plan_purge(&ids, Utc::now())).await?;
+    audit::record(
+        &ctx.data().pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "purge_old",
+            target: Some(channel.get()),
+            details: format!("{purged:?}"),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    reply(ctx, format!("Deleted {} message(s).", purged.total())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan::BULK_MAX_AGE_DAYS, *};
+    use crate::discord::mock::{Call, MockDiscord};
+
+    const CHANNEL: ChannelId = ChannelId::new(1);
+
+    #[tokio::test]
+    async fn collect_pages_back_past_newer_messages() {
+        let discord = MockDiscord::new();
+        let now = Utc::now();
+        let old = (0..150)
+            .map(|i| discord.post(CHANNEL, now - Duration::days(30) + Duration::minutes(i)))
+            .collect::<Vec<_>>();
+        for i in 0..120 {
+            discord.post(CHANNEL, now - Duration::minutes(i));
+        }
+
+        let found = collect(&discord, CHANNEL, now - Duration::days(7), 1000)
+            .await
+            .unwrap();
+        let mut expected = old.clone();
+        expected.reverse();
+        assert_eq!(found.iter().map(|m| m.id).collect::<Vec<_>>(), expected);
+
+        let limited = collect(&discord, CHANNEL, now - Duration::days(7), 10)
+            .await
+            .unwrap();
+        assert_eq!(limited.len(), 10);
+        assert_eq!(limited[0].id, old[149]);
+    }
+
+    #[tokio::test]
+    async fn delete_bulk_deletes_recent_messages_in_hundreds() {
+        let discord = MockDiscord::new();
+        let now = Utc::now();
+        for i in 0..201 {
+            discord.post(CHANNEL, now - Duration::hours(1) - Duration::seconds(i));
+        }
+        for i in 0..3 {
+            discord.post(CHANNEL, now - Duration::days(20) - Duration::seconds(i));
+        }
+
+        let messages = collect(&discord, CHANNEL, now, 1000).await.unwrap();
+        let ids = messages.iter().map(|m| m.id).collect::<Vec<_>>();
+        let purged = delete(&discord, CHANNEL, &plan_purge(&ids, now))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            purged,
+            Purged {
+                bulk: 200,
+                single: 4
+            }
+        );
+        assert!(discord.remaining(CHANNEL).is_empty());
+        let bulk_calls = discord
+            .calls()
+            .into_iter()
+            .filter(|c| matches!(c, Call::DeleteMessages(..)))
+            .count();
+        assert_eq!(bulk_calls, 2);
+    }
+
+    #[tokio::test]
+    async fn delete_leaves_messages_near_the_bulk_limit_to_single_deletes() {
+        let discord = MockDiscord::new();
+        let now = Utc::now();
+        discord.post(
+            CHANNEL,
+            now - Duration::days(BULK_MAX_AGE_DAYS) + Duration::minutes(5),
+        );
+        discord.post(
+            CHANNEL,
+            now - Duration::days(BULK_MAX_AGE_DAYS) + Duration::minutes(6),
+        );
+
+        let messages = collect(&discord, CHANNEL, now, 1000).await.unwrap();
+        let ids = messages.iter().map(|m| m.id).collect::<Vec<_>>();
+        let purged = delete(&discord, CHANNEL, &plan_purge(&ids, now))
+            .await
+            .unwrap();
+
+        assert_eq!(purged, Purged { bulk: 0, single: 2 });
+    }
+}
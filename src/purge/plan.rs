@@ -0,0 +1,200 @@
+//! The arithmetic of a purge, kept free of Discord so it can be tested and benchmarked on its own.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::MessageId;
+
+/// Discord only bulk-deletes messages younger than this.
+pub const BULK_MAX_AGE_DAYS: i64 = 14;
+
+/// Messages this close to the bulk limit are deleted one by one, so none age out mid-purge.
+const BULK_MARGIN_SECS: i64 = 60 * 60;
+
+/// The most messages one bulk delete or one page of history can hold.
+pub const PAGE_SIZE: usize = 100;
+
+/// Roughly how long each request takes once Discord's per-channel rate limits kick in.
+const BULK_DELETE_TIME: Duration = Duration::from_secs(1);
+const SINGLE_DELETE_TIME: Duration = Duration::from_secs(1);
+
+/// When a message was posted, in Unix seconds, read from its ID.
+pub fn posted_at(id: MessageId) -> i64 {
+    id.created_at().unix_timestamp()
+}
+
+/// One request a purge makes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Between 2 and [`PAGE_SIZE`] recent messages at once.
+    Bulk(Vec<MessageId>),
+    Single(MessageId),
+}
+
+/// Every request a purge will make, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PurgePlan {
+    pub bulk: Vec<Vec<MessageId>>,
+    /// Too old to bulk-delete, or left over alone.
+    pub single: Vec<MessageId>,
+}
+
+impl PurgePlan {
+    fn add(&mut self, step: Step) {
+        match step {
+            Step::Bulk(ids) => self.bulk.push(ids),
+            Step::Single(id) => self.single.push(id),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.bulk.iter().map(Vec::len).sum::<usize>() + self.single.len()
+    }
+
+    /// About how long carrying the plan out will take.
+    pub fn eta(&self) -> Duration {
+        BULK_DELETE_TIME * self.bulk.len() as u32 + SINGLE_DELETE_TIME * self.single.len() as u32
+    }
+}
+
+/// Plans a purge a message at a time, so a pipeline can start deleting before it has fetched
+/// everything. Messages must come newest first, as Discord pages history.
+pub struct Planner {
+    bulk_cutoff: i64,
+    pending: Vec<MessageId>,
+}
+
+impl Planner {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            bulk_cutoff: (now - chrono::Duration::days(BULK_MAX_AGE_DAYS)).timestamp()
+                + BULK_MARGIN_SECS,
+            pending: Vec::with_capacity(PAGE_SIZE),
+        }
+    }
+
+    /// Adds the next message, returning a request that's ready to go if this completed one.
+    pub fn push(&mut self, id: MessageId) -> Option<Step> {
+        if posted_at(id) <= self.bulk_cutoff {
+            return Some(Step::Single(id));
+        }
+
+        self.pending.push(id);
+        (self.pending.len() == PAGE_SIZE).then(|| Step::Bulk(std::mem::take(&mut self.pending)))
+    }
+
+    /// The request for whatever is left over, if anything.
+    pub fn finish(self) -> Option<Step> {
+        // A bulk delete needs at least two messages.
+        match self.pending.as_slice() {
+            [] => None,
+            [id] => Some(Step::Single(*id)),
+            _ => Some(Step::Bulk(self.pending)),
+        }
+    }
+}
+
+/// Plans deleting `messages`, newest first, in bulk wherever Discord allows it.
+pub fn plan_purge(messages: &[MessageId], now: DateTime<Utc>) -> PurgePlan {
+    let mut plan = PurgePlan::default();
+    let mut planner = Planner::new(now);
+    for id in messages {
+        if let Some(step) = planner.push(*id) {
+            plan.add(step);
+        }
+    }
+    if let Some(step) = planner.finish() {
+        plan.add(step);
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    /// Milliseconds between the Unix epoch and Discord's, which snowflakes count from.
+    const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    /// `count` message IDs, newest first, the newest posted `age` before [`now`] and the rest a
+    /// second apart.
+    fn messages(count: i64, age: chrono::Duration) -> Vec<MessageId> {
+        (0..count)
+            .map(|i| {
+                let at = now() - age - chrono::Duration::seconds(i);
+                MessageId::new(((at.timestamp_millis() - DISCORD_EPOCH_MS) as u64) << 22)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recent_messages_go_in_hundreds() {
+        let plan = plan_purge(&messages(250, chrono::Duration::hours(1)), now());
+
+        let sizes = plan.bulk.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![100, 100, 50]);
+        assert!(plan.single.is_empty());
+    }
+
+    #[test]
+    fn a_lone_leftover_is_deleted_on_its_own() {
+        let plan = plan_purge(&messages(101, chrono::Duration::hours(1)), now());
+
+        assert_eq!(plan.bulk.len(), 1);
+        assert_eq!(plan.single.len(), 1);
+    }
+
+    #[test]
+    fn old_messages_and_those_near_the_limit_are_deleted_singly() {
+        let mut ids = messages(3, chrono::Duration::days(1));
+        ids.extend(messages(
+            2,
+            chrono::Duration::days(BULK_MAX_AGE_DAYS) - chrono::Duration::minutes(5),
+        ));
+        ids.extend(messages(4, chrono::Duration::days(30)));
+
+        let plan = plan_purge(&ids, now());
+
+        assert_eq!(plan.bulk, vec![ids[..3].to_vec()]);
+        assert_eq!(plan.single, ids[3..].to_vec());
+        assert_eq!(plan.total(), ids.len());
+    }
+
+    #[test]
+    fn eta_counts_requests_not_messages() {
+        let mut ids = messages(200, chrono::Duration::hours(1));
+        ids.extend(messages(10, chrono::Duration::days(30)));
+
+        let plan = plan_purge(&ids, now());
+
+        assert_eq!(plan.eta(), BULK_DELETE_TIME * 2 + SINGLE_DELETE_TIME * 10);
+    }
+
+    #[test]
+    fn streaming_plans_the_same_as_collecting() {
+        let mut ids = messages(150, chrono::Duration::hours(1));
+        ids.extend(messages(20, chrono::Duration::days(20)));
+
+        let mut streamed = PurgePlan::default();
+        let mut planner = Planner::new(now());
+        for page in ids.chunks(PAGE_SIZE) {
+            for id in page {
+                if let Some(step) = planner.push(*id) {
+                    streamed.add(step);
+                }
+            }
+        }
+        if let Some(step) = planner.finish() {
+            streamed.add(step);
+        }
+
+        assert_eq!(streamed, plan_purge(&ids, now()));
+    }
+}
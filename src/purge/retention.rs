@@ -0,0 +1,549 @@
+//! Retention policies: old messages in a channel deleted on a schedule, the way `/purge_old`
+//! would.
+
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use super::{collect, delete, plan::plan_purge, Purged};
+use crate::{
+    audit::{self, AuditEntry},
+    discord::{Batched, Discord},
+    quotas::{Quota, Running, RunningJob},
+    settings::GuildSettings,
+    util::http_status,
+    Context, Data, SlimeError,
+};
+
+/// How often each policy is enforced.
+const RUN_HOURS: i32 = 1;
+
+/// The most messages one run deletes from a channel, so a long backlog is worked through over
+/// several runs instead of holding a purge slot for hours.
+const MESSAGES_PER_RUN: usize = 300;
+
+const MAX_POLICIES: i64 = 25;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Policy {
+    channel_id: i64,
+    guild_id: i64,
+    max_age_days: i32,
+    paused: bool,
+    last_run_at: Option<DateTime<Utc>>,
+}
+
+impl Policy {
+    fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    /// Whether the policy should run at `now`.
+    fn due(&self, now: DateTime<Utc>) -> bool {
+        !self.paused
+            && self
+                .last_run_at
+                .is_none_or(|at| now - at >= Duration::hours(RUN_HOURS.into()))
+    }
+
+    fn describe(&self) -> String {
+        let mut line = format!(
+            "{}: messages older than {} day(s) are deleted",
+            self.channel().mention(),
+            self.max_age_days
+        );
+        if self.paused {
+            line.push_str(" (paused)");
+        } else if let Some(at) = self.last_run_at {
+            line.push_str(&format!(", last checked <t:{}:R>", at.timestamp()));
+        }
+        line
+    }
+}
+
+/// Deletes up to [`MESSAGES_PER_RUN`] of the messages the policy says are too old, returning
+/// whether there were more, left for the next run.
+async fn enforce(
+    discord: &impl Discord,
+    policy: &Policy,
+    now: DateTime<Utc>,
+) -> Result<(Purged, bool), SlimeError> {
+    let cutoff = now - Duration::days(policy.max_age_days.into());
+    // One over the limit, to tell whether there's more without deleting it.
+    let mut messages = collect(discord, policy.channel(), cutoff, MESSAGES_PER_RUN + 1).await?;
+    let more = messages.len() > MESSAGES_PER_RUN;
+    messages.truncate(MESSAGES_PER_RUN);
+    let ids = messages.iter().map(|m| m.id).collect::<Vec<_>>();
+    let purged = delete(discord, policy.channel(), &plan_purge(&ids, now)).await?;
+
+    Ok((purged, more))
+}
+
+/// Enforces one policy, then says what went in the guild's bot spam channel.
+async fn run(ctx: &SerenityContext, data: &Data, policy: &Policy, now: DateTime<Utc>) {
+    let pool = &data.pool;
+    let discord = &Batched {
+        inner: ctx,
+        queue: &data.calls,
+    };
+    let (purged, more) = match enforce(discord, policy, now).await {
+        Ok(done) => done,
+        // The channel is gone, so there's nothing left to keep tidy.
+        Err(SlimeError::SerenityError(e)) if http_status(&e) == Some(404) => {
+            let dropped = sqlx::query("DELETE FROM retention_policies WHERE channel_id = $1")
+                .bind(policy.channel_id)
+                .execute(pool)
+                .await;
+            if let Err(e) = dropped {
+                error!(
+                    "Could not drop retention policy of {}: {}",
+                    policy.channel(),
+                    e
+                );
+            }
+            return;
+        }
+        Err(e) => {
+            error!("Could not enforce retention in {}: {}", policy.channel(), e);
+            return;
+        }
+    };
+    if purged.total() == 0 {
+        return;
+    }
+
+    let bot = ctx.cache.current_user().id;
+    let recorded = audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(policy.guild()),
+            actor: bot,
+            action: "retention_purge",
+            target: Some(policy.channel().get()),
+            details: format!("older than {} day(s), {purged:?}", policy.max_age_days),
+            undo: Vec::new(),
+        },
+    )
+    .await;
+    if let Err(e) = recorded {
+        error!(
+            "Could not record retention purge in {}: {}",
+            policy.channel(),
+            e
+        );
+    }
+
+    let channel = match GuildSettings::load(pool, policy.guild()).await {
+        Ok(settings) => settings.bot_spam_channel(),
+        Err(e) => {
+            error!("Could not load settings of {}: {}", policy.guild(), e);
+            return;
+        }
+    };
+    let Some(channel) = channel else {
+        return;
+    };
+    let mut summary = format!(
+        "🧹 Deleted {} message(s) older than {} day(s) in {}.",
+        purged.total(),
+        policy.max_age_days,
+        policy.channel().mention()
+    );
+    if more {
+        summary.push_str(" There are more, which the next runs will get to.");
+    }
+    if let Err(e) = channel.say(ctx, summary).await {
+        error!("Could not post retention summary in {}: {}", channel, e);
+    }
+}
+
+/// The policies to run next, most overdue first, each with the purge slot it takes. Runs share
+/// the guild's slots with `/purge_old`, and a policy there's no slot for is left out so it's
+/// first in line at the next tick, rather than waiting out another [`RUN_HOURS`].
+fn next_runs(
+    policies: Vec<Policy>,
+    now: DateTime<Utc>,
+    running: &Arc<Running>,
+    limits: &HashMap<i64, i64>,
+) -> Vec<(Policy, RunningJob)> {
+    let mut due = policies
+        .into_iter()
+        .filter(|policy| policy.due(now))
+        .collect::<Vec<_>>();
+    // Never run sorts first.
+    due.sort_by_key(|policy| policy.last_run_at);
+    due.into_iter()
+        .filter_map(|policy| {
+            let limit = *limits.get(&policy.guild_id)?;
+            let job = running
+                .start(policy.guild(), Quota::PurgeJobs, limit)
+                .ok()?;
+            Some((policy, job))
+        })
+        .collect()
+}
+
+/// Starts a run for each policy that's due and can get a purge slot. They go on in the
+/// background, since deleting old messages one at a time can take minutes.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let policies = sqlx::query_as::<_, Policy>(
+        "SELECT * FROM retention_policies
+         WHERE NOT paused
+            AND (last_run_at IS NULL OR last_run_at <= $1 - make_interval(hours => $2))
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(now)
+    .bind(RUN_HOURS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut limits = HashMap::new();
+    for policy in &policies {
+        if limits.contains_key(&policy.guild_id) {
+            continue;
+        }
+        // One guild's trouble shouldn't hold up the others' runs.
+        match Quota::PurgeJobs.limit(pool, policy.guild()).await {
+            Ok(limit) => {
+                limits.insert(policy.guild_id, limit);
+            }
+            Err(e) => error!("Could not load purge quota of {}: {}", policy.guild(), e),
+        }
+    }
+
+    for (policy, job) in next_runs(policies, now, &data.purges, &limits) {
+        // Claimed before starting, so a slow run can't be picked up again by the next tick.
+        let claimed = sqlx::query(
+            "UPDATE retention_policies SET last_run_at = $2
+             WHERE channel_id = $1 AND last_run_at IS NOT DISTINCT FROM $3",
+        )
+        .bind(policy.channel_id)
+        .bind(now)
+        .bind(policy.last_run_at)
+        .execute(pool)
+        .await;
+        match claimed {
+            Ok(claimed) if claimed.rows_affected() > 0 => {}
+            Ok(_) => continue,
+            Err(e) => {
+                error!(
+                    "Could not claim retention run of {}: {}",
+                    policy.channel(),
+                    e
+                );
+                continue;
+            }
+        }
+        let (ctx, data) = (ctx.clone(), data.clone());
+        tokio::spawn(async move {
+            run(&ctx, &data, &policy, now).await;
+            drop(job);
+        });
+    }
+    Ok(())
+}
+
+/// Whether the bot can read back through `channel` and delete what it finds, going by the cache.
+/// Anything the cache doesn't know counts as no.
+fn can_tidy(ctx: &SerenityContext, channel: &GuildChannel) -> bool {
+    let bot = ctx.cache.current_user().id;
+    let Some(guild) = ctx.cache.guild(channel.guild_id) else {
+        return false;
+    };
+    let Some(member) = guild.members.get(&bot) else {
+        return false;
+    };
+    guild
+        .user_permissions_in(channel, member)
+        .contains(Permissions::MANAGE_MESSAGES | Permissions::READ_MESSAGE_HISTORY)
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Delete old messages in channels automatically.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("set", "remove", "pause", "resume", "list")
+)]
+pub async fn retention(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Delete messages in a channel once they're a certain age, replacing its policy if it has one.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel to keep tidy"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "Delete messages older than this many days"]
+    #[min = 1]
+    #[max = 3650]
+    days: u32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    // Policies run unattended, so one the bot can't carry out would fail every hour, seen only in
+    // the logs.
+    if !can_tidy(ctx.serenity_context(), &channel) {
+        return reply(
+            ctx,
+            format!(
+                "I need the Manage Messages and Read Message History permissions in {} to \
+                 delete old messages there.",
+                channel.mention()
+            ),
+        )
+        .await;
+    }
+    let pool = &ctx.data().pool;
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM retention_policies WHERE guild_id = $1 AND channel_id <> $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.id.get() as i64)
+    .fetch_one(pool)
+    .await?;
+    if count >= MAX_POLICIES {
+        return reply(
+            ctx,
+            format!("A server can have up to {MAX_POLICIES} retention policies."),
+        )
+        .await;
+    }
+
+    let policy = sqlx::query_as::<_, Policy>(
+        "INSERT INTO retention_policies (channel_id, guild_id, max_age_days, created_by)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (channel_id) DO UPDATE SET max_age_days = EXCLUDED.max_age_days
+         RETURNING *",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(days as i32)
+    .bind(ctx.author().id.get() as i64)
+    .fetch_one(pool)
+    .await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "retention_set",
+            target: Some(channel.id.get()),
+            details: format!("older than {days} day(s)"),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    let mut content = format!("{}.", policy.describe());
+    if policy.paused {
+        content.push_str(" Resume it with `/retention resume` to start deleting again.");
+    } else {
+        content.push_str(
+            " Each run's summary goes to the bot spam channel, see `/settings bot_spam`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// Stop deleting old messages in a channel.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Channel to leave alone"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let removed =
+        sqlx::query("DELETE FROM retention_policies WHERE channel_id = $1 AND guild_id = $2")
+            .bind(channel.id.get() as i64)
+            .bind(guild_id.get() as i64)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Old messages in {} will be left alone.", channel.mention())
+    } else {
+        format!("{} has no retention policy.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Pauses or resumes the policy for `channel`, returning whether it has one.
+async fn set_paused(
+    ctx: Context<'_>,
+    channel: &GuildChannel,
+    paused: bool,
+) -> Result<bool, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let changed = sqlx::query(
+        "UPDATE retention_policies SET paused = $3 WHERE channel_id = $1 AND guild_id = $2",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(paused)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+    Ok(changed > 0)
+}
+
+/// Stop deleting old messages in a channel for now, keeping its policy.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn pause(
+    ctx: Context<'_>,
+    #[description = "Channel to stop tidying for now"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let content = if set_paused(ctx, &channel, true).await? {
+        format!(
+            "Paused deleting old messages in {}. Pick up again with `/retention resume`.",
+            channel.mention()
+        )
+    } else {
+        format!("{} has no retention policy.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Start deleting old messages in a channel again.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn resume(
+    ctx: Context<'_>,
+    #[description = "Channel to tidy again"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let content = if set_paused(ctx, &channel, false).await? {
+        format!(
+            "Old messages in {} will be deleted again from the next run.",
+            channel.mention()
+        )
+    } else {
+        format!("{} has no retention policy.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Show which channels have old messages deleted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let policies = sqlx::query_as::<_, Policy>(
+        "SELECT * FROM retention_policies WHERE guild_id = $1 ORDER BY channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if policies.is_empty() {
+        return reply(ctx, "No channels have old messages deleted.").await;
+    }
+    let lines = policies
+        .iter()
+        .map(|policy| format!("• {}", policy.describe()))
+        .collect::<Vec<_>>();
+    reply(ctx, lines.join("\n")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{Clock, SimulatedClock},
+        discord::mock::MockDiscord,
+    };
+
+    fn policy(channel_id: i64) -> Policy {
+        Policy {
+            channel_id,
+            guild_id: 1,
+            max_age_days: 30,
+            paused: false,
+            last_run_at: None,
+        }
+    }
+
+    #[test]
+    fn policies_without_a_slot_go_first_next_time() {
+        let clock = SimulatedClock::starting_at(Utc::now());
+        let running = Arc::new(Running::default());
+        let limits = HashMap::from([(1, Quota::PurgeJobs.default_limit())]);
+        let mut policies = vec![policy(1), policy(2), policy(3)];
+        // What the tick's claim does to the rows it starts.
+        let tick = |policies: &mut Vec<Policy>| {
+            let now = clock.now();
+            let started = next_runs(policies.clone(), now, &running, &limits)
+                .into_iter()
+                .map(|(policy, _job)| policy.channel_id)
+                .collect::<Vec<_>>();
+            for policy in policies.iter_mut() {
+                if started.contains(&policy.channel_id) {
+                    policy.last_run_at = Some(now);
+                }
+            }
+            started
+        };
+
+        assert_eq!(tick(&mut policies), [1, 2]);
+        clock.advance(Duration::minutes(1));
+        // The one left out is still due, and its slot is free once the others are done.
+        assert_eq!(tick(&mut policies), [3]);
+        clock.advance(Duration::minutes(1));
+        assert!(tick(&mut policies).is_empty());
+        clock.advance(Duration::hours(RUN_HOURS.into()));
+        assert_eq!(tick(&mut policies), [1, 2]);
+        clock.advance(Duration::minutes(1));
+        assert_eq!(tick(&mut policies), [3]);
+
+        // A slot taken by `/purge_old` leaves one for the most overdue policy.
+        let _purge = running.start(GuildId::new(1), Quota::PurgeJobs, 2).unwrap();
+        clock.advance(Duration::hours(RUN_HOURS.into()));
+        assert_eq!(tick(&mut policies), [1]);
+    }
+
+    #[tokio::test]
+    async fn runs_delete_old_messages_a_batch_at_a_time() {
+        let discord = MockDiscord::new();
+        let now = Utc::now();
+        let policy = policy(1);
+        for i in 0..(MESSAGES_PER_RUN as i64 + 5) {
+            discord.post(
+                policy.channel(),
+                now - Duration::days(40) + Duration::minutes(i),
+            );
+        }
+        let recent = discord.post(policy.channel(), now - Duration::days(2));
+
+        let (purged, more) = enforce(&discord, &policy, now).await.unwrap();
+        assert_eq!(purged.total(), MESSAGES_PER_RUN);
+        assert!(more);
+
+        let (purged, more) = enforce(&discord, &policy, now).await.unwrap();
+        assert_eq!(purged.total(), 5);
+        assert!(!more);
+        assert_eq!(discord.remaining(policy.channel()), [recent]);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+
+use chrono::Duration;
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+
+use crate::{i18n::Voice, settings::GuildSettings, Context, Data, SlimeError};
+
+/// Questions with fewer words than this left to compare are too short to tell apart.
+const MIN_WORDS: usize = 4;
+
+/// How many earlier questions a new one is compared with, most recent first.
+const CANDIDATES: i64 = 500;
+
+/// How many earlier questions a reply links to.
+const MAX_LINKS: usize = 3;
+
+/// Words that say nothing about what a question is about.
+const STOPWORDS: &[&str] = &[
+    "about", "and", "any", "anyone", "are", "but", "can", "could", "does", "for", "from", "get",
+    "has", "have", "hello", "help", "hey", "how", "is", "its", "just", "know", "not", "please",
+    "should", "that", "the", "thanks", "there", "this", "what", "when", "where", "which", "who",
+    "why", "will", "with", "would", "you", "your",
+];
+
+/// The distinct words of `text` worth comparing, in order.
+fn words(text: &str) -> Vec<String> {
+    let mut words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3 && !w.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .collect::<Vec<_>>();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// How much two questions' words overlap, from 0 to 1: the words they share out of all the words
+/// either uses.
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let a = a.iter().collect::<HashSet<_>>();
+    let b = b.iter().collect::<HashSet<_>>();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct HelpChannel {
+    channel_id: i64,
+    similarity_percent: i32,
+    window_days: i32,
+}
+
+impl HelpChannel {
+    fn describe(&self) -> String {
+        format!(
+            "{}: questions {}% alike within {} day(s)",
+            ChannelId::new(self.channel_id as u64).mention(),
+            self.similarity_percent,
+            self.window_days
+        )
+    }
+}
+
+async fn help_channel(
+    pool: &PgPool,
+    channel_id: ChannelId,
+) -> Result<Option<HelpChannel>, SlimeError> {
+    Ok(sqlx::query_as::<_, HelpChannel>(
+        "SELECT channel_id, similarity_percent, window_days FROM help_channels
+         WHERE channel_id = $1 AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(channel_id.get() as i64)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Where `message` was asked and what it asks, if it's a question in a help channel: a post in a
+/// help forum, with its title, or a message with a question mark in a help text channel.
+async fn question(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    message: &Message,
+) -> Result<Option<(HelpChannel, String)>, SlimeError> {
+    if message.message_reference.is_some() {
+        return Ok(None);
+    }
+    if let Some(channel) = help_channel(pool, message.channel_id).await? {
+        let asked = message.content.contains('?');
+        return Ok(asked.then(|| (channel, message.content.clone())));
+    }
+    // A forum post's opening message shares its ID with the post.
+    if message.id.get() != message.channel_id.get() {
+        return Ok(None);
+    }
+    let Some(thread) = message.channel(ctx).await?.guild() else {
+        return Ok(None);
+    };
+    let Some(parent) = thread.parent_id else {
+        return Ok(None);
+    };
+    Ok(help_channel(pool, parent)
+        .await?
+        .map(|channel| (channel, format!("{}\n{}", thread.name, message.content))))
+}
+
+/// Answers a question in a help channel with links to recent ones it looks like, so members can
+/// find an answer that's already been given. Only runs with the guild's consent to reading
+/// message content.
+pub async fn noticed(
+    ctx: &SerenityContext,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    let pool = &data.pool;
+    let Some((channel, text)) = question(ctx, pool, message).await? else {
+        return Ok(());
+    };
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    if !settings.message_content_consent {
+        return Ok(());
+    }
+    let asked = words(&text);
+    if asked.len() < MIN_WORDS {
+        return Ok(());
+    }
+
+    let now = data.clock.now();
+    sqlx::query("DELETE FROM help_questions WHERE channel_id = $1 AND asked_at < $2")
+        .bind(channel.channel_id)
+        .bind(now - Duration::days(channel.window_days.into()))
+        .execute(pool)
+        .await?;
+    let earlier = sqlx::query_as::<_, (i64, i64, Vec<String>)>(
+        "SELECT message_id, posted_in, words FROM help_questions
+         WHERE channel_id = $1 ORDER BY asked_at DESC LIMIT $2",
+    )
+    .bind(channel.channel_id)
+    .bind(CANDIDATES)
+    .fetch_all(pool)
+    .await?;
+    sqlx::query(
+        "INSERT INTO help_questions
+            (message_id, channel_id, posted_in, guild_id, author_id, words, asked_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         ON CONFLICT (message_id) DO NOTHING",
+    )
+    .bind(message.id.get() as i64)
+    .bind(channel.channel_id)
+    .bind(message.channel_id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(message.author.id.get() as i64)
+    .bind(&asked)
+    .bind(now)
+    .execute(pool)
+    .await?;
+
+    let threshold = f64::from(channel.similarity_percent) / 100.0;
+    let mut alike = earlier
+        .iter()
+        .map(|(id, posted_in, words)| (similarity(&asked, words), *id, *posted_in))
+        .filter(|(score, ..)| *score >= threshold)
+        .collect::<Vec<_>>();
+    if alike.is_empty() {
+        return Ok(());
+    }
+    alike.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let links = alike
+        .iter()
+        .take(MAX_LINKS)
+        .map(|&(_, id, posted_in)| {
+            let link = if posted_in == channel.channel_id {
+                MessageId::new(id as u64).link(ChannelId::new(posted_in as u64), Some(guild_id))
+            } else {
+                ChannelId::new(posted_in as u64).mention().to_string()
+            };
+            format!("• {link}")
+        })
+        .collect::<Vec<_>>();
+    let voice = Voice::of(ctx, guild_id, &settings);
+    message
+        .channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(format!(
+                    "{}\n{}",
+                    voice.t("question-asked-before"),
+                    links.join("\n")
+                ))
+                .reference_message(message)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Point members at recent answers when they ask something that's been asked before.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    subcommands("watch", "stop", "list")
+)]
+pub async fn questions(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Check questions in a help channel against recent ones, or change how closely.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_CHANNELS")]
+async fn watch(
+    ctx: Context<'_>,
+    #[description = "Help channel or forum"]
+    #[channel_types("Text", "Forum")]
+    channel: GuildChannel,
+    #[description = "How alike two questions' words must be, in percent (default 60)"]
+    #[min = 20]
+    #[max = 100]
+    similarity: Option<u32>,
+    #[description = "How many days back to look (default 14)"]
+    #[min = 1]
+    #[max = 90]
+    days: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let watched = sqlx::query_as::<_, HelpChannel>(
+        "INSERT INTO help_channels (channel_id, guild_id, similarity_percent, window_days)
+         VALUES ($1, $2, COALESCE($3, 60), COALESCE($4, 14))
+         ON CONFLICT (channel_id) DO UPDATE SET
+            similarity_percent = COALESCE($3, help_channels.similarity_percent),
+            window_days = COALESCE($4, help_channels.window_days)
+         RETURNING channel_id, similarity_percent, window_days",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(similarity.map(|s| s as i32))
+    .bind(days.map(|d| d as i32))
+    .fetch_one(pool)
+    .await?;
+
+    let mut content = format!("Watching {}.", watched.describe());
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        content.push_str(
+            "\nQuestions are only read once message content features are allowed with \
+             `/settings message_content`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// Stop checking questions in a help channel, and forget the ones it had.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_CHANNELS")]
+async fn stop(
+    ctx: Context<'_>,
+    #[description = "Help channel or forum"]
+    #[channel_types("Text", "Forum")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let removed = sqlx::query("DELETE FROM help_channels WHERE channel_id = $1 AND guild_id = $2")
+        .bind(channel.id.get() as i64)
+        .bind(guild_id.get() as i64)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Questions in {} are no longer checked.", channel.mention())
+    } else {
+        format!("{} wasn't being watched.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Show which help channels are watched.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_CHANNELS")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let channels = sqlx::query_as::<_, HelpChannel>(
+        "SELECT channel_id, similarity_percent, window_days FROM help_channels
+         WHERE guild_id = $1 ORDER BY channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if channels.is_empty() {
+        return reply(ctx, "No help channels are watched.").await;
+    }
+    let lines = channels
+        .iter()
+        .map(|c| format!("• {}", c.describe()))
+        .collect::<Vec<_>>();
+    reply(ctx, lines.join("\n")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rephrased_questions_are_alike() {
+        let first = words("How do I link my Steam account to the bot?");
+        let again = words("hey, how can I link a steam account to the bot??");
+        let other = words("Where is the schedule for the raid tournament?");
+
+        assert_eq!(first, vec!["account", "bot", "link", "steam"]);
+        assert_eq!(similarity(&first, &again), 1.0);
+        assert!(similarity(&first, &other) < 0.2);
+        // Mentions and IDs don't count as words.
+        assert_eq!(words("<@123456789012345678> 2024"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{collections::HashSet, fmt};
+
+use chrono::{DateTime, Timelike, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+
+use crate::{settings::GuildSettings, SlimeError};
+
+/// Hours of the day, in UTC, when notifications that can wait are held back. They run from
+/// `start` up to `end`, wrapping past midnight when `end` is earlier, so 22 to 7 is overnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: u8,
+    pub end: u8,
+}
+
+impl QuietHours {
+    /// Quiet hours from a pair of nullable columns. Both have to be set.
+    pub fn from_columns(start: Option<i16>, end: Option<i16>) -> Option<Self> {
+        match (start, end) {
+            (Some(start), Some(end)) if start != end => Some(Self {
+                start: start as u8,
+                end: end as u8,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn contains(self, hour: u32) -> bool {
+        let (start, end) = (self.start as u32, self.end as u32);
+        if start < end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:00 to {:02}:00 UTC", self.start, self.end)
+    }
+}
+
+/// Whether `hour` is quiet for any of `hours`.
+pub fn is_quiet(hours: &[Option<QuietHours>], hour: u32) -> bool {
+    hours.iter().flatten().any(|quiet| quiet.contains(hour))
+}
+
+/// Which of `users` are in quiet hours at `now`, their own or the guild's.
+pub async fn quiet_now(
+    pool: &PgPool,
+    guild_id: GuildId,
+    users: &[UserId],
+    now: DateTime<Utc>,
+) -> Result<HashSet<UserId>, SlimeError> {
+    let guild = GuildSettings::load(pool, guild_id).await?.quiet_hours();
+    if is_quiet(&[guild], now.hour()) {
+        return Ok(users.iter().copied().collect());
+    }
+
+    let ids = users.iter().map(|u| u.get() as i64).collect::<Vec<_>>();
+    let members = sqlx::query_as::<_, (i64, Option<i16>, Option<i16>)>(
+        "SELECT user_id, quiet_start, quiet_end FROM user_preferences WHERE user_id = ANY($1)",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members
+        .into_iter()
+        .filter(|(_, start, end)| is_quiet(&[QuietHours::from_columns(*start, *end)], now.hour()))
+        .map(|(id, _, _)| UserId::new(id as u64))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_hours_can_wrap_past_midnight() {
+        let overnight = QuietHours { start: 22, end: 7 };
+        let quiet = (0..24)
+            .filter(|h| overnight.contains(*h))
+            .collect::<Vec<_>>();
+        assert_eq!(quiet, vec![0, 1, 2, 3, 4, 5, 6, 22, 23]);
+
+        let afternoon = QuietHours { start: 13, end: 15 };
+        let quiet = (0..24)
+            .filter(|h| afternoon.contains(*h))
+            .collect::<Vec<_>>();
+        assert_eq!(quiet, vec![13, 14]);
+    }
+
+    #[test]
+    fn either_the_guild_or_the_member_can_make_it_quiet() {
+        let guild = QuietHours::from_columns(Some(0), Some(6));
+        let member = QuietHours::from_columns(Some(22), Some(0));
+        assert!(is_quiet(&[guild, member], 23));
+        assert!(is_quiet(&[guild, member], 3));
+        assert!(!is_quiet(&[guild, member], 12));
+        // Unset or empty quiet hours never are.
+        assert_eq!(QuietHours::from_columns(Some(5), Some(5)), None);
+        assert!(!is_quiet(
+            &[None, QuietHours::from_columns(None, Some(3))],
+            1
+        ));
+    }
+}
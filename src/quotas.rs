@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use poise::{serenity_prelude::*, ChoiceParameter, CreateReply};
+use sqlx::PgPool;
+
+use crate::{
+    audit::{self, AuditEntry},
+    Context, SlimeError,
+};
+
+/// Something a guild only gets so much of, so one busy guild can't starve the rest of a shared
+/// instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Quota {
+    #[name = "Purges running at once"]
+    PurgeJobs,
+    /// Drafts, pending and published events.
+    #[name = "Active events"]
+    ActiveEvents,
+}
+
+impl Quota {
+    const ALL: [Quota; 2] = [Quota::PurgeJobs, Quota::ActiveEvents];
+
+    fn column(self) -> &'static str {
+        match self {
+            Quota::PurgeJobs => "max_purge_jobs",
+            Quota::ActiveEvents => "max_active_events",
+        }
+    }
+
+    /// The limit for guilds the owner hasn't set one for.
+    pub fn default_limit(self) -> i64 {
+        match self {
+            Quota::PurgeJobs => 2,
+            Quota::ActiveEvents => 200,
+        }
+    }
+
+    /// What the limit is on, for errors shown to members.
+    fn noun(self) -> &'static str {
+        match self {
+            Quota::PurgeJobs => "purges running at once",
+            Quota::ActiveEvents => "active events",
+        }
+    }
+
+    /// The guild's limit, set by the owner or the default.
+    pub async fn limit(self, pool: &PgPool, guild_id: GuildId) -> Result<i64, SlimeError> {
+        let limit = sqlx::query_scalar::<_, Option<i32>>(&format!(
+            "SELECT {} FROM guild_quotas WHERE guild_id = $1",
+            self.column()
+        ))
+        .bind(guild_id.get() as i64)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+
+        Ok(limit.map_or(self.default_limit(), i64::from))
+    }
+}
+
+/// Checks that the guild has room for `adding` more active events.
+pub async fn ensure_events(
+    pool: &PgPool,
+    guild_id: GuildId,
+    adding: usize,
+) -> Result<(), SlimeError> {
+    let limit = Quota::ActiveEvents.limit(pool, guild_id).await?;
+    let active = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM events
+         WHERE guild_id = $1 AND status IN ('draft', 'pending', 'published')",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_one(pool)
+    .await?;
+
+    if active + adding as i64 > limit {
+        return Err(SlimeError::QuotaExceeded(Quota::ActiveEvents.noun(), limit));
+    }
+    Ok(())
+}
+
+/// Jobs each guild has running on this instance. Only the active instance runs jobs, so this is
+/// every job there is.
+#[derive(Default)]
+pub struct Running {
+    jobs: Mutex<HashMap<GuildId, i64>>,
+}
+
+impl Running {
+    /// Takes one of the guild's `limit` slots, or fails if they're all in use. The slot is given
+    /// back when the returned job is dropped.
+    pub fn start(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        quota: Quota,
+        limit: i64,
+    ) -> Result<RunningJob, SlimeError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let running = jobs.entry(guild_id).or_default();
+        if *running >= limit {
+            return Err(SlimeError::QuotaExceeded(quota.noun(), limit));
+        }
+        *running += 1;
+
+        Ok(RunningJob {
+            running: Arc::clone(self),
+            guild_id,
+        })
+    }
+}
+
+/// A slot held by a running job.
+pub struct RunningJob {
+    running: Arc<Running>,
+    guild_id: GuildId,
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        let mut jobs = self.running.jobs.lock().unwrap();
+        if let Some(running) = jobs.get_mut(&self.guild_id) {
+            *running -= 1;
+            if *running <= 0 {
+                jobs.remove(&self.guild_id);
+            }
+        }
+    }
+}
+
+fn parse_guild(guild: &str) -> Result<GuildId, SlimeError> {
+    guild
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(GuildId::new)
+        .ok_or_else(|| SlimeError::InvalidNumber(guild.to_string()))
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Manage how much of the bot each server may use.
+#[poise::command(slash_command, owners_only, subcommands("show", "set"))]
+pub async fn quota(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// See a server's limits.
+#[poise::command(slash_command, owners_only)]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "ID of the server, this one if left out"] guild: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = match guild {
+        Some(guild) => parse_guild(&guild)?,
+        None => ctx.guild_id().ok_or(SlimeError::NotInGuild)?,
+    };
+
+    let mut content = format!("Limits for server {guild_id}:");
+    for quota in Quota::ALL {
+        let limit = quota.limit(&ctx.data().pool, guild_id).await?;
+        let default = if limit == quota.default_limit() {
+            " (default)"
+        } else {
+            ""
+        };
+        content.push_str(&format!("\n- {}: {limit}{default}", quota.name()));
+    }
+    reply(ctx, content).await
+}
+
+/// Change one of a server's limits, or put it back to the default.
+#[poise::command(slash_command, owners_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Which limit to change"] quota: Quota,
+    #[description = "New limit, the default if left out"]
+    #[max = 100000]
+    limit: Option<u32>,
+    #[description = "ID of the server, this one if left out"] guild: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = match guild {
+        Some(guild) => parse_guild(&guild)?,
+        None => ctx.guild_id().ok_or(SlimeError::NotInGuild)?,
+    };
+    let pool = &ctx.data().pool;
+
+    sqlx::query(&format!(
+        "INSERT INTO guild_quotas (guild_id, {column}) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET {column} = EXCLUDED.{column}",
+        column = quota.column()
+    ))
+    .bind(guild_id.get() as i64)
+    .bind(limit.map(|l| l as i32))
+    .execute(pool)
+    .await?;
+
+    let limit = match limit {
+        Some(limit) => limit.to_string(),
+        None => format!("the default of {}", quota.default_limit()),
+    };
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: None,
+            actor: ctx.author().id,
+            action: "set_quota",
+            target: Some(guild_id.get()),
+            details: format!("{}: {limit}", quota.column()),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    reply(
+        ctx,
+        format!("{} for server {guild_id} is now {limit}.", quota.name()),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_jobs_give_their_slot_back() {
+        let running = Arc::new(Running::default());
+        let guild = GuildId::new(1);
+        let other = GuildId::new(2);
+
+        let first = running.start(guild, Quota::PurgeJobs, 2).unwrap();
+        let _second = running.start(guild, Quota::PurgeJobs, 2).unwrap();
+        assert!(matches!(
+            running.start(guild, Quota::PurgeJobs, 2),
+            Err(SlimeError::QuotaExceeded(_, 2))
+        ));
+        // Other guilds have their own slots.
+        assert!(running.start(other, Quota::PurgeJobs, 2).is_ok());
+
+        drop(first);
+        assert!(running.start(guild, Quota::PurgeJobs, 2).is_ok());
+    }
+}
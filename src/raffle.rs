@@ -0,0 +1,613 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    forms::Form,
+    points::current_season,
+    posts::{self, PostContent, PostKind},
+    preview::{self, Preview},
+    undo::UndoStep,
+    util::{rehearse, respond_ephemeral},
+    Context, Data, SlimeError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Weighting {
+    #[name = "Everyone equal"]
+    Flat,
+    #[name = "Events attended"]
+    Attendance,
+    #[name = "Points this season"]
+    Points,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum RaffleStatus {
+    Open,
+    Drawn,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Raffle {
+    id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    host_id: i64,
+    prize: String,
+    weighting: Weighting,
+    winners: i32,
+    status: RaffleStatus,
+    seed: Option<i64>,
+}
+
+/// SplitMix64, so anyone with the seed can replay a draw without depending on a particular
+/// version of a random number crate.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Draws up to `count` winners from `entries` (user, weight) without replacement. Entries must be
+/// in a fixed order, so the same seed always gives the same winners.
+fn draw(entries: &[(i64, i64)], count: usize, seed: u64) -> Vec<i64> {
+    let mut rng = SplitMix64(seed);
+    let mut pool = entries.to_vec();
+    let mut winners = Vec::new();
+
+    while winners.len() < count && !pool.is_empty() {
+        let total = pool.iter().map(|(_, w)| *w as u64).sum::<u64>();
+        let mut ticket = rng.next() % total;
+        let index = pool
+            .iter()
+            .position(|(_, weight)| {
+                let hit = ticket < *weight as u64;
+                ticket = ticket.saturating_sub(*weight as u64);
+                hit
+            })
+            .unwrap_or(0);
+        winners.push(pool.remove(index).0);
+    }
+
+    winners
+}
+
+impl Raffle {
+    async fn fetch(pool: &PgPool, id: i64) -> Result<Option<Self>, SlimeError> {
+        Ok(
+            sqlx::query_as::<_, Raffle>("SELECT * FROM raffles WHERE id = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?,
+        )
+    }
+
+    fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    async fn entry_count(&self, pool: &PgPool) -> Result<i64, SlimeError> {
+        Ok(
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM raffle_entries WHERE raffle_id = $1",
+            )
+            .bind(self.id)
+            .fetch_one(pool)
+            .await?,
+        )
+    }
+
+    /// Fixes every entry's weight from the member's record at the moment of the draw.
+    async fn record_weights(&self, conn: &mut sqlx::PgConnection) -> Result<(), SlimeError> {
+        let query = match self.weighting {
+            Weighting::Flat => "UPDATE raffle_entries SET weight = 1 WHERE raffle_id = $1",
+            Weighting::Attendance => {
+                "UPDATE raffle_entries r SET weight = 1 + (
+                    SELECT COUNT(*) FROM event_attendance a JOIN events e ON e.id = a.event_id
+                    WHERE e.guild_id = $2 AND a.user_id = r.user_id
+                 ) WHERE raffle_id = $1"
+            }
+            Weighting::Points => {
+                "UPDATE raffle_entries r SET weight = 1 + GREATEST(0, (
+                    SELECT COALESCE(SUM(amount), 0) FROM points_ledger p
+                    WHERE p.guild_id = $2 AND p.season = $3 AND p.user_id = r.user_id
+                 )) WHERE raffle_id = $1"
+            }
+        };
+        let season = current_season(&mut *conn, self.guild()).await?;
+
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(self.guild_id)
+            .bind(season)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn embed(&self, pool: &PgPool) -> Result<CreateEmbed, SlimeError> {
+        let weighting = match self.weighting {
+            Weighting::Flat => "Every entry has the same chance.",
+            Weighting::Attendance => "Entries are weighted by events attended.",
+            Weighting::Points => "Entries are weighted by points this season.",
+        };
+        let status = match self.status {
+            RaffleStatus::Open => format!(
+                "{} entered so far. Press **Enter** for a chance to win!",
+                self.entry_count(pool).await?
+            ),
+            RaffleStatus::Cancelled => "This raffle was cancelled.".to_string(),
+            RaffleStatus::Drawn => {
+                let winners = sqlx::query_scalar::<_, i64>(
+                    "SELECT user_id FROM raffle_entries WHERE raffle_id = $1 AND won_place IS NOT NULL
+                     ORDER BY won_place",
+                )
+                .bind(self.id)
+                .fetch_all(pool)
+                .await?;
+                format!(
+                    "Winner(s): {}\nSeed `{}`",
+                    winners
+                        .iter()
+                        .map(|id| UserId::new(*id as u64).mention().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    self.seed.unwrap_or_default()
+                )
+            }
+        };
+
+        Ok(CreateEmbed::new()
+            .title(format!("🎟️ Raffle: {}", self.prize))
+            .description(format!(
+                "Hosted by {}, {} winner(s). {weighting}\n\n{status}",
+                UserId::new(self.host_id as u64).mention(),
+                self.winners
+            ))
+            .footer(CreateEmbedFooter::new(format!("Raffle #{}", self.id))))
+    }
+
+    fn components(&self) -> Vec<CreateActionRow> {
+        if self.status != RaffleStatus::Open {
+            return vec![];
+        }
+        vec![CreateActionRow::Buttons(vec![CreateButton::new(
+            CustomId::new(Kind::Raffle, "enter", self.id),
+        )
+        .label("Enter")
+        .style(ButtonStyle::Success)])]
+    }
+
+    async fn refresh_post(&self, ctx: &impl CacheHttp, pool: &PgPool) -> Result<(), SlimeError> {
+        let Some(message_id) = self.message_id else {
+            return Ok(());
+        };
+        ChannelId::new(self.channel_id as u64)
+            .edit_message(
+                ctx,
+                MessageId::new(message_id as u64),
+                EditMessage::new()
+                    .embed(self.embed(pool).await?)
+                    .components(self.components()),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// What can be changed about a raffle before it's posted.
+#[derive(Debug, Clone, poise::Modal)]
+#[name = "Edit raffle"]
+struct RaffleModal {
+    #[name = "Prize"]
+    #[max_length = 100]
+    prize: String,
+    #[name = "Winners (1 to 20)"]
+    #[max_length = 2]
+    winners: String,
+}
+
+impl Form for RaffleModal {
+    type Output = (String, i32);
+
+    fn validate(&self) -> Result<(String, i32), SlimeError> {
+        let winners = self
+            .winners
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|n| (1..=20).contains(n))
+            .ok_or_else(|| SlimeError::InvalidNumber(self.winners.clone()))?;
+        Ok((self.prize.trim().to_string(), winners))
+    }
+}
+
+impl Preview for Raffle {
+    type Form = RaffleModal;
+
+    async fn render(&self, ctx: Context<'_>) -> Result<CreateEmbed, SlimeError> {
+        self.embed(&ctx.data().pool).await
+    }
+
+    fn form(&self) -> RaffleModal {
+        RaffleModal {
+            prize: self.prize.clone(),
+            winners: self.winners.to_string(),
+        }
+    }
+
+    async fn edit(
+        &mut self,
+        ctx: Context<'_>,
+        (prize, winners): (String, i32),
+    ) -> Result<(), SlimeError> {
+        sqlx::query("UPDATE raffles SET prize = $2, winners = $3 WHERE id = $1")
+            .bind(self.id)
+            .bind(&prize)
+            .bind(winners)
+            .execute(&ctx.data().pool)
+            .await?;
+        self.prize = prize;
+        self.winners = winners;
+        Ok(())
+    }
+
+    async fn publish(&mut self, ctx: Context<'_>) -> Result<String, SlimeError> {
+        let pool = &ctx.data().pool;
+        let message = ChannelId::new(self.channel_id as u64)
+            .send_message(
+                ctx,
+                CreateMessage::new()
+                    .embed(self.embed(pool).await?)
+                    .components(self.components()),
+            )
+            .await?;
+        sqlx::query("UPDATE raffles SET message_id = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(message.id.get() as i64)
+            .execute(pool)
+            .await?;
+        self.message_id = Some(message.id.get() as i64);
+        posts::register(pool, PostKind::Raffle, self.id, self.guild(), &message).await?;
+
+        Ok(format!(
+            "Raffle #{} is open. Run `/raffle draw {}` to pick the winners.",
+            self.id, self.id
+        ))
+    }
+
+    async fn discard(&mut self, ctx: Context<'_>) -> Result<String, SlimeError> {
+        sqlx::query("DELETE FROM raffles WHERE id = $1 AND message_id IS NULL")
+            .bind(self.id)
+            .execute(&ctx.data().pool)
+            .await?;
+        Ok("Discarded. Nothing was posted.".to_string())
+    }
+}
+
+/// A raffle's post as it should look now, while it's open for entries.
+pub async fn render_post(pool: &PgPool, id: i64) -> Result<Option<PostContent>, SlimeError> {
+    let Some(raffle) = Raffle::fetch(pool, id)
+        .await?
+        .filter(|r| r.status == RaffleStatus::Open)
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(PostContent {
+        content: None,
+        embed: raffle.embed(pool).await?,
+        components: raffle.components(),
+    }))
+}
+
+/// Opens a cancelled raffle again, for `/undo`.
+pub async fn reopen(ctx: &SerenityContext, pool: &PgPool, id: i64) -> Result<(), SlimeError> {
+    let mut raffle = Raffle::fetch(pool, id)
+        .await?
+        .filter(|r| r.status == RaffleStatus::Cancelled)
+        .ok_or(SlimeError::CannotUndo("the raffle is no longer cancelled"))?;
+
+    sqlx::query("UPDATE raffles SET status = 'open' WHERE id = $1")
+        .bind(raffle.id)
+        .execute(pool)
+        .await?;
+    raffle.status = RaffleStatus::Open;
+    raffle.refresh_post(&ctx, pool).await
+}
+
+/// Handles the enter button on raffle posts.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    if custom_id.action != "enter" {
+        return Ok(());
+    }
+    let id = custom_id.id;
+
+    let pool = &data.pool;
+    let Some(raffle) = Raffle::fetch(pool, id)
+        .await?
+        .filter(|r| r.status == RaffleStatus::Open)
+    else {
+        return respond_ephemeral(ctx, interaction, "This raffle is closed.").await;
+    };
+
+    let entered = sqlx::query(
+        "INSERT INTO raffle_entries (raffle_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(raffle.id)
+    .bind(interaction.user.id.get() as i64)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    let content = if entered > 0 {
+        "You're in, good luck!"
+    } else {
+        "You've already entered."
+    };
+    respond_ephemeral(ctx, interaction, content).await?;
+    raffle.refresh_post(ctx, pool).await
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// An open, posted raffle in this guild that the author hosts, or can manage as a moderator.
+async fn fetch_managed(ctx: Context<'_>, id: i64) -> Result<Option<Raffle>, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let permissions = ctx.author_member().await.and_then(|m| m.permissions);
+    let author = ctx.author().id;
+
+    Ok(Raffle::fetch(&ctx.data().pool, id).await?.filter(|r| {
+        r.guild() == guild_id
+            && r.status == RaffleStatus::Open
+            && r.message_id.is_some()
+            && (r.host_id == author.get() as i64 || permissions.is_some_and(|p| p.manage_events()))
+    }))
+}
+
+/// Give something away, optionally rewarding your regulars.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("start", "draw_command", "cancel")
+)]
+pub async fn raffle(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Open a raffle in this channel.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_EVENTS")]
+async fn start(
+    ctx: Context<'_>,
+    #[description = "What's being given away"]
+    #[max_length = 100]
+    prize: String,
+    #[description = "How entries are weighted"] weighting: Weighting,
+    #[description = "How many winners to draw"]
+    #[min = 1]
+    #[max = 20]
+    winners: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+
+    // Saved first so the preview shows it as it will be, but without a post nobody can enter.
+    let raffle = sqlx::query_as::<_, Raffle>(
+        "INSERT INTO raffles (guild_id, channel_id, host_id, prize, weighting, winners)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.channel_id().get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&prize)
+    .bind(weighting)
+    .bind(winners.unwrap_or(1) as i32)
+    .fetch_one(pool)
+    .await?;
+
+    preview::run(ctx, raffle).await
+}
+
+/// Close a raffle and draw its winners.
+#[poise::command(slash_command, guild_only, rename = "draw")]
+async fn draw_command(
+    ctx: Context<'_>,
+    #[description = "Raffle number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let Some(mut raffle) = fetch_managed(ctx, id).await? else {
+        return reply(
+            ctx,
+            format!("Raffle #{id} doesn't exist, isn't yours, or is closed."),
+        )
+        .await;
+    };
+    let pool = &ctx.data().pool;
+
+    let mut seed = RandomState::new().build_hasher();
+    seed.write_i64(raffle.id);
+    let seed = seed.finish() as i64;
+
+    let mut tx = pool.begin().await?;
+    // Closed before anything is drawn, so a second draw at the same time can't pick other winners.
+    let claimed = sqlx::query(
+        "UPDATE raffles SET status = 'drawn', seed = $2, drawn_at = now()
+         WHERE id = $1 AND status = 'open'",
+    )
+    .bind(raffle.id)
+    .bind(seed)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return reply(
+            ctx,
+            format!("Raffle #{id} has already been drawn or closed."),
+        )
+        .await;
+    }
+    raffle.record_weights(&mut tx).await?;
+    let entries = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT user_id, weight FROM raffle_entries WHERE raffle_id = $1 ORDER BY user_id",
+    )
+    .bind(raffle.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    if entries.is_empty() {
+        return reply(ctx, "Nobody has entered yet.").await;
+    }
+
+    let winners = draw(&entries, raffle.winners as usize, seed as u64);
+    for (place, winner) in winners.iter().enumerate() {
+        sqlx::query(
+            "UPDATE raffle_entries SET won_place = $3 WHERE raffle_id = $1 AND user_id = $2",
+        )
+        .bind(raffle.id)
+        .bind(winner)
+        .bind(place as i32 + 1)
+        .execute(&mut *tx)
+        .await?;
+    }
+    audit::record(
+        &mut *tx,
+        AuditEntry {
+            guild_id: Some(raffle.guild()),
+            actor: ctx.author().id,
+            action: "raffle_draw",
+            target: Some(raffle.id as u64),
+            details: format!("seed {seed}, {} entries", entries.len()),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    raffle.status = RaffleStatus::Drawn;
+    raffle.seed = Some(seed);
+    raffle.refresh_post(&ctx, pool).await?;
+    ctx.say(format!(
+        "Congratulations {}, you won **{}**!",
+        winners
+            .iter()
+            .map(|id| UserId::new(*id as u64).mention().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        raffle.prize
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Call off a raffle without drawing it.
+#[poise::command(slash_command, guild_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Raffle number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let Some(mut raffle) = fetch_managed(ctx, id).await? else {
+        return reply(
+            ctx,
+            format!("Raffle #{id} doesn't exist, isn't yours, or is closed."),
+        )
+        .await;
+    };
+    if rehearse(
+        ctx,
+        &format!("cancelled raffle #{} without drawing it.", raffle.id),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    let pool = &ctx.data().pool;
+
+    sqlx::query("UPDATE raffles SET status = 'cancelled' WHERE id = $1")
+        .bind(raffle.id)
+        .execute(pool)
+        .await?;
+    raffle.status = RaffleStatus::Cancelled;
+    raffle.refresh_post(&ctx, pool).await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: ctx.guild_id(),
+            actor: ctx.author().id,
+            action: "raffle_cancel",
+            target: Some(raffle.id as u64),
+            details: String::new(),
+            undo: vec![UndoStep::ReopenRaffle(raffle.id)],
+        },
+    )
+    .await?;
+
+    reply(ctx, format!("Raffle #{} was cancelled.", raffle.id)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_mix_matches_the_published_sequence() {
+        // Draws replayed from a recorded seed depend on these never changing.
+        let mut rng = SplitMix64(0);
+        assert_eq!(rng.next(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next(), 0x6e78_9e6a_a1b9_65f4);
+    }
+
+    #[test]
+    fn draws_replay_from_their_seed() {
+        let entries = (1..=50)
+            .map(|user| (user, user % 7 + 1))
+            .collect::<Vec<_>>();
+        for seed in [0, 1, 42, u64::MAX] {
+            let winners = draw(&entries, 5, seed);
+            assert_eq!(winners, draw(&entries, 5, seed));
+            assert_eq!(winners.len(), 5);
+
+            let mut distinct = winners.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            assert_eq!(distinct.len(), winners.len());
+        }
+    }
+
+    #[test]
+    fn everyone_wins_when_there_are_enough_prizes() {
+        let entries = [(3, 1), (1, 10), (2, 4)];
+        for count in [3, 20] {
+            let mut winners = draw(&entries, count, 7);
+            winners.sort_unstable();
+            assert_eq!(winners, [1, 2, 3]);
+        }
+        assert!(draw(&[], 3, 7).is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use tracing::{error, info};
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    discord,
+    settings::GuildSettings,
+    util::{respond_ephemeral, send_dm},
+    Data, SlimeError,
+};
+
+/// How far an incident raises the verification level on its own. Members need a verified email
+/// and to have been on Discord for five minutes, which slows a raid without locking anyone out.
+const RAISED: VerificationLevel = VerificationLevel::Medium;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum Status {
+    /// Spotted, with the verification level raised, and waiting for a moderator.
+    Open,
+    /// New accounts are turned away at the door too.
+    Locked,
+    Dismissed,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Incident {
+    id: i64,
+    guild_id: i64,
+    status: Status,
+    joins: i32,
+    turned_away: i32,
+    previous_verification: Option<i16>,
+    started_at: DateTime<Utc>,
+    resolved_by: Option<i64>,
+}
+
+/// The level to raise `current` to, or `None` if it's already at least that strict.
+fn raised(current: VerificationLevel) -> Option<VerificationLevel> {
+    (u8::from(current) < u8::from(RAISED)).then_some(RAISED)
+}
+
+/// The alert moderators see for `incident`, with the buttons that are still useful.
+fn alert(incident: &Incident, settings: &GuildSettings) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let button = |action, label, style| {
+        CreateButton::new(CustomId::new(Kind::Raid, action, incident.id))
+            .label(label)
+            .style(style)
+    };
+    let (status, buttons) = match incident.status {
+        Status::Open => (
+            "The verification level was raised. Lock down to also turn new accounts away."
+                .to_string(),
+            vec![
+                button("lockdown", "Lock down", ButtonStyle::Danger),
+                button("dismiss", "Dismiss", ButtonStyle::Secondary),
+            ],
+        ),
+        Status::Locked => (
+            format!(
+                "Locked down: new accounts are turned away ({} so far).",
+                incident.turned_away
+            ),
+            vec![button("dismiss", "End lockdown", ButtonStyle::Secondary)],
+        ),
+        Status::Dismissed => {
+            let mut status = match incident.resolved_by {
+                Some(user) => format!("Dismissed by {}.", UserId::new(user as u64).mention()),
+                None => "Dismissed.".to_string(),
+            };
+            if incident.previous_verification.is_some() {
+                status.push_str(" The verification level was put back.");
+            }
+            (status, vec![])
+        }
+    };
+    let embed = CreateEmbed::new()
+        .title("Possible raid")
+        .description(format!(
+            "{} accounts under {} day(s) old joined within {} minute(s).",
+            incident.joins, settings.raid_account_days, settings.raid_window_minutes
+        ))
+        .field("Status", status, false)
+        .footer(CreateEmbedFooter::new(format!("Incident #{}", incident.id)))
+        .timestamp(Timestamp::from(incident.started_at));
+    let rows = if buttons.is_empty() {
+        vec![]
+    } else {
+        vec![CreateActionRow::Buttons(buttons)]
+    };
+    (embed, rows)
+}
+
+async fn set_verification(
+    ctx: &SerenityContext,
+    guild_id: GuildId,
+    level: VerificationLevel,
+    reason: &str,
+) -> Result<(), SlimeError> {
+    guild_id
+        .edit(
+            ctx,
+            EditGuild::new()
+                .verification_level(level)
+                .audit_log_reason(reason),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Counts `member` towards a raid if their account is new, and opens an incident once enough
+/// new accounts have joined within the window. While an incident is locked down, new accounts are
+/// turned away instead.
+pub async fn joined(ctx: &SerenityContext, data: &Data, member: &Member) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let guild_id = member.guild_id;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let now = data.clock.now();
+    let account_age = now - *member.user.id.created_at();
+    if !settings.raid_detection
+        || member.user.bot
+        || account_age >= Duration::days(settings.raid_account_days.into())
+    {
+        return Ok(());
+    }
+
+    let active = sqlx::query_as::<_, Incident>(
+        "SELECT * FROM raid_incidents WHERE guild_id = $1 AND status <> 'dismissed'",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_optional(pool)
+    .await?;
+    if let Some(incident) = active.as_ref().filter(|i| i.status == Status::Locked) {
+        return turn_away(ctx, data, incident, member).await;
+    }
+
+    sqlx::query(
+        "INSERT INTO raid_joins (guild_id, user_id, joined_at) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id, user_id) DO UPDATE SET joined_at = EXCLUDED.joined_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(member.user.id.get() as i64)
+    .bind(now)
+    .execute(pool)
+    .await?;
+    sqlx::query("DELETE FROM raid_joins WHERE guild_id = $1 AND joined_at < $2")
+        .bind(guild_id.get() as i64)
+        .bind(now - Duration::minutes(settings.raid_window_minutes.into()))
+        .execute(pool)
+        .await?;
+    if let Some(incident) = active {
+        sqlx::query("UPDATE raid_incidents SET joins = joins + 1 WHERE id = $1")
+            .bind(incident.id)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+    let joins = sqlx::query_scalar::<_, i64>("SELECT count(*) FROM raid_joins WHERE guild_id = $1")
+        .bind(guild_id.get() as i64)
+        .fetch_one(pool)
+        .await?;
+    if joins < settings.raid_joins.into() {
+        return Ok(());
+    }
+
+    let current = guild_id.to_partial_guild(ctx).await?.verification_level;
+    let raise = raised(current);
+    // Only one incident can be going at once, so concurrent joins don't open two.
+    let Some(mut incident) = sqlx::query_as::<_, Incident>(
+        "INSERT INTO raid_incidents (guild_id, joins, previous_verification, started_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id) WHERE status <> 'dismissed' DO NOTHING
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(joins as i32)
+    .bind(raise.map(|_| i16::from(u8::from(current))))
+    .bind(now)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(());
+    };
+    info!(
+        "Possible raid in guild {}: incident #{}",
+        guild_id, incident.id
+    );
+
+    if let Some(level) = raise {
+        if let Err(e) = set_verification(ctx, guild_id, level, "Possible raid").await {
+            error!("Could not raise verification in guild {}: {}", guild_id, e);
+            incident.previous_verification = None;
+            sqlx::query("UPDATE raid_incidents SET previous_verification = NULL WHERE id = $1")
+                .bind(incident.id)
+                .execute(pool)
+                .await?;
+        }
+    }
+    if let Some(channel) = settings.raid_channel() {
+        let (embed, components) = alert(&incident, &settings);
+        match channel
+            .send_message(
+                ctx,
+                CreateMessage::new().embed(embed).components(components),
+            )
+            .await
+        {
+            Ok(message) => {
+                sqlx::query(
+                    "UPDATE raid_incidents SET channel_id = $2, message_id = $3 WHERE id = $1",
+                )
+                .bind(incident.id)
+                .bind(channel.get() as i64)
+                .bind(message.id.get() as i64)
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => error!("Could not post raid alert in {}: {}", channel, e),
+        }
+    }
+    let bot = ctx.cache.current_user().id;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: bot,
+            action: "raid_detected",
+            target: Some(incident.id as u64),
+            details: format!(
+                "{joins} new accounts, verification was {}",
+                u8::from(current)
+            ),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Keeps a new account out during a lockdown, telling them why first.
+async fn turn_away(
+    ctx: &SerenityContext,
+    data: &Data,
+    incident: &Incident,
+    member: &Member,
+) -> Result<(), SlimeError> {
+    let server = ctx
+        .cache
+        .guild(member.guild_id)
+        .map_or_else(|| "The server".to_string(), |g| g.name.clone());
+    let notice = format!(
+        "**{server}** is only letting in established accounts for a little while. Please try \
+         joining again later."
+    );
+    if let Err(e) = send_dm(ctx, member.user.id, CreateMessage::new().content(notice)).await {
+        info!(
+            "Could not tell {} about the lockdown: {}",
+            member.user.id, e
+        );
+    }
+    member.kick_with_reason(ctx, "Raid lockdown").await?;
+    sqlx::query("UPDATE raid_incidents SET turned_away = turned_away + 1 WHERE id = $1")
+        .bind(incident.id)
+        .execute(&data.pool)
+        .await?;
+    Ok(())
+}
+
+/// Handles the lockdown and dismiss buttons on a raid alert. Only members who can manage the
+/// server may press them.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let allowed = interaction
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_guild());
+    if !allowed {
+        return respond_ephemeral(ctx, interaction, "Only server managers can act on raids.").await;
+    }
+    let incident = sqlx::query_as::<_, Incident>("SELECT * FROM raid_incidents WHERE id = $1")
+        .bind(custom_id.id)
+        .fetch_optional(pool)
+        .await?
+        .filter(|i| Some(GuildId::new(i.guild_id as u64)) == interaction.guild_id);
+    let Some(incident) = incident else {
+        return respond_ephemeral(ctx, interaction, "That incident doesn't exist any more.").await;
+    };
+    let guild_id = GuildId::new(incident.guild_id as u64);
+    let user = interaction.user.id;
+
+    let (status, action) = match (custom_id.action.as_str(), incident.status) {
+        ("lockdown", Status::Open) => {
+            // Kept from before the incident, or from now if it didn't need raising, so
+            // dismissing always undoes the lockdown's level too.
+            let current = guild_id.to_partial_guild(ctx).await?.verification_level;
+            sqlx::query(
+                "UPDATE raid_incidents
+                 SET previous_verification = COALESCE(previous_verification, $2)
+                 WHERE id = $1",
+            )
+            .bind(incident.id)
+            .bind(i16::from(u8::from(current)))
+            .execute(pool)
+            .await?;
+            set_verification(ctx, guild_id, VerificationLevel::Higher, "Raid lockdown").await?;
+            (Status::Locked, "raid_lockdown")
+        }
+        ("dismiss", Status::Open | Status::Locked) => {
+            if let Some(previous) = incident.previous_verification {
+                let level = VerificationLevel::from(previous as u8);
+                set_verification(ctx, guild_id, level, "Raid dismissed").await?;
+            }
+            sqlx::query("DELETE FROM raid_joins WHERE guild_id = $1")
+                .bind(incident.guild_id)
+                .execute(pool)
+                .await?;
+            (Status::Dismissed, "raid_dismissed")
+        }
+        _ => {
+            return respond_ephemeral(ctx, interaction, "Someone has already dealt with that.")
+                .await;
+        }
+    };
+    let incident = sqlx::query_as::<_, Incident>(
+        "UPDATE raid_incidents SET status = $2,
+            resolved_by = CASE WHEN $2 = 'dismissed' THEN $3 END,
+            resolved_at = CASE WHEN $2 = 'dismissed' THEN $4 END
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(incident.id)
+    .bind(status)
+    .bind(user.get() as i64)
+    .bind(data.clock.now())
+    .fetch_one(pool)
+    .await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: user,
+            action,
+            target: Some(incident.id as u64),
+            details: format!(
+                "{} joins, {} turned away",
+                incident.joins, incident.turned_away
+            ),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let (embed, components) = alert(&incident, &settings);
+    discord::respond(
+        ctx,
+        interaction,
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(components),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incidents_only_ever_tighten_verification() {
+        assert_eq!(raised(VerificationLevel::None), Some(RAISED));
+        assert_eq!(raised(VerificationLevel::Low), Some(RAISED));
+        assert_eq!(raised(VerificationLevel::Medium), None);
+        assert_eq!(raised(VerificationLevel::Higher), None);
+    }
+}
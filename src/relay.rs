@@ -0,0 +1,209 @@
+use poise::serenity_prelude::*;
+use serenity::{builder::Builder, client::Context as SerenityContext, Error as SerenityError};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{settings::GuildSettings, util::http_status, SlimeError};
+
+/// Longest name Discord accepts for a webhook.
+pub const MAX_NAME_LENGTH: usize = 80;
+
+/// Why `name` can't be used for the relay, if it can't. Discord refuses webhook names that
+/// mention it or its old system user.
+pub fn name_problem(name: &str) -> Option<&'static str> {
+    let lower = name.to_lowercase();
+    if name.trim().is_empty() {
+        Some("the name can't be blank")
+    } else if name.chars().count() > MAX_NAME_LENGTH {
+        Some("the name is longer than 80 characters")
+    } else if lower.contains("discord") || lower.contains("clyde") {
+        Some("Discord doesn't allow webhook names with `discord` or `clyde` in them")
+    } else {
+        None
+    }
+}
+
+/// The webhook the bot made in `channel`, as its ID and token.
+async fn stored(
+    pool: &PgPool,
+    channel: ChannelId,
+) -> Result<Option<(WebhookId, String)>, SlimeError> {
+    let row = sqlx::query_as::<_, (i64, String)>(
+        "SELECT webhook_id, token FROM relay_webhooks WHERE channel_id = $1",
+    )
+    .bind(channel.get() as i64)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(id, token)| (WebhookId::new(id as u64), token)))
+}
+
+/// Makes a webhook in `channel` for the relay and remembers it, replacing any it had before.
+async fn create(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel: ChannelId,
+    name: &str,
+) -> Result<(WebhookId, String), SlimeError> {
+    let webhook = channel
+        .create_webhook(ctx, CreateWebhook::new(name))
+        .await?;
+    let url = webhook.url()?;
+    let token = url.rsplit('/').next().unwrap_or_default().to_string();
+    sqlx::query(
+        "INSERT INTO relay_webhooks (channel_id, guild_id, webhook_id, token) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (channel_id) DO UPDATE SET
+            webhook_id = EXCLUDED.webhook_id, token = EXCLUDED.token",
+    )
+    .bind(channel.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(webhook.id.get() as i64)
+    .bind(&token)
+    .execute(pool)
+    .await?;
+    info!("Created relay webhook in channel {}", channel);
+
+    Ok((webhook.id, token))
+}
+
+/// Publishes `message` to the servers following its channel, if it's an announcement channel and
+/// the guild wants its posts there published. Failures are only logged, since the post is up in
+/// the guild either way.
+pub async fn publish(ctx: &SerenityContext, settings: &GuildSettings, message: &Message) {
+    if !settings.publish_announcements {
+        return;
+    }
+    let announcements = match message.channel_id.to_channel(ctx).await {
+        Ok(Channel::Guild(channel)) => channel.kind == ChannelType::News,
+        _ => false,
+    };
+    if !announcements {
+        return;
+    }
+    if let Err(e) = message.crosspost(ctx).await {
+        error!(
+            "Could not publish message {} in {}: {}",
+            message.id, message.channel_id, e
+        );
+    }
+}
+
+/// Posts an announcement in `channel`. In guilds that set up a relay it goes out through the
+/// bot's webhook there, under the guild's chosen name and avatar, and the webhook is made again
+/// if it was deleted. Otherwise, or if the bot can't manage webhooks, the bot posts it itself.
+/// Mentions in announcements never ping.
+pub async fn say(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel: ChannelId,
+    content: String,
+) -> Result<(), SlimeError> {
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    if let Some(name) = &settings.relay_name {
+        let mut message = ExecuteWebhook::new()
+            .content(&content)
+            .username(name)
+            .allowed_mentions(CreateAllowedMentions::new());
+        if let Some(avatar) = &settings.relay_avatar_url {
+            message = message.avatar_url(avatar);
+        }
+        match relayed(ctx, pool, guild_id, channel, name, message).await {
+            Ok(message) => {
+                publish(ctx, &settings, &message).await;
+                return Ok(());
+            }
+            Err(e) => error!(
+                "Could not relay to channel {}, posting as the bot: {}",
+                channel, e
+            ),
+        }
+    }
+
+    let message = channel
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+    publish(ctx, &settings, &message).await;
+
+    Ok(())
+}
+
+/// Sends `message` through the webhook `id`, and returns the message it became.
+async fn execute(
+    ctx: &SerenityContext,
+    (id, token): (WebhookId, String),
+    message: ExecuteWebhook,
+) -> Result<Message, SerenityError> {
+    // Discord only sends the message back when asked to wait for it.
+    message
+        .execute(ctx, (id, &token, true))
+        .await?
+        .ok_or(SerenityError::Other(
+            "the webhook didn't return its message",
+        ))
+}
+
+/// Sends `message` through the bot's webhook in `channel`, making the webhook under `name` first
+/// if there isn't one or it was deleted.
+async fn relayed(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel: ChannelId,
+    name: &str,
+    message: ExecuteWebhook,
+) -> Result<Message, SlimeError> {
+    let webhook = match stored(pool, channel).await? {
+        Some(webhook) => webhook,
+        None => create(ctx, pool, guild_id, channel, name).await?,
+    };
+    match execute(ctx, webhook, message.clone()).await {
+        Err(e) if http_status(&e) == Some(404) => {
+            info!(
+                "Relay webhook in channel {} is gone, making another",
+                channel
+            );
+            let webhook = create(ctx, pool, guild_id, channel, name).await?;
+            Ok(execute(ctx, webhook, message).await?)
+        }
+        sent => Ok(sent?),
+    }
+}
+
+/// Posts `message` in `channel` through the bot's webhook there, for posts that go out under
+/// another name than the bot's, like messages moved from another channel. The webhook is made
+/// under the guild's relay name, or the bot's own if it has none.
+pub async fn repost(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel: ChannelId,
+    message: ExecuteWebhook,
+) -> Result<Message, SlimeError> {
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let name = match &settings.relay_name {
+        Some(name) => name.clone(),
+        None => ctx.cache.current_user().name.clone(),
+    };
+    relayed(ctx, pool, guild_id, channel, &name, message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_names_discord_refuses_are_caught() {
+        assert_eq!(name_problem("Pond Crier"), None);
+        assert!(name_problem("  ").is_some());
+        assert!(name_problem("Discord Frog").is_some());
+        assert!(name_problem("clydesdale").is_some());
+        assert!(name_problem(&"x".repeat(81)).is_some());
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+
+use crate::{roles, settings::GuildSettings, util::paginate, Context, SlimeError};
+
+const MEMBERS_PER_PAGE: usize = 20;
+
+/// When a member was last seen doing each thing the bot notices.
+#[derive(Debug, Clone, PartialEq)]
+struct Seen {
+    user: UserId,
+    name: String,
+    joined_at: Option<DateTime<Utc>>,
+    last_message: Option<DateTime<Utc>>,
+    last_reaction: Option<DateTime<Utc>>,
+    last_rsvp: Option<DateTime<Utc>>,
+}
+
+impl Seen {
+    fn last_active(&self) -> Option<DateTime<Utc>> {
+        [self.last_message, self.last_reaction, self.last_rsvp]
+            .into_iter()
+            .flatten()
+            .max()
+    }
+}
+
+/// Members who haven't done anything since `since`, least recently seen first. Members who joined
+/// after it haven't had the whole window to be active in, so they're left out.
+fn inactive_members(members: Vec<Seen>, since: DateTime<Utc>) -> Vec<Seen> {
+    let mut inactive = members
+        .into_iter()
+        .filter(|m| m.joined_at.is_some_and(|at| at <= since))
+        .filter(|m| m.last_active().is_none_or(|at| at < since))
+        .collect::<Vec<_>>();
+    inactive.sort_by(|a, b| {
+        a.last_active()
+            .cmp(&b.last_active())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    inactive
+}
+
+fn csv(members: &[Seen]) -> Vec<u8> {
+    let time = |at: Option<DateTime<Utc>>| at.map(|at| at.to_rfc3339()).unwrap_or_default();
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "user_id",
+            "name",
+            "joined_at",
+            "last_message_at",
+            "last_reaction_at",
+            "last_rsvp_at",
+        ])
+        .expect("writing to memory can't fail");
+    for member in members {
+        writer
+            .write_record([
+                member.user.to_string(),
+                member.name.clone(),
+                time(member.joined_at),
+                time(member.last_message),
+                time(member.last_reaction),
+                time(member.last_rsvp),
+            ])
+            .expect("writing to memory can't fail");
+    }
+    writer.into_inner().expect("writing to memory can't fail")
+}
+
+/// Reports to help decide on member cleanups. The bot never acts on them itself.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    subcommands("inactive")
+)]
+pub async fn report(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// List members with no messages, reactions or RSVPs in a while, from what the bot has counted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn inactive(
+    ctx: Context<'_>,
+    #[description = "How many days back to look, up to as long as activity is kept"]
+    #[min = 1]
+    #[max = 91]
+    days: u32,
+    #[description = "Send the report as a CSV file instead"] export: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        let content = "Messages and reactions are only counted once message content features are \
+                       allowed with `/settings message_content`, so there's nothing to report on \
+                       yet.";
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+    let since = ctx.data().clock.now() - Duration::days(i64::from(days));
+
+    let activity = sqlx::query_as::<_, (i64, Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(
+        "SELECT user_id, last_message_at, last_reaction_at FROM member_activity
+         WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(user, message, reaction)| (user, (message, reaction)))
+    .collect::<HashMap<_, _>>();
+    let rsvps = sqlx::query_as::<_, (i64, DateTime<Utc>)>(
+        "SELECT r.user_id, MAX(r.created_at) FROM event_rsvps r
+         JOIN events e ON e.id = r.event_id
+         WHERE e.guild_id = $1
+         GROUP BY r.user_id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+    let counted_since = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        "SELECT MIN(hour) FROM channel_activity WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_one(pool)
+    .await?;
+
+    let members = roles::fetch_all_members(ctx.serenity_context(), guild_id)
+        .await?
+        .into_iter()
+        .filter(|m| !m.user.bot)
+        .map(|m| {
+            let id = m.user.id.get() as i64;
+            let (last_message, last_reaction) = activity.get(&id).copied().unwrap_or_default();
+            Seen {
+                user: m.user.id,
+                name: m.user.tag(),
+                joined_at: m.joined_at.map(|at| *at),
+                last_message,
+                last_reaction,
+                last_rsvp: rsvps.get(&id).copied(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let inactive = inactive_members(members, since);
+
+    let mut summary = format!(
+        "{} member(s) haven't posted, reacted or RSVPed in the last {days} day(s).",
+        inactive.len()
+    );
+    if counted_since.is_none_or(|at| at > since) {
+        let start = counted_since.map_or_else(
+            || "now".to_string(),
+            |at| format!("<t:{}:D>", at.timestamp()),
+        );
+        summary.push_str(&format!(
+            " Activity has only been counted since {start}, so some may have been active before \
+             then."
+        ));
+    }
+
+    if export.unwrap_or(false) {
+        let file = CreateAttachment::bytes(csv(&inactive), "inactive-members.csv");
+        ctx.send(
+            CreateReply::default()
+                .content(summary)
+                .attachment(file)
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    if inactive.is_empty() {
+        ctx.send(CreateReply::default().content(summary).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    let pages = inactive
+        .chunks(MEMBERS_PER_PAGE)
+        .map(|chunk| {
+            let lines = chunk
+                .iter()
+                .map(|m| match m.last_active() {
+                    Some(at) => {
+                        format!("{} — last seen <t:{}:R>", m.user.mention(), at.timestamp())
+                    }
+                    None => format!("{} — never seen", m.user.mention()),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{summary}\n\n{lines}")
+        })
+        .collect::<Vec<_>>();
+    paginate(ctx, &format!("Inactive for {days} days"), &pages).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inactive_members_are_listed_least_recent_first() {
+        let now = Utc::now();
+        let since = now - Duration::days(30);
+        let member = |id: u64, joined: i64, message: Option<i64>, rsvp: Option<i64>| Seen {
+            user: UserId::new(id),
+            name: format!("member{id}"),
+            joined_at: Some(now - Duration::days(joined)),
+            last_message: message.map(|d| now - Duration::days(d)),
+            last_reaction: None,
+            last_rsvp: rsvp.map(|d| now - Duration::days(d)),
+        };
+        let members = vec![
+            member(1, 100, Some(40), None),
+            member(2, 100, Some(50), Some(5)),
+            member(3, 100, None, None),
+            // Too new to judge.
+            member(4, 10, None, None),
+            member(5, 100, Some(60), None),
+        ];
+
+        let listed = inactive_members(members, since)
+            .iter()
+            .map(|m| m.user.get())
+            .collect::<Vec<_>>();
+        assert_eq!(listed, [3, 5, 1]);
+
+        let file = String::from_utf8(csv(&inactive_members(
+            vec![member(3, 100, None, None)],
+            since,
+        )))
+        .unwrap();
+        assert!(file.starts_with("user_id,name,joined_at,"));
+        assert!(file.contains("\n3,member3,"));
+    }
+}
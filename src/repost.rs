@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use poise::{serenity_prelude::*, CreateReply};
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    relay,
+    undo::UndoStep,
+    Context, SlimeError,
+};
+
+/// How long the picker waits for a channel and a choice.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Discord allows ten embeds a message, and one goes to saying where the message came from.
+const MAX_EMBEDS: usize = 9;
+
+/// The name a reposted message goes out under: its author's, unless Discord would refuse it.
+fn username(author: &User) -> String {
+    let name = author.global_name.as_deref().unwrap_or(&author.name);
+    match relay::name_problem(name) {
+        None => name.to_string(),
+        Some(_) => "Moved message".to_string(),
+    }
+}
+
+/// `message` as it will be posted in its new channel: its text, attachments and embeds, and a
+/// note of who wrote it and where.
+async fn rebuild(
+    ctx: Context<'_>,
+    message: &Message,
+    moved: bool,
+) -> Result<ExecuteWebhook, SlimeError> {
+    let mut note = if moved {
+        format!(
+            "Moved from {} by {}",
+            message.channel_id.mention(),
+            ctx.author().mention()
+        )
+    } else {
+        format!(
+            "Copied from {} by {}",
+            message.link(),
+            ctx.author().mention()
+        )
+    };
+
+    let mut files = Vec::new();
+    for attachment in &message.attachments {
+        // Too big to post again, or gone already; a link is better than nothing.
+        match CreateAttachment::url(ctx, &attachment.url).await {
+            Ok(file) => files.push(file),
+            Err(e) => {
+                error!("Could not copy attachment {}: {}", attachment.id, e);
+                note.push_str(&format!("\n[{}]({})", attachment.filename, attachment.url));
+            }
+        }
+    }
+
+    let mut embeds = vec![CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(&message.author.name).icon_url(message.author.face()))
+        .description(note)
+        .timestamp(message.timestamp)];
+    embeds.extend(
+        message
+            .embeds
+            .iter()
+            .take(MAX_EMBEDS)
+            .cloned()
+            .map(CreateEmbed::from),
+    );
+
+    Ok(ExecuteWebhook::new()
+        .content(&message.content)
+        .username(username(&message.author))
+        .avatar_url(message.author.face())
+        .embeds(embeds)
+        .add_files(files)
+        .allowed_mentions(CreateAllowedMentions::new()))
+}
+
+/// Repost a message in another channel, and take the original down if you like.
+#[poise::command(
+    context_menu_command = "Move to…",
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn move_message(ctx: Context<'_>, message: Message) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let id = |action| CustomId::for_invocation(Kind::Move, action, ctx.id()).to_string();
+    let picker = CreateSelectMenu::new(
+        id("channel"),
+        CreateSelectMenuKind::Channel {
+            channel_types: Some(vec![ChannelType::Text, ChannelType::News]),
+            default_channels: None,
+        },
+    )
+    .placeholder("Pick a channel");
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .content("Where should this message go?")
+                .components(vec![CreateActionRow::SelectMenu(picker)])
+                .ephemeral(true),
+        )
+        .await?;
+
+    let Some(picked) = ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .custom_ids(vec![id("channel")])
+        .timeout(TIMEOUT)
+        .await
+    else {
+        handle
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Nothing was moved.")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    };
+    let ComponentInteractionDataKind::ChannelSelect { values } = &picked.data.kind else {
+        return Ok(());
+    };
+    let Some(&channel) = values.first() else {
+        return Ok(());
+    };
+    if channel == message.channel_id {
+        picked
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content("The message is already in that channel. Nothing was moved.")
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let buttons = vec![
+        CreateButton::new(id("move"))
+            .label("Move")
+            .style(ButtonStyle::Primary),
+        CreateButton::new(id("copy"))
+            .label("Copy")
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(id("cancel"))
+            .label("Cancel")
+            .style(ButtonStyle::Secondary),
+    ];
+    picked
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!(
+                        "Move the message to {} and take the original down, or only copy it there?",
+                        channel.mention()
+                    ))
+                    .components(vec![CreateActionRow::Buttons(buttons)]),
+            ),
+        )
+        .await?;
+
+    let press = ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .custom_ids(vec![id("move"), id("copy"), id("cancel")])
+        .timeout(TIMEOUT)
+        .await;
+    let action = press
+        .as_ref()
+        .and_then(|p| CustomId::parse(&p.data.custom_id))
+        .map(|c| c.action);
+    let (Some(press), Some(action)) = (press, action.filter(|a| a != "cancel")) else {
+        handle
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Nothing was moved.")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    };
+    // Copying the attachments can take longer than Discord waits for an answer.
+    press.defer(ctx).await?;
+
+    let moved = action == "move";
+    let pool = &ctx.data().pool;
+    let repost = rebuild(ctx, &message, moved).await?;
+    let copy = relay::repost(ctx.serenity_context(), pool, guild_id, channel, repost).await?;
+    let undo = if moved {
+        message.delete(ctx).await?;
+        // The original can't be put back, so undoing would only lose the copy too.
+        vec![]
+    } else {
+        vec![UndoStep::DeleteMessage(channel, copy.id)]
+    };
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: if moved {
+                "message_moved"
+            } else {
+                "message_copied"
+            },
+            target: Some(message.id.get()),
+            details: format!(
+                "from {} to {}, by {}",
+                message.channel_id, channel, message.author.id
+            ),
+            undo,
+        },
+    )
+    .await?;
+
+    handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content(format!(
+                    "{} to {}.",
+                    if moved { "Moved" } else { "Copied" },
+                    copy.link()
+                ))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reposts_keep_names_discord_accepts() {
+        let mut author = User::default();
+        author.name = "frogfan".to_string();
+        assert_eq!(username(&author), "frogfan");
+        author.global_name = Some("Frog Fan".to_string());
+        assert_eq!(username(&author), "Frog Fan");
+        author.global_name = Some("discord mod".to_string());
+        assert_eq!(username(&author), "Moved message");
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    drift, i18n,
+    undo::UndoStep,
+    util::{confirm, rehearse},
+    Context, SlimeError,
+};
+
+/// Discord's page size for listing guild members.
+const MEMBERS_PAGE: u64 = 1000;
+
+/// Every member of the guild, fetched a page at a time. Needs the server members intent.
+pub async fn fetch_all_members(
+    ctx: &SerenityContext,
+    guild_id: GuildId,
+) -> Result<Vec<Member>, SlimeError> {
+    let mut members = Vec::new();
+    let mut after = None;
+    loop {
+        let page = guild_id.members(ctx, Some(MEMBERS_PAGE), after).await?;
+        after = page.last().map(|m| m.user.id);
+        let done = (page.len() as u64) < MEMBERS_PAGE;
+        members.extend(page);
+        if done {
+            return Ok(members);
+        }
+    }
+}
+
+/// Roles that can be handed out by hand, so not `@everyone` or ones owned by an integration.
+async fn assignable_roles(
+    ctx: &SerenityContext,
+    guild_id: GuildId,
+) -> Result<HashSet<RoleId>, SlimeError> {
+    Ok(guild_id
+        .roles(ctx)
+        .await?
+        .into_values()
+        .filter(|r| !r.managed && r.id.get() != guild_id.get())
+        .map(|r| r.id)
+        .collect())
+}
+
+/// What restoring a snapshot would do.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Restore {
+    /// Saved roles members no longer have.
+    missing: Vec<(UserId, RoleId)>,
+    /// Saved roles that can't be given back, for members who left and roles that were deleted.
+    departed: usize,
+    deleted: usize,
+}
+
+/// Compares the roles saved in a snapshot with those members hold now.
+fn diff(
+    saved: impl IntoIterator<Item = (UserId, RoleId)>,
+    current: &HashMap<UserId, HashSet<RoleId>>,
+    assignable: &HashSet<RoleId>,
+) -> Restore {
+    let mut restore = Restore::default();
+    for (user, role) in saved {
+        match current.get(&user) {
+            None => restore.departed += 1,
+            Some(_) if !assignable.contains(&role) => restore.deleted += 1,
+            Some(held) if !held.contains(&role) => restore.missing.push((user, role)),
+            Some(_) => {}
+        }
+    }
+    restore
+}
+
+/// Back up and restore who has which roles in this server, and keep them consistent.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    subcommands(
+        "snapshot",
+        "restore",
+        "drift::require",
+        "drift::exclude",
+        "drift::invariants",
+        "drift::drop_invariant",
+        "drift::drift"
+    )
+)]
+pub async fn roles(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Save every member's roles so they can be put back later.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn snapshot(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    ctx.defer_ephemeral().await?;
+
+    let assignable = assignable_roles(ctx.serenity_context(), guild_id).await?;
+    let members = fetch_all_members(ctx.serenity_context(), guild_id).await?;
+    let (users, roles): (Vec<i64>, Vec<i64>) = members
+        .iter()
+        .flat_map(|m| {
+            m.roles
+                .iter()
+                .filter(|r| assignable.contains(r))
+                .map(|r| (m.user.id.get() as i64, r.get() as i64))
+        })
+        .unzip();
+
+    let pool = &ctx.data().pool;
+    let mut tx = pool.begin().await?;
+    let snapshot_id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO role_snapshots (guild_id, taken_by) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO role_snapshot_members (snapshot_id, user_id, role_id)
+         SELECT $1, * FROM UNNEST($2::BIGINT[], $3::BIGINT[])",
+    )
+    .bind(snapshot_id)
+    .bind(&users)
+    .bind(&roles)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Saved snapshot #{snapshot_id}: {} role assignment(s) across {} member(s).",
+                users.len(),
+                members.len()
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give members back any roles they had in a snapshot but have since lost.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn restore(
+    ctx: Context<'_>,
+    #[description = "Snapshot number, defaults to the latest"] snapshot: Option<i64>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    ctx.defer_ephemeral().await?;
+    let pool = &ctx.data().pool;
+
+    let found = sqlx::query_as::<_, (i64, DateTime<Utc>)>(
+        "SELECT id, created_at FROM role_snapshots
+         WHERE guild_id = $1 AND ($2::BIGINT IS NULL OR id = $2)
+         ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(snapshot)
+    .fetch_optional(pool)
+    .await?;
+    let Some((snapshot_id, taken_at)) = found else {
+        ctx.send(
+            CreateReply::default()
+                .content("There's no such snapshot, take one with `/roles snapshot`.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let saved = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT user_id, role_id FROM role_snapshot_members WHERE snapshot_id = $1",
+    )
+    .bind(snapshot_id)
+    .fetch_all(pool)
+    .await?;
+
+    let assignable = assignable_roles(ctx.serenity_context(), guild_id).await?;
+    let current = fetch_all_members(ctx.serenity_context(), guild_id)
+        .await?
+        .into_iter()
+        .map(|m| (m.user.id, m.roles.into_iter().collect::<HashSet<_>>()))
+        .collect::<HashMap<_, _>>();
+
+    let Restore {
+        missing,
+        departed,
+        deleted,
+    } = diff(
+        saved
+            .into_iter()
+            .map(|(user, role)| (UserId::new(user as u64), RoleId::new(role as u64))),
+        &current,
+        &assignable,
+    );
+
+    if missing.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "Everyone still has their roles from snapshot #{snapshot_id}, nothing to restore."
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut by_role = HashMap::<RoleId, usize>::new();
+    for (_, role) in &missing {
+        *by_role.entry(*role).or_default() += 1;
+    }
+    let mut by_role = by_role.into_iter().collect::<Vec<_>>();
+    by_role.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let mut preview = format!(
+        "Snapshot #{snapshot_id} from {} would give back **{}** role(s) to **{}** member(s):\n",
+        i18n::timestamp(taken_at, FormattedTimestampStyle::ShortDateTime),
+        missing.len(),
+        missing.iter().map(|(u, _)| u).collect::<HashSet<_>>().len()
+    );
+    for (role, count) in by_role.iter().take(15) {
+        preview.push_str(&format!("- {} to {count} member(s)\n", role.mention()));
+    }
+    if by_role.len() > 15 {
+        preview.push_str(&format!("…and {} more role(s).\n", by_role.len() - 15));
+    }
+    if departed + deleted > 0 {
+        preview.push_str(&format!(
+            "Skipping {departed} assignment(s) for members who left and {deleted} for roles that no longer exist.\n"
+        ));
+    }
+    if rehearse(ctx, &format!("restored these roles.\n{preview}")).await? {
+        return Ok(());
+    }
+    preview.push_str("Continue?");
+
+    if !confirm(ctx, &preview).await? {
+        ctx.send(
+            CreateReply::default()
+                .content("No roles were changed.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    let mut undo = Vec::new();
+    for (user, role) in &missing {
+        match ctx
+            .http()
+            .add_member_role(guild_id, *user, *role, Some("Restored from role snapshot"))
+            .await
+        {
+            Ok(()) => undo.push(UndoStep::RemoveRole(*user, *role)),
+            Err(e) => {
+                error!("Could not restore role {} to {}: {}", role, user, e);
+                failed += 1;
+            }
+        }
+    }
+
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "roles_restore",
+            target: Some(snapshot_id as u64),
+            details: format!("restored: {}, failed: {failed}", missing.len() - failed),
+            undo,
+        },
+    )
+    .await?;
+
+    let mut content = format!("Restored {} role(s).", missing.len() - failed);
+    if failed > 0 {
+        content.push_str(&format!(
+            " {failed} couldn't be given back, check that my role is above them."
+        ));
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_only_roles_that_can_come_back() {
+        let (alice, bob, gone) = (UserId::new(1), UserId::new(2), UserId::new(3));
+        let (red, blue, deleted) = (RoleId::new(10), RoleId::new(11), RoleId::new(12));
+        let current = HashMap::from([
+            (alice, HashSet::from([red])),
+            (bob, HashSet::from([red, blue])),
+        ]);
+        let assignable = HashSet::from([red, blue]);
+        let saved = [
+            (alice, red),
+            (alice, blue),
+            (alice, deleted),
+            (bob, blue),
+            (gone, red),
+            (gone, blue),
+        ];
+
+        assert_eq!(
+            diff(saved, &current, &assignable),
+            Restore {
+                missing: vec![(alice, blue)],
+                departed: 2,
+                deleted: 1,
+            }
+        );
+        assert_eq!(diff([], &current, &assignable), Restore::default());
+    }
+}
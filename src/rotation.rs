@@ -0,0 +1,619 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    announce,
+    audit::{self, AuditEntry},
+    util::http_status,
+    Context, Data, SlimeError,
+};
+
+/// A role handed around a pool of members on a schedule.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Rotation {
+    id: i64,
+    guild_id: i64,
+    role_id: i64,
+    channel_id: Option<i64>,
+    every_days: i32,
+    next_at: DateTime<Utc>,
+    holder_id: Option<i64>,
+}
+
+impl Rotation {
+    fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    fn role(&self) -> RoleId {
+        RoleId::new(self.role_id as u64)
+    }
+
+    fn holder(&self) -> Option<UserId> {
+        self.holder_id.map(|id| UserId::new(id as u64))
+    }
+}
+
+/// One member's place in a rotation.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Seat {
+    user_id: i64,
+    turns: i32,
+    passes: i32,
+    last_turn_at: Option<DateTime<Utc>>,
+    away_until: Option<DateTime<Utc>>,
+    added_at: DateTime<Utc>,
+}
+
+impl Seat {
+    fn user(&self) -> UserId {
+        UserId::new(self.user_id as u64)
+    }
+
+    fn away(&self, now: DateTime<Utc>) -> bool {
+        self.away_until.is_some_and(|until| until > now)
+    }
+
+    /// How many turns the member has had or gone without. Whoever has the fewest goes next.
+    fn standing(&self) -> i32 {
+        self.turns + self.passes
+    }
+}
+
+/// Who's next in line, best first: members who aren't away, by fewest turns, then by who has
+/// waited longest. Whoever holds the role goes last, so it only stays with them if nobody else
+/// can take it.
+fn line(seats: &[Seat], holder: Option<UserId>, now: DateTime<Utc>) -> Vec<&Seat> {
+    let mut line = seats.iter().filter(|s| !s.away(now)).collect::<Vec<_>>();
+    line.sort_by_key(|s| {
+        (
+            Some(s.user()) == holder,
+            s.standing(),
+            s.last_turn_at.is_some(),
+            s.last_turn_at,
+            s.added_at,
+        )
+    });
+    line
+}
+
+/// The members who are away when their turn would have come up before `next`'s, who go without
+/// it rather than being owed it when they're back.
+fn passed_over(seats: &[Seat], next: &Seat, now: DateTime<Utc>) -> Vec<UserId> {
+    seats
+        .iter()
+        .filter(|s| s.away(now) && s.standing() <= next.standing())
+        .map(Seat::user)
+        .collect()
+}
+
+async fn seats(data: &Data, rotation: &Rotation) -> Result<Vec<Seat>, SlimeError> {
+    Ok(sqlx::query_as::<_, Seat>(
+        "SELECT m.user_id, m.turns, m.passes, m.last_turn_at, m.added_at,
+            GREATEST(m.away_until, p.away_until) AS away_until
+         FROM role_rotation_members m
+         LEFT JOIN user_preferences p ON p.user_id = m.user_id
+         WHERE m.rotation_id = $1",
+    )
+    .bind(rotation.id)
+    .fetch_all(&data.pool)
+    .await?)
+}
+
+/// Gives the role to whoever is next, taking it from whoever had it, and announces who has it
+/// until `rotation.next_at`. Members who have left are dropped from the pool on the way.
+async fn hand_over(
+    ctx: &SerenityContext,
+    data: &Data,
+    rotation: &Rotation,
+    now: DateTime<Utc>,
+) -> Result<Option<UserId>, SlimeError> {
+    let pool = &data.pool;
+    let guild_id = rotation.guild();
+    let role = rotation.role();
+    let seats = seats(data, rotation).await?;
+    let reason = Some("Role rotation");
+
+    let mut next = None;
+    for seat in line(&seats, rotation.holder(), now) {
+        if Some(seat.user()) == rotation.holder() {
+            next = Some(seat);
+            break;
+        }
+        match ctx
+            .http
+            .add_member_role(guild_id, seat.user(), role, reason)
+            .await
+        {
+            Ok(()) => {
+                next = Some(seat);
+                break;
+            }
+            Err(e) if http_status(&e) == Some(404) => {
+                sqlx::query(
+                    "DELETE FROM role_rotation_members WHERE rotation_id = $1 AND user_id = $2",
+                )
+                .bind(rotation.id)
+                .bind(seat.user_id)
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if let Some(holder) = rotation.holder() {
+        if next.map(Seat::user) != Some(holder) {
+            match ctx
+                .http
+                .remove_member_role(guild_id, holder, role, reason)
+                .await
+            {
+                Ok(()) => {}
+                Err(e) if http_status(&e) == Some(404) => {}
+                Err(e) => error!("Could not take {} from {}: {}", role, holder, e),
+            }
+        }
+    }
+
+    if let Some(next) = next {
+        sqlx::query(
+            "UPDATE role_rotation_members SET turns = turns + 1, last_turn_at = $3
+             WHERE rotation_id = $1 AND user_id = $2",
+        )
+        .bind(rotation.id)
+        .bind(next.user_id)
+        .bind(now)
+        .execute(pool)
+        .await?;
+        let passed = passed_over(&seats, next, now)
+            .iter()
+            .map(|u| u.get() as i64)
+            .collect::<Vec<_>>();
+        sqlx::query(
+            "UPDATE role_rotation_members SET passes = passes + 1
+             WHERE rotation_id = $1 AND user_id = ANY($2)",
+        )
+        .bind(rotation.id)
+        .bind(&passed)
+        .execute(pool)
+        .await?;
+    }
+    let next = next.map(Seat::user);
+    sqlx::query("UPDATE role_rotations SET holder_id = $2 WHERE id = $1")
+        .bind(rotation.id)
+        .bind(next.map(|u| u.get() as i64))
+        .execute(pool)
+        .await?;
+
+    if let Some(channel) = rotation.channel_id.map(|id| ChannelId::new(id as u64)) {
+        let until = format!("<t:{}:f>", rotation.next_at.timestamp());
+        let (content, mentions) = match next {
+            Some(user) => (
+                format!("🔁 {} is {} until {until}.", user.mention(), role.mention()),
+                CreateAllowedMentions::new().users([user]),
+            ),
+            None => (
+                format!(
+                    "🔁 Nobody in the {} rotation is around to take it, so it's nobody's until \
+                     {until}.",
+                    role.mention()
+                ),
+                CreateAllowedMentions::new(),
+            ),
+        };
+        let post = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(mentions);
+        if let Err(e) = channel.send_message(ctx, post).await {
+            error!("Could not announce a role rotation in {}: {}", channel, e);
+        }
+    }
+    Ok(next)
+}
+
+/// Hands on every rotated role that's due. Each is moved to its next handover before being
+/// handed on, so a slow run can't do it twice, and one missed while the bot was down is only
+/// handed on once.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let due = sqlx::query_as::<_, Rotation>(
+        "UPDATE role_rotations SET next_at = next_at + make_interval(days => every_days * (
+            FLOOR(EXTRACT(EPOCH FROM $1 - next_at) / (every_days * 86400))::INT + 1))
+         WHERE next_at <= $1 AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .fetch_all(&data.pool)
+    .await?;
+    for rotation in due {
+        if let Err(e) = hand_over(ctx, data, &rotation, now).await {
+            error!("Could not rotate {}: {}", rotation.role(), e);
+        }
+    }
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+async fn fetch(ctx: Context<'_>, role: &Role) -> Result<Rotation, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    sqlx::query_as::<_, Rotation>(
+        "SELECT * FROM role_rotations WHERE guild_id = $1 AND role_id = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(role.id.get() as i64)
+    .fetch_optional(&ctx.data().pool)
+    .await?
+    .ok_or(SlimeError::RotationNotFound)
+}
+
+/// Hand a role around a pool of members on a schedule, giving everyone a turn.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("create", "add", "remove", "away", "skip", "show", "delete")
+)]
+pub async fn rotation(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Start handing a role around on a schedule. Add members to it with `/rotation add`.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Role to hand around"] role: Role,
+    #[description = "Days each turn lasts"]
+    #[min = 1]
+    #[max = 365]
+    every_days: i32,
+    #[description = "Channel to announce whose turn it is in"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+    #[description = "When the first turn starts, e.g. `2024-03-01 19:30` (default now)"]
+    first: Option<String>,
+    #[description = "Timezone `first` is in, as an offset like `+02:00` or `UTC-5` (default UTC)"]
+    timezone: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let now = ctx.data().clock.now();
+    if role.managed || role.id.get() == guild_id.get() {
+        return reply(ctx, "That role can't be handed out by hand.").await;
+    }
+    let next_at = match &first {
+        Some(first) => announce::schedule(first, timezone.as_deref(), now)?.1,
+        None => now,
+    };
+
+    let rotation = sqlx::query_as::<_, Rotation>(
+        "INSERT INTO role_rotations (guild_id, role_id, channel_id, every_days, next_at, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (guild_id, role_id) DO NOTHING
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(role.id.get() as i64)
+    .bind(channel.map(|c| c.id.get() as i64))
+    .bind(every_days)
+    .bind(next_at)
+    .bind(ctx.author().id.get() as i64)
+    .fetch_optional(pool)
+    .await?;
+    let Some(rotation) = rotation else {
+        return reply(ctx, format!("{} is already rotated.", role.mention())).await;
+    };
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "rotation_created",
+            target: Some(rotation.id as u64),
+            details: format!("{} every {every_days} day(s)", role.id),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "{} will be handed on every {every_days} day(s), starting <t:{}:R>. Add members to the \
+             rotation with `/rotation add`.",
+            role.mention(),
+            next_at.timestamp()
+        ),
+    )
+    .await
+}
+
+/// Add a member to a role's rotation.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Rotated role"] role: Role,
+    #[description = "Member to add"] member: Member,
+) -> Result<(), SlimeError> {
+    let rotation = fetch(ctx, &role).await?;
+    if member.user.bot {
+        return reply(ctx, "Bots don't take turns.").await;
+    }
+    // Starting level with whoever has had the fewest turns, so a newcomer isn't owed all of
+    // the turns they weren't there for.
+    let added = sqlx::query(
+        "INSERT INTO role_rotation_members (rotation_id, user_id, passes, added_at)
+         SELECT $1, $2, COALESCE(MIN(turns + passes), 0), $3
+         FROM role_rotation_members WHERE rotation_id = $1
+         ON CONFLICT (rotation_id, user_id) DO NOTHING",
+    )
+    .bind(rotation.id)
+    .bind(member.user.id.get() as i64)
+    .bind(ctx.data().clock.now())
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if added > 0 {
+        format!(
+            "{} now takes turns holding {}.",
+            member.mention(),
+            role.mention()
+        )
+    } else {
+        format!("{} is already in that rotation.", member.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Take a member out of a role's rotation. If it's their turn, it's handed on now.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Rotated role"] role: Role,
+    #[description = "Member to take out"] member: User,
+) -> Result<(), SlimeError> {
+    let rotation = fetch(ctx, &role).await?;
+    let removed =
+        sqlx::query("DELETE FROM role_rotation_members WHERE rotation_id = $1 AND user_id = $2")
+            .bind(rotation.id)
+            .bind(member.id.get() as i64)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+    if removed == 0 {
+        return reply(ctx, format!("{} isn't in that rotation.", member.mention())).await;
+    }
+
+    let mut content = format!("{} is out of the rotation.", member.mention());
+    if rotation.holder() == Some(member.id) {
+        ctx.defer_ephemeral().await?;
+        let next = hand_over(
+            ctx.serenity_context(),
+            ctx.data(),
+            &rotation,
+            ctx.data().clock.now(),
+        )
+        .await?;
+        content.push_str(&match next {
+            Some(next) => format!(" {} has it until the next handover.", next.mention()),
+            None => " Nobody else is around to take it.".to_string(),
+        });
+    }
+    reply(ctx, content).await
+}
+
+/// Sit out a role's rotation for a while. Turns that come up while you're away are skipped.
+#[poise::command(slash_command, guild_only)]
+async fn away(
+    ctx: Context<'_>,
+    #[description = "Rotated role"] role: Role,
+    #[description = "How many days to sit out for, or 0 to be back now"]
+    #[min = 0]
+    #[max = 365]
+    days: i64,
+    #[description = "Member to mark away, for those who manage roles (default you)"] member: Option<
+        User,
+    >,
+) -> Result<(), SlimeError> {
+    let rotation = fetch(ctx, &role).await?;
+    let member = member.unwrap_or_else(|| ctx.author().clone());
+    let permissions = ctx.author_member().await.and_then(|m| m.permissions);
+    if member.id != ctx.author().id && !permissions.is_some_and(|p| p.manage_roles()) {
+        return reply(
+            ctx,
+            "Only members who manage roles can mark someone else away.",
+        )
+        .await;
+    }
+    let until = (days > 0).then(|| ctx.data().clock.now() + Duration::days(days));
+    let marked = sqlx::query(
+        "UPDATE role_rotation_members SET away_until = $3 WHERE rotation_id = $1 AND user_id = $2",
+    )
+    .bind(rotation.id)
+    .bind(member.id.get() as i64)
+    .bind(until)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+    if marked == 0 {
+        return reply(ctx, format!("{} isn't in that rotation.", member.mention())).await;
+    }
+
+    let mut content = match until {
+        Some(until) => format!(
+            "{} is skipped for {} until <t:{}:D>.",
+            member.mention(),
+            role.mention(),
+            until.timestamp()
+        ),
+        None => format!("{} is back in the rotation.", member.mention()),
+    };
+    if until.is_some() && rotation.holder() == Some(member.id) {
+        content.push_str(" It's their turn now, so hand it on early with `/rotation skip`.");
+    }
+    reply(ctx, content).await
+}
+
+/// Hand a rotated role on to whoever is next now, without moving the schedule.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn skip(
+    ctx: Context<'_>,
+    #[description = "Rotated role"] role: Role,
+) -> Result<(), SlimeError> {
+    let rotation = fetch(ctx, &role).await?;
+    ctx.defer_ephemeral().await?;
+    let next = hand_over(
+        ctx.serenity_context(),
+        ctx.data(),
+        &rotation,
+        ctx.data().clock.now(),
+    )
+    .await?;
+    let content = match next {
+        Some(next) if Some(next) == rotation.holder() => format!(
+            "Nobody else is around, so {} keeps {}.",
+            next.mention(),
+            role.mention()
+        ),
+        Some(next) => format!(
+            "{} has {} until <t:{}:f>.",
+            next.mention(),
+            role.mention(),
+            rotation.next_at.timestamp()
+        ),
+        None => "Nobody in the rotation is around to take it.".to_string(),
+    };
+    reply(ctx, content).await
+}
+
+/// See whose turn it is with a rotated role, and how many everyone has had.
+#[poise::command(slash_command, guild_only)]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "Rotated role"] role: Role,
+) -> Result<(), SlimeError> {
+    let rotation = fetch(ctx, &role).await?;
+    let now = ctx.data().clock.now();
+    let seats = seats(ctx.data(), &rotation).await?;
+    let line = line(&seats, rotation.holder(), now);
+
+    let mut lines = vec![
+        format!(
+            "{} changes hands every {} day(s), next <t:{}:R>.",
+            role.mention(),
+            rotation.every_days,
+            rotation.next_at.timestamp()
+        ),
+        match rotation.holder() {
+            Some(holder) => format!("It's {}'s turn.", holder.mention()),
+            None => "Nobody has it right now.".to_string(),
+        },
+    ];
+    if seats.is_empty() {
+        lines.push("Nobody is in the rotation yet, add members with `/rotation add`.".to_string());
+    }
+    for (i, seat) in line.iter().enumerate() {
+        lines.push(format!(
+            "{}. {}: {} turn(s), {} skipped",
+            i + 1,
+            seat.user().mention(),
+            seat.turns,
+            seat.passes
+        ));
+    }
+    for seat in seats.iter().filter(|s| s.away(now)) {
+        lines.push(format!(
+            "Away: {} until <t:{}:D>, {} turn(s), {} skipped",
+            seat.user().mention(),
+            seat.away_until.unwrap_or(now).timestamp(),
+            seat.turns,
+            seat.passes
+        ));
+    }
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::new()
+                    .title("Role rotation")
+                    .description(lines.join("\n")),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Stop rotating a role. Whoever has it keeps it.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_ROLES")]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Rotated role"] role: Role,
+) -> Result<(), SlimeError> {
+    let rotation = fetch(ctx, &role).await?;
+    let pool = &ctx.data().pool;
+    sqlx::query("DELETE FROM role_rotations WHERE id = $1")
+        .bind(rotation.id)
+        .execute(pool)
+        .await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: Some(rotation.guild()),
+            actor: ctx.author().id,
+            action: "rotation_deleted",
+            target: Some(rotation.id as u64),
+            details: rotation.role().to_string(),
+            undo: Vec::new(),
+        },
+    )
+    .await?;
+    reply(ctx, format!("{} isn't rotated any more.", role.mention())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_go_to_whoever_has_had_fewest_and_skip_the_away() {
+        let now = Utc::now();
+        let seat = |user_id, turns, passes, last: Option<i64>, away: bool| Seat {
+            user_id,
+            turns,
+            passes,
+            last_turn_at: last.map(|d| now - Duration::days(d)),
+            away_until: away.then(|| now + Duration::days(3)),
+            added_at: now - Duration::days(100 - user_id),
+        };
+        let seats = [
+            seat(1, 2, 0, Some(7), false),
+            // Also on one turn, but had it more recently than 3.
+            seat(2, 1, 0, Some(14), false),
+            seat(3, 1, 0, Some(21), false),
+            seat(4, 0, 0, None, true),
+            seat(5, 0, 1, None, false),
+        ];
+
+        let order = line(&seats, Some(UserId::new(3)), now)
+            .iter()
+            .map(|s| s.user_id)
+            .collect::<Vec<_>>();
+        assert_eq!(order, [5, 2, 1, 3]);
+
+        let order = line(&seats, None, now);
+        assert_eq!(
+            order.iter().map(|s| s.user_id).collect::<Vec<_>>(),
+            [5, 3, 2, 1]
+        );
+        assert_eq!(passed_over(&seats, order[0], now), [UserId::new(4)]);
+    }
+}
@@ -0,0 +1,181 @@
+use chrono::{DateTime, Duration, Utc};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    alerts, announce, departure, digest, drift, events, gc, janitor, lfg, milestones, purge,
+    rotation, shifts, stats, verification, visibility, weather, Data, SlimeError,
+};
+
+/// How often scheduled work is checked for. Everything scheduled is accurate to about this.
+const TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Work that runs less often than the scheduler ticks.
+struct Periodic {
+    every: Duration,
+    last_run: Option<DateTime<Utc>>,
+}
+
+impl Periodic {
+    fn new(every: Duration) -> Self {
+        Self {
+            every,
+            last_run: None,
+        }
+    }
+
+    /// Whether the work is due at `now`, counting it as run if so. The first check always is.
+    fn claim(&mut self, now: DateTime<Utc>) -> bool {
+        if self.last_run.is_some_and(|at| now - at < self.every) {
+            return false;
+        }
+        self.last_run = Some(now);
+        true
+    }
+}
+
+/// Runs time-driven work in the background for as long as the bot is up. Each job works out what
+/// is due from the database and the time on [`Data::clock`], so nothing is lost across restarts.
+pub fn start(ctx: SerenityContext, data: Data) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        let mut collection = Periodic::new(Duration::minutes(gc::INTERVAL_MINUTES));
+        let mut discord = Periodic::new(Duration::minutes(events::interest::INTERVAL_MINUTES));
+        let mut threads = Periodic::new(Duration::minutes(janitor::INTERVAL_MINUTES));
+        loop {
+            interval.tick().await;
+            if !data.lease.is_active() {
+                continue;
+            }
+            run_due(
+                &ctx,
+                &data,
+                data.clock.now(),
+                &mut collection,
+                &mut discord,
+                &mut threads,
+            )
+            .await;
+        }
+    });
+}
+
+/// Runs everything due at `now`. A failing job is logged and doesn't hold up the others.
+async fn run_due(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+    collection: &mut Periodic,
+    discord: &mut Periodic,
+    threads: &mut Periodic,
+) {
+    if let Some(alert) = data.alerts.pool_checked(&data.pool) {
+        alerts::raise(ctx, data, alert).await;
+    }
+
+    let result = visibility::tick(ctx, data, now).await;
+    finished(ctx, data, "Scheduled channel visibility", result).await;
+    let result = events::channels::tick(ctx, data, now).await;
+    finished(ctx, data, "Event channel cleanup", result).await;
+    // Before reminders, so they can include the forecast.
+    let result = weather::tick(ctx, data, now).await;
+    finished(ctx, data, "Weather forecasts", result).await;
+    // Before reminders, so nobody is reminded of an event that's about to be called off.
+    let result = events::threshold::tick(ctx, data, now).await;
+    finished(ctx, data, "Event minimums", result).await;
+    let result = events::reminders::tick(ctx, data, now).await;
+    finished(ctx, data, "Event reminders", result).await;
+    let result = events::escalation::tick(ctx, data, now).await;
+    finished(ctx, data, "Host escalations", result).await;
+    let result = events::suggestions::tick(ctx, data, now).await;
+    finished(ctx, data, "Event suggestions", result).await;
+    let result = events::albums::tick(ctx, data, now).await;
+    finished(ctx, data, "Photo albums", result).await;
+    let result = events::archive::tick(ctx, data, now).await;
+    finished(ctx, data, "Event archiving", result).await;
+    let result = digest::tick(ctx, data, now).await;
+    finished(ctx, data, "Notification digests", result).await;
+    let result = announce::tick(ctx, data, now).await;
+    finished(ctx, data, "Scheduled announcements", result).await;
+    let result = lfg::tick(ctx, data, now).await;
+    finished(ctx, data, "LFG upkeep", result).await;
+    let result = shifts::tick(ctx, data, now).await;
+    finished(ctx, data, "Moderator shifts", result).await;
+    let result = verification::tick(ctx, data, now).await;
+    finished(ctx, data, "Verification gate", result).await;
+    let result = rotation::tick(ctx, data, now).await;
+    finished(ctx, data, "Role rotations", result).await;
+    let result = drift::tick(ctx, data, now).await;
+    finished(ctx, data, "Role drift checks", result).await;
+    let result = milestones::tick(ctx, data, now).await;
+    finished(ctx, data, "Milestone celebrations", result).await;
+    let result = purge::retention::tick(ctx, data, now).await;
+    finished(ctx, data, "Retention purges", result).await;
+    let result = departure::tick(ctx, data, now).await;
+    finished(ctx, data, "Purging detached guilds", result).await;
+    if discord.claim(now) {
+        let result = events::interest::sync_all(ctx, data, now).await;
+        finished(ctx, data, "Discord interest sync", result).await;
+        let result = events::sync::sync_all(ctx, data, now).await;
+        finished(ctx, data, "Scheduled event sync", result).await;
+    }
+    if threads.claim(now) {
+        let result = janitor::tick(ctx, data, now).await;
+        finished(ctx, data, "Thread janitor", result).await;
+    }
+    if collection.claim(now) {
+        let result = gc::collect(ctx, data, now).await;
+        finished(ctx, data, "Orphan collection", result).await;
+        let result = stats::prune(&data.pool, now).await;
+        finished(ctx, data, "Activity count pruning", result).await;
+    }
+}
+
+/// Logs a job's failure, and alerts the owners if it keeps failing.
+async fn finished<T>(
+    ctx: &SerenityContext,
+    data: &Data,
+    job: &'static str,
+    result: Result<T, SlimeError>,
+) {
+    if let Err(e) = &result {
+        error!("{} failed: {}", job, e);
+    }
+    if let Some(alert) = data.alerts.job_finished(job, &result) {
+        alerts::raise(ctx, data, alert).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, SimulatedClock};
+
+    #[test]
+    fn periodic_work_runs_once_per_interval() {
+        let clock = SimulatedClock::starting_at(Utc::now());
+        let mut collection = Periodic::new(Duration::minutes(gc::INTERVAL_MINUTES));
+
+        let mut runs = Vec::new();
+        for minute in 0..=180 {
+            if collection.claim(clock.now()) {
+                runs.push(minute);
+            }
+            clock.advance(Duration::from_std(TICK).unwrap());
+        }
+
+        assert_eq!(runs, vec![0, 60, 120, 180]);
+    }
+
+    #[test]
+    fn periodic_work_catches_up_after_a_gap() {
+        let clock = SimulatedClock::starting_at(Utc::now());
+        let mut collection = Periodic::new(Duration::minutes(60));
+
+        assert!(collection.claim(clock.now()));
+        // Standing by for a day doesn't queue up a day's worth of runs.
+        clock.advance(Duration::days(1));
+        assert!(collection.claim(clock.now()));
+        assert!(!collection.claim(clock.now()));
+    }
+}
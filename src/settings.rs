@@ -0,0 +1,2096 @@
+use poise::{serenity_prelude::*, ChoiceParameter};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    emoji,
+    events::{channels::EventVoice, sync::SyncPolicy},
+    i18n::{self, EmojiDensity, Tone},
+    invites,
+    notify::{self, NotificationKind},
+    quiet::QuietHours,
+    relay, templates,
+    undo::UndoStep,
+    Context, SlimeError,
+};
+
+/// Columns `/undo` may put back, with the type their stored text is cast back to.
+const UNDOABLE_COLUMNS: &[(&str, &str)] = &[
+    ("events_channel_id", "BIGINT"),
+    ("approval_queue", "BOOLEAN"),
+    ("approval_channel_id", "BIGINT"),
+    ("message_content_consent", "BOOLEAN"),
+    ("message_content_consent_by", "BIGINT"),
+    ("message_content_consent_at", "TIMESTAMPTZ"),
+    ("audit_channel_id", "BIGINT"),
+    ("voice_cleanup_minutes", "INT"),
+    ("event_voice", "TEXT"),
+    ("lfg_voice", "BOOLEAN"),
+    ("rehearsal", "BOOLEAN"),
+    ("quiet_start", "SMALLINT"),
+    ("quiet_end", "SMALLINT"),
+    ("escalation_minutes", "INT"),
+    ("event_threads", "BOOLEAN"),
+    ("thread_summaries", "BOOLEAN"),
+    ("photo_albums", "BOOLEAN"),
+    ("album_hours", "INT"),
+    ("event_banners", "BOOLEAN"),
+    ("event_suggestions", "BOOLEAN"),
+    ("suggestions_channel_id", "BIGINT"),
+    ("archive_channel_id", "BIGINT"),
+    ("archive_hours", "INT"),
+    ("scheduled_events_off", "BOOLEAN"),
+    ("sync_policy", "TEXT"),
+    ("relay_name", "TEXT"),
+    ("relay_avatar_url", "TEXT"),
+    ("forum_channel_id", "BIGINT"),
+    ("forum_only", "BOOLEAN"),
+    ("publish_announcements", "BOOLEAN"),
+    ("plain_text", "BOOLEAN"),
+    ("emoji", "TEXT[]"),
+    ("tone", "TEXT"),
+    ("emoji_density", "TEXT"),
+    ("invite_policy", "BOOLEAN"),
+    ("invite_notice", "TEXT"),
+    ("invite_appeal", "TEXT"),
+    ("raid_detection", "BOOLEAN"),
+    ("raid_joins", "INT"),
+    ("raid_window_minutes", "INT"),
+    ("raid_account_days", "INT"),
+    ("raid_channel_id", "BIGINT"),
+    ("verification_gate", "BOOLEAN"),
+    ("verification_role_id", "BIGINT"),
+    ("verification_wait_minutes", "INT"),
+    ("verification_timeout_minutes", "INT"),
+    ("appeals", "BOOLEAN"),
+    ("appeal_channel_id", "BIGINT"),
+    ("shift_channel_id", "BIGINT"),
+    ("duty_role_id", "BIGINT"),
+    ("milestone_channel_id", "BIGINT"),
+    ("bot_spam_channel_id", "BIGINT"),
+];
+
+/// Per-guild configuration. Guilds without a row get the defaults.
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct GuildSettings {
+    pub events_channel_id: Option<i64>,
+    pub approval_queue: bool,
+    pub approval_channel_id: Option<i64>,
+    /// Whether an admin has agreed to features that read message content, like auto-mod and
+    /// analytics. Handlers for those features must do nothing while this is off.
+    pub message_content_consent: bool,
+    pub audit_channel_id: Option<i64>,
+    /// What kind of channel, if any, events get when they go live.
+    pub event_voice: EventVoice,
+    /// Whether LFG groups get a temporary voice channel once everyone confirms.
+    pub lfg_voice: bool,
+    /// Whether destructive commands only report what they would do, for admins learning the bot.
+    pub rehearsal: bool,
+    pub quiet_start: Option<i16>,
+    pub quiet_end: Option<i16>,
+    /// Whether each event's post gets a discussion thread.
+    pub event_threads: bool,
+    /// Whether a summary of an event's thread is posted for those who missed it once the
+    /// thread archives. Only counts are shared without message content consent.
+    pub thread_summaries: bool,
+    /// Whether event posts and scheduled events get a generated banner image.
+    pub event_banners: bool,
+    /// Whether new events with tags are suggested to members whose past RSVPs match them.
+    pub event_suggestions: bool,
+    /// Where new events are also suggested, for members who'd rather follow a channel than get
+    /// DMs.
+    pub suggestions_channel_id: Option<i64>,
+    /// Whether new events skip Discord's scheduled events, leaving the bot's RSVPs as the only
+    /// way to sign up. Events can override it with `/event discord_event`.
+    pub scheduled_events_off: bool,
+    /// Which version wins when an event and its scheduled event were both edited.
+    pub sync_policy: SyncPolicy,
+    /// The name announcements are posted under through a webhook, if they are. See [`relay`].
+    pub relay_name: Option<String>,
+    pub relay_avatar_url: Option<String>,
+    /// A forum channel each event also gets a post in, or only gets one in with `forum_only`.
+    pub forum_channel_id: Option<i64>,
+    pub forum_only: bool,
+    /// Whether posts the bot makes in announcement channels are published to following servers.
+    pub publish_announcements: bool,
+    /// Whether posts write out what they'd otherwise only show with emoji, for screen readers.
+    pub plain_text: bool,
+    /// The guild's own emoji for the bot's buttons and posts. See [`crate::emoji`].
+    pub emoji: Vec<String>,
+    /// Which set of copy posts are written in. See [`i18n::Voice`].
+    pub tone: Tone,
+    pub emoji_density: EmojiDensity,
+    /// Whether invites to other servers are taken down. See [`crate::invites`].
+    pub invite_policy: bool,
+    /// What the poster of a removed invite is told, as a template.
+    pub invite_notice: Option<String>,
+    /// Where they can appeal, added to the notice.
+    pub invite_appeal: Option<String>,
+    /// Whether bursts of new accounts joining are treated as a raid. See [`crate::raid`].
+    pub raid_detection: bool,
+    pub raid_joins: i32,
+    pub raid_window_minutes: i32,
+    /// How young an account has to be to count towards a raid.
+    pub raid_account_days: i32,
+    pub raid_channel_id: Option<i64>,
+    /// Whether new members have to pass a challenge for the verified role. See
+    /// [`crate::verification`].
+    pub verification_gate: bool,
+    pub verification_role_id: Option<i64>,
+    /// How long after joining a member who passed gets the role.
+    pub verification_wait_minutes: i32,
+    /// How long a member has to pass before they're kicked, or 0 to let them stay.
+    pub verification_timeout_minutes: i32,
+    /// Whether timed out members can appeal. See [`crate::appeals`].
+    pub appeals: bool,
+    pub appeal_channel_id: Option<i64>,
+    /// Where moderator shift changes and gaps are announced. See [`crate::shifts`].
+    pub shift_channel_id: Option<i64>,
+    /// Held by whoever is on shift.
+    pub duty_role_id: Option<i64>,
+    /// Where member counts and anniversaries are celebrated, if anywhere. See
+    /// [`crate::milestones`].
+    pub milestone_channel_id: Option<i64>,
+    /// Where the bot's routine chatter goes, like retention summaries.
+    pub bot_spam_channel_id: Option<i64>,
+}
+
+impl GuildSettings {
+    pub async fn load(pool: &PgPool, guild_id: GuildId) -> Result<Self, SlimeError> {
+        let settings =
+            sqlx::query_as::<_, GuildSettings>("SELECT * FROM guild_settings WHERE guild_id = $1")
+                .bind(guild_id.get() as i64)
+                .fetch_optional(pool)
+                .await?;
+
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// The channel event posts go to, if one has been configured.
+    pub fn events_channel(&self) -> Option<ChannelId> {
+        self.events_channel_id.map(|id| ChannelId::new(id as u64))
+    }
+
+    /// The channel moderators review pending events in, if one has been configured.
+    pub fn approval_channel(&self) -> Option<ChannelId> {
+        self.approval_channel_id.map(|id| ChannelId::new(id as u64))
+    }
+
+    /// The channel the bot's own actions are logged to, if one has been configured.
+    pub fn audit_channel(&self) -> Option<ChannelId> {
+        self.audit_channel_id.map(|id| ChannelId::new(id as u64))
+    }
+
+    /// Where raid alerts go: the raid channel, or the audit channel without one.
+    pub fn raid_channel(&self) -> Option<ChannelId> {
+        self.raid_channel_id
+            .map(|id| ChannelId::new(id as u64))
+            .or_else(|| self.audit_channel())
+    }
+
+    /// Where appeals are decided: the appeal channel, or the audit channel without one.
+    pub fn appeal_channel(&self) -> Option<ChannelId> {
+        self.appeal_channel_id
+            .map(|id| ChannelId::new(id as u64))
+            .or_else(|| self.audit_channel())
+    }
+
+    /// Where moderator rotas go: the shift channel, or the audit channel without one.
+    pub fn shift_channel(&self) -> Option<ChannelId> {
+        self.shift_channel_id
+            .map(|id| ChannelId::new(id as u64))
+            .or_else(|| self.audit_channel())
+    }
+
+    /// Where routine reports go: the bot spam channel, or the audit channel without one.
+    pub fn bot_spam_channel(&self) -> Option<ChannelId> {
+        self.bot_spam_channel_id
+            .map(|id| ChannelId::new(id as u64))
+            .or_else(|| self.audit_channel())
+    }
+
+    /// The channel new events are suggested in, if one has been configured.
+    pub fn suggestions_channel(&self) -> Option<ChannelId> {
+        self.suggestions_channel_id
+            .map(|id| ChannelId::new(id as u64))
+    }
+
+    /// The forum channel events get posts in, if one has been configured.
+    pub fn forum_channel(&self) -> Option<ChannelId> {
+        self.forum_channel_id.map(|id| ChannelId::new(id as u64))
+    }
+
+    /// When notifications that can wait are held back for the whole guild, if ever.
+    pub fn quiet_hours(&self) -> Option<QuietHours> {
+        QuietHours::from_columns(self.quiet_start, self.quiet_end)
+    }
+}
+
+/// The current values of `columns`, as the steps that would put them back after a change.
+async fn previous(
+    pool: &PgPool,
+    guild_id: GuildId,
+    columns: &[&str],
+) -> Result<Vec<UndoStep>, SlimeError> {
+    let mut steps = Vec::new();
+    for column in columns {
+        let value = sqlx::query_scalar::<_, Option<String>>(&format!(
+            "SELECT {column}::TEXT FROM guild_settings WHERE guild_id = $1"
+        ))
+        .bind(guild_id.get() as i64)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+        steps.push(UndoStep::Setting(column.to_string(), value));
+    }
+
+    Ok(steps)
+}
+
+/// Puts a setting back to `value`, or to its default for `None`. Only [`UNDOABLE_COLUMNS`] can be
+/// restored, since the column name ends up in the query.
+pub async fn restore(
+    pool: &PgPool,
+    guild_id: GuildId,
+    column: &str,
+    value: Option<&str>,
+) -> Result<(), SlimeError> {
+    let Some((column, ty)) = UNDOABLE_COLUMNS.iter().find(|(c, _)| *c == column) else {
+        return Err(SlimeError::CannotUndo("that setting isn't tracked"));
+    };
+
+    let guild = guild_id.get() as i64;
+    match value {
+        Some(value) => {
+            sqlx::query(&format!(
+                "UPDATE guild_settings SET {column} = $2::{ty} WHERE guild_id = $1"
+            ))
+            .bind(guild)
+            .bind(value)
+            .execute(pool)
+            .await?
+        }
+        None => {
+            sqlx::query(&format!(
+                "UPDATE guild_settings SET {column} = DEFAULT WHERE guild_id = $1"
+            ))
+            .bind(guild)
+            .execute(pool)
+            .await?
+        }
+    };
+
+    Ok(())
+}
+
+/// Audits a settings change along with the values it replaced, so `/undo` can put them back.
+async fn record_change(
+    ctx: Context<'_>,
+    action: &'static str,
+    details: String,
+    undo: Vec<UndoStep>,
+) -> Result<(), SlimeError> {
+    audit::record(
+        &ctx.data().pool,
+        AuditEntry {
+            guild_id: ctx.guild_id(),
+            actor: ctx.author().id,
+            action,
+            target: None,
+            details,
+            undo,
+        },
+    )
+    .await
+}
+
+/// Configure how pond-slime behaves in this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands(
+        "events_channel",
+        "approval",
+        "streak_badge",
+        "message_content",
+        "audit_channel",
+        "voice_cleanup",
+        "event_voice",
+        "lfg_voice",
+        "rehearsal",
+        "notification_template",
+        "quiet_hours",
+        "escalation",
+        "event_threads",
+        "photo_albums",
+        "event_banners",
+        "scheduled_events",
+        "sync_policy",
+        "event_suggestions",
+        "event_archive",
+        "event_forum",
+        "relay",
+        "publish_announcements",
+        "plain_text",
+        "emoji",
+        "style",
+        "invites",
+        "raid_detection",
+        "verification",
+        "appeals",
+        "mod_shifts",
+        "milestones",
+        "bot_spam"
+    )
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Set the channel new events are posted in.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn events_channel(
+    ctx: Context<'_>,
+    #[description = "Channel to post events in"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["events_channel_id"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, events_channel_id) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET events_channel_id = EXCLUDED.events_channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.id.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_events_channel", channel.id.to_string(), undo).await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Events will now be posted in {}.",
+                channel.mention()
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Require moderator approval before member-created events are posted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn approval(
+    ctx: Context<'_>,
+    #[description = "Whether events from members without Manage Events need approval"]
+    enabled: bool,
+    #[description = "Channel moderators review pending events in"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let settings = GuildSettings::load(&ctx.data().pool, guild_id).await?;
+    let undo = previous(
+        &ctx.data().pool,
+        guild_id,
+        &["approval_queue", "approval_channel_id"],
+    )
+    .await?;
+
+    let channel_id = channel
+        .map(|c| c.id)
+        .or_else(|| settings.approval_channel());
+    if enabled && channel_id.is_none() {
+        return Err(SlimeError::MissingSetting("approval channel"));
+    }
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, approval_queue, approval_channel_id) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE
+         SET approval_queue = EXCLUDED.approval_queue, approval_channel_id = EXCLUDED.approval_channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(channel_id.map(|id| id.get() as i64))
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_approval",
+        format!("enabled: {enabled}"),
+        undo,
+    )
+    .await?;
+
+    let content = match (enabled, channel_id) {
+        (true, Some(channel_id)) => format!(
+            "Events from members will now wait for approval in {}.",
+            channel_id.mention()
+        ),
+        _ => "Events from members will now be posted right away.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Award a role when members reach an attendance streak, or stop awarding one.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn streak_badge(
+    ctx: Context<'_>,
+    #[description = "Consecutive events attended to earn the badge"]
+    #[min = 1]
+    streak: u32,
+    #[description = "Role to award, leave empty to stop awarding a badge at this streak"]
+    role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+
+    let content = match role {
+        Some(role) => {
+            sqlx::query(
+                "INSERT INTO streak_badges (guild_id, streak, role_id) VALUES ($1, $2, $3)
+                 ON CONFLICT (guild_id, streak) DO UPDATE SET role_id = EXCLUDED.role_id",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(streak as i32)
+            .bind(role.id.get() as i64)
+            .execute(pool)
+            .await?;
+            format!(
+                "Members will get {} after attending {streak} events in a row.",
+                role.mention()
+            )
+        }
+        None => {
+            sqlx::query("DELETE FROM streak_badges WHERE guild_id = $1 AND streak = $2")
+                .bind(guild_id.get() as i64)
+                .bind(streak as i32)
+                .execute(pool)
+                .await?;
+            format!("No badge will be awarded for a streak of {streak}.")
+        }
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Allow or stop features that read message content, like auto-mod and analytics.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn message_content(
+    ctx: Context<'_>,
+    #[description = "Whether the bot may read message content in this server"] enabled: bool,
+    #[description = "Channel to announce it in, defaults to the events channel"]
+    #[channel_types("Text")]
+    announce_in: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    if settings.message_content_consent == enabled {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "Message content features are already {}.",
+                    if enabled { "allowed" } else { "off" }
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut undo = previous(
+        pool,
+        guild_id,
+        &[
+            "message_content_consent",
+            "message_content_consent_by",
+            "message_content_consent_at",
+        ],
+    )
+    .await?;
+
+    // Members are told whenever the bot starts reading what they write, but turning it off
+    // needs no announcement. The announcement goes out first so consent is never recorded
+    // without it, and undoing the change takes it back down.
+    let content = if enabled {
+        let channel = announce_in
+            .map(|c| c.id)
+            .or_else(|| settings.events_channel())
+            .unwrap_or(ctx.channel_id());
+        let voice = i18n::Voice::of(ctx.serenity_context(), guild_id, &settings);
+        let announcement = voice.t_with(
+            "consent-announcement",
+            &[("admin", &ctx.author().mention())],
+        );
+        let message = channel
+            .send_message(ctx, CreateMessage::new().content(announcement))
+            .await?;
+        undo.push(UndoStep::DeleteMessage(channel, message.id));
+        format!(
+            "Message content features are now allowed, and members were told in {}.",
+            channel.mention()
+        )
+    } else {
+        "Message content features are now off.".to_string()
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO guild_settings
+            (guild_id, message_content_consent, message_content_consent_by, message_content_consent_at)
+         VALUES ($1, $2, $3, now())
+         ON CONFLICT (guild_id) DO UPDATE
+         SET message_content_consent = EXCLUDED.message_content_consent,
+             message_content_consent_by = EXCLUDED.message_content_consent_by,
+             message_content_consent_at = EXCLUDED.message_content_consent_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(ctx.author().id.get() as i64)
+    .execute(&mut *tx)
+    .await?;
+    audit::record(
+        &mut *tx,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "message_content_consent",
+            target: None,
+            details: format!("enabled: {enabled}"),
+            undo,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Set the channel the bot logs its own actions to.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn audit_channel(
+    ctx: Context<'_>,
+    #[description = "Channel for the audit log, leave empty to stop posting it"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["audit_channel_id"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, audit_channel_id) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET audit_channel_id = EXCLUDED.audit_channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.as_ref().map(|c| c.id.get() as i64))
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_audit_channel",
+        format!("{:?}", channel.as_ref().map(|c| c.id)),
+        undo,
+    )
+    .await?;
+
+    let content = match channel {
+        Some(channel) => format!("Bot actions will now be logged in {}.", channel.mention()),
+        None => "Bot actions will no longer be posted.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Set how long event voice channels stay around after the event ends.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn voice_cleanup(
+    ctx: Context<'_>,
+    #[description = "Minutes to wait after an event ends"]
+    #[min = 0]
+    #[max = 1440]
+    minutes: u32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["voice_cleanup_minutes"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, voice_cleanup_minutes) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET voice_cleanup_minutes = EXCLUDED.voice_cleanup_minutes",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(minutes as i32)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_voice_cleanup", minutes.to_string(), undo).await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Event voice channels will be removed {minutes} minute(s) after the event ends."
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give events their own voice or stage channel while they're live.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn event_voice(
+    ctx: Context<'_>,
+    #[description = "Channel to create when an event starts"] kind: EventVoice,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["event_voice"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, event_voice) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET event_voice = EXCLUDED.event_voice",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(kind)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_event_voice", format!("{kind:?}"), undo).await?;
+
+    let content = match kind {
+        EventVoice::Off => "Events will no longer get their own channel.",
+        EventVoice::Voice => "Events will get a voice channel for their attendees once they start.",
+        EventVoice::Stage => "Events will get a stage for their attendees once they start.",
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give LFG groups their own voice channel once everyone's ready.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn lfg_voice(
+    ctx: Context<'_>,
+    #[description = "Whether to create a voice channel for each group"] enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["lfg_voice"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, lfg_voice) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET lfg_voice = EXCLUDED.lfg_voice",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_lfg_voice", enabled.to_string(), undo).await?;
+
+    let content = if enabled {
+        "LFG groups will get a voice channel once everyone's ready."
+    } else {
+        "LFG groups will no longer get a voice channel."
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Have destructive commands say what they would do instead of doing it.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn rehearsal(
+    ctx: Context<'_>,
+    #[description = "Whether destructive commands should only be simulated"] enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["rehearsal"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, rehearsal) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET rehearsal = EXCLUDED.rehearsal",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .execute(pool)
+    .await?;
+    record_change(ctx, "settings_rehearsal", enabled.to_string(), undo).await?;
+
+    let content = if enabled {
+        "Rehearsal mode is on. Destructive commands will only describe what they would do."
+    } else {
+        "Rehearsal mode is off. Destructive commands will take effect again."
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Change the wording of a kind of DM the bot sends members.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn notification_template(
+    ctx: Context<'_>,
+    #[description = "Which DM to change"] kind: NotificationKind,
+    #[description = "New wording, using {event}, {time} or {position}; leave empty for the default"]
+    #[max_length = 1500]
+    template: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let example =
+        notify::set_template(&ctx.data().pool, guild_id, kind, template.as_deref()).await?;
+    record_change(
+        ctx,
+        "settings_notification_template",
+        format!("{kind:?}: {}", template.as_deref().unwrap_or("default")),
+        Vec::new(),
+    )
+    .await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "**{}** DMs will now look like this:\n>>> {example}",
+                kind.name()
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Hold back DMs that can wait, like cancellations and waitlist updates, during these hours.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn quiet_hours(
+    ctx: Context<'_>,
+    #[description = "Hour quiet hours start at, in UTC; leave both empty to turn them off"]
+    #[max = 23]
+    start: Option<u8>,
+    #[description = "Hour they end at, in UTC"]
+    #[max = 23]
+    end: Option<u8>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let quiet = QuietHours::from_columns(start.map(i16::from), end.map(i16::from));
+    if quiet.is_none() && (start.is_some() || end.is_some()) {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("Quiet hours need both a start and a different end.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let undo = previous(pool, guild_id, &["quiet_start", "quiet_end"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, quiet_start, quiet_end) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            quiet_start = EXCLUDED.quiet_start, quiet_end = EXCLUDED.quiet_end",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(quiet.map(|q| q.start as i16))
+    .bind(quiet.map(|q| q.end as i16))
+    .execute(pool)
+    .await?;
+    let content = match quiet {
+        Some(quiet) => format!(
+            "DMs that can wait are held back from {quiet} and sent once quiet hours end. \
+            Reminders still go out on time."
+        ),
+        None => "Quiet hours are off.".to_string(),
+    };
+    record_change(
+        ctx,
+        "settings_quiet_hours",
+        quiet.map_or("off".to_string(), |q| q.to_string()),
+        undo,
+    )
+    .await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Set how long members can wait on a full event's host before the host is pinged about them.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn escalation(
+    ctx: Context<'_>,
+    #[description = "Minutes before the first ping, 0 to never ping hosts (default 240)"]
+    #[max = 10080]
+    minutes: u32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["escalation_minutes"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, escalation_minutes) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET escalation_minutes = EXCLUDED.escalation_minutes",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(minutes as i32)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_escalation", minutes.to_string(), undo).await?;
+
+    let content = if minutes == 0 {
+        "Hosts won't be pinged about members waiting on them.".to_string()
+    } else {
+        format!(
+            "Hosts of full events will be pinged once members have waited {minutes} minute(s), \
+            then less and less often until they act."
+        )
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give each event's post a discussion thread, and sum it up for absentees once it's quiet.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn event_threads(
+    ctx: Context<'_>,
+    #[description = "Whether new event posts get a discussion thread"] enabled: bool,
+    #[description = "Whether to post a catch-up in the event channel once a thread archives"]
+    summaries: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let summaries = summaries.unwrap_or(false) && enabled;
+    let undo = previous(pool, guild_id, &["event_threads", "thread_summaries"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, event_threads, thread_summaries) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            event_threads = EXCLUDED.event_threads, thread_summaries = EXCLUDED.thread_summaries",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(summaries)
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_event_threads",
+        format!("threads: {enabled}, summaries: {summaries}"),
+        undo,
+    )
+    .await?;
+
+    let consent = GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent;
+    let content = match (enabled, summaries) {
+        (false, _) => "New event posts won't get a discussion thread.".to_string(),
+        (true, false) => "New event posts will get a discussion thread.".to_string(),
+        (true, true) if consent => "New event posts will get a discussion thread, and once one \
+            archives its pinned messages and attachments are summed up in the event channel."
+            .to_string(),
+        (true, true) => "New event posts will get a discussion thread, and once one archives \
+            the event channel is told how busy it was. Allow message content with \
+            `/settings message_content` to include pinned messages and attachments too."
+            .to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Open a thread for photos after each event, and pin an album of them once it closes.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn photo_albums(
+    ctx: Context<'_>,
+    #[description = "Whether events get a photo thread once they end"] enabled: bool,
+    #[description = "Hours the thread stays open for (default 24)"]
+    #[min = 1]
+    #[max = 168]
+    hours: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let hours = hours.unwrap_or(24);
+    let undo = previous(pool, guild_id, &["photo_albums", "album_hours"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, photo_albums, album_hours) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            photo_albums = EXCLUDED.photo_albums, album_hours = EXCLUDED.album_hours",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(hours as i32)
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_photo_albums",
+        format!("{enabled}, {hours} hour(s)"),
+        undo,
+    )
+    .await?;
+
+    let consent = GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent;
+    let content = match enabled {
+        false => "Events won't get a photo thread any more.".to_string(),
+        true if consent => format!(
+            "Events will get a photo thread once they end, open for {hours} hour(s), and an \
+            album of what was shared is pinned in the event channel after."
+        ),
+        true => format!(
+            "Events will get a photo thread once they end, open for {hours} hour(s). Allow \
+            message content with `/settings message_content` to have albums compiled from them."
+        ),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give new event posts and scheduled events a banner with the title, date and host.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn event_banners(
+    ctx: Context<'_>,
+    #[description = "Whether new events get a generated banner"] enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["event_banners"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, event_banners) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET event_banners = EXCLUDED.event_banners",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .execute(pool)
+    .await?;
+    record_change(ctx, "settings_event_banners", enabled.to_string(), undo).await?;
+
+    let content = if enabled {
+        "New events will get a banner on their post and scheduled event."
+    } else {
+        "New events won't get a banner."
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Choose whether new events also show up in Discord's event list.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn scheduled_events(
+    ctx: Context<'_>,
+    #[description = "Whether new events get a Discord event alongside their post"] enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["scheduled_events_off"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, scheduled_events_off) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET scheduled_events_off = EXCLUDED.scheduled_events_off",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(!enabled)
+    .execute(pool)
+    .await?;
+    record_change(ctx, "settings_scheduled_events", enabled.to_string(), undo).await?;
+
+    let content = if enabled {
+        "New events will show up in Discord's event list too."
+    } else {
+        "New events won't show up in Discord's event list, so the buttons on their post are the \
+         only way to sign up. Existing ones keep theirs."
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Choose what happens when an event is edited both here and in Discord's event list.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn sync_policy(
+    ctx: Context<'_>,
+    #[description = "Which version to keep"] policy: SyncPolicy,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["sync_policy"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, sync_policy) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET sync_policy = EXCLUDED.sync_policy",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(policy)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_sync_policy", format!("{policy:?}"), undo).await?;
+
+    let content = match policy {
+        SyncPolicy::BotWins => "When both are edited, the bot's version of an event will be kept.",
+        SyncPolicy::DiscordWins => {
+            "When both are edited, the version in Discord's event list will be kept."
+        }
+        SyncPolicy::LastWriter => "When both are edited, whichever was edited last will be kept.",
+        SyncPolicy::Review => {
+            "When both are edited, the host will be asked which to keep with `/event resolve`."
+        }
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Suggest new events with tags to members who went to similar ones.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn event_suggestions(
+    ctx: Context<'_>,
+    #[description = "Whether new events are suggested to members who opted in"] enabled: bool,
+    #[description = "Channel to also post suggestions in"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(
+        pool,
+        guild_id,
+        &["event_suggestions", "suggestions_channel_id"],
+    )
+    .await?;
+    let channel = channel.filter(|_| enabled).map(|c| c.id);
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, event_suggestions, suggestions_channel_id)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            event_suggestions = EXCLUDED.event_suggestions,
+            suggestions_channel_id = EXCLUDED.suggestions_channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(channel.map(|c| c.get() as i64))
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_event_suggestions",
+        format!("{enabled} {channel:?}"),
+        undo,
+    )
+    .await?;
+
+    let content = match (enabled, channel) {
+        (false, _) => "New events won't be suggested to anyone.".to_string(),
+        (true, None) => "New events with tags will be DMed to members who went to similar \
+            ones, if they've opted in with `/preferences suggestions`."
+            .to_string(),
+        (true, Some(channel)) => format!(
+            "New events with tags will be posted in {}, and DMed to members who went to \
+            similar ones if they've opted in with `/preferences suggestions`.",
+            channel.mention()
+        ),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Move the posts of past events into a read-only archive channel, with how they went.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn event_archive(
+    ctx: Context<'_>,
+    #[description = "Channel to archive past events in; leave empty to stop archiving"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+    #[description = "Hours after an event ends before it's archived (default 24)"]
+    #[min = 1]
+    #[max = 168]
+    hours: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let hours = hours.unwrap_or(24);
+    let undo = previous(pool, guild_id, &["archive_channel_id", "archive_hours"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, archive_channel_id, archive_hours) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            archive_channel_id = EXCLUDED.archive_channel_id, archive_hours = EXCLUDED.archive_hours",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.as_ref().map(|c| c.id.get() as i64))
+    .bind(hours as i32)
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_event_archive",
+        format!("{:?}, {hours} hour(s)", channel.as_ref().map(|c| c.id)),
+        undo,
+    )
+    .await?;
+
+    let Some(channel) = channel else {
+        let content = "Past events will stay in the events channel.";
+        ctx.send(
+            poise::CreateReply::default()
+                .content(content)
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    // Only the bot posts in the archive. Members can still read it, and rate events with
+    // `/event rate`.
+    let bot = ctx.cache().current_user().id;
+    let read_only = async {
+        channel
+            .create_permission(
+                ctx,
+                PermissionOverwrite {
+                    allow: Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS,
+                    deny: Permissions::empty(),
+                    kind: PermissionOverwriteType::Member(bot),
+                },
+            )
+            .await?;
+        channel
+            .create_permission(
+                ctx,
+                PermissionOverwrite {
+                    allow: Permissions::empty(),
+                    deny: Permissions::SEND_MESSAGES
+                        | Permissions::SEND_MESSAGES_IN_THREADS
+                        | Permissions::CREATE_PUBLIC_THREADS
+                        | Permissions::ADD_REACTIONS,
+                    kind: PermissionOverwriteType::Role(guild_id.everyone_role()),
+                },
+            )
+            .await
+    };
+    let mut content = format!(
+        "Events will be moved to {} {hours} hour(s) after they end.",
+        channel.mention()
+    );
+    if let Err(e) = read_only.await {
+        error!(
+            "Could not make archive channel {} read-only: {}",
+            channel.id, e
+        );
+        content.push_str(" I couldn't make it read-only, so check its permissions.");
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give each event a post in a forum channel, tagged like the event.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn event_forum(
+    ctx: Context<'_>,
+    #[description = "Forum to post events in; leave empty to stop"]
+    #[channel_types("Forum")]
+    channel: Option<GuildChannel>,
+    #[description = "Post events only in the forum, rather than in the events channel too"]
+    only: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let channel = channel.map(|c| c.id);
+    let only = channel.is_some() && only.unwrap_or(false);
+    let undo = previous(pool, guild_id, &["forum_channel_id", "forum_only"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, forum_channel_id, forum_only) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            forum_channel_id = EXCLUDED.forum_channel_id, forum_only = EXCLUDED.forum_only",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.map(|c| c.get() as i64))
+    .bind(only)
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_event_forum",
+        format!("{channel:?} only={only}"),
+        undo,
+    )
+    .await?;
+
+    let content = match channel {
+        None => "New events won't get forum posts.".to_string(),
+        Some(channel) if only => format!(
+            "New events will be posted in {} instead of the events channel, with the forum's \
+             tags matching theirs.",
+            channel.mention()
+        ),
+        Some(channel) => format!(
+            "New events will also get a post in {}, with the forum's tags matching theirs.",
+            channel.mention()
+        ),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Post announcements through a webhook with this server's own name and avatar.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn relay(
+    ctx: Context<'_>,
+    #[description = "Name to post under, like Pond Crier; leave empty to post as the bot"]
+    #[max_length = 80]
+    name: Option<String>,
+    #[description = "Link to the avatar image to post with"]
+    #[max_length = 500]
+    avatar_url: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let name = name.map(|n| n.trim().to_string());
+    let problem = match (&name, &avatar_url) {
+        (Some(name), _) if relay::name_problem(name).is_some() => relay::name_problem(name),
+        (_, Some(url)) if !url.starts_with("https://") => {
+            Some("the avatar has to be an https:// link")
+        }
+        _ => None,
+    };
+    if let Some(problem) = problem {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!("That won't work, {problem}."))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let avatar_url = avatar_url.filter(|_| name.is_some());
+    let undo = previous(pool, guild_id, &["relay_name", "relay_avatar_url"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, relay_name, relay_avatar_url) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            relay_name = EXCLUDED.relay_name, relay_avatar_url = EXCLUDED.relay_avatar_url",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&name)
+    .bind(&avatar_url)
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_relay",
+        format!("{name:?} {avatar_url:?}"),
+        undo,
+    )
+    .await?;
+
+    let content = match name {
+        Some(name) => format!(
+            "Suggestions and thread catch-ups will be posted as **{name}**. The bot needs the \
+             Manage Webhooks permission in those channels, and posts as itself where it's missing."
+        ),
+        None => "Suggestions and thread catch-ups will be posted by the bot itself.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Publish posts in announcement channels, so servers following them get them too.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn publish_announcements(
+    ctx: Context<'_>,
+    #[description = "Whether event posts and announcements are published to followers"]
+    enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["publish_announcements"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, publish_announcements) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET publish_announcements = EXCLUDED.publish_announcements",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_publish_announcements",
+        enabled.to_string(),
+        undo,
+    )
+    .await?;
+
+    let content = if enabled {
+        "Event posts and announcements in announcement channels will be published to the servers \
+         following them. The bot needs the Manage Messages permission there to do it."
+    } else {
+        "Posts in announcement channels will stay in this server."
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Write out in words what posts otherwise only show with emoji or layout, for screen readers.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn plain_text(
+    ctx: Context<'_>,
+    #[description = "Whether event posts, audit posts and buttons spell everything out in text"]
+    enabled: bool,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["plain_text"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, plain_text) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET plain_text = EXCLUDED.plain_text",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_plain_text", enabled.to_string(), undo).await?;
+
+    let content = if enabled {
+        "Posts will spell things out in text: events say when they're full, cancelled or over, \
+         audit posts are plain messages, and buttons have words on them. Posts already up change \
+         the next time they're updated."
+    } else {
+        "Posts will go back to their usual look."
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Use your own emoji on the bot's buttons and posts.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn emoji(
+    ctx: Context<'_>,
+    #[description = "Where the emoji shows"] slot: emoji::Slot,
+    #[description = "A custom emoji from this server, or any emoji. Leave out to go back to the default"]
+    emoji: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let picked = match emoji.as_deref() {
+        Some(input) => match emoji::parse(input) {
+            Some(picked) => Some(picked),
+            None => {
+                ctx.send(
+                    poise::CreateReply::default()
+                        .content(format!("`{input}` isn't an emoji."))
+                        .ephemeral(true),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    // The bot can only show custom emoji from servers it's in, so it sticks to this one's.
+    if let Some(ReactionType::Custom { id, .. }) = &picked {
+        if guild_id.emoji(ctx, *id).await.is_err() {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content("That emoji isn't one of this server's.")
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let undo = previous(pool, guild_id, &["emoji"]).await?;
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, emoji) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET emoji = EXCLUDED.emoji",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(emoji::with(&settings, slot, picked.as_ref()))
+    .execute(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_emoji",
+        format!(
+            "{}: {}",
+            slot.name(),
+            picked
+                .as_ref()
+                .map_or("default".to_string(), |e| e.to_string())
+        ),
+        undo,
+    )
+    .await?;
+
+    let content = match &picked {
+        Some(picked) => format!(
+            "{} will show {picked}. Posts already up change the next time they're updated.",
+            slot.name()
+        ),
+        None => format!("{} is back to the default emoji.", slot.name()),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Choose how the bot sounds in this server's posts.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("tone", "emoji_density")
+)]
+async fn style(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Choose between terse, professional copy and the slime's own voice.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn tone(
+    ctx: Context<'_>,
+    #[description = "How posts are worded"] tone: Tone,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["tone"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, tone) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET tone = EXCLUDED.tone",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(tone)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_tone", format!("{tone:?}"), undo).await?;
+
+    let content = match tone {
+        Tone::Formal => "Posts will keep to the point.",
+        Tone::Playful => {
+            "Posts will sound like a slime where there's playful copy in this server's language, \
+             and keep to the point where there isn't."
+        }
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "{content} Posts already up change the next time they're updated."
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Choose how many emoji go in posts.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "emoji"
+)]
+async fn emoji_density(
+    ctx: Context<'_>,
+    #[description = "How many emoji posts have"] density: EmojiDensity,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let undo = previous(&ctx.data().pool, guild_id, &["emoji_density"]).await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, emoji_density) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET emoji_density = EXCLUDED.emoji_density",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(density)
+    .execute(&ctx.data().pool)
+    .await?;
+    record_change(ctx, "settings_emoji_density", format!("{density:?}"), undo).await?;
+
+    let content = match density {
+        EmojiDensity::None => "Posts will leave out emoji. Buttons keep theirs.",
+        EmojiDensity::Normal => "Posts will have the emoji their copy comes with.",
+        EmojiDensity::Lots => "Posts will have an emoji on every heading, too.",
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "{content} Posts already up change the next time they're updated."
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Take down invites to other servers, except where they're allowed with `/invites`.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn invites(
+    ctx: Context<'_>,
+    #[description = "Whether invites to other servers are removed"] enabled: bool,
+    #[description = "What the poster is told. Can use {user}, {channel} and {server}"]
+    #[max_length = 1000]
+    notice: Option<String>,
+    #[description = "Where they can appeal, like \"ask in #mod-mail\""]
+    #[max_length = 200]
+    appeal: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    if let Some(notice) = &notice {
+        templates::validate(notice, invites::NOTICE_VARIABLES)
+            .map_err(SlimeError::InvalidTemplate)?;
+    }
+    let undo = previous(
+        pool,
+        guild_id,
+        &["invite_policy", "invite_notice", "invite_appeal"],
+    )
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, invite_policy, invite_notice, invite_appeal)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            invite_policy = EXCLUDED.invite_policy,
+            invite_notice = COALESCE(EXCLUDED.invite_notice, guild_settings.invite_notice),
+            invite_appeal = COALESCE(EXCLUDED.invite_appeal, guild_settings.invite_appeal)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(notice.map(|n| n.replace("\\n", "\n")))
+    .bind(appeal.map(|a| a.trim().to_string()))
+    .execute(pool)
+    .await?;
+    record_change(ctx, "settings_invites", enabled.to_string(), undo).await?;
+
+    let mut content = if enabled {
+        "Invites to other servers will be removed, except in channels, from roles and to partner \
+         servers allowed with `/invites`."
+            .to_string()
+    } else {
+        "Invites to other servers can be posted anywhere.".to_string()
+    };
+    if enabled
+        && !GuildSettings::load(pool, guild_id)
+            .await?
+            .message_content_consent
+    {
+        content.push_str(
+            "\nMessages are only read once message content features are allowed with \
+             `/settings message_content`.",
+        );
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Spot bursts of new accounts joining, raise the verification level and alert moderators.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn raid_detection(
+    ctx: Context<'_>,
+    #[description = "Whether bursts of new accounts are treated as a raid"] enabled: bool,
+    #[description = "How many new accounts make a raid (default 10)"]
+    #[min = 3]
+    #[max = 100]
+    joins: Option<u32>,
+    #[description = "Within how many minutes (default 5)"]
+    #[min = 1]
+    #[max = 60]
+    minutes: Option<u32>,
+    #[description = "Accounts younger than this many days count as new (default 7)"]
+    #[min = 1]
+    #[max = 90]
+    account_days: Option<u32>,
+    #[description = "Channel to alert moderators in, defaults to the audit channel"]
+    #[channel_types("Text")]
+    alert_in: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(
+        pool,
+        guild_id,
+        &[
+            "raid_detection",
+            "raid_joins",
+            "raid_window_minutes",
+            "raid_account_days",
+            "raid_channel_id",
+        ],
+    )
+    .await?;
+
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        "INSERT INTO guild_settings
+            (guild_id, raid_detection, raid_joins, raid_window_minutes, raid_account_days,
+             raid_channel_id)
+         VALUES ($1, $2, COALESCE($3, 10), COALESCE($4, 5), COALESCE($5, 7), $6)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            raid_detection = EXCLUDED.raid_detection,
+            raid_joins = COALESCE($3, guild_settings.raid_joins),
+            raid_window_minutes = COALESCE($4, guild_settings.raid_window_minutes),
+            raid_account_days = COALESCE($5, guild_settings.raid_account_days),
+            raid_channel_id = COALESCE($6, guild_settings.raid_channel_id)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(joins.map(|j| j as i32))
+    .bind(minutes.map(|m| m as i32))
+    .bind(account_days.map(|d| d as i32))
+    .bind(alert_in.map(|c| c.id.get() as i64))
+    .fetch_one(pool)
+    .await?;
+    record_change(ctx, "settings_raid_detection", enabled.to_string(), undo).await?;
+
+    let content = if enabled {
+        let alerts = match settings.raid_channel() {
+            Some(channel) => format!("moderators are alerted in {}", channel.mention()),
+            None => {
+                "nobody is alerted until there's an audit channel or one is given here".to_string()
+            }
+        };
+        format!(
+            "{} accounts under {} day(s) old joining within {} minute(s) count as a raid. The \
+             verification level goes up, and {alerts}.",
+            settings.raid_joins, settings.raid_account_days, settings.raid_window_minutes
+        )
+    } else {
+        "Raids won't be watched for.".to_string()
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Have new members pass a challenge before they get the member role.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn verification(
+    ctx: Context<'_>,
+    #[description = "Whether new members have to verify"] enabled: bool,
+    #[description = "Role given once they have"] role: Option<Role>,
+    #[description = "Minutes after joining before it's given, even once verified (default 0)"]
+    #[max = 1440]
+    wait: Option<u32>,
+    #[description = "Minutes to verify in before being kicked, 0 to never kick (default 0)"]
+    #[max = 10080]
+    timeout: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let current = GuildSettings::load(pool, guild_id).await?;
+    if enabled && role.is_none() && current.verification_role_id.is_none() {
+        return Err(SlimeError::MissingSetting("verified role"));
+    }
+    let undo = previous(
+        pool,
+        guild_id,
+        &[
+            "verification_gate",
+            "verification_role_id",
+            "verification_wait_minutes",
+            "verification_timeout_minutes",
+        ],
+    )
+    .await?;
+
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        "INSERT INTO guild_settings
+            (guild_id, verification_gate, verification_role_id, verification_wait_minutes,
+             verification_timeout_minutes)
+         VALUES ($1, $2, $3, COALESCE($4, 0), COALESCE($5, 0))
+         ON CONFLICT (guild_id) DO UPDATE SET
+            verification_gate = EXCLUDED.verification_gate,
+            verification_role_id = COALESCE($3, guild_settings.verification_role_id),
+            verification_wait_minutes = COALESCE($4, guild_settings.verification_wait_minutes),
+            verification_timeout_minutes =
+                COALESCE($5, guild_settings.verification_timeout_minutes)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(role.map(|r| r.id.get() as i64))
+    .bind(wait.map(|w| w as i32))
+    .bind(timeout.map(|t| t as i32))
+    .fetch_one(pool)
+    .await?;
+    if !enabled {
+        // Otherwise turning it back on would kick everyone who joined in between at once.
+        sqlx::query("DELETE FROM verification_pending WHERE guild_id = $1")
+            .bind(guild_id.get() as i64)
+            .execute(pool)
+            .await?;
+    }
+    record_change(ctx, "settings_verification", enabled.to_string(), undo).await?;
+
+    let content = match settings.verification_role_id {
+        Some(role) if enabled => {
+            let mut content = format!(
+                "New members get {} once they've verified",
+                RoleId::new(role as u64).mention()
+            );
+            if settings.verification_wait_minutes > 0 {
+                content.push_str(&format!(
+                    " and been here {} minute(s)",
+                    settings.verification_wait_minutes
+                ));
+            }
+            content.push('.');
+            if settings.verification_timeout_minutes > 0 {
+                content.push_str(&format!(
+                    " Anyone who hasn't after {} minute(s) is kicked.",
+                    settings.verification_timeout_minutes
+                ));
+            }
+            content.push_str(
+                "\nPost the button to verify with `/verification post`, and make sure the role \
+                 is below the bot's.",
+            );
+            content
+        }
+        _ => "New members won't have to verify.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Let timed out members appeal to the moderators.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn appeals(
+    ctx: Context<'_>,
+    #[description = "Whether timed out members are offered an appeal"] enabled: bool,
+    #[description = "Channel moderators decide appeals in, defaults to the audit channel"]
+    #[channel_types("Text")]
+    queue: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["appeals", "appeal_channel_id"]).await?;
+
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        "INSERT INTO guild_settings (guild_id, appeals, appeal_channel_id) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            appeals = EXCLUDED.appeals,
+            appeal_channel_id = COALESCE($3, guild_settings.appeal_channel_id)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(enabled)
+    .bind(queue.map(|c| c.id.get() as i64))
+    .fetch_one(pool)
+    .await?;
+    record_change(ctx, "settings_appeals", enabled.to_string(), undo).await?;
+
+    let content = match (enabled, settings.appeal_channel()) {
+        (true, Some(channel)) => format!(
+            "Timed out members are offered an appeal, which moderators decide in {}.",
+            channel.mention()
+        ),
+        (true, None) => "Appeals are on, but nobody sees them until there's an audit channel or \
+                         a queue is given here."
+            .to_string(),
+        (false, _) => "Timed out members won't be offered an appeal.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Choose where moderator rotas are posted and which role marks who's on shift.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn mod_shifts(
+    ctx: Context<'_>,
+    #[description = "Channel for rotas, shift changes and gaps, defaults to the audit channel"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+    #[description = "Role given to whoever is on shift, and pinged when shifts change"]
+    duty_role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["shift_channel_id", "duty_role_id"]).await?;
+
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        "INSERT INTO guild_settings (guild_id, shift_channel_id, duty_role_id) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id) DO UPDATE SET
+            shift_channel_id = EXCLUDED.shift_channel_id, duty_role_id = EXCLUDED.duty_role_id
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.map(|c| c.id.get() as i64))
+    .bind(duty_role.map(|r| r.id.get() as i64))
+    .fetch_one(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_mod_shifts",
+        format!(
+            "{:?} {:?}",
+            settings.shift_channel_id, settings.duty_role_id
+        ),
+        undo,
+    )
+    .await?;
+
+    let mut content = match settings.shift_channel() {
+        Some(channel) => format!("Moderator rotas go in {}.", channel.mention()),
+        None => "Moderator rotas need a channel, or an audit channel to fall back on.".to_string(),
+    };
+    match settings.duty_role_id {
+        Some(role) => content.push_str(&format!(
+            " Whoever is on shift holds {}.",
+            RoleId::new(role as u64).mention()
+        )),
+        None => content.push_str(" Nobody is given a role for being on shift."),
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Celebrate member count milestones, the server's birthday and members' join anniversaries.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn milestones(
+    ctx: Context<'_>,
+    #[description = "Channel to celebrate in, or leave empty to stop"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["milestone_channel_id"]).await?;
+
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        "INSERT INTO guild_settings (guild_id, milestone_channel_id) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET milestone_channel_id = EXCLUDED.milestone_channel_id
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.map(|c| c.id.get() as i64))
+    .fetch_one(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_milestones",
+        format!("{:?}", settings.milestone_channel_id),
+        undo,
+    )
+    .await?;
+
+    let content = match settings.milestone_channel_id {
+        Some(channel) => format!(
+            "Milestones are celebrated in {} once a day. Members can opt out of their own join \
+             anniversaries with `/preferences milestones`.",
+            ChannelId::new(channel as u64).mention()
+        ),
+        None => "Milestones aren't celebrated.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Set where the bot posts routine reports, like what retention policies deleted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn bot_spam(
+    ctx: Context<'_>,
+    #[description = "Channel for routine reports, or leave empty to use the audit channel"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let undo = previous(pool, guild_id, &["bot_spam_channel_id"]).await?;
+
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        "INSERT INTO guild_settings (guild_id, bot_spam_channel_id) VALUES ($1, $2)
+         ON CONFLICT (guild_id) DO UPDATE SET bot_spam_channel_id = EXCLUDED.bot_spam_channel_id
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.map(|c| c.id.get() as i64))
+    .fetch_one(pool)
+    .await?;
+    record_change(
+        ctx,
+        "settings_bot_spam",
+        format!("{:?}", settings.bot_spam_channel_id),
+        undo,
+    )
+    .await?;
+
+    let content = match settings.bot_spam_channel() {
+        Some(channel) => format!("Routine reports go to {}.", channel.mention()),
+        None => "Routine reports aren't posted anywhere. Set a channel here or with \
+                 `/settings audit_channel`."
+            .to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
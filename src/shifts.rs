@@ -0,0 +1,370 @@
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{
+    events::{parse_start_time, slots, Event, EventStatus, NewEvent},
+    posts::{self, PostKind},
+    quotas,
+    settings::GuildSettings,
+    Context, Data, SlimeError,
+};
+
+/// How far ahead a shift nobody has taken is flagged, leaving time to find someone.
+const GAP_WARNING_HOURS: i32 = 12;
+
+/// How far ahead `/shifts gaps` looks.
+const GAPS_DAYS: i32 = 7;
+
+/// One slot of a rota, with its times worked out from the event's start.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Shift {
+    guild_id: i64,
+    title: String,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    label: Option<String>,
+    /// Who took it, in the order they did.
+    members: Vec<i64>,
+}
+
+impl Shift {
+    fn guild(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    /// When it is, and what, for announcements.
+    fn describe(&self) -> String {
+        let mut when = format!(
+            "<t:{}:f>–<t:{}:t>",
+            self.starts_at.timestamp(),
+            self.ends_at.timestamp()
+        );
+        if let Some(label) = &self.label {
+            when.push_str(&format!(" ({label})"));
+        }
+        when
+    }
+}
+
+/// Selects a rota's shifts from `event_slots s` joined to `events e`, for the queries below.
+const SHIFT_COLUMNS: &str = "e.guild_id, e.title,
+    e.starts_at + make_interval(mins => s.offset_minutes) AS starts_at,
+    e.starts_at + make_interval(mins => s.offset_minutes + s.duration_minutes) AS ends_at,
+    s.label,
+    COALESCE((SELECT array_agg(u.user_id ORDER BY u.created_at)
+        FROM event_slot_signups u WHERE u.slot_id = s.id), '{}') AS members";
+
+/// Who of `ending` should lose the duty role, being on no shift that's still going.
+fn off_duty(ending: &[i64], on_duty: &[i64]) -> Vec<UserId> {
+    ending
+        .iter()
+        .filter(|user| !on_duty.contains(user))
+        .map(|user| UserId::new(*user as u64))
+        .collect()
+}
+
+async fn announce(
+    ctx: &SerenityContext,
+    settings: &GuildSettings,
+    content: String,
+    mentions: CreateAllowedMentions,
+) {
+    let Some(channel) = settings.shift_channel() else {
+        return;
+    };
+    let post = CreateMessage::new()
+        .content(content)
+        .allowed_mentions(mentions);
+    if let Err(e) = channel.send_message(ctx, post).await {
+        error!("Could not announce a shift in {}: {}", channel, e);
+    }
+}
+
+/// Flags shifts coming up that nobody has taken, and at every shift change moves the duty role
+/// to whoever is on and pings them.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let rota = "FROM events e
+        WHERE e.id = s.event_id AND e.coverage AND e.status = 'published'
+            AND e.guild_id NOT IN (SELECT guild_id FROM detached_guilds)";
+    let start = "e.starts_at + make_interval(mins => s.offset_minutes)";
+    let end = "e.starts_at + make_interval(mins => s.offset_minutes + s.duration_minutes)";
+
+    // Each is claimed before it's announced, so a slow run can't announce it twice.
+    let gaps = sqlx::query_as::<_, Shift>(&format!(
+        "UPDATE event_slots s SET gap_flagged_at = $1 {rota}
+            AND s.gap_flagged_at IS NULL AND s.started_at IS NULL
+            AND {start} > $1 AND {start} <= $1 + make_interval(hours => $2)
+            AND NOT EXISTS (SELECT 1 FROM event_slot_signups u WHERE u.slot_id = s.id)
+         RETURNING {SHIFT_COLUMNS}"
+    ))
+    .bind(now)
+    .bind(GAP_WARNING_HOURS)
+    .fetch_all(pool)
+    .await?;
+    for shift in gaps {
+        let settings = GuildSettings::load(pool, shift.guild()).await?;
+        let content = format!(
+            "Nobody has taken the shift {} on **{}** yet.",
+            shift.describe(),
+            shift.title
+        );
+        announce(ctx, &settings, content, CreateAllowedMentions::new()).await;
+    }
+
+    let started = sqlx::query_as::<_, Shift>(&format!(
+        "UPDATE event_slots s SET started_at = $1 {rota}
+            AND s.started_at IS NULL AND {start} <= $1 AND {end} > $1
+         RETURNING {SHIFT_COLUMNS}"
+    ))
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    for shift in started {
+        let settings = GuildSettings::load(pool, shift.guild()).await?;
+        let role = settings.duty_role_id.map(|r| RoleId::new(r as u64));
+        let members = shift
+            .members
+            .iter()
+            .map(|m| UserId::new(*m as u64))
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            let content = format!("⚠️ Nobody is covering the shift {}.", shift.describe());
+            announce(ctx, &settings, content, CreateAllowedMentions::new()).await;
+            continue;
+        }
+        if let Some(role) = role {
+            for member in &members {
+                if let Err(e) = ctx
+                    .http
+                    .add_member_role(shift.guild(), *member, role, Some("Shift started"))
+                    .await
+                {
+                    error!(
+                        "Could not put {} on duty in {}: {}",
+                        member,
+                        shift.guild(),
+                        e
+                    );
+                }
+            }
+        }
+        let names = members
+            .iter()
+            .map(|m| m.mention().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ping = role
+            .map(|r| format!("{} ", r.mention()))
+            .unwrap_or_default();
+        let content = format!(
+            "{ping}The shift {} has started. On duty: {names}.",
+            shift.describe()
+        );
+        let mentions = CreateAllowedMentions::new()
+            .roles(role.into_iter().collect::<Vec<_>>())
+            .users(members);
+        announce(ctx, &settings, content, mentions).await;
+    }
+
+    let ended = sqlx::query_as::<_, Shift>(&format!(
+        "UPDATE event_slots s SET ended_at = $1 {rota}
+            AND s.started_at IS NOT NULL AND s.ended_at IS NULL AND {end} <= $1
+         RETURNING {SHIFT_COLUMNS}"
+    ))
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    for shift in ended {
+        let settings = GuildSettings::load(pool, shift.guild()).await?;
+        let Some(role) = settings.duty_role_id.map(|r| RoleId::new(r as u64)) else {
+            continue;
+        };
+        // Someone straight onto their next shift keeps the role.
+        let on_duty = sqlx::query_scalar::<_, i64>(
+            "SELECT DISTINCT u.user_id FROM event_slot_signups u
+             JOIN event_slots s ON s.id = u.slot_id JOIN events e ON e.id = s.event_id
+             WHERE e.guild_id = $1 AND e.coverage
+                AND s.started_at IS NOT NULL AND s.ended_at IS NULL",
+        )
+        .bind(shift.guild_id)
+        .fetch_all(pool)
+        .await?;
+        for member in off_duty(&shift.members, &on_duty) {
+            if let Err(e) = ctx
+                .http
+                .remove_member_role(shift.guild(), member, role, Some("Shift ended"))
+                .await
+            {
+                error!(
+                    "Could not take {} off duty in {}: {}",
+                    member,
+                    shift.guild(),
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Plan who covers moderation when.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS",
+    subcommands("plan", "gaps")
+)]
+pub async fn shifts(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Post a rota of moderator shifts for mods to take.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn plan(
+    ctx: Context<'_>,
+    #[description = "What the rota covers, like `Weekend coverage`"]
+    #[max_length = 100]
+    title: String,
+    #[description = "When the first shift starts, e.g. `2024-03-01 19:30` (UTC) or a Unix timestamp"]
+    start: String,
+    #[description = "How many hours the rota covers"]
+    #[min = 1]
+    #[max = 168]
+    hours: u32,
+    #[description = "Length of each shift in minutes"]
+    #[min = 30]
+    shift_minutes: u32,
+    #[description = "How many mods each shift needs"]
+    #[min = 1]
+    mods_per_shift: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let starts_at = parse_start_time(&start).ok_or(SlimeError::InvalidTime(start))?;
+    let settings = GuildSettings::load(pool, guild_id).await?;
+    let channel = settings
+        .shift_channel()
+        .ok_or(SlimeError::MissingSetting("shift channel"))?;
+    quotas::ensure_events(pool, guild_id, 1).await?;
+
+    let duration = hours as i32 * 60;
+    let shifts = slots::divide(duration, shift_minutes as i32);
+    let covered = shifts.last().map_or(0, |(offset, length)| offset + length);
+    let new = NewEvent {
+        guild_id,
+        channel_id: channel,
+        host_id: ctx.author().id,
+        title,
+        description: "Moderator coverage. Pick a shift below to take it, or pick it again to \
+                      give it up."
+            .to_string(),
+        starts_at,
+        duration_minutes: covered,
+        capacity: None,
+        tags: Vec::new(),
+    };
+    let event = Event::insert(pool, new, EventStatus::Draft).await?;
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE events SET coverage = true WHERE id = $1")
+        .bind(event.id)
+        .execute(&mut *tx)
+        .await?;
+    for shift in &shifts {
+        let capacity = mods_per_shift.map(|m| m as i32);
+        slots::insert(&mut tx, event.id, *shift, None, capacity).await?;
+    }
+    slots::sync(&mut tx, event.id).await?;
+    tx.commit().await?;
+
+    let mut event = Event::fetch(pool, event.id)
+        .await?
+        .ok_or(SlimeError::EventNotFound(event.id))?;
+    let post = CreateMessage::new()
+        .embed(event.post_embed(ctx.serenity_context(), pool).await?)
+        .components(event.components(&settings));
+    let message = channel.send_message(ctx, post).await?;
+    event.status = EventStatus::Published;
+    event.message_id = Some(message.id.get() as i64);
+    event.save(pool).await?;
+    posts::register(pool, PostKind::Event, event.id, guild_id, &message).await?;
+
+    let mut content = format!(
+        "Posted **{}** in {}, with {} shift(s) to take.",
+        event.title,
+        channel.mention(),
+        shifts.len()
+    );
+    if covered < duration {
+        content.push_str(&format!(
+            " That covers the first {} hour(s); plan another rota for the rest.",
+            covered / 60
+        ));
+    }
+    reply(ctx, content).await
+}
+
+/// See upcoming shifts nobody has taken.
+#[poise::command(slash_command, guild_only, required_permissions = "MODERATE_MEMBERS")]
+async fn gaps(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let shifts = sqlx::query_as::<_, Shift>(&format!(
+        "SELECT {SHIFT_COLUMNS} FROM event_slots s JOIN events e ON e.id = s.event_id
+         WHERE e.guild_id = $1 AND e.coverage AND e.status = 'published'
+            AND e.starts_at + make_interval(mins => s.offset_minutes + s.duration_minutes) > $2
+            AND e.starts_at + make_interval(mins => s.offset_minutes)
+                <= $2 + make_interval(days => $3)
+            AND NOT EXISTS (SELECT 1 FROM event_slot_signups u WHERE u.slot_id = s.id)
+         ORDER BY 3
+         LIMIT 25"
+    ))
+    .bind(guild_id.get() as i64)
+    .bind(ctx.data().clock.now())
+    .bind(GAPS_DAYS)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if shifts.is_empty() {
+        return reply(
+            ctx,
+            format!("Every shift in the next {GAPS_DAYS} days is covered."),
+        )
+        .await;
+    }
+    let lines = shifts
+        .iter()
+        .map(|s| format!("• {} on **{}**", s.describe(), s.title))
+        .collect::<Vec<_>>();
+    reply(
+        ctx,
+        format!("Nobody has taken these yet:\n{}", lines.join("\n")),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty_role_stays_with_whoever_is_still_on_shift() {
+        assert_eq!(
+            off_duty(&[1, 2, 3], &[2]),
+            vec![UserId::new(1), UserId::new(3)]
+        );
+        assert!(off_duty(&[], &[1]).is_empty());
+    }
+}
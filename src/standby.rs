@@ -0,0 +1,140 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use poise::serenity_prelude::*;
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+use crate::{alerts, posts, Data, SlimeError};
+
+/// How often the active instance renews its lease, and a standby checks whether it lapsed.
+const RENEW_EVERY: Duration = Duration::from_secs(10);
+
+/// How long a lease lasts without renewal before a standby may take over.
+const LEASE_SECONDS: f64 = 30.0;
+
+/// This instance's claim on being the one that acts. Every instance shares the database, so a
+/// standby is always in sync and only has to take the lease to become active.
+pub struct Lease {
+    instance: String,
+    active: AtomicBool,
+}
+
+impl Default for Lease {
+    fn default() -> Self {
+        Self {
+            instance: format!("{:016x}", RandomState::new().build_hasher().finish()),
+            active: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Lease {
+    /// Whether this instance should handle commands, interactions and scheduled work.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Takes or renews the lease, returning whether this instance holds it afterwards.
+    async fn try_hold(&self, pool: &PgPool) -> Result<bool, SlimeError> {
+        let held = sqlx::query_scalar::<_, String>(
+            "INSERT INTO instance_lease (id, holder, renewed_at) VALUES (1, $1, now())
+             ON CONFLICT (id) DO UPDATE SET holder = EXCLUDED.holder, renewed_at = now()
+             WHERE instance_lease.holder = EXCLUDED.holder
+                OR instance_lease.renewed_at < now() - make_interval(secs => $2)
+             RETURNING holder",
+        )
+        .bind(&self.instance)
+        .bind(LEASE_SECONDS)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(held.is_some())
+    }
+}
+
+/// A change in whether this instance is the one that acts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    TakeOver,
+    StandDown,
+}
+
+/// What renewing the lease means for an instance that was `active` before, now that it `holds`
+/// the lease or doesn't.
+fn transition(active: bool, holds: bool) -> Option<Transition> {
+    match (active, holds) {
+        (false, true) => Some(Transition::TakeOver),
+        (true, false) => Some(Transition::StandDown),
+        _ => None,
+    }
+}
+
+/// Keeps this instance's lease up to date in the background. On becoming active it registers the
+/// bot's commands, so a replacement instance takes over without any manual steps.
+pub fn start(ctx: SerenityContext, data: Data, commands: Vec<CreateCommand>) {
+    tokio::spawn(async move {
+        let lease = &data.lease;
+        let mut interval = tokio::time::interval(RENEW_EVERY);
+        loop {
+            interval.tick().await;
+
+            let holds = match lease.try_hold(&data.pool).await {
+                Ok(holds) => holds,
+                Err(e) => {
+                    // Without the database nothing can be done safely, and another instance may
+                    // take over once the lease lapses, so stand down until it's reachable.
+                    error!("Could not renew instance lease: {}", e);
+                    false
+                }
+            };
+
+            match transition(lease.is_active(), holds) {
+                Some(Transition::TakeOver) => {
+                    info!("Instance {} is now active", lease.instance);
+                    if let Err(e) = data.config.register_commands(&ctx, commands.clone()).await {
+                        error!("Could not register commands after taking over: {}", e);
+                    }
+                    lease.active.store(true, Ordering::Release);
+                    // Adopt every post the last instance was looking after, sending any that
+                    // went missing while nobody was active.
+                    match posts::reconcile(&ctx, &data.pool, &data.calls).await {
+                        Ok(found) if found.has_discrepancies() => {
+                            warn!("{}", found.report());
+                            alerts::notify_owners(&ctx, &data.config, &found.report()).await;
+                        }
+                        Ok(found) => info!("{}", found.report()),
+                        Err(e) => error!("Could not reconcile posts after taking over: {}", e),
+                    }
+                }
+                Some(Transition::StandDown) => {
+                    warn!("Instance {} lost its lease, standing by", lease.instance);
+                    lease.active.store(false, Ordering::Release);
+                }
+                None => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instances_start_on_standby_and_follow_the_lease() {
+        let (a, b) = (Lease::default(), Lease::default());
+        assert!(!a.is_active());
+        assert_ne!(a.instance, b.instance);
+
+        assert_eq!(transition(false, true), Some(Transition::TakeOver));
+        assert_eq!(transition(true, false), Some(Transition::StandDown));
+        assert_eq!(transition(true, true), None);
+        assert_eq!(transition(false, false), None);
+    }
+}
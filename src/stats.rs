@@ -0,0 +1,446 @@
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+
+use crate::{
+    announce::parse_utc_offset,
+    events::attendance::{self, Streak},
+    i18n, metrics,
+    settings::GuildSettings,
+    util::paginate,
+    Context, Data, SlimeError,
+};
+
+/// How long hourly message counts and when members were last active are kept: enough for a
+/// quarter's worth of heatmap.
+pub const RETENTION_DAYS: i32 = 91;
+
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Cells of a heatmap, from no messages at all up to the busiest hour's share.
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Messages per weekday (from Monday) and hour.
+pub type Heat = [[i64; 24]; 7];
+
+/// Everything the bot holds about one member in one guild.
+#[derive(Debug, Default)]
+pub struct ActivitySummary {
+    pub hosted: i64,
+    pub drafts: i64,
+    pub rsvps: i64,
+    pub streak: Streak,
+    /// Upcoming events the member has signed up for, and whether they're on the waitlist.
+    pub upcoming: Vec<(i64, String, DateTime<Utc>, bool)>,
+    /// Roles the bot has handed out for attendance streaks.
+    pub badge_roles: Vec<RoleId>,
+}
+
+impl ActivitySummary {
+    pub async fn load(pool: &PgPool, guild_id: GuildId, user: UserId) -> Result<Self, SlimeError> {
+        let guild = guild_id.get() as i64;
+        let user_id = user.get() as i64;
+
+        let (hosted, drafts) = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT
+                COUNT(*) FILTER (WHERE status IN ('published', 'completed')),
+                COUNT(*) FILTER (WHERE status = 'draft')
+             FROM events WHERE guild_id = $1 AND host_id = $2",
+        )
+        .bind(guild)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        let rsvps = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM event_rsvps r JOIN events e ON e.id = r.event_id
+             WHERE e.guild_id = $1 AND r.user_id = $2 AND r.state IN ('confirmed', 'waitlist')",
+        )
+        .bind(guild)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        let upcoming = sqlx::query_as::<_, (i64, String, DateTime<Utc>, bool)>(
+            "SELECT e.id, e.title, e.starts_at, r.state = 'waitlist'
+             FROM event_rsvps r JOIN events e ON e.id = r.event_id
+             WHERE e.guild_id = $1 AND r.user_id = $2 AND e.status = 'published'
+                AND r.state IN ('confirmed', 'waitlist') AND e.starts_at > now()
+             ORDER BY e.starts_at
+             LIMIT 10",
+        )
+        .bind(guild)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let badge_roles = sqlx::query_scalar::<_, i64>(
+            "SELECT role_id FROM streak_badges WHERE guild_id = $1 ORDER BY streak",
+        )
+        .bind(guild)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|id| RoleId::new(id as u64))
+        .collect();
+
+        Ok(Self {
+            hosted,
+            drafts,
+            rsvps,
+            streak: attendance::streak(pool, guild_id, user).await?,
+            upcoming,
+            badge_roles,
+        })
+    }
+}
+
+/// See statistics about this server and its members.
+#[poise::command(slash_command, guild_only, subcommands("me", "commands", "heatmap"))]
+pub async fn stats(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// See your own activity in this server, and what the bot knows about you.
+#[poise::command(slash_command, guild_only)]
+async fn me(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let summary = ActivitySummary::load(&ctx.data().pool, guild_id, ctx.author().id).await?;
+
+    // Only show badge roles the member actually holds, since they can lose them to other bots
+    // or moderators.
+    let held = ctx
+        .author_member()
+        .await
+        .map(|m| m.roles.clone())
+        .unwrap_or_default();
+    let badges = summary
+        .badge_roles
+        .iter()
+        .filter(|role| held.contains(role))
+        .map(|role| role.mention().to_string())
+        .collect::<Vec<_>>();
+
+    let upcoming = summary
+        .upcoming
+        .iter()
+        .map(|(id, title, starts_at, waitlisted)| {
+            format!(
+                "#{id} **{title}** {}{}",
+                i18n::timestamp(*starts_at, FormattedTimestampStyle::ShortDateTime),
+                if *waitlisted { " (waitlist)" } else { "" }
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let or_none = |items: Vec<String>, separator: &str| {
+        if items.is_empty() {
+            "None".to_string()
+        } else {
+            items.join(separator)
+        }
+    };
+
+    let embed = CreateEmbed::new()
+        .title(format!(
+            "Your activity in {}",
+            ctx.guild()
+                .map_or_else(|| "this server".to_string(), |g| g.name.clone())
+        ))
+        .field("Events hosted", summary.hosted.to_string(), true)
+        .field("Drafts", summary.drafts.to_string(), true)
+        .field("RSVPs", summary.rsvps.to_string(), true)
+        .field("Events attended", summary.streak.attended.to_string(), true)
+        .field("Current streak", summary.streak.current.to_string(), true)
+        .field("Best streak", summary.streak.best.to_string(), true)
+        .field("Upcoming", or_none(upcoming, "\n"), false)
+        .field("Badge roles", or_none(badges, " "), false);
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+const COMMANDS_PER_PAGE: usize = 10;
+
+/// See which commands get used, and how well they hold up.
+#[poise::command(slash_command, guild_only, owners_only)]
+async fn commands(
+    ctx: Context<'_>,
+    #[description = "Count every server instead of just this one"] everywhere: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let everywhere = everywhere.unwrap_or(false);
+    let usage = metrics::usage(&ctx.data().pool, (!everywhere).then_some(guild_id)).await?;
+
+    let pages = if usage.is_empty() {
+        vec!["No commands have been used yet.".to_string()]
+    } else {
+        usage
+            .chunks(COMMANDS_PER_PAGE)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|u| {
+                        let name = match u.command.strip_prefix(metrics::BUTTON_PREFIX) {
+                            Some(prefix) => format!("`{prefix}` buttons"),
+                            None => format!("`/{}`", u.command),
+                        };
+                        let mut line = format!(
+                            "{name}: {} use(s), {} failed, {} ms average, {} ms slowest, last {}",
+                            u.invocations,
+                            u.failures,
+                            u.average_ms(),
+                            u.max_ms,
+                            i18n::timestamp(u.last_used_at, FormattedTimestampStyle::RelativeTime)
+                        );
+                        if u.slow > 0 {
+                            line.push_str(&format!(", {} deferred", u.slow));
+                        }
+                        line
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect()
+    };
+
+    let title = if everywhere {
+        "Command usage in every server"
+    } else {
+        "Command usage in this server"
+    };
+    paginate(ctx, title, &pages).await
+}
+
+/// Counts `message` towards its channel's activity, with the guild's consent. Only the count and
+/// when its author last posted are kept, not what was said.
+pub async fn counted(data: &Data, message: &Message) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if !GuildSettings::load(&data.pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO channel_activity (channel_id, guild_id, hour, messages)
+         VALUES ($1, $2, date_trunc('hour', $3), 1)
+         ON CONFLICT (channel_id, hour) DO UPDATE SET messages = channel_activity.messages + 1",
+    )
+    .bind(message.channel_id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(*message.timestamp)
+    .execute(&data.pool)
+    .await?;
+    sqlx::query(
+        "INSERT INTO member_activity (guild_id, user_id, last_message_at) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id, user_id) DO UPDATE SET last_message_at = EXCLUDED.last_message_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(message.author.id.get() as i64)
+    .bind(*message.timestamp)
+    .execute(&data.pool)
+    .await?;
+    Ok(())
+}
+
+/// Notes when a member last reacted to something, with the guild's consent.
+pub async fn reacted(data: &Data, reaction: &Reaction) -> Result<(), SlimeError> {
+    let (Some(guild_id), Some(user)) = (reaction.guild_id, reaction.user_id) else {
+        return Ok(());
+    };
+    if reaction.member.as_ref().is_some_and(|m| m.user.bot)
+        || !GuildSettings::load(&data.pool, guild_id)
+            .await?
+            .message_content_consent
+    {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO member_activity (guild_id, user_id, last_reaction_at) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id, user_id) DO UPDATE SET last_reaction_at = EXCLUDED.last_reaction_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(user.get() as i64)
+    .bind(data.clock.now())
+    .execute(&data.pool)
+    .await?;
+    Ok(())
+}
+
+/// Drops counts, and members not seen, older than [`RETENTION_DAYS`]. Returns how many hours'
+/// worth of counts went.
+pub async fn prune(pool: &PgPool, now: DateTime<Utc>) -> Result<u64, SlimeError> {
+    sqlx::query(
+        "DELETE FROM member_activity
+         WHERE GREATEST(last_message_at, last_reaction_at) < $1 - make_interval(days => $2)",
+    )
+    .bind(now)
+    .bind(RETENTION_DAYS)
+    .execute(pool)
+    .await?;
+    Ok(
+        sqlx::query("DELETE FROM channel_activity WHERE hour < $1 - make_interval(days => $2)")
+            .bind(now)
+            .bind(RETENTION_DAYS)
+            .execute(pool)
+            .await?
+            .rows_affected(),
+    )
+}
+
+/// Adds up hourly counts by weekday and hour in `offset`'s time.
+pub fn bucket(counts: &[(DateTime<Utc>, i32)], offset: FixedOffset) -> Heat {
+    let mut heat = [[0; 24]; 7];
+    for (hour, messages) in counts {
+        let local = hour.with_timezone(&offset);
+        heat[local.weekday().num_days_from_monday() as usize][local.hour() as usize] +=
+            i64::from(*messages);
+    }
+    heat
+}
+
+fn shade(count: i64, max: i64) -> char {
+    if count == 0 || max == 0 {
+        return SHADES[0];
+    }
+    // Any activity at all shows, however little next to the busiest hour.
+    let level = (count * 4 + max - 1) / max;
+    SHADES[level.clamp(1, 4) as usize]
+}
+
+/// The heatmap as a table, a row per weekday and a column per hour.
+fn render(heat: &Heat) -> String {
+    let max = heat.iter().flatten().copied().max().unwrap_or(0);
+    let mut table = format!("     {:<6}{:<6}{:<6}{}\n", 0, 6, 12, 18);
+    for (day, hours) in WEEKDAYS.iter().zip(heat) {
+        let cells = hours.iter().map(|c| shade(*c, max)).collect::<String>();
+        table.push_str(&format!("{day}  {cells}\n"));
+    }
+    table
+}
+
+/// The busiest and quietest times, in words.
+fn highlights(heat: &Heat) -> Vec<String> {
+    let hour_totals = (0..24)
+        .map(|h| heat.iter().map(|day| day[h]).sum::<i64>())
+        .collect::<Vec<_>>();
+    let day_totals = heat
+        .iter()
+        .map(|day| day.iter().sum())
+        .collect::<Vec<i64>>();
+    let (busiest_day, busiest_hour) = (0..7)
+        .flat_map(|d| (0..24).map(move |h| (d, h)))
+        .max_by_key(|&(d, h)| heat[d][h])
+        .unwrap_or_default();
+    let quietest_hour = (0..24).min_by_key(|&h| hour_totals[h]).unwrap_or_default();
+    let quietest_day = (0..7).min_by_key(|&d| day_totals[d]).unwrap_or_default();
+    vec![
+        format!(
+            "Busiest hour: {} {busiest_hour:02}:00, with {} message(s)",
+            WEEKDAYS[busiest_day], heat[busiest_day][busiest_hour]
+        ),
+        format!(
+            "Quietest hour of the day: {quietest_hour:02}:00, with {} message(s) across the week",
+            hour_totals[quietest_hour]
+        ),
+        format!(
+            "Quietest day: {}, with {} message(s)",
+            WEEKDAYS[quietest_day], day_totals[quietest_day]
+        ),
+    ]
+}
+
+/// See when a channel is busy, by weekday and hour, to plan slowmode, events and clean-ups.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_CHANNELS")]
+async fn heatmap(
+    ctx: Context<'_>,
+    #[description = "Channel to look at"]
+    #[channel_types("Text", "Voice", "PublicThread")]
+    channel: GuildChannel,
+    #[description = "How many weeks back to count (default 4)"]
+    #[min = 1]
+    #[max = 13]
+    weeks: Option<u32>,
+    #[description = "Timezone to show hours in, as an offset like `+02:00` or `UTC-5` (default UTC)"]
+    timezone: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let offset = match &timezone {
+        Some(timezone) => parse_utc_offset(timezone)
+            .ok_or_else(|| SlimeError::InvalidTimezone(timezone.clone()))?,
+        None => FixedOffset::east_opt(0).unwrap(),
+    };
+    let weeks = weeks.unwrap_or(4);
+    let counts = sqlx::query_as::<_, (DateTime<Utc>, i32)>(
+        "SELECT hour, messages FROM channel_activity
+         WHERE guild_id = $1 AND channel_id = $2 AND hour >= $3 - make_interval(weeks => $4)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(channel.id.get() as i64)
+    .bind(ctx.data().clock.now())
+    .bind(weeks as i32)
+    .fetch_all(pool)
+    .await?;
+
+    if counts.is_empty() {
+        let mut content = format!(
+            "No activity has been counted in {} over the last {weeks} week(s).",
+            channel.mention()
+        );
+        if !GuildSettings::load(pool, guild_id)
+            .await?
+            .message_content_consent
+        {
+            content.push_str(
+                "\nMessages are only counted once message content features are allowed with \
+                 `/settings message_content`.",
+            );
+        }
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let heat = bucket(&counts, offset);
+    let total = heat.iter().flatten().sum::<i64>();
+    let embed = CreateEmbed::new()
+        .title(format!("Activity in #{}", channel.name))
+        .description(format!(
+            "Messages over the last {weeks} week(s), by hour in UTC{offset}. Darker is busier.\n\
+             ```\n{}```",
+            render(&heat)
+        ))
+        .field("Total", total.to_string(), true)
+        .field("Times", highlights(&heat).join("\n"), false);
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn heatmaps_bucket_by_local_time_and_shade_by_share() {
+        // A Monday, 23:00 UTC, which is Tuesday 01:00 two hours east.
+        let hour = Utc.with_ymd_and_hms(2024, 3, 4, 23, 0, 0).unwrap();
+        let heat = bucket(&[(hour, 8)], FixedOffset::east_opt(2 * 3600).unwrap());
+        assert_eq!(heat[1][1], 8);
+        assert_eq!(heat.iter().flatten().sum::<i64>(), 8);
+
+        assert_eq!(shade(0, 8), '·');
+        assert_eq!(shade(1, 100), '░');
+        assert_eq!(shade(8, 8), '█');
+        let table = render(&heat);
+        assert_eq!(table.lines().count(), 8);
+        assert!(table.lines().nth(2).unwrap().starts_with("Tue  ·█"));
+    }
+}
@@ -0,0 +1,301 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+
+use crate::{util::rehearse, Context, SlimeError};
+
+/// Placeholders a tag can use, shown when creating one.
+const VARIABLES: &str = "`{user}`, `{channel}`, `{server}`";
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Tag {
+    content: String,
+    as_embed: bool,
+}
+
+impl Tag {
+    /// Looks a tag up by name, counting it as used.
+    async fn fetch_for_use(
+        pool: &PgPool,
+        guild_id: GuildId,
+        name: &str,
+    ) -> Result<Option<Self>, SlimeError> {
+        Ok(sqlx::query_as::<_, Tag>(
+            "UPDATE tags SET uses = uses + 1 WHERE guild_id = $1 AND name = $2
+             RETURNING content, as_embed",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(normalize(name))
+        .fetch_optional(pool)
+        .await?)
+    }
+
+    fn message(&self, user: &User, channel: ChannelId, server: &str) -> CreateMessage {
+        let text = substitute(&self.content, user, channel, server);
+        if self.as_embed {
+            CreateMessage::new().embed(CreateEmbed::new().description(text))
+        } else {
+            CreateMessage::new().content(text)
+        }
+    }
+}
+
+/// Posts a tag in `channel` on `user`'s behalf, for features that send tags outside of
+/// `/tag send`. Returns `None` if there's no such tag.
+pub async fn post(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    name: &str,
+    user: &User,
+    channel: ChannelId,
+) -> Result<Option<Message>, SlimeError> {
+    let Some(tag) = Tag::fetch_for_use(pool, guild_id, name).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        channel
+            .send_message(ctx, tag.message(user, channel, &server_name(ctx, guild_id)))
+            .await?,
+    ))
+}
+
+/// Replies to `message` with a tag, filled in for its author. Returns `None` if there's no such
+/// tag.
+pub async fn answer(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    guild_id: GuildId,
+    name: &str,
+    message: &Message,
+) -> Result<Option<Message>, SlimeError> {
+    let Some(tag) = Tag::fetch_for_use(pool, guild_id, name).await? else {
+        return Ok(None);
+    };
+    let answer = tag
+        .message(
+            &message.author,
+            message.channel_id,
+            &server_name(ctx, guild_id),
+        )
+        .reference_message(message)
+        .allowed_mentions(CreateAllowedMentions::new());
+
+    Ok(Some(message.channel_id.send_message(ctx, answer).await?))
+}
+
+/// Every tag whose name starts with `prefix`, as (name, content), for features built out of a
+/// set of tags.
+pub async fn with_prefix(
+    pool: &PgPool,
+    guild_id: GuildId,
+    prefix: &str,
+) -> Result<Vec<(String, String)>, SlimeError> {
+    Ok(sqlx::query_as::<_, (String, String)>(
+        "SELECT name, content FROM tags WHERE guild_id = $1 AND starts_with(name, $2)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(normalize(prefix))
+    .fetch_all(pool)
+    .await?)
+}
+
+pub fn server_name(ctx: &SerenityContext, guild_id: GuildId) -> String {
+    ctx.cache
+        .guild(guild_id)
+        .map_or_else(|| "this server".to_string(), |g| g.name.clone())
+}
+
+/// Fills in a tag's placeholders for where it's being sent.
+pub fn substitute(content: &str, user: &User, channel: ChannelId, server: &str) -> String {
+    content
+        .replace("{user}", &user.mention().to_string())
+        .replace("{channel}", &channel.mention().to_string())
+        .replace("{server}", server)
+}
+
+/// Tag names are matched case-insensitively.
+pub fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+pub async fn autocomplete_tag(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    sqlx::query_scalar::<_, String>(
+        "SELECT name FROM tags WHERE guild_id = $1 AND name LIKE $2 || '%' ORDER BY uses DESC LIMIT 25",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(normalize(partial))
+    .fetch_all(&ctx.data().pool)
+    .await
+    .unwrap_or_default()
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Saved responses for things that get asked a lot.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("create", "send", "delete", "list")
+)]
+pub async fn tag(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Save a response under a name, replacing any tag already using it.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Name to send it by"]
+    #[max_length = 32]
+    name: String,
+    #[description = "What to send. Can use {user}, {channel} and {server}"]
+    #[max_length = 2000]
+    content: String,
+    #[description = "Send it as an embed instead of plain text"] embed: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = normalize(&name);
+
+    sqlx::query(
+        "INSERT INTO tags (guild_id, name, content, as_embed, created_by) VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (guild_id, name) DO UPDATE SET
+            content = EXCLUDED.content, as_embed = EXCLUDED.as_embed, created_by = EXCLUDED.created_by",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(&name)
+    // Slash command options can't hold newlines, so let them be written as `\n`.
+    .bind(content.replace("\\n", "\n"))
+    .bind(embed.unwrap_or(false))
+    .bind(ctx.author().id.get() as i64)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!("Saved **{name}**. Send it with `/tag send {name}`; it can use {VARIABLES}."),
+    )
+    .await
+}
+
+/// Send a saved response in this channel.
+#[poise::command(slash_command, guild_only)]
+async fn send(
+    ctx: Context<'_>,
+    #[description = "Tag to send"]
+    #[autocomplete = "autocomplete_tag"]
+    name: String,
+    #[description = "Who `{user}` refers to, defaults to you"] user: Option<User>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = normalize(&name);
+
+    let Some(tag) = Tag::fetch_for_use(&ctx.data().pool, guild_id, &name).await? else {
+        return reply(ctx, format!("There's no tag called **{name}**.")).await;
+    };
+
+    let server = ctx
+        .guild()
+        .map_or_else(|| "this server".to_string(), |g| g.name.clone());
+    let text = substitute(
+        &tag.content,
+        user.as_ref().unwrap_or(ctx.author()),
+        ctx.channel_id(),
+        &server,
+    );
+    let reply = if tag.as_embed {
+        CreateReply::default().embed(CreateEmbed::new().description(text))
+    } else {
+        CreateReply::default().content(text)
+    };
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+/// Delete a saved response.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Tag to delete"]
+    #[autocomplete = "autocomplete_tag"]
+    name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let name = normalize(&name);
+
+    let mut tx = ctx.data().pool.begin().await?;
+    let deleted = sqlx::query("DELETE FROM tags WHERE guild_id = $1 AND name = $2")
+        .bind(guild_id.get() as i64)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        reply(ctx, format!("There's no tag called **{name}**.")).await
+    } else if rehearse(ctx, &format!("deleted the tag **{name}**.")).await? {
+        Ok(())
+    } else {
+        tx.commit().await?;
+        reply(ctx, format!("Deleted **{name}**.")).await
+    }
+}
+
+/// See every saved response in this server.
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+
+    let names =
+        sqlx::query_scalar::<_, String>("SELECT name FROM tags WHERE guild_id = $1 ORDER BY name")
+            .bind(guild_id.get() as i64)
+            .fetch_all(&ctx.data().pool)
+            .await?;
+
+    let content = if names.is_empty() {
+        "This server has no tags yet.".to_string()
+    } else {
+        names
+            .iter()
+            .map(|n| format!("`{n}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    reply(ctx, content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_fill_in_where_they_are_sent() {
+        let mut user = User::default();
+        user.id = UserId::new(5);
+        let filled = substitute(
+            "Welcome {user}! Ask in {channel}, {user}, and enjoy {server}. {unknown}",
+            &user,
+            ChannelId::new(9),
+            "Pond",
+        );
+        assert_eq!(
+            filled,
+            "Welcome <@5>! Ask in <#9>, <@5>, and enjoy Pond. {unknown}"
+        );
+    }
+
+    #[test]
+    fn tag_names_ignore_case_and_spacing() {
+        assert_eq!(normalize("  Rules "), "rules");
+        assert_eq!(normalize("HOSTING-1"), "hosting-1");
+    }
+}
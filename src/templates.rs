@@ -0,0 +1,129 @@
+use std::fmt::Display;
+
+/// A piece of a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    /// A literal `{` or `}`, written doubled.
+    Brace(char),
+    Variable(&'a str),
+}
+
+/// Splits `template` into text and `{name}` variables. `{{` and `}}` stand for literal braces.
+fn parse(template: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        if at > 0 {
+            segments.push(Segment::Text(&rest[..at]));
+        }
+        let brace = rest[at..].chars().next().unwrap();
+        rest = &rest[at + 1..];
+
+        if rest.starts_with(brace) {
+            segments.push(Segment::Brace(brace));
+            rest = &rest[1..];
+            continue;
+        }
+        if brace == '}' {
+            return Err(
+                "there's a `}` without a `{` before it, write `}}` for a literal one".into(),
+            );
+        }
+        let Some(end) = rest.find('}') else {
+            return Err("a `{` is never closed, write `{{` for a literal one".into());
+        };
+        let name = rest[..end].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("`{{{}}}` isn't a variable name", &rest[..end]));
+        }
+        segments.push(Segment::Variable(name));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+
+    Ok(segments)
+}
+
+/// Checks that `template` parses and only uses variables from `allowed`. The error explains the
+/// first problem found, for showing to whoever wrote the template.
+pub fn validate(template: &str, allowed: &[&str]) -> Result<(), String> {
+    for segment in parse(template)? {
+        if let Segment::Variable(name) = segment {
+            if !allowed.contains(&name) {
+                let allowed = allowed
+                    .iter()
+                    .map(|name| format!("`{{{name}}}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(format!("there's no `{{{name}}}`, try one of {allowed}"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills each `{name}` in `template` with its value. Variables without a value are left as they
+/// were, and a template that doesn't parse is returned unchanged, so a bad template still says
+/// something.
+pub fn render(template: &str, values: &[(&str, &dyn Display)]) -> String {
+    let Ok(segments) = parse(template) else {
+        return template.to_string();
+    };
+
+    let mut rendered = String::with_capacity(template.len());
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => rendered.push_str(text),
+            Segment::Brace(brace) => rendered.push(brace),
+            Segment::Variable(name) => match values.iter().find(|(n, _)| *n == name) {
+                Some((_, value)) => rendered.push_str(&value.to_string()),
+                None => rendered.push_str(&format!("{{{name}}}")),
+            },
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_variables_and_escaped_braces() {
+        let rendered = render(
+            "**{event}** starts {time}, {{not a variable}} {missing}",
+            &[("event", &"Game night"), ("time", &"soon")],
+        );
+        assert_eq!(
+            rendered,
+            "**Game night** starts soon, {not a variable} {missing}"
+        );
+    }
+
+    #[test]
+    fn validation_explains_what_is_wrong() {
+        let allowed = ["event", "time"];
+        assert_eq!(validate("{event} at {time}", &allowed), Ok(()));
+        assert!(validate("{position}", &allowed)
+            .unwrap_err()
+            .contains("`{event}`, `{time}`"));
+        assert!(validate("{event", &allowed)
+            .unwrap_err()
+            .contains("never closed"));
+        assert!(validate("event}", &allowed)
+            .unwrap_err()
+            .contains("without"));
+        assert!(validate("{an event}", &allowed)
+            .unwrap_err()
+            .contains("isn't a variable"));
+    }
+
+    #[test]
+    fn broken_templates_render_as_written() {
+        assert_eq!(render("{event", &[("event", &"x")]), "{event");
+    }
+}
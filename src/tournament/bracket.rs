@@ -0,0 +1,78 @@
+/// Seed numbers (1-based) in bracket order for a bracket of `size` players, so that adjacent
+/// pairs are first-round matches and the top seeds can only meet late on. `size` must be a power
+/// of two.
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let next = order.len() * 2;
+        order = order.iter().flat_map(|&s| [s, next + 1 - s]).collect();
+    }
+    order
+}
+
+/// The number of rounds a single-elimination bracket needs for `players` entrants.
+pub fn rounds(players: usize) -> u32 {
+    players.next_power_of_two().trailing_zeros()
+}
+
+/// First-round pairings for `players`, given in seed order. Seeds past the end of the list are
+/// byes, which only ever face a real player.
+pub fn first_round<T: Copy>(players: &[T]) -> Vec<(Option<T>, Option<T>)> {
+    let order = seed_order(players.len().next_power_of_two());
+    order
+        .chunks(2)
+        .map(|pair| {
+            (
+                players.get(pair[0] - 1).copied(),
+                players.get(pair[1] - 1).copied(),
+            )
+        })
+        .collect()
+}
+
+/// Where the winner of `slot` goes in the next round, and whether they're its first player.
+pub fn next_slot(slot: i32) -> (i32, bool) {
+    (slot / 2, slot % 2 == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_seeds_meet_last() {
+        assert_eq!(seed_order(1), [1]);
+        assert_eq!(seed_order(4), [1, 4, 2, 3]);
+        assert_eq!(seed_order(8), [1, 8, 4, 5, 2, 7, 3, 6]);
+    }
+
+    #[test]
+    fn byes_go_to_the_top_seeds() {
+        assert_eq!(rounds(2), 1);
+        assert_eq!(rounds(5), 3);
+        assert_eq!(rounds(8), 3);
+
+        let matches = first_round(&["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            matches,
+            [
+                (Some("a"), None),
+                (Some("d"), Some("e")),
+                (Some("b"), None),
+                (Some("c"), None),
+            ]
+        );
+        // Nobody is left without an opponent, bye or not.
+        assert!(first_round(&[1, 2, 3, 4, 5, 6, 7, 8, 9])
+            .iter()
+            .all(|pair| pair.0.is_some() || pair.1.is_some()));
+    }
+
+    #[test]
+    fn winners_move_to_the_next_round_in_pairs() {
+        assert_eq!(next_slot(0), (0, true));
+        assert_eq!(next_slot(1), (0, false));
+        assert_eq!(next_slot(6), (3, true));
+        assert_eq!(next_slot(7), (3, false));
+    }
+}
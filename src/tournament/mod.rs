@@ -0,0 +1,967 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    discord,
+    posts::{self, PostContent, PostKind},
+    undo::UndoStep,
+    util::{rehearse, respond_ephemeral, send_dm},
+    Context, Data, SlimeError,
+};
+
+mod bracket;
+
+/// Keeps the whole bracket inside one embed description.
+const MAX_PLAYERS: u32 = 32;
+
+const NOT_RUNNING: &str = "This tournament was cancelled or is already over.";
+
+const REPORTED_AGAIN: &str = "The score was reported again since, answer the newest prompt.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+enum TournamentStatus {
+    /// Taking sign-ups through the buttons on its post.
+    Signup,
+    Running,
+    Finished,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Tournament {
+    id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    host_id: i64,
+    name: String,
+    max_players: i32,
+    status: TournamentStatus,
+    winner_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Match {
+    id: i64,
+    tournament_id: i64,
+    round: i32,
+    slot: i32,
+    player1_id: Option<i64>,
+    player2_id: Option<i64>,
+    score1: Option<i32>,
+    score2: Option<i32>,
+    reported_by: Option<i64>,
+    winner_id: Option<i64>,
+    thread_id: Option<i64>,
+}
+
+fn user(id: i64) -> UserId {
+    UserId::new(id as u64)
+}
+
+impl Tournament {
+    async fn fetch(pool: &PgPool, id: i64) -> Result<Option<Self>, SlimeError> {
+        Ok(
+            sqlx::query_as::<_, Tournament>("SELECT * FROM tournaments WHERE id = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?,
+        )
+    }
+
+    fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    async fn players(&self, pool: &PgPool) -> Result<Vec<UserId>, SlimeError> {
+        let ids = sqlx::query_scalar::<_, i64>(
+            "SELECT user_id FROM tournament_players WHERE tournament_id = $1 ORDER BY joined_at",
+        )
+        .bind(self.id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids.into_iter().map(user).collect())
+    }
+
+    async fn matches(&self, pool: &PgPool) -> Result<Vec<Match>, SlimeError> {
+        Ok(sqlx::query_as::<_, Match>(
+            "SELECT * FROM tournament_matches WHERE tournament_id = $1 ORDER BY round, slot",
+        )
+        .bind(self.id)
+        .fetch_all(pool)
+        .await?)
+    }
+
+    async fn set_status(
+        &mut self,
+        pool: &PgPool,
+        status: TournamentStatus,
+    ) -> Result<(), SlimeError> {
+        sqlx::query("UPDATE tournaments SET status = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(status)
+            .execute(pool)
+            .await?;
+        self.status = status;
+        Ok(())
+    }
+
+    /// The live post: sign-ups while they're open, then the bracket as it plays out.
+    async fn embed(&self, pool: &PgPool) -> Result<CreateEmbed, SlimeError> {
+        let embed = CreateEmbed::new()
+            .title(format!("🏆 {}", self.name))
+            .footer(CreateEmbedFooter::new(format!("Tournament #{}", self.id)));
+
+        let description = match self.status {
+            TournamentStatus::Signup => {
+                let players = self.players(pool).await?;
+                let list = players
+                    .iter()
+                    .map(|p| p.mention().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "Hosted by {}. Sign-ups are open, **{} / {}** players so far.\n{list}",
+                    user(self.host_id).mention(),
+                    players.len(),
+                    self.max_players
+                )
+            }
+            TournamentStatus::Cancelled => "This tournament was cancelled.".to_string(),
+            TournamentStatus::Running | TournamentStatus::Finished => {
+                let matches = self.matches(pool).await?;
+                let mut text = match self.winner_id {
+                    Some(winner) => format!("Winner: {} 🎉\n", user(winner).mention()),
+                    None => String::new(),
+                };
+                let mut round = 0;
+                for m in &matches {
+                    if m.round != round {
+                        round = m.round;
+                        text.push_str(&format!("\n**Round {round}**\n"));
+                    }
+                    text.push_str(&m.describe());
+                    text.push('\n');
+                }
+                text
+            }
+        };
+
+        Ok(embed.description(description))
+    }
+
+    fn components(&self) -> Vec<CreateActionRow> {
+        if self.status != TournamentStatus::Signup {
+            return vec![];
+        }
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(CustomId::new(Kind::Tournament, "join", self.id))
+                .label("Sign up")
+                .style(ButtonStyle::Success),
+            CreateButton::new(CustomId::new(Kind::Tournament, "leave", self.id))
+                .label("Withdraw")
+                .style(ButtonStyle::Secondary),
+        ])]
+    }
+
+    async fn refresh_post(&self, ctx: &SerenityContext, pool: &PgPool) -> Result<(), SlimeError> {
+        let Some(message_id) = self.message_id else {
+            return Ok(());
+        };
+        self.channel()
+            .edit_message(
+                ctx,
+                MessageId::new(message_id as u64),
+                EditMessage::new()
+                    .embed(self.embed(pool).await?)
+                    .components(self.components()),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl Match {
+    async fn fetch(pool: &PgPool, id: i64) -> Result<Option<Self>, SlimeError> {
+        Ok(
+            sqlx::query_as::<_, Match>("SELECT * FROM tournament_matches WHERE id = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?,
+        )
+    }
+
+    fn players(&self) -> Option<(UserId, UserId)> {
+        Some((user(self.player1_id?), user(self.player2_id?)))
+    }
+
+    /// One line of the bracket embed.
+    fn describe(&self) -> String {
+        let name = |id: Option<i64>| {
+            let Some(id) = id else {
+                return "TBD".to_string();
+            };
+            let mention = user(id).mention().to_string();
+            if self.winner_id == Some(id) {
+                format!("**{mention}**")
+            } else {
+                mention
+            }
+        };
+        let result = match (self.score1, self.score2, self.winner_id) {
+            (Some(a), Some(b), Some(_)) => format!("{a}–{b}"),
+            (_, _, Some(_)) => "bye".to_string(),
+            _ => "vs".to_string(),
+        };
+        format!(
+            "`#{}` {} {result} {}",
+            self.id,
+            name(self.player1_id),
+            name(self.player2_id)
+        )
+    }
+
+    /// Opens a thread for the match once both players are known, and tells them how to report.
+    async fn open_thread(
+        &mut self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+        tournament: &Tournament,
+    ) -> Result<(), SlimeError> {
+        let Some((a, b)) = self.players() else {
+            return Ok(());
+        };
+        if self.thread_id.is_some() {
+            return Ok(());
+        }
+
+        let thread = tournament
+            .channel()
+            .create_thread(
+                ctx,
+                CreateThread::new(format!(
+                    "{} · round {} · match {}",
+                    tournament.name, self.round, self.id
+                ))
+                .kind(ChannelType::PublicThread),
+            )
+            .await?;
+        thread
+            .send_message(
+                ctx,
+                CreateMessage::new().content(format!(
+                    "{} vs {}! When you're done, either of you can run `/tournament report {}` \
+                     with the scores, and the other confirms them.",
+                    a.mention(),
+                    b.mention(),
+                    self.id
+                )),
+            )
+            .await?;
+
+        sqlx::query("UPDATE tournament_matches SET thread_id = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(thread.id.get() as i64)
+            .execute(pool)
+            .await?;
+        self.thread_id = Some(thread.id.get() as i64);
+        Ok(())
+    }
+
+    fn thread(&self) -> Option<ChannelId> {
+        self.thread_id.map(|id| ChannelId::new(id as u64))
+    }
+
+    /// The reported score, if there's one waiting to be confirmed.
+    fn reported(&self) -> Option<(i32, i32)> {
+        self.reported_by?;
+        Some((self.score1?, self.score2?))
+    }
+}
+
+/// Marks a score prompt's buttons with the score it asks about, so a press on the prompt of an
+/// earlier report doesn't settle or dispute the one that replaced it.
+fn score_nonce((score1, score2): (i32, i32)) -> u64 {
+    (score1 as u32 as u64) << 32 | score2 as u32 as u64
+}
+
+/// What came of trying to settle a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Advance {
+    /// The winner went through.
+    Done,
+    /// The match had already been settled, by a double press or the host settling it at the same
+    /// time, or its score was reported again, and the bracket was left alone.
+    Settled,
+    /// The tournament was cancelled or is over.
+    NotRunning,
+}
+
+/// Records `winner` for the match and moves them into the next round, finishing the tournament
+/// after the final. When it's settled by confirming a reported score, `reported` is that score,
+/// and the match is left alone if it's been reported differently since.
+async fn advance(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    tournament: &mut Tournament,
+    finished: &Match,
+    winner: UserId,
+    reported: Option<(i32, i32)>,
+) -> Result<Advance, SlimeError> {
+    let mut tx = pool.begin().await?;
+    // Holds the tournament for the transaction, so it can't be cancelled halfway through.
+    let running =
+        sqlx::query("UPDATE tournaments SET status = status WHERE id = $1 AND status = 'running'")
+            .bind(tournament.id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    if running == 0 {
+        return Ok(Advance::NotRunning);
+    }
+    let claimed = sqlx::query(
+        "UPDATE tournament_matches SET winner_id = $2, reported_by = NULL
+         WHERE id = $1 AND winner_id IS NULL
+            AND ($3::integer IS NULL OR (score1 = $3 AND score2 = $4))",
+    )
+    .bind(finished.id)
+    .bind(winner.get() as i64)
+    .bind(reported.map(|scores| scores.0))
+    .bind(reported.map(|scores| scores.1))
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(Advance::Settled);
+    }
+
+    let (slot, first) = bracket::next_slot(finished.slot);
+    let column = if first { "player1_id" } else { "player2_id" };
+    let next = sqlx::query_as::<_, Match>(&format!(
+        "UPDATE tournament_matches SET {column} = $4
+         WHERE tournament_id = $1 AND round = $2 AND slot = $3
+         RETURNING *"
+    ))
+    .bind(tournament.id)
+    .bind(finished.round + 1)
+    .bind(slot)
+    .bind(winner.get() as i64)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if next.is_none() {
+        sqlx::query(
+            "UPDATE tournaments SET status = 'finished', winner_id = $2
+             WHERE id = $1 AND status = 'running'",
+        )
+        .bind(tournament.id)
+        .bind(winner.get() as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    match next {
+        Some(mut next) => next.open_thread(ctx, pool, tournament).await?,
+        None => {
+            tournament.status = TournamentStatus::Finished;
+            tournament.winner_id = Some(winner.get() as i64);
+        }
+    }
+
+    Ok(Advance::Done)
+}
+
+/// A tournament's post as it should look now, while sign-ups are open or it's being played.
+pub async fn render_post(pool: &PgPool, id: i64) -> Result<Option<PostContent>, SlimeError> {
+    let Some(tournament) = Tournament::fetch(pool, id).await?.filter(|t| {
+        matches!(
+            t.status,
+            TournamentStatus::Signup | TournamentStatus::Running
+        )
+    }) else {
+        return Ok(None);
+    };
+
+    Ok(Some(PostContent {
+        content: None,
+        embed: tournament.embed(pool).await?,
+        components: tournament.components(),
+    }))
+}
+
+/// Resumes a cancelled tournament where it left off, for `/undo`. Tournaments with a bracket were
+/// running when they were cancelled; the rest were still taking sign-ups.
+pub async fn reopen(ctx: &SerenityContext, pool: &PgPool, id: i64) -> Result<(), SlimeError> {
+    let mut tournament = Tournament::fetch(pool, id)
+        .await?
+        .filter(|t| t.status == TournamentStatus::Cancelled)
+        .ok_or(SlimeError::CannotUndo(
+            "the tournament is no longer cancelled",
+        ))?;
+
+    let status = if tournament.matches(pool).await?.is_empty() {
+        TournamentStatus::Signup
+    } else {
+        TournamentStatus::Running
+    };
+    tournament.set_status(pool, status).await?;
+    tournament.refresh_post(ctx, pool).await
+}
+
+/// Handles sign-ups and score confirmations.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let (action, id) = (custom_id.action.as_str(), custom_id.id);
+
+    let pool = &data.pool;
+    let clicker = interaction.user.id;
+    match action {
+        "join" | "leave" => {
+            let Some(tournament) = Tournament::fetch(pool, id)
+                .await?
+                .filter(|t| t.status == TournamentStatus::Signup)
+            else {
+                return respond_ephemeral(
+                    ctx,
+                    interaction,
+                    "Sign-ups for this tournament are closed.",
+                )
+                .await;
+            };
+
+            let content = if action == "join" {
+                let joined = sqlx::query(
+                    "INSERT INTO tournament_players (tournament_id, user_id)
+                     SELECT $1, $2 WHERE (SELECT COUNT(*) FROM tournament_players WHERE tournament_id = $1) < $3
+                     ON CONFLICT DO NOTHING",
+                )
+                .bind(tournament.id)
+                .bind(clicker.get() as i64)
+                .bind(tournament.max_players as i64)
+                .execute(pool)
+                .await?
+                .rows_affected();
+                if joined > 0 {
+                    "You're signed up!"
+                } else {
+                    "You're already signed up, or the tournament is full."
+                }
+            } else {
+                sqlx::query(
+                    "DELETE FROM tournament_players WHERE tournament_id = $1 AND user_id = $2",
+                )
+                .bind(tournament.id)
+                .bind(clicker.get() as i64)
+                .execute(pool)
+                .await?;
+                "You've withdrawn."
+            };
+            respond_ephemeral(ctx, interaction, content).await?;
+            tournament.refresh_post(ctx, pool).await
+        }
+        "confirm" | "dispute" => {
+            let Some(m) = Match::fetch(pool, id)
+                .await?
+                .filter(|m| m.winner_id.is_none())
+            else {
+                return respond_ephemeral(ctx, interaction, "This match is already settled.").await;
+            };
+            let (Some((a, b)), Some(reporter), Some(reported)) =
+                (m.players(), m.reported_by.map(user), m.reported())
+            else {
+                return respond_ephemeral(
+                    ctx,
+                    interaction,
+                    "There's no score waiting to be confirmed.",
+                )
+                .await;
+            };
+            if custom_id.nonce != Some(score_nonce(reported)) {
+                return respond_ephemeral(ctx, interaction, REPORTED_AGAIN).await;
+            }
+            let opponent = if reporter == a { b } else { a };
+            if clicker != opponent {
+                return respond_ephemeral(
+                    ctx,
+                    interaction,
+                    "Only the other player can confirm this score.",
+                )
+                .await;
+            }
+            let Some(mut tournament) = Tournament::fetch(pool, m.tournament_id)
+                .await?
+                .filter(|t| t.status == TournamentStatus::Running)
+            else {
+                return respond_ephemeral(ctx, interaction, NOT_RUNNING).await;
+            };
+
+            let content = if action == "confirm" {
+                let winner = if reported.0 > reported.1 { a } else { b };
+                match advance(ctx, pool, &mut tournament, &m, winner, Some(reported)).await? {
+                    Advance::Done => {}
+                    Advance::Settled => {
+                        return respond_ephemeral(
+                            ctx,
+                            interaction,
+                            "This match is already settled, or its score was reported again.",
+                        )
+                        .await
+                    }
+                    Advance::NotRunning => {
+                        return respond_ephemeral(ctx, interaction, NOT_RUNNING).await
+                    }
+                }
+                tournament.refresh_post(ctx, pool).await?;
+                format!("Confirmed, {} goes through!", winner.mention())
+            } else {
+                sqlx::query(
+                    "UPDATE tournament_matches SET score1 = NULL, score2 = NULL, reported_by = NULL
+                     WHERE id = $1 AND winner_id IS NULL AND score1 = $2 AND score2 = $3",
+                )
+                .bind(m.id)
+                .bind(reported.0)
+                .bind(reported.1)
+                .execute(pool)
+                .await?;
+                let host = user(tournament.host_id);
+                let note = format!(
+                    "The score for match #{} in **{}** was disputed, settle it with `/tournament settle`.",
+                    m.id, tournament.name
+                );
+                if let Err(e) = send_dm(ctx, host, CreateMessage::new().content(note)).await {
+                    error!("Could not DM tournament host about dispute: {}", e);
+                }
+                format!(
+                    "Score disputed, and the host {} has been told.",
+                    host.mention()
+                )
+            };
+
+            discord::respond(
+                ctx,
+                interaction,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Loads a tournament in this guild that the author hosts, or can manage as a moderator.
+async fn fetch_managed(ctx: Context<'_>, id: i64) -> Result<Option<Tournament>, SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let permissions = ctx.author_member().await.and_then(|m| m.permissions);
+    let author = ctx.author().id;
+
+    Ok(Tournament::fetch(&ctx.data().pool, id).await?.filter(|t| {
+        t.guild_id == guild_id.get() as i64
+            && (user(t.host_id) == author || permissions.is_some_and(|p| p.manage_events()))
+    }))
+}
+
+/// Run single-elimination tournaments.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("create", "start", "report", "settle", "cancel")
+)]
+pub async fn tournament(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Open sign-ups for a new tournament in this channel.
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Tournament name"]
+    #[max_length = 80]
+    name: String,
+    #[description = "Most players who can sign up"]
+    #[min = 2]
+    #[max = 32]
+    max_players: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+
+    let mut tournament = sqlx::query_as::<_, Tournament>(
+        "INSERT INTO tournaments (guild_id, channel_id, host_id, name, max_players)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING *",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.channel_id().get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&name)
+    .bind(max_players.unwrap_or(MAX_PLAYERS) as i32)
+    .fetch_one(pool)
+    .await?;
+
+    let message = ctx
+        .channel_id()
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(tournament.embed(pool).await?)
+                .components(tournament.components()),
+        )
+        .await?;
+    sqlx::query("UPDATE tournaments SET message_id = $2 WHERE id = $1")
+        .bind(tournament.id)
+        .bind(message.id.get() as i64)
+        .execute(pool)
+        .await?;
+    tournament.message_id = Some(message.id.get() as i64);
+    posts::register(
+        pool,
+        PostKind::Tournament,
+        tournament.id,
+        guild_id,
+        &message,
+    )
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Sign-ups for **{name}** are open. Run `/tournament start {}` when you're ready.",
+            tournament.id
+        ),
+    )
+    .await
+}
+
+/// Close sign-ups and draw the bracket.
+#[poise::command(slash_command, guild_only)]
+async fn start(
+    ctx: Context<'_>,
+    #[description = "Tournament number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let Some(mut tournament) = fetch_managed(ctx, id)
+        .await?
+        .filter(|t| t.status == TournamentStatus::Signup)
+    else {
+        return reply(
+            ctx,
+            format!("Tournament #{id} doesn't exist, isn't yours, or has already started."),
+        )
+        .await;
+    };
+    let pool = &ctx.data().pool;
+    ctx.defer_ephemeral().await?;
+
+    // Closes sign-ups first, so two starts at once can't both draw a bracket.
+    let mut tx = pool.begin().await?;
+    let claimed = sqlx::query(
+        "UPDATE tournaments SET status = 'running' WHERE id = $1 AND status = 'signup'",
+    )
+    .bind(tournament.id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return reply(ctx, format!("Tournament #{id} has already started.")).await;
+    }
+    // Seeds are drawn at random.
+    let players = sqlx::query_scalar::<_, i64>(
+        "SELECT user_id FROM tournament_players WHERE tournament_id = $1 ORDER BY random()",
+    )
+    .bind(tournament.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    if players.len() < 2 {
+        return reply(ctx, "At least two players need to sign up first.").await;
+    }
+
+    let rounds = bracket::rounds(players.len());
+    for (slot, (a, b)) in bracket::first_round(&players).into_iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO tournament_matches (tournament_id, round, slot, player1_id, player2_id)
+             VALUES ($1, 1, $2, $3, $4)",
+        )
+        .bind(tournament.id)
+        .bind(slot as i32)
+        .bind(a)
+        .bind(b)
+        .execute(&mut *tx)
+        .await?;
+    }
+    for round in 2..=rounds {
+        let matches = 1 << (rounds - round);
+        for slot in 0..matches {
+            sqlx::query(
+                "INSERT INTO tournament_matches (tournament_id, round, slot) VALUES ($1, $2, $3)",
+            )
+            .bind(tournament.id)
+            .bind(round as i32)
+            .bind(slot)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    tournament.status = TournamentStatus::Running;
+
+    let ctx_serenity = ctx.serenity_context();
+    for mut m in tournament
+        .matches(pool)
+        .await?
+        .into_iter()
+        .filter(|m| m.round == 1)
+    {
+        match (m.player1_id, m.player2_id) {
+            (Some(_), Some(_)) => m.open_thread(ctx_serenity, pool, &tournament).await?,
+            (Some(p), None) | (None, Some(p)) => {
+                advance(ctx_serenity, pool, &mut tournament, &m, user(p), None).await?;
+            }
+            (None, None) => {}
+        }
+    }
+    tournament.refresh_post(ctx_serenity, pool).await?;
+
+    reply(
+        ctx,
+        format!("**{}** has begun, good luck everyone!", tournament.name),
+    )
+    .await
+}
+
+/// Report the score of your match. Your opponent confirms it before the bracket moves on.
+#[poise::command(slash_command, guild_only)]
+async fn report(
+    ctx: Context<'_>,
+    #[description = "Match number, shown in the bracket"] id: i64,
+    #[description = "Your score"]
+    #[min = 0]
+    yours: u32,
+    #[description = "Your opponent's score"]
+    #[min = 0]
+    theirs: u32,
+) -> Result<(), SlimeError> {
+    let pool = &ctx.data().pool;
+    let author = ctx.author().id;
+    let Some(m) = Match::fetch(pool, id)
+        .await?
+        .filter(|m| m.winner_id.is_none())
+    else {
+        return reply(
+            ctx,
+            format!("Match #{id} doesn't exist or is already settled."),
+        )
+        .await;
+    };
+    let Some((a, b)) = m.players().filter(|(a, b)| *a == author || *b == author) else {
+        return reply(ctx, "You're not playing in that match.").await;
+    };
+    if yours == theirs {
+        return reply(ctx, "Matches need a winner, scores can't be tied.").await;
+    }
+    let running = Tournament::fetch(pool, m.tournament_id)
+        .await?
+        .is_some_and(|t| t.status == TournamentStatus::Running);
+    if !running {
+        return reply(ctx, NOT_RUNNING).await;
+    }
+
+    let opponent = if author == a { b } else { a };
+    let scores = if author == a {
+        (yours as i32, theirs as i32)
+    } else {
+        (theirs as i32, yours as i32)
+    };
+    sqlx::query(
+        "UPDATE tournament_matches SET score1 = $2, score2 = $3, reported_by = $4 WHERE id = $1",
+    )
+    .bind(m.id)
+    .bind(scores.0)
+    .bind(scores.1)
+    .bind(author.get() as i64)
+    .execute(pool)
+    .await?;
+
+    let prompt = CreateMessage::new()
+        .content(format!(
+            "{} reported {yours}–{theirs} against {}. {}, is that right?",
+            author.mention(),
+            opponent.mention(),
+            opponent.mention()
+        ))
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(
+                CustomId::new(Kind::Tournament, "confirm", m.id).nonce(score_nonce(scores)),
+            )
+            .label("Confirm")
+            .style(ButtonStyle::Success),
+            CreateButton::new(
+                CustomId::new(Kind::Tournament, "dispute", m.id).nonce(score_nonce(scores)),
+            )
+            .label("Dispute")
+            .style(ButtonStyle::Danger),
+        ])]);
+    m.thread()
+        .unwrap_or(ctx.channel_id())
+        .send_message(ctx, prompt)
+        .await?;
+
+    reply(ctx, "Score reported, waiting for your opponent to confirm.").await
+}
+
+/// Decide a match yourself, for disputes or no-shows.
+#[poise::command(slash_command, guild_only)]
+async fn settle(
+    ctx: Context<'_>,
+    #[description = "Match number, shown in the bracket"] id: i64,
+    #[description = "Player who goes through"] winner: User,
+) -> Result<(), SlimeError> {
+    let pool = &ctx.data().pool;
+    let Some(m) = Match::fetch(pool, id)
+        .await?
+        .filter(|m| m.winner_id.is_none())
+    else {
+        return reply(
+            ctx,
+            format!("Match #{id} doesn't exist or is already settled."),
+        )
+        .await;
+    };
+    let Some(mut tournament) = fetch_managed(ctx, m.tournament_id).await? else {
+        return reply(ctx, "Only the host can settle matches.").await;
+    };
+    if tournament.status != TournamentStatus::Running {
+        return reply(ctx, NOT_RUNNING).await;
+    }
+    if !m
+        .players()
+        .is_some_and(|(a, b)| a == winner.id || b == winner.id)
+    {
+        return reply(
+            ctx,
+            format!("{} isn't playing in that match.", winner.mention()),
+        )
+        .await;
+    }
+
+    // Scores aren't known, so show it like a walkover.
+    sqlx::query(
+        "UPDATE tournament_matches SET score1 = NULL, score2 = NULL
+         WHERE id = $1 AND winner_id IS NULL",
+    )
+    .bind(m.id)
+    .execute(pool)
+    .await?;
+    match advance(
+        ctx.serenity_context(),
+        pool,
+        &mut tournament,
+        &m,
+        winner.id,
+        None,
+    )
+    .await?
+    {
+        Advance::Done => {}
+        Advance::Settled => return reply(ctx, format!("Match #{id} is already settled.")).await,
+        Advance::NotRunning => return reply(ctx, NOT_RUNNING).await,
+    }
+    tournament
+        .refresh_post(ctx.serenity_context(), pool)
+        .await?;
+
+    reply(ctx, format!("{} goes through.", winner.mention())).await
+}
+
+/// Call off a tournament.
+#[poise::command(slash_command, guild_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Tournament number, shown in its footer"] id: i64,
+) -> Result<(), SlimeError> {
+    let Some(mut tournament) = fetch_managed(ctx, id).await?.filter(|t| {
+        matches!(
+            t.status,
+            TournamentStatus::Signup | TournamentStatus::Running
+        )
+    }) else {
+        return reply(
+            ctx,
+            format!("Tournament #{id} doesn't exist, isn't yours, or is already over."),
+        )
+        .await;
+    };
+
+    if rehearse(ctx, &format!("cancelled **{}**.", tournament.name)).await? {
+        return Ok(());
+    }
+
+    let pool = &ctx.data().pool;
+    tournament
+        .set_status(pool, TournamentStatus::Cancelled)
+        .await?;
+    tournament
+        .refresh_post(ctx.serenity_context(), pool)
+        .await?;
+    audit::record(
+        pool,
+        AuditEntry {
+            guild_id: ctx.guild_id(),
+            actor: ctx.author().id,
+            action: "tournament_cancel",
+            target: Some(tournament.id as u64),
+            details: tournament.name.clone(),
+            undo: vec![UndoStep::ReopenTournament(tournament.id)],
+        },
+    )
+    .await?;
+
+    reply(ctx, format!("**{}** was cancelled.", tournament.name)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_reported_score_has_its_own_prompt() {
+        let report = |score1, score2| Match {
+            id: 1,
+            tournament_id: 1,
+            round: 1,
+            slot: 0,
+            player1_id: Some(10),
+            player2_id: Some(20),
+            score1,
+            score2,
+            reported_by: Some(10),
+            winner_id: None,
+            thread_id: None,
+        };
+        assert_eq!(report(Some(3), Some(1)).reported(), Some((3, 1)));
+        assert_eq!(report(None, None).reported(), None);
+
+        assert_ne!(score_nonce((3, 1)), score_nonce((1, 3)));
+        assert_ne!(score_nonce((0, 1)), score_nonce((1, 0)));
+        assert_eq!(score_nonce((3, 1)), score_nonce((3, 1)));
+    }
+}
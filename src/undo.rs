@@ -0,0 +1,377 @@
+use std::fmt;
+
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit::{self, AuditEntry},
+    events::{Event, EventStatus},
+    raffle, settings, tournament,
+    util::{confirm, rehearse},
+    Context, SlimeError,
+};
+
+/// How long after an action `/undo` will still reverse it.
+const UNDO_WINDOW_MINUTES: i32 = 30;
+
+/// One piece of reversing an audited action. An action's steps are stored with its audit record
+/// and run in reverse order by `/undo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoStep {
+    /// Take back a role the action handed out.
+    RemoveRole(UserId, RoleId),
+    /// Give back a role the action took away.
+    AddRole(UserId, RoleId),
+    /// Put a guild setting back to an earlier value, `None` meaning the column's default.
+    Setting(String, Option<String>),
+    /// Delete a message the action posted.
+    DeleteMessage(ChannelId, MessageId),
+    /// Post an event the action cancelled again.
+    RepublishEvent(i64),
+    /// Take down an event the action published.
+    WithdrawEvent(i64),
+    /// Open a raffle the action cancelled again.
+    ReopenRaffle(i64),
+    /// Resume a tournament the action cancelled.
+    ReopenTournament(i64),
+    /// Put back a channel overwrite as it was, or remove it if there wasn't one.
+    Overwrite(
+        ChannelId,
+        PermissionOverwriteType,
+        Option<(Permissions, Permissions)>,
+    ),
+}
+
+impl UndoStep {
+    /// Reverses this step in `guild_id`.
+    pub async fn apply(
+        &self,
+        ctx: &SerenityContext,
+        pool: &PgPool,
+        guild_id: GuildId,
+    ) -> Result<(), SlimeError> {
+        match self {
+            UndoStep::RemoveRole(user, role) => {
+                ctx.http
+                    .remove_member_role(guild_id, *user, *role, Some("Undone"))
+                    .await?
+            }
+            UndoStep::AddRole(user, role) => {
+                ctx.http
+                    .add_member_role(guild_id, *user, *role, Some("Undone"))
+                    .await?
+            }
+            UndoStep::Setting(column, value) => {
+                settings::restore(pool, guild_id, column, value.as_deref()).await?
+            }
+            UndoStep::DeleteMessage(channel, message) => {
+                channel.delete_message(ctx, *message).await?
+            }
+            UndoStep::RepublishEvent(id) => {
+                let mut event = Event::fetch(pool, *id)
+                    .await?
+                    .filter(|e| e.status == EventStatus::Cancelled)
+                    .ok_or(SlimeError::CannotUndo("the event is no longer cancelled"))?;
+                if event.starts_at <= chrono::Utc::now() {
+                    return Err(SlimeError::CannotUndo(
+                        "the event would have started already",
+                    ));
+                }
+                event.publish(ctx, pool).await?
+            }
+            UndoStep::WithdrawEvent(id) => {
+                if let Some(mut event) = Event::fetch(pool, *id).await? {
+                    event.withdraw(ctx, pool).await?;
+                }
+            }
+            UndoStep::ReopenRaffle(id) => raffle::reopen(ctx, pool, *id).await?,
+            UndoStep::ReopenTournament(id) => tournament::reopen(ctx, pool, *id).await?,
+            UndoStep::Overwrite(channel, kind, Some((allow, deny))) => {
+                channel
+                    .create_permission(
+                        ctx,
+                        PermissionOverwrite {
+                            allow: *allow,
+                            deny: *deny,
+                            kind: *kind,
+                        },
+                    )
+                    .await?
+            }
+            UndoStep::Overwrite(channel, kind, None) => {
+                channel.delete_permission(ctx, *kind).await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Steps are stored one per line as a keyword followed by space-separated fields.
+impl fmt::Display for UndoStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UndoStep::RemoveRole(user, role) => write!(f, "remove_role {user} {role}"),
+            UndoStep::AddRole(user, role) => write!(f, "add_role {user} {role}"),
+            UndoStep::Setting(column, Some(value)) => write!(f, "setting {column} {value}"),
+            UndoStep::Setting(column, None) => write!(f, "setting {column}"),
+            UndoStep::DeleteMessage(channel, message) => {
+                write!(f, "delete_message {channel} {message}")
+            }
+            UndoStep::RepublishEvent(id) => write!(f, "republish_event {id}"),
+            UndoStep::WithdrawEvent(id) => write!(f, "withdraw_event {id}"),
+            UndoStep::ReopenRaffle(id) => write!(f, "reopen_raffle {id}"),
+            UndoStep::ReopenTournament(id) => write!(f, "reopen_tournament {id}"),
+            UndoStep::Overwrite(channel, kind, permissions) => {
+                match kind {
+                    PermissionOverwriteType::Member(user) => {
+                        write!(f, "overwrite {channel} member {user}")?
+                    }
+                    PermissionOverwriteType::Role(role) => {
+                        write!(f, "overwrite {channel} role {role}")?
+                    }
+                    _ => unreachable!("Discord has no other overwrite targets"),
+                }
+                match permissions {
+                    Some((allow, deny)) => write!(f, " {} {}", allow.bits(), deny.bits()),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl UndoStep {
+    /// Reads back a step written with its [`Display`](fmt::Display) impl.
+    fn parse(line: &str) -> Result<Self, SlimeError> {
+        const MALFORMED: SlimeError = SlimeError::CannotUndo("its record is damaged");
+        let fields = line.split(' ').collect::<Vec<_>>();
+        let id = |i: usize| {
+            fields
+                .get(i)
+                .and_then(|f| f.parse::<u64>().ok())
+                .filter(|id| *id != 0)
+                .ok_or(MALFORMED)
+        };
+        let number = |i: usize| {
+            fields
+                .get(i)
+                .and_then(|f| f.parse::<i64>().ok())
+                .ok_or(MALFORMED)
+        };
+        let bits = |i: usize| {
+            fields
+                .get(i)
+                .and_then(|f| f.parse::<u64>().ok())
+                .ok_or(MALFORMED)
+        };
+
+        Ok(match fields[0] {
+            "remove_role" => UndoStep::RemoveRole(UserId::new(id(1)?), RoleId::new(id(2)?)),
+            "add_role" => UndoStep::AddRole(UserId::new(id(1)?), RoleId::new(id(2)?)),
+            "setting" => UndoStep::Setting(
+                fields.get(1).ok_or(MALFORMED)?.to_string(),
+                (fields.len() > 2).then(|| fields[2..].join(" ")),
+            ),
+            "delete_message" => {
+                UndoStep::DeleteMessage(ChannelId::new(id(1)?), MessageId::new(id(2)?))
+            }
+            "republish_event" => UndoStep::RepublishEvent(number(1)?),
+            "withdraw_event" => UndoStep::WithdrawEvent(number(1)?),
+            "reopen_raffle" => UndoStep::ReopenRaffle(number(1)?),
+            "reopen_tournament" => UndoStep::ReopenTournament(number(1)?),
+            "overwrite" => {
+                let kind = match fields.get(2) {
+                    Some(&"member") => PermissionOverwriteType::Member(UserId::new(id(3)?)),
+                    Some(&"role") => PermissionOverwriteType::Role(RoleId::new(id(3)?)),
+                    _ => return Err(MALFORMED),
+                };
+                let permissions = match fields.len() {
+                    4 => None,
+                    _ => Some((
+                        Permissions::from_bits_truncate(bits(4)?),
+                        Permissions::from_bits_truncate(bits(5)?),
+                    )),
+                };
+                UndoStep::Overwrite(ChannelId::new(id(1)?), kind, permissions)
+            }
+            _ => return Err(MALFORMED),
+        })
+    }
+}
+
+/// Encodes `steps` for the audit log's `undo` column. Actions that can't be undone store `NULL`.
+pub fn encode(steps: &[UndoStep]) -> Option<String> {
+    (!steps.is_empty()).then(|| {
+        steps
+            .iter()
+            .map(UndoStep::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// Reverse the last thing you had the bot do, if it was in the last half hour.
+#[poise::command(slash_command, guild_only)]
+pub async fn undo(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+
+    let found = sqlx::query_as::<_, (i64, String, String, String)>(
+        "SELECT id, action, details, undo FROM audit_log
+         WHERE guild_id = $1 AND actor_id = $2 AND undo IS NOT NULL AND undone_at IS NULL
+            AND created_at > now() - make_interval(mins => $3)
+         ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(UNDO_WINDOW_MINUTES)
+    .fetch_optional(pool)
+    .await?;
+    let Some((audit_id, action, details, undo)) = found else {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "Nothing you've done in the last {UNDO_WINDOW_MINUTES} minutes can be undone."
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let steps = undo
+        .lines()
+        .map(UndoStep::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let summary = if details.is_empty() {
+        format!("`{action}`")
+    } else {
+        format!("`{action}` ({details})")
+    };
+    if rehearse(ctx, &format!("undone your last action, {summary}.")).await? {
+        return Ok(());
+    }
+    if !confirm(ctx, &format!("Undo your last action, {summary}?")).await? {
+        ctx.send(
+            CreateReply::default()
+                .content("Nothing was undone.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Claim the record first, so pressing undo twice can't reverse the action twice.
+    let claimed =
+        sqlx::query("UPDATE audit_log SET undone_at = now() WHERE id = $1 AND undone_at IS NULL")
+            .bind(audit_id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+    if claimed == 0 {
+        ctx.send(
+            CreateReply::default()
+                .content("That was already undone.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for step in steps.iter().rev() {
+        if let Err(e) = step.apply(ctx.serenity_context(), pool, guild_id).await {
+            error!(
+                "Could not undo step `{}` of audit record {}: {}",
+                step, audit_id, e
+            );
+            failed += 1;
+        }
+    }
+
+    audit::record_and_post(
+        ctx.serenity_context(),
+        pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: ctx.author().id,
+            action: "undo",
+            target: Some(audit_id as u64),
+            details: format!("{action}, failed steps: {failed}"),
+            undo: Vec::new(),
+        },
+        &format!("Undid {summary}."),
+    )
+    .await?;
+
+    let mut content = format!("Undid {summary}.");
+    if failed > 0 {
+        content.push_str(&format!(
+            " {failed} of its {} part(s) couldn't be reversed.",
+            steps.len()
+        ));
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_steps_read_back_as_written() {
+        let steps = [
+            UndoStep::RemoveRole(UserId::new(1), RoleId::new(2)),
+            UndoStep::AddRole(UserId::new(3), RoleId::new(4)),
+            UndoStep::Setting(
+                "welcome_message".to_string(),
+                Some("Hi there all".to_string()),
+            ),
+            UndoStep::Setting("plain_text".to_string(), None),
+            UndoStep::DeleteMessage(ChannelId::new(5), MessageId::new(6)),
+            UndoStep::RepublishEvent(7),
+            UndoStep::WithdrawEvent(8),
+            UndoStep::ReopenRaffle(9),
+            UndoStep::ReopenTournament(10),
+            UndoStep::Overwrite(
+                ChannelId::new(11),
+                PermissionOverwriteType::Role(RoleId::new(12)),
+                Some((Permissions::VIEW_CHANNEL, Permissions::SEND_MESSAGES)),
+            ),
+            UndoStep::Overwrite(
+                ChannelId::new(13),
+                PermissionOverwriteType::Member(UserId::new(14)),
+                None,
+            ),
+        ];
+        let encoded = encode(&steps).unwrap();
+        let decoded = encoded
+            .lines()
+            .map(UndoStep::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, steps);
+        assert_eq!(encode(&[]), None);
+    }
+
+    #[test]
+    fn damaged_records_are_refused() {
+        for line in [
+            "",
+            "remove_role 1",
+            "add_role 0 2",
+            "republish_event x",
+            "overwrite 1 channel 2",
+            "overwrite 1 role 2 3",
+            "explode 1",
+        ] {
+            assert!(UndoStep::parse(line).is_err(), "{line:?} was read");
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::{client::Context as SerenityContext, Error as SerenityError};
+
+use crate::{
+    custom_id::{CustomId, Kind},
+    discord::{self, Discord},
+    emoji::{self, Slot},
+    make_uuid_buttons,
+    settings::GuildSettings,
+    Context, SlimeError,
+};
+
+/// Replies to a component interaction with a message only the clicker can see.
+pub async fn respond_ephemeral(
+    ctx: &SerenityContext,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    discord::respond(
+        ctx,
+        interaction,
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .ephemeral(true),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The HTTP status Discord answered with, if `e` is an error response from its API.
+pub fn http_status(e: &SerenityError) -> Option<u16> {
+    match e {
+        SerenityError::Http(HttpError::UnsuccessfulRequest(response)) => {
+            Some(response.status_code.as_u16())
+        }
+        _ => None,
+    }
+}
+
+/// Percent-encodes `text` for a URL's query string.
+pub fn query_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// DMs `user`. This fails routinely (closed DMs, no mutual guild), so callers usually just log.
+pub async fn send_dm(
+    ctx: &SerenityContext,
+    user: UserId,
+    message: CreateMessage,
+) -> Result<Message, SerenityError> {
+    user.create_dm_channel(ctx)
+        .await?
+        .send_message(ctx, message)
+        .await
+}
+
+/// Previous and next buttons for paging through a reply, with the guild's emoji. Guilds in plain
+/// text mode get them labelled, since screen readers name arrows by their shape.
+pub async fn page_buttons(
+    ctx: Context<'_>,
+    prev_id: &str,
+    next_id: &str,
+) -> Result<CreateActionRow, SlimeError> {
+    let settings = match ctx.guild_id() {
+        Some(guild_id) => GuildSettings::load(&ctx.data().pool, guild_id).await?,
+        None => GuildSettings::default(),
+    };
+    let button = |id: &str, slot: Slot, label: &str| {
+        let mut button = CreateButton::new(id).style(ButtonStyle::Secondary);
+        if let Some(emoji) = emoji::lookup(&settings, slot) {
+            button = button.emoji(emoji);
+        }
+        if settings.plain_text {
+            button = button.label(label);
+        }
+        button
+    };
+
+    Ok(CreateActionRow::Buttons(vec![
+        button(prev_id, Slot::Previous, "Previous"),
+        button(next_id, Slot::Next, "Next"),
+    ]))
+}
+
+/// Shows `pages` as an embed with previous/next buttons, for the invoker only. Navigation stops
+/// working after five idle minutes.
+pub async fn paginate(ctx: Context<'_>, title: &str, pages: &[String]) -> Result<(), SlimeError> {
+    let page_embed = |index: usize| {
+        CreateEmbed::new()
+            .title(title)
+            .description(&pages[index])
+            .footer(CreateEmbedFooter::new(format!(
+                "Page {} of {}",
+                index + 1,
+                pages.len()
+            )))
+    };
+
+    let id = ctx.id();
+    let prev_id = CustomId::for_invocation(Kind::Page, "prev", id).to_string();
+    let next_id = CustomId::for_invocation(Kind::Page, "next", id).to_string();
+    let buttons = page_buttons(ctx, &prev_id, &next_id).await?;
+
+    let mut reply = CreateReply::default().embed(page_embed(0)).ephemeral(true);
+    if pages.len() > 1 {
+        reply = reply.components(vec![buttons]);
+    }
+    ctx.send(reply).await?;
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut current = 0;
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .custom_ids(vec![prev_id.clone(), next_id.clone()])
+        .timeout(std::time::Duration::from_secs(300))
+        .await
+    {
+        current = if press.data.custom_id == next_id {
+            (current + 1) % pages.len()
+        } else {
+            current.checked_sub(1).unwrap_or(pages.len() - 1)
+        };
+
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(page_embed(current)),
+                ),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Asks the invoker a yes/no question, for commands that are hard to take back. Returns `true`
+/// only if they pressed yes within two minutes; the buttons are disabled once either is pressed.
+pub async fn confirm(ctx: Context<'_>, prompt: &str) -> Result<bool, SlimeError> {
+    let id = ctx.id();
+    let yes_uuid = CustomId::for_invocation(Kind::Confirm, "yes", id).to_string();
+    let no_uuid = CustomId::for_invocation(Kind::Confirm, "no", id).to_string();
+    ctx.send(
+        CreateReply::default()
+            .content(prompt)
+            .components(vec![make_uuid_buttons(&yes_uuid, &no_uuid, false)])
+            .ephemeral(true),
+    )
+    .await?;
+
+    await_confirmation(ctx.serenity_context(), id, ctx.author().id, prompt).await
+}
+
+/// Waits for `user` to answer the prompt [`confirm`] sent for invocation `id`.
+async fn await_confirmation(
+    discord: &impl Discord,
+    id: u64,
+    user: UserId,
+    prompt: &str,
+) -> Result<bool, SlimeError> {
+    let yes_uuid = CustomId::for_invocation(Kind::Confirm, "yes", id).to_string();
+    let no_uuid = CustomId::for_invocation(Kind::Confirm, "no", id).to_string();
+    let pressed = discord
+        .await_button(
+            user,
+            &[yes_uuid.clone(), no_uuid],
+            std::time::Duration::from_secs(120),
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(prompt)
+                    .components(vec![make_uuid_buttons("yes_disabled", "no_disabled", true)]),
+            ),
+        )
+        .await?;
+
+    Ok(pressed == Some(yes_uuid))
+}
+
+/// In guilds with rehearsal mode on, tells the invoker what a destructive command would have
+/// done and returns `true`, in which case the caller must stop before changing anything. `plan`
+/// should read as the end of "this would have…".
+pub async fn rehearse(ctx: Context<'_>, plan: &str) -> Result<bool, SlimeError> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(false);
+    };
+    if !GuildSettings::load(&ctx.data().pool, guild_id)
+        .await?
+        .rehearsal
+    {
+        return Ok(false);
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Rehearsal mode is on, so nothing was changed. For real, this would have {plan}"
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord::mock::{Call, MockDiscord};
+
+    const USER: UserId = UserId::new(7);
+
+    #[tokio::test]
+    async fn confirmation_needs_the_yes_button() {
+        let discord = MockDiscord::new();
+        discord.press(Some("confirm:yes:0:42"));
+        discord.press(Some("confirm:no:0:42"));
+        discord.press(None);
+
+        assert!(await_confirmation(&discord, 42, USER, "Sure?")
+            .await
+            .unwrap());
+        assert!(!await_confirmation(&discord, 42, USER, "Sure?")
+            .await
+            .unwrap());
+        assert!(!await_confirmation(&discord, 42, USER, "Sure?")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn confirmation_ignores_other_prompts() {
+        let discord = MockDiscord::new();
+        discord.press(Some("confirm:yes:0:41"));
+
+        assert!(!await_confirmation(&discord, 42, USER, "Sure?")
+            .await
+            .unwrap());
+        assert_eq!(
+            discord.calls(),
+            vec![Call::AwaitButton(
+                USER,
+                vec![
+                    "confirm:yes:0:42".to_string(),
+                    "confirm:no:0:42".to_string()
+                ]
+            )]
+        );
+    }
+}
@@ -0,0 +1,608 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::{error, info};
+
+use crate::{
+    audit::{self, AuditEntry},
+    custom_id::{CustomId, Kind},
+    discord,
+    settings::GuildSettings,
+    util::{http_status, respond_ephemeral, send_dm},
+    Context, Data, SlimeError,
+};
+
+/// What the challenge asks members to pick from, and what it calls each.
+const CHALLENGE: [(&str, &str); 8] = [
+    ("🐸", "frog"),
+    ("🦆", "duck"),
+    ("🐢", "turtle"),
+    ("🐟", "fish"),
+    ("🦎", "lizard"),
+    ("🐌", "snail"),
+    ("🐍", "snake"),
+    ("🦀", "crab"),
+];
+
+/// How many buttons the challenge shows.
+const SHOWN: usize = 4;
+
+/// As many as fit in a modal.
+const MAX_QUESTIONS: i64 = 5;
+
+/// The buttons a challenge shows, as indexes into [`CHALLENGE`], and which of them is the one to
+/// press. Worked out again from `seed` when one is pressed, so nothing needs storing.
+fn challenge(seed: u64) -> ([usize; SHOWN], usize) {
+    let mixed = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let start = (mixed >> 32) as usize;
+    // Stepping by 3 visits every animal before coming back round, so none shows twice.
+    let shown = std::array::from_fn(|i| (start + i * 3) % CHALLENGE.len());
+    (shown, (mixed >> 48) as usize % SHOWN)
+}
+
+/// Whether `given` is `expected`, give or take case and surrounding spaces.
+fn correct(given: &str, expected: &str) -> bool {
+    given.trim().to_lowercase() == expected.trim().to_lowercase()
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct Question {
+    id: i64,
+    prompt: String,
+    answer: Option<String>,
+}
+
+async fn questions(data: &Data, guild_id: GuildId) -> Result<Vec<Question>, SlimeError> {
+    Ok(sqlx::query_as::<_, Question>(
+        "SELECT id, prompt, answer FROM verification_questions WHERE guild_id = $1 ORDER BY id",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(&data.pool)
+    .await?)
+}
+
+/// Holds `member` at the gate if the guild has one, until they pass or time runs out.
+pub async fn joined(
+    _ctx: &SerenityContext,
+    data: &Data,
+    member: &Member,
+) -> Result<(), SlimeError> {
+    let settings = GuildSettings::load(&data.pool, member.guild_id).await?;
+    if !settings.verification_gate || member.user.bot {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO verification_pending (guild_id, user_id, joined_at) VALUES ($1, $2, $3)
+         ON CONFLICT (guild_id, user_id) DO UPDATE SET
+            joined_at = EXCLUDED.joined_at, passed_at = NULL",
+    )
+    .bind(member.guild_id.get() as i64)
+    .bind(member.user.id.get() as i64)
+    .bind(data.clock.now())
+    .execute(&data.pool)
+    .await?;
+    Ok(())
+}
+
+/// Gives `user` the verified role and lets them through the gate. Someone who already left is
+/// let through all the same.
+async fn admit(
+    ctx: &SerenityContext,
+    data: &Data,
+    guild_id: GuildId,
+    user: UserId,
+    role: RoleId,
+) -> Result<(), SlimeError> {
+    match ctx
+        .http
+        .add_member_role(guild_id, user, role, Some("Passed verification"))
+        .await
+    {
+        Ok(()) => {}
+        Err(e) if http_status(&e) == Some(404) => {}
+        Err(e) => return Err(e.into()),
+    }
+    sqlx::query("DELETE FROM verification_pending WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id.get() as i64)
+        .bind(user.get() as i64)
+        .execute(&data.pool)
+        .await?;
+    Ok(())
+}
+
+/// Marks `member` as having passed, letting them in now if they've waited long enough, and
+/// shows moderators what they answered. Returns what to tell them.
+async fn passed(
+    ctx: &SerenityContext,
+    data: &Data,
+    guild_id: GuildId,
+    member: &Member,
+    answers: &[(String, String)],
+) -> Result<String, SlimeError> {
+    let settings = GuildSettings::load(&data.pool, guild_id).await?;
+    let Some(role) = settings.verification_role_id.map(|r| RoleId::new(r as u64)) else {
+        return Ok("Verification isn't set up in this server right now.".to_string());
+    };
+    let now = data.clock.now();
+    // Members who were here before the gate went up have no row, and count from when they joined.
+    let joined_at = member.joined_at.map_or(now, |at| *at);
+    let joined_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+        "INSERT INTO verification_pending (guild_id, user_id, joined_at, passed_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id, user_id) DO UPDATE SET
+            passed_at = COALESCE(verification_pending.passed_at, EXCLUDED.passed_at)
+         RETURNING joined_at",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(member.user.id.get() as i64)
+    .bind(joined_at)
+    .bind(now)
+    .fetch_one(&data.pool)
+    .await?;
+
+    let mut summary = format!("{} passed verification.", member.user.id.mention());
+    for (prompt, answer) in answers {
+        summary.push_str(&format!("\n**{prompt}**\n{answer}"));
+    }
+    audit::record_and_post(
+        ctx,
+        &data.pool,
+        AuditEntry {
+            guild_id: Some(guild_id),
+            actor: member.user.id,
+            action: "verification_passed",
+            target: Some(member.user.id.get()),
+            details: format!("{} answer(s)", answers.len()),
+            undo: Vec::new(),
+        },
+        &summary,
+    )
+    .await?;
+
+    let due = joined_at + Duration::minutes(settings.verification_wait_minutes.into());
+    if due <= now {
+        admit(ctx, data, guild_id, member.user.id, role).await?;
+        Ok("You're verified. Welcome in!".to_string())
+    } else {
+        Ok(format!(
+            "You're verified! You'll be let in <t:{}:R>.",
+            due.timestamp()
+        ))
+    }
+}
+
+/// Handles the buttons of the gate: **Verify** on the post, then the animals of the challenge.
+/// Picking the right one opens the guild's questions, or lets the member through if it has none.
+pub async fn handle_component(
+    ctx: &SerenityContext,
+    data: &Data,
+    interaction: &ComponentInteraction,
+    custom_id: &CustomId,
+) -> Result<(), SlimeError> {
+    let (Some(guild_id), Some(member)) = (interaction.guild_id, interaction.member.as_ref()) else {
+        return Ok(());
+    };
+    let settings = GuildSettings::load(&data.pool, guild_id).await?;
+    let Some(role) = settings
+        .verification_role_id
+        .filter(|_| settings.verification_gate)
+    else {
+        return respond_ephemeral(ctx, interaction, "Verification isn't on in this server.").await;
+    };
+    if member.roles.contains(&RoleId::new(role as u64)) {
+        return respond_ephemeral(ctx, interaction, "You're already verified.").await;
+    }
+
+    let update = |content: &str| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(vec![]),
+        )
+    };
+    match (custom_id.action.as_str(), custom_id.nonce) {
+        ("start", _) => {
+            let seed = interaction.id.get();
+            let (shown, target) = challenge(seed);
+            let buttons = shown
+                .iter()
+                .enumerate()
+                .map(|(position, &animal)| {
+                    let id = CustomId::new(Kind::Verify, "pick", position as i64).nonce(seed);
+                    CreateButton::new(id)
+                        .emoji(ReactionType::Unicode(CHALLENGE[animal].0.to_string()))
+                        .style(ButtonStyle::Secondary)
+                })
+                .collect();
+            let content = format!(
+                "To show you're not a bot, press the **{}**.",
+                CHALLENGE[shown[target]].1
+            );
+            discord::respond(
+                ctx,
+                interaction,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![CreateActionRow::Buttons(buttons)])
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        }
+        ("pick", Some(seed)) => {
+            let (shown, target) = challenge(seed);
+            if custom_id.id != target as i64 {
+                let content = format!(
+                    "That's not the {}. Press **Verify** to try again.",
+                    CHALLENGE[shown[target]].1
+                );
+                discord::respond(ctx, interaction, update(&content)).await?;
+                return Ok(());
+            }
+            let questions = questions(data, guild_id).await?;
+            if questions.is_empty() {
+                let content = passed(ctx, data, guild_id, member, &[]).await?;
+                discord::respond(ctx, interaction, update(&content)).await?;
+                return Ok(());
+            }
+            let rows = questions
+                .iter()
+                .map(|q| {
+                    let style = match q.answer {
+                        Some(_) => InputTextStyle::Short,
+                        None => InputTextStyle::Paragraph,
+                    };
+                    CreateActionRow::InputText(
+                        CreateInputText::new(style, &q.prompt, q.id.to_string()).max_length(500),
+                    )
+                })
+                .collect();
+            let modal_id = CustomId::new(Kind::Verify, "answers", 0).nonce(seed);
+            discord::respond(
+                ctx,
+                interaction,
+                CreateInteractionResponse::Modal(
+                    CreateModal::new(modal_id, "A few questions first").components(rows),
+                ),
+            )
+            .await?;
+        }
+        _ => {
+            discord::respond(
+                ctx,
+                interaction,
+                update("This button is out of date. Press **Verify** again."),
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the answers to a guild's questions. Any that's wrong sends the member back to the
+/// start, without saying which, so the challenge can't be worked through one answer at a time.
+pub async fn submitted(
+    ctx: &SerenityContext,
+    data: &Data,
+    modal: &ModalInteraction,
+) -> Result<(), SlimeError> {
+    let (Some(guild_id), Some(member)) = (modal.guild_id, modal.member.as_ref()) else {
+        return Ok(());
+    };
+    let given = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .filter_map(|component| match component {
+            ActionRowComponent::InputText(input) => Some((
+                input.custom_id.clone(),
+                input.value.clone().unwrap_or_default(),
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut answers = Vec::new();
+    let mut all_correct = true;
+    for question in questions(data, guild_id).await? {
+        let answer = given
+            .iter()
+            .find(|(id, _)| *id == question.id.to_string())
+            .map_or("", |(_, value)| value.as_str());
+        if question
+            .answer
+            .as_ref()
+            .is_some_and(|a| !correct(answer, a))
+        {
+            all_correct = false;
+        }
+        answers.push((question.prompt, answer.to_string()));
+    }
+    let content = if all_correct {
+        passed(ctx, data, guild_id, member, &answers).await?
+    } else {
+        "Not quite, at least one of those isn't right. Press **Verify** to try again.".to_string()
+    };
+    modal
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Lets in members who passed once they've waited long enough, and kicks those who ran out of
+/// time to pass.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    let due = sqlx::query_as::<_, (i64, i64, bool, Option<i64>, i32)>(
+        "SELECT p.guild_id, p.user_id, p.passed_at IS NOT NULL, s.verification_role_id,
+            s.verification_timeout_minutes
+         FROM verification_pending p JOIN guild_settings s USING (guild_id)
+         WHERE s.verification_gate
+            AND p.guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+            AND CASE WHEN p.passed_at IS NOT NULL
+                THEN p.joined_at + make_interval(mins => s.verification_wait_minutes) <= $1
+                ELSE s.verification_timeout_minutes > 0
+                    AND p.joined_at + make_interval(mins => s.verification_timeout_minutes) <= $1
+            END",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    for (guild_id, user_id, passed, role, timeout) in due {
+        let guild_id = GuildId::new(guild_id as u64);
+        let user = UserId::new(user_id as u64);
+        if passed {
+            let Some(role) = role else { continue };
+            if let Err(e) = admit(ctx, data, guild_id, user, RoleId::new(role as u64)).await {
+                error!(
+                    "Could not give {} the verified role in {}: {}",
+                    user, guild_id, e
+                );
+            }
+            continue;
+        }
+
+        let server = ctx
+            .cache
+            .guild(guild_id)
+            .map_or_else(|| "the server".to_string(), |g| g.name.clone());
+        let notice = format!(
+            "You were removed from **{server}** for not verifying within {timeout} minute(s). \
+             You're welcome to join again."
+        );
+        if let Err(e) = send_dm(ctx, user, CreateMessage::new().content(notice)).await {
+            info!("Could not tell {} why they were kicked: {}", user, e);
+        }
+        match guild_id
+            .kick_with_reason(ctx, user, "Didn't verify in time")
+            .await
+        {
+            Ok(()) => {}
+            Err(e) if http_status(&e) == Some(404) => {}
+            Err(e) => {
+                error!(
+                    "Could not kick unverified {} from {}: {}",
+                    user, guild_id, e
+                );
+                continue;
+            }
+        }
+        sqlx::query("DELETE FROM verification_pending WHERE guild_id = $1 AND user_id = $2")
+            .bind(guild_id.get() as i64)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        let bot = ctx.cache.current_user().id;
+        audit::record(
+            pool,
+            AuditEntry {
+                guild_id: Some(guild_id),
+                actor: bot,
+                action: "verification_kicked",
+                target: Some(user.get()),
+                details: format!("not verified within {timeout} minute(s)"),
+                undo: Vec::new(),
+            },
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Set up the challenge new members pass before they get the member role.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("post", "ask", "forget", "questions_list")
+)]
+pub async fn verification(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Post the button new members press to verify.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn post(
+    ctx: Context<'_>,
+    #[description = "Channel to post in, defaults to this one"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+    #[description = "What to say above the button"]
+    #[max_length = 1000]
+    message: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let channel = channel.map_or(ctx.channel_id(), |c| c.id);
+    let content = message.unwrap_or_else(|| {
+        "Welcome! Press **Verify** to get access to the rest of the server.".to_string()
+    });
+    channel
+        .send_message(
+            ctx,
+            CreateMessage::new().content(content).button(
+                CreateButton::new(CustomId::new(Kind::Verify, "start", 0))
+                    .label("Verify")
+                    .emoji('✅')
+                    .style(ButtonStyle::Success),
+            ),
+        )
+        .await?;
+
+    let mut content = format!("Posted the **Verify** button in {}.", channel.mention());
+    let settings = GuildSettings::load(&ctx.data().pool, guild_id).await?;
+    if !settings.verification_gate {
+        content.push_str(
+            " It won't do anything until verification is on with `/settings verification`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// Ask new members a question when they verify.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn ask(
+    ctx: Context<'_>,
+    #[description = "The question, like \"Which rule is about spoilers?\""]
+    #[max_length = 45]
+    question: String,
+    #[description = "The answer it needs, if any; without one, anything goes"]
+    #[max_length = 100]
+    answer: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM verification_questions WHERE guild_id = $1",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_one(pool)
+    .await?;
+    if count >= MAX_QUESTIONS {
+        return reply(
+            ctx,
+            format!("Members can only be asked {MAX_QUESTIONS} questions. Forget one first."),
+        )
+        .await;
+    }
+    let answer = answer
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty());
+    sqlx::query(
+        "INSERT INTO verification_questions (guild_id, prompt, answer, created_by)
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(question.trim())
+    .bind(&answer)
+    .bind(ctx.author().id.get() as i64)
+    .execute(pool)
+    .await?;
+
+    let content = match answer {
+        Some(answer) => format!(
+            "New members will be asked \"{}\", and must answer \"{answer}\".",
+            question.trim()
+        ),
+        None => format!(
+            "New members will be asked \"{}\". Their answers show up in the audit channel.",
+            question.trim()
+        ),
+    };
+    reply(ctx, content).await
+}
+
+/// Stop asking a question.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn forget(
+    ctx: Context<'_>,
+    #[description = "Its number in /verification questions"]
+    #[min = 1]
+    number: u32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let forgotten = sqlx::query_scalar::<_, String>(
+        "DELETE FROM verification_questions WHERE id = (
+            SELECT id FROM verification_questions WHERE guild_id = $1
+            ORDER BY id OFFSET $2 LIMIT 1
+         )
+         RETURNING prompt",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(i64::from(number) - 1)
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+
+    let content = match forgotten {
+        Some(prompt) => format!("New members won't be asked \"{prompt}\" any more."),
+        None => format!("There's no question {number}."),
+    };
+    reply(ctx, content).await
+}
+
+/// See what new members are asked when they verify.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "questions",
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn questions_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let questions = questions(ctx.data(), guild_id).await?;
+    if questions.is_empty() {
+        return reply(
+            ctx,
+            "New members only have to pass the button challenge. Add questions with \
+             `/verification ask`.",
+        )
+        .await;
+    }
+    let lines = questions
+        .iter()
+        .enumerate()
+        .map(|(i, q)| match &q.answer {
+            Some(answer) => format!("{}. {} → \"{answer}\"", i + 1, q.prompt),
+            None => format!("{}. {} (any answer)", i + 1, q.prompt),
+        })
+        .collect::<Vec<_>>();
+    reply(ctx, lines.join("\n")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_show_different_animals_and_one_to_press() {
+        for seed in [0, 1, 42, 1_234_567_890_123_456_789, u64::MAX] {
+            let (shown, target) = challenge(seed);
+            assert!(target < SHOWN);
+            let mut sorted = shown;
+            sorted.sort_unstable();
+            assert!(sorted.windows(2).all(|w| w[0] != w[1]));
+            assert_eq!(challenge(seed), (shown, target));
+        }
+        assert!(correct("  Rule Three ", "rule three"));
+        assert!(!correct("rule 3", "rule three"));
+    }
+}
@@ -0,0 +1,462 @@
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    events::{Event, EventStatus},
+    permtemplate::{normalize, StoredOverwrite},
+    util::rehearse,
+    Context, Data, SlimeError,
+};
+
+/// What the schedule's role is granted while a channel is open.
+const OPEN_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::READ_MESSAGE_HISTORY);
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ChannelSchedule {
+    channel_id: i64,
+    role_id: i64,
+    open_time: Option<NaiveTime>,
+    close_time: Option<NaiveTime>,
+    event_id: Option<i64>,
+    lead_minutes: i32,
+    linger_minutes: i32,
+    is_open: bool,
+    event_starts_at: Option<DateTime<Utc>>,
+    event_ends_at: Option<DateTime<Utc>>,
+    event_status: Option<EventStatus>,
+}
+
+impl ChannelSchedule {
+    fn channel(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    /// Whether the channel should be visible at `now`.
+    fn should_be_open(&self, now: DateTime<Utc>) -> bool {
+        if let (Some(open), Some(close)) = (self.open_time, self.close_time) {
+            let time = now.time();
+            return if open <= close {
+                open <= time && time < close
+            } else {
+                // The window runs over midnight.
+                time >= open || time < close
+            };
+        }
+
+        match (self.event_starts_at, self.event_ends_at, self.event_status) {
+            (Some(starts), Some(ends), Some(EventStatus::Published | EventStatus::Completed)) => {
+                starts - Duration::minutes(self.lead_minutes.into()) <= now
+                    && now < ends + Duration::minutes(self.linger_minutes.into())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether an event schedule's window is over for good, so the schedule can be dropped.
+    fn is_finished(&self, now: DateTime<Utc>) -> bool {
+        if self.event_id.is_none() {
+            return false;
+        }
+        match (self.event_ends_at, self.event_status) {
+            (_, Some(EventStatus::Cancelled | EventStatus::Rejected)) => true,
+            (Some(ends), _) => now >= ends + Duration::minutes(self.linger_minutes.into()),
+            _ => false,
+        }
+    }
+}
+
+async fn load_schedules(
+    pool: &PgPool,
+    channel_id: Option<ChannelId>,
+) -> Result<Vec<ChannelSchedule>, SlimeError> {
+    Ok(sqlx::query_as::<_, ChannelSchedule>(
+        "SELECT s.*,
+            e.starts_at AS event_starts_at,
+            e.starts_at + make_interval(mins => e.duration_minutes) AS event_ends_at,
+            e.status AS event_status
+         FROM channel_schedules s LEFT JOIN events e ON e.id = s.event_id
+         WHERE ($1::BIGINT IS NULL OR s.channel_id = $1)
+            AND s.guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(channel_id.map(|id| id.get() as i64))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Saves the channel's overwrites and grants the schedule's role [`OPEN_PERMISSIONS`].
+async fn open(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    schedule: &ChannelSchedule,
+) -> Result<(), SlimeError> {
+    let channel = schedule.channel();
+    let Some(current) = channel.to_channel(ctx).await?.guild() else {
+        return Ok(());
+    };
+    let saved = normalize(&current.permission_overwrites);
+
+    let role = RoleId::new(schedule.role_id as u64);
+    let mut overwrites = current.permission_overwrites.clone();
+    match overwrites
+        .iter_mut()
+        .find(|o| o.kind == PermissionOverwriteType::Role(role))
+    {
+        Some(overwrite) => {
+            overwrite.allow |= OPEN_PERMISSIONS;
+            overwrite.deny -= OPEN_PERMISSIONS;
+        }
+        None => overwrites.push(PermissionOverwrite {
+            allow: OPEN_PERMISSIONS,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Role(role),
+        }),
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM channel_schedule_overwrites WHERE channel_id = $1")
+        .bind(schedule.channel_id)
+        .execute(&mut *tx)
+        .await?;
+    for overwrite in &saved {
+        sqlx::query(
+            "INSERT INTO channel_schedule_overwrites (channel_id, kind, target_id, allow, deny)
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(schedule.channel_id)
+        .bind(&overwrite.kind)
+        .bind(overwrite.target_id)
+        .bind(overwrite.allow)
+        .bind(overwrite.deny)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("UPDATE channel_schedules SET is_open = true WHERE channel_id = $1")
+        .bind(schedule.channel_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Edit inside the transaction so a failed edit doesn't leave the channel marked open.
+    channel
+        .edit(
+            ctx,
+            EditChannel::new()
+                .permissions(overwrites)
+                .audit_log_reason("Scheduled channel opening"),
+        )
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Puts back the overwrites saved when the channel opened.
+async fn close(
+    ctx: &SerenityContext,
+    pool: &PgPool,
+    schedule: &ChannelSchedule,
+) -> Result<(), SlimeError> {
+    let saved = sqlx::query_as::<_, StoredOverwrite>(
+        "SELECT kind, target_id, allow, deny FROM channel_schedule_overwrites WHERE channel_id = $1",
+    )
+    .bind(schedule.channel_id)
+    .fetch_all(pool)
+    .await?;
+
+    schedule
+        .channel()
+        .edit(
+            ctx,
+            EditChannel::new()
+                .permissions(saved.iter().map(StoredOverwrite::to_overwrite))
+                .audit_log_reason("Scheduled channel closing"),
+        )
+        .await?;
+
+    sqlx::query("UPDATE channel_schedules SET is_open = false WHERE channel_id = $1")
+        .bind(schedule.channel_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Opens and closes every scheduled channel that's due. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let pool = &data.pool;
+    for schedule in load_schedules(pool, None).await? {
+        let result = match (schedule.is_open, schedule.should_be_open(now)) {
+            (false, true) => open(ctx, pool, &schedule).await,
+            (true, false) => close(ctx, pool, &schedule).await,
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            error!(
+                "Could not update visibility of {}: {}",
+                schedule.channel(),
+                e
+            );
+            continue;
+        }
+
+        if schedule.is_finished(now) {
+            sqlx::query("DELETE FROM channel_schedules WHERE channel_id = $1")
+                .bind(schedule.channel_id)
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, SlimeError> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| SlimeError::InvalidTime(value.to_string()))
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Make channels visible only at certain times.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    subcommands("hours", "event", "clear")
+)]
+pub async fn visibility(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Open a channel during the same hours every day.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES"
+)]
+async fn hours(
+    ctx: Context<'_>,
+    #[description = "Channel to open and close"] channel: GuildChannel,
+    #[description = "Opening time in UTC, like 18:00"] opens: String,
+    #[description = "Closing time in UTC, like 23:30"] closes: String,
+    #[description = "Role that can see it while open, defaults to everyone"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let (open_time, close_time) = (parse_time(&opens)?, parse_time(&closes)?);
+    let role_id = role.map_or(guild_id.get(), |r| r.id.get());
+
+    sqlx::query(
+        "INSERT INTO channel_schedules (channel_id, guild_id, role_id, open_time, close_time)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (channel_id) DO UPDATE SET
+            role_id = EXCLUDED.role_id, open_time = EXCLUDED.open_time,
+            close_time = EXCLUDED.close_time, event_id = NULL",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(role_id as i64)
+    .bind(open_time)
+    .bind(close_time)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "{} will be open from {} to {} UTC every day.",
+            channel.mention(),
+            open_time.format("%H:%M"),
+            close_time.format("%H:%M")
+        ),
+    )
+    .await
+}
+
+/// Open a channel around an event, like a spoiler channel that opens when it starts.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES"
+)]
+async fn event(
+    ctx: Context<'_>,
+    #[description = "Channel to open and close"] channel: GuildChannel,
+    #[description = "Event number, shown in its footer"] id: i64,
+    #[description = "Minutes before the start to open"]
+    #[min = 0]
+    before: Option<u32>,
+    #[description = "Minutes after the end to close"]
+    #[min = 0]
+    after: Option<u32>,
+    #[description = "Role that can see it while open, defaults to everyone"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let event = Event::fetch(pool, id)
+        .await?
+        .filter(|e| e.guild() == guild_id)
+        .ok_or(SlimeError::EventNotFound(id))?;
+    let role_id = role.map_or(guild_id.get(), |r| r.id.get());
+
+    sqlx::query(
+        "INSERT INTO channel_schedules
+            (channel_id, guild_id, role_id, event_id, lead_minutes, linger_minutes)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (channel_id) DO UPDATE SET
+            role_id = EXCLUDED.role_id, event_id = EXCLUDED.event_id,
+            lead_minutes = EXCLUDED.lead_minutes, linger_minutes = EXCLUDED.linger_minutes,
+            open_time = NULL, close_time = NULL",
+    )
+    .bind(channel.id.get() as i64)
+    .bind(guild_id.get() as i64)
+    .bind(role_id as i64)
+    .bind(event.id)
+    .bind(before.unwrap_or(0) as i32)
+    .bind(after.unwrap_or(0) as i32)
+    .execute(pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "{} will open for **{}** and close again afterwards.",
+            channel.mention(),
+            event.title
+        ),
+    )
+    .await
+}
+
+/// Stop scheduling a channel, putting its permissions back if it's open.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES"
+)]
+async fn clear(
+    ctx: Context<'_>,
+    #[description = "Scheduled channel"] channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let pool = &ctx.data().pool;
+    let Some(schedule) = load_schedules(pool, Some(channel.id)).await?.pop() else {
+        return reply(ctx, format!("{} isn't scheduled.", channel.mention())).await;
+    };
+    let plan = if schedule.is_open {
+        format!(
+            "closed {} again and removed its schedule.",
+            channel.mention()
+        )
+    } else {
+        format!("removed the schedule from {}.", channel.mention())
+    };
+    if rehearse(ctx, &plan).await? {
+        return Ok(());
+    }
+
+    if schedule.is_open {
+        close(ctx.serenity_context(), pool, &schedule).await?;
+    }
+    sqlx::query("DELETE FROM channel_schedules WHERE channel_id = $1")
+        .bind(schedule.channel_id)
+        .execute(pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!("{} is no longer scheduled.", channel.mention()),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::clock::{Clock, SimulatedClock};
+
+    fn schedule() -> ChannelSchedule {
+        ChannelSchedule {
+            channel_id: 1,
+            role_id: 2,
+            open_time: None,
+            close_time: None,
+            event_id: None,
+            lead_minutes: 0,
+            linger_minutes: 0,
+            is_open: false,
+            event_starts_at: None,
+            event_ends_at: None,
+            event_status: None,
+        }
+    }
+
+    #[test]
+    fn overnight_hours_open_across_midnight() {
+        let schedule = ChannelSchedule {
+            open_time: NaiveTime::from_hms_opt(22, 0, 0),
+            close_time: NaiveTime::from_hms_opt(2, 0, 0),
+            ..schedule()
+        };
+        let clock =
+            SimulatedClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap());
+
+        let mut open_minutes = 0;
+        for _ in 0..24 * 60 {
+            if schedule.should_be_open(clock.now()) {
+                open_minutes += 1;
+            }
+            assert!(!schedule.is_finished(clock.now()));
+            clock.advance(Duration::minutes(1));
+        }
+
+        assert_eq!(open_minutes, 4 * 60);
+    }
+
+    #[test]
+    fn event_schedules_open_early_and_expire_after_lingering() {
+        let starts = Utc.with_ymd_and_hms(2024, 3, 1, 19, 0, 0).unwrap();
+        let schedule = ChannelSchedule {
+            event_id: Some(1),
+            lead_minutes: 15,
+            linger_minutes: 30,
+            event_starts_at: Some(starts),
+            event_ends_at: Some(starts + Duration::hours(1)),
+            event_status: Some(EventStatus::Published),
+            ..schedule()
+        };
+        let clock = SimulatedClock::starting_at(starts - Duration::hours(1));
+
+        let mut opened = None;
+        let mut closed = None;
+        let mut finished = None;
+        while finished.is_none() {
+            let now = clock.now();
+            match (schedule.should_be_open(now), opened, closed) {
+                (true, None, _) => opened = Some(now),
+                (false, Some(_), None) => closed = Some(now),
+                _ => {}
+            }
+            if schedule.is_finished(now) {
+                finished = Some(now);
+            }
+            clock.advance(Duration::minutes(1));
+        }
+
+        assert_eq!(opened, Some(starts - Duration::minutes(15)));
+        assert_eq!(closed, Some(starts + Duration::minutes(90)));
+        assert_eq!(finished, closed);
+    }
+}
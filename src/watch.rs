@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+
+use chrono::Timelike;
+use poise::{serenity_prelude::*, CreateReply};
+use serenity::client::Context as SerenityContext;
+use tracing::error;
+
+use crate::{quiet::QuietHours, settings::GuildSettings, util::send_dm, Context, Data, SlimeError};
+
+/// Patterns need this many characters besides `*`, or they'd match nearly everything.
+const MIN_PATTERN_LENGTH: usize = 3;
+
+/// How much of the message an alert quotes.
+const EXCERPT_LENGTH: usize = 300;
+
+/// A watched pattern as it's stored: trimmed, lowercase, without runs of `*`.
+fn normalize(pattern: &str) -> String {
+    let mut normalized = String::new();
+    for c in pattern.trim().to_lowercase().chars() {
+        if !(c == '*' && normalized.ends_with('*')) {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+/// Whether `text` contains `pattern`, ignoring case, with each `*` in it standing for anything.
+fn found(pattern: &str, text: &str) -> bool {
+    let text = text.to_lowercase();
+    let mut rest = text.as_str();
+    let mut parts = pattern.split('*').filter(|p| !p.is_empty()).peekable();
+    if parts.peek().is_none() {
+        return false;
+    }
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Where an alert goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Destination {
+    Dm(UserId),
+    Channel(ChannelId),
+}
+
+/// Whether `user` can see `channel`, going by the cache. Anything the cache doesn't know counts
+/// as no, so a DM never quotes a channel its watcher couldn't read.
+fn can_see(ctx: &SerenityContext, guild_id: GuildId, channel: ChannelId, user: UserId) -> bool {
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return false;
+    };
+    let Some(member) = guild.members.get(&user) else {
+        return false;
+    };
+    // Threads go by their parent's permissions.
+    let channel = guild.channels.get(&channel).or_else(|| {
+        let parent = guild.threads.iter().find(|t| t.id == channel)?.parent_id?;
+        guild.channels.get(&parent)
+    });
+    channel.is_some_and(|c| guild.user_permissions_in(c, member).view_channel())
+}
+
+fn alert(message: &Message, patterns: &[&str]) -> CreateMessage {
+    let mut excerpt = message
+        .content
+        .chars()
+        .take(EXCERPT_LENGTH)
+        .collect::<String>();
+    if message.content.chars().count() > EXCERPT_LENGTH {
+        excerpt.push('…');
+    }
+    let patterns = patterns
+        .iter()
+        .map(|p| format!("`{p}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    CreateMessage::new().embed(
+        CreateEmbed::new()
+            .title("Watched term spotted")
+            .url(message.link())
+            .description(excerpt)
+            .field("Matched", patterns, false)
+            .field(
+                "Where",
+                format!(
+                    "{} ([jump]({}))",
+                    message.channel_id.mention(),
+                    message.link()
+                ),
+                true,
+            )
+            .field("Who", message.author.mention().to_string(), true)
+            .timestamp(message.timestamp),
+    )
+}
+
+/// Alerts every moderator watching for something `message` contains, unless they wrote it or
+/// it's during their mute window. Several of one moderator's watches, or several moderators'
+/// watches alerting the same channel, make one alert. Only runs with the guild's consent to
+/// reading message content.
+pub async fn noticed(
+    ctx: &SerenityContext,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    let pool = &data.pool;
+    let watches = sqlx::query_as::<_, (i64, String, Option<i64>, Option<i16>, Option<i16>)>(
+        "SELECT w.user_id, w.pattern, w.channel_id, m.mute_start, m.mute_end
+         FROM keyword_watches w
+         LEFT JOIN keyword_watch_mutes m USING (guild_id, user_id)
+         WHERE w.guild_id = $1 AND w.guild_id NOT IN (SELECT guild_id FROM detached_guilds)",
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    if watches.is_empty() {
+        return Ok(());
+    }
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        return Ok(());
+    }
+
+    let hour = data.clock.now().hour();
+    let mut alerts = BTreeMap::<Destination, Vec<&str>>::new();
+    for (user, pattern, channel, mute_start, mute_end) in &watches {
+        let user = UserId::new(*user as u64);
+        if user == message.author.id
+            || QuietHours::from_columns(*mute_start, *mute_end).is_some_and(|q| q.contains(hour))
+            || !found(pattern, &message.content)
+        {
+            continue;
+        }
+        let destination = match channel {
+            Some(channel) => Destination::Channel(ChannelId::new(*channel as u64)),
+            None if can_see(ctx, guild_id, message.channel_id, user) => Destination::Dm(user),
+            None => continue,
+        };
+        let patterns = alerts.entry(destination).or_default();
+        if !patterns.contains(&pattern.as_str()) {
+            patterns.push(pattern);
+        }
+    }
+
+    for (destination, patterns) in alerts {
+        let sent = match destination {
+            Destination::Dm(user) => send_dm(ctx, user, alert(message, &patterns)).await,
+            Destination::Channel(channel) => {
+                channel.send_message(ctx, alert(message, &patterns)).await
+            }
+        };
+        if let Err(e) = sent {
+            error!("Could not send a keyword alert to {:?}: {}", destination, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: impl Into<String>) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+async fn autocomplete_watch(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    sqlx::query_scalar::<_, String>(
+        "SELECT pattern FROM keyword_watches
+         WHERE guild_id = $1 AND user_id = $2 AND pattern LIKE $3 || '%'
+         ORDER BY pattern LIMIT 25",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(normalize(partial))
+    .fetch_all(&ctx.data().pool)
+    .await
+    .unwrap_or_default()
+}
+
+/// Get told privately when terms you're keeping an eye on come up.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("add", "remove", "list", "mute")
+)]
+pub async fn watch(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Watch for a term, or change where its alerts go.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Term to watch for; * stands for anything, like free*nitro"]
+    #[max_length = 100]
+    pattern: String,
+    #[description = "Mod channel to alert in, instead of your DMs"]
+    #[channel_types("Text")]
+    alert_in: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pattern = normalize(&pattern);
+    if pattern.chars().filter(|&c| c != '*').count() < MIN_PATTERN_LENGTH {
+        return reply(
+            ctx,
+            format!("Patterns need at least {MIN_PATTERN_LENGTH} characters besides `*`."),
+        )
+        .await;
+    }
+
+    let pool = &ctx.data().pool;
+    sqlx::query(
+        "INSERT INTO keyword_watches (guild_id, user_id, pattern, channel_id)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (guild_id, user_id, pattern) DO UPDATE SET channel_id = EXCLUDED.channel_id",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&pattern)
+    .bind(alert_in.as_ref().map(|c| c.id.get() as i64))
+    .execute(pool)
+    .await?;
+
+    let mut content = match alert_in {
+        Some(channel) => format!(
+            "Watching for `{pattern}`, with alerts in {}.",
+            channel.mention()
+        ),
+        None => format!("Watching for `{pattern}`, with alerts in your DMs."),
+    };
+    if !GuildSettings::load(pool, guild_id)
+        .await?
+        .message_content_consent
+    {
+        content.push_str(
+            "\nMessages are only read once message content features are allowed with \
+             `/settings message_content`.",
+        );
+    }
+    reply(ctx, content).await
+}
+
+/// Stop watching for a term.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Term to stop watching for"]
+    #[autocomplete = "autocomplete_watch"]
+    pattern: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pattern = normalize(&pattern);
+    let removed = sqlx::query(
+        "DELETE FROM keyword_watches WHERE guild_id = $1 AND user_id = $2 AND pattern = $3",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&pattern)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Stopped watching for `{pattern}`.")
+    } else {
+        format!("You weren't watching for `{pattern}`.")
+    };
+    reply(ctx, content).await
+}
+
+/// See what you're watching for here.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let pool = &ctx.data().pool;
+    let watches = sqlx::query_as::<_, (String, Option<i64>)>(
+        "SELECT pattern, channel_id FROM keyword_watches
+         WHERE guild_id = $1 AND user_id = $2 ORDER BY pattern",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .fetch_all(pool)
+    .await?;
+    let mute = sqlx::query_as::<_, (i16, i16)>(
+        "SELECT mute_start, mute_end FROM keyword_watch_mutes WHERE guild_id = $1 AND user_id = $2",
+    )
+    .bind(guild_id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .fetch_optional(pool)
+    .await?
+    .and_then(|(start, end)| QuietHours::from_columns(Some(start), Some(end)));
+
+    if watches.is_empty() {
+        return reply(ctx, "You aren't watching for anything here.").await;
+    }
+    let mut lines = watches
+        .iter()
+        .map(|(pattern, channel)| match channel {
+            Some(channel) => format!(
+                "• `{pattern}`, alerts in {}",
+                ChannelId::new(*channel as u64).mention()
+            ),
+            None => format!("• `{pattern}`, alerts in your DMs"),
+        })
+        .collect::<Vec<_>>();
+    if let Some(mute) = mute {
+        lines.push(format!("Muted from {mute}."));
+    }
+    reply(ctx, lines.join("\n")).await
+}
+
+/// Keep your watches quiet at the same hours every day.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn mute(
+    ctx: Context<'_>,
+    #[description = "Hour to go quiet at, in UTC; leave both empty to unmute"]
+    #[max = 23]
+    start: Option<u8>,
+    #[description = "Hour to start alerting again, in UTC"]
+    #[max = 23]
+    end: Option<u8>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().ok_or(SlimeError::NotInGuild)?;
+    let mute = QuietHours::from_columns(start.map(i16::from), end.map(i16::from));
+    if mute.is_none() && (start.is_some() || end.is_some()) {
+        return reply(ctx, "A mute needs both a start and a different end.").await;
+    }
+
+    let pool = &ctx.data().pool;
+    match mute {
+        Some(mute) => {
+            sqlx::query(
+                "INSERT INTO keyword_watch_mutes (guild_id, user_id, mute_start, mute_end)
+                 VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (guild_id, user_id) DO UPDATE SET
+                    mute_start = EXCLUDED.mute_start, mute_end = EXCLUDED.mute_end",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(ctx.author().id.get() as i64)
+            .bind(mute.start as i16)
+            .bind(mute.end as i16)
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM keyword_watch_mutes WHERE guild_id = $1 AND user_id = $2")
+                .bind(guild_id.get() as i64)
+                .bind(ctx.author().id.get() as i64)
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    let content = match mute {
+        Some(mute) => format!("Your watches here stay quiet from {mute}. Nothing is saved up."),
+        None => "Your watches here alert at any hour.".to_string(),
+    };
+    reply(ctx, content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_with_wildcards() {
+        assert_eq!(normalize("  Free**NITRO "), "free*nitro");
+        assert!(found("free*nitro", "Get FREE discord Nitro here"));
+        assert!(!found("free*nitro", "nitro isn't free"));
+        assert!(found("drama", "so much DRAMA today"));
+        assert!(found("steam*gift.", "steamcommunity gift.ly/abc"));
+        assert!(!found("*", "anything"));
+    }
+}
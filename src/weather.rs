@@ -0,0 +1,243 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serenity::{client::Context as SerenityContext, json::Value};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{events::Event, templates, util::query_encode, Data, SlimeError};
+
+/// How long before an outdoor event its forecast is first looked up. Further out, forecasts
+/// aren't worth much.
+const LEAD_HOURS: i32 = 24;
+
+/// How long a forecast for a place and day is reused, both across events there and before an
+/// event's forecast is looked up again.
+const CACHE_HOURS: i32 = 6;
+
+/// Variables `WEATHER_API_URL` may use.
+pub const URL_VARIABLES: &[&str] = &["location", "date"];
+
+/// A day's weather, as shown on an event's post and in its reminder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub condition: String,
+    pub low_c: f64,
+    pub high_c: f64,
+    pub rain_chance: Option<u64>,
+}
+
+impl Display for Forecast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {:.0} to {:.0}°C",
+            self.condition, self.low_c, self.high_c
+        )?;
+        match self.rain_chance {
+            Some(chance) if chance > 0 => write!(f, ", {chance}% chance of rain"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Forecast {
+    /// Reads the forecast for `day` out of an API response. Responses are expected in the shape
+    /// WeatherAPI.com's forecast endpoint uses, which several other providers copy.
+    pub fn parse(body: &str, day: NaiveDate) -> Option<Self> {
+        let body = serenity::json::from_str::<Value>(body).ok()?;
+        let date = day.format("%Y-%m-%d").to_string();
+        let forecast = body["forecast"]["forecastday"]
+            .as_array()?
+            .iter()
+            .find(|d| d["date"].as_str() == Some(&date))?;
+        let day = &forecast["day"];
+
+        Some(Self {
+            condition: day["condition"]["text"].as_str()?.trim().to_string(),
+            low_c: day["mintemp_c"].as_f64()?,
+            high_c: day["maxtemp_c"].as_f64()?,
+            rain_chance: day["daily_chance_of_rain"].as_u64(),
+        })
+    }
+}
+
+/// The request URL for `location` on `day`, from the configured template.
+fn request_url(template: &str, location: &str, day: NaiveDate) -> String {
+    let date = day.format("%Y-%m-%d").to_string();
+    templates::render(
+        template,
+        &[("location", &query_encode(location)), ("date", &date)],
+    )
+}
+
+/// Asks the API for `location` on `day`. Failures are logged and come back as `None`.
+async fn fetch(
+    client: &reqwest::Client,
+    template: &str,
+    location: &str,
+    day: NaiveDate,
+) -> Option<Forecast> {
+    let response = client
+        .get(request_url(template, location, day))
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    let body = match response {
+        Ok(response) => response.text().await,
+        Err(e) => Err(e),
+    };
+    match body {
+        Ok(body) => {
+            let forecast = Forecast::parse(&body, day);
+            if forecast.is_none() {
+                warn!("Weather API had no forecast for {} on {}", location, day);
+            }
+            forecast
+        }
+        Err(e) => {
+            error!("Could not fetch the forecast for {}: {}", location, e);
+            None
+        }
+    }
+}
+
+/// The forecast for `location` on `day`, from the cache while it's fresh. Returns `Ok(None)` when
+/// the API has nothing, or couldn't be reached.
+async fn lookup(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    template: &str,
+    location: &str,
+    day: NaiveDate,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, SlimeError> {
+    let cached = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT forecast FROM weather_forecasts
+         WHERE location = $1 AND day = $2 AND fetched_at > $3",
+    )
+    .bind(location)
+    .bind(day)
+    .bind(now - Duration::hours(CACHE_HOURS.into()))
+    .fetch_optional(pool)
+    .await?;
+    if let Some(forecast) = cached {
+        return Ok(forecast);
+    }
+
+    let forecast = fetch(client, template, location, day)
+        .await
+        .map(|f| f.to_string());
+    sqlx::query(
+        "INSERT INTO weather_forecasts (location, day, forecast, fetched_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (location, day)
+            DO UPDATE SET forecast = EXCLUDED.forecast, fetched_at = EXCLUDED.fetched_at",
+    )
+    .bind(location)
+    .bind(day)
+    .bind(&forecast)
+    .bind(now)
+    .execute(pool)
+    .await?;
+    // Old days are never asked for again.
+    sqlx::query("DELETE FROM weather_forecasts WHERE day < $1")
+        .bind(now.date_naive())
+        .execute(pool)
+        .await?;
+
+    Ok(forecast)
+}
+
+/// Looks up forecasts for outdoor events starting within a day, and keeps them fresh until they
+/// start. Does nothing unless `WEATHER_API_URL` is set. Called by the scheduler.
+pub async fn tick(
+    ctx: &SerenityContext,
+    data: &Data,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let Some(template) = &data.config.weather_api else {
+        return Ok(());
+    };
+    let pool = &data.pool;
+
+    // Claimed before looking up, so a slow API can't have the same event picked up twice.
+    let due = sqlx::query_as::<_, Event>(
+        "UPDATE events SET forecast_checked_at = $1
+         WHERE status = 'published' AND outdoor AND address IS NOT NULL
+            AND starts_at > $1 AND starts_at <= $1 + make_interval(hours => $2)
+            AND (forecast_checked_at IS NULL
+                OR forecast_checked_at <= $1 - make_interval(hours => $3))
+            AND guild_id NOT IN (SELECT guild_id FROM detached_guilds)
+         RETURNING *",
+    )
+    .bind(now)
+    .bind(LEAD_HOURS)
+    .bind(CACHE_HOURS)
+    .fetch_all(pool)
+    .await?;
+
+    for mut event in due {
+        let Some(address) = event.address.clone() else {
+            continue;
+        };
+        let day = event.starts_at.date_naive();
+        let forecast = lookup(pool, &data.http, template, address.trim(), day, now).await?;
+        if forecast.is_none() || forecast == event.forecast {
+            continue;
+        }
+
+        sqlx::query("UPDATE events SET forecast = $2 WHERE id = $1")
+            .bind(event.id)
+            .bind(&forecast)
+            .execute(pool)
+            .await?;
+        event.forecast = forecast;
+        data.calls.turn().await;
+        if let Err(e) = event.refresh_post(ctx, pool).await {
+            error!("Could not show the forecast on event {}: {}", event.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forecasts_are_read_for_the_right_day() {
+        let body = r#"{"forecast": {"forecastday": [
+            {"date": "2024-06-01", "day": {"maxtemp_c": 18.2, "mintemp_c": 9.6,
+                "daily_chance_of_rain": 0, "condition": {"text": "Sunny"}}},
+            {"date": "2024-06-02", "day": {"maxtemp_c": 14.0, "mintemp_c": 8.4,
+                "daily_chance_of_rain": 85, "condition": {"text": "Patchy rain nearby "}}}
+        ]}}"#;
+        let day = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+
+        let sunny = Forecast::parse(body, day(1)).unwrap();
+        assert_eq!(sunny.to_string(), "Sunny, 10 to 18°C");
+        let rainy = Forecast::parse(body, day(2)).unwrap();
+        assert_eq!(
+            rainy.to_string(),
+            "Patchy rain nearby, 8 to 14°C, 85% chance of rain"
+        );
+        assert_eq!(Forecast::parse(body, day(3)), None);
+        assert_eq!(Forecast::parse("not json", day(1)), None);
+    }
+
+    #[test]
+    fn request_urls_fill_in_the_location_and_date() {
+        let url = request_url(
+            "https://api.example.com/forecast?key=abc&q={location}&dt={date}",
+            "12 Pond Lane, Bath",
+            NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+        );
+        assert_eq!(
+            url,
+            "https://api.example.com/forecast?key=abc&q=12+Pond+Lane%2C+Bath&dt=2024-06-01"
+        );
+    }
+}